@@ -133,6 +133,7 @@ fn create_sample_issue() -> Issue {
         reporter: Some(User {
             uuid: "{user-uuid}".to_string(),
             username: Some("reporter".to_string()),
+            nickname: None,
             display_name: "Bug Reporter".to_string(),
             account_id: Some("account123".to_string()),
             user_type: "user".to_string(),
@@ -151,6 +152,7 @@ fn create_sample_issue() -> Issue {
         updated_on: Some(chrono::Utc::now()),
         edited_on: None,
         links: None,
+        repository: None,
     }
 }
 
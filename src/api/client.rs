@@ -1,28 +1,176 @@
-use anyhow::{Context, Result};
-use reqwest::{Client, Response, StatusCode};
+use futures::{Stream, TryStreamExt, stream};
+use reqwest::header::{ACCEPT, CONTENT_TYPE, IF_RANGE, RANGE};
+use reqwest::{Client, ClientBuilder, Request, RequestBuilder, Response, StatusCode};
 use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+
+use super::middleware::{self, Middleware, Next};
+use super::multipart::Form;
+use super::{decode, recording};
 
 use crate::auth::{AuthManager, Credential, OAuthFlow};
+use crate::config::{Config, NetworkConfig};
+use crate::error::{Error, Result};
 use crate::models::Paginated;
 
 const API_BASE_URL: &str = "https://api.bitbucket.org/2.0";
+const API_URL_ENV: &str = "BITBUCKET_API_URL";
+const USER_AGENT: &str = "bitbucket-cli";
+
+/// Where the offline snapshot layer sits in a client's stack
+const SNAPSHOTS: usize = 1;
+
+/// Process-wide offline switch picked up by clients created afterwards
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Serve reads for all subsequently created clients from local snapshots
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+/// Whether offline mode was requested for this process
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+/// Requests that may have changed something, for the audit log
+static WRITES: Mutex<Vec<ApiWrite>> = Mutex::new(Vec::new());
+
+/// A non-GET request sent by this process
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiWrite {
+    pub method: String,
+    pub path: String,
+    /// Response status, or `None` if no response arrived
+    pub status: Option<u16>,
+}
+
+/// Every write request sent so far, in order
+pub fn writes() -> Vec<ApiWrite> {
+    WRITES.lock().map(|w| w.clone()).unwrap_or_default()
+}
+
+pub(crate) fn record_write(write: ApiWrite) {
+    if let Ok(mut writes) = WRITES.lock() {
+        writes.push(write);
+    }
+}
+
+/// Where a download picks up again: the bytes already held, and the ETag of
+/// the file they came from
+#[derive(Debug, Clone, Default)]
+pub struct Resume {
+    pub offset: u64,
+    pub etag: Option<String>,
+}
+
+/// API base URL, overridable with `BITBUCKET_API_URL` (for proxies and tests)
+pub fn default_base_url() -> String {
+    std::env::var(API_URL_ENV)
+        .ok()
+        .filter(|url| !url.is_empty())
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or_else(|| API_BASE_URL.to_string())
+}
+
+/// Create an HTTP client builder with the CLI's user agent and timeouts applied
+pub fn http_client_builder(network: &NetworkConfig) -> ClientBuilder {
+    let builder = Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(network.connect_timeout());
+
+    match network.request_timeout() {
+        Some(timeout) => builder.timeout(timeout),
+        None => builder,
+    }
+}
 
 /// Bitbucket API client
+///
+/// Every request goes through the client's [`Middleware`] stack. Clones
+/// share one request limiter, so fanning work out over clones still keeps
+/// at most `[network] max_concurrent_requests` requests in flight.
 #[derive(Clone)]
 pub struct BitbucketClient {
     client: Client,
     credential: Credential,
+    network: NetworkConfig,
+    base_url: String,
+    offline: bool,
+    layers: Vec<Arc<dyn Middleware>>,
 }
 
 impl BitbucketClient {
-    /// Create a new authenticated client
+    /// Create a new authenticated client with default network settings
     pub fn new(credential: Credential) -> Result<Self> {
-        let client = Client::builder()
-            .user_agent("bitbucket-cli")
-            .build()
-            .context("Failed to create HTTP client")?;
+        Self::with_network(credential, NetworkConfig::default())
+    }
+
+    /// Create a new authenticated client with explicit network settings
+    pub fn with_network(credential: Credential, network: NetworkConfig) -> Result<Self> {
+        let client = http_client_builder(&network).build()?;
+        let limiter = Arc::new(Semaphore::new(network.max_concurrent_requests()));
+        let offline = is_offline();
+        let layers: Vec<Arc<dyn Middleware>> = vec![
+            Arc::new(middleware::Replay),
+            Arc::new(middleware::Snapshots {
+                offline,
+                account: credential.account_key(),
+            }),
+            Arc::new(middleware::Record),
+            Arc::new(middleware::Retry),
+            Arc::new(middleware::Limit(limiter)),
+            Arc::new(middleware::Log),
+            Arc::new(middleware::Auth(credential.clone())),
+        ];
+
+        Ok(Self {
+            client,
+            credential,
+            network,
+            base_url: default_base_url(),
+            offline,
+            layers,
+        })
+    }
+
+    /// Add a layer to every request, after retrying and logging and before
+    /// the `Authorization` header is added. Layers added later run closer to
+    /// the network.
+    pub fn with_middleware(mut self, layer: impl Middleware + 'static) -> Self {
+        let auth = self.layers.len() - 1;
+        self.layers.insert(auth, Arc::new(layer));
+        self
+    }
 
-        Ok(Self { client, credential })
+    /// Send requests to a different API root, such as a mock server
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Serve reads from local snapshots and refuse writes
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self.layers[SNAPSHOTS] = Arc::new(middleware::Snapshots {
+            offline,
+            account: self.credential.account_key(),
+        });
+        self
+    }
+
+    /// Whether this client serves reads from local snapshots
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Get the credential this client authenticates with
+    pub fn credential(&self) -> &Credential {
+        &self.credential
     }
 
     /// Get the authorization header value
@@ -33,12 +181,19 @@ impl BitbucketClient {
     /// Create a client from stored credentials, automatically refreshing if needed
     pub async fn from_stored() -> Result<Self> {
         let auth_manager = AuthManager::new()?;
-        let credential = auth_manager
-            .get_credentials()?
-            .context("Not authenticated. Run 'bitbucket auth login' first.")?;
+        // A replay answers from the recording, so any credential will do
+        let credential = match auth_manager.get_credentials()? {
+            Some(credential) => credential,
+            None if recording::is_replaying() => Credential::ApiKey {
+                username: "replay".to_string(),
+                api_key: "replay".to_string(),
+            },
+            None => return Err(Error::NotAuthenticated),
+        };
 
-        // Auto-refresh if the token is expiring soon and we have everything needed
-        let credential = if credential.needs_refresh() {
+        // Auto-refresh if the token is expiring soon and we have everything needed.
+        // Offline mode never touches the network, so an expired token is fine.
+        let credential = if credential.needs_refresh() && !is_offline() {
             if let (
                 Credential::OAuth {
                     refresh_token: Some(refresh_token),
@@ -48,9 +203,14 @@ impl BitbucketClient {
             ) = (&credential, credential.oauth_consumer_credentials())
             {
                 let flow = OAuthFlow::new(client_id.to_string(), client_secret.to_string());
+                tracing::debug!("access token expiring soon, refreshing");
                 match flow.refresh_token(&auth_manager, refresh_token).await {
                     Ok(refreshed) => refreshed,
-                    Err(_) => credential, // Fall back to existing credential if refresh fails
+                    Err(e) => {
+                        // Fall back to existing credential if refresh fails
+                        tracing::warn!("token refresh failed: {:#}", e);
+                        credential
+                    }
                 }
             } else {
                 credential
@@ -59,30 +219,39 @@ impl BitbucketClient {
             credential
         };
 
-        Self::new(credential)
+        let config = Config::load().map_err(Error::config)?;
+        Self::with_network(credential, config.network)
     }
 
     /// Get the base API URL
     pub fn base_url(&self) -> &str {
-        API_BASE_URL
+        &self.base_url
     }
 
     /// Build a URL for an API endpoint
     pub fn url(&self, path: &str) -> String {
-        format!("{}{}", API_BASE_URL, path)
+        format!("{}{}", self.base_url, path)
+    }
+
+    /// Get the network settings this client was created with
+    pub fn network(&self) -> &NetworkConfig {
+        &self.network
+    }
+
+    /// Build a request and send it down the middleware stack
+    async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let request = request.build()?;
+        self.execute(request).await
+    }
+
+    /// Execute a built request
+    async fn execute(&self, request: Request) -> Result<Response> {
+        Next::new(&self.client, &self.layers).run(request).await
     }
 
     /// Make a GET request
     pub async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
-        let response = self
-            .client
-            .get(self.url(path))
-            .header("Authorization", self.credential.auth_header())
-            .send()
-            .await
-            .context("Request failed")?;
-
-        self.handle_response(response).await
+        self.get_json(&self.url(path), &[]).await
     }
 
     /// Make a GET request with query parameters
@@ -91,16 +260,95 @@ impl BitbucketClient {
         path: &str,
         query: &[(&str, &str)],
     ) -> Result<T> {
-        let response = self
-            .client
-            .get(self.url(path))
-            .header("Authorization", self.credential.auth_header())
-            .query(query)
-            .send()
-            .await
-            .context("Request failed")?;
+        self.get_json(&self.url(path), query).await
+    }
 
-        self.handle_response(response).await
+    /// Make a GET request for a raw text body (diffs, logs, file contents)
+    pub async fn get_text(&self, path: &str, accept: &str) -> Result<String> {
+        let request = self.client.get(self.url(path)).header(ACCEPT, accept);
+
+        self.read_body(request).await
+    }
+
+    /// Make a GET request for a raw body kept byte for byte, such as a
+    /// snippet file that may not be text. Bodies aren't snapshotted, so this
+    /// fails offline.
+    pub async fn get_bytes(&self, path: &str) -> Result<Vec<u8>> {
+        let request = self.client.get(self.url(path)).header(ACCEPT, "*/*");
+        let response = self.send(request).await?;
+        let status = response.status();
+        if !status.is_success() {
+            return self.handle_error(status, response).await;
+        }
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    /// Start a GET for a file whose body the caller streams, asking for the
+    /// bytes from `resume.offset` on when it's non-zero, as long as the file
+    /// still has `resume.etag`. The server may ignore the range, or find the
+    /// file changed, and answer 200 with the whole file; check for 206.
+    /// Redirects to file storage are followed. Files are never snapshotted,
+    /// so this fails offline.
+    pub async fn get_file(&self, path: &str, resume: Resume) -> Result<Response> {
+        let mut request = self.client.get(self.url(path));
+        if resume.offset > 0 {
+            request = request.header(RANGE, format!("bytes={}-", resume.offset));
+            if let Some(etag) = &resume.etag {
+                request = request.header(IF_RANGE, etag);
+            }
+        }
+
+        let response = self.send(request).await?;
+        let status = response.status();
+        if !status.is_success() {
+            return self.handle_error(status, response).await;
+        }
+        Ok(response)
+    }
+
+    /// Scopes granted to the credential, from the `X-OAuth-Scopes` header of
+    /// a request for the current user. `None` when the API doesn't report
+    /// them, as for API keys.
+    pub async fn granted_scopes(&self) -> Result<Option<Vec<String>>> {
+        let request = self.client.get(self.url("/user"));
+
+        let response = self.send(request).await?;
+        let status = response.status();
+        if !status.is_success() {
+            return self.handle_error(status, response).await;
+        }
+        Ok(response
+            .headers()
+            .get("x-oauth-scopes")
+            .and_then(|v| v.to_str().ok())
+            .map(|scopes| {
+                scopes
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            }))
+    }
+
+    /// GET an absolute URL and parse the JSON body
+    async fn get_json<T: DeserializeOwned>(&self, url: &str, query: &[(&str, &str)]) -> Result<T> {
+        let request = self.client.get(url).query(query);
+        let body = self.read_body(request).await?;
+
+        decode::parse(url, &body)
+    }
+
+    /// Read a GET response body, snapshotting it for offline use. In offline
+    /// mode the snapshot is returned instead of contacting the API.
+    async fn read_body(&self, request: RequestBuilder) -> Result<String> {
+        let request = request.header(middleware::SNAPSHOT, "1");
+        let response = self.send(request).await?;
+        let status = response.status();
+        if !status.is_success() {
+            return self.handle_error(status, response).await;
+        }
+
+        Ok(response.text().await?)
     }
 
     /// Make a POST request with JSON body
@@ -109,28 +357,41 @@ impl BitbucketClient {
         path: &str,
         body: &B,
     ) -> Result<T> {
-        let response = self
-            .client
-            .post(self.url(path))
-            .header("Authorization", self.credential.auth_header())
-            .json(body)
-            .send()
-            .await
-            .context("Request failed")?;
+        let request = self.client.post(self.url(path)).json(body);
+        let response = self.send(request).await?;
 
         self.handle_response(response).await
     }
 
     /// Make a POST request without expecting a response body
     pub async fn post_no_response<B: serde::Serialize>(&self, path: &str, body: &B) -> Result<()> {
-        let response = self
+        let request = self.client.post(self.url(path)).json(body);
+        let response = self.send(request).await?;
+
+        self.handle_empty_response(response).await
+    }
+
+    /// Make a POST request with a `multipart/form-data` body
+    pub async fn post_form<T: DeserializeOwned>(&self, path: &str, form: Form) -> Result<T> {
+        let request = self
+            .client
+            .post(self.url(path))
+            .header(CONTENT_TYPE, form.content_type())
+            .body(form.into_body());
+        let response = self.send(request).await?;
+
+        self.handle_response(response).await
+    }
+
+    /// Make a POST request with a `multipart/form-data` body without
+    /// expecting a response body
+    pub async fn post_form_no_response(&self, path: &str, form: Form) -> Result<()> {
+        let request = self
             .client
             .post(self.url(path))
-            .header("Authorization", self.credential.auth_header())
-            .json(body)
-            .send()
-            .await
-            .context("Request failed")?;
+            .header(CONTENT_TYPE, form.content_type())
+            .body(form.into_body());
+        let response = self.send(request).await?;
 
         self.handle_empty_response(response).await
     }
@@ -141,51 +402,94 @@ impl BitbucketClient {
         path: &str,
         body: &B,
     ) -> Result<T> {
-        let response = self
-            .client
-            .put(self.url(path))
-            .header("Authorization", self.credential.auth_header())
-            .json(body)
-            .send()
-            .await
-            .context("Request failed")?;
+        let request = self.client.put(self.url(path)).json(body);
+        let response = self.send(request).await?;
 
         self.handle_response(response).await
     }
 
+    /// Make a PUT request without expecting a response body
+    pub async fn put_no_response<B: serde::Serialize>(&self, path: &str, body: &B) -> Result<()> {
+        let request = self.client.put(self.url(path)).json(body);
+        let response = self.send(request).await?;
+
+        self.handle_empty_response(response).await
+    }
+
     /// Make a DELETE request
     pub async fn delete(&self, path: &str) -> Result<()> {
-        let response = self
-            .client
-            .delete(self.url(path))
-            .header("Authorization", self.credential.auth_header())
-            .send()
-            .await
-            .context("Request failed")?;
+        let request = self.client.delete(self.url(path));
+        let response = self.send(request).await?;
 
         self.handle_empty_response(response).await
     }
 
     /// Fetch all pages of a paginated endpoint
-    pub async fn get_all_pages<T: DeserializeOwned>(&self, path: &str) -> Result<Vec<T>> {
-        let mut all_items = Vec::new();
-        let mut next_url: Option<String> = Some(self.url(path));
-
-        while let Some(url) = next_url {
-            let response = self
-                .client
-                .get(&url)
-                .header("Authorization", self.credential.auth_header())
-                .send()
-                .await
-                .context("Request failed")?;
-
-            let page: Paginated<T> = self.handle_response(response).await?;
-            all_items.extend(page.values);
-            next_url = page.next;
-        }
+    pub async fn get_all_pages<T>(&self, path: &str) -> Result<Vec<T>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        self.paginate(path).try_collect().await
+    }
+
+    /// Lazily stream every item of a paginated endpoint. Pages are only
+    /// requested as the consumer reaches them, so dropping the stream early
+    /// stops further requests.
+    pub fn paginate<T>(&self, path: &str) -> impl Stream<Item = Result<T>> + Send + use<T>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        self.paginate_with_query(path, &[])
+    }
+
+    /// Like [`paginate`](Self::paginate), with query parameters for the first
+    /// page. Later pages follow the API's `next` links, which carry them over.
+    pub fn paginate_with_query<T>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> impl Stream<Item = Result<T>> + Send + use<T>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let client = Arc::new(self.clone());
+        let cursor = PageCursor {
+            next: Some(self.url(path)),
+            query: query
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            items: VecDeque::new(),
+        };
 
-        Ok(all_items)
+        stream::try_unfold(cursor, move |mut cursor| {
+            let client = Arc::clone(&client);
+            async move {
+                loop {
+                    if let Some(item) = cursor.items.pop_front() {
+                        return Ok(Some((item, cursor)));
+                    }
+                    let Some(url) = cursor.next.take() else {
+                        return Ok(None);
+                    };
+
+                    let query = std::mem::take(&mut cursor.query);
+                    let query: Vec<(&str, &str)> = query
+                        .iter()
+                        .map(|(k, v)| (k.as_str(), v.as_str()))
+                        .collect();
+                    let page: Paginated<T> = client.get_json(&url, &query).await?;
+
+                    tracing::debug!(
+                        items = page.values.len(),
+                        more = page.next.is_some(),
+                        "fetched page"
+                    );
+                    cursor.items.extend(page.values);
+                    cursor.next = page.next;
+                }
+            }
+        })
     }
 
     /// Handle API response
@@ -193,10 +497,9 @@ impl BitbucketClient {
         let status = response.status();
 
         if status.is_success() {
-            response
-                .json()
-                .await
-                .context("Failed to parse response JSON")
+            let url = response.url().to_string();
+            let body = response.text().await?;
+            decode::parse(&url, &body)
         } else {
             self.handle_error(status, response).await
         }
@@ -216,33 +519,32 @@ impl BitbucketClient {
     /// Handle API errors
     async fn handle_error<T>(&self, status: StatusCode, response: Response) -> Result<T> {
         let body = response.text().await.unwrap_or_default();
+        tracing::debug!(status = status.as_u16(), %body, "API error response");
 
-        match status {
-            StatusCode::UNAUTHORIZED => {
-                anyhow::bail!("Authentication failed. Try running 'bitbucket auth login' again.")
-            }
-            StatusCode::FORBIDDEN => {
-                anyhow::bail!("Access denied. You don't have permission to access this resource.")
-            }
-            StatusCode::NOT_FOUND => {
-                anyhow::bail!("Resource not found.")
-            }
-            StatusCode::TOO_MANY_REQUESTS => {
-                anyhow::bail!("Rate limit exceeded. Please wait and try again.")
-            }
+        Err(match status {
+            StatusCode::UNAUTHORIZED => Error::Unauthorized,
+            StatusCode::FORBIDDEN => Error::Forbidden,
+            StatusCode::NOT_FOUND => Error::NotFound("Resource".to_string()),
+            StatusCode::TOO_MANY_REQUESTS => Error::RateLimited,
             _ => {
-                // Try to parse error message from response
-                if let Ok(error) = serde_json::from_str::<ApiError>(&body) {
-                    if let Some(msg) = error.error.message {
-                        anyhow::bail!("API error: {}", msg);
-                    }
-                }
-                anyhow::bail!("API error ({}): {}", status, body)
+                // Prefer the message from the error payload over the raw body
+                let message = serde_json::from_str::<ApiError>(&body)
+                    .ok()
+                    .and_then(|error| error.error.message)
+                    .unwrap_or(body);
+                Error::Api { status, message }
             }
-        }
+        })
     }
 }
 
+/// Position within a paginated listing
+struct PageCursor<T> {
+    next: Option<String>,
+    query: Vec<(String, String)>,
+    items: VecDeque<T>,
+}
+
 #[derive(serde::Deserialize)]
 struct ApiError {
     error: ApiErrorDetail,
@@ -0,0 +1,207 @@
+use futures::Stream;
+
+use crate::error::Result;
+
+use super::BitbucketClient;
+use super::snippets::encode_path;
+use crate::models::{Commit, CommitComment, DiffStat, FileCommit, Paginated};
+
+impl BitbucketClient {
+    /// Get a commit by hash
+    pub async fn get_commit(&self, workspace: &str, repo_slug: &str, hash: &str) -> Result<Commit> {
+        let path = format!("/repositories/{}/{}/commit/{}", workspace, repo_slug, hash);
+        self.get(&path).await
+    }
+
+    /// Get the best common ancestor of two commits
+    pub async fn get_merge_base(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        first: &str,
+        second: &str,
+    ) -> Result<Commit> {
+        let path = format!(
+            "/repositories/{}/{}/merge-base/{}..{}",
+            workspace, repo_slug, first, second
+        );
+        self.get(&path).await
+    }
+
+    /// Stream commits reachable from `revision` (a branch, tag or hash),
+    /// or from the main branch, newest first
+    pub fn stream_commits(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        revision: Option<&str>,
+    ) -> impl Stream<Item = Result<Commit>> + Send + use<> {
+        let path = match revision {
+            Some(revision) => format!(
+                "/repositories/{}/{}/commits/{}",
+                workspace, repo_slug, revision
+            ),
+            None => format!("/repositories/{}/{}/commits", workspace, repo_slug),
+        };
+        self.paginate_with_query(&path, &[("pagelen", "50")])
+    }
+
+    /// Stream commits reachable from `include` but not from `exclude`,
+    /// newest first
+    pub fn stream_commits_between(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        include: &str,
+        exclude: &str,
+    ) -> impl Stream<Item = Result<Commit>> + Send + use<> {
+        let path = format!("/repositories/{}/{}/commits", workspace, repo_slug);
+        self.paginate_with_query(
+            &path,
+            &[
+                ("include", include),
+                ("exclude", exclude),
+                ("pagelen", "50"),
+            ],
+        )
+    }
+
+    /// List the files `head` changes since its merge base with `base`
+    pub async fn list_diffstat(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        base: &str,
+        head: &str,
+    ) -> Result<Vec<DiffStat>> {
+        let path = format!(
+            "/repositories/{}/{}/diffstat/{}..{}",
+            workspace, repo_slug, head, base
+        );
+        self.get_all_pages(&path).await
+    }
+
+    /// Stream the commits reachable from `revision` that changed `file`,
+    /// newest first, following renames
+    pub fn stream_file_history(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        revision: &str,
+        file: &str,
+    ) -> impl Stream<Item = Result<FileCommit>> + Send + use<> {
+        let path = format!(
+            "/repositories/{}/{}/filehistory/{}/{}",
+            workspace,
+            repo_slug,
+            revision,
+            encode_path(file)
+        );
+        // The commits come with only their hash unless asked for more
+        self.paginate_with_query(
+            &path,
+            &[
+                (
+                    "fields",
+                    "next,values.path,values.commit.hash,values.commit.date,\
+                     values.commit.message,values.commit.author",
+                ),
+                ("pagelen", "50"),
+            ],
+        )
+    }
+
+    /// Get the diff of commit `hash` against its first parent, limited to
+    /// `file`
+    pub async fn get_commit_file_diff(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        hash: &str,
+        file: &str,
+    ) -> Result<String> {
+        let path = format!(
+            "/repositories/{}/{}/diff/{}?path={}",
+            workspace,
+            repo_slug,
+            hash,
+            encode_path(file)
+        );
+        self.get_text(&path, "text/plain").await
+    }
+
+    /// Get the diff of `head` against its merge base with `base`
+    pub async fn get_diff(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        base: &str,
+        head: &str,
+    ) -> Result<String> {
+        let path = format!(
+            "/repositories/{}/{}/diff/{}..{}",
+            workspace, repo_slug, head, base
+        );
+        self.get_text(&path, "text/plain").await
+    }
+
+    /// List comments on a commit
+    pub async fn list_commit_comments(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        hash: &str,
+    ) -> Result<Paginated<CommitComment>> {
+        let path = format!(
+            "/repositories/{}/{}/commit/{}/comments",
+            workspace, repo_slug, hash
+        );
+        self.get(&path).await
+    }
+
+    /// Comment on a commit, optionally inline on a line of a file
+    pub async fn add_commit_comment(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        hash: &str,
+        content: &str,
+        inline: Option<(&str, u32)>,
+    ) -> Result<CommitComment> {
+        let mut request = serde_json::json!({
+            "content": { "raw": content },
+        });
+        if let Some((path, line)) = inline {
+            request["inline"] = serde_json::json!({ "path": path, "to": line });
+        }
+
+        let path = format!(
+            "/repositories/{}/{}/commit/{}/comments",
+            workspace, repo_slug, hash
+        );
+        self.post(&path, &request).await
+    }
+
+    /// Approve a commit
+    pub async fn approve_commit(&self, workspace: &str, repo_slug: &str, hash: &str) -> Result<()> {
+        let path = format!(
+            "/repositories/{}/{}/commit/{}/approve",
+            workspace, repo_slug, hash
+        );
+        self.post_no_response(&path, &serde_json::json!({})).await
+    }
+
+    /// Withdraw approval of a commit
+    pub async fn unapprove_commit(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        hash: &str,
+    ) -> Result<()> {
+        let path = format!(
+            "/repositories/{}/{}/commit/{}/approve",
+            workspace, repo_slug, hash
+        );
+        self.delete(&path).await
+    }
+}
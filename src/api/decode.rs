@@ -0,0 +1,134 @@
+//! Response decoding with diagnostics
+//!
+//! Parsing goes through `serde_path_to_error` so a failure names the field
+//! that did not match (e.g. `values[3].state.name`) and the value found
+//! there. When dumping is enabled (`--debug`) the raw body is also written to
+//! `$XDG_STATE_HOME/bitbucket-cli/responses` for attaching to a bug report.
+
+use chrono::Utc;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use serde_path_to_error::Segment;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::config::{Config, xdg};
+use crate::error::{Error, Result};
+
+/// Longest rendering of the offending value kept in the error message
+const MAX_VALUE_LEN: usize = 80;
+
+/// Process-wide switch for saving bodies that fail to parse
+static DUMP_BODIES: AtomicBool = AtomicBool::new(false);
+
+/// Save the raw body of responses that fail to parse to the state directory
+pub fn set_dump_bodies(dump: bool) {
+    DUMP_BODIES.store(dump, Ordering::Relaxed);
+}
+
+/// Get the directory unparseable response bodies are saved to
+pub fn dump_dir() -> Result<PathBuf> {
+    Ok(Config::state_dir()
+        .map_err(Error::config)?
+        .join("responses"))
+}
+
+/// Parse a JSON response body from `url`
+pub fn parse<T: DeserializeOwned>(url: &str, body: &str) -> Result<T> {
+    let deserializer = &mut serde_json::Deserializer::from_str(body);
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        let value = value_at(body, e.path());
+        let source = e.into_inner();
+        let dump = if DUMP_BODIES.load(Ordering::Relaxed) {
+            dump(url, body)
+        } else {
+            None
+        };
+
+        tracing::warn!(url, path, error = %source, "failed to parse response JSON");
+        Error::Decode {
+            url: url.to_string(),
+            path,
+            value,
+            source,
+            dump,
+        }
+    })
+}
+
+/// Render the value at `path` in `body`, if the body is valid JSON and the
+/// path leads somewhere
+fn value_at(body: &str, path: &serde_path_to_error::Path) -> Option<String> {
+    let mut value = &serde_json::from_str::<Value>(body).ok()?;
+    for segment in path.iter() {
+        value = match segment {
+            Segment::Seq { index } => value.get(*index)?,
+            Segment::Map { key } => value.get(key)?,
+            Segment::Enum { .. } | Segment::Unknown => continue,
+        };
+    }
+
+    let rendered = value.to_string();
+    Some(match rendered.char_indices().nth(MAX_VALUE_LEN) {
+        Some((end, _)) => format!("{}…", &rendered[..end]),
+        None => rendered,
+    })
+}
+
+/// Best-effort write of a body to the dump directory
+fn dump(url: &str, body: &str) -> Option<PathBuf> {
+    let dir = dump_dir().ok()?;
+    xdg::ensure_dir(&dir).ok()?;
+
+    let path = dir.join(format!("{}.json", Utc::now().format("%Y%m%dT%H%M%S%.3f")));
+    match fs::write(&path, body) {
+        Ok(()) => {
+            tracing::info!(url, path = %path.display(), "saved unparseable response");
+            Some(path)
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "failed to save unparseable response");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Paginated, Pipeline, PipelineStateName};
+
+    fn pipeline(state: &str, build_number: &str) -> String {
+        format!(
+            r#"{{"uuid": "{{p}}", "build_number": {}, "created_on": "2024-01-01T00:00:00Z",
+                "target": {{"type": "pipeline_ref_target"}},
+                "state": {{"name": "{}", "type": "pipeline_state"}}}}"#,
+            build_number, state
+        )
+    }
+
+    #[test]
+    fn unknown_enum_values_fall_back() {
+        let body = pipeline("QUEUED_SOMEWHERE_NEW", "7");
+        let pipeline: Pipeline = parse("https://example.test", &body).unwrap();
+        assert_eq!(pipeline.state.name, PipelineStateName::Unknown);
+    }
+
+    #[test]
+    fn errors_name_the_path_and_value() {
+        let body = format!(
+            r#"{{"values": [{}, {}]}}"#,
+            pipeline("COMPLETED", "1"),
+            pipeline("COMPLETED", "\"two\"")
+        );
+        let error = parse::<Paginated<Pipeline>>("https://example.test", &body).unwrap_err();
+
+        let Error::Decode { path, value, .. } = &error else {
+            panic!("unexpected error: {:?}", error);
+        };
+        assert_eq!(path, "values[1].build_number");
+        assert_eq!(value.as_deref(), Some("\"two\""));
+    }
+}
@@ -0,0 +1,52 @@
+use futures::Stream;
+use reqwest::Response;
+
+use crate::error::Result;
+
+use super::multipart::Form;
+use super::{BitbucketClient, Resume};
+use crate::models::Download;
+
+impl BitbucketClient {
+    /// Stream the files in the repository's Downloads, newest first,
+    /// fetching pages as needed
+    pub fn stream_downloads(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+    ) -> impl Stream<Item = Result<Download>> + Send + use<> {
+        let path = format!("/repositories/{}/{}/downloads", workspace, repo_slug);
+        self.paginate(&path)
+    }
+
+    /// Upload a file to the repository's Downloads, replacing any file with
+    /// the same name
+    pub async fn upload_download(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        file_name: &str,
+        contents: &[u8],
+    ) -> Result<()> {
+        let form = Form::new().file("files", file_name, contents);
+
+        let path = format!("/repositories/{}/{}/downloads", workspace, repo_slug);
+        self.post_form_no_response(&path, form).await
+    }
+
+    /// Start downloading a file from the repository's Downloads, from where
+    /// `resume` says
+    pub async fn get_download(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        file_name: &str,
+        resume: Resume,
+    ) -> Result<Response> {
+        let path = format!(
+            "/repositories/{}/{}/downloads/{}",
+            workspace, repo_slug, file_name
+        );
+        self.get_file(&path, resume).await
+    }
+}
@@ -0,0 +1,58 @@
+use crate::error::Result;
+
+use super::BitbucketClient;
+use crate::models::{Annotation, CreateReportRequest, Paginated, Report};
+
+/// Most annotations the API accepts in one request
+pub const MAX_ANNOTATIONS_PER_REQUEST: usize = 100;
+
+impl BitbucketClient {
+    /// List Code Insights reports on a commit
+    pub async fn list_reports(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        commit: &str,
+    ) -> Result<Paginated<Report>> {
+        let path = format!(
+            "/repositories/{}/{}/commit/{}/reports",
+            workspace, repo_slug, commit
+        );
+        self.get(&path).await
+    }
+
+    /// Create or replace a report on a commit
+    pub async fn put_report(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        commit: &str,
+        report_id: &str,
+        request: &CreateReportRequest,
+    ) -> Result<Report> {
+        let path = format!(
+            "/repositories/{}/{}/commit/{}/reports/{}",
+            workspace, repo_slug, commit, report_id
+        );
+        self.put(&path, request).await
+    }
+
+    /// Add annotations to a report, in batches the API accepts
+    pub async fn add_annotations(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        commit: &str,
+        report_id: &str,
+        annotations: &[Annotation],
+    ) -> Result<()> {
+        let path = format!(
+            "/repositories/{}/{}/commit/{}/reports/{}/annotations",
+            workspace, repo_slug, commit, report_id
+        );
+        for batch in annotations.chunks(MAX_ANNOTATIONS_PER_REQUEST) {
+            self.post_no_response(&path, &batch).await?;
+        }
+        Ok(())
+    }
+}
@@ -1,17 +1,24 @@
-use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::Stream;
+
+use crate::error::Result;
 
 use super::BitbucketClient;
+use super::multipart::Form;
 use crate::models::{
-    CreateIssueCommentRequest, CreateIssueRequest, Issue, IssueComment, IssueState, Paginated,
+    Component, CreateIssueCommentRequest, CreateIssueRequest, Issue, IssueComment, IssueState,
+    Paginated, UpdateIssueRequest,
 };
 
 impl BitbucketClient {
-    /// List issues for a repository
+    /// List issues for a repository, optionally matching a Bitbucket query
+    /// language filter
     pub async fn list_issues(
         &self,
         workspace: &str,
         repo_slug: &str,
         state: Option<IssueState>,
+        filter: Option<&str>,
         page: Option<u32>,
         pagelen: Option<u32>,
     ) -> Result<Paginated<Issue>> {
@@ -20,6 +27,9 @@ impl BitbucketClient {
         if let Some(s) = state {
             query.push(("state", s.to_string()));
         }
+        if let Some(q) = filter {
+            query.push(("q", q.to_string()));
+        }
         if let Some(p) = page {
             query.push(("page", p.to_string()));
         }
@@ -33,6 +43,44 @@ impl BitbucketClient {
         self.get_with_query(&path, &query_refs).await
     }
 
+    /// Stream every issue in a repository, optionally matching a filter,
+    /// fetching pages as needed
+    pub fn stream_issues(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        state: Option<IssueState>,
+        filter: Option<&str>,
+    ) -> impl Stream<Item = Result<Issue>> + Send + use<> {
+        let state = state.map(|s| s.to_string());
+        let mut query = vec![("pagelen", "50")];
+        if let Some(s) = &state {
+            query.push(("state", s.as_str()));
+        }
+        if let Some(q) = filter {
+            query.push(("q", q));
+        }
+
+        let path = format!("/repositories/{}/{}/issues", workspace, repo_slug);
+        self.paginate_with_query(&path, &query)
+    }
+
+    /// Stream issues matching a Bitbucket query language filter
+    /// (e.g. `priority = "blocker" AND state = "open"`), most recently
+    /// updated first
+    pub fn search_issues(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        filter: &str,
+    ) -> impl Stream<Item = Result<Issue>> + Send + use<> {
+        let path = format!("/repositories/{}/{}/issues", workspace, repo_slug);
+        self.paginate_with_query(
+            &path,
+            &[("pagelen", "50"), ("q", filter), ("sort", "-updated_on")],
+        )
+    }
+
     /// Get a specific issue
     pub async fn get_issue(
         &self,
@@ -96,6 +144,72 @@ impl BitbucketClient {
         self.put(&path, &request).await
     }
 
+    /// Move an issue to `state`, with `message` posted as a comment in the
+    /// same change
+    pub async fn change_issue_state(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        issue_id: u64,
+        state: IssueState,
+        message: &str,
+    ) -> Result<()> {
+        let request = serde_json::json!({
+            "changes": { "state": { "new": state } },
+            "message": { "raw": message },
+        });
+
+        let path = format!(
+            "/repositories/{}/{}/issues/{}/changes",
+            workspace, repo_slug, issue_id
+        );
+        self.post_no_response(&path, &request).await
+    }
+
+    /// Change an issue's kind, priority, assignee or state
+    pub async fn edit_issue(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        issue_id: u64,
+        request: &UpdateIssueRequest,
+    ) -> Result<Issue> {
+        let path = format!(
+            "/repositories/{}/{}/issues/{}",
+            workspace, repo_slug, issue_id
+        );
+        self.put(&path, request).await
+    }
+
+    /// Set or clear an issue's component
+    pub async fn set_issue_component(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        issue_id: u64,
+        component: Option<&str>,
+    ) -> Result<Issue> {
+        let request = serde_json::json!({
+            "component": component.map(|name| serde_json::json!({ "name": name })),
+        });
+
+        let path = format!(
+            "/repositories/{}/{}/issues/{}",
+            workspace, repo_slug, issue_id
+        );
+        self.put(&path, &request).await
+    }
+
+    /// List the components defined for a repository's issue tracker
+    pub async fn list_components(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+    ) -> Result<Vec<Component>> {
+        let path = format!("/repositories/{}/{}/components", workspace, repo_slug);
+        self.get_all_pages(&path).await
+    }
+
     /// Delete an issue
     pub async fn delete_issue(
         &self,
@@ -124,6 +238,32 @@ impl BitbucketClient {
         self.get(&path).await
     }
 
+    /// Stream an issue's comments oldest first, optionally only those
+    /// created at or after `since`
+    pub fn stream_issue_comments(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        issue_id: u64,
+        since: Option<DateTime<Utc>>,
+    ) -> impl Stream<Item = Result<IssueComment>> + Send + use<> {
+        let path = format!(
+            "/repositories/{}/{}/issues/{}/comments",
+            workspace, repo_slug, issue_id
+        );
+        let filter = since.map(|since| {
+            format!(
+                "created_on >= {}",
+                since.to_rfc3339_opts(SecondsFormat::Micros, true)
+            )
+        });
+        let mut query = vec![("pagelen", "100"), ("sort", "created_on")];
+        if let Some(filter) = &filter {
+            query.push(("q", filter));
+        }
+        self.paginate_with_query(&path, &query)
+    }
+
     /// Add a comment to an issue
     pub async fn add_issue_comment(
         &self,
@@ -145,6 +285,24 @@ impl BitbucketClient {
         self.post(&path, &request).await
     }
 
+    /// Attach a file to an issue, replacing any attachment with the same name
+    pub async fn upload_issue_attachment(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        issue_id: u64,
+        file_name: &str,
+        contents: &[u8],
+    ) -> Result<()> {
+        let form = Form::new().file("files", file_name, contents);
+
+        let path = format!(
+            "/repositories/{}/{}/issues/{}/attachments",
+            workspace, repo_slug, issue_id
+        );
+        self.post_form_no_response(&path, form).await
+    }
+
     /// Vote for an issue
     pub async fn vote_issue(&self, workspace: &str, repo_slug: &str, issue_id: u64) -> Result<()> {
         let path = format!(
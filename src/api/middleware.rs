@@ -0,0 +1,324 @@
+//! Layers every API request passes through on its way to the network
+//!
+//! A [`BitbucketClient`](super::BitbucketClient) sends each request down a
+//! stack of [`Middleware`], outermost first. A layer can change the request,
+//! answer it itself, or hand it to the rest of the stack with [`Next::run`]
+//! and look at the response on the way back. The client's own concerns
+//! (replaying traffic, offline snapshots, recording, retrying, the
+//! concurrency limit, logging and the audit trail, authentication) are layers
+//! like any other, and
+//! [`with_middleware`](super::BitbucketClient::with_middleware) adds more
+//! between logging and authentication.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use reqwest::header::{AUTHORIZATION, HeaderName, HeaderValue, RETRY_AFTER};
+use reqwest::{Client, Method, Request, Response, StatusCode};
+use tokio::sync::Semaphore;
+
+use super::client::{ApiWrite, record_write};
+use super::{recording, snapshot};
+use crate::auth::Credential;
+use crate::error::{Error, Result};
+
+/// One layer of request handling
+pub trait Middleware: Send + Sync {
+    /// Handle `request`, usually by passing it on with `next.run`
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>>;
+}
+
+/// The rest of the stack below a layer
+#[derive(Clone)]
+pub struct Next<'a> {
+    client: &'a Client,
+    layers: &'a [Arc<dyn Middleware>],
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(client: &'a Client, layers: &'a [Arc<dyn Middleware>]) -> Self {
+        Self { client, layers }
+    }
+
+    /// Send `request` through the remaining layers, then to the network,
+    /// where a timeout becomes [`Error::Timeout`]
+    pub fn run(self, request: Request) -> BoxFuture<'a, Result<Response>> {
+        match self.layers.split_first() {
+            Some((layer, layers)) => layer.handle(
+                request,
+                Next {
+                    client: self.client,
+                    layers,
+                },
+            ),
+            None => Box::pin(async move {
+                self.client.execute(request).await.map_err(|e| {
+                    if e.is_timeout() {
+                        Error::Timeout
+                    } else {
+                        Error::from(e)
+                    }
+                })
+            }),
+        }
+    }
+}
+
+/// Answers from the `--replay` recording instead of the network
+pub(crate) struct Replay;
+
+impl Middleware for Replay {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>> {
+        if recording::is_replaying() {
+            return Box::pin(async move { recording::replay(&request) });
+        }
+        next.run(request)
+    }
+}
+
+/// Marks a GET whose body is kept as a snapshot for offline use; taken off
+/// by [`Snapshots`] before the request goes further
+pub(crate) const SNAPSHOT: HeaderName = HeaderName::from_static("x-bitbucket-cli-snapshot");
+
+/// Keeps successful reads marked with [`SNAPSHOT`] on disk, and in offline
+/// mode answers them from there and refuses everything else
+pub(crate) struct Snapshots {
+    pub offline: bool,
+    /// Whose snapshots these are, from [`Credential::account_key`]
+    pub account: String,
+}
+
+impl Middleware for Snapshots {
+    fn handle<'a>(
+        &'a self,
+        mut request: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<Response>> {
+        let snapshotted =
+            request.headers_mut().remove(SNAPSHOT).is_some() && request.method() == Method::GET;
+        let key = request.url().to_string();
+        let path = request.url().path().to_string();
+
+        if self.offline {
+            let method = request.method().clone();
+            return Box::pin(async move {
+                if !snapshotted && method == Method::GET {
+                    return Err(Error::Offline(format!(
+                        "Cannot download {} in offline mode.",
+                        path
+                    )));
+                }
+                if !snapshotted {
+                    return Err(Error::Offline(format!(
+                        "Cannot {} {} in offline mode. Drop --offline to make changes.",
+                        method, path
+                    )));
+                }
+                match snapshot::load(&self.account, &key)? {
+                    Some(snapshot) => {
+                        tracing::debug!(url = %key, fetched_at = %snapshot.fetched_at, "serving snapshot");
+                        Ok(recording::to_response(
+                            200,
+                            std::iter::empty(),
+                            snapshot.body.into_bytes(),
+                        ))
+                    }
+                    None => Err(Error::Offline(format!(
+                        "No cached data for {} yet. Run the command once while online to make it available offline.",
+                        path
+                    ))),
+                }
+            });
+        }
+        if !snapshotted {
+            return next.run(request);
+        }
+        Box::pin(async move {
+            let response = next.run(request).await?;
+            if !response.status().is_success() {
+                return Ok(response);
+            }
+            let status = response.status().as_u16();
+            let headers = response.headers().clone();
+            let body = response.text().await?;
+            if let Err(e) = snapshot::store(&self.account, &key, &body) {
+                tracing::debug!(error = %e, "failed to store snapshot");
+            }
+            Ok(recording::to_response(
+                status,
+                headers
+                    .iter()
+                    .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?))),
+                body.into_bytes(),
+            ))
+        })
+    }
+}
+
+/// Writes each exchange to the `--record` file
+pub(crate) struct Record;
+
+impl Middleware for Record {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>> {
+        if !recording::is_recording() {
+            return next.run(request);
+        }
+        let method = request.method().clone();
+        let url = request.url().to_string();
+        let body = request
+            .body()
+            .and_then(|b| b.as_bytes())
+            .map(|b| String::from_utf8_lossy(b).into_owned());
+        Box::pin(async move {
+            let response = next.run(request).await?;
+            recording::record(body, method.as_str(), &url, response).await
+        })
+    }
+}
+
+/// Keeps at most `[network] max_concurrent_requests` requests in flight,
+/// across every clone of the client
+pub(crate) struct Limit(pub Arc<Semaphore>);
+
+impl Middleware for Limit {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(async move {
+            // The semaphore is never closed, so acquiring cannot fail
+            let _permit = self.0.acquire().await.ok();
+            next.run(request).await
+        })
+    }
+}
+
+/// Logs each request with its status and timing, and keeps every write for
+/// the audit log
+pub(crate) struct Log;
+
+impl Middleware for Log {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>> {
+        let method = request.method().clone();
+        let url = request.url().clone();
+        Box::pin(async move {
+            let started = Instant::now();
+            tracing::debug!(%method, %url, "sending request");
+
+            let result = next.run(request).await;
+            let elapsed_ms = started.elapsed().as_millis() as u64;
+
+            if method != Method::GET && method != Method::HEAD {
+                record_write(ApiWrite {
+                    method: method.to_string(),
+                    path: url.path().to_string(),
+                    status: result.as_ref().ok().map(|r| r.status().as_u16()),
+                });
+            }
+            match &result {
+                Ok(response) => {
+                    tracing::debug!(%method, %url, status = response.status().as_u16(), elapsed_ms, "received response")
+                }
+                Err(e) => tracing::warn!(%method, %url, elapsed_ms, error = %e, "request failed"),
+            }
+            result
+        })
+    }
+}
+
+/// Sends a request that can safely be repeated again when it fails in a way
+/// that may pass: a network error, rate limiting, or the gateway being
+/// briefly unavailable. Waits as long as `Retry-After` asks, or a little
+/// longer each time, above [`Limit`] so the wait holds no place in it.
+pub(crate) struct Retry;
+
+impl Retry {
+    const ATTEMPTS: u32 = 3;
+    /// The longest `Retry-After` honoured; longer waits are left to the user
+    const LONGEST_WAIT: Duration = Duration::from_secs(30);
+
+    fn repeatable(method: &Method) -> bool {
+        [Method::GET, Method::HEAD, Method::PUT, Method::DELETE].contains(method)
+    }
+
+    fn passing(status: StatusCode) -> bool {
+        matches!(
+            status,
+            StatusCode::TOO_MANY_REQUESTS
+                | StatusCode::BAD_GATEWAY
+                | StatusCode::SERVICE_UNAVAILABLE
+                | StatusCode::GATEWAY_TIMEOUT
+        )
+    }
+
+    /// How long to wait before attempt `attempt + 1`
+    fn wait(attempt: u32, response: Option<&Response>) -> Duration {
+        let asked = response
+            .and_then(|r| r.headers().get(RETRY_AFTER))
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+        match asked {
+            Some(asked) => asked.min(Self::LONGEST_WAIT),
+            None => Duration::from_millis(250 << attempt),
+        }
+    }
+}
+
+impl Middleware for Retry {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>> {
+        if !Self::repeatable(request.method()) {
+            return next.run(request);
+        }
+        Box::pin(async move {
+            let mut request = request;
+            let mut attempt = 1;
+            loop {
+                // A streamed body can't be sent twice
+                let Some(again) = request.try_clone() else {
+                    return next.run(request).await;
+                };
+                if attempt >= Self::ATTEMPTS {
+                    return next.run(request).await;
+                }
+                let wait = match next.clone().run(request).await {
+                    Ok(response) if Self::passing(response.status()) => {
+                        Self::wait(attempt, Some(&response))
+                    }
+                    Err(e) if e.is_transient() => Self::wait(attempt, None),
+                    result => return result,
+                };
+                tracing::debug!(
+                    url = %again.url(),
+                    attempt,
+                    wait_ms = wait.as_millis() as u64,
+                    "retrying request"
+                );
+                tokio::time::sleep(wait).await;
+                request = again;
+                attempt += 1;
+            }
+        })
+    }
+}
+
+/// Adds the credential's `Authorization` header, unless the request
+/// already has one
+pub(crate) struct Auth(pub Credential);
+
+impl Middleware for Auth {
+    fn handle<'a>(
+        &'a self,
+        mut request: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<Response>> {
+        if !request.headers().contains_key(AUTHORIZATION) {
+            match HeaderValue::from_str(&self.0.auth_header()) {
+                Ok(mut value) => {
+                    value.set_sensitive(true);
+                    request.headers_mut().insert(AUTHORIZATION, value);
+                }
+                Err(e) => return Box::pin(async move { Err(Error::Auth(e.to_string())) }),
+            }
+        }
+        next.run(request)
+    }
+}
@@ -1,7 +1,21 @@
 pub mod client;
+pub mod commits;
+pub mod decode;
+pub mod downloads;
+pub mod insights;
 pub mod issues;
+pub mod middleware;
+pub mod multipart;
 pub mod pipelines;
 pub mod pullrequests;
+pub mod recording;
+pub mod refs;
 pub mod repos;
+pub mod snapshot;
+pub mod snippets;
+pub mod status;
+pub mod users;
+pub mod variables;
+pub mod webhooks;
 
 pub use client::*;
@@ -0,0 +1,88 @@
+//! `multipart/form-data` request bodies
+//!
+//! A few endpoints (snippets, downloads) only accept form uploads. The bodies
+//! are small and built in memory, so this encodes them directly rather than
+//! pulling in reqwest's streaming multipart support.
+
+/// A form of text fields and file parts
+#[derive(Debug, Clone)]
+pub struct Form {
+    boundary: String,
+    body: Vec<u8>,
+}
+
+impl Form {
+    /// A form with a random boundary, so file contents can't end a part
+    /// early by containing it
+    pub fn new() -> Self {
+        let mut random = [0u8; 16];
+        getrandom::fill(&mut random).expect("operating system random source");
+        let boundary = random
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        Self {
+            boundary: format!("bitbucket-cli-{}", boundary),
+            body: Vec::new(),
+        }
+    }
+
+    /// Add a text field
+    pub fn text(mut self, name: &str, value: &str) -> Self {
+        self.start_part(&format!("form-data; name=\"{}\"", escape(name)), None);
+        self.body.extend_from_slice(value.as_bytes());
+        self.body.extend_from_slice(b"\r\n");
+        self
+    }
+
+    /// Add a file part
+    pub fn file(mut self, name: &str, file_name: &str, contents: &[u8]) -> Self {
+        let disposition = format!(
+            "form-data; name=\"{}\"; filename=\"{}\"",
+            escape(name),
+            escape(file_name)
+        );
+        self.start_part(&disposition, Some("application/octet-stream"));
+        self.body.extend_from_slice(contents);
+        self.body.extend_from_slice(b"\r\n");
+        self
+    }
+
+    /// Value for the `Content-Type` request header
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    /// The encoded body, closing the last part
+    pub fn into_body(mut self) -> Vec<u8> {
+        self.body
+            .extend_from_slice(format!("--{}--\r\n", self.boundary).as_bytes());
+        self.body
+    }
+
+    fn start_part(&mut self, disposition: &str, content_type: Option<&str>) {
+        let mut header = format!(
+            "--{}\r\nContent-Disposition: {}\r\n",
+            self.boundary, disposition
+        );
+        if let Some(content_type) = content_type {
+            header.push_str(&format!("Content-Type: {}\r\n", content_type));
+        }
+        header.push_str("\r\n");
+        self.body.extend_from_slice(header.as_bytes());
+    }
+}
+
+impl Default for Form {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Quote-safe header parameter value
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace(['\r', '\n'], " ")
+}
@@ -1,14 +1,19 @@
-use anyhow::Result;
+use futures::Stream;
+use reqwest::StatusCode;
 
-use super::BitbucketClient;
+use crate::error::{Error, Result};
+
+use super::{BitbucketClient, Resume};
 use crate::models::{Paginated, Pipeline, PipelineStep, TriggerPipelineRequest};
 
 impl BitbucketClient {
-    /// List pipelines for a repository
+    /// List pipelines for a repository, optionally matching a Bitbucket query
+    /// language filter
     pub async fn list_pipelines(
         &self,
         workspace: &str,
         repo_slug: &str,
+        filter: Option<&str>,
         page: Option<u32>,
         pagelen: Option<u32>,
     ) -> Result<Paginated<Pipeline>> {
@@ -17,6 +22,10 @@ impl BitbucketClient {
         // Sort by created_on descending to get most recent first
         query.push(("sort", "-created_on".to_string()));
 
+        if let Some(q) = filter {
+            query.push(("q", q.to_string()));
+        }
+
         if let Some(p) = page {
             query.push(("page", p.to_string()));
         }
@@ -30,6 +39,23 @@ impl BitbucketClient {
         self.get_with_query(&path, &query_refs).await
     }
 
+    /// Stream every pipeline in a repository, newest first, optionally
+    /// matching a filter, fetching pages as needed
+    pub fn stream_pipelines(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        filter: Option<&str>,
+    ) -> impl Stream<Item = Result<Pipeline>> + Send + use<> {
+        let mut query = vec![("sort", "-created_on"), ("pagelen", "100")];
+        if let Some(q) = filter {
+            query.push(("q", q));
+        }
+
+        let path = format!("/repositories/{}/{}/pipelines", workspace, repo_slug);
+        self.paginate_with_query(&path, &query)
+    }
+
     /// Get a specific pipeline
     pub async fn get_pipeline(
         &self,
@@ -111,17 +137,41 @@ impl BitbucketClient {
             workspace, repo_slug, pipeline_uuid, step_uuid
         );
 
-        let response = reqwest::Client::new()
-            .get(self.url(&path))
-            .header("Authorization", self.auth_header())
-            .send()
-            .await?;
+        self.get_text(&path, "*/*").await
+    }
 
-        if response.status().is_success() {
-            Ok(response.text().await?)
+    /// The bytes of a step's log after the first `offset`, to follow it while
+    /// the step runs; empty when there's nothing new or no log yet
+    pub async fn get_step_log_from(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        pipeline_uuid: &str,
+        step_uuid: &str,
+        offset: u64,
+    ) -> Result<Vec<u8>> {
+        let path = format!(
+            "/repositories/{}/{}/pipelines/{}/steps/{}/log",
+            workspace, repo_slug, pipeline_uuid, step_uuid
+        );
+        // A growing log keeps no ETag worth checking; its start doesn't change
+        let resume = Resume { offset, etag: None };
+        let response = match self.get_file(&path, resume).await {
+            Ok(response) => response,
+            Err(Error::NotFound(_)) => return Ok(Vec::new()),
+            Err(e) if e.status() == Some(StatusCode::RANGE_NOT_SATISFIABLE) => {
+                return Ok(Vec::new());
+            }
+            Err(e) => return Err(e),
+        };
+        // A server that ignores the range sends the whole log again
+        let skip = if response.status() == StatusCode::PARTIAL_CONTENT {
+            0
         } else {
-            anyhow::bail!("Failed to get step log: {}", response.status())
-        }
+            offset as usize
+        };
+        let bytes = response.bytes().await?;
+        Ok(bytes.get(skip..).unwrap_or_default().to_vec())
     }
 
     /// List pipelines whose target commit matches `commit_hash`, newest first.
@@ -143,7 +193,7 @@ impl BitbucketClient {
     ) -> Result<Vec<Pipeline>> {
         let pagelen = scan_limit.clamp(1, 100);
         let pipelines = self
-            .list_pipelines(workspace, repo_slug, None, Some(pagelen))
+            .list_pipelines(workspace, repo_slug, None, None, Some(pagelen))
             .await?;
         Ok(pipelines
             .values
@@ -166,14 +216,14 @@ impl BitbucketClient {
     ) -> Result<Pipeline> {
         // Search for the pipeline with the given build number
         let pipelines = self
-            .list_pipelines(workspace, repo_slug, Some(1), Some(100))
+            .list_pipelines(workspace, repo_slug, None, Some(1), Some(100))
             .await?;
 
         pipelines
             .values
             .into_iter()
             .find(|p| p.build_number == build_number)
-            .ok_or_else(|| anyhow::anyhow!("Pipeline #{} not found", build_number))
+            .ok_or_else(|| Error::NotFound(format!("Pipeline #{}", build_number)))
     }
 }
 
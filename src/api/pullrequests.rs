@@ -1,18 +1,24 @@
-use anyhow::Result;
+use futures::Stream;
+use reqwest::Response;
 
-use super::BitbucketClient;
+use crate::error::Result;
+
+use super::{BitbucketClient, Resume};
 use crate::models::{
-    CreatePullRequestRequest, MergePullRequestRequest, Paginated, PullRequest, PullRequestComment,
-    PullRequestState,
+    Commit, CommitStatus, CreatePullRequestRequest, DiffStat, InlineComment,
+    MergePullRequestRequest, Paginated, PullRequest, PullRequestActivity, PullRequestComment,
+    PullRequestState, UserRef,
 };
 
 impl BitbucketClient {
-    /// List pull requests for a repository
+    /// List pull requests for a repository, optionally matching a Bitbucket
+    /// query language filter
     pub async fn list_pull_requests(
         &self,
         workspace: &str,
         repo_slug: &str,
         state: Option<PullRequestState>,
+        filter: Option<&str>,
         page: Option<u32>,
         pagelen: Option<u32>,
     ) -> Result<Paginated<PullRequest>> {
@@ -21,6 +27,9 @@ impl BitbucketClient {
         if let Some(s) = state {
             query.push(("state", s.to_string()));
         }
+        if let Some(q) = filter {
+            query.push(("q", q.to_string()));
+        }
         if let Some(p) = page {
             query.push(("page", p.to_string()));
         }
@@ -34,6 +43,105 @@ impl BitbucketClient {
         self.get_with_query(&path, &query_refs).await
     }
 
+    /// Stream every pull request in a repository, optionally matching a
+    /// filter, fetching pages as needed
+    pub fn stream_pull_requests(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        state: Option<PullRequestState>,
+        filter: Option<&str>,
+    ) -> impl Stream<Item = Result<PullRequest>> + Send + use<> {
+        let state = state.map(|s| s.to_string());
+        let mut query = vec![("pagelen", "50")];
+        if let Some(s) = &state {
+            query.push(("state", s.as_str()));
+        }
+        if let Some(q) = filter {
+            query.push(("q", q));
+        }
+
+        let path = format!("/repositories/{}/{}/pullrequests", workspace, repo_slug);
+        self.paginate_with_query(&path, &query)
+    }
+
+    /// Stream every pull request in a state, including participants and
+    /// their approvals, which listings otherwise leave out
+    pub fn stream_pull_requests_with_participants(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        state: PullRequestState,
+    ) -> impl Stream<Item = Result<PullRequest>> + Send + use<> {
+        let state = state.to_string();
+        let query = [
+            ("pagelen", "50"),
+            ("state", state.as_str()),
+            ("fields", "+values.participants"),
+        ];
+
+        let path = format!("/repositories/{}/{}/pullrequests", workspace, repo_slug);
+        self.paginate_with_query(&path, &query)
+    }
+
+    /// Stream pull requests in a state that match a Bitbucket query language
+    /// filter (e.g. `updated_on >= 2024-05-01T00:00:00+00:00`)
+    pub fn search_pull_requests(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        state: PullRequestState,
+        filter: &str,
+    ) -> impl Stream<Item = Result<PullRequest>> + Send + use<> {
+        let state = state.to_string();
+        let query = [
+            ("pagelen", "50"),
+            ("state", state.as_str()),
+            ("q", filter),
+            ("sort", "-updated_on"),
+        ];
+
+        let path = format!("/repositories/{}/{}/pullrequests", workspace, repo_slug);
+        self.paginate_with_query(&path, &query)
+    }
+
+    /// Like [`search_pull_requests`](Self::search_pull_requests) for merged
+    /// ones, with the date of each merge commit
+    pub fn search_merged_pull_requests(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        filter: &str,
+    ) -> impl Stream<Item = Result<PullRequest>> + Send + use<> {
+        let query = [
+            ("pagelen", "50"),
+            ("state", "MERGED"),
+            ("q", filter),
+            ("sort", "-updated_on"),
+            ("fields", "+values.merge_commit.date"),
+        ];
+
+        let path = format!("/repositories/{}/{}/pullrequests", workspace, repo_slug);
+        self.paginate_with_query(&path, &query)
+    }
+
+    /// Like [`search_pull_requests`](Self::search_pull_requests), but
+    /// matching any of several states
+    pub fn search_pull_requests_in(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        states: &[PullRequestState],
+        filter: &str,
+    ) -> impl Stream<Item = Result<PullRequest>> + Send + use<> {
+        let states: Vec<String> = states.iter().map(|s| s.to_string()).collect();
+        let mut query = vec![("pagelen", "50"), ("q", filter), ("sort", "-updated_on")];
+        query.extend(states.iter().map(|s| ("state", s.as_str())));
+
+        let path = format!("/repositories/{}/{}/pullrequests", workspace, repo_slug);
+        self.paginate_with_query(&path, &query)
+    }
+
     /// Get a specific pull request
     pub async fn get_pull_request(
         &self,
@@ -88,6 +196,26 @@ impl BitbucketClient {
         self.put(&path, &request).await
     }
 
+    /// Replace a pull request's reviewers; the API wants the title with them
+    pub async fn set_pr_reviewers(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        pr_id: u64,
+        title: &str,
+        reviewers: &[UserRef],
+    ) -> Result<PullRequest> {
+        let request = serde_json::json!({
+            "title": title,
+            "reviewers": reviewers,
+        });
+        let path = format!(
+            "/repositories/{}/{}/pullrequests/{}",
+            workspace, repo_slug, pr_id
+        );
+        self.put(&path, &request).await
+    }
+
     /// Merge a pull request
     pub async fn merge_pull_request(
         &self,
@@ -148,6 +276,34 @@ impl BitbucketClient {
         self.post(&path, &serde_json::json!({})).await
     }
 
+    /// List build statuses reported for a pull request's head commit
+    pub async fn list_pr_statuses(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        pr_id: u64,
+    ) -> Result<Vec<CommitStatus>> {
+        let path = format!(
+            "/repositories/{}/{}/pullrequests/{}/statuses",
+            workspace, repo_slug, pr_id
+        );
+        self.get_all_pages(&path).await
+    }
+
+    /// List the files a pull request changes, including merge conflicts
+    pub async fn list_pr_diffstat(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        pr_id: u64,
+    ) -> Result<Vec<DiffStat>> {
+        let path = format!(
+            "/repositories/{}/{}/pullrequests/{}/diffstat",
+            workspace, repo_slug, pr_id
+        );
+        self.get_all_pages(&path).await
+    }
+
     /// List comments on a pull request
     pub async fn list_pr_comments(
         &self,
@@ -162,6 +318,25 @@ impl BitbucketClient {
         self.get(&path).await
     }
 
+    /// The `count` most recent comments on a pull request, newest first
+    pub async fn latest_pr_comments(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        pr_id: u64,
+        count: usize,
+    ) -> Result<Vec<PullRequestComment>> {
+        let path = format!(
+            "/repositories/{}/{}/pullrequests/{}/comments",
+            workspace, repo_slug, pr_id
+        );
+        let pagelen = count.to_string();
+        let page: Paginated<PullRequestComment> = self
+            .get_with_query(&path, &[("sort", "-created_on"), ("pagelen", &pagelen)])
+            .await?;
+        Ok(page.values)
+    }
+
     /// Get a specific comment on a pull request
     pub async fn get_pr_comment(
         &self,
@@ -177,17 +352,50 @@ impl BitbucketClient {
         self.get(&path).await
     }
 
-    /// Add a comment to a pull request
+    /// Stream a pull request's activity (updates, approvals, comments),
+    /// newest first
+    pub fn stream_pr_activity(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        pr_id: u64,
+    ) -> impl Stream<Item = Result<PullRequestActivity>> + Send + use<> {
+        let path = format!(
+            "/repositories/{}/{}/pullrequests/{}/activity",
+            workspace, repo_slug, pr_id
+        );
+        self.paginate_with_query(&path, &[("pagelen", "50")])
+    }
+
+    /// Stream every comment on a pull request, oldest first
+    pub fn stream_pr_comments(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        pr_id: u64,
+    ) -> impl Stream<Item = Result<PullRequestComment>> + Send + use<> {
+        let path = format!(
+            "/repositories/{}/{}/pullrequests/{}/comments",
+            workspace, repo_slug, pr_id
+        );
+        self.paginate_with_query(&path, &[("pagelen", "100"), ("sort", "created_on")])
+    }
+
+    /// Add a comment to a pull request, anchored to a line of its diff when
+    /// `inline` is given
     pub async fn add_pr_comment(
         &self,
         workspace: &str,
         repo_slug: &str,
         pr_id: u64,
         content: &str,
+        inline: Option<&InlineComment>,
     ) -> Result<PullRequestComment> {
         #[derive(serde::Serialize)]
-        struct CommentRequest {
+        struct CommentRequest<'a> {
             content: ContentRequest,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            inline: Option<&'a InlineComment>,
         }
 
         #[derive(serde::Serialize)]
@@ -199,6 +407,7 @@ impl BitbucketClient {
             content: ContentRequest {
                 raw: content.to_string(),
             },
+            inline,
         };
 
         let path = format!(
@@ -208,6 +417,21 @@ impl BitbucketClient {
         self.post(&path, &request).await
     }
 
+    /// Stream the commits on a pull request's source branch that aren't on
+    /// its destination, newest first
+    pub fn stream_pr_commits(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        pr_id: u64,
+    ) -> impl Stream<Item = Result<Commit>> + Send + use<> {
+        let path = format!(
+            "/repositories/{}/{}/pullrequests/{}/commits",
+            workspace, repo_slug, pr_id
+        );
+        self.paginate_with_query(&path, &[("pagelen", "50")])
+    }
+
     /// Get the diff for a pull request
     pub async fn get_pr_diff(
         &self,
@@ -215,24 +439,25 @@ impl BitbucketClient {
         repo_slug: &str,
         pr_id: u64,
     ) -> Result<String> {
-        use reqwest::header::ACCEPT;
-
         let path = format!(
             "/repositories/{}/{}/pullrequests/{}/diff",
             workspace, repo_slug, pr_id
         );
+        self.get_text(&path, "text/plain").await
+    }
 
-        let response = reqwest::Client::new()
-            .get(self.url(&path))
-            .header("Authorization", self.auth_header())
-            .header(ACCEPT, "text/plain")
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            Ok(response.text().await?)
-        } else {
-            anyhow::bail!("Failed to get diff: {}", response.status())
-        }
+    /// Start downloading a pull request's diff, from where `resume` says
+    pub async fn get_pr_diff_file(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        pr_id: u64,
+        resume: Resume,
+    ) -> Result<Response> {
+        let path = format!(
+            "/repositories/{}/{}/pullrequests/{}/diff",
+            workspace, repo_slug, pr_id
+        );
+        self.get_file(&path, resume).await
     }
 }
@@ -0,0 +1,306 @@
+//! Recording API traffic to a file and replaying it (`--record`/`--replay`)
+//!
+//! A recording is a JSON Lines file with one [`Exchange`] per request,
+//! appended as responses arrive. Secrets in URLs, headers and bodies are
+//! masked, query strings of signed storage URLs are dropped, and request
+//! headers are never written, so a recording can be attached to a bug
+//! report. Response headers are kept and bodies that aren't UTF-8 are stored
+//! base64-encoded, so a replayed download gets the same bytes, length and
+//! ETag it was recorded with. Replaying answers each request with the first unused
+//! exchange for the same method, path and query, whatever host or API
+//! version prefix it was recorded against, and fails requests that weren't
+//! recorded.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use reqwest::header::{HeaderMap, LOCATION, SET_COOKIE};
+use reqwest::{Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::logging;
+
+/// One request and the response it got
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Exchange {
+    pub method: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<String>,
+    pub status: u16,
+    /// Response headers, by name
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<(String, String)>,
+    /// The body, when it's UTF-8 text
+    #[serde(default)]
+    pub body: String,
+    /// The body, base64-encoded, when it isn't UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_base64: Option<String>,
+}
+
+impl Exchange {
+    fn body_bytes(&self) -> Vec<u8> {
+        match &self.body_base64 {
+            Some(encoded) => STANDARD.decode(encoded).unwrap_or_default(),
+            None => self.body.clone().into_bytes(),
+        }
+    }
+}
+
+/// Path prefix of the public API, absent when recording against a mock
+const API_PREFIX: &str = "/2.0";
+
+enum Mode {
+    Record(File),
+    Replay(Vec<Option<Exchange>>),
+}
+
+static MODE: Mutex<Option<Mode>> = Mutex::new(None);
+
+/// Append every exchange from now on to `path`, replacing its contents
+pub fn start_recording(path: &Path) -> Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(path)
+        .map_err(Error::io("Failed to create recording"))?;
+    set(Mode::Record(file));
+    Ok(())
+}
+
+/// Answer requests from the exchanges recorded in `path`
+pub fn start_replay(path: &Path) -> Result<()> {
+    let contents = fs::read_to_string(path).map_err(Error::io("Failed to read recording"))?;
+    let exchanges = contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map(Some))
+        .collect::<std::result::Result<_, _>>()?;
+    set(Mode::Replay(exchanges));
+    Ok(())
+}
+
+/// Whether requests are answered from a recording
+pub fn is_replaying() -> bool {
+    matches!(MODE.lock().ok().as_deref(), Some(Some(Mode::Replay(_))))
+}
+
+pub(crate) fn is_recording() -> bool {
+    matches!(MODE.lock().ok().as_deref(), Some(Some(Mode::Record(_))))
+}
+
+fn set(mode: Mode) {
+    if let Ok(mut current) = MODE.lock() {
+        *current = Some(mode);
+    }
+}
+
+/// The recorded response to `request`
+pub(crate) fn replay(request: &Request) -> Result<Response> {
+    let wanted = key(request.method().as_str(), request.url().as_str());
+    let exchange = MODE.lock().ok().and_then(|mut mode| match mode.as_mut() {
+        Some(Mode::Replay(exchanges)) => exchanges
+            .iter_mut()
+            .find(|e| e.as_ref().is_some_and(|e| key(&e.method, &e.url) == wanted))
+            .and_then(Option::take),
+        _ => None,
+    });
+    let exchange = exchange.ok_or_else(|| {
+        Error::Replay(format!(
+            "No recorded response for {} {}",
+            request.method(),
+            request.url().path()
+        ))
+    })?;
+    tracing::debug!(method = %exchange.method, url = %exchange.url, "replaying response");
+    Ok(to_response(
+        exchange.status,
+        exchange
+            .headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str())),
+        exchange.body_bytes(),
+    ))
+}
+
+/// Record `response` to the request for `url`, handing back an equivalent
+/// response since reading the body consumes it. The request's URL is kept
+/// rather than the response's, which is a signed storage URL once a
+/// download has been redirected.
+pub(crate) async fn record(
+    request_body: Option<String>,
+    method: &str,
+    url: &str,
+    response: Response,
+) -> Result<Response> {
+    let status = response.status().as_u16();
+    let headers = response.headers().clone();
+    let body = response.bytes().await?.to_vec();
+
+    let (text, body_base64) = match std::str::from_utf8(&body) {
+        Ok(text) => (logging::redact(text).into_owned(), None),
+        Err(_) => (String::new(), Some(STANDARD.encode(&body))),
+    };
+    let exchange = Exchange {
+        method: method.to_string(),
+        url: redact_url(url),
+        request_body: request_body.map(|b| logging::redact(&b).into_owned()),
+        status,
+        headers: recorded_headers(&headers),
+        body: text,
+        body_base64,
+    };
+    if let Ok(mut mode) = MODE.lock()
+        && let Some(Mode::Record(file)) = mode.as_mut()
+    {
+        let line = serde_json::to_string(&exchange)?;
+        writeln!(file, "{}", line).map_err(Error::io("Failed to write recording"))?;
+    }
+
+    // Hand back what the server sent, not the redacted copy
+    Ok(to_response(
+        status,
+        headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?))),
+        body,
+    ))
+}
+
+/// `headers` as recorded: cookies left out, secrets masked and signed
+/// redirect targets stripped of their query
+fn recorded_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter(|(name, _)| *name != SET_COOKIE)
+        .filter_map(|(name, value)| {
+            let value = value.to_str().ok()?;
+            let value = if name == LOCATION {
+                redact_url(value)
+            } else {
+                logging::redact(value).into_owned()
+            };
+            Some((name.to_string(), value))
+        })
+        .collect()
+}
+
+/// Query parameters that mark a URL as signed, so that its query is a
+/// credential
+const SIGNATURE_PARAMS: &[&str] = &["signature", "x-amz-signature", "sig", "key-pair-id"];
+
+/// `url` with secrets masked, and its whole query dropped when it's a
+/// signed URL
+fn redact_url(url: &str) -> String {
+    if let Ok(mut parsed) = url::Url::parse(url)
+        && parsed
+            .query_pairs()
+            .any(|(name, _)| SIGNATURE_PARAMS.contains(&name.to_ascii_lowercase().as_str()))
+    {
+        parsed.set_query(Some("[REDACTED]"));
+        return parsed.to_string();
+    }
+    logging::redact(url).into_owned()
+}
+
+/// A response with `status`, `headers` and `body`, as if from the network
+pub(crate) fn to_response<'a>(
+    status: u16,
+    headers: impl Iterator<Item = (&'a str, &'a str)>,
+    body: Vec<u8>,
+) -> Response {
+    let mut builder =
+        http::Response::builder().status(StatusCode::from_u16(status).unwrap_or(StatusCode::OK));
+    for (name, value) in headers {
+        builder = builder.header(name, value);
+    }
+    let response = builder
+        .body(body.clone())
+        .unwrap_or_else(|_| http::Response::new(body));
+    Response::from(response)
+}
+
+/// Method, path and query of a URL, ignoring the host and API version
+fn key(method: &str, url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(url) => format!(
+            "{} {}{}",
+            method,
+            url.path().strip_prefix(API_PREFIX).unwrap_or(url.path()),
+            url.query().map(|q| format!("?{}", q)).unwrap_or_default()
+        ),
+        Err(_) => format!("{} {}", method, url),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_ignores_the_host() {
+        assert_eq!(
+            key(
+                "GET",
+                "https://api.bitbucket.org/2.0/repositories/acme?pagelen=50"
+            ),
+            key("GET", "http://127.0.0.1:4000/repositories/acme?pagelen=50")
+        );
+        assert_ne!(
+            key("GET", "https://api.bitbucket.org/2.0/repositories/acme"),
+            key("DELETE", "https://api.bitbucket.org/2.0/repositories/acme")
+        );
+    }
+
+    #[test]
+    fn signed_urls_lose_their_query() {
+        assert_eq!(
+            redact_url(
+                "https://bbuseruploads.s3.amazonaws.com/a/b.zip?X-Amz-Credential=AK&X-Amz-Signature=abc"
+            ),
+            "https://bbuseruploads.s3.amazonaws.com/a/b.zip?[REDACTED]"
+        );
+        assert_eq!(
+            redact_url("https://api.bitbucket.org/2.0/repositories/acme?pagelen=50"),
+            "https://api.bitbucket.org/2.0/repositories/acme?pagelen=50"
+        );
+    }
+
+    #[tokio::test]
+    async fn binary_bodies_and_headers_survive_a_round_trip() {
+        let body = vec![0xff, 0x00, 0xd8, 0x42];
+        let response = Response::from(
+            http::Response::builder()
+                .status(206)
+                .header("ETag", "\"v1\"")
+                .header("Content-Range", "bytes 4-7/8")
+                .body(body.clone())
+                .unwrap(),
+        );
+        let exchange = Exchange {
+            method: "GET".into(),
+            url: "https://api.bitbucket.org/2.0/repositories/acme/app/downloads/a.bin".into(),
+            request_body: None,
+            status: 206,
+            headers: recorded_headers(response.headers()),
+            body: String::new(),
+            body_base64: Some(STANDARD.encode(&body)),
+        };
+        let line = serde_json::to_string(&exchange).unwrap();
+        let exchange: Exchange = serde_json::from_str(&line).unwrap();
+        assert_eq!(exchange.body_bytes(), body);
+        assert!(
+            exchange
+                .headers
+                .iter()
+                .any(|(name, value)| name == "etag" && value == "\"v1\"")
+        );
+    }
+}
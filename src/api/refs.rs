@@ -0,0 +1,45 @@
+use crate::error::Result;
+
+use super::BitbucketClient;
+use crate::models::Ref;
+
+impl BitbucketClient {
+    /// Get a branch and its head commit
+    pub async fn get_branch(&self, workspace: &str, repo_slug: &str, name: &str) -> Result<Ref> {
+        let path = format!(
+            "/repositories/{}/{}/refs/branches/{}",
+            workspace, repo_slug, name
+        );
+        self.get(&path).await
+    }
+
+    /// Get a tag and the commit it points at
+    pub async fn get_tag(&self, workspace: &str, repo_slug: &str, name: &str) -> Result<Ref> {
+        let path = format!(
+            "/repositories/{}/{}/refs/tags/{}",
+            workspace, repo_slug, name
+        );
+        self.get(&path).await
+    }
+
+    /// Create a tag on a commit; a message makes it an annotated tag
+    pub async fn create_tag(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        name: &str,
+        hash: &str,
+        message: Option<&str>,
+    ) -> Result<Ref> {
+        let mut request = serde_json::json!({
+            "name": name,
+            "target": { "hash": hash },
+        });
+        if let Some(message) = message {
+            request["message"] = message.into();
+        }
+
+        let path = format!("/repositories/{}/{}/refs/tags", workspace, repo_slug);
+        self.post(&path, &request).await
+    }
+}
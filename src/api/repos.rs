@@ -1,7 +1,9 @@
-use anyhow::Result;
+use futures::Stream;
+
+use crate::error::Result;
 
 use super::BitbucketClient;
-use crate::models::{CreateRepositoryRequest, Paginated, Repository};
+use crate::models::{CodeSearchResult, CreateRepositoryRequest, Paginated, Repository, User};
 
 impl BitbucketClient {
     /// List repositories for a workspace
@@ -26,6 +28,60 @@ impl BitbucketClient {
         self.get_with_query(&path, &query_refs).await
     }
 
+    /// Stream every repository in a workspace, fetching pages as needed
+    pub fn stream_repositories(
+        &self,
+        workspace: &str,
+    ) -> impl Stream<Item = Result<Repository>> + Send + use<> {
+        let path = format!("/repositories/{}", workspace);
+        self.paginate_with_query(&path, &[("pagelen", "100")])
+    }
+
+    /// List repositories in `workspace`, or in every workspace the
+    /// authenticated user belongs to, optionally only those they have `role`
+    /// on (`member`, `contributor`, `admin` or `owner`) and ordered by the
+    /// `sort` field (`-updated_on` for newest first)
+    pub async fn list_repositories_matching(
+        &self,
+        workspace: Option<&str>,
+        role: Option<&str>,
+        sort: Option<&str>,
+        pagelen: u32,
+    ) -> Result<Paginated<Repository>> {
+        let pagelen = pagelen.to_string();
+        let mut query = repository_query(role, sort);
+        query.push(("pagelen", &pagelen));
+        self.get_with_query(&repositories_path(workspace), &query)
+            .await
+    }
+
+    /// Stream every repository [`list_repositories_matching`] would list,
+    /// fetching pages as needed
+    ///
+    /// [`list_repositories_matching`]: Self::list_repositories_matching
+    pub fn stream_repositories_matching(
+        &self,
+        workspace: Option<&str>,
+        role: Option<&str>,
+        sort: Option<&str>,
+    ) -> impl Stream<Item = Result<Repository>> + Send + use<> {
+        let mut query = repository_query(role, sort);
+        query.push(("pagelen", "100"));
+        self.paginate_with_query(&repositories_path(workspace), &query)
+    }
+
+    /// List the most recently updated repositories in a workspace
+    pub async fn list_recently_updated_repositories(
+        &self,
+        workspace: &str,
+        pagelen: u32,
+    ) -> Result<Paginated<Repository>> {
+        let pagelen = pagelen.to_string();
+        let path = format!("/repositories/{}", workspace);
+        self.get_with_query(&path, &[("sort", "-updated_on"), ("pagelen", &pagelen)])
+            .await
+    }
+
     /// Get a specific repository
     pub async fn get_repository(&self, workspace: &str, repo_slug: &str) -> Result<Repository> {
         let path = format!("/repositories/{}/{}", workspace, repo_slug);
@@ -91,6 +147,16 @@ impl BitbucketClient {
         self.get(&path).await
     }
 
+    /// Stream every branch of a repository, fetching pages as needed
+    pub fn stream_branches(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+    ) -> impl Stream<Item = Result<crate::models::Branch>> + Send + use<> {
+        let path = format!("/repositories/{}/{}/refs/branches", workspace, repo_slug);
+        self.paginate_with_query(&path, &[("pagelen", "100")])
+    }
+
     /// Get the main branch
     pub async fn get_main_branch(
         &self,
@@ -100,4 +166,136 @@ impl BitbucketClient {
         let path = format!("/repositories/{}/{}/main-branch", workspace, repo_slug);
         self.get(&path).await
     }
+
+    /// List the top-level files and directories of a repository at `revision`
+    pub async fn list_source_root(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        revision: &str,
+    ) -> Result<Vec<crate::models::SourceEntry>> {
+        let path = format!(
+            "/repositories/{}/{}/src/{}/",
+            workspace, repo_slug, revision
+        );
+        self.get_all_pages(&path).await
+    }
+
+    /// Get the contents of a file at `revision`
+    pub async fn get_source_file(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        revision: &str,
+        file_path: &str,
+    ) -> Result<String> {
+        let path = format!(
+            "/repositories/{}/{}/src/{}/{}",
+            workspace, repo_slug, revision, file_path
+        );
+        self.get_text(&path, "*/*").await
+    }
+
+    /// List a repository's branch restrictions
+    pub async fn list_branch_restrictions(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+    ) -> Result<Vec<crate::models::BranchRestriction>> {
+        let path = format!(
+            "/repositories/{}/{}/branch-restrictions",
+            workspace, repo_slug
+        );
+        self.get_all_pages(&path).await
+    }
+
+    /// Get a repository's effective branching model
+    pub async fn get_branching_model(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+    ) -> Result<crate::models::BranchingModel> {
+        let path = format!("/repositories/{}/{}/branching-model", workspace, repo_slug);
+        self.get(&path).await
+    }
+
+    /// List users watching a repository
+    pub async fn list_watchers(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        pagelen: Option<u32>,
+    ) -> Result<Paginated<User>> {
+        let pagelen = pagelen.map(|len| len.to_string());
+        let mut query = Vec::new();
+        if let Some(len) = &pagelen {
+            query.push(("pagelen", len.as_str()));
+        }
+
+        let path = format!("/repositories/{}/{}/watchers", workspace, repo_slug);
+        self.get_with_query(&path, &query).await
+    }
+
+    /// Stream every user watching a repository, fetching pages as needed
+    pub fn stream_watchers(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+    ) -> impl Stream<Item = Result<User>> + Send + use<> {
+        let path = format!("/repositories/{}/{}/watchers", workspace, repo_slug);
+        self.paginate_with_query(&path, &[("pagelen", "100")])
+    }
+
+    /// Stream the files across a workspace's repositories that code search
+    /// finds for `query`, fetching pages as needed
+    pub fn stream_code_search(
+        &self,
+        workspace: &str,
+        query: &str,
+    ) -> impl Stream<Item = Result<CodeSearchResult>> + Send + use<> {
+        let path = format!("/workspaces/{}/search/code", workspace);
+        self.paginate_with_query(&path, &[("search_query", query), ("pagelen", "100")])
+    }
+
+    /// Subscribe a user (by UUID) to a repository's notifications
+    pub async fn watch_repository(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        user_uuid: &str,
+    ) -> Result<()> {
+        let path = format!(
+            "/repositories/{}/{}/watchers/{}",
+            workspace, repo_slug, user_uuid
+        );
+        self.put_no_response(&path, &serde_json::json!({})).await
+    }
+
+    /// Unsubscribe a user (by UUID) from a repository's notifications
+    pub async fn unwatch_repository(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        user_uuid: &str,
+    ) -> Result<()> {
+        let path = format!(
+            "/repositories/{}/{}/watchers/{}",
+            workspace, repo_slug, user_uuid
+        );
+        self.delete(&path).await
+    }
+}
+
+fn repositories_path(workspace: Option<&str>) -> String {
+    match workspace {
+        Some(workspace) => format!("/repositories/{}", workspace),
+        None => "/repositories".to_string(),
+    }
+}
+
+fn repository_query<'a>(role: Option<&'a str>, sort: Option<&'a str>) -> Vec<(&'a str, &'a str)> {
+    [("role", role), ("sort", sort)]
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|value| (key, value)))
+        .collect()
 }
@@ -0,0 +1,165 @@
+//! On-disk snapshots of successful GET responses
+//!
+//! Every successful read is stored under `$XDG_CACHE_HOME/bitbucket-cli/snapshots`,
+//! in a directory for the account that made it, keyed by its full request
+//! URL. Offline mode serves requests from these snapshots instead of the
+//! network and records the age of the oldest one it used so callers can tell
+//! the user how fresh the data is. Once per process, the least recently
+//! stored snapshots are removed to keep them all under [`MAX_BYTES`].
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, Once};
+use std::time::SystemTime;
+
+use crate::config::{Config, xdg};
+use crate::error::{Error, Result};
+
+/// Most space snapshots take up, across every account
+pub const MAX_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Oldest snapshot served during this process, for freshness reporting
+static OLDEST_SERVED: Mutex<Option<DateTime<Utc>>> = Mutex::new(None);
+
+static PRUNED: Once = Once::new();
+
+/// A cached response body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub url: String,
+    pub fetched_at: DateTime<Utc>,
+    pub body: String,
+}
+
+/// Get the directory snapshots are stored in
+pub fn snapshot_dir() -> Result<PathBuf> {
+    Ok(Config::cache_dir()
+        .map_err(Error::config)?
+        .join("snapshots"))
+}
+
+/// Store a response body `account` got for `url`, replacing any previous
+/// snapshot
+pub fn store(account: &str, url: &str, body: &str) -> Result<()> {
+    let root = snapshot_dir()?;
+    PRUNED.call_once(|| prune(&root, MAX_BYTES));
+    let dir = root.join(account);
+    xdg::ensure_dir(&dir).map_err(Error::config)?;
+
+    let snapshot = Snapshot {
+        url: url.to_string(),
+        fetched_at: Utc::now(),
+        body: body.to_string(),
+    };
+    let json = serde_json::to_string(&snapshot)?;
+
+    fs::write(snapshot_path(account, url)?, json).map_err(Error::io("Failed to write snapshot"))?;
+    Ok(())
+}
+
+/// Load the snapshot `account` got for `url`, recording its age for
+/// freshness reporting
+pub fn load(account: &str, url: &str) -> Result<Option<Snapshot>> {
+    let path = snapshot_path(account, url)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let json = fs::read_to_string(&path).map_err(Error::io("Failed to read snapshot"))?;
+    let snapshot: Snapshot = serde_json::from_str(&json)?;
+
+    // Guard against (unlikely) hash collisions between different URLs
+    if snapshot.url != url {
+        return Ok(None);
+    }
+
+    if let Ok(mut oldest) = OLDEST_SERVED.lock() {
+        if oldest.is_none_or(|t| snapshot.fetched_at < t) {
+            *oldest = Some(snapshot.fetched_at);
+        }
+    }
+
+    Ok(Some(snapshot))
+}
+
+/// Fetch time of the oldest snapshot served so far, if any
+pub fn oldest_served() -> Option<DateTime<Utc>> {
+    OLDEST_SERVED.lock().ok().and_then(|t| *t)
+}
+
+/// Map a URL to its snapshot file. `DefaultHasher` is stable for a given
+/// build, which is all a regenerable cache needs.
+fn snapshot_path(account: &str, url: &str) -> Result<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    Ok(snapshot_dir()?
+        .join(account)
+        .join(format!("{:016x}.json", hasher.finish())))
+}
+
+/// Remove the least recently written snapshots under `root` until the rest
+/// take up no more than `max_bytes`
+fn prune(root: &Path, max_bytes: u64) {
+    let mut files: Vec<(SystemTime, u64, PathBuf)> = fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .flat_map(|account| fs::read_dir(account.path()).into_iter().flatten().flatten())
+        .filter_map(|file| {
+            let metadata = file.metadata().ok()?;
+            Some((metadata.modified().ok()?, metadata.len(), file.path()))
+        })
+        .collect();
+    let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+    if total <= max_bytes {
+        return;
+    }
+    files.sort();
+    for (_, len, path) in files {
+        if total <= max_bytes {
+            break;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => total -= len,
+            Err(e) => {
+                tracing::debug!(path = %path.display(), error = %e, "failed to remove snapshot")
+            }
+        }
+    }
+    tracing::debug!(bytes = total, "pruned snapshots");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pruning_removes_the_oldest_snapshots_first() {
+        let root = tempfile::tempdir().unwrap();
+        let account = root.path().join("a1b2");
+        fs::create_dir(&account).unwrap();
+        for (name, age) in [("old", 30), ("middle", 20), ("new", 10)] {
+            let path = account.join(format!("{}.json", name));
+            fs::write(&path, [0u8; 100]).unwrap();
+            let modified = SystemTime::now() - std::time::Duration::from_secs(age);
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        }
+
+        prune(root.path(), 250);
+        let mut left: Vec<String> = fs::read_dir(&account)
+            .unwrap()
+            .map(|f| f.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["middle.json", "new.json"]);
+    }
+}
@@ -0,0 +1,104 @@
+use futures::Stream;
+
+use crate::error::Result;
+
+use super::BitbucketClient;
+use super::multipart::Form;
+use crate::models::{Paginated, Snippet};
+
+impl BitbucketClient {
+    /// List snippets in a workspace, optionally filtered by the caller's role
+    /// (`owner`, `contributor` or `member`)
+    pub async fn list_snippets(
+        &self,
+        workspace: &str,
+        role: Option<&str>,
+        pagelen: Option<u32>,
+    ) -> Result<Paginated<Snippet>> {
+        let pagelen = pagelen.map(|len| len.to_string());
+        let mut query = Vec::new();
+        if let Some(role) = role {
+            query.push(("role", role));
+        }
+        if let Some(len) = &pagelen {
+            query.push(("pagelen", len.as_str()));
+        }
+
+        let path = format!("/snippets/{}", workspace);
+        self.get_with_query(&path, &query).await
+    }
+
+    /// Stream every snippet in a workspace, fetching pages as needed
+    pub fn stream_snippets(
+        &self,
+        workspace: &str,
+        role: Option<&str>,
+    ) -> impl Stream<Item = Result<Snippet>> + Send + use<> {
+        let mut query = vec![("pagelen", "50")];
+        if let Some(role) = role {
+            query.push(("role", role));
+        }
+
+        let path = format!("/snippets/{}", workspace);
+        self.paginate_with_query(&path, &query)
+    }
+
+    /// Get a snippet
+    pub async fn get_snippet(&self, workspace: &str, snippet_id: &str) -> Result<Snippet> {
+        let path = format!("/snippets/{}/{}", workspace, snippet_id);
+        self.get(&path).await
+    }
+
+    /// Get the raw contents of one file in a snippet
+    pub async fn get_snippet_file(
+        &self,
+        workspace: &str,
+        snippet_id: &str,
+        file_name: &str,
+    ) -> Result<Vec<u8>> {
+        let path = format!(
+            "/snippets/{}/{}/files/{}",
+            workspace,
+            snippet_id,
+            encode_path(file_name)
+        );
+        self.get_bytes(&path).await
+    }
+
+    /// Create a snippet from `(file name, contents)` pairs
+    pub async fn create_snippet(
+        &self,
+        workspace: &str,
+        title: &str,
+        is_private: bool,
+        files: &[(String, Vec<u8>)],
+    ) -> Result<Snippet> {
+        let form = files.iter().fold(
+            Form::new()
+                .text("title", title)
+                .text("is_private", &is_private.to_string()),
+            |form, (name, contents)| form.file("file", name, contents),
+        );
+
+        let path = format!("/snippets/{}", workspace);
+        self.post_form(&path, form).await
+    }
+
+    /// Delete a snippet
+    pub async fn delete_snippet(&self, workspace: &str, snippet_id: &str) -> Result<()> {
+        let path = format!("/snippets/{}/{}", workspace, snippet_id);
+        self.delete(&path).await
+    }
+}
+
+/// Percent-encode a file path for use in a URL, keeping `/` separators
+pub(super) fn encode_path(path: &str) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
@@ -0,0 +1,47 @@
+//! Bitbucket Cloud's public status page, which needs no credentials
+
+use std::time::Duration;
+
+use super::client::http_client_builder;
+use crate::config::NetworkConfig;
+use crate::error::{Error, Result};
+use crate::models::StatusSummary;
+
+const STATUS_URL: &str = "https://bitbucket.status.atlassian.com";
+const STATUS_URL_ENV: &str = "BITBUCKET_STATUS_URL";
+
+/// Status page base URL, overridable with `BITBUCKET_STATUS_URL` (for tests)
+pub fn status_url() -> String {
+    std::env::var(STATUS_URL_ENV)
+        .ok()
+        .filter(|url| !url.is_empty())
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or_else(|| STATUS_URL.to_string())
+}
+
+/// Fetch the current component statuses and open incidents, giving up
+/// after `timeout`
+pub async fn service_status(network: &NetworkConfig, timeout: Duration) -> Result<StatusSummary> {
+    let url = format!("{}/api/v2/summary.json", status_url());
+    let response = http_client_builder(network)
+        .timeout(timeout)
+        .build()?
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| {
+            if e.is_timeout() {
+                Error::Timeout
+            } else {
+                e.into()
+            }
+        })?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(Error::Api {
+            status,
+            message: format!("status page answered {}", url),
+        });
+    }
+    Ok(response.json().await?)
+}
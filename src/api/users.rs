@@ -0,0 +1,51 @@
+use futures::Stream;
+
+use crate::error::Result;
+
+use super::BitbucketClient;
+use crate::models::{Paginated, User, WorkspaceMembership};
+
+impl BitbucketClient {
+    /// Get the authenticated user
+    pub async fn get_current_user(&self) -> Result<User> {
+        self.get("/user").await
+    }
+
+    /// Get a user by UUID (with braces) or Atlassian account ID
+    pub async fn get_user(&self, selected_user: &str) -> Result<User> {
+        let path = format!("/users/{}", selected_user);
+        self.get(&path).await
+    }
+
+    /// Stream the workspaces the authenticated user belongs to
+    pub fn stream_workspaces(
+        &self,
+    ) -> impl Stream<Item = Result<WorkspaceMembership>> + Send + use<> {
+        self.paginate_with_query("/user/permissions/workspaces", &[("pagelen", "100")])
+    }
+
+    /// List members of a workspace
+    pub async fn list_workspace_members(
+        &self,
+        workspace: &str,
+        pagelen: Option<u32>,
+    ) -> Result<Paginated<WorkspaceMembership>> {
+        let pagelen = pagelen.map(|len| len.to_string());
+        let mut query = Vec::new();
+        if let Some(len) = &pagelen {
+            query.push(("pagelen", len.as_str()));
+        }
+
+        let path = format!("/workspaces/{}/members", workspace);
+        self.get_with_query(&path, &query).await
+    }
+
+    /// Stream every member of a workspace, fetching pages as needed
+    pub fn stream_workspace_members(
+        &self,
+        workspace: &str,
+    ) -> impl Stream<Item = Result<WorkspaceMembership>> + Send + use<> {
+        let path = format!("/workspaces/{}/members", workspace);
+        self.paginate_with_query(&path, &[("pagelen", "100")])
+    }
+}
@@ -0,0 +1,54 @@
+use crate::error::Result;
+
+use super::BitbucketClient;
+use crate::models::{PipelineVariable, PipelineVariableRequest};
+
+impl BitbucketClient {
+    /// List a workspace's Pipelines variables
+    pub async fn list_workspace_variables(&self, workspace: &str) -> Result<Vec<PipelineVariable>> {
+        let path = format!("/workspaces/{}/pipelines-config/variables", workspace);
+        self.get_all_pages(&path).await
+    }
+
+    /// List a repository's Pipelines variables
+    pub async fn list_repository_variables(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+    ) -> Result<Vec<PipelineVariable>> {
+        let path = format!(
+            "/repositories/{}/{}/pipelines_config/variables",
+            workspace, repo_slug
+        );
+        self.get_all_pages(&path).await
+    }
+
+    /// Create a Pipelines variable on a repository
+    pub async fn create_repository_variable(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        request: &PipelineVariableRequest,
+    ) -> Result<PipelineVariable> {
+        let path = format!(
+            "/repositories/{}/{}/pipelines_config/variables",
+            workspace, repo_slug
+        );
+        self.post(&path, request).await
+    }
+
+    /// Replace a repository's Pipelines variable by UUID
+    pub async fn update_repository_variable(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        uuid: &str,
+        request: &PipelineVariableRequest,
+    ) -> Result<PipelineVariable> {
+        let path = format!(
+            "/repositories/{}/{}/pipelines_config/variables/{}",
+            workspace, repo_slug, uuid
+        );
+        self.put(&path, request).await
+    }
+}
@@ -0,0 +1,35 @@
+use futures::Stream;
+
+use crate::error::Result;
+
+use super::BitbucketClient;
+use crate::models::{CreateWebhookRequest, HookEvent, Webhook};
+
+impl BitbucketClient {
+    /// Stream the events a webhook on a `subject_type` ("repository" or
+    /// "workspace") can subscribe to
+    pub fn stream_hook_events(
+        &self,
+        subject_type: &str,
+    ) -> impl Stream<Item = Result<HookEvent>> + Send + use<> {
+        let path = format!("/hook_events/{}", subject_type);
+        self.paginate_with_query(&path, &[("pagelen", "100")])
+    }
+
+    /// Create a webhook on a repository
+    pub async fn create_webhook(
+        &self,
+        workspace: &str,
+        repo_slug: &str,
+        request: &CreateWebhookRequest,
+    ) -> Result<Webhook> {
+        let path = format!("/repositories/{}/{}/hooks", workspace, repo_slug);
+        self.post(&path, request).await
+    }
+
+    /// Delete a repository webhook by UUID
+    pub async fn delete_webhook(&self, workspace: &str, repo_slug: &str, uid: &str) -> Result<()> {
+        let path = format!("/repositories/{}/{}/hooks/{}", workspace, repo_slug, uid);
+        self.delete(&path).await
+    }
+}
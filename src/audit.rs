@@ -0,0 +1,319 @@
+//! Append-only audit log of changes made through the CLI
+//!
+//! Every run that sends a write request (anything but GET) appends one JSON
+//! line to `$XDG_STATE_HOME/bitbucket-cli/audit.log` recording when, by whom,
+//! the command line, the requests sent, and how the run ended. Entries are
+//! hash-chained: each carries the SHA-256 of its content including the
+//! previous entry's hash, so edits and deletions show up in [`verify`]. With
+//! `[audit] signing_key` set, each hash is also signed with `ssh-keygen -Y
+//! sign` or `gpg --detach-sign`. SSH signatures are verified against the
+//! keys in `[audit] allowed_signers`; GPG ones against the keyring.
+//!
+//! Appending holds a lock on `audit.log.lock`, so concurrent runs can't both
+//! chain onto the same entry.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::api::ApiWrite;
+use crate::config::{AuditConfig, Config, SigningFormat, xdg};
+use crate::logging;
+
+const LOG_FILE: &str = "audit.log";
+
+/// Namespace for SSH signatures, so they can't be replayed as other signatures
+const SSH_NAMESPACE: &str = "bitbucket-cli-audit";
+
+/// What happened in one run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub timestamp: DateTime<Utc>,
+    /// Bitbucket username, when known
+    pub user: Option<String>,
+    /// Local account that ran the command
+    pub local_user: Option<String>,
+    pub command: String,
+    pub args: Vec<String>,
+    pub requests: Vec<ApiWrite>,
+    pub exit_code: i32,
+    pub error: Option<String>,
+    /// Hash of the previous entry, empty for the first
+    pub prev: String,
+}
+
+/// A line of the audit log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    #[serde(flatten)]
+    pub record: Record,
+    pub hash: String,
+    pub signature: Option<String>,
+}
+
+/// Path of the audit log
+pub fn log_path() -> Result<PathBuf> {
+    Ok(Config::state_dir()?.join(LOG_FILE))
+}
+
+/// Append an entry for this run if it sent any write requests. `args` is
+/// the command line without the program name.
+pub fn record(command: &str, args: &[String], exit_code: i32, error: Option<String>) -> Result<()> {
+    let requests = crate::api::writes();
+    if requests.is_empty() {
+        return Ok(());
+    }
+
+    let config = Config::load().unwrap_or_default();
+    if !config.audit.enabled {
+        return Ok(());
+    }
+
+    let path = log_path()?;
+    xdg::ensure_dir(&Config::state_dir()?)?;
+    // Held until the entry is written, so no other run appends in between
+    let _lock = xdg::lock(&path)?;
+    let prev = read(&path)?
+        .last()
+        .map(|e| e.hash.clone())
+        .unwrap_or_default();
+
+    let record = Record {
+        timestamp: Utc::now(),
+        user: config.username().map(str::to_string),
+        local_user: std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .ok(),
+        command: command.to_string(),
+        args: args
+            .iter()
+            .map(|a| logging::redact(a).into_owned())
+            .collect(),
+        requests,
+        exit_code,
+        error,
+        prev,
+    };
+    let hash = hash(&record)?;
+
+    // An unsigned entry is better than none, so sign failures are reported
+    // only after the entry is written
+    let (signature, sign_error) = match &config.audit.signing_key {
+        Some(key) => match sign(&hash, key, &config.audit) {
+            Ok(signature) => (Some(signature), None),
+            Err(e) => (None, Some(e)),
+        },
+        None => (None, None),
+    };
+    let entry = Entry {
+        record,
+        hash,
+        signature,
+    };
+
+    let mut file = open_append(&path)?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)
+        .with_context(|| format!("Failed to write audit log {:?}", path))?;
+
+    match sign_error {
+        Some(e) => Err(e.context("Audit log entry was written unsigned")),
+        None => Ok(()),
+    }
+}
+
+/// Read every entry, oldest first; a missing log has none
+pub fn read(path: &Path) -> Result<Vec<Entry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Failed to read audit log {:?}", path))?;
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Audit log line {} is not a valid entry", i + 1))
+        })
+        .collect()
+}
+
+/// Check the hash chain, and signatures when present, SSH ones against the
+/// `allowed_signers` file. Returns the number of entries checked, or the
+/// first problem found.
+pub fn verify(entries: &[Entry], allowed_signers: Option<&Path>) -> Result<usize> {
+    let mut prev = "";
+    for (i, entry) in entries.iter().enumerate() {
+        let line = i + 1;
+        if entry.record.prev != prev {
+            anyhow::bail!(
+                "Entry {} does not follow entry {}: an entry was removed or reordered",
+                line,
+                i
+            );
+        }
+        if hash(&entry.record)? != entry.hash {
+            anyhow::bail!("Entry {} was modified after it was written", line);
+        }
+        if let Some(signature) = &entry.signature {
+            check_signature(&entry.hash, signature, allowed_signers)
+                .with_context(|| format!("Entry {} has an invalid signature", line))?;
+        }
+        prev = &entry.hash;
+    }
+    Ok(entries.len())
+}
+
+fn hash(record: &Record) -> Result<String> {
+    let digest = Sha256::digest(serde_json::to_vec(record)?);
+    Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+fn open_append(path: &Path) -> Result<fs::File> {
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .with_context(|| format!("Failed to open audit log {:?}", path))
+}
+
+/// Sign `hash`, returning an armored signature
+fn sign(hash: &str, key: &str, config: &AuditConfig) -> Result<String> {
+    let mut command = match config.signing_format {
+        SigningFormat::Ssh => {
+            let mut command = Command::new("ssh-keygen");
+            command.args(["-Y", "sign", "-n", SSH_NAMESPACE, "-f"]);
+            command.arg(expand_home(key));
+            command
+        }
+        SigningFormat::Gpg => {
+            let mut command = Command::new("gpg");
+            command.args(["--batch", "--armor", "--detach-sign", "--local-user", key]);
+            command
+        }
+    };
+    let signature = pipe(&mut command, hash).context("Failed to sign audit log entry")?;
+    Ok(signature)
+}
+
+/// `path` with a leading `~/` expanded to the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Check a signature with the tool that made it, told apart by its armor
+fn check_signature(hash: &str, signature: &str, allowed_signers: Option<&Path>) -> Result<()> {
+    let mut signature_file =
+        tempfile::NamedTempFile::new().context("Failed to create a temporary file")?;
+    signature_file.write_all(signature.as_bytes())?;
+    let signature_path = signature_file.path();
+
+    if signature.contains("BEGIN PGP SIGNATURE") {
+        let mut command = Command::new("gpg");
+        command.args(["--batch", "--verify"]);
+        command.arg(signature_path).arg("-");
+        return pipe(&mut command, hash).map(|_| ());
+    }
+
+    let allowed_signers = allowed_signers
+        .context("Set [audit] allowed_signers to the keys SSH signatures may be made with")?;
+    let principals = pipe(
+        Command::new("ssh-keygen")
+            .args(["-Y", "find-principals", "-f"])
+            .arg(allowed_signers)
+            .arg("-s")
+            .arg(signature_path),
+        "",
+    )
+    .context("The signing key is not in the allowed signers file")?;
+    let principal = principals
+        .lines()
+        .next()
+        .context("The signing key is not in the allowed signers file")?;
+
+    let mut command = Command::new("ssh-keygen");
+    command.args(["-Y", "verify", "-n", SSH_NAMESPACE, "-f"]);
+    command.arg(allowed_signers).arg("-I").arg(principal);
+    command.arg("-s").arg(signature_path);
+    pipe(&mut command, hash).map(|_| ())
+}
+
+/// Run `command` with `input` on stdin, returning its stdout
+fn pipe(command: &mut Command, input: &str) -> Result<String> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {:?}", command.get_program()))?;
+    child
+        .stdin
+        .take()
+        .context("stdin was not captured")?
+        .write_all(input.as_bytes())?;
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(prev: &str, command: &str) -> Entry {
+        let record = Record {
+            timestamp: Utc::now(),
+            user: Some("ada".to_string()),
+            local_user: None,
+            command: command.to_string(),
+            args: vec![command.to_string()],
+            requests: Vec::new(),
+            exit_code: 0,
+            error: None,
+            prev: prev.to_string(),
+        };
+        Entry {
+            hash: hash(&record).unwrap(),
+            record,
+            signature: None,
+        }
+    }
+
+    #[test]
+    fn chain_detects_edits_and_removals() {
+        let first = entry("", "pr");
+        let second = entry(&first.hash, "repo");
+        let third = entry(&second.hash, "issue");
+
+        let mut entries = vec![first, second, third];
+        assert_eq!(verify(&entries, None).unwrap(), 3);
+
+        entries[1].record.exit_code = 1;
+        let error = verify(&entries, None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Entry 2 was modified after it was written"
+        );
+
+        entries.remove(1);
+        assert!(verify(&entries, None).is_err());
+    }
+}
@@ -1,93 +1,68 @@
-use anyhow::{Context, Result};
-use dialoguer::{Input, Password};
-
 use super::{AuthManager, Credential};
+use crate::api::{default_base_url, http_client_builder};
+use crate::config::Config;
+use crate::error::{Error, Result};
 
-/// API key authentication flow (fallback method)
+/// API key authentication (for automation/CI)
 /// Note: Atlassian has deprecated app passwords in favor of OAuth2
 pub struct ApiKeyAuth;
 
 impl ApiKeyAuth {
-    /// Run the interactive API key authentication flow
-    pub async fn authenticate(auth_manager: &AuthManager) -> Result<Credential> {
-        println!("\n🔐 Bitbucket API Key Authentication");
-        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-        println!();
-        println!("⚠️  Note: OAuth 2.0 is the preferred authentication method.");
-        println!("   API keys are provided for automation/CI scenarios.");
-        println!();
-        println!("To create an API key (HTTP access token):");
-        println!("1. Go to Bitbucket Settings → Personal settings");
-        println!("2. Click 'HTTP access tokens' under 'Access management'");
-        println!("3. Click 'Create token'");
-        println!("4. Give it a label and select required permissions");
-        println!();
-
-        let username: String = Input::new()
-            .with_prompt("Bitbucket username")
-            .interact_text()
-            .context("Failed to read username")?;
-
-        let api_key: String = Password::new()
-            .with_prompt("API key (HTTP access token)")
-            .interact()
-            .context("Failed to read API key")?;
+    /// Prefixes of Atlassian API tokens
+    pub const TOKEN_PREFIXES: &[&str] = &["ATATT", "ATCTT"];
+
+    /// Check whether a key looks like an Atlassian API token
+    pub fn has_expected_prefix(api_key: &str) -> bool {
+        Self::TOKEN_PREFIXES
+            .iter()
+            .any(|prefix| api_key.trim().starts_with(prefix))
+    }
 
+    /// Validate an API key against the Bitbucket API and store it
+    pub async fn login(
+        auth_manager: &AuthManager,
+        username: &str,
+        api_key: &str,
+    ) -> Result<Credential> {
         // Trim whitespace from token (common copy-paste issue)
-        let api_key = api_key.trim().to_string();
+        let api_key = api_key.trim();
 
-        // Validate token format
         if api_key.is_empty() {
-            anyhow::bail!("API key cannot be empty");
-        }
-
-        // Check for common Atlassian token prefixes
-        if !api_key.starts_with("ATATT") && !api_key.starts_with("ATCTT") {
-            println!("⚠️  Warning: Token doesn't start with expected prefix (ATATT or ATCTT)");
-            println!("   This might not be a valid Bitbucket API token.");
-            println!(
-                "   Token starts with: {}",
-                &api_key.chars().take(5).collect::<String>()
-            );
+            return Err(Error::Auth("API key cannot be empty".to_string()));
         }
 
         let credential = Credential::ApiKey {
-            username: username.clone(),
-            api_key,
+            username: username.to_string(),
+            api_key: api_key.to_string(),
         };
 
         // Validate credentials by making a test API call
         Self::validate_credentials(&credential).await?;
 
-        // Store credentials
         auth_manager.store_credentials(&credential)?;
 
-        println!("\n✅ Successfully authenticated as {}", username);
-        println!("💡 Tip: Use 'bitbucket auth login --oauth' for a better experience");
-
         Ok(credential)
     }
 
     /// Validate credentials against the Bitbucket API
     async fn validate_credentials(credential: &Credential) -> Result<()> {
-        let client = reqwest::Client::new();
-
-        println!("🔍 Validating credentials with Bitbucket API...");
+        let network = Config::load().map(|c| c.network).unwrap_or_default();
+        let client = http_client_builder(&network).build()?;
 
         let response = client
-            .get("https://api.bitbucket.org/2.0/user")
+            .get(format!("{}/user", default_base_url()))
             .header("Authorization", credential.auth_header())
             .header("User-Agent", "bitbucket-cli/0.3.0")
             .send()
-            .await
-            .context("Failed to connect to Bitbucket API")?;
+            .await?;
 
         let status = response.status();
+        tracing::debug!(status = status.as_u16(), "API key validation response");
 
         if status.is_success() {
             Ok(())
         } else if status == reqwest::StatusCode::UNAUTHORIZED {
-            anyhow::bail!(
+            Err(Error::Auth(
                 "Authentication failed (401 Unauthorized).\n\n\
                 Possible causes:\n\
                 - Incorrect username\n\
@@ -97,21 +72,21 @@ impl ApiKeyAuth {
                 1. Your Bitbucket username is correct\n\
                 2. Your API token is copied completely (should start with 'ATATT' or 'ATCTT')\n\
                 3. Token has 'Read' permission at minimum"
-            )
+                    .to_string(),
+            ))
         } else {
             let body = response
                 .text()
                 .await
                 .unwrap_or_else(|_| String::from("<unable to read response>"));
-            anyhow::bail!(
+            Err(Error::Auth(format!(
                 "API error ({}):\n{}\n\n\
                 This might indicate:\n\
                 - Network connectivity issues\n\
                 - Bitbucket API is unavailable\n\
                 - Rate limiting",
-                status,
-                body
-            )
+                status, body
+            )))
         }
     }
 }
@@ -1,6 +1,5 @@
-use anyhow::Result;
-
 use super::{Credential, FileStore, KeyringStore};
+use crate::error::Result;
 
 /// Credential storage that uses the platform secret store (macOS Keychain,
 /// Windows Credential Manager, GNOME Keyring / KDE Wallet), with file-based
@@ -21,11 +20,13 @@ impl CredentialStore {
     pub fn get_credential(&self) -> Result<Option<Credential>> {
         if let Some(keyring) = &self.keyring {
             if let Some(credential) = keyring.get_credential()? {
+                tracing::debug!(store = "keyring", "loaded credential");
                 return Ok(Some(credential));
             }
 
             // Migrate credentials from the legacy plain-text file store.
             if let Some(credential) = self.file.get_credential()? {
+                tracing::info!("migrating credential from file store to keyring");
                 keyring.store_credential(&credential)?;
                 let _ = self.file.delete_credential();
                 return Ok(Some(credential));
@@ -34,6 +35,7 @@ impl CredentialStore {
             return Ok(None);
         }
 
+        tracing::debug!(store = "file", "keyring unavailable, using file store");
         self.file.get_credential()
     }
 
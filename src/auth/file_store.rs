@@ -1,8 +1,8 @@
-use anyhow::{Context, Result};
 use std::fs;
 use std::path::PathBuf;
 
 use super::Credential;
+use crate::error::{Error, Result};
 
 /// File-based credential storage (fallback when keyring is unavailable)
 pub struct FileStore {
@@ -12,11 +12,11 @@ pub struct FileStore {
 impl FileStore {
     pub fn new() -> Result<Self> {
         let config_dir = dirs::config_dir()
-            .context("Could not determine config directory")?
+            .ok_or_else(|| Error::Config("Could not determine config directory".to_string()))?
             .join("bitbucket");
 
         // Create config directory if it doesn't exist
-        fs::create_dir_all(&config_dir).context("Failed to create config directory")?;
+        fs::create_dir_all(&config_dir).map_err(Error::io("Failed to create config directory"))?;
 
         let path = config_dir.join("credentials.json");
 
@@ -25,8 +25,7 @@ impl FileStore {
 
     /// Store credentials in a file
     pub fn store_credential(&self, credential: &Credential) -> Result<()> {
-        let json =
-            serde_json::to_string_pretty(credential).context("Failed to serialize credential")?;
+        let json = serde_json::to_string_pretty(credential)?;
 
         // Write with restrictive permissions (0600 = read/write for owner only)
         #[cfg(unix)]
@@ -44,12 +43,12 @@ impl FileStore {
                     use std::io::Write;
                     file.write_all(json.as_bytes())
                 })
-                .context("Failed to write credential file")?;
+                .map_err(Error::io("Failed to write credential file"))?;
         }
 
         #[cfg(not(unix))]
         {
-            fs::write(&self.path, json).context("Failed to write credential file")?;
+            fs::write(&self.path, json).map_err(Error::io("Failed to write credential file"))?;
         }
 
         Ok(())
@@ -61,10 +60,10 @@ impl FileStore {
             return Ok(None);
         }
 
-        let json = fs::read_to_string(&self.path).context("Failed to read credential file")?;
+        let json =
+            fs::read_to_string(&self.path).map_err(Error::io("Failed to read credential file"))?;
 
-        let credential: Credential =
-            serde_json::from_str(&json).context("Failed to parse stored credential")?;
+        let credential: Credential = serde_json::from_str(&json)?;
 
         Ok(Some(credential))
     }
@@ -72,7 +71,7 @@ impl FileStore {
     /// Delete credentials from the file
     pub fn delete_credential(&self) -> Result<()> {
         if self.path.exists() {
-            fs::remove_file(&self.path).context("Failed to delete credential file")?;
+            fs::remove_file(&self.path).map_err(Error::io("Failed to delete credential file"))?;
         }
         Ok(())
     }
@@ -1,7 +1,7 @@
-use anyhow::{Context, Result};
 use keyring::Entry;
 
 use super::Credential;
+use crate::error::{Error, Result};
 
 const SERVICE_NAME: &str = "bitbucket-cli";
 const CREDENTIAL_KEY: &str = "credentials";
@@ -14,18 +14,19 @@ pub struct KeyringStore {
 
 impl KeyringStore {
     pub fn new() -> Result<Self> {
-        let entry =
-            Entry::new(SERVICE_NAME, CREDENTIAL_KEY).context("Failed to create keyring entry")?;
+        let entry = Entry::new(SERVICE_NAME, CREDENTIAL_KEY).map_err(|e| {
+            Error::CredentialStore(format!("Failed to create keyring entry: {}", e))
+        })?;
         Ok(Self { entry })
     }
 
     /// Store credentials in the keyring
     pub fn store_credential(&self, credential: &Credential) -> Result<()> {
-        let json = serde_json::to_string(credential).context("Failed to serialize credential")?;
+        let json = serde_json::to_string(credential)?;
 
-        self.entry
-            .set_password(&json)
-            .context("Failed to store credential in keyring")?;
+        self.entry.set_password(&json).map_err(|e| {
+            Error::CredentialStore(format!("Failed to store credential in keyring: {}", e))
+        })?;
 
         Ok(())
     }
@@ -34,15 +35,14 @@ impl KeyringStore {
     pub fn get_credential(&self) -> Result<Option<Credential>> {
         match self.entry.get_password() {
             Ok(json) => {
-                let credential: Credential =
-                    serde_json::from_str(&json).context("Failed to parse stored credential")?;
+                let credential: Credential = serde_json::from_str(&json)?;
                 Ok(Some(credential))
             }
             Err(keyring::Error::NoEntry) => Ok(None),
-            Err(e) => Err(anyhow::anyhow!(
+            Err(e) => Err(Error::CredentialStore(format!(
                 "Failed to get credential from keyring: {}",
                 e
-            )),
+            ))),
         }
     }
 
@@ -51,10 +51,10 @@ impl KeyringStore {
         match self.entry.delete_credential() {
             Ok(()) => Ok(()),
             Err(keyring::Error::NoEntry) => Ok(()), // Already deleted
-            Err(e) => Err(anyhow::anyhow!(
+            Err(e) => Err(Error::CredentialStore(format!(
                 "Failed to delete credential from keyring: {}",
                 e
-            )),
+            ))),
         }
     }
 }
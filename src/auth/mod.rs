@@ -4,9 +4,11 @@ pub mod file_store;
 pub mod keyring_store;
 pub mod oauth;
 
-use anyhow::Result;
+use crate::error::Result;
 use base64::Engine;
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
 pub use api_key::*;
 pub use credential_store::*;
@@ -32,6 +34,44 @@ pub enum Credential {
 }
 
 impl Credential {
+    /// Read a credential from the environment, for CI and tests.
+    ///
+    /// `BITBUCKET_ACCESS_TOKEN` is used as a bearer token; otherwise
+    /// `BITBUCKET_USERNAME` and `BITBUCKET_API_KEY` together form an API key
+    /// credential.
+    pub fn from_env() -> Option<Self> {
+        let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+
+        if let Some(access_token) = var("BITBUCKET_ACCESS_TOKEN") {
+            return Some(Credential::OAuth {
+                access_token,
+                refresh_token: None,
+                expires_at: None,
+                client_id: None,
+                client_secret: None,
+            });
+        }
+
+        Some(Credential::ApiKey {
+            username: var("BITBUCKET_USERNAME")?,
+            api_key: var("BITBUCKET_API_KEY")?,
+        })
+    }
+
+    /// Environment variables that [`Credential::from_env`] reads back into
+    /// this credential, for handing authentication to child processes
+    pub fn to_env(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Credential::OAuth { access_token, .. } => {
+                vec![("BITBUCKET_ACCESS_TOKEN", access_token.clone())]
+            }
+            Credential::ApiKey { username, api_key } => vec![
+                ("BITBUCKET_USERNAME", username.clone()),
+                ("BITBUCKET_API_KEY", api_key.clone()),
+            ],
+        }
+    }
+
     /// Get the authorization header value for API requests
     #[inline]
     pub fn auth_header(&self) -> String {
@@ -89,6 +129,23 @@ impl Credential {
         }
     }
 
+    /// A name for the account this credential signs in as that gives
+    /// nothing away, for keeping per-account caches apart. OAuth access
+    /// tokens change on every refresh, so the refresh token stands in.
+    pub fn account_key(&self) -> String {
+        let identity = match self {
+            Credential::ApiKey { username, .. } => username,
+            Credential::OAuth {
+                refresh_token,
+                access_token,
+                ..
+            } => refresh_token.as_ref().unwrap_or(access_token),
+        };
+        let mut hasher = DefaultHasher::new();
+        identity.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+
     /// Get username (only available for API key credentials)
     pub fn username(&self) -> Option<&str> {
         match self {
@@ -110,8 +167,17 @@ impl AuthManager {
         })
     }
 
-    /// Get stored credentials
+    /// Get credentials, preferring any set in the environment over stored ones
     pub fn get_credentials(&self) -> Result<Option<Credential>> {
+        if let Some(credential) = Credential::from_env() {
+            tracing::debug!("using credential from environment");
+            return Ok(Some(credential));
+        }
+        self.store.get_credential()
+    }
+
+    /// Get the stored credentials, ignoring any set in the environment
+    pub fn stored_credentials(&self) -> Result<Option<Credential>> {
         self.store.get_credential()
     }
 
@@ -1,19 +1,22 @@
-use anyhow::{Context, Result};
 use oauth2::basic::BasicClient;
 use oauth2::{
-    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, PkceCodeChallenge, RedirectUrl,
-    RefreshToken, Scope, TokenResponse, TokenUrl,
+    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, EndpointNotSet, EndpointSet,
+    PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, RefreshToken, Scope, TokenResponse, TokenUrl,
 };
 use std::io::{BufRead, BufReader, Write};
 use std::net::TcpListener;
 
 use super::{AuthManager, Credential};
+use crate::api::http_client_builder;
+use crate::config::Config;
+use crate::error::{Error, Result};
 
 /// Async HTTP client for OAuth2 token exchange
 async fn async_http_client(
     request: oauth2::HttpRequest,
 ) -> Result<oauth2::HttpResponse, reqwest::Error> {
-    let client = reqwest::Client::builder()
+    let network = Config::load().map(|c| c.network).unwrap_or_default();
+    let client = http_client_builder(&network)
         .redirect(reqwest::redirect::Policy::none())
         .build()?;
 
@@ -44,20 +47,50 @@ async fn async_http_client(
 const BITBUCKET_AUTH_URL: &str = "https://bitbucket.org/site/oauth2/authorize";
 const BITBUCKET_TOKEN_URL: &str = "https://bitbucket.org/site/oauth2/access_token";
 
+/// OAuth client with the authorization and token endpoints configured
+type ConfiguredClient =
+    BasicClient<EndpointSet, EndpointNotSet, EndpointNotSet, EndpointNotSet, EndpointSet>;
+
 /// OAuth 2.0 authentication flow
 pub struct OAuthFlow {
     client_id: String,
     client_secret: String,
+    callback_ports: Vec<u16>,
+}
+
+/// An authorization started by [`OAuthFlow::start`], waiting for the user to
+/// approve it in the browser
+pub struct PendingAuthorization {
+    client: ConfiguredClient,
+    listener: TcpListener,
+    port: u16,
+    redirect_url: String,
+    authorize_url: String,
+    csrf_token: CsrfToken,
+    pkce_verifier: PkceCodeVerifier,
+    client_id: String,
+    client_secret: String,
 }
 
 impl OAuthFlow {
+    /// Callback ports tried in order. Bitbucket requires the callback URL to
+    /// match the consumer configuration exactly, so the port must be static.
+    pub const CALLBACK_PORTS: &[u16] = &[8080, 3000, 8888, 9000];
+
     pub fn new(client_id: String, client_secret: String) -> Self {
         Self {
             client_id,
             client_secret,
+            callback_ports: Self::CALLBACK_PORTS.to_vec(),
         }
     }
 
+    /// Listen for the callback on the first free port of `ports` instead
+    pub fn with_callback_ports(mut self, ports: Vec<u16>) -> Self {
+        self.callback_ports = ports;
+        self
+    }
+
     /// Try to bind to one of the preferred ports
     fn bind_to_available_port(ports: &[u16]) -> Result<(TcpListener, u16)> {
         for &port in ports {
@@ -69,47 +102,41 @@ impl OAuthFlow {
             }
         }
 
-        anyhow::bail!(
+        Err(Error::Auth(format!(
             "Could not bind to any preferred port. Tried: {:?}\n\n\
             Please ensure at least one of these ports is available:\n\
             - Close any applications using these ports\n\
+            - Or pick another with --callback-port or [oauth] callback_ports in the config\n\
             - Or use API key authentication: bitbucket auth login --api-key",
             ports
-        )
+        )))
     }
 
-    /// Run the OAuth 2.0 authentication flow
-    pub async fn authenticate(&self, auth_manager: &AuthManager) -> Result<Credential> {
-        println!("\n🔐 Bitbucket OAuth Authentication");
-        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-        println!();
-
-        // Use a static port for OAuth callback (required by Bitbucket)
-        // Try common ports in order: 8080, 3000, 8888, 9000
-        const PREFERRED_PORTS: &[u16] = &[8080, 3000, 8888, 9000];
+    /// Build the OAuth client for the Bitbucket endpoints
+    fn client(&self) -> Result<ConfiguredClient> {
+        Ok(BasicClient::new(ClientId::new(self.client_id.clone()))
+            .set_client_secret(ClientSecret::new(self.client_secret.clone()))
+            .set_auth_uri(AuthUrl::new(BITBUCKET_AUTH_URL.to_string()).map_err(invalid_url)?)
+            .set_token_uri(TokenUrl::new(BITBUCKET_TOKEN_URL.to_string()).map_err(invalid_url)?))
+    }
 
-        let (listener, port) = Self::bind_to_available_port(PREFERRED_PORTS)
-            .context("Failed to bind callback server. Please ensure one of these ports is available: 8080, 3000, 8888, or 9000")?;
+    /// Start the authorization code flow: bind the local callback server and
+    /// build the URL the user must open to approve access
+    pub fn start(&self) -> Result<PendingAuthorization> {
+        let (listener, port) = Self::bind_to_available_port(&self.callback_ports)?;
 
         let redirect_url = format!("http://127.0.0.1:{}/callback", port);
+        tracing::info!(port, "OAuth callback server listening");
 
-        println!("📡 Callback server listening on port {}", port);
-        println!("   Make sure your OAuth consumer callback URL is set to:");
-        println!("   {}", redirect_url);
-        println!();
-
-        // Create OAuth client
-        let client = BasicClient::new(ClientId::new(self.client_id.clone()))
-            .set_client_secret(ClientSecret::new(self.client_secret.clone()))
-            .set_auth_uri(AuthUrl::new(BITBUCKET_AUTH_URL.to_string())?)
-            .set_token_uri(TokenUrl::new(BITBUCKET_TOKEN_URL.to_string())?)
-            .set_redirect_uri(RedirectUrl::new(redirect_url.clone())?);
+        let client = self
+            .client()?
+            .set_redirect_uri(RedirectUrl::new(redirect_url.clone()).map_err(invalid_url)?);
 
         // Generate PKCE challenge
         let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
 
         // Generate authorization URL
-        let (auth_url, csrf_token) = client
+        let (authorize_url, csrf_token) = client
             .authorize_url(CsrfToken::new_random)
             .add_scope(Scope::new("repository".to_string()))
             .add_scope(Scope::new("pullrequest".to_string()))
@@ -119,24 +146,91 @@ impl OAuthFlow {
             .set_pkce_challenge(pkce_challenge)
             .url();
 
-        println!("Opening browser for authentication...");
-        println!();
+        Ok(PendingAuthorization {
+            client,
+            listener,
+            port,
+            redirect_url,
+            authorize_url: authorize_url.to_string(),
+            csrf_token,
+            pkce_verifier,
+            client_id: self.client_id.clone(),
+            client_secret: self.client_secret.clone(),
+        })
+    }
 
-        // Try to open browser
-        if open::that(auth_url.as_str()).is_err() {
-            println!("Could not open browser automatically.");
-            println!("Please open this URL in your browser:");
-            println!();
-            println!("  {}", auth_url);
-            println!();
-        }
+    /// Refresh an expired OAuth token
+    pub async fn refresh_token(
+        &self,
+        auth_manager: &AuthManager,
+        refresh_token: &str,
+    ) -> Result<Credential> {
+        tracing::debug!("refreshing OAuth access token");
+        let token_response = self
+            .client()?
+            .exchange_refresh_token(&RefreshToken::new(refresh_token.to_string()))
+            .request_async(&async_http_client)
+            .await
+            .map_err(|e| Error::Auth(format!("Failed to refresh token: {}", e)))?;
 
-        println!("Waiting for authorization...");
+        let access_token = token_response.access_token().secret().to_string();
+        let new_refresh_token = token_response
+            .refresh_token()
+            .map(|t| t.secret().to_string())
+            .unwrap_or_else(|| refresh_token.to_string());
+        let expires_at = token_response
+            .expires_in()
+            .map(|d| chrono::Utc::now().timestamp() + d.as_secs() as i64);
+
+        let credential = Credential::OAuth {
+            access_token,
+            refresh_token: Some(new_refresh_token),
+            expires_at,
+            client_id: Some(self.client_id.clone()),
+            client_secret: Some(self.client_secret.clone()),
+        };
+
+        auth_manager.store_credentials(&credential)?;
+
+        Ok(credential)
+    }
+}
+
+impl PendingAuthorization {
+    /// Port the callback server is listening on
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Callback URL the OAuth consumer must be configured with
+    pub fn redirect_url(&self) -> &str {
+        &self.redirect_url
+    }
+
+    /// URL the user opens to approve access
+    pub fn authorize_url(&self) -> &str {
+        &self.authorize_url
+    }
+
+    /// Wait for the browser redirect, exchange the code for a token, and
+    /// store the resulting credential
+    pub async fn complete(self, auth_manager: &AuthManager) -> Result<Credential> {
+        let PendingAuthorization {
+            client,
+            listener,
+            csrf_token,
+            pkce_verifier,
+            client_id,
+            client_secret,
+            ..
+        } = self;
 
-        // Wait for callback
-        let code = Self::wait_for_callback(listener, csrf_token)?;
+        // Wait for callback on a blocking thread so Ctrl-C can still interrupt
+        let code = tokio::task::spawn_blocking(move || wait_for_callback(listener, csrf_token))
+            .await
+            .map_err(|e| Error::Auth(format!("Callback server task failed: {}", e)))??;
 
-        println!("Authorization received, exchanging for token...");
+        tracing::debug!("exchanging authorization code for token");
 
         // Exchange code for token
         let token_response = client
@@ -144,7 +238,12 @@ impl OAuthFlow {
             .set_pkce_verifier(pkce_verifier)
             .request_async(&async_http_client)
             .await
-            .context("Failed to exchange authorization code for token")?;
+            .map_err(|e| {
+                Error::Auth(format!(
+                    "Failed to exchange authorization code for token: {}",
+                    e
+                ))
+            })?;
 
         let access_token = token_response.access_token().secret().to_string();
         let refresh_token = token_response
@@ -158,66 +257,62 @@ impl OAuthFlow {
             access_token,
             refresh_token,
             expires_at,
-            client_id: Some(self.client_id.clone()),
-            client_secret: Some(self.client_secret.clone()),
+            client_id: Some(client_id),
+            client_secret: Some(client_secret),
         };
 
-        // Store credentials
         auth_manager.store_credentials(&credential)?;
 
-        println!("\n✅ Successfully authenticated via OAuth");
-
         Ok(credential)
     }
+}
 
-    /// Wait for the OAuth callback and extract the authorization code
-    fn wait_for_callback(
-        listener: TcpListener,
-        expected_csrf: CsrfToken,
-    ) -> Result<AuthorizationCode> {
-        for stream in listener.incoming() {
-            let mut stream = match stream {
-                Ok(s) => s,
-                Err(_) => continue,
-            };
+/// Wait for the OAuth callback and extract the authorization code
+fn wait_for_callback(listener: TcpListener, expected_csrf: CsrfToken) -> Result<AuthorizationCode> {
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(s) => s,
+            Err(_) => continue,
+        };
 
-            let mut reader = BufReader::new(&stream);
-            let mut request_line = String::new();
-            if reader.read_line(&mut request_line).is_err() {
-                continue;
-            }
+        let mut reader = BufReader::new(&stream);
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).is_err() {
+            continue;
+        }
 
-            // Parse the request URL
-            let Some(redirect_url) = request_line.split_whitespace().nth(1) else {
-                continue;
-            };
+        // Parse the request URL
+        let Some(redirect_url) = request_line.split_whitespace().nth(1) else {
+            continue;
+        };
 
-            let Ok(url) = url::Url::parse(&format!("http://localhost{}", redirect_url)) else {
-                continue;
-            };
+        let Ok(url) = url::Url::parse(&format!("http://localhost{}", redirect_url)) else {
+            continue;
+        };
 
-            let mut code = None;
-            let mut state = None;
+        let mut code = None;
+        let mut state = None;
 
-            for (key, value) in url.query_pairs() {
-                match key.as_ref() {
-                    "code" => code = Some(AuthorizationCode::new(value.to_string())),
-                    "state" => state = Some(CsrfToken::new(value.to_string())),
-                    _ => {}
-                }
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "code" => code = Some(AuthorizationCode::new(value.to_string())),
+                "state" => state = Some(CsrfToken::new(value.to_string())),
+                _ => {}
             }
+        }
 
-            // Verify CSRF token
-            if let Some(ref state) = state {
-                if state.secret() != expected_csrf.secret() {
-                    let response = "HTTP/1.1 400 Bad Request\r\n\r\nCSRF token mismatch";
-                    let _ = stream.write_all(response.as_bytes());
-                    continue;
-                }
+        // Verify CSRF token
+        if let Some(ref state) = state {
+            if state.secret() != expected_csrf.secret() {
+                tracing::warn!("OAuth callback rejected: CSRF token mismatch");
+                let response = "HTTP/1.1 400 Bad Request\r\n\r\nCSRF token mismatch";
+                let _ = stream.write_all(response.as_bytes());
+                continue;
             }
+        }
 
-            // Send success response
-            let response = r#"HTTP/1.1 200 OK
+        // Send success response
+        let response = r#"HTTP/1.1 200 OK
 Content-Type: text/html
 
 <!DOCTYPE html>
@@ -228,52 +323,18 @@ Content-Type: text/html
 <p>You can close this window and return to the terminal.</p>
 </body>
 </html>"#;
-            let _ = stream.write_all(response.as_bytes());
+        let _ = stream.write_all(response.as_bytes());
 
-            if let Some(code) = code {
-                return Ok(code);
-            }
+        if let Some(code) = code {
+            return Ok(code);
         }
-
-        anyhow::bail!("Callback server closed unexpectedly")
     }
 
-    /// Refresh an expired OAuth token
-    pub async fn refresh_token(
-        &self,
-        auth_manager: &AuthManager,
-        refresh_token: &str,
-    ) -> Result<Credential> {
-        let client = BasicClient::new(ClientId::new(self.client_id.clone()))
-            .set_client_secret(ClientSecret::new(self.client_secret.clone()))
-            .set_auth_uri(AuthUrl::new(BITBUCKET_AUTH_URL.to_string())?)
-            .set_token_uri(TokenUrl::new(BITBUCKET_TOKEN_URL.to_string())?);
-
-        let token_response = client
-            .exchange_refresh_token(&RefreshToken::new(refresh_token.to_string()))
-            .request_async(&async_http_client)
-            .await
-            .context("Failed to refresh token")?;
-
-        let access_token = token_response.access_token().secret().to_string();
-        let new_refresh_token = token_response
-            .refresh_token()
-            .map(|t| t.secret().to_string())
-            .unwrap_or_else(|| refresh_token.to_string());
-        let expires_at = token_response
-            .expires_in()
-            .map(|d| chrono::Utc::now().timestamp() + d.as_secs() as i64);
-
-        let credential = Credential::OAuth {
-            access_token,
-            refresh_token: Some(new_refresh_token),
-            expires_at,
-            client_id: Some(self.client_id.clone()),
-            client_secret: Some(self.client_secret.clone()),
-        };
-
-        auth_manager.store_credentials(&credential)?;
+    Err(Error::Auth(
+        "Callback server closed unexpectedly".to_string(),
+    ))
+}
 
-        Ok(credential)
-    }
+fn invalid_url(e: url::ParseError) -> Error {
+    Error::Auth(format!("Invalid OAuth URL: {}", e))
 }
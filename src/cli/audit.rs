@@ -0,0 +1,574 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Subcommand;
+use colored::Colorize;
+use futures::TryStreamExt;
+use serde::Serialize;
+use tabled::Tabled;
+
+use super::icons::Icon;
+use super::output::{Porcelain, ReportFormat};
+use super::pipeline_audit::{self, PIPELINES_FILE, PipelineCompliance, Policy};
+use super::secret_audit::{self, SecretFindingRow};
+use super::{UsageError, fanout, format, output};
+use crate::api::BitbucketClient;
+use crate::audit::{self, Entry};
+use crate::config::Config;
+use crate::error::Error;
+use crate::models::{BranchRestriction, Repository};
+
+#[derive(Subcommand)]
+pub enum AuditCommands {
+    /// Show the local log of changes made through the CLI
+    Show {
+        /// Number of entries, most recent last
+        #[arg(short, long, default_value = "25")]
+        limit: usize,
+
+        /// Show every entry
+        #[arg(long, conflicts_with = "limit")]
+        all: bool,
+
+        /// Only entries for this top-level command (e.g. pr)
+        #[arg(long, value_name = "NAME")]
+        command: Option<String>,
+
+        /// Check that no entry was changed or removed, and any signatures
+        #[arg(long)]
+        verify: bool,
+    },
+
+    /// Report repositories in a workspace (--workspace) whose main branch
+    /// doesn't require approvals and passing builds to merge
+    BranchRestrictions {
+        /// Approvals the main branch must require
+        #[arg(long, default_value = "1", value_name = "N")]
+        min_approvals: u32,
+
+        /// Passing builds the main branch must require
+        #[arg(long, default_value = "1", value_name = "N")]
+        min_builds: u32,
+
+        /// Include compliant repositories
+        #[arg(long)]
+        all: bool,
+    },
+
+    /// Check every repository's bitbucket-pipelines.yml in a workspace
+    /// (--workspace) against a policy of required steps, banned images and
+    /// a size limit
+    Pipelines {
+        /// TOML or YAML file with required_steps, banned_images and max_size
+        #[arg(long, value_name = "FILE")]
+        policy: PathBuf,
+
+        /// Include compliant repositories
+        #[arg(long)]
+        all: bool,
+    },
+
+    /// Report unsecured Pipelines variables with secret-like names, and
+    /// files in a workspace (--workspace) that code search finds
+    /// credentials in
+    Secrets {
+        /// Only check Pipelines variables
+        #[arg(long)]
+        no_code_search: bool,
+    },
+}
+
+/// How one repository's main branch is protected
+#[derive(Debug, Serialize)]
+struct Compliance {
+    repository: String,
+    main_branch: String,
+    required_approvals: u32,
+    required_builds: u32,
+    compliant: bool,
+    problems: Vec<String>,
+}
+
+impl Porcelain for Compliance {
+    fn porcelain(&self) -> String {
+        self.repository.clone()
+    }
+}
+
+#[derive(Tabled)]
+struct ComplianceRow {
+    #[tabled(rename = "REPOSITORY")]
+    repository: String,
+    #[tabled(rename = "BRANCH")]
+    branch: String,
+    #[tabled(rename = "APPROVALS")]
+    approvals: u32,
+    #[tabled(rename = "BUILDS")]
+    builds: u32,
+    #[tabled(rename = "PROBLEMS")]
+    problems: String,
+}
+
+impl From<&Compliance> for ComplianceRow {
+    fn from(c: &Compliance) -> Self {
+        Self {
+            repository: c.repository.clone(),
+            branch: c.main_branch.clone(),
+            approvals: c.required_approvals,
+            builds: c.required_builds,
+            problems: if c.compliant {
+                "-".to_string()
+            } else {
+                c.problems.join(", ")
+            },
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct PipelineComplianceRow {
+    #[tabled(rename = "REPOSITORY")]
+    repository: String,
+    #[tabled(rename = "SIZE")]
+    size: String,
+    #[tabled(rename = "PROBLEMS")]
+    problems: String,
+}
+
+impl From<&PipelineCompliance> for PipelineComplianceRow {
+    fn from(c: &PipelineCompliance) -> Self {
+        Self {
+            repository: c.repository.clone(),
+            size: c.size.map(|s| s.to_string()).unwrap_or("-".into()),
+            problems: if c.compliant {
+                "-".to_string()
+            } else {
+                c.problems.join(", ")
+            },
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct EntryRow {
+    #[tabled(rename = "TIME")]
+    time: String,
+    #[tabled(rename = "USER")]
+    user: String,
+    #[tabled(rename = "COMMAND")]
+    command: String,
+    #[tabled(rename = "REQUESTS")]
+    requests: String,
+    #[tabled(rename = "RESULT")]
+    result: String,
+}
+
+impl From<&Entry> for EntryRow {
+    fn from(entry: &Entry) -> Self {
+        let record = &entry.record;
+        Self {
+            time: format::date(&record.timestamp),
+            user: record
+                .user
+                .clone()
+                .or_else(|| record.local_user.clone())
+                .unwrap_or_default(),
+            command: record.args.join(" "),
+            requests: record
+                .requests
+                .iter()
+                .map(|r| {
+                    let status = r.status.map(|s| s.to_string()).unwrap_or("-".into());
+                    format!("{} {} {}", r.method, r.path, status)
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            result: match record.exit_code {
+                0 => "ok".to_string(),
+                code => format!("exit {}", code),
+            },
+        }
+    }
+}
+
+impl AuditCommands {
+    /// `workspace` is the global `--workspace`
+    pub async fn run(self, workspace: Option<String>) -> Result<()> {
+        match self {
+            AuditCommands::Show {
+                limit,
+                all,
+                command,
+                verify,
+            } => {
+                let path = audit::log_path()?;
+                let entries = audit::read(&path)?;
+
+                if verify {
+                    let allowed_signers = Config::load()?
+                        .audit
+                        .allowed_signers
+                        .map(|path| audit::expand_home(&path));
+                    let checked = audit::verify(&entries, allowed_signers.as_deref())?;
+                    output::note(format!(
+                        "{} Verified {} {} in {}",
+                        Icon::Ok.glyph().green(),
+                        checked,
+                        if checked == 1 { "entry" } else { "entries" },
+                        path.display()
+                    ));
+                }
+
+                let mut shown: Vec<Entry> = entries
+                    .into_iter()
+                    .filter(|e| command.as_deref().is_none_or(|c| e.record.command == c))
+                    .collect();
+                if !all && shown.len() > limit {
+                    shown.drain(..shown.len() - limit);
+                }
+
+                if output::print(&shown)? {
+                    return Ok(());
+                }
+
+                if shown.is_empty() {
+                    output::note("No audit log entries");
+                    return Ok(());
+                }
+
+                output::table(shown.iter().map(EntryRow::from).collect())?;
+
+                Ok(())
+            }
+
+            AuditCommands::BranchRestrictions {
+                min_approvals,
+                min_builds,
+                all,
+            } => {
+                let workspace = audited_workspace(workspace)?;
+                let client = BitbucketClient::from_stored().await?;
+
+                let repositories: Vec<Repository> =
+                    client.stream_repositories(&workspace).try_collect().await?;
+                let mut outcome = fanout::run(
+                    "Checking branch restrictions",
+                    repositories.iter().map(|r| r.full_name.clone()).collect(),
+                    fanout::DEFAULT_CONCURRENCY,
+                    |name: String| {
+                        let client = &client;
+                        let repository = repositories.iter().find(|r| r.full_name == name);
+                        let main_branch = repository
+                            .and_then(|r| r.mainbranch.as_ref())
+                            .map(|b| b.name.clone());
+                        async move {
+                            check_compliance(client, &name, main_branch, min_approvals, min_builds)
+                                .await
+                        }
+                    },
+                )
+                .await;
+
+                let mut report: Vec<Compliance> = outcome
+                    .succeeded
+                    .drain(..)
+                    .map(|(_, compliance)| compliance)
+                    .filter(|c| all || !c.compliant)
+                    .collect();
+                report.sort_by(|a, b| a.repository.cmp(&b.repository));
+
+                if !output::print(&report)? {
+                    if report.is_empty() && output::format() != ReportFormat::Csv {
+                        output::note(format!(
+                            "{} Every checked repository's main branch is protected",
+                            Icon::Ok.glyph().green()
+                        ));
+                    } else {
+                        output::table(report.iter().map(ComplianceRow::from).collect())?;
+                    }
+                }
+                outcome.finish("check", "repositories")?;
+
+                Ok(())
+            }
+
+            AuditCommands::Pipelines { policy, all } => {
+                let policy = Policy::load(&policy)?;
+                let workspace = audited_workspace(workspace)?;
+                let client = BitbucketClient::from_stored().await?;
+
+                let repositories: Vec<Repository> =
+                    client.stream_repositories(&workspace).try_collect().await?;
+                let mut outcome = fanout::run(
+                    "Checking pipeline files",
+                    repositories.iter().map(|r| r.full_name.clone()).collect(),
+                    fanout::DEFAULT_CONCURRENCY,
+                    |name: String| {
+                        let client = &client;
+                        let policy = &policy;
+                        let main_branch = repositories
+                            .iter()
+                            .find(|r| r.full_name == name)
+                            .and_then(|r| r.mainbranch.as_ref())
+                            .map(|b| b.name.clone());
+                        async move {
+                            let file = pipelines_file(client, &name, main_branch).await?;
+                            Ok(pipeline_audit::check(&name, file.as_deref(), policy))
+                        }
+                    },
+                )
+                .await;
+
+                let mut report: Vec<PipelineCompliance> = outcome
+                    .succeeded
+                    .drain(..)
+                    .map(|(_, compliance)| compliance)
+                    .filter(|c| all || !c.compliant)
+                    .collect();
+                report.sort_by(|a, b| a.repository.cmp(&b.repository));
+
+                if !output::print(&report)? {
+                    if report.is_empty() {
+                        output::note(format!(
+                            "{} Every checked repository's {} meets the policy",
+                            Icon::Ok.glyph().green(),
+                            PIPELINES_FILE
+                        ));
+                    } else {
+                        output::table(report.iter().map(PipelineComplianceRow::from).collect())?;
+                    }
+                }
+                outcome.finish("check", "repositories")?;
+
+                Ok(())
+            }
+
+            AuditCommands::Secrets { no_code_search } => {
+                let workspace = audited_workspace(workspace)?;
+                let client = BitbucketClient::from_stored().await?;
+
+                let mut report = secret_audit::unsecured_variables(
+                    None,
+                    &client.list_workspace_variables(&workspace).await?,
+                );
+                let repositories: Vec<Repository> =
+                    client.stream_repositories(&workspace).try_collect().await?;
+                let mut outcome = fanout::run(
+                    "Checking Pipelines variables",
+                    repositories.iter().map(|r| r.full_name.clone()).collect(),
+                    fanout::DEFAULT_CONCURRENCY,
+                    |name: String| {
+                        let client = &client;
+                        async move {
+                            let (workspace, repo_slug) =
+                                name.split_once('/').unwrap_or((&name, ""));
+                            let variables = client
+                                .list_repository_variables(workspace, repo_slug)
+                                .await?;
+                            Ok(secret_audit::unsecured_variables(Some(&name), &variables))
+                        }
+                    },
+                )
+                .await;
+                report.extend(outcome.succeeded.drain(..).flat_map(|(_, f)| f));
+
+                let mut capped = Vec::new();
+                if !no_code_search {
+                    match secret_audit::scan_code(&client, &workspace).await {
+                        Ok(scan) => {
+                            report.extend(scan.findings);
+                            capped = scan.capped;
+                        }
+                        // Code search is off until the workspace turns it on
+                        Err(e) if matches!(e.status(), Some(s) if s == 403 || s == 404) => {
+                            output::note(format!(
+                                "{} Skipped files: code search isn't available in {} ({})",
+                                Icon::Warning.glyph().yellow(),
+                                workspace,
+                                e
+                            ));
+                        }
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+                report.sort_by(|a, b| {
+                    (a.kind, &a.repository, &a.location).cmp(&(b.kind, &b.repository, &b.location))
+                });
+
+                if !output::print(&report)? {
+                    if report.is_empty() {
+                        output::note(format!(
+                            "{} No secrets found in the open",
+                            Icon::Ok.glyph().green()
+                        ));
+                    } else {
+                        output::table(report.iter().map(SecretFindingRow::from).collect())?;
+                    }
+                }
+                if !capped.is_empty() {
+                    output::note(format!(
+                        "{} Looked at only the first {} files for: {}; there may be more",
+                        Icon::Warning.glyph().yellow(),
+                        secret_audit::FILES_PER_RULE,
+                        capped.join(", ")
+                    ));
+                }
+                outcome.finish("check", "repositories")?;
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The global `--workspace`, else the configured default
+fn audited_workspace(workspace: Option<String>) -> Result<String> {
+    Ok(workspace
+        .or_else(|| {
+            Config::load()
+                .ok()
+                .and_then(|c| c.default_workspace().map(str::to_string))
+        })
+        .ok_or_else(|| UsageError("Pass --workspace".to_string()))?)
+}
+
+/// A repository's pipeline file on its main branch, or `None` if it has none
+async fn pipelines_file(
+    client: &BitbucketClient,
+    repository: &str,
+    main_branch: Option<String>,
+) -> Result<Option<String>> {
+    let (workspace, repo_slug) = repository.split_once('/').unwrap_or((repository, ""));
+    let main_branch = match main_branch {
+        Some(name) => name,
+        // An empty repository has no main branch, so no pipeline file either
+        None => match client.get_main_branch(workspace, repo_slug).await {
+            Ok(branch) => branch.name,
+            Err(Error::NotFound(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        },
+    };
+    match client
+        .get_source_file(workspace, repo_slug, &main_branch, PIPELINES_FILE)
+        .await
+    {
+        Ok(text) => Ok(Some(text)),
+        Err(Error::NotFound(_)) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Find the approvals and passing builds required to merge into a
+/// repository's main branch
+async fn check_compliance(
+    client: &BitbucketClient,
+    repository: &str,
+    main_branch: Option<String>,
+    min_approvals: u32,
+    min_builds: u32,
+) -> Result<Compliance> {
+    let (workspace, repo_slug) = repository.split_once('/').unwrap_or((repository, ""));
+    let main_branch = match main_branch {
+        Some(name) => name,
+        None => client.get_main_branch(workspace, repo_slug).await?.name,
+    };
+    let restrictions = client
+        .list_branch_restrictions(workspace, repo_slug)
+        .await?;
+
+    // Only look up the branching model if a rule refers to it
+    let model_branches = if restrictions
+        .iter()
+        .any(|r| r.branch_match_kind.as_deref() == Some("branching_model"))
+    {
+        let model = client.get_branching_model(workspace, repo_slug).await?;
+        [
+            ("development", model.development),
+            ("production", model.production),
+        ]
+        .into_iter()
+        .filter_map(|(kind, branch)| {
+            let name = branch?.branch.map(|b| b.name)?;
+            Some((kind, name))
+        })
+        .collect()
+    } else {
+        Vec::new()
+    };
+    let applies = |restriction: &BranchRestriction| match restriction.branch_match_kind.as_deref() {
+        Some("branching_model") => model_branches.iter().any(|(kind, name)| {
+            restriction.branch_type.as_deref() == Some(*kind) && *name == main_branch
+        }),
+        _ => restriction
+            .pattern
+            .as_deref()
+            .is_some_and(|pattern| glob_match(pattern, &main_branch)),
+    };
+    let required = |kinds: &[&str]| {
+        restrictions
+            .iter()
+            .filter(|r| kinds.contains(&r.kind.as_str()) && applies(r))
+            .filter_map(|r| r.value)
+            .max()
+            .unwrap_or(0)
+    };
+
+    let required_approvals = required(&[
+        "require_approvals_to_merge",
+        "require_default_reviewer_approvals_to_merge",
+    ]);
+    let required_builds = required(&["require_passing_builds_to_merge"]);
+
+    let mut problems = Vec::new();
+    if required_approvals < min_approvals {
+        problems.push(format!(
+            "requires {} of {} approvals",
+            required_approvals, min_approvals
+        ));
+    }
+    if required_builds < min_builds {
+        problems.push(format!(
+            "requires {} of {} passing builds",
+            required_builds, min_builds
+        ));
+    }
+
+    Ok(Compliance {
+        repository: repository.to_string(),
+        main_branch,
+        required_approvals,
+        required_builds,
+        compliant: problems.is_empty(),
+        problems,
+    })
+}
+
+/// Match a branch restriction pattern, where `*` stands for any characters
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            let Some(remainder) = name.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=remainder.len())
+                .filter(|i| remainder.is_char_boundary(*i))
+                .any(|i| glob_match(rest, &remainder[i..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_match_like_bitbucket() {
+        assert!(glob_match("main", "main"));
+        assert!(!glob_match("main", "maintenance"));
+        assert!(glob_match("*", "main"));
+        assert!(glob_match("release/*", "release/1.2"));
+        assert!(glob_match("m*n", "main"));
+        assert!(!glob_match("release/*", "main"));
+    }
+}
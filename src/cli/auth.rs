@@ -1,9 +1,11 @@
 use anyhow::{Context, Result};
 use clap::Subcommand;
 use colored::Colorize;
-use dialoguer::{Input, Select};
+use dialoguer::{Input, Password, Select};
 
-use crate::auth::{ApiKeyAuth, AuthManager, OAuthFlow};
+use super::icons::Icon;
+use super::output;
+use crate::auth::{ApiKeyAuth, AuthManager, Credential, OAuthFlow};
 use crate::config::Config;
 
 #[derive(Subcommand)]
@@ -25,6 +27,16 @@ pub enum AuthCommands {
         /// OAuth Client Secret (for OAuth authentication)
         #[arg(long, env = "BITBUCKET_CLIENT_SECRET")]
         client_secret: Option<String>,
+
+        /// Port for the OAuth callback server, instead of `[oauth]
+        /// callback_ports` or the built-in 8080, 3000, 8888 and 9000
+        #[arg(
+            long,
+            value_name = "PORT",
+            conflicts_with = "api_key",
+            value_parser = clap::value_parser!(u16).range(1..)
+        )]
+        callback_port: Option<u16>,
     },
 
     /// Remove stored credentials
@@ -32,6 +44,20 @@ pub enum AuthCommands {
 
     /// Show authentication status
     Status,
+
+    /// Exchange the stored OAuth refresh token for a new access token
+    Refresh,
+
+    /// Set the OAuth consumer used to refresh the stored login
+    SetOauthApp {
+        /// OAuth Client ID (Key)
+        #[arg(long, env = "BITBUCKET_CLIENT_ID")]
+        client_id: Option<String>,
+
+        /// OAuth Client Secret
+        #[arg(long, env = "BITBUCKET_CLIENT_SECRET")]
+        client_secret: Option<String>,
+    },
 }
 
 impl AuthCommands {
@@ -42,17 +68,27 @@ impl AuthCommands {
                 api_key,
                 client_id,
                 client_secret,
+                callback_port,
             } => {
                 let auth_manager = AuthManager::new()?;
+                let callback_ports = match callback_port {
+                    Some(port) => vec![port],
+                    None => Config::load()?.oauth.callback_ports,
+                };
+                let callback_ports = if callback_ports.is_empty() {
+                    OAuthFlow::CALLBACK_PORTS.to_vec()
+                } else {
+                    callback_ports
+                };
 
                 let use_api_key = resolve_auth_method(
                     oauth,
                     api_key,
-                    client_id.is_some() || client_secret.is_some(),
+                    client_id.is_some() || client_secret.is_some() || callback_port.is_some(),
                 )?;
 
                 if use_api_key {
-                    ApiKeyAuth::authenticate(&auth_manager).await?;
+                    login_with_api_key(&auth_manager).await?;
                     return Ok(());
                 }
 
@@ -70,21 +106,25 @@ impl AuthCommands {
                     .or_else(|| stored_consumer.as_ref().map(|(id, _)| id.clone()))
                     .or_else(|| {
                         println!();
-                        println!("📋 OAuth Consumer Setup Required");
+                        println!("{} OAuth Consumer Setup Required", Icon::Task);
                         println!();
                         println!("To use OAuth authentication, create an OAuth consumer in Bitbucket:");
                         println!("1. Go to: https://bitbucket.org/[workspace]/workspace/settings/oauth-consumers/new");
-                        println!("2. Set callback URL to ONE of these (pick any available port):");
-                        println!("   • http://127.0.0.1:8080/callback");
-                        println!("   • http://127.0.0.1:3000/callback");
-                        println!("   • http://127.0.0.1:8888/callback");
-                        println!("   • http://127.0.0.1:9000/callback");
+                        if let [port] = callback_ports[..] {
+                            println!("2. Set callback URL to:");
+                            println!("   • http://127.0.0.1:{}/callback", port);
+                        } else {
+                            println!("2. Set callback URL to ONE of these (pick any available port):");
+                            for port in &callback_ports {
+                                println!("   • http://127.0.0.1:{}/callback", port);
+                            }
+                        }
                         println!("3. Select required permissions:");
-                        println!("   ✓ Account (Read)");
-                        println!("   ✓ Repositories (Read)");
-                        println!("   ✓ Pull requests (Read, Write)");
-                        println!("   ✓ Issues (Read, Write)");
-                        println!("   ✓ Pipelines (Read, Write)");
+                        println!("   {} Account (Read)", Icon::Ok);
+                        println!("   {} Repositories (Read)", Icon::Ok);
+                        println!("   {} Pull requests (Read, Write)", Icon::Ok);
+                        println!("   {} Issues (Read, Write)", Icon::Ok);
+                        println!("   {} Pipelines (Read, Write)", Icon::Ok);
                         println!("4. Copy the Key (Client ID) and Secret");
                         println!();
 
@@ -105,8 +145,9 @@ impl AuthCommands {
                     })
                     .ok_or_else(|| anyhow::anyhow!("OAuth Client Secret is required"))?;
 
-                let oauth = OAuthFlow::new(client_id, client_secret);
-                oauth.authenticate(&auth_manager).await?;
+                let oauth =
+                    OAuthFlow::new(client_id, client_secret).with_callback_ports(callback_ports);
+                login_with_oauth(&oauth, &auth_manager).await?;
 
                 Ok(())
             }
@@ -115,11 +156,11 @@ impl AuthCommands {
                 let auth_manager = AuthManager::new()?;
                 auth_manager.clear_credentials()?;
 
-                let mut config = Config::load()?;
+                let mut config = Config::load_file()?;
                 config.clear_auth();
                 config.save()?;
 
-                println!("{} Logged out successfully", "✓".green());
+                output::success("Logged out successfully");
                 Ok(())
             }
 
@@ -128,7 +169,7 @@ impl AuthCommands {
                 let config = Config::load()?;
 
                 if auth_manager.is_authenticated() {
-                    println!("{} Authenticated", "✓".green());
+                    output::success("Authenticated");
 
                     if let Ok(Some(credential)) = auth_manager.get_credentials() {
                         println!("  {} {}", "Method:".dimmed(), credential.type_name());
@@ -140,6 +181,10 @@ impl AuthCommands {
                             println!("  {} {}", "Username:".dimmed(), username);
                         }
 
+                        if let Some((client_id, _)) = credential.oauth_consumer_credentials() {
+                            println!("  {} {}", "OAuth app:".dimmed(), client_id);
+                        }
+
                         if credential.needs_refresh() {
                             println!(
                                 "  {} {}",
@@ -165,25 +210,231 @@ impl AuthCommands {
                                 }
                             }
                             Err(e) => {
-                                println!("{} Credentials may be invalid: {}", "⚠".yellow(), e);
+                                println!(
+                                    "{} Credentials may be invalid: {}",
+                                    Icon::Warning.glyph().yellow(),
+                                    e
+                                );
                             }
                         },
                         Err(e) => {
-                            println!("{} Failed to create client: {}", "✗".red(), e);
+                            println!(
+                                "{} Failed to create client: {}",
+                                Icon::Error.glyph().red(),
+                                e
+                            );
                         }
                     }
                 } else {
-                    println!("{} Not authenticated", "✗".red());
+                    println!("{} Not authenticated", Icon::Error.glyph().red());
                     println!();
                     println!("Run {} to authenticate", "bitbucket auth login".cyan());
                 }
 
                 Ok(())
             }
+
+            AuthCommands::Refresh => refresh().await,
+
+            AuthCommands::SetOauthApp {
+                client_id,
+                client_secret,
+            } => set_oauth_app(client_id, client_secret),
         }
     }
 }
 
+/// The stored OAuth login, refusing when the environment overrides it
+fn stored_oauth_login(auth_manager: &AuthManager) -> Result<Credential> {
+    if Credential::from_env().is_some() {
+        anyhow::bail!(
+            "Credentials are set in the environment; unset them to manage the stored login"
+        );
+    }
+    match auth_manager.stored_credentials()? {
+        Some(credential @ Credential::OAuth { .. }) => Ok(credential),
+        Some(_) => anyhow::bail!("The stored login is an API key; there is no OAuth app to use"),
+        None => anyhow::bail!(crate::error::Error::NotAuthenticated),
+    }
+}
+
+async fn refresh() -> Result<()> {
+    let auth_manager = AuthManager::new()?;
+    let credential = stored_oauth_login(&auth_manager)?;
+    let Credential::OAuth {
+        refresh_token: Some(refresh_token),
+        ..
+    } = &credential
+    else {
+        anyhow::bail!("The stored login has no refresh token; run 'bitbucket auth login --oauth'");
+    };
+    let Some((client_id, client_secret)) = credential.oauth_consumer_credentials() else {
+        anyhow::bail!(
+            "No OAuth app is stored with the login; run 'bitbucket auth set-oauth-app' first"
+        );
+    };
+
+    let flow = OAuthFlow::new(client_id.to_string(), client_secret.to_string());
+    let refreshed = flow
+        .refresh_token(&auth_manager, refresh_token)
+        .await
+        .context("Failed to refresh the access token")?;
+
+    match refreshed {
+        Credential::OAuth {
+            expires_at: Some(expires_at),
+            ..
+        } => output::success(format!(
+            "Access token refreshed, expires {}",
+            chrono::DateTime::from_timestamp(expires_at, 0)
+                .map(|at| at
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string())
+                .unwrap_or_else(|| expires_at.to_string())
+        )),
+        _ => output::success("Access token refreshed"),
+    }
+    Ok(())
+}
+
+fn set_oauth_app(client_id: Option<String>, client_secret: Option<String>) -> Result<()> {
+    let auth_manager = AuthManager::new()?;
+    let Credential::OAuth {
+        access_token,
+        refresh_token,
+        expires_at,
+        client_id: current_id,
+        ..
+    } = stored_oauth_login(&auth_manager)?
+    else {
+        unreachable!("stored_oauth_login returns OAuth logins only");
+    };
+
+    let client_id = match client_id {
+        Some(id) => id,
+        None => {
+            let mut input = Input::<String>::new().with_prompt("OAuth Client ID (Key)");
+            if let Some(current) = current_id {
+                input = input.default(current);
+            }
+            input.interact_text().context("Failed to read client ID")?
+        }
+    };
+    let client_secret = match client_secret {
+        Some(secret) => secret,
+        None => Password::new()
+            .with_prompt("OAuth Client Secret")
+            .interact()
+            .context("Failed to read client secret")?,
+    };
+
+    auth_manager.store_credentials(&Credential::OAuth {
+        access_token,
+        refresh_token,
+        expires_at,
+        client_id: Some(client_id.trim().to_string()),
+        client_secret: Some(client_secret.trim().to_string()),
+    })?;
+    output::success("OAuth app updated; refreshes will use it");
+    Ok(())
+}
+
+/// Run the interactive API key sign-in
+async fn login_with_api_key(auth_manager: &AuthManager) -> Result<()> {
+    println!("\n{} Bitbucket API Key Authentication", Icon::Private);
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    println!();
+    println!(
+        "{} Note: OAuth 2.0 is the preferred authentication method.",
+        Icon::Warning
+    );
+    println!("   API keys are provided for automation/CI scenarios.");
+    println!();
+    println!("To create an API key (HTTP access token):");
+    println!("1. Go to Bitbucket Settings → Personal settings");
+    println!("2. Click 'HTTP access tokens' under 'Access management'");
+    println!("3. Click 'Create token'");
+    println!("4. Give it a label and select required permissions");
+    println!();
+
+    let username: String = Input::new()
+        .with_prompt("Bitbucket username")
+        .interact_text()
+        .context("Failed to read username")?;
+
+    let api_key: String = Password::new()
+        .with_prompt("API key (HTTP access token)")
+        .interact()
+        .context("Failed to read API key")?;
+
+    // Check for common Atlassian token prefixes
+    let trimmed = api_key.trim();
+    if !trimmed.is_empty() && !ApiKeyAuth::has_expected_prefix(trimmed) {
+        println!(
+            "{} Warning: Token doesn't start with expected prefix (ATATT or ATCTT)",
+            Icon::Warning
+        );
+        println!("   This might not be a valid Bitbucket API token.");
+        println!(
+            "   Token starts with: {}",
+            &trimmed.chars().take(5).collect::<String>()
+        );
+    }
+
+    println!("Validating credentials with Bitbucket API...");
+    ApiKeyAuth::login(auth_manager, &username, &api_key).await?;
+
+    println!(
+        "\n{} Successfully authenticated as {}",
+        Icon::Ok.glyph().green(),
+        username
+    );
+    println!(
+        "{} Tip: Use 'bitbucket auth login --oauth' for a better experience",
+        Icon::Proposal
+    );
+
+    Ok(())
+}
+
+/// Run the interactive OAuth 2.0 browser sign-in
+async fn login_with_oauth(oauth: &OAuthFlow, auth_manager: &AuthManager) -> Result<()> {
+    println!("\n{} Bitbucket OAuth Authentication", Icon::Private);
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    println!();
+
+    let pending = oauth.start()?;
+
+    println!("Callback server listening on port {}", pending.port());
+    println!("   Make sure your OAuth consumer callback URL is set to:");
+    println!("   {}", pending.redirect_url());
+    println!();
+
+    println!("Opening browser for authentication...");
+    println!();
+
+    // Try to open browser
+    if open::that(pending.authorize_url()).is_err() {
+        println!("Could not open browser automatically.");
+        println!("Please open this URL in your browser:");
+        println!();
+        println!("  {}", pending.authorize_url());
+        println!();
+    }
+
+    println!("Waiting for authorization...");
+
+    pending.complete(auth_manager).await?;
+
+    println!(
+        "\n{} Successfully authenticated via OAuth",
+        Icon::Ok.glyph().green()
+    );
+
+    Ok(())
+}
+
 /// Resolve which authentication method to use.
 ///
 /// Returns `true` for API key, `false` for OAuth 2.0.
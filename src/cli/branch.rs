@@ -0,0 +1,210 @@
+//! Branch naming conventions
+//!
+//! `[branch]` in the config sets the prefixes a name must start with and a
+//! pattern it must match, such as a ticket key. Without `prefixes`, those of
+//! the repository's branching model (`feature/`, `bugfix/`, ...) apply.
+//! `branch new` refuses names that break them; `pr create` only warns.
+
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use colored::Colorize;
+use regex::Regex;
+
+use super::icons::Icon;
+use super::{UsageError, git, output};
+use crate::api::BitbucketClient;
+use crate::config::{BranchConfig, Config};
+
+#[derive(Subcommand)]
+pub enum BranchCommands {
+    /// Create a local branch after checking its name against the conventions
+    New {
+        /// Branch name, e.g. feature/ENG-12-carry-bit
+        name: String,
+
+        /// Start the branch here instead of at HEAD
+        #[arg(long, value_name = "REF")]
+        from: Option<String>,
+
+        /// Push it to origin and track it
+        #[arg(long)]
+        push: bool,
+
+        /// Create it even if the name breaks the conventions
+        #[arg(long)]
+        no_verify: bool,
+    },
+}
+
+impl BranchCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            BranchCommands::New {
+                name,
+                from,
+                push,
+                no_verify,
+            } => {
+                if git::root().is_none() {
+                    anyhow::bail!(UsageError("Not in a git checkout".to_string()));
+                }
+                git::run(&["check-ref-format", "--branch", &name])
+                    .map_err(|_| UsageError(format!("'{}' is not a valid branch name", name)))?;
+                if git::has_branch(&name) {
+                    anyhow::bail!(UsageError(format!("Branch '{}' already exists", name)));
+                }
+
+                if !no_verify {
+                    let conventions = Conventions::for_origin().await?;
+                    let problems = conventions.check(&name);
+                    if !problems.is_empty() {
+                        let list: Vec<String> =
+                            problems.iter().map(|p| format!("  • {}", p)).collect();
+                        anyhow::bail!(UsageError(format!(
+                            "Branch name breaks the conventions:\n{}\nRename it, or pass --no-verify to create it anyway",
+                            list.join("\n")
+                        )));
+                    }
+                }
+
+                let mut args = vec!["checkout", "--quiet", "-b", name.as_str()];
+                args.extend(from.as_deref());
+                git::run(&args)?;
+                output::success(format!("Created and switched to {}", name));
+
+                if push {
+                    git::run(&["push", "--quiet", "--set-upstream", "origin", &name])?;
+                    output::success(format!("Pushed {} to origin", name));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// What branch names must look like
+#[derive(Debug, Default)]
+pub struct Conventions {
+    /// A name must start with one of these, unless there are none
+    pub prefixes: Vec<String>,
+    pub ticket: Option<Regex>,
+    /// Names exempt from the rules: the branching model's development and
+    /// production branches
+    pub exempt: Vec<String>,
+}
+
+impl Conventions {
+    /// `[branch]` from the config, with the branching model of the
+    /// repository `origin` points at filling in the prefixes
+    pub async fn for_origin() -> Result<Self> {
+        let config = Config::load()?.branch;
+        if let Some((workspace, slug)) = git::origin_repo()
+            && let Ok(client) = BitbucketClient::from_stored().await
+        {
+            return Self::for_repository(&config, &client, &workspace, &slug).await;
+        }
+        Self::from_config(&config)
+    }
+
+    /// `config`, with the repository's branching model filling in the
+    /// prefixes; a model that can't be fetched adds nothing
+    pub async fn for_repository(
+        config: &BranchConfig,
+        client: &BitbucketClient,
+        workspace: &str,
+        repo_slug: &str,
+    ) -> Result<Self> {
+        let mut conventions = Self::from_config(config)?;
+        match client.get_branching_model(workspace, repo_slug).await {
+            Ok(model) => {
+                if conventions.prefixes.is_empty() {
+                    conventions.prefixes =
+                        model.branch_types.into_iter().map(|t| t.prefix).collect();
+                }
+                conventions.exempt = [model.development, model.production]
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|b| b.branch.map(|b| b.name).or(b.name))
+                    .collect();
+            }
+            Err(e) => tracing::debug!("no branching model for {}/{}: {}", workspace, repo_slug, e),
+        }
+        Ok(conventions)
+    }
+
+    fn from_config(config: &BranchConfig) -> Result<Self> {
+        let ticket = config
+            .ticket_pattern
+            .as_deref()
+            .map(|pattern| {
+                Regex::new(pattern)
+                    .with_context(|| format!("Invalid [branch] ticket_pattern '{}'", pattern))
+            })
+            .transpose()?;
+        Ok(Self {
+            prefixes: config.prefixes.clone(),
+            ticket,
+            exempt: Vec::new(),
+        })
+    }
+
+    /// What's wrong with `name`; empty when it follows the conventions
+    pub fn check(&self, name: &str) -> Vec<String> {
+        if self.exempt.iter().any(|e| e == name) {
+            return Vec::new();
+        }
+        let mut problems = Vec::new();
+        if !self.prefixes.is_empty() && !self.prefixes.iter().any(|p| name.starts_with(p)) {
+            problems.push(format!(
+                "'{}' doesn't start with one of: {}",
+                name,
+                self.prefixes.join(", ")
+            ));
+        }
+        if let Some(ticket) = &self.ticket
+            && !ticket.is_match(name)
+        {
+            problems.push(format!(
+                "'{}' has no ticket reference matching '{}'",
+                name,
+                ticket.as_str()
+            ));
+        }
+        problems
+    }
+}
+
+/// Warn about each way `name` breaks the conventions
+pub fn warn(name: &str, conventions: &Conventions) {
+    for problem in conventions.check(name) {
+        output::note(format!(
+            "{} Branch {}",
+            Icon::Warning.glyph().yellow(),
+            problem
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_checked_against_prefixes_and_tickets() {
+        let conventions = Conventions {
+            prefixes: vec!["feature/".into(), "bugfix/".into()],
+            ticket: Some(Regex::new(r"[A-Z]+-\d+").unwrap()),
+            exempt: vec!["main".into()],
+        };
+        assert!(conventions.check("feature/ENG-12-carry").is_empty());
+        assert!(conventions.check("main").is_empty());
+        assert_eq!(
+            conventions.check("carry-bit"),
+            [
+                "'carry-bit' doesn't start with one of: feature/, bugfix/",
+                "'carry-bit' has no ticket reference matching '[A-Z]+-\\d+'",
+            ]
+        );
+        assert!(Conventions::default().check("anything").is_empty());
+    }
+}
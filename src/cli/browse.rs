@@ -0,0 +1,174 @@
+use anyhow::{Context, Result};
+use clap::Args;
+
+use super::{git, output};
+
+/// Base URL of the Bitbucket web interface
+pub const WEB_URL: &str = "https://bitbucket.org";
+
+#[derive(Args)]
+pub struct BrowseArgs {
+    /// File to open, relative to the current directory, optionally with a
+    /// line number (e.g. src/main.rs:42)
+    #[arg(conflicts_with_all = ["pr", "pipelines", "settings"])]
+    path: Option<String>,
+
+    /// Open a branch: `--branch=NAME`, or the current branch with a bare
+    /// `--branch`, so a path after it isn't taken for the name
+    #[arg(
+        long,
+        value_name = "NAME",
+        require_equals = true,
+        conflicts_with = "commit"
+    )]
+    branch: Option<Option<String>>,
+
+    /// Open a commit
+    #[arg(long, value_name = "HASH")]
+    commit: Option<String>,
+
+    /// Open a pull request
+    #[arg(long, value_name = "ID", conflicts_with_all = ["branch", "commit", "pipelines", "settings"])]
+    pr: Option<u64>,
+
+    /// Open the pipelines page
+    #[arg(long, conflicts_with_all = ["branch", "commit", "settings"])]
+    pipelines: bool,
+
+    /// Open the repository settings
+    #[arg(long, conflicts_with_all = ["branch", "commit"])]
+    settings: bool,
+
+    /// Print the URL instead of opening a browser
+    #[arg(long)]
+    no_browser: bool,
+}
+
+/// A page of a repository on the web
+#[derive(Debug, PartialEq)]
+enum Page {
+    Root,
+    Branch(String),
+    Commit(String),
+    PullRequest(u64),
+    Pipelines,
+    Settings,
+    File {
+        rev: String,
+        path: String,
+        line: Option<u32>,
+    },
+}
+
+impl BrowseArgs {
+    /// Open the page; `repo` is the global `--repo`, otherwise the repository
+    /// is taken from the `origin` remote of the current checkout
+    pub fn run(self, repo: Option<String>) -> Result<()> {
+        let (workspace, slug) = git::repo_or_origin(repo)?;
+
+        let url = url(&workspace, &slug, &self.page()?);
+        if self.no_browser || output::is_quiet() {
+            println!("{}", url);
+            return Ok(());
+        }
+
+        tracing::debug!(%url, "opening browser");
+        open::that(&url).with_context(|| format!("Failed to open {}", url))?;
+        output::success(format!("Opened {}", url));
+        Ok(())
+    }
+
+    fn page(&self) -> Result<Page> {
+        if let Some(id) = self.pr {
+            return Ok(Page::PullRequest(id));
+        }
+        if self.pipelines {
+            return Ok(Page::Pipelines);
+        }
+        if self.settings {
+            return Ok(Page::Settings);
+        }
+
+        let branch = match &self.branch {
+            Some(Some(name)) => Some(name.clone()),
+            Some(None) => Some(
+                git::current_branch()
+                    .context("Not on a branch. Pass the branch name: --branch=<NAME>")?,
+            ),
+            None => None,
+        };
+
+        let Some(target) = &self.path else {
+            return Ok(match (branch, &self.commit) {
+                (Some(branch), _) => Page::Branch(branch),
+                (None, Some(commit)) => Page::Commit(commit.clone()),
+                (None, None) => Page::Root,
+            });
+        };
+
+        let (path, line) = split_line(target);
+        let rev = branch
+            .or_else(|| self.commit.clone())
+            .or_else(git::current_branch)
+            .or_else(git::head_commit)
+            .unwrap_or_else(|| "HEAD".to_string());
+        let prefix = git::prefix().unwrap_or_default();
+
+        Ok(Page::File {
+            rev,
+            path: format!("{}{}", prefix, path.trim_start_matches("./")),
+            line,
+        })
+    }
+}
+
+/// Split `path:42` into the path and line number
+fn split_line(target: &str) -> (&str, Option<u32>) {
+    match target.rsplit_once(':') {
+        Some((path, line)) => match line.parse() {
+            Ok(line) => (path, Some(line)),
+            Err(_) => (target, None),
+        },
+        None => (target, None),
+    }
+}
+
+fn url(workspace: &str, slug: &str, page: &Page) -> String {
+    let root = format!("{}/{}/{}", WEB_URL, workspace, slug);
+    match page {
+        Page::Root => root,
+        Page::Branch(name) => format!("{}/branch/{}", root, name),
+        Page::Commit(hash) => format!("{}/commits/{}", root, hash),
+        Page::PullRequest(id) => format!("{}/pull-requests/{}", root, id),
+        Page::Pipelines => format!("{}/pipelines", root),
+        Page::Settings => format!("{}/admin", root),
+        Page::File { rev, path, line } => {
+            let anchor = line.map(|l| format!("#lines-{}", l)).unwrap_or_default();
+            format!("{}/src/{}/{}{}", root, rev, path, anchor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_map_to_web_urls() {
+        let file = Page::File {
+            rev: "main".into(),
+            path: "src/main.rs".into(),
+            line: Some(42),
+        };
+        assert_eq!(
+            url("acme", "engine", &file),
+            "https://bitbucket.org/acme/engine/src/main/src/main.rs#lines-42"
+        );
+        assert_eq!(
+            url("acme", "engine", &Page::PullRequest(7)),
+            "https://bitbucket.org/acme/engine/pull-requests/7"
+        );
+        assert_eq!(split_line("src/main.rs:42"), ("src/main.rs", Some(42)));
+        assert_eq!(split_line("README.md"), ("README.md", None));
+    }
+}
@@ -0,0 +1,467 @@
+//! Cached workspace, repository, branch and member names
+//!
+//! Shell completions, pickers and the TUI read names from
+//! `$XDG_CACHE_HOME/bitbucket-cli/metadata-<key>.json` instead of asking the
+//! API every time, with one file for each profile and signed-in account.
+//! Reading a cache older than a day starts `bitbucket cache refresh` in the
+//! background, so the next read is fresh; only a refresh writes the file.
+//! Branches are only fetched for repositories something has asked about,
+//! since a workspace can have thousands.
+
+use std::collections::BTreeMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use clap::{Subcommand, ValueEnum};
+use colored::Colorize;
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+use super::output::Porcelain;
+use super::{UsageError, fanout, format, output};
+use crate::api::{self, BitbucketClient, recording};
+use crate::auth::AuthManager;
+use crate::config::{self, Config, xdg};
+use crate::models::User;
+
+/// The global `--workspace`, which a refresh caches even if you aren't a
+/// member of it
+static WORKSPACE: OnceLock<String> = OnceLock::new();
+
+/// Cache names for `workspace` too, and hand it to background refreshes
+pub fn select_workspace(workspace: &str) {
+    let _ = WORKSPACE.set(workspace.to_string());
+}
+
+/// Age after which a read starts a background refresh
+const MAX_AGE: Duration = Duration::hours(24);
+
+/// Age after which a lock is assumed left behind by a refresh that died
+const LOCK_TIMEOUT: Duration = Duration::minutes(10);
+
+#[derive(Subcommand)]
+pub enum CacheCommands {
+    /// Fetch workspace, repository, member and branch names now
+    Refresh {
+        /// Also cache the branches of this repository (repeatable)
+        #[arg(long = "branches-of", value_name = "WORKSPACE/REPO")]
+        branches_of: Vec<String>,
+    },
+
+    /// Show what the cache holds and how old it is
+    Show,
+
+    /// Print cached names one per line, for shell completion scripts
+    Names {
+        /// Which names to print
+        #[arg(value_enum)]
+        kind: NameKind,
+
+        /// Workspace for repos and members, or workspace/repo for branches
+        scope: Option<String>,
+    },
+
+    /// Delete the cache
+    Clear,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum NameKind {
+    Workspaces,
+    Repos,
+    Branches,
+    Members,
+}
+
+/// Names cached for completions and pickers
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Metadata {
+    pub refreshed_at: Option<DateTime<Utc>>,
+    /// Slugs of the workspaces you belong to
+    #[serde(default)]
+    pub workspaces: Vec<String>,
+    /// Repository slugs by workspace
+    #[serde(default)]
+    pub repositories: BTreeMap<String, Vec<String>>,
+    /// Members by workspace
+    #[serde(default)]
+    pub members: BTreeMap<String, Vec<User>>,
+    /// Branch names by `workspace/repo`, for repositories asked about
+    #[serde(default)]
+    pub branches: BTreeMap<String, Vec<String>>,
+}
+
+impl Metadata {
+    /// The cached names, starting a background refresh if they're stale
+    pub fn current() -> Self {
+        let metadata = Self::load();
+        if metadata.is_stale() {
+            spawn_refresh(None);
+        }
+        metadata
+    }
+
+    /// The cached names as they are, empty if nothing is cached
+    pub fn load() -> Self {
+        let Ok(path) = path() else {
+            return Self::default();
+        };
+        fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = path()?;
+        if let Some(dir) = path.parent() {
+            xdg::ensure_dir(&dir.to_path_buf())?;
+        }
+        fs::write(&path, serde_json::to_string(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn is_stale(&self) -> bool {
+        self.refreshed_at.is_none_or(|at| Utc::now() - at > MAX_AGE)
+    }
+
+    /// Cached members of `workspace`, if any
+    pub fn members_of(&self, workspace: &str) -> Option<&[User]> {
+        self.members
+            .get(workspace)
+            .map(Vec::as_slice)
+            .filter(|members| !members.is_empty())
+    }
+
+    /// Cached branches of `repo`; for a repository not asked about before,
+    /// none yet, and a background refresh starts fetching them
+    pub fn branches_of(&self, repo: &str) -> &[String] {
+        match self.branches.get(repo) {
+            Some(names) => names,
+            None => {
+                spawn_refresh(Some(repo));
+                &[]
+            }
+        }
+    }
+
+    /// `workspace/repo` names, in one workspace or all of them
+    fn repository_names(&self, workspace: Option<&str>) -> Vec<String> {
+        self.repositories
+            .iter()
+            .filter(|(ws, _)| workspace.is_none_or(|w| w == *ws))
+            .flat_map(|(ws, slugs)| slugs.iter().map(move |slug| format!("{}/{}", ws, slug)))
+            .collect()
+    }
+}
+
+impl Porcelain for Metadata {
+    fn porcelain(&self) -> String {
+        self.workspaces.join("\n")
+    }
+}
+
+/// Whose names these are: the profile in use and the signed-in account, so
+/// switching either never shows the other's names
+fn owner() -> Result<String> {
+    let profile = Config::load()?.active_profile;
+    let account = AuthManager::new()?
+        .get_credentials()?
+        .map(|credential| credential.account_key());
+    let mut hasher = DefaultHasher::new();
+    (profile, account).hash(&mut hasher);
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+fn path() -> Result<PathBuf> {
+    Ok(Config::cache_dir()?.join(format!("metadata-{}.json", owner()?)))
+}
+
+/// Present while a background refresh runs, so reads don't start another
+fn lock_path() -> Result<PathBuf> {
+    Ok(Config::cache_dir()?.join(format!("metadata-{}.refreshing", owner()?)))
+}
+
+/// Start `bitbucket cache refresh` detached, for the same profile and
+/// workspace and also fetching `branches_of`, unless one is already running
+fn spawn_refresh(branches_of: Option<&str>) {
+    if api::is_offline() || recording::is_replaying() {
+        return;
+    }
+    let Ok(lock) = lock_path() else {
+        return;
+    };
+    let locked = fs::metadata(&lock)
+        .and_then(|m| m.modified())
+        .is_ok_and(|at| DateTime::<Utc>::from(at) > Utc::now() - LOCK_TIMEOUT);
+    if locked {
+        return;
+    }
+    if let Some(dir) = lock.parent() {
+        let _ = xdg::ensure_dir(&dir.to_path_buf());
+    }
+    if fs::write(&lock, "").is_err() {
+        return;
+    }
+
+    let mut args = vec!["cache", "refresh", "--quiet"];
+    if let Some(profile) = config::selected_profile() {
+        args.extend(["--profile", profile]);
+    }
+    if let Some(workspace) = WORKSPACE.get() {
+        args.extend(["--workspace", workspace]);
+    }
+    if let Some(repo) = branches_of {
+        args.extend(["--branches-of", repo]);
+    }
+    let spawned = std::env::current_exe().and_then(|exe| {
+        Command::new(exe)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+    });
+    match spawned {
+        Ok(_) => tracing::debug!("refreshing metadata cache in the background"),
+        Err(e) => {
+            tracing::debug!("could not start a metadata refresh: {}", e);
+            let _ = fs::remove_file(&lock);
+        }
+    }
+}
+
+/// Fetch everything the cache holds, and the branches of `branches_of`,
+/// keeping the old names for anything that fails; returns how many fetches
+/// failed
+async fn refresh(
+    client: &BitbucketClient,
+    mut previous: Metadata,
+    branches_of: Vec<String>,
+) -> Result<(Metadata, usize)> {
+    let mut workspaces: Vec<String> = client
+        .stream_workspaces()
+        .map_ok(|membership| membership.workspace.map(|ws| ws.slug))
+        .try_filter_map(|slug| futures::future::ready(Ok(slug)))
+        .try_collect()
+        .await?;
+    workspaces.extend(WORKSPACE.get().cloned());
+    workspaces.sort();
+    workspaces.dedup();
+
+    let mut metadata = Metadata {
+        refreshed_at: Some(Utc::now()),
+        workspaces: workspaces.clone(),
+        ..Default::default()
+    };
+    let mut failed = 0;
+
+    let outcome = fanout::run(
+        "Caching workspaces",
+        workspaces,
+        fanout::DEFAULT_CONCURRENCY,
+        |workspace| async move {
+            let (mut slugs, mut members): (Vec<String>, Vec<User>) = futures::try_join!(
+                client
+                    .stream_repositories(&workspace)
+                    .map_ok(|repo| {
+                        repo.slug.unwrap_or_else(|| {
+                            let (_, slug) = repo.full_name.split_once('/').unwrap_or_default();
+                            slug.to_string()
+                        })
+                    })
+                    .try_collect(),
+                client
+                    .stream_workspace_members(&workspace)
+                    .map_ok(|membership| membership.user)
+                    .try_collect(),
+            )?;
+            slugs.sort();
+            members.sort_by(|a, b| a.display_name.cmp(&b.display_name));
+            Ok((slugs, members))
+        },
+    )
+    .await;
+    for (workspace, (slugs, members)) in outcome.succeeded {
+        metadata.repositories.insert(workspace.clone(), slugs);
+        metadata.members.insert(workspace, members);
+    }
+    for (workspace, error) in outcome.failed {
+        tracing::warn!(%workspace, "could not cache workspace: {:#}", error);
+        failed += 1;
+        if let Some(slugs) = previous.repositories.remove(&workspace) {
+            metadata.repositories.insert(workspace.clone(), slugs);
+        }
+        if let Some(members) = previous.members.remove(&workspace) {
+            metadata.members.insert(workspace, members);
+        }
+    }
+
+    let mut repos: Vec<String> = previous.branches.keys().cloned().collect();
+    repos.extend(branches_of);
+    repos.sort();
+    repos.dedup();
+    let outcome = fanout::run(
+        "Caching branches",
+        repos,
+        fanout::DEFAULT_CONCURRENCY,
+        |repo| async move {
+            let (workspace, repo_slug) = repo
+                .split_once('/')
+                .with_context(|| format!("Invalid repository '{}'", repo))?;
+            let mut names: Vec<String> = client
+                .stream_branches(workspace, repo_slug)
+                .map_ok(|branch| branch.name)
+                .try_collect()
+                .await?;
+            names.sort();
+            Ok(names)
+        },
+    )
+    .await;
+    metadata.branches.extend(outcome.succeeded);
+    for (repo, error) in outcome.failed {
+        tracing::warn!(%repo, "could not cache branches: {:#}", error);
+        failed += 1;
+        let names = previous.branches.remove(&repo).unwrap_or_default();
+        metadata.branches.insert(repo, names);
+    }
+
+    Ok((metadata, failed))
+}
+
+impl CacheCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            CacheCommands::Refresh { branches_of } => {
+                let result = async {
+                    let client = BitbucketClient::from_stored().await?;
+                    refresh(&client, Metadata::load(), branches_of).await
+                }
+                .await;
+                if let Ok(lock) = lock_path() {
+                    let _ = fs::remove_file(lock);
+                }
+                let (metadata, failed) = result?;
+                metadata.save()?;
+
+                output::success(format!(
+                    "Cached {} workspaces, {} repositories, {} members and branches of {} repositories",
+                    metadata.workspaces.len(),
+                    metadata.repositories.values().map(Vec::len).sum::<usize>(),
+                    metadata.members.values().map(Vec::len).sum::<usize>(),
+                    metadata.branches.len()
+                ));
+                if failed > 0 {
+                    output::note(format!(
+                        "{} fetches failed and kept their previous names; run with --debug for details",
+                        failed
+                    ));
+                }
+                Ok(())
+            }
+
+            CacheCommands::Show => {
+                let metadata = Metadata::load();
+                if output::print(&metadata)? {
+                    return Ok(());
+                }
+                let Some(refreshed_at) = metadata.refreshed_at else {
+                    output::note("Nothing cached yet; run 'bitbucket cache refresh'");
+                    return Ok(());
+                };
+                println!("{} {}", "Refreshed:".dimmed(), format::date(&refreshed_at));
+                println!("{} {}", "Path:".dimmed(), path()?.display());
+                println!();
+                for workspace in &metadata.workspaces {
+                    println!(
+                        "{}  {} repositories, {} members",
+                        workspace.bold(),
+                        metadata.repositories.get(workspace).map_or(0, Vec::len),
+                        metadata.members.get(workspace).map_or(0, Vec::len)
+                    );
+                }
+                if !metadata.branches.is_empty() {
+                    println!();
+                    for (repo, names) in &metadata.branches {
+                        println!("{}  {} branches", repo.bold(), names.len());
+                    }
+                }
+                Ok(())
+            }
+
+            CacheCommands::Names { kind, scope } => {
+                let metadata = Metadata::current();
+                let names: Vec<String> = match kind {
+                    NameKind::Workspaces => metadata.workspaces.clone(),
+                    NameKind::Repos => metadata.repository_names(scope.as_deref()),
+                    NameKind::Members => metadata
+                        .members
+                        .iter()
+                        .filter(|(ws, _)| scope.as_deref().is_none_or(|s| s == *ws))
+                        .flat_map(|(_, users)| users.iter().map(|u| u.display_name.clone()))
+                        .collect(),
+                    NameKind::Branches => {
+                        let repo =
+                            scope
+                                .as_deref()
+                                .filter(|s| s.contains('/'))
+                                .ok_or_else(|| {
+                                    UsageError("Branch names need a workspace/repo".to_string())
+                                })?;
+                        metadata.branches_of(repo).to_vec()
+                    }
+                };
+                for name in names {
+                    println!("{}", name);
+                }
+                Ok(())
+            }
+
+            CacheCommands::Clear => {
+                for path in [path()?, lock_path()?] {
+                    if path.exists() {
+                        fs::remove_file(&path)
+                            .with_context(|| format!("Failed to remove {}", path.display()))?;
+                    }
+                }
+                output::success("Cleared the metadata cache");
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repository_names_include_the_workspace() {
+        let metadata = Metadata {
+            repositories: BTreeMap::from([
+                (
+                    "acme".to_string(),
+                    vec!["engine".to_string(), "mill".to_string()],
+                ),
+                ("babbage".to_string(), vec!["notes".to_string()]),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            metadata.repository_names(None),
+            ["acme/engine", "acme/mill", "babbage/notes"]
+        );
+        assert_eq!(
+            metadata.repository_names(Some("babbage")),
+            ["babbage/notes"]
+        );
+        assert!(metadata.is_stale());
+    }
+}
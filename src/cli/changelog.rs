@@ -0,0 +1,319 @@
+//! Release notes from merged pull requests
+//!
+//! Pull requests merged in the range, going by the date of their merge
+//! commit, are grouped by the kind of change their
+//! title suggests: a conventional-commit prefix (`feat:`, `fix(ui):`) when
+//! present, otherwise the leading verb ("Add", "Fix", ...). Issues referenced
+//! as `#123` in the title or description are linked after each entry.
+
+use std::fmt::Write;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use clap::Args;
+use colored::Colorize;
+use futures::TryStreamExt;
+
+use super::browse::WEB_URL;
+use super::git::parse_repo;
+use super::icons::Icon;
+use super::{format, output};
+use crate::api::BitbucketClient;
+use crate::models::PullRequest;
+
+#[derive(Args)]
+pub struct ChangelogArgs {
+    /// Repository in format workspace/repo-slug
+    repo: String,
+
+    /// Start of the range: a tag, or a date (YYYY-MM-DD or RFC 3339)
+    #[arg(long)]
+    since: String,
+
+    /// End of the range: a tag or a date (default: now)
+    #[arg(long)]
+    until: Option<String>,
+
+    /// Heading for the release notes (default: "Changes since <since>")
+    #[arg(long)]
+    title: Option<String>,
+
+    /// Also upload the notes to the repository's Downloads under this file name
+    #[arg(long, value_name = "FILE_NAME")]
+    upload: Option<String>,
+
+    /// Also create an annotated tag with the notes as its message
+    #[arg(long, value_name = "NAME")]
+    tag: Option<String>,
+
+    /// Branch or commit to tag (default: the main branch)
+    #[arg(long, value_name = "REF", requires = "tag")]
+    target: Option<String>,
+}
+
+/// Kind of change, in the order sections are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    Feature,
+    Fix,
+    Performance,
+    Documentation,
+    Other,
+}
+
+impl Kind {
+    fn heading(self) -> &'static str {
+        match self {
+            Kind::Feature => "Features",
+            Kind::Fix => "Bug Fixes",
+            Kind::Performance => "Performance",
+            Kind::Documentation => "Documentation",
+            Kind::Other => "Other Changes",
+        }
+    }
+
+    /// Infer the kind of change from a pull request title
+    fn of(title: &str) -> Self {
+        let title = title.trim().to_lowercase();
+        let title = title.trim_start_matches('[');
+        let word: String = title
+            .chars()
+            .take_while(|c| c.is_ascii_alphabetic())
+            .collect();
+
+        match word.as_str() {
+            "feat" | "feature" | "add" | "adds" | "added" | "implement" | "introduce"
+            | "support" => Kind::Feature,
+            "fix" | "fixes" | "fixed" | "bugfix" | "hotfix" | "bug" => Kind::Fix,
+            "perf" | "optimize" | "speed" => Kind::Performance,
+            "docs" | "doc" | "document" | "documentation" => Kind::Documentation,
+            _ => Kind::Other,
+        }
+    }
+}
+
+impl ChangelogArgs {
+    pub async fn run(self) -> Result<()> {
+        let (workspace, repo_slug) = parse_repo(&self.repo)?;
+        let client = BitbucketClient::from_stored().await?;
+
+        let since = resolve_point(&client, &workspace, &repo_slug, &self.since).await?;
+        let until = match &self.until {
+            Some(until) => Some(resolve_point(&client, &workspace, &repo_slug, until).await?),
+            None => None,
+        };
+
+        // Merging updates a pull request, so this finds every one merged
+        // since; a comment afterwards can update it past --until, though
+        let filter = format!("updated_on >= {}", since.to_rfc3339());
+        tracing::debug!(%filter, "collecting merged pull requests");
+
+        let candidates: Vec<PullRequest> = client
+            .search_merged_pull_requests(&workspace, &repo_slug, &filter)
+            .try_collect()
+            .await?;
+        let mut prs = Vec::new();
+        for pr in candidates {
+            let merged = merged_on(&client, &workspace, &repo_slug, &pr).await?;
+            if merged >= since && until.is_none_or(|until| merged <= until) {
+                prs.push(pr);
+            }
+        }
+
+        let title = self
+            .title
+            .clone()
+            .unwrap_or_else(|| format!("Changes since {}", self.since));
+        let notes = render(&title, &workspace, &repo_slug, &prs);
+
+        if !output::print(&prs)? {
+            print!("{}", notes);
+        }
+
+        if let Some(file_name) = &self.upload {
+            client
+                .upload_download(&workspace, &repo_slug, file_name, notes.as_bytes())
+                .await?;
+            output::note(format!(
+                "{} Uploaded {} to Downloads",
+                Icon::Ok.glyph().green(),
+                file_name
+            ));
+        }
+
+        if let Some(tag) = &self.tag {
+            let hash = match &self.target {
+                Some(target) => resolve_commit(&client, &workspace, &repo_slug, target).await?,
+                None => {
+                    let repository = client.get_repository(&workspace, &repo_slug).await?;
+                    let branch = repository
+                        .mainbranch
+                        .context("Repository has no main branch. Pass --target.")?;
+                    client
+                        .get_branch(&workspace, &repo_slug, &branch.name)
+                        .await?
+                        .target
+                        .hash
+                }
+            };
+
+            client
+                .create_tag(&workspace, &repo_slug, tag, &hash, Some(&notes))
+                .await?;
+            output::note(format!(
+                "{} Created tag {} at {}",
+                Icon::Ok.glyph().green(),
+                tag,
+                &hash[..hash.len().min(12)]
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Resolve a tag or date to a point in time
+async fn resolve_point(
+    client: &BitbucketClient,
+    workspace: &str,
+    repo_slug: &str,
+    value: &str,
+) -> Result<DateTime<Utc>> {
+    if let Some(at) = format::parse_date(value) {
+        return Ok(at);
+    }
+
+    let tag = client
+        .get_tag(workspace, repo_slug, value)
+        .await
+        .with_context(|| format!("'{}' is neither a date nor a tag", value))?;
+    tag.target
+        .date
+        .or(tag.date)
+        .with_context(|| format!("Tag '{}' has no date", value))
+}
+
+/// When `pr` was merged: its merge commit's date, looked up if the listing
+/// left it out, or else when it was last updated
+async fn merged_on(
+    client: &BitbucketClient,
+    workspace: &str,
+    repo_slug: &str,
+    pr: &PullRequest,
+) -> Result<DateTime<Utc>> {
+    let Some(commit) = &pr.merge_commit else {
+        return Ok(pr.updated_on);
+    };
+    let date = match commit.date {
+        Some(date) => Some(date),
+        None => {
+            client
+                .get_commit(workspace, repo_slug, &commit.hash)
+                .await?
+                .date
+        }
+    };
+    Ok(date.unwrap_or(pr.updated_on))
+}
+
+/// Resolve a branch name to its head commit; anything else is taken as a hash
+async fn resolve_commit(
+    client: &BitbucketClient,
+    workspace: &str,
+    repo_slug: &str,
+    target: &str,
+) -> Result<String> {
+    match client.get_branch(workspace, repo_slug, target).await {
+        Ok(branch) => Ok(branch.target.hash),
+        Err(crate::Error::NotFound(_)) => Ok(target.to_string()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Render the release notes as Markdown
+fn render(title: &str, workspace: &str, repo_slug: &str, prs: &[PullRequest]) -> String {
+    let mut notes = String::new();
+    let _ = writeln!(notes, "## {}", title);
+
+    if prs.is_empty() {
+        let _ = writeln!(notes, "\nNo pull requests were merged in this range.");
+        return notes;
+    }
+
+    let mut prs: Vec<(Kind, &PullRequest)> =
+        prs.iter().map(|pr| (Kind::of(&pr.title), pr)).collect();
+    prs.sort_by_key(|(kind, pr)| (*kind, pr.id));
+
+    let repo_url = format!("{}/{}/{}", WEB_URL, workspace, repo_slug);
+    let mut current = None;
+    for (kind, pr) in prs {
+        if current != Some(kind) {
+            let _ = writeln!(notes, "\n### {}\n", kind.heading());
+            current = Some(kind);
+        }
+
+        let _ = write!(
+            notes,
+            "- {} ([#{}]({}/pull-requests/{})) by {}",
+            pr.title.trim(),
+            pr.id,
+            repo_url,
+            pr.id,
+            pr.author.display_name
+        );
+
+        let text = format!("{} {}", pr.title, pr.description.as_deref().unwrap_or(""));
+        let issues: Vec<String> = issue_refs(&text)
+            .into_iter()
+            .map(|id| format!("[#{}]({}/issues/{})", id, repo_url, id))
+            .collect();
+        if !issues.is_empty() {
+            let _ = write!(notes, ", closes {}", issues.join(", "));
+        }
+        let _ = writeln!(notes);
+    }
+
+    notes
+}
+
+/// Issue numbers referenced as `#123`, in order of first mention
+fn issue_refs(text: &str) -> Vec<u64> {
+    let mut ids = Vec::new();
+    let mut previous = ' ';
+
+    for (i, c) in text.char_indices() {
+        if c == '#' && !previous.is_alphanumeric() {
+            let digits: String = text[i + 1..]
+                .chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            if let Ok(id) = digits.parse::<u64>() {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+        previous = c;
+    }
+
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds_are_inferred_from_titles() {
+        assert_eq!(Kind::of("feat(ui): dark mode"), Kind::Feature);
+        assert_eq!(Kind::of("Add snippet support"), Kind::Feature);
+        assert_eq!(Kind::of("[Fix] crash on empty repo"), Kind::Fix);
+        assert_eq!(Kind::of("docs: explain --jq"), Kind::Documentation);
+        assert_eq!(Kind::of("Bump tokio"), Kind::Other);
+    }
+
+    #[test]
+    fn issue_refs_are_collected_once() {
+        assert_eq!(issue_refs("Fix #3 and #12 (see #3), not a#4"), [3, 12]);
+    }
+}
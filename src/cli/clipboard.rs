@@ -0,0 +1,112 @@
+//! Reading an image from, and copying text to, the system clipboard
+//!
+//! There's no portable clipboard API, so this asks the platform's tools in
+//! turn: `pngpaste` and `pbcopy` on macOS, PowerShell and `clip` on Windows,
+//! and `wl-paste`/`wl-copy` or `xclip` elsewhere.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::Result;
+
+const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Commands that print the clipboard's image as PNG, tried in order
+fn readers() -> Vec<(&'static str, Vec<&'static str>)> {
+    if cfg!(target_os = "macos") {
+        vec![("pngpaste", vec!["-"])]
+    } else if cfg!(windows) {
+        vec![(
+            "powershell",
+            vec![
+                "-NoProfile",
+                "-Command",
+                "Add-Type -AssemblyName System.Windows.Forms; \
+                 $image = [Windows.Forms.Clipboard]::GetImage(); \
+                 if ($image) { $stream = New-Object IO.MemoryStream; \
+                 $image.Save($stream, [Drawing.Imaging.ImageFormat]::Png); \
+                 [Console]::OpenStandardOutput().Write($stream.ToArray(), 0, $stream.Length) }",
+            ],
+        )]
+    } else {
+        vec![
+            ("wl-paste", vec!["--no-newline", "--type", "image/png"]),
+            (
+                "xclip",
+                vec!["-selection", "clipboard", "-target", "image/png", "-out"],
+            ),
+        ]
+    }
+}
+
+/// Commands that put their stdin on the clipboard, tried in order
+fn writers() -> Vec<(&'static str, Vec<&'static str>)> {
+    if cfg!(target_os = "macos") {
+        vec![("pbcopy", vec![])]
+    } else if cfg!(windows) {
+        vec![("clip", vec![])]
+    } else {
+        vec![
+            ("wl-copy", vec![]),
+            ("xclip", vec!["-selection", "clipboard", "-in"]),
+        ]
+    }
+}
+
+/// Put `text` on the clipboard
+pub fn copy_text(text: &str) -> Result<()> {
+    let writers = writers();
+    for (program, args) in &writers {
+        let Ok(mut child) = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        if child.wait()?.success() {
+            return Ok(());
+        }
+    }
+
+    let names: Vec<&str> = writers.iter().map(|(program, _)| *program).collect();
+    anyhow::bail!(
+        "Copying to the clipboard needs {} installed",
+        names.join(" or ")
+    )
+}
+
+/// The clipboard's image as PNG bytes
+pub fn image() -> Result<Vec<u8>> {
+    let readers = readers();
+    let mut found_tool = false;
+
+    for (program, args) in &readers {
+        let Ok(output) = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+        else {
+            continue;
+        };
+        found_tool = true;
+        if output.status.success() && output.stdout.starts_with(PNG_MAGIC) {
+            return Ok(output.stdout);
+        }
+    }
+
+    if !found_tool {
+        let names: Vec<&str> = readers.iter().map(|(program, _)| *program).collect();
+        anyhow::bail!(
+            "Reading an image from the clipboard needs {} installed",
+            names.join(" or ")
+        );
+    }
+    anyhow::bail!("The clipboard doesn't hold an image")
+}
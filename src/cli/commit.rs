@@ -0,0 +1,413 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use clap::Subcommand;
+use futures::{Stream, StreamExt, TryStreamExt};
+use tabled::Tabled;
+
+use super::git::parse_repo;
+use super::range::DateRange;
+use super::{format, output, pager};
+use crate::api::BitbucketClient;
+use crate::models::{Commit, FileCommit};
+
+#[derive(Subcommand)]
+pub enum CommitCommands {
+    /// List commits, newest first
+    List {
+        /// Repository in format workspace/repo-slug
+        repo: String,
+
+        /// Branch, tag or commit to list from (default: the main branch)
+        #[arg(short, long)]
+        branch: Option<String>,
+
+        /// Number of results
+        #[arg(short, long, default_value = "25")]
+        limit: u32,
+
+        /// List every commit instead of stopping at --limit
+        #[arg(long, conflicts_with = "limit")]
+        all: bool,
+
+        /// Only commits made in this window
+        #[command(flatten)]
+        range: DateRange,
+
+        /// Only commits that changed this file, following renames
+        #[arg(long, value_name = "PATH")]
+        follow_file: Option<String>,
+
+        /// Show each commit's diff of the file, like `git log -p`
+        #[arg(long, requires = "follow_file")]
+        patch: bool,
+    },
+
+    /// Comment on a commit, optionally on a line of a file
+    Comment {
+        /// Repository in format workspace/repo-slug
+        repo: String,
+
+        /// Commit hash
+        hash: String,
+
+        /// Comment text
+        #[arg(short, long)]
+        body: String,
+
+        /// File to comment on (path in the repository)
+        #[arg(long, requires = "line")]
+        file: Option<String>,
+
+        /// Line of the file to comment on, in the commit's version
+        #[arg(long, requires = "file")]
+        line: Option<u32>,
+    },
+
+    /// List comments on a commit
+    ListComments {
+        /// Repository in format workspace/repo-slug
+        repo: String,
+
+        /// Commit hash
+        hash: String,
+    },
+
+    /// Approve a commit
+    Approve {
+        /// Repository in format workspace/repo-slug
+        repo: String,
+
+        /// Commit hash
+        hash: String,
+    },
+
+    /// Withdraw your approval of a commit
+    Unapprove {
+        /// Repository in format workspace/repo-slug
+        repo: String,
+
+        /// Commit hash
+        hash: String,
+    },
+}
+
+#[derive(Tabled)]
+pub(super) struct CommitRow {
+    #[tabled(rename = "HASH")]
+    hash: String,
+    #[tabled(rename = "AUTHOR")]
+    author: String,
+    #[tabled(rename = "DATE")]
+    date: String,
+    #[tabled(rename = "MESSAGE")]
+    message: String,
+}
+
+impl From<&Commit> for CommitRow {
+    fn from(commit: &Commit) -> Self {
+        let author = commit.author.as_ref().and_then(|a| {
+            a.user.as_ref().map(|u| u.display_name.clone()).or_else(|| {
+                // "Name <email>"
+                a.raw
+                    .as_deref()
+                    .map(|raw| raw.split(" <").next().unwrap_or(raw).to_string())
+            })
+        });
+        Self {
+            hash: commit.hash[..commit.hash.len().min(7)].to_string(),
+            author: author.unwrap_or_else(|| "-".to_string()),
+            date: commit
+                .date
+                .as_ref()
+                .map(format::date)
+                .unwrap_or_else(|| "-".to_string()),
+            message: commit
+                .message
+                .as_deref()
+                .and_then(|m| m.lines().next())
+                .unwrap_or("")
+                .to_string(),
+        }
+    }
+}
+
+/// Commits in a row from before `--since` after which paging stops. History
+/// comes in graph order rather than by date, so an old commit from a branch
+/// merged later doesn't mean everything after it is older too.
+const OLDER_RUN: usize = 20;
+
+/// The commits of `commits` made at or after `since`, paging until
+/// [`OLDER_RUN`] in a row were made before it
+fn made_since<T>(
+    commits: impl Stream<Item = crate::error::Result<T>>,
+    since: Option<DateTime<Utc>>,
+    commit: fn(&T) -> &Commit,
+) -> impl Stream<Item = crate::error::Result<T>> {
+    commits
+        .scan(0, move |older, item| {
+            let item = match item {
+                Ok(c) if after(since, commit(&c)) => {
+                    *older = 0;
+                    Some(Ok(c))
+                }
+                Ok(_) => {
+                    *older += 1;
+                    None
+                }
+                Err(e) => Some(Err(e)),
+            };
+            futures::future::ready((*older < OLDER_RUN).then_some(item))
+        })
+        .filter_map(futures::future::ready)
+}
+
+/// Whether `commit` was made at or after `since`, if it's given
+fn after(since: Option<DateTime<Utc>>, commit: &Commit) -> bool {
+    match (since, commit.date) {
+        (Some(since), Some(date)) => date >= since,
+        _ => true,
+    }
+}
+
+/// Whether `commit` was made before `until`, if it's given
+fn before(until: Option<DateTime<Utc>>, commit: &Commit) -> bool {
+    match (until, commit.date) {
+        (Some(until), Some(date)) => date < until,
+        _ => true,
+    }
+}
+
+/// Page each commit's header and its diff of the file, newest first
+async fn print_patches(
+    client: &BitbucketClient,
+    workspace: &str,
+    repo_slug: &str,
+    history: &[FileCommit],
+) -> Result<()> {
+    let diffs = futures::stream::iter(history)
+        .map(|c| client.get_commit_file_diff(workspace, repo_slug, &c.commit.hash, &c.path))
+        .buffered(4)
+        .try_collect::<Vec<String>>()
+        .await?;
+
+    let mut text = String::new();
+    for (entry, diff) in history.iter().zip(diffs) {
+        let commit = &entry.commit;
+        text.push_str(&format!("commit {}\n", commit.hash));
+        if let Some(raw) = commit.author.as_ref().and_then(|a| a.raw.as_deref()) {
+            text.push_str(&format!("Author: {}\n", raw));
+        }
+        if let Some(date) = &commit.date {
+            text.push_str(&format!("Date:   {}\n", format::date(date)));
+        }
+        text.push('\n');
+        for line in commit.message.as_deref().unwrap_or("").trim_end().lines() {
+            text.push_str(&format!("    {}\n", line));
+        }
+        text.push('\n');
+        text.push_str(&diff);
+        if !diff.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push('\n');
+    }
+    pager::page(&text)
+}
+
+#[derive(Tabled)]
+struct CommentRow {
+    #[tabled(rename = "ID")]
+    id: u64,
+    #[tabled(rename = "AUTHOR")]
+    author: String,
+    #[tabled(rename = "CREATED")]
+    created: String,
+    #[tabled(rename = "LOCATION")]
+    location: String,
+    #[tabled(rename = "CONTENT")]
+    content: String,
+}
+
+impl CommitCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            CommitCommands::List {
+                repo,
+                branch,
+                limit,
+                all,
+                range,
+                follow_file,
+                patch,
+            } => {
+                let (workspace, repo_slug) = parse_repo(&repo)?;
+                let (since, until) = range.bounds()?;
+                let client = BitbucketClient::from_stored().await?;
+
+                if let Some(file) = follow_file {
+                    let revision = match branch {
+                        Some(branch) => branch,
+                        None => {
+                            client
+                                .get_repository(&workspace, &repo_slug)
+                                .await?
+                                .mainbranch
+                                .context("Repository has no main branch. Pass --branch.")?
+                                .name
+                        }
+                    };
+                    let history = made_since(
+                        client.stream_file_history(&workspace, &repo_slug, &revision, &file),
+                        since,
+                        |c| &c.commit,
+                    )
+                    .try_filter(|c| futures::future::ready(before(until, &c.commit)));
+                    let history: Vec<FileCommit> = if all {
+                        history.try_collect().await?
+                    } else {
+                        history.take(limit as usize).try_collect().await?
+                    };
+
+                    if patch {
+                        return print_patches(&client, &workspace, &repo_slug, &history).await;
+                    }
+                    if output::print(&history)? {
+                        return Ok(());
+                    }
+                    if history.is_empty() {
+                        output::note(format!("No commits changed {}", file));
+                        return Ok(());
+                    }
+                    return output::table(
+                        history.iter().map(|c| CommitRow::from(&c.commit)).collect(),
+                    );
+                }
+
+                // The commits endpoint takes no query filter, but lists newest
+                // first, so paging stops after a run of commits before --since
+                let commits = made_since(
+                    client.stream_commits(&workspace, &repo_slug, branch.as_deref()),
+                    since,
+                    |c| c,
+                )
+                .try_filter(|c| futures::future::ready(before(until, c)));
+                let commits: Vec<Commit> = if all {
+                    commits.try_collect().await?
+                } else {
+                    commits.take(limit as usize).try_collect().await?
+                };
+
+                if output::print(&commits)? {
+                    return Ok(());
+                }
+
+                if commits.is_empty() {
+                    output::note("No commits found");
+                    return Ok(());
+                }
+
+                output::table(commits.iter().map(CommitRow::from).collect())
+            }
+
+            CommitCommands::Comment {
+                repo,
+                hash,
+                body,
+                file,
+                line,
+            } => {
+                let (workspace, repo_slug) = parse_repo(&repo)?;
+                let client = BitbucketClient::from_stored().await?;
+
+                let inline = file.as_deref().zip(line);
+                let comment = client
+                    .add_commit_comment(&workspace, &repo_slug, &hash, &body, inline)
+                    .await?;
+
+                if output::print(&comment)? {
+                    return Ok(());
+                }
+
+                match inline {
+                    Some((file, line)) => output::success(format!(
+                        "Added comment on {}:{} in commit {}",
+                        file,
+                        line,
+                        short(&hash)
+                    )),
+                    None => output::success(format!("Added comment to commit {}", short(&hash))),
+                }
+
+                Ok(())
+            }
+
+            CommitCommands::ListComments { repo, hash } => {
+                let (workspace, repo_slug) = parse_repo(&repo)?;
+                let client = BitbucketClient::from_stored().await?;
+
+                let mut comments = client
+                    .list_commit_comments(&workspace, &repo_slug, &hash)
+                    .await?
+                    .values;
+                comments.sort_by_key(|c| c.created_on);
+
+                if output::print(&comments)? {
+                    return Ok(());
+                }
+
+                if comments.is_empty() {
+                    output::note("No comments found");
+                    return Ok(());
+                }
+
+                let rows: Vec<CommentRow> = comments
+                    .iter()
+                    .map(|c| CommentRow {
+                        id: c.id,
+                        author: c.user.display_name.clone(),
+                        created: format::date(&c.created_on),
+                        location: match &c.inline {
+                            Some(inline) => match inline.to.or(inline.from) {
+                                Some(line) => format!("{}:{}", inline.path, line),
+                                None => inline.path.clone(),
+                            },
+                            None => "-".to_string(),
+                        },
+                        content: format::one_line(&c.content.raw),
+                    })
+                    .collect();
+
+                output::table(rows)
+            }
+
+            CommitCommands::Approve { repo, hash } => {
+                let (workspace, repo_slug) = parse_repo(&repo)?;
+                let client = BitbucketClient::from_stored().await?;
+
+                client.approve_commit(&workspace, &repo_slug, &hash).await?;
+
+                output::success(format!("Approved commit {}", short(&hash)));
+
+                Ok(())
+            }
+
+            CommitCommands::Unapprove { repo, hash } => {
+                let (workspace, repo_slug) = parse_repo(&repo)?;
+                let client = BitbucketClient::from_stored().await?;
+
+                client
+                    .unapprove_commit(&workspace, &repo_slug, &hash)
+                    .await?;
+
+                output::success(format!("Removed approval from commit {}", short(&hash)));
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Abbreviate a commit hash for messages
+fn short(hash: &str) -> &str {
+    &hash[..hash.len().min(12)]
+}
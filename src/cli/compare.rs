@@ -0,0 +1,163 @@
+//! Ahead/behind comparison of two refs (`bitbucket compare`)
+
+use anyhow::Result;
+use clap::Args;
+use colored::Colorize;
+use futures::TryStreamExt;
+use serde::Serialize;
+
+use super::commit::CommitRow;
+use super::git::parse_repo;
+use super::output::Porcelain;
+use super::{UsageError, output, pager};
+use crate::api::BitbucketClient;
+use crate::models::Commit;
+
+#[derive(Args)]
+pub struct CompareArgs {
+    /// Repository in format workspace/repo-slug
+    repo: String,
+
+    /// Refs to compare as BASE..HEAD, e.g. main..feature
+    range: String,
+
+    /// Show the diff of HEAD against where it branched from BASE instead
+    #[arg(long)]
+    diff: bool,
+
+    /// Commits to list on each side
+    #[arg(short, long, default_value = "20")]
+    limit: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct Comparison {
+    base: String,
+    head: String,
+    ahead: usize,
+    behind: usize,
+    /// Commits on head that base doesn't have, newest first
+    ahead_commits: Vec<Commit>,
+    /// Commits on base that head doesn't have, newest first
+    behind_commits: Vec<Commit>,
+}
+
+impl Porcelain for Comparison {
+    fn porcelain(&self) -> String {
+        format!("{}\t{}", self.ahead, self.behind)
+    }
+}
+
+impl CompareArgs {
+    pub async fn run(self) -> Result<()> {
+        let (workspace, repo_slug) = parse_repo(&self.repo)?;
+        let (base, head) = parse_range(&self.range)?;
+        let client = BitbucketClient::from_stored().await?;
+
+        if self.diff {
+            let diff = client
+                .get_diff(&workspace, &repo_slug, &base, &head)
+                .await?;
+            if diff.is_empty() {
+                output::note(format!("No changes on {} since it left {}", head, base));
+                return Ok(());
+            }
+            return pager::page(&diff);
+        }
+
+        let (ahead_commits, behind_commits): (Vec<Commit>, Vec<Commit>) = futures::try_join!(
+            client
+                .stream_commits_between(&workspace, &repo_slug, &head, &base)
+                .try_collect(),
+            client
+                .stream_commits_between(&workspace, &repo_slug, &base, &head)
+                .try_collect(),
+        )?;
+        let comparison = Comparison {
+            ahead: ahead_commits.len(),
+            behind: behind_commits.len(),
+            base,
+            head,
+            ahead_commits,
+            behind_commits,
+        };
+
+        if output::print(&comparison)? {
+            return Ok(());
+        }
+
+        let Comparison {
+            base,
+            head,
+            ahead,
+            behind,
+            ..
+        } = &comparison;
+        println!(
+            "{} is {} and {} {}",
+            head.cyan(),
+            commits(*ahead, "ahead of"),
+            commits(*behind, "behind"),
+            base.green()
+        );
+        for (label, list) in [
+            (format!("Only on {}", head), &comparison.ahead_commits),
+            (format!("Only on {}", base), &comparison.behind_commits),
+        ] {
+            if list.is_empty() {
+                continue;
+            }
+            println!();
+            println!("{}", format!("{} ({})", label, list.len()).bold());
+            output::table(list.iter().take(self.limit).map(CommitRow::from).collect())?;
+            if list.len() > self.limit {
+                output::note(format!("… and {} more", list.len() - self.limit));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn commits(count: usize, side: &str) -> String {
+    format!(
+        "{} {} {}",
+        count,
+        if count == 1 { "commit" } else { "commits" },
+        side
+    )
+}
+
+/// Split `BASE..HEAD` (or `BASE...HEAD`, which means the same here)
+fn parse_range(range: &str) -> Result<(String, String)> {
+    let parts = range
+        .split_once("...")
+        .or_else(|| range.split_once(".."))
+        .filter(|(base, head)| !base.is_empty() && !head.is_empty());
+    match parts {
+        Some((base, head)) => Ok((base.to_string(), head.to_string())),
+        None => anyhow::bail!(UsageError(format!(
+            "Invalid range '{}'. Expected BASE..HEAD, e.g. main..feature",
+            range
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_split_into_base_and_head() {
+        assert_eq!(
+            parse_range("main..feature/mill").unwrap(),
+            ("main".to_string(), "feature/mill".to_string())
+        );
+        assert_eq!(
+            parse_range("v1.0...main").unwrap(),
+            ("v1.0".to_string(), "main".to_string())
+        );
+        assert!(parse_range("main").is_err());
+        assert!(parse_range("main..").is_err());
+    }
+}
@@ -0,0 +1,70 @@
+//! Confirmation before destructive commands
+//!
+//! `--yes` (or `BITBUCKET_ASSUME_YES`) answers every question with yes for
+//! scripts. Without it, a command that needs an answer refuses to run when
+//! there's no terminal to ask on, rather than hang or guess. Confirmations
+//! that must be typed back are never assumed; scripts pass the text instead.
+
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Result;
+
+use super::UsageError;
+
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+/// Answer yes to every confirmation for the rest of the process
+pub fn set_assume_yes(yes: bool) {
+    ASSUME_YES.store(yes, Ordering::Relaxed);
+}
+
+/// Ask `question`, defaulting to no
+pub fn confirm(question: impl Into<String>) -> Result<bool> {
+    let question = question.into();
+    if ASSUME_YES.load(Ordering::Relaxed) {
+        tracing::debug!(%question, "assuming yes");
+        return Ok(true);
+    }
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!(UsageError(format!(
+            "{} Refusing to continue without confirmation; pass --yes",
+            question
+        )));
+    }
+    Ok(dialoguer::Confirm::new()
+        .with_prompt(question)
+        .default(false)
+        .interact()?)
+}
+
+/// Ask the user to type `expected` back before something that can't be
+/// undone; anything else is a no. `given` is the text passed on the command
+/// line (`--confirm`) for running without a terminal; `--yes` doesn't count.
+pub fn confirm_typed(
+    question: impl Into<String>,
+    expected: &str,
+    given: Option<&str>,
+) -> Result<bool> {
+    let question = question.into();
+    if let Some(given) = given {
+        if given != expected {
+            anyhow::bail!(UsageError(format!(
+                "{} --confirm '{}' doesn't match; pass --confirm {}",
+                question, given, expected
+            )));
+        }
+        return Ok(true);
+    }
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!(UsageError(format!(
+            "{} Refusing to continue without confirmation; pass --confirm {}",
+            question, expected
+        )));
+    }
+    let answer: String = dialoguer::Input::new()
+        .with_prompt(format!("{} Type {} to confirm", question, expected))
+        .allow_empty(true)
+        .interact_text()?;
+    Ok(answer.trim() == expected)
+}
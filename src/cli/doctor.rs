@@ -0,0 +1,430 @@
+//! Environment diagnostics (`bitbucket doctor`)
+//!
+//! Each check reports ok, a warning, or a failure with the fix to try.
+//! Warnings cover things the CLI works around, such as a missing keyring;
+//! failures are things that stop commands working, and make the command exit
+//! non-zero so it can gate CI setup scripts.
+
+use std::io::IsTerminal;
+use std::process::Command;
+use std::time::Instant;
+
+use anyhow::Result;
+use colored::Colorize;
+use serde::Serialize;
+
+use super::icons::Icon;
+use super::output::{self, Porcelain};
+use crate::api::{self, BitbucketClient};
+use crate::auth::{AuthManager, Credential, KeyringStore};
+use crate::config::Config;
+use crate::error::Error;
+
+/// Scopes `auth login` asks for, which commands between them rely on
+const REQUIRED_SCOPES: &[&str] = &["account", "repository", "pullrequest", "issue", "pipeline"];
+
+/// Proxy variables reqwest reads, in the order it prefers them
+const PROXY_VARS: &[&str] = &[
+    "HTTPS_PROXY",
+    "https_proxy",
+    "HTTP_PROXY",
+    "http_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Serialize)]
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+impl Porcelain for Check {
+    fn porcelain(&self) -> String {
+        let status = match self.status {
+            Status::Ok => "ok",
+            Status::Warn => "warn",
+            Status::Fail => "fail",
+        };
+        format!("{}\t{}", self.name, status)
+    }
+}
+
+/// Run every check and print the results
+pub async fn run() -> Result<()> {
+    let config = Config::load();
+    let network = config
+        .as_ref()
+        .map(|c| c.network.clone())
+        .unwrap_or_default();
+
+    let checks = vec![
+        git(),
+        config_check(&config),
+        proxy(),
+        reachability(&network).await,
+        keyring(),
+        credentials().await,
+        terminal(),
+    ];
+
+    if !output::print(&checks)? {
+        for check in &checks {
+            let symbol = match check.status {
+                Status::Ok => Icon::Ok.glyph().green(),
+                Status::Warn => Icon::Warning.glyph().yellow(),
+                Status::Fail => Icon::Error.glyph().red(),
+            };
+            println!("{} {:<12} {}", symbol, check.name, check.detail);
+            if let Some(fix) = &check.fix {
+                println!("  {:<12} {} {}", "", "→".dimmed(), fix);
+            }
+        }
+    }
+
+    let failed = checks.iter().filter(|c| c.status == Status::Fail).count();
+    if failed > 0 {
+        anyhow::bail!("{} of {} checks failed", failed, checks.len());
+    }
+    Ok(())
+}
+
+fn git() -> Check {
+    match Command::new("git").arg("--version").output() {
+        Ok(out) if out.status.success() => Check::ok(
+            "git",
+            String::from_utf8_lossy(&out.stdout).trim().to_string(),
+        ),
+        Ok(out) => Check::fail(
+            "git",
+            format!(
+                "git --version failed: {}",
+                String::from_utf8_lossy(&out.stderr).trim()
+            ),
+            "Reinstall git from https://git-scm.com/downloads",
+        ),
+        Err(_) => Check::fail(
+            "git",
+            "git not found on PATH",
+            "Install git from https://git-scm.com/downloads; clone, checkout and repository detection need it",
+        ),
+    }
+}
+
+fn config_check(config: &anyhow::Result<Config>) -> Check {
+    let path = match Config::config_path() {
+        Ok(path) => path,
+        Err(e) => {
+            return Check::fail(
+                "config",
+                format!("No config directory: {:#}", e),
+                "Set HOME or XDG_CONFIG_HOME",
+            );
+        }
+    };
+    match config {
+        Ok(_) if !path.exists() => Check::ok(
+            "config",
+            format!("{} not created yet, using defaults", path.display()),
+        ),
+        Ok(config) => match &config.active_profile {
+            Some(name) => Check::ok("config", format!("{}, profile {}", path.display(), name)),
+            None => Check::ok("config", path.display().to_string()),
+        },
+        Err(e) => Check::fail(
+            "config",
+            format!("{:#}", e),
+            format!("Fix or remove {} to go back to defaults", path.display()),
+        ),
+    }
+}
+
+fn proxy() -> Check {
+    let set: Vec<(&str, String)> = PROXY_VARS
+        .iter()
+        .filter_map(|name| {
+            let value = std::env::var(name).ok().filter(|v| !v.is_empty())?;
+            Some((*name, value))
+        })
+        .collect();
+    if set.is_empty() {
+        return Check::ok("proxy", "none, connecting directly");
+    }
+
+    let mut shown = Vec::new();
+    for (name, value) in &set {
+        // reqwest assumes http:// for a bare host:port
+        let with_scheme = if value.contains("://") {
+            value.clone()
+        } else {
+            format!("http://{}", value)
+        };
+        match url::Url::parse(&with_scheme) {
+            Ok(mut url) => {
+                if url.password().is_some() {
+                    let _ = url.set_password(Some("****"));
+                }
+                shown.push(format!("{}={}", name, url));
+            }
+            Err(e) => {
+                return Check::fail(
+                    "proxy",
+                    format!("{} is not a valid URL: {}", name, e),
+                    format!(
+                        "Set {} to a URL such as http://proxy.example.com:8080",
+                        name
+                    ),
+                );
+            }
+        }
+    }
+    if let Some(no_proxy) = ["NO_PROXY", "no_proxy"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|v| !v.is_empty()))
+    {
+        shown.push(format!("NO_PROXY={}", no_proxy));
+    }
+    Check::ok("proxy", shown.join(", "))
+}
+
+async fn reachability(network: &crate::config::NetworkConfig) -> Check {
+    let base_url = api::default_base_url();
+    let host = url::Url::parse(&base_url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_else(|| base_url.clone());
+    let client = match api::http_client_builder(network).build() {
+        Ok(client) => client,
+        Err(e) => {
+            return Check::fail(
+                "network",
+                format!("Could not set up HTTP: {}", e),
+                "Check the proxy variables above",
+            );
+        }
+    };
+
+    let started = Instant::now();
+    match client.get(&base_url).send().await {
+        // Any answer, even an error status, means the API is reachable
+        Ok(response) => Check::ok(
+            "network",
+            format!(
+                "{} reachable (HTTP {}, {} ms)",
+                host,
+                response.status().as_u16(),
+                started.elapsed().as_millis()
+            ),
+        ),
+        Err(e) => {
+            let reason = if e.is_timeout() {
+                "timed out".to_string()
+            } else if e.is_connect() {
+                "could not connect".to_string()
+            } else {
+                e.to_string()
+            };
+            Check::fail(
+                "network",
+                format!("{} unreachable: {}", host, reason),
+                "Check your connection and proxy settings, raise [network] connect_timeout, \
+                 or see https://bitbucket.status.atlassian.com",
+            )
+        }
+    }
+}
+
+fn keyring() -> Check {
+    match KeyringStore::new().and_then(|store| store.get_credential()) {
+        Ok(Some(_)) => Check::ok("keyring", "available, holds your credentials"),
+        Ok(None) => Check::ok("keyring", "available"),
+        Err(e) => {
+            let fix = if cfg!(target_os = "linux") {
+                "Start a Secret Service provider such as GNOME Keyring or KeePassXC, then log in again"
+            } else {
+                "Unlock the system keychain, then log in again"
+            };
+            Check::warn(
+                "keyring",
+                format!(
+                    "unavailable ({}); credentials are kept in a file instead",
+                    e
+                ),
+                fix,
+            )
+        }
+    }
+}
+
+async fn credentials() -> Check {
+    let credential = match AuthManager::new().and_then(|auth| auth.get_credentials()) {
+        Ok(Some(credential)) => credential,
+        Ok(None) => {
+            return Check::fail(
+                "credentials",
+                "Not logged in",
+                "Run 'bitbucket auth login', or set BITBUCKET_ACCESS_TOKEN for CI",
+            );
+        }
+        Err(e) => {
+            let fix = if matches!(e, Error::CredentialStore(_)) {
+                "Fix the keyring, or set BITBUCKET_ACCESS_TOKEN to skip it"
+            } else {
+                "Run 'bitbucket auth logout' then 'bitbucket auth login'"
+            };
+            return Check::fail(
+                "credentials",
+                format!("Could not read credentials: {}", e),
+                fix,
+            );
+        }
+    };
+    let source = if Credential::from_env().is_some() {
+        "from the environment"
+    } else {
+        "stored"
+    };
+    let kind = format!("{} {}", credential.type_name(), source);
+
+    let client = match BitbucketClient::from_stored().await {
+        Ok(client) => client,
+        Err(e) => {
+            return Check::fail(
+                "credentials",
+                format!("{}: {}", kind, e),
+                "Run 'bitbucket auth login'",
+            );
+        }
+    };
+    match client.granted_scopes().await {
+        Ok(Some(scopes)) => {
+            let missing: Vec<&str> = REQUIRED_SCOPES
+                .iter()
+                .copied()
+                .filter(|required| !scopes.iter().any(|s| covers(s, required)))
+                .collect();
+            if missing.is_empty() {
+                Check::ok("credentials", format!("{}, valid", kind))
+            } else {
+                Check::warn(
+                    "credentials",
+                    format!("{}, valid but missing scopes: {}", kind, missing.join(", ")),
+                    "Run 'bitbucket auth login' again, or create a token with those scopes",
+                )
+            }
+        }
+        Ok(None) => Check::ok("credentials", format!("{}, valid", kind)),
+        Err(Error::Unauthorized) => Check::fail(
+            "credentials",
+            format!("{} rejected by Bitbucket", kind),
+            "The token has expired or been revoked; run 'bitbucket auth login'",
+        ),
+        Err(e) => Check::fail(
+            "credentials",
+            format!("{} could not be checked: {}", kind, e),
+            "Fix the network check first, then run this again",
+        ),
+    }
+}
+
+/// Whether a granted scope, such as `pullrequest:write`, includes `required`
+fn covers(granted: &str, required: &str) -> bool {
+    granted == required
+        || granted
+            .strip_prefix(required)
+            .is_some_and(|rest| rest.starts_with(':'))
+}
+
+fn terminal() -> Check {
+    let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+    let tty = std::io::stdout().is_terminal();
+    let term = var("TERM");
+    let color = colored::control::SHOULD_COLORIZE.should_colorize();
+    let unicode = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|name| var(name))
+        .is_some_and(|locale| {
+            let locale = locale.to_ascii_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        });
+
+    let mut detail = vec![if tty { "interactive" } else { "not a terminal" }.to_string()];
+    if let Some(term) = &term {
+        detail.push(format!("TERM={}", term));
+    }
+    if tty && let Ok((width, height)) = crossterm::terminal::size() {
+        detail.push(format!("{}x{}", width, height));
+    }
+    detail.push(if color { "colour" } else { "no colour" }.to_string());
+    detail.push(if unicode { "UTF-8" } else { "no UTF-8 locale" }.to_string());
+    let detail = detail.join(", ");
+
+    if tty && term.as_deref() == Some("dumb") {
+        Check::warn(
+            "terminal",
+            detail,
+            "The TUI needs a terminal that supports cursor movement; set TERM, e.g. xterm-256color",
+        )
+    } else if tty && !unicode && !cfg!(windows) {
+        Check::warn(
+            "terminal",
+            detail,
+            "Symbols may render as garbage; set LANG to a UTF-8 locale such as en_US.UTF-8",
+        )
+    } else {
+        Check::ok("terminal", detail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_scopes_cover_read_scopes() {
+        assert!(covers("pullrequest", "pullrequest"));
+        assert!(covers("pullrequest:write", "pullrequest"));
+        assert!(!covers("pullrequests", "pullrequest"));
+        assert!(!covers("pipeline", "pullrequest"));
+    }
+}
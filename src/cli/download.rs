@@ -0,0 +1,325 @@
+//! Downloads of files too large to hold in memory
+//!
+//! Bytes go to `<dest>.part` and are renamed into place once complete, so an
+//! interrupted download resumes where it stopped, within a run (up to
+//! [`ATTEMPTS`] tries) or on the next one, using a Range request. The file's
+//! ETag is kept in `<dest>.part.etag` and sent as `If-Range`, so a file that
+//! changed in between, like one served without an ETag or by a server that
+//! ignores the range, is downloaded again from the start. When a SHA-256 is
+//! known, from the caller or the response's `Digest` header, the finished
+//! file is checked against it.
+
+use std::fs::{self, File, OpenOptions};
+use std::future::Future;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use colored::Colorize;
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::header::ETAG;
+use reqwest::{Response, StatusCode};
+use sha2::{Digest, Sha256};
+
+use super::icons::Icon;
+use super::output;
+use crate::api::Resume;
+use crate::error::Error;
+
+/// Tries per download before giving up
+const ATTEMPTS: u32 = 3;
+
+/// A finished download
+#[derive(Debug)]
+pub struct Downloaded {
+    pub path: PathBuf,
+    pub bytes: u64,
+    /// Whether the contents matched a known checksum
+    pub verified: bool,
+}
+
+/// Download to `dest`, calling `request` with where to start from for each
+/// attempt. `sha256` is the expected hex digest, if known.
+pub async fn fetch<F, Fut>(
+    label: &str,
+    dest: &Path,
+    sha256: Option<&str>,
+    request: F,
+) -> Result<Downloaded>
+where
+    F: Fn(Resume) -> Fut,
+    Fut: Future<Output = crate::error::Result<Response>>,
+{
+    let part = part_path(dest);
+    let etag_file = etag_path(&part);
+    let progress = progress_bar(label);
+    let mut expected = sha256.map(|s| s.trim().to_ascii_lowercase());
+
+    let mut attempt = 1;
+    loop {
+        let resume = Resume {
+            offset: fs::metadata(&part).map(|m| m.len()).unwrap_or(0),
+            etag: fs::read_to_string(&etag_file).ok(),
+        };
+        // Bytes nothing can vouch for are fetched again
+        let resume = if resume.etag.is_some() {
+            resume
+        } else {
+            Resume::default()
+        };
+        let offset = resume.offset;
+        match transfer(&request, &part, resume, &progress).await {
+            Ok(digest) => {
+                expected = expected.or(digest);
+                break;
+            }
+            Err(e) if is_unsatisfiable(&e) && offset > 0 => {
+                // The partial file no longer fits what the server has
+                fs::remove_file(&part).ok();
+                fs::remove_file(&etag_file).ok();
+            }
+            Err(e) if attempt < ATTEMPTS && is_retryable(&e) => {
+                progress.suspend(|| {
+                    output::note(format!(
+                        "{} Download interrupted ({:#}), resuming",
+                        Icon::Warning.glyph().yellow(),
+                        e
+                    ))
+                });
+                tokio::time::sleep(Duration::from_secs(attempt as u64)).await;
+            }
+            Err(e) => {
+                progress.abandon();
+                return Err(e.context(format!(
+                    "Download failed; run it again to resume from {}",
+                    part.display()
+                )));
+            }
+        }
+        attempt += 1;
+    }
+    progress.finish_and_clear();
+
+    let verified = match &expected {
+        Some(expected) => {
+            let actual = sha256_file(&part)?;
+            if &actual != expected {
+                fs::remove_file(&part).ok();
+                fs::remove_file(&etag_file).ok();
+                anyhow::bail!(
+                    "Checksum mismatch for {}: expected SHA-256 {}, got {}",
+                    dest.display(),
+                    expected,
+                    actual
+                );
+            }
+            true
+        }
+        None => false,
+    };
+
+    fs::rename(&part, dest).with_context(|| format!("Failed to write {}", dest.display()))?;
+    fs::remove_file(&etag_file).ok();
+    let bytes = fs::metadata(dest)?.len();
+    Ok(Downloaded {
+        path: dest.to_path_buf(),
+        bytes,
+        verified,
+    })
+}
+
+/// One attempt, appending to `part` from `resume.offset`, or starting it
+/// over when the server sends the whole file. Returns the SHA-256 the server
+/// declared, if any.
+async fn transfer<F, Fut>(
+    request: &F,
+    part: &Path,
+    resume: Resume,
+    progress: &ProgressBar,
+) -> Result<Option<String>>
+where
+    F: Fn(Resume) -> Fut,
+    Fut: Future<Output = crate::error::Result<Response>>,
+{
+    let offset = resume.offset;
+    let mut response = request(resume).await?;
+    let resumed = offset > 0 && response.status() == StatusCode::PARTIAL_CONTENT;
+    let start = if resumed { offset } else { 0 };
+    let digest = declared_sha256(&response);
+
+    if !resumed {
+        // A weak ETag can't be sent as If-Range
+        let etag_file = etag_path(part);
+        match response
+            .headers()
+            .get(ETAG)
+            .and_then(|v| v.to_str().ok())
+            .filter(|etag| !etag.starts_with("W/"))
+        {
+            Some(etag) => fs::write(&etag_file, etag)
+                .with_context(|| format!("Failed to write {}", etag_file.display()))?,
+            None => {
+                fs::remove_file(&etag_file).ok();
+            }
+        }
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(part)
+        .with_context(|| format!("Failed to open {}", part.display()))?;
+
+    let total = response.content_length().map(|len| start + len);
+    if let Some(total) = total {
+        progress.set_length(total);
+    }
+    progress.set_position(start);
+
+    let mut written = start;
+    while let Some(chunk) = response.chunk().await.map_err(Error::from)? {
+        file.write_all(&chunk)
+            .with_context(|| format!("Failed to write {}", part.display()))?;
+        written += chunk.len() as u64;
+        progress.set_position(written);
+    }
+    file.flush()?;
+
+    if let Some(total) = total
+        && written < total
+    {
+        anyhow::bail!("connection closed after {} of {} bytes", written, total);
+    }
+    Ok(digest)
+}
+
+/// The SHA-256 in a `Digest: sha-256=…` or `Repr-Digest: sha-256=:…:`
+/// header, as hex
+fn declared_sha256(response: &Response) -> Option<String> {
+    ["repr-digest", "digest"].iter().find_map(|name| {
+        let value = response.headers().get(*name)?.to_str().ok()?;
+        parse_digest(value)
+    })
+}
+
+fn parse_digest(value: &str) -> Option<String> {
+    value.split(',').find_map(|entry| {
+        let (algorithm, encoded) = entry.trim().split_once('=')?;
+        if !algorithm.eq_ignore_ascii_case("sha-256") {
+            return None;
+        }
+        let bytes = STANDARD.decode(encoded.trim_matches(':')).ok()?;
+        Some(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+    })
+}
+
+fn sha256_file(path: &Path) -> Result<String> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+fn part_path(dest: &Path) -> PathBuf {
+    let mut name = dest.as_os_str().to_owned();
+    name.push(".part");
+    PathBuf::from(name)
+}
+
+/// Where the ETag of the file `part` holds the start of is kept
+fn etag_path(part: &Path) -> PathBuf {
+    let mut name = part.as_os_str().to_owned();
+    name.push(".etag");
+    PathBuf::from(name)
+}
+
+/// Whether trying again could help, unlike e.g. a missing file
+fn is_retryable(error: &anyhow::Error) -> bool {
+    !matches!(
+        error.downcast_ref::<Error>(),
+        Some(
+            Error::Unauthorized
+                | Error::Forbidden
+                | Error::NotFound(_)
+                | Error::Offline(_)
+                | Error::NotAuthenticated
+        )
+    )
+}
+
+fn is_unsatisfiable(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<Error>(),
+        Some(Error::Api { status, .. }) if *status == StatusCode::RANGE_NOT_SATISFIABLE
+    )
+}
+
+fn progress_bar(label: &str) -> ProgressBar {
+    if !output::is_tty() || output::is_quiet() {
+        return ProgressBar::hidden();
+    }
+
+    let progress = ProgressBar::no_length();
+    progress.set_style(
+        ProgressStyle::default_bar()
+            .template(
+                "{spinner:.blue} {msg} [{bar:30}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})",
+            )
+            .unwrap()
+            .progress_chars("=> "),
+    );
+    progress.set_message(label.to_string());
+    progress
+}
+
+/// One-line summary of a finished download
+pub fn summary(downloaded: &Downloaded) -> String {
+    format!(
+        "Downloaded {} ({}{})",
+        downloaded.path.display(),
+        indicatif::HumanBytes(downloaded.bytes),
+        if downloaded.verified {
+            ", SHA-256 verified"
+        } else {
+            ""
+        }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digest_headers_become_hex() {
+        // SHA-256 of "hello"
+        let hex = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert_eq!(
+            parse_digest("sha-256=LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=").as_deref(),
+            Some(hex)
+        );
+        assert_eq!(
+            parse_digest("md5=XUFAKrxLKna5cZ2REBfFkg==, SHA-256=:LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=:")
+                .as_deref(),
+            Some(hex)
+        );
+        assert_eq!(parse_digest("md5=XUFAKrxLKna5cZ2REBfFkg=="), None);
+    }
+}
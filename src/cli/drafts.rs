@@ -0,0 +1,308 @@
+//! Comments that failed to post, kept so a long one isn't lost
+//!
+//! When `pr comment` or `issue comment` fails with a network or server error,
+//! the body is saved to `$XDG_STATE_HOME/bitbucket-cli/drafts.json`. `bitbucket
+//! drafts retry` posts it again and forgets it once it goes through. Unless
+//! the error shows the request was turned away, the comment may have been
+//! posted after all, so the draft is marked and retrying it warns of a
+//! possible duplicate.
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use clap::Subcommand;
+use colored::Colorize;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use tabled::Tabled;
+
+use super::icons::Icon;
+use super::output::Porcelain;
+use super::{UsageError, format, output};
+use crate::api::BitbucketClient;
+use crate::config::{Config, xdg};
+
+const FILE: &str = "drafts.json";
+
+#[derive(Subcommand)]
+pub enum DraftsCommands {
+    /// List comments waiting to be posted
+    List,
+
+    /// Post saved comments again, forgetting those that go through
+    Retry {
+        /// Draft to post (default: all of them)
+        id: Option<u64>,
+    },
+
+    /// Forget a draft without posting it
+    Discard {
+        /// Draft ID
+        id: u64,
+    },
+}
+
+/// What a draft comments on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DraftTarget {
+    Pr,
+    Issue,
+}
+
+impl DraftTarget {
+    fn noun(self) -> &'static str {
+        match self {
+            DraftTarget::Pr => "pull request",
+            DraftTarget::Issue => "issue",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Draft {
+    pub id: u64,
+    pub target: DraftTarget,
+    /// `workspace/repo`
+    pub repository: String,
+    /// Pull request or issue ID
+    pub number: u64,
+    pub body: String,
+    /// Why the last attempt failed
+    pub error: String,
+    /// Whether the comment may have been posted despite the error
+    #[serde(default)]
+    pub maybe_posted: bool,
+    pub saved_on: DateTime<Utc>,
+}
+
+impl Porcelain for Draft {
+    fn porcelain(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+#[derive(Tabled)]
+struct DraftRow {
+    #[tabled(rename = "ID")]
+    id: u64,
+    #[tabled(rename = "ON")]
+    on: String,
+    #[tabled(rename = "SAVED")]
+    saved: String,
+    #[tabled(rename = "COMMENT")]
+    comment: String,
+    #[tabled(rename = "ERROR")]
+    error: String,
+}
+
+impl From<&Draft> for DraftRow {
+    fn from(draft: &Draft) -> Self {
+        Self {
+            id: draft.id,
+            on: format!(
+                "{} {}#{}",
+                draft.target.noun(),
+                draft.repository,
+                draft.number
+            ),
+            saved: format::date(&draft.saved_on),
+            comment: format::one_line(&draft.body),
+            error: draft.error.clone(),
+        }
+    }
+}
+
+impl DraftsCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            DraftsCommands::List => {
+                let drafts = load()?;
+                if output::print(&drafts)? {
+                    return Ok(());
+                }
+                if drafts.is_empty() {
+                    output::note("No drafts");
+                    return Ok(());
+                }
+                output::table(drafts.iter().map(DraftRow::from).collect())
+            }
+
+            DraftsCommands::Retry { id } => {
+                let mut drafts = load()?;
+                if let Some(id) = id
+                    && !drafts.iter().any(|d| d.id == id)
+                {
+                    anyhow::bail!(UsageError(format!("No draft {}", id)));
+                }
+                if drafts.is_empty() {
+                    output::note("No drafts");
+                    return Ok(());
+                }
+
+                let client = BitbucketClient::from_stored().await?;
+                let mut posted = Vec::new();
+                for draft in drafts.iter_mut().filter(|d| id.is_none_or(|id| d.id == id)) {
+                    if draft.maybe_posted {
+                        output::note(format!(
+                            "{} Draft {} may already be on {} #{}; look for a duplicate",
+                            Icon::Warning.glyph().yellow(),
+                            draft.id,
+                            draft.target.noun(),
+                            draft.number
+                        ));
+                    }
+                    match post(&client, draft).await {
+                        Ok(()) => {
+                            output::success(format!(
+                                "Posted draft {} on {} #{}",
+                                draft.id,
+                                draft.target.noun(),
+                                draft.number
+                            ));
+                            posted.push(draft.id);
+                        }
+                        Err(e) => {
+                            output::note(format!(
+                                "{} Draft {} failed again: {:#}",
+                                Icon::Error.glyph().red(),
+                                draft.id,
+                                e
+                            ));
+                            draft.error = format!("{:#}", e);
+                            draft.maybe_posted |= e
+                                .downcast_ref::<crate::Error>()
+                                .is_none_or(|e| !turned_away(e));
+                        }
+                    }
+                }
+                drafts.retain(|d| !posted.contains(&d.id));
+                write(&drafts)?;
+
+                let failed = match id {
+                    Some(_) => 1 - posted.len(),
+                    None => drafts.len(),
+                };
+                if failed > 0 {
+                    anyhow::bail!("{} draft(s) could not be posted and were kept", failed);
+                }
+                Ok(())
+            }
+
+            DraftsCommands::Discard { id } => {
+                let mut drafts = load()?;
+                let before = drafts.len();
+                drafts.retain(|d| d.id != id);
+                if drafts.len() == before {
+                    anyhow::bail!(UsageError(format!("No draft {}", id)));
+                }
+                write(&drafts)?;
+                output::success(format!("Discarded draft {}", id));
+                Ok(())
+            }
+        }
+    }
+}
+
+async fn post(client: &BitbucketClient, draft: &Draft) -> Result<()> {
+    let (workspace, repo_slug) = draft
+        .repository
+        .split_once('/')
+        .with_context(|| format!("Draft {} has no valid repository", draft.id))?;
+    match draft.target {
+        DraftTarget::Pr => {
+            client
+                .add_pr_comment(workspace, repo_slug, draft.number, &draft.body, None)
+                .await?;
+        }
+        DraftTarget::Issue => {
+            client
+                .add_issue_comment(workspace, repo_slug, draft.number, &draft.body)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+fn path() -> Result<PathBuf> {
+    Ok(Config::state_dir()?.join(FILE))
+}
+
+/// Saved drafts, oldest first
+pub fn load() -> Result<Vec<Draft>> {
+    let path = path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("{} is corrupt", path.display()))
+}
+
+fn write(drafts: &[Draft]) -> Result<()> {
+    let path = path()?;
+    xdg::ensure_dir(&Config::state_dir()?)?;
+    fs::write(&path, serde_json::to_string_pretty(drafts)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Whether `error` shows the API refused the request before acting on it:
+/// no connection was made, or it answered that it's rate limiting or
+/// unavailable. A timeout or a gateway error may come after the comment
+/// was saved.
+fn turned_away(error: &crate::Error) -> bool {
+    match error {
+        crate::Error::RateLimited => true,
+        crate::Error::Api { status, .. } => *status == StatusCode::SERVICE_UNAVAILABLE,
+        crate::Error::Http(e) => e.is_connect(),
+        _ => false,
+    }
+}
+
+/// Keep a comment that failed to post when sending it again could work,
+/// and say how to retry it
+pub fn keep_on_failure(
+    error: crate::Error,
+    target: DraftTarget,
+    workspace: &str,
+    repo_slug: &str,
+    number: u64,
+    body: &str,
+) -> anyhow::Error {
+    if !error.is_transient() {
+        return error.into();
+    }
+    let saved = load().and_then(|mut drafts| {
+        let id = drafts.iter().map(|d| d.id).max().unwrap_or(0) + 1;
+        drafts.push(Draft {
+            id,
+            target,
+            repository: format!("{}/{}", workspace, repo_slug),
+            number,
+            body: body.to_string(),
+            error: error.to_string(),
+            maybe_posted: !turned_away(&error),
+            saved_on: Utc::now(),
+        });
+        write(&drafts).map(|()| id)
+    });
+    match saved {
+        Ok(id) => {
+            let mut message = format!(
+                "The comment was saved as draft {}; post it with `bitbucket drafts retry {}`",
+                id, id
+            );
+            if !turned_away(&error) {
+                message.push_str(", but it may have been posted anyway, so check first");
+            }
+            output::note(message);
+            error.into()
+        }
+        Err(e) => {
+            tracing::warn!("could not save draft: {:#}", e);
+            error.into()
+        }
+    }
+}
@@ -0,0 +1,318 @@
+//! Extensions
+//!
+//! Any subcommand the CLI does not know, `bitbucket foo ...`, runs an
+//! executable named `bitbucket-foo`: first from an installed extension, then
+//! from `PATH`. The extension receives the remaining arguments and the
+//! current credential, API URL, and `--workspace`/`--repo` in the environment
+//! (see [`dispatch`]), so it can call the API or `bitbucket` itself.
+//!
+//! Installed extensions are git repositories cloned into
+//! `$XDG_DATA_HOME/bitbucket-cli/extensions/bitbucket-<name>`, each containing
+//! an executable of the same name.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use colored::Colorize;
+use tabled::Tabled;
+
+use super::{UsageError, output};
+use crate::api::{self, BitbucketClient};
+use crate::config::{Config, xdg};
+
+const PREFIX: &str = "bitbucket-";
+
+#[derive(Subcommand)]
+pub enum ExtCommands {
+    /// Install an extension from a git repository
+    Install {
+        /// Repository as workspace/repo (on Bitbucket) or any URL git can clone.
+        /// Its name must start with "bitbucket-".
+        source: String,
+    },
+
+    /// List installed extensions
+    List,
+
+    /// Remove an installed extension
+    Remove {
+        /// Extension name, with or without the "bitbucket-" prefix
+        name: String,
+    },
+
+    /// Update installed extensions with git pull
+    Upgrade {
+        /// Extension to upgrade (default: all)
+        name: Option<String>,
+    },
+}
+
+#[derive(Tabled)]
+struct ExtensionRow {
+    #[tabled(rename = "NAME")]
+    name: String,
+    #[tabled(rename = "SOURCE")]
+    source: String,
+}
+
+impl ExtCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            ExtCommands::Install { source } => {
+                let url = clone_url(&source);
+                let name = repo_name(&url);
+                if !name.starts_with(PREFIX) || name.len() == PREFIX.len() {
+                    anyhow::bail!(UsageError(format!(
+                        "Extension repositories must be named '{}<name>', got '{}'",
+                        PREFIX, name
+                    )));
+                }
+
+                let dir = extensions_dir()?;
+                xdg::ensure_dir(&dir)?;
+                let target = dir.join(&name);
+                if target.exists() {
+                    anyhow::bail!(
+                        "Extension '{}' is already installed. Use 'bitbucket ext upgrade' to update it.",
+                        command_name(&name)
+                    );
+                }
+
+                git(None, &["clone", "--depth", "1", &url, &path_arg(&target)?])?;
+
+                if !target.join(&name).is_file() {
+                    let _ = std::fs::remove_dir_all(&target);
+                    anyhow::bail!("Repository has no '{}' executable at its root", name);
+                }
+
+                output::success(format!(
+                    "Installed extension {}. Run it with 'bitbucket {}'.",
+                    command_name(&name).cyan(),
+                    command_name(&name)
+                ));
+                Ok(())
+            }
+
+            ExtCommands::List => {
+                let rows: Vec<ExtensionRow> = installed()?
+                    .into_iter()
+                    .map(|dir| ExtensionRow {
+                        name: dir_name(&dir),
+                        source: remote_url(&dir).unwrap_or_else(|| "-".to_string()),
+                    })
+                    .collect();
+
+                if rows.is_empty() {
+                    output::note("No extensions installed");
+                    return Ok(());
+                }
+
+                output::table(rows)
+            }
+
+            ExtCommands::Remove { name } => {
+                let dir = installed_dir(&name)?;
+                std::fs::remove_dir_all(&dir)
+                    .with_context(|| format!("Failed to remove {}", dir.display()))?;
+
+                output::success(format!("Removed extension {}", command_name(&name)));
+                Ok(())
+            }
+
+            ExtCommands::Upgrade { name } => {
+                let dirs = match name {
+                    Some(name) => vec![installed_dir(&name)?],
+                    None => installed()?,
+                };
+
+                for dir in dirs {
+                    git(Some(&dir), &["pull", "--ff-only", "--quiet"])?;
+                    output::success(format!("Upgraded extension {}", dir_name(&dir)));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Run the extension named by `args[0]` with the remaining arguments, and
+/// return its exit code.
+///
+/// Besides the inherited environment, the extension gets:
+/// - `BITBUCKET_ACCESS_TOKEN`, or `BITBUCKET_USERNAME` and `BITBUCKET_API_KEY`,
+///   when signed in
+/// - `BITBUCKET_API_URL`
+/// - `BITBUCKET_WORKSPACE` and `BITBUCKET_REPO` from the global flags
+pub async fn dispatch(
+    args: Vec<String>,
+    workspace: Option<String>,
+    repo: Option<String>,
+) -> Result<i32> {
+    let (name, rest) = args.split_first().context("No command given")?;
+    let executable = find(name).ok_or_else(|| {
+        UsageError(format!(
+            "Unknown command '{}'. No '{}{}' extension is installed or on PATH.",
+            name, PREFIX, name
+        ))
+    })?;
+
+    let mut command = Command::new(&executable);
+    command
+        .args(rest)
+        .env("BITBUCKET_API_URL", api::default_base_url());
+
+    // Extensions that don't touch the API must still run when signed out
+    match BitbucketClient::from_stored().await {
+        Ok(client) => {
+            command.envs(client.credential().to_env());
+        }
+        Err(e) => tracing::debug!(error = %e, "running extension without credentials"),
+    }
+    if let Some(workspace) = workspace {
+        command.env("BITBUCKET_WORKSPACE", workspace);
+    }
+    if let Some(repo) = repo {
+        command.env("BITBUCKET_REPO", repo);
+    }
+
+    tracing::debug!(executable = %executable.display(), "running extension");
+    let status = command
+        .status()
+        .with_context(|| format!("Failed to run {}", executable.display()))?;
+
+    // A signal-terminated extension has no code; report it like a shell would
+    Ok(status.code().unwrap_or(128))
+}
+
+/// Get the directory extensions are installed in
+pub fn extensions_dir() -> Result<PathBuf> {
+    Ok(Config::data_dir()?.join("extensions"))
+}
+
+/// Locate `bitbucket-<name>`, preferring installed extensions over PATH
+fn find(name: &str) -> Option<PathBuf> {
+    let file = format!("{}{}", PREFIX, name);
+    let installed = extensions_dir().ok()?.join(&file).join(&file);
+    if installed.is_file() {
+        return Some(installed);
+    }
+
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .flat_map(|dir| executable_candidates(&dir, &file))
+        .find(|path| path.is_file())
+}
+
+fn executable_candidates(dir: &Path, file: &str) -> Vec<PathBuf> {
+    if cfg!(windows) {
+        vec![dir.join(format!("{}.exe", file)), dir.join(file)]
+    } else {
+        vec![dir.join(file)]
+    }
+}
+
+fn installed() -> Result<Vec<PathBuf>> {
+    let dir = extensions_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_dir() && dir_name(path).starts_with(PREFIX))
+        .collect();
+    dirs.sort();
+    Ok(dirs)
+}
+
+fn installed_dir(name: &str) -> Result<PathBuf> {
+    let dir = extensions_dir()?.join(format!("{}{}", PREFIX, command_name(name)));
+    if !dir.is_dir() {
+        anyhow::bail!("Extension '{}' is not installed", command_name(name));
+    }
+    Ok(dir)
+}
+
+/// Expand `workspace/repo` to a Bitbucket clone URL; pass anything else through
+fn clone_url(source: &str) -> String {
+    let is_shorthand = source.split('/').count() == 2
+        && !source.contains(':')
+        && !source.starts_with('.')
+        && !Path::new(source).exists();
+    if is_shorthand {
+        format!("https://bitbucket.org/{}.git", source)
+    } else {
+        source.to_string()
+    }
+}
+
+fn repo_name(url: &str) -> String {
+    let last = url
+        .trim_end_matches('/')
+        .rsplit(['/', ':', '\\'])
+        .next()
+        .unwrap_or_default();
+    last.trim_end_matches(".git").to_string()
+}
+
+fn command_name(name: &str) -> &str {
+    name.strip_prefix(PREFIX).unwrap_or(name)
+}
+
+fn dir_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn path_arg(path: &Path) -> Result<String> {
+    path.to_str()
+        .map(String::from)
+        .context("Extension directory path is not valid UTF-8")
+}
+
+fn remote_url(dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["config", "--get", "remote.origin.url"])
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn git(dir: Option<&Path>, args: &[&str]) -> Result<()> {
+    let mut command = Command::new("git");
+    command.args(args);
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    tracing::debug!(?args, "running git");
+
+    let status = command.status().context("Failed to run git")?;
+    if !status.success() {
+        anyhow::bail!("git {} failed", args[0]);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources_resolve_to_clone_urls_and_names() {
+        let url = clone_url("acme/bitbucket-stale");
+        assert_eq!(url, "https://bitbucket.org/acme/bitbucket-stale.git");
+        assert_eq!(repo_name(&url), "bitbucket-stale");
+        assert_eq!(
+            repo_name("git@bitbucket.org:acme/bitbucket-stale.git"),
+            "bitbucket-stale"
+        );
+        assert_eq!(clone_url("git@host:x/y.git"), "git@host:x/y.git");
+    }
+}
@@ -0,0 +1,140 @@
+//! Bounded-concurrency fan-out for commands that act on many repositories
+//!
+//! [`run`] drives one async job per item with at most `concurrency` in
+//! flight, shows a progress bar on a terminal, and collects failures instead
+//! of stopping at the first one. [`Outcome::finish`] then reports what failed
+//! and turns any failure into the command's error.
+
+use std::fmt::Display;
+use std::future::Future;
+
+use anyhow::Result;
+use colored::Colorize;
+use futures::{StreamExt, stream};
+use indicatif::{ProgressBar, ProgressStyle};
+
+use super::icons::Icon;
+use super::output;
+
+/// Jobs in flight at once unless a command picks its own limit
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Results of a fan-out, in the order the items were given
+pub struct Outcome<K, T> {
+    pub succeeded: Vec<(K, T)>,
+    pub failed: Vec<(K, anyhow::Error)>,
+    /// Items run, still right after `succeeded` is drained
+    total: usize,
+}
+
+impl<K: Display, T> Outcome<K, T> {
+    /// Print each failure to stderr and fail with a summary if there were
+    /// any; otherwise hand back the successful results. A lone item's error
+    /// is returned as is, keeping its exit code.
+    pub fn finish(mut self, verb: &str, noun: &str) -> Result<Vec<(K, T)>> {
+        if self.failed.is_empty() {
+            return Ok(self.succeeded);
+        }
+        if self.total == 1 {
+            let (_, error) = self.failed.remove(0);
+            return Err(error);
+        }
+
+        for (item, error) in &self.failed {
+            eprintln!("{} {}: {:#}", Icon::Error.glyph().red(), item, error);
+        }
+        anyhow::bail!(
+            "Failed to {} {} of {} {}",
+            verb,
+            self.failed.len(),
+            self.total,
+            noun
+        )
+    }
+}
+
+/// Run `job` for every item with bounded parallelism, labelling the progress
+/// bar with `label`
+pub async fn run<K, T, F, Fut>(
+    label: &str,
+    items: Vec<K>,
+    concurrency: usize,
+    job: F,
+) -> Outcome<K, T>
+where
+    K: Clone,
+    F: Fn(K) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let progress = progress_bar(label, items.len());
+
+    let results: Vec<(K, Result<T>)> = stream::iter(items)
+        .map(|item| {
+            let future = job(item.clone());
+            let progress = &progress;
+            async move {
+                let result = future.await;
+                progress.inc(1);
+                (item, result)
+            }
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+    progress.finish_and_clear();
+
+    let mut outcome = Outcome {
+        succeeded: Vec::new(),
+        failed: Vec::new(),
+        total: results.len(),
+    };
+    for (item, result) in results {
+        match result {
+            Ok(value) => outcome.succeeded.push((item, value)),
+            Err(error) => outcome.failed.push((item, error)),
+        }
+    }
+    outcome
+}
+
+/// A progress bar on stderr, hidden when piped, under `--quiet`, or for a
+/// single item
+fn progress_bar(label: &str, len: usize) -> ProgressBar {
+    if len < 2 || !output::is_tty() || output::is_quiet() {
+        return ProgressBar::hidden();
+    }
+
+    let progress = ProgressBar::new(len as u64);
+    progress.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.blue} {msg} [{bar:30}] {pos}/{len}")
+            .unwrap()
+            .progress_chars("=> "),
+    );
+    progress.set_message(label.to_string());
+    progress
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn failures_are_collected_in_order() {
+        let outcome = run("Checking", vec![1, 2, 3, 4], 2, |n| async move {
+            if n % 2 == 0 {
+                anyhow::bail!("even")
+            }
+            Ok(n * 10)
+        })
+        .await;
+
+        assert_eq!(outcome.succeeded, [(1, 10), (3, 30)]);
+        assert_eq!(
+            outcome.failed.iter().map(|(n, _)| *n).collect::<Vec<_>>(),
+            [2, 4]
+        );
+        let error = outcome.finish("check", "numbers").unwrap_err();
+        assert_eq!(error.to_string(), "Failed to check 2 of 4 numbers");
+    }
+}
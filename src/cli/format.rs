@@ -0,0 +1,157 @@
+//! Display settings shared by every command
+//!
+//! [`init`] applies `[display]` from the config once at startup: it turns
+//! `colored` output off when `color = false`, `NO_COLOR` is set, or stdout is
+//! not a terminal, picks the [`icons`](super::icons) set, and picks how
+//! [`date`] renders timestamps. `date_format`
+//! takes a `strftime` pattern, shown in local time, or `relative` for
+//! "2 hours ago" style dates.
+
+use std::sync::OnceLock;
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+
+use super::{icons, output};
+use crate::config::DisplayConfig;
+use crate::error::{Error, Result};
+
+/// `date_format` value selecting relative dates
+pub const RELATIVE: &str = "relative";
+
+static DATE_FORMAT: OnceLock<String> = OnceLock::new();
+
+/// Apply display settings for the rest of the process. A `date_format` that
+/// isn't a valid `strftime` pattern is a config error, since rendering with
+/// it would panic.
+pub fn init(display: &DisplayConfig) -> Result<()> {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    if !display.color || no_color || !output::is_tty() {
+        colored::control::set_override(false);
+    }
+    icons::init(display.icons);
+    validate_date_format(&display.date_format)?;
+    let _ = DATE_FORMAT.set(display.date_format.clone());
+    Ok(())
+}
+
+fn validate_date_format(format: &str) -> Result<()> {
+    if format != RELATIVE && StrftimeItems::new(format).any(|item| item == Item::Error) {
+        return Err(Error::Config(format!(
+            "[display] date_format '{}' is not a valid strftime pattern",
+            format
+        )));
+    }
+    Ok(())
+}
+
+/// Render a timestamp per `[display] date_format`
+pub fn date(at: &DateTime<Utc>) -> String {
+    let format = DATE_FORMAT.get_or_init(|| DisplayConfig::default().date_format);
+    if format == RELATIVE {
+        relative(at, &Utc::now())
+    } else {
+        at.with_timezone(&Local).format(format).to_string()
+    }
+}
+
+/// `text` squeezed onto one line, for a table cell
+pub fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Parse a date given on the command line: `YYYY-MM-DD` (midnight UTC) or
+/// an RFC 3339 timestamp
+pub fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Some(at.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|day| day.and_hms_opt(0, 0, 0))
+        .map(|at| at.and_utc())
+}
+
+/// Parse an age given on the command line: a count of hours, days or weeks
+/// such as `36h`, `90d` or `2w`
+pub fn parse_age(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (split, _) = value.char_indices().last()?;
+    let (count, unit) = value.split_at(split);
+    let count: i64 = count.parse().ok().filter(|count| *count >= 0)?;
+    match unit {
+        "h" => Duration::try_hours(count),
+        "d" => Duration::try_days(count),
+        "w" => Duration::try_weeks(count),
+        _ => None,
+    }
+}
+
+/// Parse a point in time given on the command line: an age before now, as
+/// for [`parse_age`], or a date, as for [`parse_date`]
+pub fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    parse_age(value)
+        .map(|age| Utc::now() - age)
+        .or_else(|| parse_date(value))
+}
+
+/// Describe how long before (or after) `now` a timestamp is
+pub fn relative(at: &DateTime<Utc>, now: &DateTime<Utc>) -> String {
+    let delta = *now - *at;
+    let seconds = delta.num_seconds().abs();
+
+    let (count, unit) = match seconds {
+        0..60 => return "just now".to_string(),
+        60..3_600 => (seconds / 60, "minute"),
+        3_600..86_400 => (seconds / 3_600, "hour"),
+        86_400..2_592_000 => (seconds / 86_400, "day"),
+        2_592_000..31_536_000 => (seconds / 2_592_000, "month"),
+        _ => (seconds / 31_536_000, "year"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+
+    if delta.num_seconds() >= 0 {
+        format!("{} {}{} ago", count, unit, plural)
+    } else {
+        format!("in {} {}{}", count, unit, plural)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_date_formats_are_config_errors() {
+        assert!(validate_date_format("%Y-%m-%d %H:%M").is_ok());
+        assert!(validate_date_format(RELATIVE).is_ok());
+        assert!(matches!(
+            validate_date_format("%Y-%Q"),
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn relative_dates() {
+        let now = Utc::now();
+        let ago = |d: Duration| relative(&(now - d), &now);
+
+        assert_eq!(ago(Duration::seconds(5)), "just now");
+        assert_eq!(ago(Duration::minutes(1)), "1 minute ago");
+        assert_eq!(ago(Duration::hours(2)), "2 hours ago");
+        assert_eq!(ago(Duration::days(3)), "3 days ago");
+        assert_eq!(ago(Duration::days(65)), "2 months ago");
+        assert_eq!(ago(Duration::days(800)), "2 years ago");
+        assert_eq!(ago(Duration::hours(-5)), "in 5 hours");
+    }
+
+    #[test]
+    fn ages() {
+        assert_eq!(parse_age("90d"), Some(Duration::days(90)));
+        assert_eq!(parse_age("2w"), Some(Duration::weeks(2)));
+        assert_eq!(parse_age("36h"), Some(Duration::hours(36)));
+        assert_eq!(parse_age("90"), None);
+        assert_eq!(parse_age("-1d"), None);
+        assert_eq!(parse_age("d"), None);
+    }
+}
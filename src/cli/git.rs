@@ -0,0 +1,182 @@
+//! Context from the local git checkout
+
+use std::process::Command;
+
+use anyhow::{Context, Result};
+
+use super::{UsageError, output, recent};
+use crate::config::Config;
+
+/// Run git and return its trimmed stdout, or `None` if it failed
+fn capture(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Run git with its output captured, failing with git's message
+pub fn run(args: &[&str]) -> Result<()> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Whether a local branch exists
+pub fn has_branch(name: &str) -> bool {
+    capture(&[
+        "rev-parse",
+        "--verify",
+        "--quiet",
+        &format!("refs/heads/{}", name),
+    ])
+    .is_some()
+}
+
+/// The Bitbucket repository (workspace, slug) the `origin` remote points at
+pub fn origin_repo() -> Option<(String, String)> {
+    parse_remote(&capture(&["remote", "get-url", "origin"])?)
+}
+
+/// The repository given, otherwise the one the `origin` remote of the
+/// current checkout points at, otherwise `[defaults] workspace` and
+/// `repository` from the config
+pub fn repo_or_origin(repo: Option<String>) -> Result<(String, String)> {
+    if let Some(repo) = repo {
+        return parse_repo(&repo);
+    }
+    if let Some((workspace, slug)) = origin_repo() {
+        recent::touch(&workspace, &slug);
+        return Ok((workspace, slug));
+    }
+    if let Some((workspace, slug)) = configured_repo()? {
+        output::note(format!(
+            "Using {}/{} from [defaults] in the config",
+            workspace, slug
+        ));
+        recent::touch(&workspace, &slug);
+        return Ok((workspace, slug));
+    }
+    anyhow::bail!(UsageError(
+        "No repository given, the current directory is not a Bitbucket checkout, and \
+         [defaults] in the config sets no workspace and repository. \
+         Pass workspace/repo-slug."
+            .to_string()
+    ))
+}
+
+/// Split `workspace/repo-slug`, where `-` is the repository used last, and
+/// remember it as used when the command line named it
+pub fn parse_repo(repo: &str) -> Result<(String, String)> {
+    let (workspace, slug) = if repo == "-" {
+        split_repo(&recent::last()?)?
+    } else {
+        split_repo(repo)?
+    };
+    recent::touch_named(repo, &workspace, &slug);
+    Ok((workspace, slug))
+}
+
+fn split_repo(repo: &str) -> Result<(String, String)> {
+    match repo.split_once('/') {
+        Some((workspace, slug)) if !slug.contains('/') => {
+            Ok((workspace.to_string(), slug.to_string()))
+        }
+        _ => anyhow::bail!(UsageError(format!(
+            "Invalid repository format. Expected 'workspace/repo-slug', got '{}'",
+            repo
+        ))),
+    }
+}
+
+/// `[defaults] repository`, as `workspace/slug` or a slug in
+/// `[defaults] workspace`
+fn configured_repo() -> Result<Option<(String, String)>> {
+    let defaults = Config::load()?.defaults;
+    let Some(repository) = defaults.repository else {
+        return Ok(None);
+    };
+    if repository.contains('/') {
+        return split_repo(&repository).map(Some);
+    }
+    Ok(defaults.workspace.map(|workspace| (workspace, repository)))
+}
+
+/// The checked-out branch, or `None` on a detached HEAD
+pub fn current_branch() -> Option<String> {
+    capture(&["symbolic-ref", "--quiet", "--short", "HEAD"])
+}
+
+/// The commit hash of HEAD
+pub fn head_commit() -> Option<String> {
+    capture(&["rev-parse", "HEAD"])
+}
+
+/// Absolute path of the repository root
+pub fn root() -> Option<String> {
+    capture(&["rev-parse", "--show-toplevel"])
+}
+
+/// Path of the current directory relative to the repository root, with a
+/// trailing slash (empty at the root)
+pub fn prefix() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--show-prefix"])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Extract (workspace, slug) from a bitbucket.org HTTPS or SSH remote URL
+pub fn parse_remote(url: &str) -> Option<(String, String)> {
+    let (_, path) = url.split_once("bitbucket.org")?;
+    let path = path
+        .trim_start_matches([':', '/'])
+        .trim_end_matches('/')
+        .trim_end_matches(".git");
+
+    match path.split('/').collect::<Vec<_>>()[..] {
+        [workspace, slug] if !workspace.is_empty() && !slug.is_empty() => {
+            Some((workspace.to_string(), slug.to_string()))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remotes_resolve_to_workspace_and_slug() {
+        let expected = Some(("acme".to_string(), "engine".to_string()));
+        assert_eq!(
+            parse_remote("https://bitbucket.org/acme/engine.git"),
+            expected
+        );
+        assert_eq!(
+            parse_remote("https://ada@bitbucket.org/acme/engine"),
+            expected
+        );
+        assert_eq!(parse_remote("git@bitbucket.org:acme/engine.git"), expected);
+        assert_eq!(
+            parse_remote("ssh://git@bitbucket.org/acme/engine.git"),
+            expected
+        );
+        assert_eq!(parse_remote("git@github.com:acme/engine.git"), None);
+    }
+}
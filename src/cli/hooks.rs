@@ -0,0 +1,108 @@
+//! User scripts run after commands succeed
+//!
+//! An executable in the config directory's `hooks/` named after an event,
+//! such as `post-pr-create`, runs once that command has done its work, with
+//! a JSON description of what happened on stdin and the event name in
+//! `BITBUCKET_HOOK`. Its output goes to stderr so it can't mix with the
+//! command's own. Like notifications, a failing hook is a warning, never the
+//! command's error.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+
+use super::icons::Icon;
+use crate::config::Config;
+
+/// Events with a hook, named as the executable is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    PostPrCreate,
+    PostMerge,
+    PostPipelineTrigger,
+}
+
+impl Hook {
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::PostPrCreate => "post-pr-create",
+            Hook::PostMerge => "post-merge",
+            Hook::PostPipelineTrigger => "post-pipeline-trigger",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Payload<'a, T: Serialize> {
+    event: &'static str,
+    repository: &'a str,
+    #[serde(flatten)]
+    details: &'a T,
+}
+
+/// The directory hooks are looked up in
+pub fn dir() -> Result<PathBuf> {
+    Ok(Config::config_dir()?.join("hooks"))
+}
+
+/// Run the hook for `hook`, if there is one, with the fields of `details`
+/// (a JSON object) added to what it reads
+pub async fn run<T: Serialize>(hook: Hook, repository: &str, details: &T) {
+    let path = match dir() {
+        Ok(dir) => dir.join(hook.name()),
+        Err(_) => return,
+    };
+    if !path.is_file() {
+        return;
+    }
+
+    let payload = Payload {
+        event: hook.name(),
+        repository,
+        details,
+    };
+    if let Err(e) = execute(&path, hook, &payload).await {
+        tracing::warn!(hook = hook.name(), error = %e, "hook failed");
+        eprintln!(
+            "{} Hook {} failed: {:#}",
+            Icon::Warning.glyph().yellow(),
+            hook.name(),
+            e
+        );
+    }
+}
+
+async fn execute<T: Serialize>(path: &Path, hook: Hook, payload: &Payload<'_, T>) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(path)?.permissions().mode();
+        if mode & 0o111 == 0 {
+            anyhow::bail!("{} is not executable; run chmod +x on it", path.display());
+        }
+    }
+
+    let input = serde_json::to_vec(payload)?;
+    tracing::debug!(hook = hook.name(), path = %path.display(), "running hook");
+    let mut child = tokio::process::Command::new(path)
+        .env("BITBUCKET_HOOK", hook.name())
+        .stdin(Stdio::piped())
+        .stdout(std::io::stderr())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", path.display()))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that ignores stdin may exit before reading it
+        let _ = stdin.write_all(&input).await;
+    }
+    let status = child.wait().await?;
+    if !status.success() {
+        anyhow::bail!("exited with {}", status);
+    }
+    Ok(())
+}
@@ -0,0 +1,190 @@
+//! Glyphs for statuses and kinds of item, per `[display] icons`
+//!
+//! Some terminals draw emoji two cells wide (or as boxes), which misaligns
+//! tables and TUI lists. Everything that marks a status or an item's kind
+//! asks for an [`Icon`] instead of writing the glyph itself, so switching
+//! to `nerd-font` or `ascii` changes them all at once.
+
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::config::IconSet;
+
+static SET: OnceLock<IconSet> = OnceLock::new();
+
+/// Use `set` for the rest of the process
+pub fn init(set: IconSet) {
+    let _ = SET.set(set);
+}
+
+fn current() -> IconSet {
+    SET.get().copied().unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Icon {
+    // Marks in front of messages and list entries
+    Ok,
+    Error,
+    Warning,
+    /// An in-progress step
+    Active,
+    /// A step or item with nothing to report
+    Inactive,
+    /// A row marked for a batch action
+    Marked,
+    Unmarked,
+    Vote,
+    Watch,
+    /// An item updated since it was last looked at
+    Unread,
+
+    // Kinds of item
+    Private,
+    Public,
+    Repository,
+    PullRequest,
+    Issues,
+    Pipelines,
+    Bug,
+    Enhancement,
+    Proposal,
+    Task,
+    Other,
+
+    // Pipeline states
+    Pending,
+    Running,
+    Passed,
+    Failed,
+    Halted,
+    Paused,
+    Neutral,
+}
+
+impl Icon {
+    /// This icon in the configured set
+    pub fn glyph(self) -> &'static str {
+        self.in_set(current())
+    }
+
+    pub fn in_set(self, set: IconSet) -> &'static str {
+        match set {
+            IconSet::Emoji => self.emoji(),
+            IconSet::NerdFont => self.nerd_font(),
+            IconSet::Ascii => self.ascii(),
+        }
+    }
+
+    fn emoji(self) -> &'static str {
+        match self {
+            Icon::Ok => "✓",
+            Icon::Error => "✗",
+            Icon::Warning => "⚠",
+            Icon::Active => "◉",
+            Icon::Inactive => "○",
+            Icon::Marked => "●",
+            Icon::Unmarked => "○",
+            Icon::Vote => "▲",
+            Icon::Watch => "👁",
+            Icon::Unread => "●",
+            Icon::Private => "🔒",
+            Icon::Public => "🌐",
+            Icon::Repository => "📁",
+            Icon::PullRequest => "🔀",
+            Icon::Issues | Icon::Bug => "🐛",
+            Icon::Pipelines => "⚙️",
+            Icon::Enhancement => "✨",
+            Icon::Proposal => "💡",
+            Icon::Task => "📋",
+            Icon::Other => "•",
+            Icon::Pending => "⏳",
+            Icon::Running => "🔄",
+            Icon::Passed => "✅",
+            Icon::Failed => "❌",
+            Icon::Halted => "⛔",
+            Icon::Paused => "⏸️",
+            Icon::Neutral => "⚪",
+        }
+    }
+
+    fn nerd_font(self) -> &'static str {
+        match self {
+            Icon::Ok | Icon::Passed => "\u{f00c}",
+            Icon::Error | Icon::Failed => "\u{f00d}",
+            Icon::Warning => "\u{f071}",
+            Icon::Active | Icon::Marked => "\u{f111}",
+            Icon::Inactive | Icon::Unmarked | Icon::Neutral => "\u{f10c}",
+            Icon::Vote => "\u{f062}",
+            Icon::Watch => "\u{f06e}",
+            Icon::Unread => "\u{f111}",
+            Icon::Private => "\u{f023}",
+            Icon::Public => "\u{f0ac}",
+            Icon::Repository => "\u{f401}",
+            Icon::PullRequest => "\u{f407}",
+            Icon::Issues => "\u{f41b}",
+            Icon::Pipelines => "\u{f013}",
+            Icon::Bug => "\u{f188}",
+            Icon::Enhancement => "\u{f0d0}",
+            Icon::Proposal => "\u{f0eb}",
+            Icon::Task => "\u{f0ae}",
+            Icon::Other => "\u{f111}",
+            Icon::Pending => "\u{f017}",
+            Icon::Running => "\u{f021}",
+            Icon::Halted => "\u{f05e}",
+            Icon::Paused => "\u{f04c}",
+        }
+    }
+
+    fn ascii(self) -> &'static str {
+        match self {
+            Icon::Ok | Icon::Passed => "+",
+            Icon::Error | Icon::Failed => "x",
+            Icon::Warning => "!",
+            Icon::Active | Icon::Running => "~",
+            Icon::Inactive | Icon::Neutral | Icon::Other => "-",
+            Icon::Marked => "*",
+            Icon::Unmarked => " ",
+            Icon::Vote => "^",
+            Icon::Watch => "@",
+            Icon::Unread => "*",
+            Icon::Private => "P",
+            Icon::Public => " ",
+            Icon::Repository => "R",
+            Icon::PullRequest => "PR",
+            Icon::Issues | Icon::Bug => "B",
+            Icon::Pipelines => "CI",
+            Icon::Enhancement => "E",
+            Icon::Proposal => "?",
+            Icon::Task => "T",
+            Icon::Pending => ".",
+            Icon::Halted => "#",
+            Icon::Paused => "=",
+        }
+    }
+}
+
+impl fmt::Display for Icon {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.glyph())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_icons_are_ascii() {
+        for icon in [
+            Icon::Ok,
+            Icon::Bug,
+            Icon::Private,
+            Icon::Paused,
+            Icon::Watch,
+        ] {
+            assert!(icon.in_set(IconSet::Ascii).is_ascii(), "{:?}", icon);
+        }
+        assert_eq!(Icon::Passed.in_set(IconSet::Emoji), "✅");
+    }
+}
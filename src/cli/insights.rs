@@ -0,0 +1,589 @@
+//! Code Insights reports
+//!
+//! CI tools publish a report per commit (pass/fail plus a few metrics) and
+//! line annotations that Bitbucket shows in pull request diffs. Findings can
+//! be read from a SARIF log, the format clippy (via `clippy-sarif`), CodeQL,
+//! Semgrep and most security scanners produce, or from a JSON array of
+//! annotations in the API's own format.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::{Subcommand, ValueEnum};
+use colored::Colorize;
+use serde::Deserialize;
+use tabled::Tabled;
+
+use super::git::parse_repo;
+use super::{UsageError, format, git, output};
+use crate::api::BitbucketClient;
+use crate::models::{
+    Annotation, AnnotationResult, AnnotationType, CreateReportRequest, ReportData, ReportResult,
+    ReportType, Severity,
+};
+
+#[derive(Subcommand)]
+pub enum InsightsCommands {
+    /// Publish and list Code Insights reports
+    Report {
+        #[command(subcommand)]
+        command: ReportCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ReportCommands {
+    /// List reports on a commit
+    List {
+        /// Repository in format workspace/repo-slug
+        repo: String,
+
+        /// Commit hash
+        commit: String,
+    },
+
+    /// Create or replace a report on a commit
+    Create {
+        /// Repository in format workspace/repo-slug
+        repo: String,
+
+        /// Commit hash
+        commit: String,
+
+        /// Report ID, unique per commit (e.g. "clippy")
+        #[arg(long)]
+        id: String,
+
+        /// Report title
+        #[arg(short, long)]
+        title: String,
+
+        /// Kind of report
+        #[arg(long = "type", value_enum, default_value = "bug")]
+        report_type: ReportTypeArg,
+
+        /// Overall result (default: failed if --sarif has errors, else passed)
+        #[arg(long, value_enum)]
+        result: Option<ReportResultArg>,
+
+        /// Longer description
+        #[arg(short, long)]
+        details: Option<String>,
+
+        /// Name of the tool that produced the report
+        #[arg(long)]
+        reporter: Option<String>,
+
+        /// Link to the full report
+        #[arg(long)]
+        link: Option<String>,
+
+        /// Metric to show, as TITLE=VALUE (e.g. "Coverage=87.5%"); repeatable
+        #[arg(long = "data", value_name = "TITLE=VALUE")]
+        data: Vec<String>,
+
+        /// Also publish the findings in this SARIF file as annotations
+        #[arg(long)]
+        sarif: Option<PathBuf>,
+    },
+
+    /// Add annotations to an existing report
+    Annotate {
+        /// Repository in format workspace/repo-slug
+        repo: String,
+
+        /// Commit hash
+        commit: String,
+
+        /// Report ID
+        #[arg(long)]
+        id: String,
+
+        /// SARIF file to convert into annotations
+        #[arg(long, conflicts_with = "json", required_unless_present = "json")]
+        sarif: Option<PathBuf>,
+
+        /// JSON file with an array of annotations in the API's format
+        #[arg(long)]
+        json: Option<PathBuf>,
+    },
+}
+
+#[derive(ValueEnum, Clone)]
+pub enum ReportTypeArg {
+    Security,
+    Coverage,
+    Test,
+    Bug,
+}
+
+impl From<ReportTypeArg> for ReportType {
+    fn from(report_type: ReportTypeArg) -> Self {
+        match report_type {
+            ReportTypeArg::Security => ReportType::Security,
+            ReportTypeArg::Coverage => ReportType::Coverage,
+            ReportTypeArg::Test => ReportType::Test,
+            ReportTypeArg::Bug => ReportType::Bug,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone)]
+pub enum ReportResultArg {
+    Passed,
+    Failed,
+    Pending,
+}
+
+impl From<ReportResultArg> for ReportResult {
+    fn from(result: ReportResultArg) -> Self {
+        match result {
+            ReportResultArg::Passed => ReportResult::Passed,
+            ReportResultArg::Failed => ReportResult::Failed,
+            ReportResultArg::Pending => ReportResult::Pending,
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct ReportRow {
+    #[tabled(rename = "ID")]
+    id: String,
+    #[tabled(rename = "TITLE")]
+    title: String,
+    #[tabled(rename = "RESULT")]
+    result: String,
+    #[tabled(rename = "REPORTER")]
+    reporter: String,
+    #[tabled(rename = "UPDATED")]
+    updated: String,
+}
+
+impl InsightsCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            InsightsCommands::Report { command } => command.run().await,
+        }
+    }
+}
+
+impl ReportCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            ReportCommands::List { repo, commit } => {
+                let (workspace, repo_slug) = parse_repo(&repo)?;
+                let client = BitbucketClient::from_stored().await?;
+
+                let reports = client
+                    .list_reports(&workspace, &repo_slug, &commit)
+                    .await?
+                    .values;
+
+                if output::print(&reports)? {
+                    return Ok(());
+                }
+
+                if reports.is_empty() {
+                    output::note("No reports found");
+                    return Ok(());
+                }
+
+                let rows: Vec<ReportRow> = reports
+                    .iter()
+                    .map(|r| ReportRow {
+                        id: r.external_id.clone().unwrap_or_default(),
+                        title: r.title.clone(),
+                        result: r
+                            .result
+                            .as_ref()
+                            .map(|result| match result {
+                                ReportResult::Passed => result.to_string().green().to_string(),
+                                ReportResult::Failed => result.to_string().red().to_string(),
+                                _ => result.to_string(),
+                            })
+                            .unwrap_or_default(),
+                        reporter: r.reporter.clone().unwrap_or_default(),
+                        updated: r.updated_on.map(|d| format::date(&d)).unwrap_or_default(),
+                    })
+                    .collect();
+
+                output::table(rows)
+            }
+
+            ReportCommands::Create {
+                repo,
+                commit,
+                id,
+                title,
+                report_type,
+                result,
+                details,
+                reporter,
+                link,
+                data,
+                sarif,
+            } => {
+                let (workspace, repo_slug) = parse_repo(&repo)?;
+                let data = data
+                    .iter()
+                    .map(|item| parse_data(item))
+                    .collect::<Result<Vec<_>>>()?;
+                let findings = sarif.as_deref().map(read_sarif).transpose()?;
+
+                let failed = findings.as_ref().is_some_and(|f| {
+                    f.annotations
+                        .iter()
+                        .any(|a| a.severity.as_ref() >= Some(&Severity::High))
+                });
+                let result = match result {
+                    Some(result) => result.into(),
+                    None if failed => ReportResult::Failed,
+                    None => ReportResult::Passed,
+                };
+
+                let request = CreateReportRequest {
+                    title,
+                    details: details.or_else(|| findings.as_ref().map(Findings::summary)),
+                    report_type: report_type.into(),
+                    reporter: reporter.or_else(|| findings.as_ref().and_then(|f| f.tool.clone())),
+                    link,
+                    result,
+                    data,
+                };
+
+                let client = BitbucketClient::from_stored().await?;
+                let report = client
+                    .put_report(&workspace, &repo_slug, &commit, &id, &request)
+                    .await?;
+
+                if let Some(findings) = &findings {
+                    client
+                        .add_annotations(
+                            &workspace,
+                            &repo_slug,
+                            &commit,
+                            &id,
+                            &findings.annotations,
+                        )
+                        .await?;
+                }
+
+                if output::print(&report)? {
+                    return Ok(());
+                }
+
+                output::success(format!(
+                    "Published report {} ({}) on commit {}",
+                    id,
+                    request.result,
+                    short(&commit)
+                ));
+                if let Some(findings) = &findings {
+                    output::success(format!(
+                        "Added {}",
+                        annotation_count(findings.annotations.len())
+                    ));
+                }
+
+                Ok(())
+            }
+
+            ReportCommands::Annotate {
+                repo,
+                commit,
+                id,
+                sarif,
+                json,
+            } => {
+                let (workspace, repo_slug) = parse_repo(&repo)?;
+                let annotations = match (sarif, json) {
+                    (Some(path), _) => read_sarif(&path)?.annotations,
+                    (None, Some(path)) => {
+                        let text = std::fs::read_to_string(&path)
+                            .with_context(|| format!("Failed to read {}", path.display()))?;
+                        serde_json::from_str(&text)
+                            .with_context(|| format!("Invalid annotations in {}", path.display()))?
+                    }
+                    (None, None) => unreachable!("clap requires --sarif or --json"),
+                };
+
+                let client = BitbucketClient::from_stored().await?;
+                client
+                    .add_annotations(&workspace, &repo_slug, &commit, &id, &annotations)
+                    .await?;
+
+                output::success(format!(
+                    "Added {} to report {}",
+                    annotation_count(annotations.len()),
+                    id
+                ));
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Parse `TITLE=VALUE`, typing the value as Bitbucket displays it
+fn parse_data(item: &str) -> Result<ReportData> {
+    let (title, value) = item
+        .split_once('=')
+        .ok_or_else(|| UsageError(format!("Invalid --data '{}'. Expected TITLE=VALUE", item)))?;
+
+    let (data_type, value) = if let Some(percent) = value.strip_suffix('%') {
+        match percent.parse::<f64>() {
+            Ok(n) => ("PERCENTAGE", serde_json::json!(n)),
+            Err(_) => ("TEXT", serde_json::json!(value)),
+        }
+    } else if let Ok(b) = value.parse::<bool>() {
+        ("BOOLEAN", serde_json::json!(b))
+    } else if let Ok(n) = value.parse::<f64>() {
+        ("NUMBER", serde_json::json!(n))
+    } else if value.starts_with("http://") || value.starts_with("https://") {
+        ("LINK", serde_json::json!({ "text": title, "href": value }))
+    } else {
+        ("TEXT", serde_json::json!(value))
+    };
+
+    Ok(ReportData {
+        title: title.to_string(),
+        data_type: data_type.to_string(),
+        value,
+    })
+}
+
+/// Annotations converted from a SARIF log
+struct Findings {
+    tool: Option<String>,
+    annotations: Vec<Annotation>,
+}
+
+impl Findings {
+    fn summary(&self) -> String {
+        let count = self.annotations.len();
+        let plural = if count == 1 { "" } else { "s" };
+        match &self.tool {
+            Some(tool) => format!("{} found {} issue{}", tool, count, plural),
+            None => format!("{} issue{} found", count, plural),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SarifLog {
+    #[serde(default)]
+    runs: Vec<SarifRun>,
+}
+
+#[derive(Deserialize)]
+struct SarifRun {
+    tool: Option<SarifTool>,
+    #[serde(default)]
+    results: Vec<SarifResult>,
+}
+
+#[derive(Deserialize)]
+struct SarifTool {
+    driver: SarifDriver,
+}
+
+#[derive(Deserialize)]
+struct SarifDriver {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id: Option<String>,
+    level: Option<String>,
+    message: SarifMessage,
+    #[serde(default)]
+    locations: Vec<SarifLocation>,
+}
+
+#[derive(Deserialize)]
+struct SarifMessage {
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation {
+    physical_location: Option<SarifPhysicalLocation>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifPhysicalLocation {
+    artifact_location: Option<SarifArtifactLocation>,
+    region: Option<SarifRegion>,
+}
+
+#[derive(Deserialize)]
+struct SarifArtifactLocation {
+    uri: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRegion {
+    start_line: Option<u32>,
+}
+
+fn read_sarif(path: &Path) -> Result<Findings> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let log: SarifLog = serde_json::from_str(&text)
+        .with_context(|| format!("Invalid SARIF in {}", path.display()))?;
+    Ok(convert_sarif(log, git::root().as_deref()))
+}
+
+/// Convert SARIF results to annotations. Paths are made relative to
+/// `repo_root`, since Bitbucket matches annotations to diff paths.
+fn convert_sarif(log: SarifLog, repo_root: Option<&str>) -> Findings {
+    let tool = log
+        .runs
+        .iter()
+        .find_map(|run| run.tool.as_ref().map(|t| t.driver.name.clone()));
+
+    let annotations = log
+        .runs
+        .into_iter()
+        .flat_map(|run| run.results)
+        .enumerate()
+        .map(|(i, result)| {
+            let location = result
+                .locations
+                .into_iter()
+                .find_map(|l| l.physical_location);
+            let path = location
+                .as_ref()
+                .and_then(|l| l.artifact_location.as_ref())
+                .and_then(|a| a.uri.as_deref())
+                .map(|uri| relative_path(uri, repo_root));
+            let line = location
+                .as_ref()
+                .and_then(|l| l.region.as_ref())
+                .and_then(|r| r.start_line);
+
+            // SARIF's default level is "warning"
+            let (annotation_type, severity) = match result.level.as_deref() {
+                Some("error") => (AnnotationType::Bug, Severity::High),
+                Some("note") | Some("none") => (AnnotationType::CodeSmell, Severity::Low),
+                _ => (AnnotationType::CodeSmell, Severity::Medium),
+            };
+
+            let message = result.message.text.unwrap_or_default();
+            let summary = match &result.rule_id {
+                Some(rule) => format!("{}: {}", rule, first_line(&message)),
+                None => first_line(&message).to_string(),
+            };
+
+            Annotation {
+                external_id: format!(
+                    "{}-{}",
+                    result.rule_id.as_deref().unwrap_or("finding"),
+                    i + 1
+                ),
+                annotation_type,
+                // The API caps summaries at 450 characters
+                summary: summary.chars().take(450).collect(),
+                details: (message.lines().count() > 1).then_some(message),
+                path,
+                line,
+                severity: Some(severity),
+                result: Some(AnnotationResult::Failed),
+                link: None,
+            }
+        })
+        .collect();
+
+    Findings { tool, annotations }
+}
+
+fn relative_path(uri: &str, repo_root: Option<&str>) -> String {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let path = match repo_root {
+        Some(root) => path
+            .strip_prefix(root)
+            .map(|p| p.trim_start_matches('/'))
+            .unwrap_or(path),
+        None => path,
+    };
+    path.trim_start_matches("./").to_string()
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines().next().unwrap_or_default()
+}
+
+fn annotation_count(count: usize) -> String {
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{} annotation{}", count, plural)
+}
+
+/// Abbreviate a commit hash for messages
+fn short(hash: &str) -> &str {
+    &hash[..hash.len().min(12)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sarif_results_become_annotations() {
+        let log: SarifLog = serde_json::from_value(serde_json::json!({
+            "runs": [{
+                "tool": { "driver": { "name": "clippy" } },
+                "results": [
+                    {
+                        "ruleId": "clippy::unwrap_used",
+                        "level": "error",
+                        "message": { "text": "used `unwrap()` on a `Result`\nhelp: handle it" },
+                        "locations": [{
+                            "physicalLocation": {
+                                "artifactLocation": { "uri": "file:///work/engine/src/mill.rs" },
+                                "region": { "startLine": 42 }
+                            }
+                        }]
+                    },
+                    { "message": { "text": "no location" } }
+                ]
+            }]
+        }))
+        .unwrap();
+
+        let findings = convert_sarif(log, Some("/work/engine"));
+        assert_eq!(findings.summary(), "clippy found 2 issues");
+
+        let first = &findings.annotations[0];
+        assert_eq!(first.external_id, "clippy::unwrap_used-1");
+        assert_eq!(
+            first.summary,
+            "clippy::unwrap_used: used `unwrap()` on a `Result`"
+        );
+        assert_eq!(first.path.as_deref(), Some("src/mill.rs"));
+        assert_eq!(first.line, Some(42));
+        assert_eq!(first.severity, Some(Severity::High));
+        assert!(first.details.is_some());
+
+        let second = &findings.annotations[1];
+        assert_eq!(second.external_id, "finding-2");
+        assert_eq!(second.severity, Some(Severity::Medium));
+        assert_eq!(second.path, None);
+    }
+
+    #[test]
+    fn data_values_are_typed() {
+        assert_eq!(
+            parse_data("Coverage=87.5%").unwrap().data_type,
+            "PERCENTAGE"
+        );
+        assert_eq!(parse_data("Safe=true").unwrap().data_type, "BOOLEAN");
+        assert_eq!(parse_data("Issues=3").unwrap().data_type, "NUMBER");
+        assert_eq!(parse_data("Tool=clippy 0.1").unwrap().data_type, "TEXT");
+        assert!(parse_data("nope").is_err());
+    }
+}
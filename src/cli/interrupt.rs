@@ -0,0 +1,30 @@
+//! Ctrl-C handling
+//!
+//! By default Ctrl-C aborts the process straight away (see `main`). A
+//! command that has to clean up first, such as removing a temporary webhook,
+//! holds a [`Deferred`] guard while it waits on `tokio::signal::ctrl_c`
+//! itself; the global handler then leaves the interrupt to it.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static DEFERRED: AtomicUsize = AtomicUsize::new(0);
+
+/// While alive, Ctrl-C is left to the holder instead of exiting
+pub struct Deferred(());
+
+impl Drop for Deferred {
+    fn drop(&mut self) {
+        DEFERRED.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Handle Ctrl-C in the caller until the guard is dropped
+pub fn defer() -> Deferred {
+    DEFERRED.fetch_add(1, Ordering::SeqCst);
+    Deferred(())
+}
+
+/// Whether a command is handling Ctrl-C itself
+pub fn is_deferred() -> bool {
+    DEFERRED.load(Ordering::SeqCst) > 0
+}
@@ -1,46 +1,119 @@
-use anyhow::Result;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
 use clap::{Subcommand, ValueEnum};
 use colored::Colorize;
-use tabled::{Table, Tabled};
-
+use futures::{StreamExt, TryStreamExt, stream};
+use serde::Serialize;
+use tabled::Tabled;
+
+use super::browse::WEB_URL;
+use super::drafts::{self, DraftTarget};
+use super::git::parse_repo;
+use super::icons::Icon;
+use super::last_run::UpdatedSince;
+use super::output::{Porcelain, ReportFormat};
+use super::range::DateRange;
+use super::{UsageError, clipboard, confirm, format, git, label, markdown, output, triage, user};
 use crate::api::BitbucketClient;
+use crate::config::LabelStrategy;
 use crate::models::{
-    CreateIssueRequest, IssueContentRequest, IssueKind, IssuePriority, IssueState,
+    CreateIssueRequest, Issue, IssueComment, IssueContentRequest, IssueKind, IssuePriority,
+    IssueState, UserAccountId,
 };
 
 #[derive(Subcommand)]
 pub enum IssueCommands {
     /// List issues
     List {
-        /// Repository in format workspace/repo-slug
-        repo: String,
+        /// Repository in format workspace/repo-slug (default: the current
+        /// checkout's, then `[defaults]` in the config)
+        repo: Option<String>,
 
-        /// Filter by state
+        /// Filter by state (default: all)
         #[arg(short, long, value_enum)]
         state: Option<IssueStateArg>,
 
         /// Number of results
         #[arg(short, long, default_value = "25")]
         limit: u32,
+
+        /// Fetch every page instead of stopping at --limit
+        #[arg(long, conflicts_with = "limit")]
+        all: bool,
+
+        /// Only issues with this label (repeatable)
+        #[arg(long = "label", value_name = "LABEL")]
+        labels: Vec<String>,
+
+        /// Only issues assigned to this user (account ID, UUID or @me)
+        #[arg(long, value_name = "USER")]
+        assignee: Option<String>,
+
+        /// Only issues last updated in this window
+        #[command(flatten)]
+        range: DateRange,
+
+        #[command(flatten)]
+        updated_since: UpdatedSince,
+
+        /// Open the issue list in the browser instead, filtered by --state;
+        /// the web list can't take the other filters
+        #[arg(
+            long,
+            conflicts_with_all = ["labels", "assignee", "since", "until", "updated_since"]
+        )]
+        web: bool,
     },
 
     /// View issue details
     View {
-        /// Repository in format workspace/repo-slug
+        /// Repository in format workspace/repo-slug, or the issue ID to use
+        /// the inferred repository
         repo: String,
 
         /// Issue ID
-        id: u64,
+        id: Option<u64>,
 
         /// Open in browser
         #[arg(short, long)]
         web: bool,
+
+        /// Print the web URL instead, without fetching the issue
+        #[arg(long, conflicts_with_all = ["web", "follow"])]
+        url: bool,
+
+        /// Also show the comment thread
+        #[arg(long)]
+        comments: bool,
+
+        /// Show the body as written instead of rendering its Markdown
+        #[arg(long)]
+        raw: bool,
+
+        /// Keep printing new comments and state changes until Ctrl-C
+        /// (implies --comments)
+        #[arg(long, conflicts_with = "web")]
+        follow: bool,
+
+        /// Seconds between checks with --follow
+        #[arg(
+            long,
+            default_value = "15",
+            value_name = "SECONDS",
+            requires = "follow"
+        )]
+        interval: u64,
     },
 
     /// Create a new issue
     Create {
-        /// Repository in format workspace/repo-slug
-        repo: String,
+        /// Repository in format workspace/repo-slug (default: the current
+        /// checkout's, then `[defaults]` in the config)
+        repo: Option<String>,
 
         /// Issue title
         #[arg(short, long)]
@@ -57,15 +130,28 @@ pub enum IssueCommands {
         /// Issue priority
         #[arg(short, long, value_enum, default_value = "major")]
         priority: IssuePriorityArg,
+
+        /// Assign to this user (account ID, UUID or @me)
+        #[arg(long, value_name = "USER")]
+        assignee: Option<String>,
+
+        /// Attach a file once the issue is created (repeatable)
+        #[arg(long, value_name = "FILE")]
+        attach: Vec<PathBuf>,
+
+        /// Attach the image on the clipboard, e.g. a screenshot
+        #[arg(long)]
+        from_clipboard: bool,
     },
 
     /// Add a comment to an issue
     Comment {
-        /// Repository in format workspace/repo-slug
+        /// Repository in format workspace/repo-slug, or the issue ID to use
+        /// the inferred repository
         repo: String,
 
         /// Issue ID
-        id: u64,
+        id: Option<u64>,
 
         /// Comment text
         #[arg(short, long)]
@@ -74,25 +160,100 @@ pub enum IssueCommands {
 
     /// Close an issue
     Close {
-        /// Repository in format workspace/repo-slug
+        /// Repository in format workspace/repo-slug, or the issue ID to use
+        /// the inferred repository
         repo: String,
 
         /// Issue ID
-        id: u64,
+        id: Option<u64>,
+
+        /// Resolution to close with (default: closed)
+        #[arg(long = "as", value_enum)]
+        resolution: Option<Resolution>,
+
+        /// Comment to post with the state change
+        #[arg(short, long)]
+        comment: Option<String>,
+
+        /// Issue this one duplicates (implies --as duplicate)
+        #[arg(long, value_name = "ID")]
+        duplicate_of: Option<u64>,
     },
 
     /// Reopen an issue
     Reopen {
+        /// Repository in format workspace/repo-slug, or the issue ID to use
+        /// the inferred repository
+        repo: String,
+
+        /// Issue ID
+        id: Option<u64>,
+    },
+
+    /// Delete an issue
+    Delete {
+        /// Repository in format workspace/repo-slug, or the issue ID to use
+        /// the inferred repository
+        repo: String,
+
+        /// Issue ID
+        id: Option<u64>,
+    },
+
+    /// Step through new issues, setting kind, priority and assignee or
+    /// closing them with single keys
+    Triage {
+        /// Repository in format workspace/repo-slug (default: the current
+        /// checkout's, then `[defaults]` in the config)
+        repo: Option<String>,
+    },
+
+    /// Manage issue labels, stored per `[labels] strategy` in the config
+    Label {
+        #[command(subcommand)]
+        command: IssueLabelCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum IssueLabelCommands {
+    /// Add labels to an issue
+    Add {
         /// Repository in format workspace/repo-slug
         repo: String,
 
         /// Issue ID
         id: u64,
+
+        /// Labels to add
+        #[arg(required = true)]
+        labels: Vec<String>,
+    },
+
+    /// Remove labels from an issue
+    Remove {
+        /// Repository in format workspace/repo-slug
+        repo: String,
+
+        /// Issue ID
+        id: u64,
+
+        /// Labels to remove
+        #[arg(required = true)]
+        labels: Vec<String>,
+    },
+
+    /// List labels in use, with how many issues carry each
+    List {
+        /// Repository in format workspace/repo-slug (default: the current
+        /// checkout's, then `[defaults]` in the config)
+        repo: Option<String>,
     },
 }
 
 #[derive(ValueEnum, Clone)]
 pub enum IssueStateArg {
+    All,
     New,
     Open,
     Resolved,
@@ -103,21 +264,53 @@ pub enum IssueStateArg {
     Closed,
 }
 
-impl From<IssueStateArg> for IssueState {
-    fn from(state: IssueStateArg) -> Self {
-        match state {
-            IssueStateArg::New => IssueState::New,
-            IssueStateArg::Open => IssueState::Open,
-            IssueStateArg::Resolved => IssueState::Resolved,
-            IssueStateArg::OnHold => IssueState::OnHold,
-            IssueStateArg::Invalid => IssueState::Invalid,
-            IssueStateArg::Duplicate => IssueState::Duplicate,
-            IssueStateArg::Wontfix => IssueState::Wontfix,
-            IssueStateArg::Closed => IssueState::Closed,
+impl IssueStateArg {
+    /// The state to filter on, `None` for all of them
+    fn state(self) -> Option<IssueState> {
+        match self {
+            IssueStateArg::All => None,
+            IssueStateArg::New => Some(IssueState::New),
+            IssueStateArg::Open => Some(IssueState::Open),
+            IssueStateArg::Resolved => Some(IssueState::Resolved),
+            IssueStateArg::OnHold => Some(IssueState::OnHold),
+            IssueStateArg::Invalid => Some(IssueState::Invalid),
+            IssueStateArg::Duplicate => Some(IssueState::Duplicate),
+            IssueStateArg::Wontfix => Some(IssueState::Wontfix),
+            IssueStateArg::Closed => Some(IssueState::Closed),
+        }
+    }
+}
+
+/// How `issue close` resolves an issue
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Resolved,
+    Invalid,
+    Duplicate,
+    Wontfix,
+}
+
+impl From<Resolution> for IssueState {
+    fn from(resolution: Resolution) -> Self {
+        match resolution {
+            Resolution::Resolved => IssueState::Resolved,
+            Resolution::Invalid => IssueState::Invalid,
+            Resolution::Duplicate => IssueState::Duplicate,
+            Resolution::Wontfix => IssueState::Wontfix,
         }
     }
 }
 
+/// The comment posted when closing: `comment`, then the duplicated issue
+fn close_message(comment: Option<&str>, duplicate_of: Option<u64>) -> Option<String> {
+    let duplicate = duplicate_of.map(|id| format!("Duplicate of #{}", id));
+    match (comment.map(str::trim).filter(|c| !c.is_empty()), duplicate) {
+        (Some(comment), Some(duplicate)) => Some(format!("{}\n\n{}", comment, duplicate)),
+        (Some(comment), None) => Some(comment.to_string()),
+        (None, duplicate) => duplicate,
+    }
+}
+
 #[derive(ValueEnum, Clone)]
 pub enum IssueKindArg {
     Bug,
@@ -164,6 +357,8 @@ struct IssueRow {
     id: u64,
     #[tabled(rename = "TITLE")]
     title: String,
+    #[tabled(rename = "LABELS")]
+    labels: String,
     #[tabled(rename = "STATE")]
     state: String,
     #[tabled(rename = "KIND")]
@@ -172,48 +367,179 @@ struct IssueRow {
     priority: String,
 }
 
+impl IssueRow {
+    fn new(issue: &Issue, strategy: LabelStrategy) -> Self {
+        Self {
+            id: issue.id,
+            title: label::bare_title(issue, strategy).to_string(),
+            labels: label::of(issue, strategy).join(", "),
+            state: format_state(&issue.state),
+            kind: format!("{}", issue.kind),
+            priority: format_priority(&issue.priority),
+        }
+    }
+}
+
+/// How many issues carry a label
+#[derive(Serialize)]
+struct LabelUsage {
+    label: String,
+    issues: usize,
+}
+
+impl Porcelain for LabelUsage {
+    fn porcelain(&self) -> String {
+        self.label.clone()
+    }
+}
+
+#[derive(Tabled)]
+struct LabelRow {
+    #[tabled(rename = "LABEL")]
+    label: String,
+    #[tabled(rename = "ISSUES")]
+    issues: usize,
+}
+
 impl IssueCommands {
     pub async fn run(self) -> Result<()> {
         match self {
-            IssueCommands::List { repo, state, limit } => {
-                let (workspace, repo_slug) = parse_repo(&repo)?;
+            IssueCommands::List {
+                repo,
+                state,
+                limit,
+                all,
+                labels,
+                assignee,
+                range,
+                updated_since,
+                web,
+            } => {
+                let (workspace, repo_slug) = git::repo_or_origin(repo)?;
+                let state = state.and_then(IssueStateArg::state);
+                if web {
+                    let url = issues_url(&workspace, &repo_slug, state.as_ref());
+                    open::that(&url)?;
+                    println!("Opened {} in browser", url.cyan());
+                    return Ok(());
+                }
+                let repository = format!("{}/{}", workspace, repo_slug);
+                let range =
+                    updated_since.apply(range, "issue list", std::slice::from_ref(&repository))?;
+                let all = all || updated_since.wants_everything();
+                let mut filters: Vec<String> = range.filter("updated_on")?.into_iter().collect();
                 let client = BitbucketClient::from_stored().await?;
+                if let Some(assignee) = &assignee {
+                    let assignee = user::resolve(&client, assignee).await?;
+                    filters.push(user::filter("assignee", &assignee));
+                }
+                let query = (!filters.is_empty()).then(|| filters.join(" AND "));
+                let strategy = label::strategy()?;
+
+                // How many issues match in all, when the API says
+                let mut total = None;
+                let issues = if !labels.is_empty() {
+                    let mut filter = label_filter(state.clone(), &labels, strategy);
+                    if let Some(query) = &query {
+                        filter = format!("({}) AND {}", filter, query);
+                    }
+                    tracing::debug!(%filter, "searching issues by label");
+
+                    // The query narrows by substring; exact matching happens here
+                    let matching = client
+                        .search_issues(&workspace, &repo_slug, &filter)
+                        .try_filter(|issue| {
+                            let found = label::of(issue, strategy);
+                            futures::future::ready(
+                                labels.iter().all(|l| label::contains(&found, l)),
+                            )
+                        });
+                    if all {
+                        matching.boxed()
+                    } else {
+                        matching.take(limit as usize).boxed()
+                    }
+                } else if all {
+                    client
+                        .stream_issues(&workspace, &repo_slug, state.clone(), query.as_deref())
+                        .boxed()
+                } else {
+                    let page = client
+                        .list_issues(
+                            &workspace,
+                            &repo_slug,
+                            state.clone(),
+                            query.as_deref(),
+                            None,
+                            Some(limit),
+                        )
+                        .await?;
+                    total = page.size;
+                    stream::iter(page.values.into_iter().map(Ok)).boxed()
+                };
+                let mut newest = None;
+                let issues = issues.inspect_ok(|i| newest = newest.max(i.updated_on));
+
+                if all && output::streams() {
+                    let count =
+                        output::stream(issues, |issue| IssueRow::new(issue, strategy)).await?;
+                    if all {
+                        updated_since.listed("issue list", &repository, newest);
+                    }
+                    if count == 0 && !output::is_structured() {
+                        output::note("No issues found");
+                    }
+                    return Ok(());
+                }
+                let issues: Vec<Issue> = issues.try_collect().await?;
+                if all {
+                    updated_since.listed("issue list", &repository, newest);
+                }
 
-                let issues = client
-                    .list_issues(
-                        &workspace,
-                        &repo_slug,
-                        state.map(|s| s.into()),
-                        None,
-                        Some(limit),
-                    )
-                    .await?;
-
-                if issues.values.is_empty() {
-                    println!("No issues found");
+                if output::print(&issues)? {
                     return Ok(());
                 }
 
                 let rows: Vec<IssueRow> = issues
-                    .values
                     .iter()
-                    .map(|issue| IssueRow {
-                        id: issue.id,
-                        title: issue.title.chars().take(50).collect(),
-                        state: format_state(&issue.state),
-                        kind: format!("{}", issue.kind),
-                        priority: format_priority(&issue.priority),
-                    })
+                    .map(|issue| IssueRow::new(issue, strategy))
                     .collect();
+                if rows.is_empty() {
+                    output::note("No issues found");
+                    return Ok(());
+                }
 
-                let table = Table::new(rows).to_string();
-                println!("{}", table);
+                if output::format() == ReportFormat::Table {
+                    output::note(format!(
+                        "{}\n",
+                        list_summary(
+                            rows.len(),
+                            total,
+                            state.as_ref(),
+                            &format!("{}/{}", workspace, repo_slug)
+                        )
+                    ));
+                }
+                output::table(rows)?;
 
                 Ok(())
             }
 
-            IssueCommands::View { repo, id, web } => {
-                let (workspace, repo_slug) = parse_repo(&repo)?;
+            IssueCommands::View {
+                repo,
+                id,
+                web,
+                url,
+                comments,
+                raw,
+                follow,
+                interval,
+            } => {
+                let (workspace, repo_slug, id) = issue_target(repo, id)?;
+                if url {
+                    println!("{}/{}/{}/issues/{}", WEB_URL, workspace, repo_slug, id);
+                    return Ok(());
+                }
                 let client = BitbucketClient::from_stored().await?;
                 let issue = client.get_issue(&workspace, &repo_slug, id).await?;
 
@@ -228,63 +554,39 @@ impl IssueCommands {
                     anyhow::bail!("Could not find issue URL");
                 }
 
-                println!(
-                    "{} {} #{}",
-                    format_state(&issue.state),
-                    issue.title.bold(),
-                    issue.id
-                );
-                println!("{}", "─".repeat(60));
-
-                println!("{} {}", "Kind:".dimmed(), issue.kind);
-                println!(
-                    "{} {}",
-                    "Priority:".dimmed(),
-                    format_priority(&issue.priority)
-                );
-
-                if let Some(reporter) = &issue.reporter {
-                    println!("{} {}", "Reporter:".dimmed(), reporter.display_name);
-                }
-
-                if let Some(assignee) = &issue.assignee {
-                    println!("{} {}", "Assignee:".dimmed(), assignee.display_name);
+                // Under --jq/--template/--quiet, comments follow the issue
+                // as separate values
+                let filtered = output::print(&issue)?;
+                if !filtered {
+                    print_issue(&issue, raw)?;
                 }
 
-                println!(
-                    "{} {}",
-                    "Created:".dimmed(),
-                    issue.created_on.format("%Y-%m-%d %H:%M")
-                );
-
-                if let Some(updated) = issue.updated_on {
-                    println!(
-                        "{} {}",
-                        "Updated:".dimmed(),
-                        updated.format("%Y-%m-%d %H:%M")
-                    );
+                if !comments && !follow {
+                    return Ok(());
                 }
 
-                if let Some(votes) = issue.votes {
-                    if votes > 0 {
-                        println!("{} {}", "Votes:".dimmed(), votes);
+                let thread: Vec<IssueComment> = client
+                    .stream_issue_comments(&workspace, &repo_slug, id, None)
+                    .try_collect()
+                    .await?;
+                if filtered {
+                    for comment in &thread {
+                        output::print(comment)?;
                     }
-                }
-
-                if let Some(content) = &issue.content {
-                    if let Some(raw) = &content.raw {
-                        if !raw.is_empty() {
-                            println!();
-                            println!("{}", raw);
-                        }
+                } else {
+                    let shown: Vec<&IssueComment> = thread.iter().filter(|c| has_text(c)).collect();
+                    println!();
+                    println!("{}", format!("Comments ({})", shown.len()).bold());
+                    for comment in shown {
+                        print_comment(comment);
                     }
                 }
 
-                if let Some(links) = &issue.links {
-                    if let Some(html) = &links.html {
-                        println!();
-                        println!("{} {}", "URL:".dimmed(), html.href.cyan());
-                    }
+                if follow {
+                    follow_issue(
+                        &client, &workspace, &repo_slug, issue, &thread, interval, filtered,
+                    )
+                    .await?;
                 }
 
                 Ok(())
@@ -296,16 +598,47 @@ impl IssueCommands {
                 body,
                 kind,
                 priority,
+                assignee,
+                attach,
+                from_clipboard,
             } => {
-                let (workspace, repo_slug) = parse_repo(&repo)?;
+                let (workspace, repo_slug) = git::repo_or_origin(repo)?;
+
+                // Read everything first so a bad path doesn't leave an issue
+                // without its attachments
+                let mut attachments = Vec::new();
+                for path in &attach {
+                    let contents = std::fs::read(path)
+                        .with_context(|| format!("Failed to read {}", path.display()))?;
+                    let name = path
+                        .file_name()
+                        .map(|n| n.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| path.display().to_string());
+                    attachments.push((name, contents));
+                }
+                if from_clipboard {
+                    let name = format!("screenshot-{}.png", Utc::now().format("%Y%m%d-%H%M%S"));
+                    attachments.push((name, clipboard::image()?));
+                }
+
                 let client = BitbucketClient::from_stored().await?;
+                let assignee = match &assignee {
+                    Some(assignee) => {
+                        let found = user::resolve(&client, assignee).await?;
+                        let account_id = found.account_id.with_context(|| {
+                            format!("{} has no account ID to assign", found.display_name)
+                        })?;
+                        Some(UserAccountId { account_id })
+                    }
+                    None => None,
+                };
 
                 let request = CreateIssueRequest {
                     title,
                     content: body.map(|b| IssueContentRequest { raw: b }),
                     kind: Some(kind.into()),
                     priority: Some(priority.into()),
-                    assignee: None,
+                    assignee,
                     component: None,
                     milestone: None,
                     version: None,
@@ -315,32 +648,114 @@ impl IssueCommands {
                     .create_issue(&workspace, &repo_slug, &request)
                     .await?;
 
-                println!("{} Created issue #{}", "✓".green(), issue.id);
+                let mut failed = Vec::new();
+                for (name, contents) in &attachments {
+                    if let Err(e) = client
+                        .upload_issue_attachment(&workspace, &repo_slug, issue.id, name, contents)
+                        .await
+                    {
+                        failed.push(format!("{}: {}", name, e));
+                    }
+                }
+
+                if !output::print(&issue)? {
+                    output::success(format!("Created issue #{}", issue.id));
+                    let attached = attachments.len() - failed.len();
+                    if attached > 0 {
+                        output::success(format!(
+                            "Attached {} {}",
+                            attached,
+                            if attached == 1 { "file" } else { "files" }
+                        ));
+                    }
 
-                if let Some(links) = &issue.links {
-                    if let Some(html) = &links.html {
-                        println!("{} {}", "URL:".dimmed(), html.href.cyan());
+                    if let Some(links) = &issue.links {
+                        if let Some(html) = &links.html {
+                            println!("{} {}", "URL:".dimmed(), html.href.cyan());
+                        }
                     }
                 }
 
+                if !failed.is_empty() {
+                    anyhow::bail!(
+                        "Created issue #{}, but some attachments failed:\n  {}",
+                        issue.id,
+                        failed.join("\n  ")
+                    );
+                }
+
                 Ok(())
             }
 
             IssueCommands::Comment { repo, id, body } => {
-                let (workspace, repo_slug) = parse_repo(&repo)?;
+                let (workspace, repo_slug, id) = issue_target(repo, id)?;
                 let client = BitbucketClient::from_stored().await?;
 
                 client
                     .add_issue_comment(&workspace, &repo_slug, id, &body)
-                    .await?;
+                    .await
+                    .map_err(|e| {
+                        drafts::keep_on_failure(
+                            e,
+                            DraftTarget::Issue,
+                            &workspace,
+                            &repo_slug,
+                            id,
+                            &body,
+                        )
+                    })?;
+
+                output::success(format!("Added comment to issue #{}", id));
+
+                Ok(())
+            }
+
+            IssueCommands::Close {
+                repo,
+                id,
+                resolution,
+                comment,
+                duplicate_of,
+            } => {
+                let resolution = match (resolution, duplicate_of) {
+                    (Some(r), Some(_)) if r != Resolution::Duplicate => {
+                        return Err(UsageError(
+                            "--duplicate-of can only be used with --as duplicate".to_string(),
+                        )
+                        .into());
+                    }
+                    (None, Some(_)) => Some(Resolution::Duplicate),
+                    (r, _) => r,
+                };
+                let state = resolution.map_or(IssueState::Closed, IssueState::from);
+                let (workspace, repo_slug, id) = issue_target(repo, id)?;
+                let done = match resolution {
+                    Some(_) => format!("Closed issue #{} as {}", id, state),
+                    None => format!("Closed issue #{}", id),
+                };
 
-                println!("{} Added comment to issue #{}", "✓".green(), id);
+                let client = BitbucketClient::from_stored().await?;
+
+                match close_message(comment.as_deref(), duplicate_of) {
+                    Some(message) => {
+                        client
+                            .change_issue_state(&workspace, &repo_slug, id, state, &message)
+                            .await?;
+                    }
+                    None => {
+                        client
+                            .update_issue(&workspace, &repo_slug, id, None, None, Some(state))
+                            .await?;
+                    }
+                }
+
+                output::success(done);
 
                 Ok(())
             }
 
-            IssueCommands::Close { repo, id } => {
-                let (workspace, repo_slug) = parse_repo(&repo)?;
+            IssueCommands::Reopen { repo, id } => {
+                let (workspace, repo_slug, id) = issue_target(repo, id)?;
                 let client = BitbucketClient::from_stored().await?;
 
                 client
@@ -350,31 +765,202 @@ impl IssueCommands {
                         id,
                         None,
                         None,
-                        Some(IssueState::Closed),
+                        Some(IssueState::Open),
                     )
                     .await?;
 
-                println!("{} Closed issue #{}", "✓".green(), id);
+                output::success(format!("Reopened issue #{}", id));
 
                 Ok(())
             }
 
-            IssueCommands::Reopen { repo, id } => {
+            IssueCommands::Delete { repo, id } => {
+                let (workspace, repo_slug, id) = issue_target(repo, id)?;
+                if !confirm::confirm(format!(
+                    "Are you sure you want to delete issue #{} in {}? This cannot be undone!",
+                    id,
+                    format!("{}/{}", workspace, repo_slug).red()
+                ))? {
+                    output::note("Aborted");
+                    return Ok(());
+                }
+
+                let client = BitbucketClient::from_stored().await?;
+                client.delete_issue(&workspace, &repo_slug, id).await?;
+
+                output::success(format!("Deleted issue #{}", id));
+
+                Ok(())
+            }
+
+            IssueCommands::Triage { repo } => {
+                let (workspace, repo_slug) = git::repo_or_origin(repo)?;
+                triage::run(&workspace, &repo_slug).await
+            }
+
+            IssueCommands::Label { command } => command.run().await,
+        }
+    }
+}
+
+impl IssueLabelCommands {
+    pub async fn run(self) -> Result<()> {
+        let strategy = label::strategy()?;
+
+        match self {
+            IssueLabelCommands::Add { repo, id, labels } => {
+                label::check(&labels)?;
                 let (workspace, repo_slug) = parse_repo(&repo)?;
                 let client = BitbucketClient::from_stored().await?;
+                let issue = client.get_issue(&workspace, &repo_slug, id).await?;
 
-                client
-                    .update_issue(
-                        &workspace,
-                        &repo_slug,
-                        id,
-                        None,
-                        None,
-                        Some(IssueState::Open),
-                    )
+                let issue = match strategy {
+                    LabelStrategy::Title => {
+                        let (mut current, title) = label::split_title(&issue.title);
+                        for l in &labels {
+                            if !label::contains(&current, l) {
+                                current.push(l.clone());
+                            }
+                        }
+                        let title = label::join_title(&current, title);
+                        client
+                            .update_issue(&workspace, &repo_slug, id, Some(&title), None, None)
+                            .await?
+                    }
+                    LabelStrategy::Component => {
+                        let [l] = labels.as_slice() else {
+                            anyhow::bail!(UsageError(
+                                "The component label strategy allows one label per issue"
+                                    .to_string()
+                            ));
+                        };
+                        if let Some(previous) = &issue.component {
+                            if !previous.name.eq_ignore_ascii_case(l) {
+                                output::note(format!("Replacing label '{}'", previous.name));
+                            }
+                        }
+                        client
+                            .set_issue_component(&workspace, &repo_slug, id, Some(l))
+                            .await?
+                    }
+                };
+
+                if output::print(&issue)? {
+                    return Ok(());
+                }
+                output::success(format!(
+                    "Labels on issue #{}: {}",
+                    id,
+                    label::of(&issue, strategy).join(", ")
+                ));
+
+                Ok(())
+            }
+
+            IssueLabelCommands::Remove { repo, id, labels } => {
+                let (workspace, repo_slug) = parse_repo(&repo)?;
+                let client = BitbucketClient::from_stored().await?;
+                let issue = client.get_issue(&workspace, &repo_slug, id).await?;
+
+                let current = label::of(&issue, strategy);
+                let missing: Vec<&String> = labels
+                    .iter()
+                    .filter(|l| !label::contains(&current, l))
+                    .collect();
+                if missing.len() == labels.len() {
+                    output::note(format!("Issue #{} has none of those labels", id));
+                    return Ok(());
+                }
+
+                let issue = match strategy {
+                    LabelStrategy::Title => {
+                        let (current, title) = label::split_title(&issue.title);
+                        let kept: Vec<String> = current
+                            .into_iter()
+                            .filter(|c| !label::contains(&labels, c))
+                            .collect();
+                        let title = label::join_title(&kept, title);
+                        client
+                            .update_issue(&workspace, &repo_slug, id, Some(&title), None, None)
+                            .await?
+                    }
+                    LabelStrategy::Component => {
+                        client
+                            .set_issue_component(&workspace, &repo_slug, id, None)
+                            .await?
+                    }
+                };
+
+                if output::print(&issue)? {
+                    return Ok(());
+                }
+                let remaining = label::of(&issue, strategy);
+                output::success(format!(
+                    "Labels on issue #{}: {}",
+                    id,
+                    if remaining.is_empty() {
+                        "none".to_string()
+                    } else {
+                        remaining.join(", ")
+                    }
+                ));
+
+                Ok(())
+            }
+
+            IssueLabelCommands::List { repo } => {
+                let (workspace, repo_slug) = git::repo_or_origin(repo)?;
+                let client = BitbucketClient::from_stored().await?;
+
+                let issues: Vec<Issue> = client
+                    .stream_issues(&workspace, &repo_slug, None, None)
+                    .try_collect()
                     .await?;
 
-                println!("{} Reopened issue #{}", "✓".green(), id);
+                // Components exist before any issue uses them, so list those too
+                let mut usage: Vec<LabelUsage> = match strategy {
+                    LabelStrategy::Component => client
+                        .list_components(&workspace, &repo_slug)
+                        .await?
+                        .into_iter()
+                        .map(|c| LabelUsage {
+                            label: c.name,
+                            issues: 0,
+                        })
+                        .collect(),
+                    LabelStrategy::Title => Vec::new(),
+                };
+                for issue in &issues {
+                    for l in label::of(issue, strategy) {
+                        match usage.iter_mut().find(|u| u.label.eq_ignore_ascii_case(&l)) {
+                            Some(u) => u.issues += 1,
+                            None => usage.push(LabelUsage {
+                                label: l,
+                                issues: 1,
+                            }),
+                        }
+                    }
+                }
+                usage.sort_by(|a, b| b.issues.cmp(&a.issues).then(a.label.cmp(&b.label)));
+
+                if output::print(&usage)? {
+                    return Ok(());
+                }
+
+                if usage.is_empty() {
+                    output::note("No labels found");
+                    return Ok(());
+                }
+
+                output::table(
+                    usage
+                        .into_iter()
+                        .map(|u| LabelRow {
+                            label: u.label,
+                            issues: u.issues,
+                        })
+                        .collect(),
+                )?;
 
                 Ok(())
             }
@@ -382,15 +968,250 @@ impl IssueCommands {
     }
 }
 
-fn parse_repo(repo: &str) -> Result<(String, String)> {
-    let parts: Vec<&str> = repo.split('/').collect();
-    if parts.len() != 2 {
-        anyhow::bail!(
-            "Invalid repository format. Expected 'workspace/repo-slug', got '{}'",
-            repo
-        );
+/// "Showing 25 of 143 open issues in acme/engine"
+fn list_summary(
+    shown: usize,
+    total: Option<u32>,
+    state: Option<&IssueState>,
+    repo: &str,
+) -> String {
+    let state = state.map(|s| format!("{} ", s)).unwrap_or_default();
+    let count = match total {
+        Some(total) if total as usize > shown => format!("{} of {}", shown, total),
+        _ => shown.to_string(),
+    };
+    let noun = if shown == 1 && total.is_none_or(|t| t <= 1) {
+        "issue"
+    } else {
+        "issues"
+    };
+    format!("Showing {} {}{} in {}", count, state, noun, repo)
+}
+
+/// The repository's issue list on the web, filtered to `state`
+fn issues_url(workspace: &str, repo_slug: &str, state: Option<&IssueState>) -> String {
+    let mut url = format!("{}/{}/{}/issues", WEB_URL, workspace, repo_slug);
+    if let Some(state) = state {
+        url.push_str("?status=");
+        url.push_str(&state.to_string().replace(' ', "+"));
+    }
+    url
+}
+
+/// A query narrowing issues to those that may carry all `labels`
+fn label_filter(state: Option<IssueState>, labels: &[String], strategy: LabelStrategy) -> String {
+    let mut terms: Vec<String> = labels
+        .iter()
+        .map(|l| match strategy {
+            LabelStrategy::Title => format!("title ~ \"[{}]\"", l.replace('"', "\\\"")),
+            LabelStrategy::Component => {
+                format!("component.name = \"{}\"", l.replace('"', "\\\""))
+            }
+        })
+        .collect();
+    if let Some(state) = state {
+        terms.insert(0, format!("state = \"{}\"", state));
+    }
+    terms.join(" AND ")
+}
+
+/// Print an issue's details
+pub(super) fn print_issue(issue: &Issue, raw: bool) -> Result<()> {
+    println!(
+        "{} {} #{}",
+        format_state(&issue.state),
+        issue.title.bold(),
+        issue.id
+    );
+    print!("{}", output::rule(60));
+
+    println!("{} {}", "Kind:".dimmed(), issue.kind);
+    let labels = label::of(issue, label::strategy()?);
+    if !labels.is_empty() {
+        println!("{} {}", "Labels:".dimmed(), labels.join(", "));
+    }
+    println!(
+        "{} {}",
+        "Priority:".dimmed(),
+        format_priority(&issue.priority)
+    );
+
+    if let Some(reporter) = &issue.reporter {
+        println!("{} {}", "Reporter:".dimmed(), reporter.display_name);
+    }
+
+    if let Some(assignee) = &issue.assignee {
+        println!("{} {}", "Assignee:".dimmed(), assignee.display_name);
+    }
+
+    println!(
+        "{} {}",
+        "Created:".dimmed(),
+        format::date(&issue.created_on)
+    );
+
+    if let Some(updated) = issue.updated_on {
+        println!("{} {}", "Updated:".dimmed(), format::date(&updated));
+    }
+
+    if let Some(votes) = issue.votes {
+        if votes > 0 {
+            println!("{} {}", "Votes:".dimmed(), votes);
+        }
+    }
+
+    if let Some(content) = &issue.content {
+        if let Some(body) = &content.raw {
+            if !body.is_empty() {
+                println!();
+                println!("{}", markdown::display(body, raw));
+            }
+        }
+    }
+
+    if let Some(links) = &issue.links {
+        if let Some(html) = &links.html {
+            println!();
+            println!("{} {}", "URL:".dimmed(), html.href.cyan());
+        }
     }
-    Ok((parts[0].to_string(), parts[1].to_string()))
+    Ok(())
+}
+
+/// Comments without text are left by edits such as state changes
+fn has_text(comment: &IssueComment) -> bool {
+    comment
+        .content
+        .raw
+        .as_deref()
+        .is_some_and(|raw| !raw.trim().is_empty())
+}
+
+fn print_comment(comment: &IssueComment) {
+    println!();
+    println!(
+        "{} {} {}",
+        comment.user.display_name.bold(),
+        "·".dimmed(),
+        format::date(&comment.created_on).dimmed()
+    );
+    for line in comment.content.raw.as_deref().unwrap_or("").lines() {
+        println!("  {}", line);
+    }
+}
+
+/// Poll an issue until Ctrl-C, printing comments not in `thread` and
+/// changes to its state, assignee and title as they appear
+async fn follow_issue(
+    client: &BitbucketClient,
+    workspace: &str,
+    repo_slug: &str,
+    mut issue: Issue,
+    thread: &[IssueComment],
+    interval: u64,
+    filtered: bool,
+) -> Result<()> {
+    let mut seen: HashSet<u64> = thread.iter().map(|c| c.id).collect();
+    let mut since = thread
+        .iter()
+        .map(|c| c.created_on)
+        .max()
+        .unwrap_or(issue.created_on);
+
+    output::note(format!(
+        "{} Following issue #{}. Press Ctrl-C to stop.",
+        "→".blue(),
+        issue.id
+    ));
+
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(interval.max(1))) => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+
+        // A failed check shouldn't end a long watch; try again next time
+        let current = match client.get_issue(workspace, repo_slug, issue.id).await {
+            Ok(current) => current,
+            Err(e) => {
+                output::note(format!("{} {}", Icon::Warning.glyph().yellow(), e));
+                continue;
+            }
+        };
+        let changes = changes(&issue, &current);
+        if filtered {
+            if !changes.is_empty() {
+                output::print(&current)?;
+            }
+        } else {
+            for change in changes {
+                println!();
+                println!(
+                    "{} {} {}",
+                    Icon::Marked.glyph().yellow(),
+                    change,
+                    format::date(&current.updated_on.unwrap_or_else(Utc::now)).dimmed()
+                );
+            }
+        }
+        issue = current;
+
+        let new: Vec<IssueComment> = match client
+            .stream_issue_comments(workspace, repo_slug, issue.id, Some(since))
+            .try_collect()
+            .await
+        {
+            Ok(new) => new,
+            Err(e) => {
+                output::note(format!("{} {}", Icon::Warning.glyph().yellow(), e));
+                continue;
+            }
+        };
+        for comment in new.into_iter().filter(|c| seen.insert(c.id)) {
+            since = since.max(comment.created_on);
+            if filtered {
+                output::print(&comment)?;
+            } else if has_text(&comment) {
+                print_comment(&comment);
+            }
+        }
+    }
+}
+
+/// What changed between two snapshots of an issue
+fn changes(before: &Issue, after: &Issue) -> Vec<String> {
+    let mut changes = Vec::new();
+    if before.state != after.state {
+        changes.push(format!(
+            "State changed from {} to {}",
+            before.state, after.state
+        ));
+    }
+    let assignee = |issue: &Issue| issue.assignee.as_ref().map(|u| u.display_name.clone());
+    if assignee(before) != assignee(after) {
+        changes.push(match assignee(after) {
+            Some(name) => format!("Assigned to {}", name),
+            None => "Unassigned".to_string(),
+        });
+    }
+    if before.title != after.title {
+        changes.push(format!("Title changed to \"{}\"", after.title));
+    }
+    changes
+}
+
+/// The repository and issue from `[REPO] ID`, where a lone argument is the
+/// issue ID in the inferred repository
+fn issue_target(repo: String, id: Option<u64>) -> Result<(String, String, u64)> {
+    let (repo, id) = match id {
+        Some(id) => (Some(repo), id),
+        None => match repo.parse() {
+            Ok(id) => (None, id),
+            Err(_) => anyhow::bail!(UsageError(format!("Missing the issue ID after '{}'", repo))),
+        },
+    };
+    let (workspace, repo_slug) = git::repo_or_origin(repo)?;
+    Ok((workspace, repo_slug, id))
 }
 
 fn format_state(state: &IssueState) -> String {
@@ -403,6 +1224,7 @@ fn format_state(state: &IssueState) -> String {
         IssueState::Duplicate => "DUPLICATE".dimmed().to_string(),
         IssueState::Wontfix => "WONTFIX".dimmed().to_string(),
         IssueState::Closed => "CLOSED".purple().to_string(),
+        IssueState::Unknown => "UNKNOWN".dimmed().to_string(),
     }
 }
 
@@ -413,5 +1235,57 @@ fn format_priority(priority: &IssuePriority) -> String {
         IssuePriority::Major => "major".yellow().to_string(),
         IssuePriority::Critical => "critical".red().to_string(),
         IssuePriority::Blocker => "blocker".red().bold().to_string(),
+        IssuePriority::Unknown => "unknown".dimmed().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issue(state: &str, assignee: Option<&str>) -> Issue {
+        serde_json::from_value(serde_json::json!({
+            "id": 3,
+            "title": "Punched cards jam on reload",
+            "state": state,
+            "kind": "bug",
+            "priority": "major",
+            "assignee": assignee.map(|name| serde_json::json!({ "type": "user", "uuid": "{ada}", "display_name": name })),
+            "created_on": "2024-06-04T09:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn changes_describe_state_and_assignee() {
+        let before = issue("open", None);
+        assert!(changes(&before, &before).is_empty());
+        assert_eq!(
+            changes(&before, &issue("resolved", Some("Ada Lovelace"))),
+            [
+                "State changed from open to resolved",
+                "Assigned to Ada Lovelace"
+            ]
+        );
+        assert_eq!(
+            changes(&issue("open", Some("Ada Lovelace")), &before),
+            ["Unassigned"]
+        );
+    }
+
+    #[test]
+    fn list_summary_counts_against_the_total() {
+        assert_eq!(
+            list_summary(25, Some(143), Some(&IssueState::Open), "acme/engine"),
+            "Showing 25 of 143 open issues in acme/engine"
+        );
+        assert_eq!(
+            list_summary(1, Some(1), None, "acme/engine"),
+            "Showing 1 issue in acme/engine"
+        );
+        assert_eq!(
+            issues_url("acme", "engine", Some(&IssueState::OnHold)),
+            "https://bitbucket.org/acme/engine/issues?status=on+hold"
+        );
     }
 }
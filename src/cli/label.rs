@@ -0,0 +1,117 @@
+//! Labels for issues, which Bitbucket Cloud doesn't have
+//!
+//! Labels are stored according to `[labels] strategy`: as bracketed prefixes
+//! on the title (`[bug] [ui] Crash on start`, any number per issue), or as
+//! the issue's component (one per issue). Labels compare case-insensitively.
+
+use anyhow::Result;
+
+use super::UsageError;
+use crate::config::{Config, LabelStrategy};
+use crate::models::Issue;
+
+/// The configured strategy
+pub fn strategy() -> Result<LabelStrategy> {
+    Ok(Config::load()?.labels.strategy)
+}
+
+/// Refuse labels that couldn't be read back: blank ones, and ones with
+/// brackets, which would end a title prefix early
+pub fn check(labels: &[String]) -> Result<()> {
+    for label in labels {
+        if label.trim().is_empty() {
+            anyhow::bail!(UsageError("Labels can't be blank".to_string()));
+        }
+        if label.contains(['[', ']']) {
+            anyhow::bail!(UsageError(format!(
+                "Label '{}' can't contain '[' or ']'",
+                label
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// An issue's labels under `strategy`
+pub fn of(issue: &Issue, strategy: LabelStrategy) -> Vec<String> {
+    match strategy {
+        LabelStrategy::Title => split_title(&issue.title).0,
+        LabelStrategy::Component => issue
+            .component
+            .as_ref()
+            .map(|c| vec![c.name.clone()])
+            .unwrap_or_default(),
+    }
+}
+
+/// The title to show for an issue, without label prefixes
+pub fn bare_title(issue: &Issue, strategy: LabelStrategy) -> &str {
+    match strategy {
+        LabelStrategy::Title => split_title(&issue.title).1,
+        LabelStrategy::Component => &issue.title,
+    }
+}
+
+/// Whether `labels` includes `label`
+pub fn contains(labels: &[String], label: &str) -> bool {
+    labels.iter().any(|l| l.eq_ignore_ascii_case(label))
+}
+
+/// Split leading `[label]` prefixes off a title
+pub fn split_title(title: &str) -> (Vec<String>, &str) {
+    let mut labels = Vec::new();
+    let mut rest = title.trim_start();
+
+    while let Some(inner) = rest.strip_prefix('[') {
+        let Some((label, after)) = inner.split_once(']') else {
+            break;
+        };
+        let label = label.trim();
+        if label.is_empty() || label.contains('[') {
+            break;
+        }
+        labels.push(label.to_string());
+        rest = after.trim_start();
+    }
+
+    (labels, rest)
+}
+
+/// A title carrying exactly `labels` as prefixes
+pub fn join_title(labels: &[String], title: &str) -> String {
+    let mut joined: String = labels.iter().map(|l| format!("[{}] ", l)).collect();
+    joined.push_str(title);
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn title_prefixes_are_split_off() {
+        let (labels, title) = split_title("[bug] [ UI ][] Crash on start");
+        assert_eq!(labels, ["bug", "UI"]);
+        assert_eq!(title, "[] Crash on start");
+
+        let (labels, title) = split_title("Array [0] is empty");
+        assert!(labels.is_empty());
+        assert_eq!(title, "Array [0] is empty");
+    }
+
+    #[test]
+    fn bracketed_and_blank_labels_are_refused() {
+        assert!(check(&["bug".to_string(), "good first issue".to_string()]).is_ok());
+        for label in ["a]b", "[bug", " "] {
+            assert!(check(&[label.to_string()]).is_err(), "{}", label);
+        }
+    }
+
+    #[test]
+    fn titles_round_trip() {
+        let labels = vec!["bug".to_string(), "ui".to_string()];
+        let title = join_title(&labels, "Crash on start");
+        assert_eq!(title, "[bug] [ui] Crash on start");
+        assert_eq!(split_title(&title), (labels, "Crash on start"));
+    }
+}
@@ -0,0 +1,146 @@
+//! `--updated-since last-run` for list commands
+//!
+//! When a list command given `--updated-since` succeeds, the newest time
+//! among what it listed is saved for that command and repository in
+//! `$XDG_STATE_HOME/bitbucket-cli/last-run.json`. `--updated-since last-run`
+//! then lists only what changed since, so a polling script needs no state of
+//! its own. The first run, with nothing saved yet, lists everything.
+//!
+//! Times come from the listed items rather than the local clock, so clock
+//! skew can't skip anything, and `last-run` fetches every page rather than
+//! stopping at `--limit`. An item updated at exactly the saved time is listed
+//! again. For pipelines, which are filtered on when they started, the saved
+//! time stays at the oldest one still running, so it's listed again once it
+//! finishes.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::Args;
+
+use super::range::DateRange;
+use crate::config::{Config, xdg};
+
+const FILE: &str = "last-run.json";
+
+/// The value that stands for the last successful run
+pub const LAST_RUN: &str = "last-run";
+
+/// Runs this invocation will save, by key, once it succeeds
+static PENDING: Mutex<BTreeMap<String, DateTime<Utc>>> = Mutex::new(BTreeMap::new());
+
+#[derive(Args, Debug, Default, Clone)]
+pub struct UpdatedSince {
+    /// Only those updated (pipelines: started, or still running last time)
+    /// since `last-run`, the last successful run of this command on the
+    /// repository with this flag; or since an age such as 7d, or a date
+    #[arg(long, value_name = "last-run|AGE|DATE", conflicts_with = "since")]
+    pub updated_since: Option<String>,
+}
+
+impl UpdatedSince {
+    /// `range` starting where `--updated-since` says, for `command` listing
+    /// `repositories` (`workspace/repo`); with several, the earliest of their
+    /// last runs applies
+    pub fn apply(
+        &self,
+        range: DateRange,
+        command: &str,
+        repositories: &[String],
+    ) -> Result<DateRange> {
+        let Some(value) = &self.updated_since else {
+            return Ok(range);
+        };
+        let keys: Vec<String> = repositories
+            .iter()
+            .map(|repository| key(command, repository))
+            .collect();
+
+        let since = if value == LAST_RUN {
+            let runs = load()?;
+            // A repository never listed before means listing everything
+            let last = keys
+                .iter()
+                .map(|key| runs.get(key).copied())
+                .collect::<Option<Vec<_>>>()
+                .and_then(|last| last.into_iter().min());
+            if last.is_none() {
+                tracing::debug!(command, "no last run saved; listing everything");
+            }
+            last.map(|at| at.to_rfc3339_opts(SecondsFormat::Secs, true))
+        } else {
+            Some(value.clone())
+        };
+        let range = DateRange { since, ..range };
+
+        // Nothing listed still moves a run on to where it started looking
+        let (start, _) = range.bounds()?;
+        if let (Some(start), Ok(mut pending)) = (start, PENDING.lock()) {
+            pending.extend(keys.into_iter().map(|key| (key, start)));
+        }
+        Ok(range)
+    }
+
+    /// Whether every page has to be fetched, as `last-run` does so nothing
+    /// past `--limit` is skipped for good
+    pub fn wants_everything(&self) -> bool {
+        self.updated_since.as_deref() == Some(LAST_RUN)
+    }
+
+    /// Note that `command` on `repository` listed items up to `newest`, to
+    /// save as its last run
+    pub fn listed(&self, command: &str, repository: &str, newest: Option<DateTime<Utc>>) {
+        if self.updated_since.is_none() {
+            return;
+        }
+        let Some(newest) = newest else { return };
+        if let Ok(mut pending) = PENDING.lock() {
+            let at = pending.entry(key(command, repository)).or_insert(newest);
+            *at = (*at).max(newest);
+        }
+    }
+}
+
+fn key(command: &str, repository: &str) -> String {
+    format!("{} {}", command, repository)
+}
+
+fn path() -> Result<PathBuf> {
+    Ok(Config::state_dir()?.join(FILE))
+}
+
+/// When each command last succeeded on each repository, by key
+fn load() -> Result<BTreeMap<String, DateTime<Utc>>> {
+    let path = path()?;
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let text =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    parse(&path, &text)
+}
+
+fn parse(path: &Path, text: &str) -> Result<BTreeMap<String, DateTime<Utc>>> {
+    serde_json::from_str(text).with_context(|| format!("{} is corrupt", path.display()))
+}
+
+/// Remember the runs this invocation made with `--updated-since`
+pub fn save() -> Result<()> {
+    let pending = PENDING.lock().map(|p| p.clone()).unwrap_or_default();
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let path = path()?;
+    xdg::update_locked(&path, |current| {
+        let mut runs = match current {
+            Some(text) => parse(&path, &text)?,
+            None => BTreeMap::new(),
+        };
+        runs.extend(pending);
+        Ok(serde_json::to_string_pretty(&runs)?)
+    })
+}
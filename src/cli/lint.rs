@@ -0,0 +1,152 @@
+//! Pre-submit checks for `pr create`
+//!
+//! `[pr]` in the config turns on built-in checks (a description, a ticket
+//! reference, a title length limit) and can name a command to run on the
+//! draft. Every failing check is reported at once, and the pull request is
+//! only created when all pass, or with `--no-verify`.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::config::PrConfig;
+
+/// A pull request as it will be submitted
+#[derive(Debug, Serialize)]
+pub struct Draft<'a> {
+    pub repository: &'a str,
+    pub title: &'a str,
+    pub description: &'a str,
+    pub source: &'a str,
+    pub destination: Option<&'a str>,
+}
+
+/// Run the configured checks, failing with every problem found
+pub fn check(draft: &Draft, config: &PrConfig) -> Result<()> {
+    let mut problems = builtin(draft, config)?;
+    if let Some(command) = &config.lint_command {
+        problems.extend(run_command(command, draft)?);
+    }
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    let list: Vec<String> = problems.iter().map(|p| format!("  • {}", p)).collect();
+    anyhow::bail!(
+        "Pull request blocked by pre-submit checks:\n{}\nFix these, or pass --no-verify to skip the checks",
+        list.join("\n")
+    )
+}
+
+fn builtin(draft: &Draft, config: &PrConfig) -> Result<Vec<String>> {
+    let mut problems = Vec::new();
+
+    if config.require_description && draft.description.trim().is_empty() {
+        problems.push("The description is empty".to_string());
+    }
+    if let Some(max) = config.max_title_length {
+        let length = draft.title.chars().count();
+        if length > max {
+            problems.push(format!(
+                "The title is {} characters; the limit is {}",
+                length, max
+            ));
+        }
+    }
+    if let Some(pattern) = &config.ticket_pattern {
+        let ticket = Regex::new(pattern)
+            .with_context(|| format!("Invalid [pr] ticket_pattern '{}'", pattern))?;
+        if !ticket.is_match(draft.title) && !ticket.is_match(draft.description) {
+            problems.push(format!(
+                "No ticket reference matching '{}' in the title or description",
+                pattern
+            ));
+        }
+    }
+    Ok(problems)
+}
+
+/// Run `lint_command` with the draft as JSON on stdin, and in
+/// `BITBUCKET_PR_*` variables. Its output explains a failure.
+fn run_command(command: &str, draft: &Draft) -> Result<Option<String>> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut child = Command::new(shell)
+        .args([flag, command])
+        .env("BITBUCKET_PR_REPOSITORY", draft.repository)
+        .env("BITBUCKET_PR_TITLE", draft.title)
+        .env("BITBUCKET_PR_DESCRIPTION", draft.description)
+        .env("BITBUCKET_PR_SOURCE", draft.source)
+        .env("BITBUCKET_PR_DESTINATION", draft.destination.unwrap_or(""))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run [pr] lint_command '{}'", command))?;
+
+    // Written from another thread, so a command that fills its output pipe
+    // before reading all of stdin can't deadlock with us
+    let input = serde_json::to_string(draft)?;
+    let writer = child.stdin.take().map(|mut stdin| {
+        std::thread::spawn(move || {
+            // A command that ignores stdin may exit before reading it
+            let _ = stdin.write_all(input.as_bytes());
+        })
+    });
+    let output = child.wait_with_output()?;
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    if output.status.success() {
+        return Ok(None);
+    }
+
+    let said = [&output.stdout, &output.stderr]
+        .iter()
+        .map(|bytes| String::from_utf8_lossy(bytes).trim().to_string())
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    Ok(Some(if said.is_empty() {
+        format!("'{}' failed ({})", command, output.status)
+    } else {
+        format!("'{}' failed: {}", command, said.replace('\n', "\n    "))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_checks_report_every_problem() {
+        let config = PrConfig {
+            require_description: true,
+            ticket_pattern: Some(r"[A-Z]+-\d+".to_string()),
+            max_title_length: Some(10),
+            lint_command: None,
+            ..PrConfig::default()
+        };
+        let draft = Draft {
+            repository: "acme/engine",
+            title: "Add Bernoulli numbers",
+            description: "",
+            source: "feature/bernoulli",
+            destination: None,
+        };
+        assert_eq!(builtin(&draft, &config).unwrap().len(), 3);
+
+        let draft = Draft {
+            title: "ENG-42 Add",
+            description: "Computes them",
+            ..draft
+        };
+        assert!(builtin(&draft, &config).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,339 @@
+//! Markdown rendering for pull request descriptions and issue bodies
+//!
+//! Bitbucket stores these as Markdown. On a terminal they're shown styled:
+//! bold and coloured headings, emphasis, bullets, quotes, links with their
+//! target, and fenced code blocks highlighted by language. Piped output,
+//! and `--raw`, get the text as written.
+
+use std::sync::LazyLock;
+
+use colored::{ColoredString, Colorize};
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+use syntect::util::{LinesWithEndings, as_24_bit_terminal_escaped};
+
+use super::output;
+
+const THEME: &str = "base16-ocean.dark";
+
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEMES: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+/// `text` as it should be printed: rendered on a terminal, as written
+/// when piped or `raw`
+pub fn display(text: &str, raw: bool) -> String {
+    if raw || !output::is_tty() {
+        text.to_string()
+    } else {
+        render(text)
+    }
+}
+
+/// Render Markdown for a terminal
+pub fn render(text: &str) -> String {
+    let mut renderer = Renderer::default();
+    let options = Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    for event in Parser::new_ext(text, options) {
+        renderer.event(event);
+    }
+    renderer.out.trim_end().to_string()
+}
+
+#[derive(Default)]
+struct Renderer {
+    out: String,
+    at_line_start: bool,
+    bold: usize,
+    italic: usize,
+    strike: usize,
+    heading: Option<HeadingLevel>,
+    /// Target and text so far of the link being written
+    link: Option<(String, String)>,
+    image: bool,
+    quote: usize,
+    /// Next number of each open list, `None` for bullets
+    lists: Vec<Option<u64>>,
+    /// Just wrote a list marker, so the item's paragraph continues the line
+    item_start: bool,
+    /// Language and contents of the code block being read
+    code: Option<(String, String)>,
+}
+
+impl Renderer {
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => match &mut self.code {
+                Some((_, code)) => code.push_str(&text),
+                None => self.text(&text),
+            },
+            Event::Code(code) => {
+                let styled = code.yellow().to_string();
+                self.write(&styled);
+            }
+            Event::Html(html) | Event::InlineHtml(html) => self.text(&html),
+            Event::SoftBreak | Event::HardBreak => self.newline(),
+            Event::Rule => {
+                self.gap();
+                let rule = "─".repeat(40).dimmed().to_string();
+                self.write(&rule);
+                self.newline();
+            }
+            Event::TaskListMarker(done) => self.write(if done { "[x] " } else { "[ ] " }),
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            // A list item's first paragraph continues the marker's line
+            Tag::Paragraph if !std::mem::take(&mut self.item_start) => self.gap(),
+            Tag::Heading { level, .. } => {
+                self.gap();
+                self.heading = Some(level);
+                self.text(&format!("{} ", "#".repeat(level as usize)));
+            }
+            Tag::BlockQuote(_) => {
+                self.gap();
+                self.quote += 1;
+            }
+            Tag::CodeBlock(kind) => {
+                self.gap();
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => {
+                        info.split_whitespace().next().unwrap_or("").to_string()
+                    }
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code = Some((language, String::new()));
+            }
+            Tag::List(first) => {
+                if self.lists.is_empty() {
+                    self.gap();
+                } else if !self.at_line_start {
+                    self.newline();
+                }
+                self.lists.push(first);
+            }
+            Tag::Item => {
+                if !self.at_line_start {
+                    self.newline();
+                }
+                let depth = self.lists.len().saturating_sub(1);
+                let marker = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "• ".to_string(),
+                };
+                self.write_prefix(depth);
+                self.out.push_str(&marker);
+                self.at_line_start = false;
+                self.item_start = true;
+            }
+            Tag::Emphasis => self.italic += 1,
+            Tag::Strong => self.bold += 1,
+            Tag::Strikethrough => self.strike += 1,
+            Tag::Link { dest_url, .. } => self.link = Some((dest_url.to_string(), String::new())),
+            Tag::Image { dest_url, .. } => {
+                self.image = true;
+                self.link = Some((dest_url.to_string(), String::new()));
+                self.text("[image: ");
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => self.newline(),
+            TagEnd::Heading(_) => {
+                self.heading = None;
+                self.newline();
+            }
+            TagEnd::BlockQuote(_) => self.quote = self.quote.saturating_sub(1),
+            TagEnd::CodeBlock => {
+                if let Some((language, code)) = self.code.take() {
+                    self.code_block(&language, &code);
+                }
+            }
+            TagEnd::List(_) => {
+                self.lists.pop();
+                if !self.at_line_start {
+                    self.newline();
+                }
+            }
+            TagEnd::Item => {
+                self.item_start = false;
+                if !self.at_line_start {
+                    self.newline();
+                }
+            }
+            TagEnd::Emphasis => self.italic = self.italic.saturating_sub(1),
+            TagEnd::Strong => self.bold = self.bold.saturating_sub(1),
+            TagEnd::Strikethrough => self.strike = self.strike.saturating_sub(1),
+            TagEnd::Link => {
+                if let Some((url, text)) = self.link.take()
+                    && url != text
+                    && !url.is_empty()
+                {
+                    let target = format!(" ({})", url).dimmed().to_string();
+                    self.write(&target);
+                }
+            }
+            TagEnd::Image => {
+                let link = self.link.take();
+                self.image = false;
+                self.text("]");
+                if let Some((url, _)) = link
+                    && !url.is_empty()
+                {
+                    let target = format!(" ({})", url).dimmed().to_string();
+                    self.write(&target);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Write styled text, which may span lines
+    fn text(&mut self, text: &str) {
+        if let Some((_, link_text)) = &mut self.link {
+            link_text.push_str(text);
+        }
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.newline();
+            }
+            if !line.is_empty() {
+                let styled = self.style(line).to_string();
+                self.write(&styled);
+            }
+        }
+    }
+
+    fn style(&self, text: &str) -> ColoredString {
+        let mut styled = text.normal();
+        if self.heading.is_some() || self.bold > 0 {
+            styled = styled.bold();
+        }
+        if matches!(self.heading, Some(HeadingLevel::H1 | HeadingLevel::H2)) {
+            styled = styled.cyan();
+        }
+        if self.italic > 0 {
+            styled = styled.italic();
+        }
+        if self.strike > 0 {
+            styled = styled.strikethrough();
+        }
+        if self.link.is_some() && !self.image {
+            styled = styled.blue().underline();
+        }
+        styled
+    }
+
+    fn code_block(&mut self, language: &str, code: &str) {
+        let code = code.strip_suffix('\n').unwrap_or(code);
+        let lines = highlight(code, language)
+            .unwrap_or_else(|| code.lines().map(|l| l.dimmed().to_string()).collect());
+        for line in lines {
+            self.write(&format!("  {}", line));
+            self.newline();
+        }
+    }
+
+    /// Write already-styled text, starting the line with quote bars and list
+    /// indentation when needed
+    fn write(&mut self, text: &str) {
+        if self.at_line_start {
+            self.write_prefix(self.lists.len());
+            self.at_line_start = false;
+        }
+        self.out.push_str(text);
+    }
+
+    fn write_prefix(&mut self, depth: usize) {
+        for _ in 0..self.quote {
+            self.out.push_str(&"│ ".dimmed().to_string());
+        }
+        self.out.push_str(&"  ".repeat(depth));
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.at_line_start = true;
+    }
+
+    /// Separate a new block from what came before with a blank line
+    fn gap(&mut self) {
+        if self.out.is_empty() {
+            self.at_line_start = true;
+            return;
+        }
+        if !self.at_line_start {
+            self.newline();
+        }
+        if !self.out.ends_with("\n\n") {
+            self.newline();
+        }
+    }
+}
+
+/// Highlight `code` as `language`, one escaped line per source line, or
+/// `None` if colour is off or the language is unknown
+fn highlight(code: &str, language: &str) -> Option<Vec<String>> {
+    if language.is_empty() || !colored::control::SHOULD_COLORIZE.should_colorize() {
+        return None;
+    }
+    let syntax = SYNTAXES.find_syntax_by_token(language)?;
+    let theme = THEMES.themes.get(THEME)?;
+    let mut highlighter = HighlightLines::new(syntax, theme);
+
+    LinesWithEndings::from(code)
+        .map(|line| {
+            let ranges = highlighter.highlight_line(line, &SYNTAXES).ok()?;
+            let escaped = as_24_bit_terminal_escaped(&ranges, false);
+            Some(format!("{}\x1b[0m", escaped.trim_end_matches(['\n', '\r'])))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(text: &str) -> String {
+        colored::control::set_override(false);
+        render(text)
+    }
+
+    #[test]
+    fn blocks_are_laid_out_for_the_terminal() {
+        let rendered = plain(
+            "# Title\n\nSome **bold** text with [a link](https://example.com).\n\n\
+             - one\n- two\n  1. nested\n\n> quoted\n\n```rust\nfn main() {}\n```\n",
+        );
+        assert_eq!(
+            rendered,
+            "# Title\n\n\
+             Some bold text with a link (https://example.com).\n\n\
+             • one\n\
+             • two\n  \
+             1. nested\n\n\
+             │ quoted\n\n  \
+             fn main() {}"
+        );
+    }
+
+    #[test]
+    fn bare_links_are_not_repeated() {
+        assert_eq!(
+            plain("See <https://example.com>"),
+            "See https://example.com"
+        );
+    }
+}
@@ -1,17 +1,122 @@
+pub mod audit;
 pub mod auth;
+pub mod branch;
+pub mod browse;
+pub mod cache;
+pub mod changelog;
+pub mod clipboard;
+pub mod commit;
+pub mod compare;
+pub mod confirm;
+pub mod doctor;
+pub mod download;
+pub mod drafts;
+pub mod ext;
+pub mod fanout;
+pub mod format;
+pub mod git;
+pub mod hooks;
+pub mod icons;
+pub mod insights;
+pub mod interrupt;
 pub mod issue;
+pub mod label;
+pub mod last_run;
+pub mod lint;
+pub mod markdown;
+pub mod notify;
+pub mod output;
+pub mod pager;
+pub mod patch;
 pub mod pipeline;
+pub mod pipeline_audit;
+pub mod pipeline_stats;
 pub mod pr;
+pub mod range;
+pub mod recent;
 pub mod repo;
+pub mod reviewers;
+pub mod search;
+pub mod secret_audit;
+pub mod service_status;
+pub mod snippet;
+pub mod stats;
+pub mod status;
+pub mod step_log;
+pub mod triage;
+pub mod user;
+pub mod variable;
+pub mod webhook;
+pub mod workspace;
+
+use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
 
+/// Stable process exit codes, part of the scripting interface
+pub mod exit_code {
+    /// The command succeeded
+    pub const OK: i32 = 0;
+    /// The API or network request failed
+    pub const API: i32 = 1;
+    /// The command line was invalid
+    pub const USAGE: i32 = 2;
+    /// Not authenticated, or the credentials were rejected
+    pub const AUTH: i32 = 3;
+    /// The requested resource does not exist
+    pub const NOT_FOUND: i32 = 4;
+}
+
+/// An invalid argument detected after parsing, reported with the usage exit code
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct UsageError(pub String);
+
+/// Exit code for a failed command
+pub fn exit_code_for(error: &anyhow::Error) -> i32 {
+    use crate::Error;
+
+    for cause in error.chain() {
+        if cause.is::<UsageError>() {
+            return exit_code::USAGE;
+        }
+        if let Some(error) = cause.downcast_ref::<Error>() {
+            return match error {
+                Error::NotAuthenticated
+                | Error::Unauthorized
+                | Error::Forbidden
+                | Error::Auth(_)
+                | Error::CredentialStore(_) => exit_code::AUTH,
+                Error::NotFound(_) => exit_code::NOT_FOUND,
+                _ => exit_code::API,
+            };
+        }
+    }
+    exit_code::API
+}
+
+/// Gives a subcommand whose own `-w` is `--web` or `--wait` the global
+/// `--workspace` without its short flag. The value still reaches
+/// [`Cli::workspace`], as clap hands global values up from subcommands.
+fn long_only_workspace(command: clap::Command) -> clap::Command {
+    command.arg(
+        clap::Arg::new("workspace")
+            .long("workspace")
+            .value_name("WORKSPACE")
+            .help("Workspace to use (overrides config default)"),
+    )
+}
+
 #[derive(Parser)]
 #[command(name = "bitbucket")]
 #[command(author = "Pegasus Heavy Industries")]
 #[command(version)]
 #[command(about = "A command-line interface for Bitbucket Cloud", long_about = None)]
 #[command(propagate_version = true)]
+#[command(mut_subcommand("pr", |c| c.mut_subcommand("view", long_only_workspace)))]
+#[command(mut_subcommand("issue", |c| c.mut_subcommand("view", long_only_workspace)))]
+#[command(mut_subcommand("repo", |c| c.mut_subcommand("view", long_only_workspace)))]
+#[command(mut_subcommand("pipeline", |c| c.mut_subcommand("trigger", long_only_workspace)))]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
@@ -20,9 +125,69 @@ pub struct Cli {
     #[arg(short, long, global = true)]
     pub workspace: Option<String>,
 
-    /// Repository to use (overrides auto-detection)
+    /// Repository to use (overrides auto-detection); `-` for the one used last
     #[arg(short, long, global = true)]
     pub repo: Option<String>,
+
+    /// Use the defaults of `[profile.<NAME>]` in the config, instead of the
+    /// profile whose paths contain the current directory
+    #[arg(long, global = true, env = "BITBUCKET_PROFILE", value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Print debug logs to stderr (a debug log is always kept in the state directory)
+    #[arg(long, global = true)]
+    pub debug: bool,
+
+    /// Serve read-only commands from locally cached responses instead of the API
+    #[arg(long, global = true, env = "BITBUCKET_OFFLINE")]
+    pub offline: bool,
+
+    /// Save every API request and response to FILE, with secrets masked,
+    /// e.g. to attach to a bug report
+    #[arg(long, global = true, value_name = "FILE", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Answer API requests from a file made with --record instead of the network
+    #[arg(long, global = true, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+
+    /// Print only essential values (IDs, names) on stdout
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Answer yes to confirmation prompts, e.g. before deleting
+    #[arg(
+        short = 'y',
+        long,
+        global = true,
+        env = "BITBUCKET_ASSUME_YES",
+        value_parser = clap::builder::BoolishValueParser::new()
+    )]
+    pub yes: bool,
+
+    /// Never page long output, overriding `[display] pager`
+    #[arg(long, global = true)]
+    pub no_pager: bool,
+
+    /// Show long table cells in full instead of fitting tables to the terminal
+    #[arg(long, global = true)]
+    pub full: bool,
+
+    /// Filter JSON output with a jq expression (e.g. '.[].title')
+    #[arg(long, global = true, value_name = "EXPR")]
+    pub jq: Option<String>,
+
+    /// Format JSON output with a template, once per list item (e.g. '{{ id }} {{ title }}')
+    #[arg(long, global = true, value_name = "TEMPLATE", conflicts_with = "jq")]
+    pub template: Option<String>,
+
+    /// Print tables as a table, JSON or CSV (with a header row, for spreadsheets)
+    #[arg(short, long, global = true, value_enum, value_name = "FORMAT")]
+    pub output: Option<output::ReportFormat>,
+
+    /// Columns to show, by header, in order (e.g. 'id,title,author')
+    #[arg(long, global = true, value_name = "COLUMNS", value_delimiter = ',')]
+    pub columns: Vec<String>,
 }
 
 #[derive(Subcommand)]
@@ -57,6 +222,148 @@ pub enum Commands {
         command: pipeline::PipelineCommands,
     },
 
+    /// Create branches that follow the naming conventions
+    Branch {
+        #[command(subcommand)]
+        command: branch::BranchCommands,
+    },
+
+    /// Comment on and approve commits
+    Commit {
+        #[command(subcommand)]
+        command: commit::CommitCommands,
+    },
+
+    /// Show how far two branches have diverged, with the commits unique to each
+    Compare(compare::CompareArgs),
+
+    /// Publish Code Insights reports and annotations
+    Insights {
+        #[command(subcommand)]
+        command: insights::InsightsCommands,
+    },
+
+    /// Manage snippets
+    Snippet {
+        #[command(subcommand)]
+        command: snippet::SnippetCommands,
+    },
+
+    /// Look up users
+    User {
+        #[command(subcommand)]
+        command: user::UserCommands,
+    },
+
+    /// View and copy Pipelines variables across a workspace and its repositories
+    Variable {
+        #[command(subcommand)]
+        command: variable::VariableCommands,
+    },
+
+    /// Work with repository webhooks
+    Webhook {
+        #[command(subcommand)]
+        command: webhook::WebhookCommands,
+    },
+
+    /// Look up workspace members
+    Workspace {
+        #[command(subcommand)]
+        command: workspace::WorkspaceCommands,
+    },
+
+    /// Generate release notes from pull requests merged since a tag or date
+    Changelog(changelog::ChangelogArgs),
+
+    /// Review changes made through the CLI
+    Audit {
+        #[command(subcommand)]
+        command: audit::AuditCommands,
+    },
+
+    /// Search pull requests or issues across a workspace's repositories
+    Search {
+        #[command(subcommand)]
+        command: search::SearchCommands,
+    },
+
+    /// Post comments that failed to send, saved as drafts
+    Drafts {
+        #[command(subcommand)]
+        command: drafts::DraftsCommands,
+    },
+
+    /// List recently used repositories, or switch which one `-` means
+    Recent(recent::RecentArgs),
+
+    /// Summarise open PRs, failing pipelines and blocker issues
+    Status(status::StatusArgs),
+
+    /// Pull request cycle time, review latency and issue rates for a workspace
+    Stats(stats::StatsArgs),
+
+    /// Check git, network, proxy, keyring, config, credentials and terminal setup
+    Doctor,
+
+    /// Show Bitbucket Cloud's component statuses and open incidents
+    ServiceStatus,
+
+    /// Cached workspace, repository, member and branch names for completions
+    Cache {
+        #[command(subcommand)]
+        command: cache::CacheCommands,
+    },
+
     /// Launch interactive TUI
     Tui,
+
+    /// Open the repository, a branch, commit, pull request or file in the browser
+    Browse(browse::BrowseArgs),
+
+    /// Manage CLI extensions
+    Ext {
+        #[command(subcommand)]
+        command: ext::ExtCommands,
+    },
+
+    /// Run an extension (`bitbucket-<name>` installed or on PATH)
+    #[command(external_subcommand)]
+    External(Vec<String>),
+}
+
+impl Commands {
+    /// Name of the top-level subcommand, for logging
+    pub fn name(&self) -> &'static str {
+        match self {
+            Commands::Auth { .. } => "auth",
+            Commands::Repo { .. } => "repo",
+            Commands::Pr { .. } => "pr",
+            Commands::Issue { .. } => "issue",
+            Commands::Pipeline { .. } => "pipeline",
+            Commands::Branch { .. } => "branch",
+            Commands::Commit { .. } => "commit",
+            Commands::Compare(_) => "compare",
+            Commands::Insights { .. } => "insights",
+            Commands::Snippet { .. } => "snippet",
+            Commands::User { .. } => "user",
+            Commands::Variable { .. } => "variable",
+            Commands::Webhook { .. } => "webhook",
+            Commands::Workspace { .. } => "workspace",
+            Commands::Changelog(_) => "changelog",
+            Commands::Audit { .. } => "audit",
+            Commands::Status(_) => "status",
+            Commands::Search { .. } => "search",
+            Commands::Stats(_) => "stats",
+            Commands::Recent(_) => "recent",
+            Commands::Drafts { .. } => "drafts",
+            Commands::Doctor => "doctor",
+            Commands::ServiceStatus => "service-status",
+            Commands::Cache { .. } => "cache",
+            Commands::Tui => "tui",
+            Commands::Browse(_) => "browse",
+            Commands::Ext { .. } => "ext",
+            Commands::External(_) => "extension",
+        }
+    }
 }
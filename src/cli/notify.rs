@@ -0,0 +1,149 @@
+//! Completion notifications for commands that wait, such as `pipeline
+//! trigger --wait`
+//!
+//! With `[notify] webhook` set, the outcome is POSTed as JSON whose `text`
+//! field Slack incoming webhooks display; other fields are for generic
+//! receivers. With `[notify] command` set, the command is run through the
+//! shell with the outcome in `BITBUCKET_NOTIFY_*` environment variables.
+//! Notification failures are warnings, never the command's error.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+
+use super::icons::Icon;
+use crate::config::{Config, NotifyConfig};
+
+/// How long a webhook gets to accept a notification
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The outcome of a finished command
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    /// Slack-formatted summary
+    pub text: String,
+    /// The summary as plain text
+    pub message: String,
+    /// Top-level command, e.g. `pipeline`
+    pub command: String,
+    pub success: bool,
+    /// Web page for whatever finished
+    pub url: Option<String>,
+}
+
+impl Notification {
+    pub fn new(command: &str, success: bool, message: String, url: Option<String>) -> Self {
+        let icon = if success { "✅" } else { "❌" };
+        let text = match &url {
+            Some(url) => format!("{} <{}|{}>", icon, url, message),
+            None => format!("{} {}", icon, message),
+        };
+        Self {
+            text,
+            message,
+            command: command.to_string(),
+            success,
+            url,
+        }
+    }
+}
+
+/// Send `notification` everywhere `[notify]` says, warning about failures
+pub async fn send(notification: Notification) {
+    let config = Config::load().map(|c| c.notify).unwrap_or_default();
+    if config.failures_only && notification.success {
+        return;
+    }
+
+    for error in deliver(&config, &notification).await {
+        tracing::warn!(error = %error, "notification failed");
+        eprintln!(
+            "{} Notification failed: {:#}",
+            Icon::Warning.glyph().yellow(),
+            error
+        );
+    }
+}
+
+async fn deliver(config: &NotifyConfig, notification: &Notification) -> Vec<anyhow::Error> {
+    let mut errors = Vec::new();
+    if let Some(url) = &config.webhook
+        && let Err(e) = post(url, notification).await
+    {
+        errors.push(e);
+    }
+    if let Some(command) = &config.command
+        && let Err(e) = run_command(command, notification).await
+    {
+        errors.push(e);
+    }
+    errors
+}
+
+async fn post(url: &str, notification: &Notification) -> Result<()> {
+    reqwest::Client::new()
+        .post(url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .json(notification)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Could not post to {}", url))?;
+    Ok(())
+}
+
+async fn run_command(command: &str, notification: &Notification) -> Result<()> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let status = tokio::process::Command::new(shell)
+        .args([flag, command])
+        .env("BITBUCKET_NOTIFY_MESSAGE", &notification.message)
+        .env("BITBUCKET_NOTIFY_COMMAND", &notification.command)
+        .env(
+            "BITBUCKET_NOTIFY_STATUS",
+            if notification.success {
+                "success"
+            } else {
+                "failure"
+            },
+        )
+        .env(
+            "BITBUCKET_NOTIFY_URL",
+            notification.url.as_deref().unwrap_or(""),
+        )
+        .status()
+        .await
+        .with_context(|| format!("Failed to run notify command `{}`", command))?;
+    if !status.success() {
+        anyhow::bail!("Notify command `{}` exited with {}", command, status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_link_to_their_page() {
+        let notification = Notification::new(
+            "pipeline",
+            false,
+            "Pipeline #42 failed".to_string(),
+            Some("https://bitbucket.org/acme/engine/pipelines/results/42".to_string()),
+        );
+        assert_eq!(
+            notification.text,
+            "❌ <https://bitbucket.org/acme/engine/pipelines/results/42|Pipeline #42 failed>"
+        );
+        assert_eq!(notification.message, "Pipeline #42 failed");
+
+        let notification = Notification::new("pr", true, "Merged #7".to_string(), None);
+        assert_eq!(notification.text, "✅ Merged #7");
+    }
+}
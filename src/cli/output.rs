@@ -0,0 +1,741 @@
+//! Scriptable output (`--jq`, `--template`, `--quiet`)
+//!
+//! Commands that print API data hand it to [`print`] before drawing their
+//! usual tables. When a filter was given on the command line the data is
+//! serialized to JSON and shaped by it instead:
+//!
+//! - `--jq` runs a jq program (via `jaq`) and prints each result; strings are
+//!   printed raw, other values as JSON.
+//! - `--template` renders a Jinja-style template (via `minijinja`). Lists are
+//!   rendered once per item; an item's fields are available by name and the
+//!   whole item as `this`.
+//! - `--quiet` prints only each item's [`Porcelain`] value, one per line.
+//! - `--output json` prints the data as JSON.
+//!
+//! [`table`] honours `--output csv` (and json, for commands without API data
+//! to hand over) and `--columns`, which picks and orders columns by header.
+//! [`stream`] does both for a listing that arrives a page at a time, printing
+//! each item as it comes where the output allows.
+//! The remaining helpers keep decoration (check marks, rules, table borders)
+//! for terminals and print plain, stable text when stdout is piped.
+
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{Context, Result, anyhow};
+use clap::ValueEnum;
+use colored::Colorize;
+use futures::{Stream, TryStreamExt};
+use jaq_core::load::{Arena, File, Loader};
+use jaq_core::{Compiler, Ctx, Filter, Native, RcIter};
+use jaq_json::Val;
+use serde::Serialize;
+use serde_json::Value;
+use tabled::Tabled;
+
+use super::UsageError;
+use super::icons::Icon;
+use super::patch::FileCheck;
+use crate::audit::Entry;
+use crate::models::{
+    Commit, FileCommit, Issue, IssueComment, Pipeline, PipelineStep, PullRequest,
+    PullRequestComment, Report, Repository, Snippet, StatusSummary, User, WorkspaceMembership,
+};
+
+/// How to shape command output
+#[derive(Debug, Clone)]
+pub enum OutputFilter {
+    Jq(String),
+    Template(String),
+}
+
+static FILTER: OnceLock<OutputFilter> = OnceLock::new();
+
+/// Set when a command printed through the filter
+static USED: AtomicBool = AtomicBool::new(false);
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+static FORMAT: OnceLock<ReportFormat> = OnceLock::new();
+
+/// Columns picked with `--columns`, normalized with [`column_key`]
+static COLUMNS: OnceLock<Vec<String>> = OnceLock::new();
+/// Set by `--full` to keep long table cells whole
+static FULL: AtomicBool = AtomicBool::new(false);
+
+/// Columns that give up width when a table is too wide for the terminal,
+/// by header, in order of preference
+const FLEXIBLE_COLUMNS: [&str; 4] = ["TITLE", "DESCRIPTION", "MESSAGE", "CONTENT"];
+/// Narrowest a flexible column gets, however wide the rest of the table is
+const MIN_FLEXIBLE_WIDTH: usize = 12;
+/// Old names of renamed columns, so `--columns` in scripts keeps working
+/// where no column has the old name any more
+const COLUMN_ALIASES: [(&str, &str); 1] = [("branch", "target")];
+
+/// The essential value printed for an item by `--quiet`, usually its ID
+pub trait Porcelain {
+    fn porcelain(&self) -> String;
+}
+
+impl Porcelain for Repository {
+    fn porcelain(&self) -> String {
+        self.full_name.clone()
+    }
+}
+
+impl Porcelain for PullRequest {
+    fn porcelain(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+impl Porcelain for Commit {
+    fn porcelain(&self) -> String {
+        self.hash.clone()
+    }
+}
+
+impl Porcelain for FileCommit {
+    fn porcelain(&self) -> String {
+        self.commit.hash.clone()
+    }
+}
+
+impl Porcelain for PullRequestComment {
+    fn porcelain(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+impl Porcelain for Issue {
+    fn porcelain(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+impl Porcelain for IssueComment {
+    fn porcelain(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+impl Porcelain for Pipeline {
+    fn porcelain(&self) -> String {
+        self.build_number.to_string()
+    }
+}
+
+impl Porcelain for PipelineStep {
+    fn porcelain(&self) -> String {
+        self.uuid.clone()
+    }
+}
+
+impl Porcelain for Report {
+    fn porcelain(&self) -> String {
+        self.external_id
+            .clone()
+            .or_else(|| self.uuid.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Porcelain for Snippet {
+    fn porcelain(&self) -> String {
+        self.encoded_id().to_string()
+    }
+}
+
+impl Porcelain for StatusSummary {
+    fn porcelain(&self) -> String {
+        self.status.indicator.clone()
+    }
+}
+
+impl Porcelain for User {
+    fn porcelain(&self) -> String {
+        self.account_id.clone().unwrap_or_else(|| self.uuid.clone())
+    }
+}
+
+impl Porcelain for WorkspaceMembership {
+    fn porcelain(&self) -> String {
+        self.user.porcelain()
+    }
+}
+
+impl Porcelain for Entry {
+    fn porcelain(&self) -> String {
+        self.hash.clone()
+    }
+}
+
+impl Porcelain for FileCheck {
+    fn porcelain(&self) -> String {
+        self.path.clone()
+    }
+}
+
+impl<T: Porcelain> Porcelain for [T] {
+    fn porcelain(&self) -> String {
+        self.iter()
+            .map(Porcelain::porcelain)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl<T: Porcelain> Porcelain for Vec<T> {
+    fn porcelain(&self) -> String {
+        self.as_slice().porcelain()
+    }
+}
+
+/// Print only essential values and keep notes off the terminal
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Keep long table cells whole instead of fitting tables to the terminal
+pub fn set_full(full: bool) {
+    FULL.store(full, Ordering::Relaxed);
+}
+
+/// Set the `--output` format and `--columns` for this process
+pub fn set_format(format: Option<ReportFormat>, columns: Vec<String>) {
+    if let Some(format) = format {
+        let _ = FORMAT.set(format);
+    }
+    let columns: Vec<String> = columns
+        .iter()
+        .map(|c| column_key(c))
+        .filter(|c| !c.is_empty())
+        .collect();
+    if !columns.is_empty() {
+        let _ = COLUMNS.set(columns);
+    }
+}
+
+/// The `--output` format, `Table` unless given
+pub fn format() -> ReportFormat {
+    FORMAT.get().copied().unwrap_or(ReportFormat::Table)
+}
+
+/// Whether stdout is a terminal, i.e. decoration is wanted
+pub fn is_tty() -> bool {
+    static TTY: OnceLock<bool> = OnceLock::new();
+    *TTY.get_or_init(|| std::io::stdout().is_terminal())
+}
+
+/// Validate and install the filter for this process
+pub fn init(jq: Option<String>, template: Option<String>) -> Result<()> {
+    let filter = match (jq, template) {
+        (Some(expr), _) => {
+            compile_jq(&expr).context("Invalid --jq expression")?;
+            OutputFilter::Jq(expr)
+        }
+        (None, Some(template)) => {
+            minijinja::Environment::new()
+                .template_from_str(&template)
+                .context("Invalid --template")?;
+            OutputFilter::Template(template)
+        }
+        (None, None) => return Ok(()),
+    };
+
+    let _ = FILTER.set(filter);
+    Ok(())
+}
+
+/// Whether [`print`] will handle output, so callers can skip fetching what
+/// only their normal output shows
+pub fn is_structured() -> bool {
+    FILTER.get().is_some() || is_quiet() || format() == ReportFormat::Json
+}
+
+/// Print `value` through the active filter, or just its porcelain value
+/// under `--quiet`. Returns `false`, printing nothing, when the caller should
+/// use its normal output.
+pub fn print<T: Serialize + Porcelain + ?Sized>(value: &T) -> Result<bool> {
+    let Some(filter) = FILTER.get() else {
+        if is_quiet() {
+            let porcelain = value.porcelain();
+            if !porcelain.is_empty() {
+                println!("{}", porcelain);
+            }
+            return Ok(true);
+        }
+        if format() == ReportFormat::Json {
+            let value = select_json_columns(serde_json::to_value(value)?);
+            println!("{}", serde_json::to_string_pretty(&value)?);
+            return Ok(true);
+        }
+        return Ok(false);
+    };
+    USED.store(true, Ordering::Relaxed);
+
+    let value = serde_json::to_value(value)?;
+    let lines = match filter {
+        OutputFilter::Jq(expr) => run_jq(expr, value)?,
+        OutputFilter::Template(template) => render_template(template, value)?,
+    };
+    for line in lines {
+        println!("{}", line);
+    }
+
+    Ok(true)
+}
+
+/// Report a completed action: `✓ message` on a terminal, plain text when
+/// piped, nothing under `--quiet`
+pub fn success(message: impl Display) {
+    if is_quiet() {
+        return;
+    }
+    if is_tty() {
+        println!("{} {}", Icon::Ok.glyph().green(), message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+/// Print an informational note (empty results, hints) to stderr, so it never
+/// ends up in piped output. Suppressed under `--quiet`.
+pub fn note(message: impl Display) {
+    if !is_quiet() {
+        eprintln!("{}", message);
+    }
+}
+
+/// A horizontal rule (with newline) to go under a heading, empty when piped
+pub fn rule(width: usize) -> String {
+    if is_tty() {
+        format!("{}\n", "─".repeat(width))
+    } else {
+        String::new()
+    }
+}
+
+/// Whether output goes out a line per item: under `--quiet`, as CSV, or as
+/// rows piped without a filter. A terminal table has to be measured, and
+/// JSON and filters take the whole list, so those wait for every item.
+pub fn streams() -> bool {
+    FILTER.get().is_none()
+        && (is_quiet()
+            || match format() {
+                ReportFormat::Csv => true,
+                ReportFormat::Table => !is_tty(),
+                ReportFormat::Json => false,
+            })
+}
+
+/// Print `items` as [`print`] and then [`table`] would, with `row` making
+/// each table row, but a line at a time as they arrive where [`streams`]
+/// allows, so `--all` never holds a whole listing. Returns how many items
+/// there were.
+pub async fn stream<T, E, R>(
+    items: impl Stream<Item = std::result::Result<T, E>>,
+    row: impl Fn(&T) -> R,
+) -> Result<usize>
+where
+    T: Serialize + Porcelain,
+    E: Into<anyhow::Error>,
+    R: Tabled,
+{
+    let items = items.map_err(Into::into);
+    if !streams() {
+        let items: Vec<T> = items.try_collect().await?;
+        if !print(&items)? && !items.is_empty() {
+            table(items.iter().map(row).collect())?;
+        }
+        return Ok(items.len());
+    }
+
+    let headers: Vec<String> = R::headers().into_iter().map(|h| h.into_owned()).collect();
+    let picked = pick_columns(&headers)?;
+    let mut items = std::pin::pin!(items);
+    let mut count = 0;
+    while let Some(item) = items.try_next().await? {
+        count += 1;
+        if is_quiet() {
+            let porcelain = item.porcelain();
+            if !porcelain.is_empty() {
+                println!("{}", porcelain);
+            }
+            continue;
+        }
+        let fields = picked_fields(&row(&item), &picked);
+        if format() == ReportFormat::Csv {
+            if count == 1 {
+                println!("{}", csv_record(&pick(&headers, &picked)));
+            }
+            println!("{}", csv_record(&fields));
+        } else {
+            println!("{}", fields.join("\t"));
+        }
+    }
+    Ok(count)
+}
+
+/// The `picked` fields of `fields`, in order
+fn pick(fields: &[String], picked: &[usize]) -> Vec<String> {
+    picked.iter().map(|&i| fields[i].clone()).collect()
+}
+
+/// The `picked` fields of a table row
+fn picked_fields<T: Tabled>(row: &T, picked: &[usize]) -> Vec<String> {
+    let fields: Vec<String> = row.fields().into_iter().map(|f| f.into_owned()).collect();
+    pick(&fields, picked)
+}
+
+/// One line of CSV
+fn csv_record(fields: &[String]) -> String {
+    let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+    fields.join(",")
+}
+
+/// Print rows as a bordered table on a terminal (paged when long), or as
+/// tab-separated lines without a header when piped. `--output csv` prints
+/// CSV with a header, `--output json` an array of objects keyed by column.
+pub fn table<T: Tabled>(rows: Vec<T>) -> Result<()> {
+    let headers: Vec<String> = T::headers().into_iter().map(|h| h.into_owned()).collect();
+    let picked = pick_columns(&headers)?;
+    let headers = pick(&headers, &picked);
+    let rows: Vec<Vec<String>> = rows.iter().map(|row| picked_fields(row, &picked)).collect();
+
+    match format() {
+        ReportFormat::Csv => {
+            for record in std::iter::once(&headers).chain(&rows) {
+                println!("{}", csv_record(record));
+            }
+        }
+        ReportFormat::Json => {
+            let objects: Vec<Value> = rows
+                .iter()
+                .map(|row| {
+                    let object = headers
+                        .iter()
+                        .zip(row)
+                        .map(|(header, field)| (column_key(header), Value::from(field.as_str())))
+                        .collect();
+                    Value::Object(object)
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&objects)?);
+        }
+        ReportFormat::Table if is_tty() => {
+            let mut rows = rows;
+            if !FULL.load(Ordering::Relaxed)
+                && let Ok((width, _)) = crossterm::terminal::size()
+            {
+                fit_to_width(&headers, &mut rows, width as usize);
+            }
+            let mut builder = tabled::builder::Builder::default();
+            builder.push_record(headers);
+            for row in rows {
+                builder.push_record(row);
+            }
+            super::pager::page(&builder.build().to_string())?;
+        }
+        ReportFormat::Table => {
+            for row in rows {
+                println!("{}", row.join("\t"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Shorten the first of [`FLEXIBLE_COLUMNS`] in `rows` so the bordered table
+/// fits in `width` terminal columns, leaving the others whole
+fn fit_to_width(headers: &[String], rows: &mut [Vec<String>], width: usize) {
+    let Some(flexible) = FLEXIBLE_COLUMNS
+        .iter()
+        .find_map(|name| headers.iter().position(|h| h == name))
+    else {
+        return;
+    };
+    let column_width = |i: usize| {
+        rows.iter()
+            .map(|row| text_width(&row[i]))
+            .chain([text_width(&headers[i])])
+            .max()
+            .unwrap_or(0)
+    };
+    // Each column has a space either side and a border after it, plus the
+    // border before the first
+    let total: usize = (0..headers.len())
+        .map(|i| column_width(i) + 3)
+        .sum::<usize>()
+        + 1;
+    if total <= width {
+        return;
+    }
+    let fitted = column_width(flexible)
+        .saturating_sub(total - width)
+        .max(MIN_FLEXIBLE_WIDTH);
+    for row in rows {
+        shorten(&mut row[flexible], fitted);
+    }
+}
+
+fn text_width(text: &str) -> usize {
+    tabled::grid::util::string::get_text_width(text)
+}
+
+/// Cut `text` to `width` columns, ending it with `…` if anything was cut
+fn shorten(text: &mut String, width: usize) {
+    if text_width(text) <= width {
+        return;
+    }
+    let mut used = 0;
+    let mut cut = String::new();
+    for c in text.chars() {
+        let c_width = text_width(c.encode_utf8(&mut [0; 4]));
+        if used + c_width + 1 > width {
+            break;
+        }
+        used += c_width;
+        cut.push(c);
+    }
+    cut.push('…');
+    *text = cut;
+}
+
+/// Indexes of the `--columns` picked from `headers`, in the order given, or
+/// all of them
+fn pick_columns(headers: &[String]) -> Result<Vec<usize>> {
+    let Some(columns) = COLUMNS.get() else {
+        return Ok((0..headers.len()).collect());
+    };
+    let keys: Vec<String> = headers.iter().map(|h| column_key(h)).collect();
+    columns
+        .iter()
+        .map(|column| {
+            resolve_column(&keys, column)
+                .and_then(|column| keys.iter().position(|k| k == column))
+                .ok_or_else(|| {
+                    let available: Vec<String> = headers.iter().map(|h| column_key(h)).collect();
+                    UsageError(format!(
+                        "Unknown column '{}'; available: {}",
+                        column,
+                        available.join(", ")
+                    ))
+                    .into()
+                })
+        })
+        .collect()
+}
+
+/// Keep only the `--columns` fields of JSON objects, or of each object in
+/// an array
+fn select_json_columns(value: Value) -> Value {
+    let Some(columns) = COLUMNS.get() else {
+        return value;
+    };
+    let select = |value: Value| match value {
+        Value::Object(object) => {
+            let keys: Vec<String> = object.keys().map(|k| column_key(k)).collect();
+            Value::Object(
+                columns
+                    .iter()
+                    .filter_map(|column| {
+                        let column = resolve_column(&keys, column)?;
+                        let (key, value) = object.iter().find(|(k, _)| column_key(k) == column)?;
+                        Some((key.clone(), value.clone()))
+                    })
+                    .collect(),
+            )
+        }
+        other => other,
+    };
+    match value {
+        Value::Array(items) => Value::Array(items.into_iter().map(select).collect()),
+        other => select(other),
+    }
+}
+
+/// The one of `keys` that `column` picks: itself, or what it was renamed to
+fn resolve_column<'a>(keys: &[String], column: &'a str) -> Option<&'a str> {
+    if keys.iter().any(|k| k == column) {
+        return Some(column);
+    }
+    COLUMN_ALIASES
+        .iter()
+        .find(|(old, new)| *old == column && keys.iter().any(|k| k == new))
+        .map(|(_, new)| *new)
+}
+
+/// A column header or field name as `--columns` matches it: `MEDIAN CYCLE
+/// TIME`, `median-cycle-time` and `median_cycle_time` are the same column
+fn column_key(name: &str) -> String {
+    name.trim().to_lowercase().replace([' ', '-'], "_")
+}
+
+/// `--output` format
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+/// Quote a CSV field if it needs it
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Name of the filter flag if one was given but no command output used it
+pub fn unused_flag() -> Option<&'static str> {
+    match FILTER.get() {
+        Some(_) if USED.load(Ordering::Relaxed) => None,
+        Some(OutputFilter::Jq(_)) => Some("--jq"),
+        Some(OutputFilter::Template(_)) => Some("--template"),
+        None => None,
+    }
+}
+
+fn compile_jq(expr: &str) -> Result<Filter<Native<Val>>> {
+    let program = File {
+        code: expr,
+        path: (),
+    };
+    let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+    let arena = Arena::default();
+    let modules = loader
+        .load(&arena, program)
+        .map_err(|errors| anyhow!(describe_load_errors(errors)))?;
+
+    Compiler::default()
+        .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+        .compile(modules)
+        .map_err(|errors| {
+            let undefined: Vec<_> = errors
+                .into_iter()
+                .flat_map(|(_, errors)| errors)
+                .map(|(name, kind)| format!("undefined {} '{}'", kind.as_str(), name))
+                .collect();
+            anyhow!(undefined.join(", "))
+        })
+}
+
+fn run_jq(expr: &str, input: Value) -> Result<Vec<String>> {
+    let filter = compile_jq(expr)?;
+    let inputs = RcIter::new(core::iter::empty());
+    filter
+        .run((Ctx::new([], &inputs), Val::from(input)))
+        .map(|result| {
+            let value = Value::from(result.map_err(|e| anyhow!("jq: {}", e))?);
+            Ok(match value {
+                Value::String(s) => s,
+                other => serde_json::to_string_pretty(&other)?,
+            })
+        })
+        .collect()
+}
+
+fn describe_load_errors(errors: jaq_core::load::Errors<&str, ()>) -> String {
+    use jaq_core::load::Error;
+
+    let messages: Vec<String> = errors
+        .into_iter()
+        .flat_map(|(_, error)| match error {
+            Error::Io(errors) => errors
+                .into_iter()
+                .map(|(path, e)| format!("cannot load '{}': {}", path, e))
+                .collect::<Vec<_>>(),
+            Error::Lex(errors) => errors
+                .into_iter()
+                .map(|(expected, found)| expected_at(expected.as_str(), found))
+                .collect(),
+            Error::Parse(errors) => errors
+                .into_iter()
+                .map(|(expected, found)| expected_at(expected.as_str(), found))
+                .collect(),
+        })
+        .collect();
+    messages.join(", ")
+}
+
+fn expected_at(expected: &str, found: &str) -> String {
+    if found.is_empty() {
+        format!("expected {} at end of input", expected)
+    } else {
+        let found: String = found.chars().take(20).collect();
+        format!("expected {} before '{}'", expected, found)
+    }
+}
+
+fn render_template(template: &str, value: Value) -> Result<Vec<String>> {
+    let env = minijinja::Environment::new();
+    let template = env.template_from_str(template)?;
+    let items = match value {
+        Value::Array(items) => items,
+        other => vec![other],
+    };
+
+    items
+        .into_iter()
+        .map(|item| {
+            let this = minijinja::Value::from_serialize(&item);
+            let ctx = minijinja::context! { this => this.clone(), ..this };
+            Ok(template.render(ctx)?)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn jq_prints_strings_raw_and_other_values_as_json() {
+        let input = json!([{"id": 1, "title": "Fix"}, {"id": 2, "title": "Add"}]);
+        assert_eq!(run_jq(".[].title", input.clone()).unwrap(), ["Fix", "Add"]);
+        assert_eq!(run_jq("map(.id)", input).unwrap(), ["[\n  1,\n  2\n]"]);
+    }
+
+    #[test]
+    fn jq_syntax_errors_are_reported() {
+        let error = run_jq(".[", Value::Null).unwrap_err().to_string();
+        assert!(error.contains("expected"), "{}", error);
+        let error = run_jq("nope", Value::Null).unwrap_err().to_string();
+        assert_eq!(error, "undefined filter 'nope'");
+    }
+
+    #[test]
+    fn templates_render_once_per_list_item() {
+        let input = json!([{"id": 1, "title": "Fix"}, {"id": 2, "title": "Add"}]);
+        let lines = render_template("#{{ id }} {{ this.title | upper }}", input).unwrap();
+        assert_eq!(lines, ["#1 FIX", "#2 ADD"]);
+    }
+
+    #[test]
+    fn titles_give_up_width_to_fit_the_terminal() {
+        let headers = vec!["ID".to_string(), "TITLE".to_string(), "STATE".to_string()];
+        let mut rows = vec![
+            vec!["1".to_string(), "a".repeat(60), "OPEN".to_string()],
+            vec!["2".to_string(), "Short".to_string(), "MERGED".to_string()],
+        ];
+        fit_to_width(&headers, &mut rows, 40);
+        // 40 columns less the borders, padding, ID and STATE
+        assert_eq!(rows[0][1], format!("{}…", "a".repeat(21)));
+        assert_eq!(rows[1][1], "Short");
+
+        let mut narrow = rows.clone();
+        fit_to_width(&headers, &mut narrow, 10);
+        assert_eq!(text_width(&narrow[0][1]), MIN_FLEXIBLE_WIDTH);
+    }
+}
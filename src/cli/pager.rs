@@ -0,0 +1,104 @@
+//! Paging long output
+//!
+//! Output taller than the terminal is piped through `$BITBUCKET_PAGER`,
+//! `$PAGER`, or `less -FRX`, in that order. Paging only happens when stdout
+//! is a terminal and `[display] pager` is on; `--no-pager` turns it off for a
+//! single run, as does setting the pager to an empty string or `cat`.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Result;
+
+use super::output;
+
+const DEFAULT_PAGER: &str = "less -FRX";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Allow output to be paged for the rest of this process
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Print `text`, through the pager when it would not fit on screen
+pub fn page(text: &str) -> Result<()> {
+    if ENABLED.load(Ordering::Relaxed) && output::is_tty() && !fits_on_screen(text) {
+        let command = pager_command(
+            std::env::var("BITBUCKET_PAGER").ok(),
+            std::env::var("PAGER").ok(),
+        );
+        if let Some(command) = command {
+            match spawn(&command, text) {
+                Ok(()) => return Ok(()),
+                Err(e) => tracing::warn!(pager = ?command, error = %e, "failed to run pager"),
+            }
+        }
+    }
+
+    print!("{}", text);
+    if !text.ends_with('\n') {
+        println!();
+    }
+    Ok(())
+}
+
+fn fits_on_screen(text: &str) -> bool {
+    match crossterm::terminal::size() {
+        Ok((_, rows)) => text.lines().count() < rows as usize,
+        Err(_) => true,
+    }
+}
+
+/// Resolve the pager program and arguments, or `None` when paging is disabled
+fn pager_command(bitbucket_pager: Option<String>, pager: Option<String>) -> Option<Vec<String>> {
+    let command = bitbucket_pager
+        .or(pager)
+        .unwrap_or_else(|| DEFAULT_PAGER.to_string());
+    let words: Vec<String> = command.split_whitespace().map(String::from).collect();
+
+    match words.first().map(String::as_str) {
+        None | Some("cat") => None,
+        Some(_) => Some(words),
+    }
+}
+
+fn spawn(command: &[String], text: &str) -> std::io::Result<()> {
+    let mut pager = Command::new(&command[0]);
+    pager.args(&command[1..]).stdin(Stdio::piped());
+    // Like git: a bare `less` should still pass colors and quit on short output
+    if std::env::var_os("LESS").is_none() {
+        pager.env("LESS", "FRX");
+    }
+
+    let mut child = pager.spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The user quitting the pager early closes the pipe; that is not an error
+        match stdin.write_all(text.as_bytes()) {
+            Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => return Err(e),
+            _ => {}
+        }
+    }
+    child.wait()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pager_command_prefers_bitbucket_pager() {
+        let command = pager_command(Some("most -s".into()), Some("more".into()));
+        assert_eq!(command.unwrap(), ["most", "-s"]);
+        assert_eq!(pager_command(None, Some("more".into())).unwrap(), ["more"]);
+        assert_eq!(pager_command(None, None).unwrap(), ["less", "-FRX"]);
+    }
+
+    #[test]
+    fn empty_or_cat_pager_disables_paging() {
+        assert!(pager_command(Some(String::new()), Some("less".into())).is_none());
+        assert!(pager_command(None, Some("cat".into())).is_none());
+    }
+}
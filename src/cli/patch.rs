@@ -0,0 +1,249 @@
+//! Checking a pull request's diff against the working tree, for
+//! `pr diff --local`
+//!
+//! Each hunk is tried on its own: first as it is, then, where the file has
+//! changed since the diff's base, as a 3-way merge of the base blob, the
+//! working tree and the base with the hunk applied. Nothing in the checkout
+//! is written to.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+/// How a hunk, or a whole file, would go onto the working tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Applies,
+    /// Doesn't apply as is, but merges cleanly with the local changes
+    Merges,
+    Conflicts,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HunkCheck {
+    /// The hunk's `@@ ... @@` line
+    pub header: String,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileCheck {
+    pub path: String,
+    /// The worst of the hunks' outcomes, or the file's own for a change
+    /// without hunks (a rename, a binary file)
+    pub outcome: Outcome,
+    pub hunks: Vec<HunkCheck>,
+}
+
+/// One file's part of a diff
+struct FilePatch<'a> {
+    path: String,
+    /// `diff --git` through `+++`
+    header: String,
+    /// Blob the diff was made against, from the `index` line
+    base: Option<&'a str>,
+    hunks: Vec<String>,
+}
+
+/// Check every hunk of `diff` against the working tree at `root`
+pub fn check(diff: &str, root: &Path) -> Result<Vec<FileCheck>> {
+    let mut checks = Vec::new();
+    for file in split(diff) {
+        if file.hunks.is_empty() {
+            let outcome = if applies(root, &file.header)? {
+                Outcome::Applies
+            } else {
+                Outcome::Conflicts
+            };
+            checks.push(FileCheck {
+                path: file.path,
+                outcome,
+                hunks: Vec::new(),
+            });
+            continue;
+        }
+
+        let mut hunks = Vec::new();
+        for hunk in &file.hunks {
+            let outcome = if applies(root, &format!("{}{}", file.header, hunk))? {
+                Outcome::Applies
+            } else {
+                match merges(root, &file, hunk)? {
+                    Some(true) => Outcome::Merges,
+                    _ => Outcome::Conflicts,
+                }
+            };
+            hunks.push(HunkCheck {
+                header: hunk.lines().next().unwrap_or_default().to_string(),
+                outcome,
+            });
+        }
+        checks.push(FileCheck {
+            path: file.path,
+            outcome: hunks
+                .iter()
+                .map(|h| h.outcome)
+                .max()
+                .unwrap_or(Outcome::Applies),
+            hunks,
+        });
+    }
+    Ok(checks)
+}
+
+fn split(diff: &str) -> Vec<FilePatch<'_>> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    for line in diff.split_inclusive('\n') {
+        if line.starts_with("diff --git ") {
+            let path = line
+                .trim_end()
+                .rsplit_once(" b/")
+                .map(|(_, path)| path.to_string())
+                .unwrap_or_default();
+            files.push(FilePatch {
+                path,
+                header: String::new(),
+                base: None,
+                hunks: Vec::new(),
+            });
+        }
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+        if line.starts_with("@@") {
+            file.hunks.push(String::new());
+        }
+        match file.hunks.last_mut() {
+            Some(hunk) => hunk.push_str(line),
+            None => {
+                if let Some(ids) = line.strip_prefix("index ") {
+                    file.base = ids.split("..").next().filter(|id| !id.is_empty());
+                }
+                file.header.push_str(line);
+            }
+        }
+    }
+    files
+}
+
+/// Whether `patch` applies to the working tree as it is
+fn applies(root: &Path, patch: &str) -> Result<bool> {
+    let mut child = Command::new("git")
+        .args(["apply", "--check", "-"])
+        .current_dir(root)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to run git apply")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(patch.as_bytes())?;
+    }
+    Ok(child.wait()?.success())
+}
+
+/// Whether `hunk` merges with the working tree's copy of the file, or
+/// `None` when there's nothing to merge with: the base blob isn't in the
+/// local repository, or the file isn't in the working tree
+fn merges(root: &Path, file: &FilePatch, hunk: &str) -> Result<Option<bool>> {
+    let Some(base_id) = file.base else {
+        return Ok(None);
+    };
+    let Ok(ours) = std::fs::read_to_string(root.join(&file.path)) else {
+        return Ok(None);
+    };
+    let output = Command::new("git")
+        .args(["cat-file", "blob", base_id])
+        .current_dir(root)
+        .output()
+        .context("Failed to run git cat-file")?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let base = String::from_utf8_lossy(&output.stdout).into_owned();
+    let Some(theirs) = apply_hunk(&base, hunk) else {
+        return Ok(None);
+    };
+
+    // A private directory of its own, removed when dropped, so the working
+    // tree's contents aren't left readable by others
+    let dir = tempfile::tempdir().context("Failed to create a temporary directory")?;
+    let mut files = Vec::new();
+    for (name, contents) in [("ours", &ours), ("base", &base), ("theirs", &theirs)] {
+        let path = dir.path().join(name);
+        std::fs::write(&path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        files.push(path);
+    }
+    // Prints the merge instead of writing it over the first file, and exits
+    // with the number of conflicts
+    let status = Command::new("git")
+        .args(["merge-file", "-p", "--quiet"])
+        .args(&files)
+        .stdout(Stdio::null())
+        .status()
+        .context("Failed to run git merge-file")?;
+    Ok(Some(status.success()))
+}
+
+/// `base` with `hunk` applied at the lines its header names, or `None` if
+/// they don't match what the hunk expects
+fn apply_hunk(base: &str, hunk: &str) -> Option<String> {
+    let mut lines = hunk.split_inclusive('\n');
+    let range = lines.next()?.strip_prefix("@@ -")?.split(' ').next()?;
+    let (start, count) = match range.split_once(',') {
+        Some((start, count)) => (start.parse::<usize>().ok()?, count.parse::<usize>().ok()?),
+        None => (range.parse().ok()?, 1),
+    };
+    // An insertion into an empty range comes after line `start`
+    let start = if count == 0 {
+        start
+    } else {
+        start.saturating_sub(1)
+    };
+
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let mut out: Vec<&str> = base_lines.get(..start)?.to_vec();
+    let mut at = start;
+    for line in lines {
+        match line.chars().next() {
+            Some(' ') | Some('-') => {
+                if base_lines.get(at)?.trim_end_matches('\n') != line[1..].trim_end_matches('\n') {
+                    return None;
+                }
+                if line.starts_with(' ') {
+                    out.push(base_lines[at]);
+                }
+                at += 1;
+            }
+            Some('+') => out.push(&line[1..]),
+            // "\ No newline at end of file"
+            _ => {}
+        }
+    }
+    out.extend_from_slice(base_lines.get(at..)?);
+    Some(out.concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hunks_apply_to_their_base_at_the_stated_lines() {
+        let base = "a\nb\nc\nd\n";
+        assert_eq!(
+            apply_hunk(base, "@@ -2,2 +2,3 @@\n b\n-c\n+C\n+c2\n").as_deref(),
+            Some("a\nb\nC\nc2\nd\n")
+        );
+        assert_eq!(
+            apply_hunk(base, "@@ -4,0 +5 @@\n+e\n").as_deref(),
+            Some("a\nb\nc\nd\ne\n")
+        );
+        assert_eq!(apply_hunk(base, "@@ -1 +1 @@\n-x\n+y\n"), None);
+    }
+}
@@ -1,28 +1,68 @@
-use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
 use clap::Subcommand;
 use colored::Colorize;
-use indicatif::{ProgressBar, ProgressStyle};
-use tabled::{Table, Tabled};
+use futures::{StreamExt, TryStreamExt, stream};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::json;
+use tabled::Tabled;
 
+use super::browse::WEB_URL;
+use super::git::parse_repo;
+use super::hooks::{self, Hook};
+use super::icons::Icon;
+use super::last_run::UpdatedSince;
+use super::notify::{self, Notification};
+use super::range::DateRange;
+use super::{UsageError, fanout, format, git, output, pager, pipeline_stats, step_log};
 use crate::api::BitbucketClient;
-use crate::models::{PipelineResultName, PipelineStateName, TriggerPipelineRequest};
+use crate::models::{
+    Commit, Download, Pipeline, PipelineResultName, PipelineStateName, PipelineStep, PullRequest,
+    TriggerPipelineRequest,
+};
+
+/// How often `--wait` checks on a running pipeline
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often `--logs` checks finished steps' logs for a last few lines
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Subcommand)]
 pub enum PipelineCommands {
     /// List pipelines
     List {
-        /// Repository in format workspace/repo-slug
-        repo: String,
+        /// Repository in format workspace/repo-slug (default: the current
+        /// checkout's, then `[defaults]` in the config)
+        repo: Option<String>,
 
         /// Number of results
         #[arg(short, long, default_value = "25")]
         limit: u32,
+
+        /// Fetch every page instead of stopping at --limit
+        #[arg(long, conflicts_with = "limit")]
+        all: bool,
+
+        /// Only pipelines started in this window
+        #[command(flatten)]
+        range: DateRange,
+
+        #[command(flatten)]
+        updated_since: UpdatedSince,
     },
 
     /// View pipeline details
     View {
-        /// Repository in format workspace/repo-slug
-        repo: String,
+        /// Repository in format workspace/repo-slug (default: the current
+        /// checkout's, then `[defaults]` in the config)
+        repo: Option<String>,
 
         /// Pipeline build number
         #[arg(short, long)]
@@ -31,12 +71,62 @@ pub enum PipelineCommands {
         /// Show step logs
         #[arg(short, long)]
         logs: bool,
+
+        /// Print the web URL instead, without fetching the pipeline
+        #[arg(long, conflicts_with_all = ["logs", "step"])]
+        url: bool,
+
+        /// Show one step, by number (from 1) or name, with its commands and
+        /// full log
+        #[arg(short, long, conflicts_with = "logs")]
+        step: Option<String>,
+
+        /// Write the step's untruncated log to FILE, or stdout without one
+        #[arg(long, value_name = "FILE", num_args = 0..=1, default_missing_value = "-", requires = "step")]
+        raw_log: Option<PathBuf>,
+    },
+
+    /// Print a step's log, or the part of it worth reading
+    Logs {
+        /// Repository in format workspace/repo-slug (default: the current
+        /// checkout's, then `[defaults]` in the config)
+        repo: Option<String>,
+
+        /// Pipeline build number
+        #[arg(short, long)]
+        build: u64,
+
+        /// Step, by number (from 1) or name (default: the first failed
+        /// step, else the last)
+        #[arg(short, long)]
+        step: Option<String>,
+
+        /// Only lines matching this regular expression
+        #[arg(long, value_name = "PATTERN")]
+        grep: Option<String>,
+
+        /// Lines to show around each --grep match
+        #[arg(short = 'C', long, default_value = "0", requires = "grep")]
+        context: usize,
+
+        /// Only the first N lines
+        #[arg(long, value_name = "N", conflicts_with_all = ["grep", "tail"])]
+        head: Option<usize>,
+
+        /// Only the last N lines
+        #[arg(long, value_name = "N", conflicts_with = "grep")]
+        tail: Option<usize>,
+
+        /// The lines around the first error, failed command or panic
+        #[arg(long, conflicts_with_all = ["grep", "head", "tail"])]
+        errors: bool,
     },
 
     /// Trigger a new pipeline
     Trigger {
-        /// Repository in format workspace/repo-slug
-        repo: String,
+        /// Repository in format workspace/repo-slug (default: the current
+        /// checkout's, then `[defaults]` in the config)
+        repo: Option<String>,
 
         /// Branch to run pipeline on
         #[arg(short, long, default_value = "main")]
@@ -49,12 +139,49 @@ pub enum PipelineCommands {
         /// Wait for pipeline to complete
         #[arg(short, long)]
         wait: bool,
+
+        /// While waiting, print each step's log as it runs, under a header
+        /// whenever the output moves to another step
+        #[arg(long, requires = "wait")]
+        logs: bool,
+    },
+
+    /// Trigger the same pipeline in several repositories at once, e.g. for
+    /// a coordinated release
+    TriggerMany {
+        /// Repositories in format workspace/repo-slug, comma-separated
+        #[arg(long, required = true, value_delimiter = ',')]
+        repos: Vec<String>,
+
+        /// Branch to run pipelines on
+        #[arg(short, long, default_value = "main")]
+        branch: String,
+
+        /// Custom pipeline name (from bitbucket-pipelines.yml)
+        #[arg(short, long)]
+        pipeline: Option<String>,
+
+        /// Wait for every pipeline to complete
+        #[arg(long)]
+        wait: bool,
+    },
+
+    /// Durations, success rates and flaky steps of recent pipelines
+    Stats {
+        /// Repository in format workspace/repo-slug (default: the current
+        /// checkout's, then `[defaults]` in the config)
+        repo: Option<String>,
+
+        /// Start of the window: an age such as 30d, 2w or 36h, or a date
+        #[arg(long, default_value = "30d", value_name = "AGE|DATE")]
+        since: String,
     },
 
     /// Stop a running pipeline
     Stop {
-        /// Repository in format workspace/repo-slug
-        repo: String,
+        /// Repository in format workspace/repo-slug (default: the current
+        /// checkout's, then `[defaults]` in the config)
+        repo: Option<String>,
 
         /// Pipeline build number
         #[arg(short, long)]
@@ -62,149 +189,433 @@ pub enum PipelineCommands {
     },
 }
 
+/// A pipeline with its steps, as printed by `pipeline view --jq`
+#[derive(Serialize)]
+struct PipelineDetail<'a> {
+    #[serde(flatten)]
+    pipeline: &'a Pipeline,
+    steps: &'a [PipelineStep],
+    #[serde(flatten)]
+    context: &'a PipelineContext,
+}
+
+/// What a run was for and what it produced, looked up alongside it
+#[derive(Serialize, Default)]
+struct PipelineContext {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    commit: Option<Commit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pull_request: Option<PullRequest>,
+    /// Files added to the repository's Downloads while it ran, named for its
+    /// build number or commit
+    artifacts: Vec<Download>,
+}
+
+/// Look up the commit, pull request and artifacts of `pipeline` at once;
+/// each is left out if it can't be found
+async fn pipeline_context(
+    client: &BitbucketClient,
+    workspace: &str,
+    repo_slug: &str,
+    pipeline: &Pipeline,
+) -> PipelineContext {
+    let commit = async {
+        let hash = &pipeline.target.commit.as_ref()?.hash;
+        client
+            .get_commit(workspace, repo_slug, hash)
+            .await
+            .inspect_err(|e| tracing::debug!(error = %e, "no commit for pipeline"))
+            .ok()
+    };
+    let pull_request = async {
+        let id = pipeline.target.pullrequest.as_ref()?.id;
+        client
+            .get_pull_request(workspace, repo_slug, id)
+            .await
+            .inspect_err(|e| tracing::debug!(error = %e, "no pull request for pipeline"))
+            .ok()
+    };
+    let artifacts = async {
+        let finished = pipeline.completed_on.unwrap_or_else(Utc::now);
+        // Newest first, so nothing after one older than the run is its
+        let downloads = client
+            .stream_downloads(workspace, repo_slug)
+            .try_take_while(|d| {
+                futures::future::ready(Ok(d.created_on.is_none_or(|at| at >= pipeline.created_on)))
+            })
+            .try_filter(|d| {
+                futures::future::ready(
+                    d.created_on.is_some_and(|at| at <= finished) && names_run(&d.name, pipeline),
+                )
+            })
+            .try_collect()
+            .await;
+        match downloads {
+            Ok(downloads) => downloads,
+            Err(e) => {
+                tracing::debug!(error = %e, "no downloads for pipeline");
+                Vec::new()
+            }
+        }
+    };
+
+    let (commit, pull_request, artifacts) = tokio::join!(commit, pull_request, artifacts);
+    PipelineContext {
+        commit,
+        pull_request,
+        artifacts,
+    }
+}
+
+/// Whether a file called `name` says it came from `pipeline`: its build
+/// number on its own, or the start of its commit hash
+fn names_run(name: &str, pipeline: &Pipeline) -> bool {
+    let build = pipeline.build_number.to_string();
+    let numbered = name
+        .split(|c: char| !c.is_ascii_digit())
+        .any(|number| number == build);
+    let hash = pipeline.target.commit.as_ref().map(|c| c.hash.as_str());
+    let committed = hash.is_some_and(|hash| {
+        name.split(|c: char| !c.is_ascii_alphanumeric())
+            .any(|word| word.len() >= 7 && hash.starts_with(word))
+    });
+    numbered || committed
+}
+
+impl output::Porcelain for PipelineDetail<'_> {
+    fn porcelain(&self) -> String {
+        self.pipeline.porcelain()
+    }
+}
+
+/// A pipeline started by `trigger-many`, as it stood when the command ended
+#[derive(Serialize)]
+struct TriggeredRun {
+    repository: String,
+    build_number: u64,
+    state: String,
+    /// Whether it succeeded, once completed
+    success: Option<bool>,
+    url: String,
+    #[serde(skip)]
+    status: String,
+}
+
+impl TriggeredRun {
+    fn new(repository: String, pipeline: Pipeline) -> Self {
+        let result = pipeline.state.result.as_ref().map(|r| &r.name);
+        let success = match pipeline.state.name {
+            PipelineStateName::Completed => Some(matches!(
+                result,
+                Some(PipelineResultName::Successful) | None
+            )),
+            PipelineStateName::Halted => Some(false),
+            _ => None,
+        };
+        Self {
+            url: format!(
+                "{}/{}/pipelines/results/{}",
+                WEB_URL, repository, pipeline.build_number
+            ),
+            repository,
+            build_number: pipeline.build_number,
+            state: pipeline.state.name.to_string(),
+            success,
+            status: format_status(&pipeline.state.name, result),
+        }
+    }
+}
+
+impl output::Porcelain for TriggeredRun {
+    fn porcelain(&self) -> String {
+        format!("{}#{}", self.repository, self.build_number)
+    }
+}
+
+#[derive(Tabled)]
+struct TriggeredRunRow {
+    #[tabled(rename = "REPOSITORY")]
+    repository: String,
+    #[tabled(rename = "#")]
+    build: u64,
+    #[tabled(rename = "STATUS")]
+    status: String,
+    #[tabled(rename = "URL")]
+    url: String,
+}
+
+impl From<&TriggeredRun> for TriggeredRunRow {
+    fn from(run: &TriggeredRun) -> Self {
+        Self {
+            repository: run.repository.clone(),
+            build: run.build_number,
+            status: run.status.clone(),
+            url: run.url.clone(),
+        }
+    }
+}
+
 #[derive(Tabled)]
 struct PipelineRow {
     #[tabled(rename = "#")]
     build: u64,
     #[tabled(rename = "STATUS")]
     status: String,
-    #[tabled(rename = "BRANCH")]
-    branch: String,
+    #[tabled(rename = "TARGET")]
+    target: String,
     #[tabled(rename = "TRIGGERED")]
     triggered: String,
     #[tabled(rename = "DURATION")]
     duration: String,
 }
 
+impl From<&Pipeline> for PipelineRow {
+    fn from(p: &Pipeline) -> Self {
+        let duration = if let Some(seconds) = p.build_seconds_used {
+            format_duration(seconds)
+        } else if p.state.name == PipelineStateName::InProgress {
+            "running...".to_string()
+        } else {
+            "-".to_string()
+        };
+
+        Self {
+            build: p.build_number,
+            status: format_status(&p.state.name, p.state.result.as_ref().map(|r| &r.name)),
+            target: p.target.to_string(),
+            triggered: format::date(&p.created_on),
+            duration,
+        }
+    }
+}
+
 impl PipelineCommands {
     pub async fn run(self) -> Result<()> {
         match self {
-            PipelineCommands::List { repo, limit } => {
-                let (workspace, repo_slug) = parse_repo(&repo)?;
+            PipelineCommands::List {
+                repo,
+                limit,
+                all,
+                range,
+                updated_since,
+            } => {
+                let (workspace, repo_slug) = git::repo_or_origin(repo)?;
+                let repository = format!("{}/{}", workspace, repo_slug);
+                let range = updated_since.apply(
+                    range,
+                    "pipeline list",
+                    std::slice::from_ref(&repository),
+                )?;
+                let all = all || updated_since.wants_everything();
+                let created = range.filter("created_on")?;
                 let client = BitbucketClient::from_stored().await?;
 
-                let pipelines = client
-                    .list_pipelines(&workspace, &repo_slug, None, Some(limit))
-                    .await?;
+                let pipelines = if all {
+                    client
+                        .stream_pipelines(&workspace, &repo_slug, created.as_deref())
+                        .boxed()
+                } else {
+                    let page = client
+                        .list_pipelines(
+                            &workspace,
+                            &repo_slug,
+                            created.as_deref(),
+                            None,
+                            Some(limit),
+                        )
+                        .await?;
+                    stream::iter(page.values.into_iter().map(Ok)).boxed()
+                };
+                let (mut running, mut newest) = (None, None);
+                let pipelines = pipelines.inspect_ok(|p| {
+                    // Still running means listing it again once it's finished
+                    if !matches!(
+                        p.state.name,
+                        PipelineStateName::Completed | PipelineStateName::Halted
+                    ) {
+                        running = Some(running.map_or(p.created_on, |r| p.created_on.min(r)));
+                    }
+                    newest = newest.max(Some(p.created_on));
+                });
 
-                if pipelines.values.is_empty() {
-                    println!("No pipelines found");
-                    return Ok(());
+                let count = output::stream(pipelines, |p| PipelineRow::from(p)).await?;
+                if all {
+                    updated_since.listed("pipeline list", &repository, running.or(newest));
+                }
+                if count == 0 && !output::is_structured() {
+                    output::note("No pipelines found");
                 }
-
-                let rows: Vec<PipelineRow> = pipelines
-                    .values
-                    .iter()
-                    .map(|p| {
-                        let duration = if let Some(seconds) = p.build_seconds_used {
-                            format_duration(seconds)
-                        } else if p.state.name == PipelineStateName::InProgress {
-                            "running...".to_string()
-                        } else {
-                            "-".to_string()
-                        };
-
-                        PipelineRow {
-                            build: p.build_number,
-                            status: format_status(
-                                &p.state.name,
-                                p.state.result.as_ref().map(|r| &r.name),
-                            ),
-                            branch: p.target.ref_name.clone().unwrap_or_else(|| "-".to_string()),
-                            triggered: p.created_on.format("%Y-%m-%d %H:%M").to_string(),
-                            duration,
-                        }
-                    })
-                    .collect();
-
-                let table = Table::new(rows).to_string();
-                println!("{}", table);
 
                 Ok(())
             }
 
-            PipelineCommands::View { repo, build, logs } => {
-                let (workspace, repo_slug) = parse_repo(&repo)?;
+            PipelineCommands::Stats { repo, since } => {
+                let (workspace, repo_slug) = git::repo_or_origin(repo)?;
+                pipeline_stats::run(&workspace, &repo_slug, &since).await
+            }
+
+            PipelineCommands::View {
+                repo,
+                build,
+                logs,
+                url,
+                step,
+                raw_log,
+            } => {
+                let (workspace, repo_slug) = git::repo_or_origin(repo)?;
+                if url {
+                    println!(
+                        "{}/{}/{}/pipelines/results/{}",
+                        WEB_URL, workspace, repo_slug, build
+                    );
+                    return Ok(());
+                }
                 let client = BitbucketClient::from_stored().await?;
 
                 let pipeline = client
                     .get_pipeline_by_build_number(&workspace, &repo_slug, build)
                     .await?;
+                let steps = client
+                    .list_pipeline_steps(&workspace, &repo_slug, &pipeline.uuid)
+                    .await?;
+
+                if let Some(wanted) = &step {
+                    let (number, step) = find_step(&steps.values, wanted)?;
+                    let log = client
+                        .get_step_log(&workspace, &repo_slug, &pipeline.uuid, &step.uuid)
+                        .await;
+
+                    if let Some(path) = raw_log {
+                        let log = log?;
+                        if path.as_os_str() == "-" {
+                            print!("{}", log);
+                        } else {
+                            std::fs::write(&path, &log)
+                                .with_context(|| format!("Failed to write {}", path.display()))?;
+                            output::success(format!(
+                                "Saved the log of step {} to {}",
+                                number,
+                                path.display()
+                            ));
+                        }
+                        return Ok(());
+                    }
+
+                    if output::print(step)? {
+                        return Ok(());
+                    }
+                    // The log may not exist yet for a step that hasn't started
+                    let log = log.ok();
+                    pager::page(&describe_step(&pipeline, number, step, log.as_deref())?)?;
+                    return Ok(());
+                }
+
+                let context = pipeline_context(&client, &workspace, &repo_slug, &pipeline).await;
+                let detail = PipelineDetail {
+                    pipeline: &pipeline,
+                    steps: &steps.values,
+                    context: &context,
+                };
+                if output::print(&detail)? {
+                    return Ok(());
+                }
 
-                println!(
+                let mut out = String::new();
+                writeln!(
+                    out,
                     "{} Pipeline #{} - {}",
                     format_status(
                         &pipeline.state.name,
                         pipeline.state.result.as_ref().map(|r| &r.name)
                     ),
                     pipeline.build_number,
-                    pipeline.target.ref_name.as_deref().unwrap_or("unknown")
-                );
-                println!("{}", "─".repeat(60));
+                    pipeline.target
+                )?;
+                out.push_str(&output::rule(60));
 
                 if let Some(creator) = &pipeline.creator {
-                    println!("{} {}", "Triggered by:".dimmed(), creator.display_name);
+                    writeln!(out, "{} {}", "Triggered by:".dimmed(), creator.display_name)?;
                 }
 
                 if let Some(trigger) = &pipeline.trigger {
-                    println!("{} {}", "Trigger type:".dimmed(), trigger.trigger_type);
+                    writeln!(out, "{} {}", "Trigger type:".dimmed(), trigger.trigger_type)?;
                 }
 
-                println!(
+                writeln!(
+                    out,
                     "{} {}",
                     "Started:".dimmed(),
-                    pipeline.created_on.format("%Y-%m-%d %H:%M:%S")
-                );
+                    format::date(&pipeline.created_on)
+                )?;
 
                 if let Some(completed) = pipeline.completed_on {
-                    println!(
+                    writeln!(
+                        out,
                         "{} {}",
                         "Completed:".dimmed(),
-                        completed.format("%Y-%m-%d %H:%M:%S")
-                    );
+                        format::date(&completed)
+                    )?;
                 }
 
                 if let Some(seconds) = pipeline.build_seconds_used {
-                    println!("{} {}", "Duration:".dimmed(), format_duration(seconds));
+                    writeln!(out, "{} {}", "Duration:".dimmed(), format_duration(seconds))?;
                 }
 
-                // Show pipeline steps
-                let steps = client
-                    .list_pipeline_steps(&workspace, &repo_slug, &pipeline.uuid)
-                    .await?;
+                if let Some(commit) = &context.commit {
+                    let hash: String = commit.hash.chars().take(7).collect();
+                    let message = commit.message.as_deref().unwrap_or_default();
+                    let summary = message.lines().next().unwrap_or_default();
+                    writeln!(out, "{} {} {}", "Commit:".dimmed(), hash.yellow(), summary)?;
+                }
+                if let Some(pr) = &context.pull_request {
+                    writeln!(
+                        out,
+                        "{} #{} {} ({})",
+                        "Pull request:".dimmed(),
+                        pr.id,
+                        pr.title,
+                        pr.state
+                    )?;
+                    if let Some(html) = pr.links.as_ref().and_then(|l| l.html.as_ref()) {
+                        writeln!(out, "{} {}", "PR URL:".dimmed(), html.href.cyan())?;
+                    }
+                }
+                writeln!(
+                    out,
+                    "{} {}",
+                    "URL:".dimmed(),
+                    format!(
+                        "{}/{}/{}/pipelines/results/{}",
+                        WEB_URL, workspace, repo_slug, pipeline.build_number
+                    )
+                    .cyan()
+                )?;
+
+                if !context.artifacts.is_empty() {
+                    writeln!(out)?;
+                    writeln!(out, "{}", "Artifacts:".bold())?;
+                    for artifact in &context.artifacts {
+                        let size = artifact
+                            .size
+                            .map(|bytes| format!(" ({})", HumanBytes(bytes)))
+                            .unwrap_or_default();
+                        writeln!(out, "  {}{}", artifact.name, size.dimmed())?;
+                        if let Some(link) =
+                            artifact.links.as_ref().and_then(|l| l.self_link.as_ref())
+                        {
+                            writeln!(out, "    {}", link.href.cyan())?;
+                        }
+                    }
+                }
 
+                // Show pipeline steps
                 if !steps.values.is_empty() {
-                    println!();
-                    println!("{}", "Steps:".bold());
+                    writeln!(out)?;
+                    writeln!(out, "{}", "Steps:".bold())?;
 
                     for step in &steps.values {
-                        let status = step
-                            .state
-                            .as_ref()
-                            .map(|s| s.name.as_str())
-                            .unwrap_or("unknown");
-
-                        let status_icon = match status {
-                            "COMPLETED" => {
-                                let result = step
-                                    .state
-                                    .as_ref()
-                                    .and_then(|s| s.result.as_ref())
-                                    .map(|r| r.name.as_str())
-                                    .unwrap_or("");
-                                match result {
-                                    "SUCCESSFUL" => "✓".green(),
-                                    "FAILED" => "✗".red(),
-                                    _ => "○".normal(),
-                                }
-                            }
-                            "IN_PROGRESS" => "◉".blue(),
-                            "PENDING" => "○".dimmed(),
-                            _ => "○".normal(),
-                        };
-
                         let name = step.name.as_deref().unwrap_or("Step");
-                        println!("  {} {}", status_icon, name);
+                        writeln!(out, "  {} {}", step_icon(step), name)?;
 
                         if logs {
                             // Fetch and display step log
@@ -214,14 +625,14 @@ impl PipelineCommands {
                             {
                                 Ok(log) => {
                                     if !log.is_empty() {
-                                        println!();
+                                        writeln!(out)?;
                                         for line in log.lines().take(50) {
-                                            println!("    {}", line.dimmed());
+                                            writeln!(out, "    {}", line.dimmed())?;
                                         }
                                         if log.lines().count() > 50 {
-                                            println!("    {} ... (truncated)", "".dimmed());
+                                            writeln!(out, "    {} ... (truncated)", "".dimmed())?;
                                         }
-                                        println!();
+                                        writeln!(out)?;
                                     }
                                 }
                                 Err(_) => {
@@ -232,6 +643,85 @@ impl PipelineCommands {
                     }
                 }
 
+                pager::page(&out)?;
+
+                Ok(())
+            }
+
+            PipelineCommands::Logs {
+                repo,
+                build,
+                step,
+                grep,
+                context,
+                head,
+                tail,
+                errors,
+            } => {
+                let (workspace, repo_slug) = git::repo_or_origin(repo)?;
+                let pattern = grep
+                    .as_deref()
+                    .map(Regex::new)
+                    .transpose()
+                    .map_err(|e| UsageError(format!("Invalid --grep pattern: {}", e)))?;
+                let client = BitbucketClient::from_stored().await?;
+
+                let pipeline = client
+                    .get_pipeline_by_build_number(&workspace, &repo_slug, build)
+                    .await?;
+                let steps = client
+                    .list_pipeline_steps(&workspace, &repo_slug, &pipeline.uuid)
+                    .await?;
+                let (number, step) = match &step {
+                    Some(wanted) => find_step(&steps.values, wanted)?,
+                    None => default_step(&steps.values)
+                        .ok_or_else(|| UsageError(format!("Pipeline #{} has no steps", build)))?,
+                };
+                let log = client
+                    .get_step_log(&workspace, &repo_slug, &pipeline.uuid, &step.uuid)
+                    .await?;
+
+                let name = step.name.as_deref().unwrap_or("Step");
+                if let Some(pattern) = &pattern {
+                    let kept = step_log::grep(&log, pattern, context);
+                    if kept.is_empty() {
+                        output::note(format!(
+                            "No lines of step {} ({}) match '{}'",
+                            number,
+                            name,
+                            pattern.as_str()
+                        ));
+                    }
+                    print_log_lines(&kept);
+                } else if errors {
+                    match step_log::first_failure(&log) {
+                        Some(kept) => {
+                            output::note(format!("First failure in step {} ({}):", number, name));
+                            print_log_lines(&kept.into_iter().map(Some).collect::<Vec<_>>());
+                        }
+                        None => {
+                            output::note(format!(
+                                "Nothing in the log of step {} ({}) looks like a failure; its last {} lines:",
+                                number,
+                                name,
+                                2 * step_log::ERROR_CONTEXT
+                            ));
+                            for line in step_log::tail(&log, 2 * step_log::ERROR_CONTEXT) {
+                                println!("{}", line.text);
+                            }
+                        }
+                    }
+                } else if let Some(count) = head {
+                    for line in step_log::head(&log, count) {
+                        println!("{}", line.text);
+                    }
+                } else if let Some(count) = tail {
+                    for line in step_log::tail(&log, count) {
+                        println!("{}", line.text);
+                    }
+                } else {
+                    print!("{}", log);
+                }
                 Ok(())
             }
 
@@ -240,8 +730,9 @@ impl PipelineCommands {
                 branch,
                 pipeline,
                 wait,
+                logs,
             } => {
-                let (workspace, repo_slug) = parse_repo(&repo)?;
+                let (workspace, repo_slug) = git::repo_or_origin(repo)?;
                 let client = BitbucketClient::from_stored().await?;
 
                 let request = if let Some(pipeline_name) = pipeline {
@@ -253,82 +744,205 @@ impl PipelineCommands {
                 let triggered = client
                     .trigger_pipeline(&workspace, &repo_slug, &request)
                     .await?;
+                hooks::run(
+                    Hook::PostPipelineTrigger,
+                    &format!("{}/{}", workspace, repo_slug),
+                    &json!({ "branch": branch, "pipeline": triggered }),
+                )
+                .await;
+
+                if !wait && output::print(&triggered)? {
+                    return Ok(());
+                }
 
-                println!(
-                    "{} Triggered pipeline #{} on branch {}",
-                    "✓".green(),
+                output::success(format!(
+                    "Triggered pipeline #{} on branch {}",
                     triggered.build_number,
                     branch.cyan()
-                );
+                ));
 
                 if wait {
                     println!();
-                    let pb = ProgressBar::new_spinner();
-                    pb.set_style(
-                        ProgressStyle::default_spinner()
-                            .template("{spinner:.blue} {msg}")
-                            .unwrap(),
-                    );
-                    pb.set_message("Waiting for pipeline to complete...");
-
-                    loop {
-                        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-
-                        let current = client
-                            .get_pipeline(&workspace, &repo_slug, &triggered.uuid)
-                            .await?;
-
-                        match current.state.name {
-                            PipelineStateName::Completed => {
-                                pb.finish_and_clear();
-
-                                if let Some(result) = &current.state.result {
-                                    match result.name {
-                                        PipelineResultName::Successful => {
-                                            println!(
-                                                "{} Pipeline #{} completed successfully!",
-                                                "✓".green(),
-                                                current.build_number
-                                            );
-                                        }
-                                        PipelineResultName::Failed => {
-                                            println!(
-                                                "{} Pipeline #{} failed",
-                                                "✗".red(),
-                                                current.build_number
-                                            );
-                                        }
-                                        _ => {
-                                            println!(
-                                                "Pipeline #{} completed with status: {:?}",
-                                                current.build_number, result.name
-                                            );
-                                        }
-                                    }
+                    let current = if logs {
+                        follow_logs(&client, &workspace, &repo_slug, &triggered.uuid).await?
+                    } else {
+                        let pb = ProgressBar::new_spinner();
+                        pb.set_style(
+                            ProgressStyle::default_spinner()
+                                .template("{spinner:.blue} {msg}")
+                                .unwrap(),
+                        );
+                        pb.set_message("Waiting for pipeline to complete...");
+
+                        let current =
+                            wait_for(&client, &workspace, &repo_slug, &triggered.uuid, &pb).await?;
+                        pb.finish_and_clear();
+                        current
+                    };
+
+                    let (success, outcome) = match current.state.name {
+                        PipelineStateName::Completed => {
+                            match current.state.result.as_ref().map(|r| &r.name) {
+                                Some(PipelineResultName::Successful) => {
+                                    println!(
+                                        "{} Pipeline #{} completed successfully!",
+                                        Icon::Ok.glyph().green(),
+                                        current.build_number
+                                    );
+                                    (true, "completed successfully".to_string())
                                 }
-                                break;
-                            }
-                            PipelineStateName::Halted => {
-                                pb.finish_and_clear();
-                                println!(
-                                    "{} Pipeline #{} was halted",
-                                    "⚠".yellow(),
-                                    current.build_number
-                                );
-                                break;
-                            }
-                            _ => {
-                                pb.tick();
+                                Some(PipelineResultName::Failed) => {
+                                    println!(
+                                        "{} Pipeline #{} failed",
+                                        Icon::Error.glyph().red(),
+                                        current.build_number
+                                    );
+                                    (false, "failed".to_string())
+                                }
+                                Some(name) => {
+                                    println!(
+                                        "Pipeline #{} completed with status: {:?}",
+                                        current.build_number, name
+                                    );
+                                    (false, format!("completed with status {:?}", name))
+                                }
+                                None => (true, "completed".to_string()),
                             }
                         }
-                    }
+                        _ => {
+                            println!(
+                                "{} Pipeline #{} was halted",
+                                Icon::Warning.glyph().yellow(),
+                                current.build_number
+                            );
+                            (false, "was halted".to_string())
+                        }
+                    };
+
+                    notify::send(Notification::new(
+                        "pipeline",
+                        success,
+                        format!(
+                            "Pipeline #{} on {} in {}/{} {}",
+                            triggered.build_number, branch, workspace, repo_slug, outcome
+                        ),
+                        Some(format!(
+                            "{}/{}/{}/pipelines/results/{}",
+                            WEB_URL, workspace, repo_slug, triggered.build_number
+                        )),
+                    ))
+                    .await;
+                }
+
+                Ok(())
+            }
+
+            PipelineCommands::TriggerMany {
+                repos,
+                branch,
+                pipeline,
+                wait,
+            } => {
+                for repo in &repos {
+                    parse_repo(repo)?;
+                }
+                let client = BitbucketClient::from_stored().await?;
+                let request = match &pipeline {
+                    Some(name) => TriggerPipelineRequest::for_branch_with_pipeline(&branch, name),
+                    None => TriggerPipelineRequest::for_branch(&branch),
+                };
+
+                let mut outcome = fanout::run(
+                    "Triggering pipelines",
+                    repos,
+                    fanout::DEFAULT_CONCURRENCY,
+                    |repo| {
+                        let client = &client;
+                        let request = &request;
+                        async move {
+                            let (workspace, repo_slug) = parse_repo(&repo)?;
+                            Ok(client
+                                .trigger_pipeline(&workspace, &repo_slug, request)
+                                .await?)
+                        }
+                    },
+                )
+                .await;
+
+                // Every repository is triggered before any is waited on, and
+                // the runs are then polled together
+                if wait {
+                    let triggered: Vec<(String, Pipeline)> = outcome.succeeded.drain(..).collect();
+                    let repos: Vec<String> =
+                        triggered.iter().map(|(repo, _)| repo.clone()).collect();
+                    let concurrency = repos.len();
+                    let waited = fanout::run("Running pipelines", repos, concurrency, |repo| {
+                        let client = &client;
+                        let triggered = &triggered;
+                        async move {
+                            let uuid = triggered
+                                .iter()
+                                .find(|(r, _)| *r == repo)
+                                .map(|(_, pipeline)| pipeline.uuid.as_str())
+                                .unwrap_or_default();
+                            let (workspace, repo_slug) = parse_repo(&repo)?;
+                            let hidden = ProgressBar::hidden();
+                            wait_for(client, &workspace, &repo_slug, uuid, &hidden).await
+                        }
+                    })
+                    .await;
+                    outcome.succeeded = waited.succeeded;
+                    outcome.failed.extend(waited.failed);
+                }
+
+                let runs: Vec<TriggeredRun> = outcome
+                    .succeeded
+                    .drain(..)
+                    .map(|(repository, pipeline)| TriggeredRun::new(repository, pipeline))
+                    .collect();
+                let unsuccessful = runs.iter().filter(|r| r.success == Some(false)).count();
+
+                if !output::print(&runs)? && !runs.is_empty() {
+                    output::table(runs.iter().map(TriggeredRunRow::from).collect())?;
+                }
+
+                if wait && !runs.is_empty() {
+                    let message = match unsuccessful {
+                        0 => format!(
+                            "Pipelines on {} succeeded in {} repositories",
+                            branch,
+                            runs.len()
+                        ),
+                        n => format!(
+                            "Pipelines on {} failed in {} of {} repositories",
+                            branch,
+                            n,
+                            runs.len()
+                        ),
+                    };
+                    notify::send(Notification::new(
+                        "pipeline",
+                        unsuccessful == 0,
+                        message,
+                        None,
+                    ))
+                    .await;
+                }
+
+                outcome.finish("trigger pipelines in", "repositories")?;
+                if unsuccessful > 0 {
+                    anyhow::bail!(
+                        "{} of {} pipelines did not succeed",
+                        unsuccessful,
+                        runs.len()
+                    );
                 }
 
                 Ok(())
             }
 
             PipelineCommands::Stop { repo, build } => {
-                let (workspace, repo_slug) = parse_repo(&repo)?;
+                let (workspace, repo_slug) = git::repo_or_origin(repo)?;
                 let client = BitbucketClient::from_stored().await?;
 
                 let pipeline = client
@@ -339,7 +953,7 @@ impl PipelineCommands {
                     .stop_pipeline(&workspace, &repo_slug, &pipeline.uuid)
                     .await?;
 
-                println!("{} Stopped pipeline #{}", "✓".green(), build);
+                output::success(format!("Stopped pipeline #{}", build));
 
                 Ok(())
             }
@@ -347,15 +961,119 @@ impl PipelineCommands {
     }
 }
 
-fn parse_repo(repo: &str) -> Result<(String, String)> {
-    let parts: Vec<&str> = repo.split('/').collect();
-    if parts.len() != 2 {
-        anyhow::bail!(
-            "Invalid repository format. Expected 'workspace/repo-slug', got '{}'",
-            repo
-        );
+/// Poll a pipeline until it completes or halts
+async fn wait_for(
+    client: &BitbucketClient,
+    workspace: &str,
+    repo_slug: &str,
+    uuid: &str,
+    progress: &ProgressBar,
+) -> Result<Pipeline> {
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        let current = client.get_pipeline(workspace, repo_slug, uuid).await?;
+        match current.state.name {
+            PipelineStateName::Completed | PipelineStateName::Halted => return Ok(current),
+            _ => progress.tick(),
+        }
+    }
+}
+
+/// How far `--logs` has got through a step's log
+#[derive(Default)]
+struct FollowedLog {
+    /// Bytes fetched so far
+    offset: u64,
+    /// Fetched bytes after the last newline, held back until the line ends
+    partial: Vec<u8>,
+    /// The step finished and its log was printed to the end
+    done: bool,
+}
+
+/// Poll a pipeline until it completes or halts, printing its steps' logs as
+/// they grow, with a header whenever the output moves to another step. A
+/// step is over once it's COMPLETED and a fetch after that finds nothing
+/// new, since its log can still be catching up when its state changes.
+async fn follow_logs(
+    client: &BitbucketClient,
+    workspace: &str,
+    repo_slug: &str,
+    uuid: &str,
+) -> Result<Pipeline> {
+    let mut logs: HashMap<String, FollowedLog> = HashMap::new();
+    let mut last_step: Option<String> = None;
+    loop {
+        // The pipeline first, so that the steps fetched after it have
+        // finished if it has
+        let current = client.get_pipeline(workspace, repo_slug, uuid).await?;
+        let steps = client
+            .list_pipeline_steps(workspace, repo_slug, uuid)
+            .await?
+            .values;
+
+        for (index, step) in steps.iter().enumerate() {
+            let state = step.state.as_ref().map(|s| s.name.as_str());
+            if matches!(state, None | Some("PENDING")) {
+                continue;
+            }
+            let log = logs.entry(step.uuid.clone()).or_default();
+            let completed = state == Some("COMPLETED");
+            // A completed step's log is read to its end before moving on,
+            // so steps' output doesn't interleave
+            while !log.done {
+                let chunk = client
+                    .get_step_log_from(workspace, repo_slug, uuid, &step.uuid, log.offset)
+                    .await?;
+                let finished = completed && chunk.is_empty();
+                log.offset += chunk.len() as u64;
+                log.partial.extend(chunk);
+
+                let ready = if finished {
+                    log.partial.len()
+                } else {
+                    log.partial
+                        .iter()
+                        .rposition(|b| *b == b'\n')
+                        .map_or(0, |i| i + 1)
+                };
+                let name = step.name.as_deref().unwrap_or("Step");
+                if ready > 0 || finished {
+                    if last_step.as_deref() != Some(step.uuid.as_str()) {
+                        println!("{}", format!("── {}. {} ──", index + 1, name).bold());
+                        last_step = Some(step.uuid.clone());
+                    }
+                    let text: Vec<u8> = log.partial.drain(..ready).collect();
+                    let text = String::from_utf8_lossy(&text);
+                    print!("{}", text);
+                    if finished && !text.is_empty() && !text.ends_with('\n') {
+                        println!();
+                    }
+                }
+
+                if finished {
+                    let result = step
+                        .state
+                        .as_ref()
+                        .and_then(|s| s.result.as_ref())
+                        .map(|r| r.name.to_lowercase())
+                        .unwrap_or_else(|| "finished".to_string());
+                    println!("{} {}. {} {}", step_icon(step), index + 1, name, result);
+                    println!();
+                    log.done = true;
+                } else if completed {
+                    tokio::time::sleep(FLUSH_INTERVAL).await;
+                } else {
+                    break;
+                }
+            }
+        }
+
+        match current.state.name {
+            PipelineStateName::Completed | PipelineStateName::Halted => return Ok(current),
+            _ => tokio::time::sleep(POLL_INTERVAL).await,
+        }
     }
-    Ok((parts[0].to_string(), parts[1].to_string()))
 }
 
 pub(crate) fn format_status(
@@ -375,12 +1093,154 @@ pub(crate) fn format_status(
                     PipelineResultName::Error => "ERROR".red().to_string(),
                     PipelineResultName::Stopped => "STOPPED".yellow().to_string(),
                     PipelineResultName::Expired => "EXPIRED".dimmed().to_string(),
+                    PipelineResultName::Unknown => "UNKNOWN".dimmed().to_string(),
                 }
             } else {
                 "COMPLETED".normal().to_string()
             }
         }
+        PipelineStateName::Unknown => "UNKNOWN".dimmed().to_string(),
+    }
+}
+
+/// The step numbered `wanted` (from 1), or named it, with its number
+fn find_step<'a>(steps: &'a [PipelineStep], wanted: &str) -> Result<(usize, &'a PipelineStep)> {
+    let by_number = wanted
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_sub(1))
+        .and_then(|i| steps.get(i).map(|step| (i, step)));
+    let by_name = || {
+        steps.iter().enumerate().find(|(_, step)| {
+            step.name
+                .as_deref()
+                .is_some_and(|name| name.eq_ignore_ascii_case(wanted))
+        })
+    };
+    match by_number.or_else(by_name) {
+        Some((i, step)) => Ok((i + 1, step)),
+        None => {
+            let names: Vec<String> = steps
+                .iter()
+                .enumerate()
+                .map(|(i, step)| format!("{}. {}", i + 1, step.name.as_deref().unwrap_or("Step")))
+                .collect();
+            anyhow::bail!(UsageError(format!(
+                "No step '{}' in this pipeline; its steps are:\n  {}",
+                wanted,
+                names.join("\n  ")
+            )))
+        }
+    }
+}
+
+/// The step whose log is likeliest to be wanted: the first that failed,
+/// otherwise the last
+fn default_step(steps: &[PipelineStep]) -> Option<(usize, &PipelineStep)> {
+    let failed = steps.iter().position(|step| {
+        step.state
+            .as_ref()
+            .and_then(|s| s.result.as_ref())
+            .is_some_and(|r| r.name == "FAILED")
+    });
+    let index = failed.or(steps.len().checked_sub(1))?;
+    Some((index + 1, &steps[index]))
+}
+
+/// Lines kept from a log, numbered as `grep -n` does: `12:` for a match,
+/// `12-` for context and `--` between groups
+fn print_log_lines(lines: &[Option<step_log::Kept>]) {
+    for line in lines {
+        match line {
+            Some(line) if line.matched => println!(
+                "{}{} {}",
+                line.number.to_string().green(),
+                ":".dimmed(),
+                line.text.bold()
+            ),
+            Some(line) => println!(
+                "{}{} {}",
+                line.number.to_string().green(),
+                "-".dimmed(),
+                line.text
+            ),
+            None => println!("{}", "--".dimmed()),
+        }
+    }
+}
+
+fn step_icon(step: &PipelineStep) -> colored::ColoredString {
+    let Some(state) = &step.state else {
+        return Icon::Inactive.glyph().normal();
+    };
+    match state.name.as_str() {
+        "COMPLETED" => match state.result.as_ref().map(|r| r.name.as_str()) {
+            Some("SUCCESSFUL") => Icon::Ok.glyph().green(),
+            Some("FAILED") => Icon::Error.glyph().red(),
+            _ => Icon::Inactive.glyph().normal(),
+        },
+        "IN_PROGRESS" => Icon::Active.glyph().blue(),
+        "PENDING" => Icon::Inactive.glyph().dimmed(),
+        _ => Icon::Inactive.glyph().normal(),
+    }
+}
+
+/// A step's image, timing, commands and full log
+fn describe_step(
+    pipeline: &Pipeline,
+    number: usize,
+    step: &PipelineStep,
+    log: Option<&str>,
+) -> Result<String> {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{} Step {}: {} (pipeline #{})",
+        step_icon(step),
+        number,
+        step.name.as_deref().unwrap_or("Step").bold(),
+        pipeline.build_number
+    )?;
+    out.push_str(&output::rule(60));
+
+    if let Some(image) = &step.image {
+        writeln!(out, "{} {}", "Image:".dimmed(), image.name)?;
+    }
+    if let Some(started) = step.started_on {
+        writeln!(out, "{} {}", "Started:".dimmed(), format::date(&started))?;
+        if let Some(completed) = step.completed_on {
+            let seconds = (completed - started).num_seconds().max(0) as u64;
+            writeln!(out, "{} {}", "Duration:".dimmed(), format_duration(seconds))?;
+        }
+    }
+
+    let commands = step
+        .setup_commands
+        .iter()
+        .chain(&step.script_commands)
+        .flatten()
+        .filter_map(|c| c.command.as_deref());
+    let mut commands = commands.peekable();
+    if commands.peek().is_some() {
+        writeln!(out)?;
+        writeln!(out, "{}", "Commands:".bold())?;
+        for command in commands {
+            writeln!(out, "  {} {}", "$".dimmed(), command)?;
+        }
+    }
+
+    writeln!(out)?;
+    match log {
+        Some(log) if !log.is_empty() => {
+            writeln!(out, "{}", "Log:".bold())?;
+            out.push_str(log);
+            if !log.ends_with('\n') {
+                out.push('\n');
+            }
+        }
+        _ => writeln!(out, "{}", "No log yet".dimmed())?,
     }
+    Ok(out)
 }
 
 pub(crate) fn format_duration(seconds: u64) -> String {
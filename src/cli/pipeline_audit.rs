@@ -0,0 +1,232 @@
+//! Checking repositories' `bitbucket-pipelines.yml` against a policy, for
+//! `audit pipelines`
+//!
+//! The policy is a TOML file:
+//!
+//! ```toml
+//! required_steps = ["Build and test", "Security scan"]
+//! banned_images = ["node:12*", "python:2*"]  # a trailing * matches any tag
+//! max_size = 20000                           # bytes
+//! ```
+//!
+//! or the same in YAML, when the file ends in `.yml` or `.yaml`.
+//!
+//! Steps are found wherever the file defines them, YAML anchors included, and
+//! images at the top level, on steps and on service definitions.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+use super::output::Porcelain;
+
+pub const PIPELINES_FILE: &str = "bitbucket-pipelines.yml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// Step names every pipeline file must have, compared case-insensitively
+    #[serde(default)]
+    pub required_steps: Vec<String>,
+    /// Images no step may use
+    #[serde(default)]
+    pub banned_images: Vec<String>,
+    /// Largest the file may be, in bytes
+    pub max_size: Option<usize>,
+}
+
+impl Policy {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let yaml = path.extension().is_some_and(|e| e == "yml" || e == "yaml");
+        let policy = if yaml {
+            serde_yaml::from_str(&text).map_err(anyhow::Error::from)
+        } else {
+            toml::from_str(&text).map_err(anyhow::Error::from)
+        };
+        policy.with_context(|| format!("Invalid policy in {}", path.display()))
+    }
+}
+
+/// How one repository's pipeline file measures up to the policy
+#[derive(Debug, Serialize)]
+pub struct PipelineCompliance {
+    pub repository: String,
+    /// Size of the file in bytes; `None` when the repository has none
+    pub size: Option<usize>,
+    pub compliant: bool,
+    pub problems: Vec<String>,
+}
+
+impl Porcelain for PipelineCompliance {
+    fn porcelain(&self) -> String {
+        self.repository.clone()
+    }
+}
+
+/// Check `file`, the contents of the repository's pipeline file if it has one
+pub fn check(repository: &str, file: Option<&str>, policy: &Policy) -> PipelineCompliance {
+    let mut problems = Vec::new();
+    match file {
+        None => {
+            if !policy.required_steps.is_empty() {
+                problems.push(format!("no {}", PIPELINES_FILE));
+            }
+        }
+        Some(text) => {
+            if let Some(max) = policy.max_size
+                && text.len() > max
+            {
+                problems.push(format!("{} bytes, over the {} limit", text.len(), max));
+            }
+            match serde_yaml::from_str::<Value>(text) {
+                Ok(doc) => problems.extend(check_yaml(&doc, policy)),
+                Err(e) => problems.push(format!("not valid YAML: {}", e)),
+            }
+        }
+    }
+    PipelineCompliance {
+        repository: repository.to_string(),
+        size: file.map(str::len),
+        compliant: problems.is_empty(),
+        problems,
+    }
+}
+
+fn check_yaml(doc: &Value, policy: &Policy) -> Vec<String> {
+    let mut steps = Vec::new();
+    // An image used by several steps is reported once
+    let mut images = BTreeSet::new();
+    collect(doc, &mut steps, &mut images);
+
+    let mut problems = Vec::new();
+    for required in &policy.required_steps {
+        if !steps.iter().any(|s| s.eq_ignore_ascii_case(required)) {
+            problems.push(format!("no '{}' step", required));
+        }
+    }
+    for image in &images {
+        if let Some(banned) = policy
+            .banned_images
+            .iter()
+            .find(|pattern| image_matches(pattern, image))
+        {
+            problems.push(format!("uses {} (banned: {})", image, banned));
+        }
+    }
+    problems
+}
+
+/// Gather step names and images from anywhere in `node`
+fn collect(node: &Value, steps: &mut Vec<String>, images: &mut BTreeSet<String>) {
+    match node {
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping {
+                match key.as_str() {
+                    Some("step") => {
+                        if let Some(name) = value["name"].as_str() {
+                            steps.push(name.to_string());
+                        }
+                    }
+                    Some("image") => {
+                        let name = value.as_str().or_else(|| value["name"].as_str());
+                        if let Some(name) = name {
+                            images.insert(name.to_string());
+                        }
+                    }
+                    _ => {}
+                }
+                collect(value, steps, images);
+            }
+        }
+        Value::Sequence(items) => {
+            for item in items {
+                collect(item, steps, images);
+            }
+        }
+        Value::Tagged(tagged) => collect(&tagged.value, steps, images),
+        _ => {}
+    }
+}
+
+/// Whether `image` is `pattern`, where a trailing `*` matches anything
+fn image_matches(pattern: &str, image: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => image.starts_with(prefix),
+        None => {
+            image == pattern
+                || image
+                    .split_once(':')
+                    .is_some_and(|(name, _)| name == pattern)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIPELINES: &str = r#"
+image: node:12.22
+definitions:
+  steps:
+    - step: &build
+        name: Build and test
+        script:
+          - npm test
+pipelines:
+  default:
+    - step: *build
+    - step:
+        name: Deploy
+        image:
+          name: atlassian/pipelines-awscli
+        script:
+          - ./deploy.sh
+"#;
+
+    #[test]
+    fn steps_and_images_are_checked_against_the_policy() {
+        let policy = Policy {
+            required_steps: vec!["build and test".into(), "Security scan".into()],
+            banned_images: vec!["node:12*".into(), "atlassian/pipelines-awscli".into()],
+            max_size: Some(10),
+        };
+        let report = check("acme/engine", Some(PIPELINES), &policy);
+        assert!(!report.compliant);
+        assert_eq!(
+            report.problems[1..],
+            [
+                "no 'Security scan' step",
+                "uses atlassian/pipelines-awscli (banned: atlassian/pipelines-awscli)",
+                "uses node:12.22 (banned: node:12*)",
+            ]
+        );
+        assert!(report.problems[0].ends_with("over the 10 limit"));
+
+        assert!(check("acme/notes", None, &policy).problems == ["no bitbucket-pipelines.yml"]);
+        assert!(check("acme/notes", None, &Policy::default()).compliant);
+    }
+
+    #[test]
+    fn policies_load_from_toml_or_yaml() {
+        let dir = tempfile::tempdir().unwrap();
+        let toml = dir.path().join("policy.toml");
+        std::fs::write(&toml, "required_steps = [\"Build\"]\nmax_size = 100\n").unwrap();
+        let yaml = dir.path().join("policy.yml");
+        std::fs::write(&yaml, "required_steps: [Build]\nmax_size: 100\n").unwrap();
+        for path in [toml, yaml] {
+            let policy = Policy::load(&path).unwrap();
+            assert_eq!(policy.required_steps, ["Build"]);
+            assert_eq!(policy.max_size, Some(100));
+        }
+
+        let unknown = dir.path().join("unknown.yaml");
+        std::fs::write(&unknown, "required_step: [Build]\n").unwrap();
+        assert!(Policy::load(&unknown).is_err());
+    }
+}
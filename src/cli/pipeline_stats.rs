@@ -0,0 +1,426 @@
+//! Pipeline and step durations, success rates and flaky steps
+//! (`bitbucket pipeline stats`)
+//!
+//! Pipelines are grouped by what ran them: the default pipeline, a branch
+//! pattern, pull requests or a custom pipeline. A step is flagged as flaky
+//! when its result keeps flipping between passing and failing on the same
+//! branch, which a step that is simply broken doesn't do.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use colored::Colorize;
+use futures::TryStreamExt;
+use serde::Serialize;
+use tabled::Tabled;
+
+use super::output::Porcelain;
+use super::pipeline::format_duration;
+use super::{UsageError, fanout, format, output};
+use crate::api::BitbucketClient;
+use crate::models::{Pipeline, PipelineResultName, PipelineStateName, PipelineStep};
+
+/// Steps fetched at once; each pipeline's steps are one small request
+const STEP_CONCURRENCY: usize = 8;
+
+/// Flips on one branch needed before a step can be called flaky, so that
+/// breaking once and being fixed doesn't count
+const FLAKY_MIN_FLIPS: usize = 3;
+
+/// Share of consecutive runs on a branch whose result differs from the one
+/// before, at or above which a step is flaky
+const FLAKY_FLIP_RATE: f64 = 0.3;
+
+#[derive(Debug, Serialize)]
+struct PipelineStats {
+    repository: String,
+    since: DateTime<Utc>,
+    pipelines: Vec<Summary>,
+    steps: Vec<StepSummary>,
+}
+
+/// Durations and outcomes of one pipeline or step
+#[derive(Debug, Serialize)]
+struct Summary {
+    name: String,
+    runs: usize,
+    passed: usize,
+    failed: usize,
+    /// Of the runs that passed or failed, the share that passed
+    success_rate: Option<f64>,
+    mean_seconds: Option<u64>,
+    p50_seconds: Option<u64>,
+    p90_seconds: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct StepSummary {
+    #[serde(flatten)]
+    summary: Summary,
+    /// Times a run's result differed from the previous run on the same branch
+    flips: usize,
+    flaky: bool,
+}
+
+impl Porcelain for PipelineStats {
+    /// Flaky steps, one per line
+    fn porcelain(&self) -> String {
+        self.steps
+            .iter()
+            .filter(|s| s.flaky)
+            .map(|s| s.summary.name.clone())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[derive(Tabled)]
+struct SummaryRow {
+    #[tabled(rename = "NAME")]
+    name: String,
+    #[tabled(rename = "RUNS")]
+    runs: usize,
+    #[tabled(rename = "SUCCESS")]
+    success: String,
+    #[tabled(rename = "MEAN")]
+    mean: String,
+    #[tabled(rename = "P50")]
+    p50: String,
+    #[tabled(rename = "P90")]
+    p90: String,
+}
+
+impl From<&Summary> for SummaryRow {
+    fn from(summary: &Summary) -> Self {
+        let duration = |seconds: Option<u64>| seconds.map_or("-".to_string(), format_duration);
+        Self {
+            name: summary.name.clone(),
+            runs: summary.runs,
+            success: summary
+                .success_rate
+                .map_or("-".to_string(), |rate| format!("{:.0}%", rate * 100.0)),
+            mean: duration(summary.mean_seconds),
+            p50: duration(summary.p50_seconds),
+            p90: duration(summary.p90_seconds),
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct StepRow {
+    #[tabled(inline)]
+    summary: SummaryRow,
+    #[tabled(rename = "FLIPS")]
+    flips: String,
+}
+
+/// How a pipeline or step run ended, for the runs that count
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Passed,
+    Failed,
+}
+
+/// One finished run of a pipeline or step
+struct Run<'a> {
+    branch: &'a str,
+    created_on: DateTime<Utc>,
+    seconds: Option<u64>,
+    /// `None` when stopped, expired or otherwise neither passed nor failed
+    outcome: Option<Outcome>,
+}
+
+/// Report on `repo`'s pipelines created since `since`
+pub async fn run(workspace: &str, repo_slug: &str, since: &str) -> Result<()> {
+    let since = format::parse_time(since).ok_or_else(|| {
+        UsageError(format!(
+            "Invalid --since '{}': use an age such as 30d or a date such as 2024-06-01",
+            since
+        ))
+    })?;
+    let client = BitbucketClient::from_stored().await?;
+
+    let filter = format!(
+        "created_on >= {}",
+        since.to_rfc3339_opts(SecondsFormat::Secs, true)
+    );
+    let pipelines: Vec<Pipeline> = client
+        .stream_pipelines(workspace, repo_slug, Some(&filter))
+        .try_filter(|p| futures::future::ready(p.state.name == PipelineStateName::Completed))
+        .try_collect()
+        .await?;
+
+    let mut outcome = fanout::run(
+        "Fetching steps",
+        pipelines.iter().map(|p| p.uuid.clone()).collect(),
+        STEP_CONCURRENCY,
+        |uuid: String| {
+            let client = &client;
+            async move {
+                let steps = client
+                    .list_pipeline_steps(workspace, repo_slug, &uuid)
+                    .await?;
+                Ok(steps.values)
+            }
+        },
+    )
+    .await;
+    let mut steps: BTreeMap<String, Vec<PipelineStep>> = outcome.succeeded.drain(..).collect();
+    let runs: Vec<(&Pipeline, Vec<PipelineStep>)> = pipelines
+        .iter()
+        .filter_map(|p| steps.remove(&p.uuid).map(|steps| (p, steps)))
+        .collect();
+
+    let stats = compute(format!("{}/{}", workspace, repo_slug), since, &runs);
+    if !output::print(&stats)? {
+        print_tables(&stats)?;
+    }
+    outcome.finish("fetch steps of", "pipelines")?;
+    Ok(())
+}
+
+fn compute(
+    repository: String,
+    since: DateTime<Utc>,
+    runs: &[(&Pipeline, Vec<PipelineStep>)],
+) -> PipelineStats {
+    let mut pipelines: BTreeMap<String, Vec<Run>> = BTreeMap::new();
+    let mut steps: BTreeMap<String, Vec<Run>> = BTreeMap::new();
+
+    for (pipeline, pipeline_steps) in runs {
+        let branch = pipeline.target.ref_name.as_deref().unwrap_or_default();
+        pipelines
+            .entry(pipeline_name(pipeline))
+            .or_default()
+            .push(Run {
+                branch,
+                created_on: pipeline.created_on,
+                seconds: pipeline
+                    .completed_on
+                    .map(|done| (done - pipeline.created_on).num_seconds().max(0) as u64),
+                outcome: match pipeline.state.result.as_ref().map(|r| &r.name) {
+                    Some(PipelineResultName::Successful) => Some(Outcome::Passed),
+                    Some(PipelineResultName::Failed | PipelineResultName::Error) => {
+                        Some(Outcome::Failed)
+                    }
+                    _ => None,
+                },
+            });
+
+        for (index, step) in pipeline_steps.iter().enumerate() {
+            let name = step
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("Step {}", index + 1));
+            let result = step
+                .state
+                .as_ref()
+                .and_then(|s| s.result.as_ref())
+                .map(|r| r.name.as_str());
+            steps.entry(name).or_default().push(Run {
+                branch,
+                created_on: pipeline.created_on,
+                seconds: match (step.started_on, step.completed_on) {
+                    (Some(start), Some(end)) => Some((end - start).num_seconds().max(0) as u64),
+                    _ => None,
+                },
+                outcome: match result {
+                    Some("SUCCESSFUL") => Some(Outcome::Passed),
+                    Some("FAILED" | "ERROR") => Some(Outcome::Failed),
+                    _ => None,
+                },
+            });
+        }
+    }
+
+    let mut pipelines: Vec<Summary> = pipelines
+        .into_iter()
+        .map(|(name, runs)| summarize(name, &runs))
+        .collect();
+    pipelines.sort_by(|a, b| b.runs.cmp(&a.runs).then(a.name.cmp(&b.name)));
+
+    let mut steps: Vec<StepSummary> = steps
+        .into_iter()
+        .map(|(name, mut runs)| {
+            runs.sort_by_key(|run| run.created_on);
+            let (flips, flaky) = flips(&runs);
+            StepSummary {
+                summary: summarize(name, &runs),
+                flips,
+                flaky,
+            }
+        })
+        .collect();
+    steps.sort_by(|a, b| {
+        b.flaky
+            .cmp(&a.flaky)
+            .then(b.summary.runs.cmp(&a.summary.runs))
+            .then(a.summary.name.cmp(&b.summary.name))
+    });
+
+    PipelineStats {
+        repository,
+        since,
+        pipelines,
+        steps,
+    }
+}
+
+/// What a pipeline was run as: `default`, `branches: main`, `custom: deploy`
+fn pipeline_name(pipeline: &Pipeline) -> String {
+    match &pipeline.target.selector {
+        Some(selector) => match &selector.pattern {
+            Some(pattern) => format!("{}: {}", selector.selector_type, pattern),
+            None => selector.selector_type.clone(),
+        },
+        None => "default".to_string(),
+    }
+}
+
+fn summarize(name: String, runs: &[Run]) -> Summary {
+    let passed = runs
+        .iter()
+        .filter(|r| r.outcome == Some(Outcome::Passed))
+        .count();
+    let failed = runs
+        .iter()
+        .filter(|r| r.outcome == Some(Outcome::Failed))
+        .count();
+    let mut seconds: Vec<u64> = runs.iter().filter_map(|r| r.seconds).collect();
+    seconds.sort_unstable();
+
+    Summary {
+        name,
+        runs: runs.len(),
+        passed,
+        failed,
+        success_rate: (passed + failed > 0).then(|| passed as f64 / (passed + failed) as f64),
+        mean_seconds: (!seconds.is_empty())
+            .then(|| seconds.iter().sum::<u64>() / seconds.len() as u64),
+        p50_seconds: percentile(&seconds, 50),
+        p90_seconds: percentile(&seconds, 90),
+    }
+}
+
+/// The nearest-rank percentile of sorted values
+fn percentile(sorted: &[u64], percent: usize) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (percent * sorted.len()).div_ceil(100).max(1);
+    Some(sorted[rank - 1])
+}
+
+/// How often consecutive runs on the same branch disagree, and whether
+/// that's often enough to call the step flaky. `runs` is oldest first.
+fn flips(runs: &[Run]) -> (usize, bool) {
+    let mut by_branch: BTreeMap<&str, Vec<Outcome>> = BTreeMap::new();
+    for run in runs {
+        if let Some(outcome) = run.outcome {
+            by_branch.entry(run.branch).or_default().push(outcome);
+        }
+    }
+
+    let mut flips = 0;
+    let mut flaky = false;
+    for outcomes in by_branch.values() {
+        let changed = outcomes.windows(2).filter(|w| w[0] != w[1]).count();
+        flips += changed;
+        if changed >= FLAKY_MIN_FLIPS
+            && changed as f64 / (outcomes.len() - 1) as f64 >= FLAKY_FLIP_RATE
+        {
+            flaky = true;
+        }
+    }
+    (flips, flaky)
+}
+
+fn print_tables(stats: &PipelineStats) -> Result<()> {
+    if stats.pipelines.is_empty() {
+        output::note(format!(
+            "No completed pipelines since {}",
+            format::date(&stats.since)
+        ));
+        return Ok(());
+    }
+    if output::is_tty() {
+        println!(
+            "{} since {}\n",
+            stats.repository.bold(),
+            format::date(&stats.since)
+        );
+    }
+
+    output::table(stats.pipelines.iter().map(SummaryRow::from).collect())?;
+    if stats.steps.is_empty() {
+        return Ok(());
+    }
+    println!();
+    output::table(
+        stats
+            .steps
+            .iter()
+            .map(|step| StepRow {
+                summary: SummaryRow::from(&step.summary),
+                flips: if step.flaky {
+                    format!("{} flaky", step.flips).yellow().to_string()
+                } else {
+                    step.flips.to_string()
+                },
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(branch: &str, minute: u32, outcome: Option<Outcome>) -> Run<'_> {
+        Run {
+            branch,
+            created_on: format::parse_date(&format!("2024-06-01T10:{:02}:00Z", minute)).unwrap(),
+            seconds: Some(60),
+            outcome,
+        }
+    }
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        let values = [10, 20, 30, 40, 50, 60, 70, 80, 90, 100];
+        assert_eq!(percentile(&values, 50), Some(50));
+        assert_eq!(percentile(&values, 90), Some(90));
+        assert_eq!(percentile(&[7], 90), Some(7));
+        assert_eq!(percentile(&[], 50), None);
+    }
+
+    #[test]
+    fn steps_that_keep_flipping_are_flaky() {
+        use Outcome::{Failed, Passed};
+        let flaky: Vec<Run> = [Passed, Failed, Passed, Passed, Failed]
+            .into_iter()
+            .enumerate()
+            .map(|(i, outcome)| run("main", i as u32, Some(outcome)))
+            .collect();
+        assert_eq!(flips(&flaky), (3, true));
+
+        // Broken once and then fixed is not flaky
+        let fixed: Vec<Run> = [Passed, Failed, Failed, Passed, Passed, Passed]
+            .into_iter()
+            .enumerate()
+            .map(|(i, outcome)| run("main", i as u32, Some(outcome)))
+            .collect();
+        assert_eq!(flips(&fixed), (2, false));
+
+        // Different results on different branches don't count
+        let branches = vec![
+            run("main", 0, Some(Passed)),
+            run("topic", 1, Some(Failed)),
+            run("main", 2, Some(Passed)),
+            run("topic", 3, Some(Failed)),
+            run("main", 4, None),
+        ];
+        assert_eq!(flips(&branches), (0, false));
+    }
+}
@@ -1,20 +1,56 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use clap::{Subcommand, ValueEnum};
 use colored::Colorize;
-use tabled::{Table, Tabled};
-
+use futures::{StreamExt, TryStreamExt, stream};
+use serde::Serialize;
+use serde_json::json;
+use tabled::Tabled;
+
+use super::branch;
+use super::browse::WEB_URL;
+use super::drafts::{self, DraftTarget};
+use super::git::parse_repo;
+use super::hooks::{self, Hook};
+use super::icons::Icon;
+use super::last_run::UpdatedSince;
+use super::output::Porcelain;
+use super::range::DateRange;
+use super::{
+    UsageError, confirm, download, fanout, format, git, lint, markdown, output, pager, patch,
+    reviewers, user,
+};
 use crate::api::BitbucketClient;
+use crate::config::{Config, PrConfig};
+use crate::error::Error;
 use crate::models::{
-    BranchInfo, CreatePullRequestRequest, MergePullRequestRequest, MergeStrategy,
-    PullRequestBranchRef, PullRequestState,
+    BranchInfo, Commit, CommitStatus, CommitStatusState, CreatePullRequestRequest, DiffStat,
+    MergePullRequestRequest, MergeStrategy, Participant, ParticipantRole, ParticipantState,
+    PullRequest, PullRequestBranchRef, PullRequestComment, PullRequestState, UserRef,
 };
 
+/// Comments `pr view` shows at the end of its summary
+const LATEST_COMMENTS: usize = 3;
+
 #[derive(Subcommand)]
 pub enum PrCommands {
     /// List pull requests
     List {
         /// Repository in format workspace/repo-slug
-        repo: String,
+        #[arg(required_unless_present_any = ["repos", "group"])]
+        repo: Option<String>,
+
+        /// Another repository to list; repeat to combine several
+        #[arg(long = "repo", value_name = "REPO")]
+        repos: Vec<String>,
+
+        /// Combine the repositories of a `[groups]` entry in the config
+        #[arg(long)]
+        group: Option<String>,
 
         /// Filter by state
         #[arg(short, long, value_enum)]
@@ -23,6 +59,25 @@ pub enum PrCommands {
         /// Number of results
         #[arg(short, long, default_value = "25")]
         limit: u32,
+
+        /// Fetch every page instead of stopping at --limit
+        #[arg(long, conflicts_with = "limit")]
+        all: bool,
+
+        /// Only pull requests by this user (account ID, UUID or @me)
+        #[arg(long, value_name = "USER")]
+        author: Option<String>,
+
+        /// Only pull requests this user reviews (account ID, UUID or @me)
+        #[arg(long, value_name = "USER")]
+        reviewer: Option<String>,
+
+        /// Only pull requests last updated in this window
+        #[command(flatten)]
+        range: DateRange,
+
+        #[command(flatten)]
+        updated_since: UpdatedSince,
     },
 
     /// View pull request details
@@ -36,6 +91,14 @@ pub enum PrCommands {
         /// Open in browser
         #[arg(short, long)]
         web: bool,
+
+        /// Print the web URL instead, without fetching the pull request
+        #[arg(long, conflicts_with = "web")]
+        url: bool,
+
+        /// Show the description as written instead of rendering its Markdown
+        #[arg(long)]
+        raw: bool,
     },
 
     /// Create a new pull request
@@ -60,8 +123,20 @@ pub enum PrCommands {
         body: Option<String>,
 
         /// Close source branch after merge
-        #[arg(long)]
+        #[arg(long, alias = "close-after-merge")]
         close_source_branch: bool,
+
+        /// Keep the source branch after merge despite [pr] close_source_branch
+        #[arg(long, conflicts_with = "close_source_branch")]
+        keep_source_branch: bool,
+
+        /// Request a review from this user (account ID, UUID or @me; repeatable)
+        #[arg(long = "reviewer", value_name = "USER")]
+        reviewers: Vec<String>,
+
+        /// Skip the pre-submit checks configured under [pr]
+        #[arg(long)]
+        no_verify: bool,
     },
 
     /// Merge a pull request
@@ -72,17 +147,33 @@ pub enum PrCommands {
         /// Pull request ID
         id: u64,
 
-        /// Merge strategy
-        #[arg(short, long, value_enum, default_value = "merge-commit")]
-        strategy: MergeStrategyArg,
+        /// Merge strategy (default: [pr] merge_strategy if the repository
+        /// allows it into the destination branch, else the repository's
+        /// default there, else merge-commit)
+        #[arg(short, long, value_enum)]
+        strategy: Option<MergeStrategyArg>,
 
-        /// Commit message
+        /// Commit message (default: [pr] merge_message, if set). A squash
+        /// without one opens $EDITOR on the title and commit list
         #[arg(short, long)]
         message: Option<String>,
 
+        /// Squash with the default message instead of opening $EDITOR
+        #[arg(long, conflicts_with = "message")]
+        no_edit: bool,
+
         /// Close source branch
         #[arg(long)]
         close_source_branch: bool,
+
+        /// Keep the source branch despite [pr] close_source_branch
+        #[arg(long, conflicts_with = "close_source_branch")]
+        keep_source_branch: bool,
+
+        /// Afterwards, switch to the destination branch and pull, prune
+        /// origin, and delete the local source branch (in a clone of the repository)
+        #[arg(long)]
+        delete_local_branch: bool,
     },
 
     /// Approve a pull request
@@ -103,6 +194,59 @@ pub enum PrCommands {
         id: u64,
     },
 
+    /// Decline stale open pull requests: long inactive, with a deleted
+    /// source branch, or already merged elsewhere
+    Cleanup {
+        /// Repository in format workspace/repo-slug
+        repo: String,
+
+        /// Inactivity after which a pull request is stale, e.g. 90d, 12w
+        #[arg(long, default_value = "90d", value_name = "AGE")]
+        older_than: String,
+
+        /// Only count inactive pull requests nobody has commented on or
+        /// reviewed
+        #[arg(long)]
+        no_activity: bool,
+
+        /// List stale pull requests without declining them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Comment to leave on each before declining (default: the reasons)
+        #[arg(long)]
+        comment: Option<String>,
+    },
+
+    /// Rank open pull requests by readiness to merge: approvals met, builds
+    /// green, no conflicts, oldest first
+    Queue {
+        /// Repository in format workspace/repo-slug
+        repo: String,
+
+        /// Approvals a pull request needs to be ready
+        #[arg(long, default_value = "1")]
+        approvals: usize,
+
+        /// Merge the head of the queue if it is ready
+        #[arg(long)]
+        merge_next: bool,
+
+        /// Merge strategy for --merge-next (default: [pr] merge_strategy if
+        /// the repository allows it, else the repository's default, else
+        /// merge-commit)
+        #[arg(short, long, value_enum)]
+        strategy: Option<MergeStrategyArg>,
+
+        /// Close the source branch when merging with --merge-next
+        #[arg(long, requires = "merge_next")]
+        close_source_branch: bool,
+
+        /// Keep the source branch despite [pr] close_source_branch
+        #[arg(long, requires = "merge_next", conflicts_with = "close_source_branch")]
+        keep_source_branch: bool,
+    },
+
     /// Checkout a pull request branch locally
     Checkout {
         /// Repository in format workspace/repo-slug
@@ -110,6 +254,12 @@ pub enum PrCommands {
 
         /// Pull request ID
         id: u64,
+
+        /// Check it out into a new git worktree instead of switching
+        /// branches here (default path: [pr] worktree_path, or
+        /// ../<repo>-pr-<id>)
+        #[arg(long, value_name = "PATH")]
+        worktree: Option<Option<PathBuf>>,
     },
 
     /// View pull request diff
@@ -119,6 +269,16 @@ pub enum PrCommands {
 
         /// Pull request ID
         id: u64,
+
+        /// Save the diff to a file instead of showing it, with progress and
+        /// resume for large diffs
+        #[arg(short = 'O', long, value_name = "FILE")]
+        output_file: Option<PathBuf>,
+
+        /// Check the diff against the working tree instead, reporting the
+        /// hunks that would conflict
+        #[arg(long, conflicts_with = "output_file")]
+        local: bool,
     },
 
     /// Add a comment to a pull request
@@ -172,6 +332,37 @@ pub enum PrCommands {
         #[arg(short, long, default_value = "100")]
         scan_limit: u32,
     },
+
+    /// Suggest reviewers who recently committed to the changed files,
+    /// favouring those with fewer open reviews
+    SuggestReviewers {
+        /// Repository in format workspace/repo-slug
+        repo: String,
+
+        /// Pull request ID
+        #[arg(required_unless_present = "source", conflicts_with = "source")]
+        id: Option<u64>,
+
+        /// Suggest for this branch before opening a pull request
+        #[arg(long, value_name = "BRANCH")]
+        source: Option<String>,
+
+        /// Branch --source would merge into (default: the main branch)
+        #[arg(long, value_name = "BRANCH", requires = "source")]
+        destination: Option<String>,
+
+        /// Reviewers to suggest
+        #[arg(short = 'n', long, default_value = "2")]
+        count: usize,
+
+        /// Recent commits to look at per file
+        #[arg(long, default_value = "20")]
+        depth: usize,
+
+        /// Add the suggested reviewers to the pull request
+        #[arg(long, requires = "id")]
+        apply: bool,
+    },
 }
 
 #[derive(ValueEnum, Clone)]
@@ -198,6 +389,9 @@ pub enum MergeStrategyArg {
     MergeCommit,
     Squash,
     FastForward,
+    SquashFastForward,
+    RebaseFastForward,
+    RebaseMerge,
 }
 
 impl From<MergeStrategyArg> for MergeStrategy {
@@ -206,12 +400,122 @@ impl From<MergeStrategyArg> for MergeStrategy {
             MergeStrategyArg::MergeCommit => MergeStrategy::MergeCommit,
             MergeStrategyArg::Squash => MergeStrategy::Squash,
             MergeStrategyArg::FastForward => MergeStrategy::FastForward,
+            MergeStrategyArg::SquashFastForward => MergeStrategy::SquashFastForward,
+            MergeStrategyArg::RebaseFastForward => MergeStrategy::RebaseFastForward,
+            MergeStrategyArg::RebaseMerge => MergeStrategy::RebaseMerge,
         }
     }
 }
 
+/// `strategy`, or else `[pr] merge_strategy` where the repository allows it
+/// into `destination`, or else the repository's default there, or else a
+/// merge commit; an error if the repository doesn't allow the one chosen
+fn merge_strategy(
+    strategy: Option<MergeStrategyArg>,
+    config: &PrConfig,
+    destination: &BranchInfo,
+) -> Result<MergeStrategy> {
+    let allowed = &destination.merge_strategies;
+    let allows = |s: &MergeStrategy| allowed.is_empty() || allowed.iter().any(|a| a == s.as_str());
+    let strategy = strategy
+        .map(MergeStrategy::from)
+        .or_else(|| config.merge_strategy.clone().filter(allows))
+        .or_else(|| {
+            destination
+                .default_merge_strategy
+                .as_deref()
+                .and_then(MergeStrategy::from_api)
+        })
+        .unwrap_or(MergeStrategy::MergeCommit);
+    if !allows(&strategy) {
+        let names: Vec<String> = allowed.iter().map(|s| s.replace('_', "-")).collect();
+        anyhow::bail!(UsageError(format!(
+            "The repository doesn't allow {} merges into {}; pass --strategy with one of: {}",
+            strategy.as_str().replace('_', "-"),
+            destination.name,
+            names.join(", ")
+        )));
+    }
+    Ok(strategy)
+}
+
+/// Whether to close the source branch, given the flags and
+/// `[pr] close_source_branch`
+fn closes_source_branch(close: bool, keep: bool, config: &PrConfig) -> bool {
+    close || (config.close_source_branch && !keep)
+}
+
+/// Where `pr checkout --worktree` goes without `[pr] worktree_path`
+const DEFAULT_WORKTREE_PATH: &str = "../{repo}-pr-{id}";
+
+/// A `[pr] worktree_path` template filled in; slashes in the branch name
+/// become dashes so it stays one directory
+fn worktree_path(template: &str, repo_slug: &str, id: u64, branch: &str) -> String {
+    template
+        .replace("{repo}", repo_slug)
+        .replace("{id}", &id.to_string())
+        .replace("{branch}", &branch.replace('/', "-"))
+}
+
+/// `[pr] merge_message` with the pull request's details filled in
+fn merge_message(template: &str, pr: &PullRequest) -> String {
+    template
+        .replace("{title}", &pr.title)
+        .replace("{id}", &pr.id.to_string())
+        .replace("{source}", &pr.source.branch.name)
+        .replace("{destination}", &pr.destination.branch.name)
+        .replace("{author}", &pr.author.display_name)
+}
+
+/// A squash commit message: the title with the pull request's number, then
+/// each commit's summary, oldest first
+fn squash_message(title: &str, id: u64, commits: &[Commit]) -> String {
+    let mut message = format!("{} (#{})\n", title, id);
+    if !commits.is_empty() {
+        message.push('\n');
+    }
+    for commit in commits.iter().rev() {
+        let summary = commit
+            .message
+            .as_deref()
+            .and_then(|m| m.lines().next())
+            .unwrap_or_default();
+        message.push_str(&format!("* {}\n", summary));
+    }
+    message
+}
+
+/// Let the user adjust `draft` in their editor, as `git commit` does:
+/// `#` lines are dropped, and `None` means an empty message or an unsaved
+/// file, to abort on
+fn edit_squash_message(draft: &str, repo: &str, id: u64) -> Result<Option<String>> {
+    let text = format!(
+        "{}\n# Squash message for pull request #{} in {}.\n\
+         # Lines starting with '#' are ignored; an empty message aborts the merge.\n",
+        draft, id, repo
+    );
+    let Some(edited) = dialoguer::Editor::new()
+        .extension(".txt")
+        .edit(&text)
+        .context("Failed to open an editor; set $EDITOR or pass --message")?
+    else {
+        return Ok(None);
+    };
+    let message = strip_comments(&edited);
+    Ok((!message.is_empty()).then_some(message))
+}
+
+fn strip_comments(text: &str) -> String {
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
 #[derive(Tabled)]
-struct PrRow {
+pub(super) struct PrRow {
     #[tabled(rename = "ID")]
     id: u64,
     #[tabled(rename = "TITLE")]
@@ -224,6 +528,27 @@ struct PrRow {
     updated: String,
 }
 
+impl From<&PullRequest> for PrRow {
+    fn from(pr: &PullRequest) -> Self {
+        Self {
+            id: pr.id,
+            title: pr.title.clone(),
+            author: pr.author.display_name.clone(),
+            state: format_state(&pr.state),
+            updated: format::date(&pr.updated_on),
+        }
+    }
+}
+
+/// A row of `pr list` across several repositories
+#[derive(Tabled)]
+struct RepoPrRow {
+    #[tabled(rename = "REPO")]
+    repo: String,
+    #[tabled(inline)]
+    pr: PrRow,
+}
+
 #[derive(Tabled)]
 struct PipelineRow {
     #[tabled(rename = "#")]
@@ -254,64 +579,306 @@ struct CommentRow {
     content: String,
 }
 
+#[derive(Tabled)]
+struct StaleRow {
+    #[tabled(rename = "ID")]
+    id: u64,
+    #[tabled(rename = "TITLE")]
+    title: String,
+    #[tabled(rename = "AUTHOR")]
+    author: String,
+    #[tabled(rename = "UPDATED")]
+    updated: String,
+    #[tabled(rename = "REASON")]
+    reason: String,
+}
+
+/// An open pull request found stale by `pr cleanup`
+#[derive(Serialize)]
+struct StalePullRequest {
+    id: u64,
+    title: String,
+    author: String,
+    updated_on: DateTime<Utc>,
+    reasons: Vec<String>,
+}
+
+impl Porcelain for StalePullRequest {
+    fn porcelain(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+#[derive(Tabled)]
+struct QueueRow {
+    #[tabled(rename = "#")]
+    position: usize,
+    #[tabled(rename = "ID")]
+    id: u64,
+    #[tabled(rename = "TITLE")]
+    title: String,
+    #[tabled(rename = "APPROVALS")]
+    approvals: String,
+    #[tabled(rename = "BUILDS")]
+    builds: String,
+    #[tabled(rename = "AGE")]
+    age: String,
+    #[tabled(rename = "STATUS")]
+    status: String,
+}
+
+/// Combined state of a pull request's build statuses
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Builds {
+    Passed,
+    Running,
+    Failed,
+    /// Nothing reported
+    None,
+}
+
+impl Builds {
+    fn of(statuses: &[CommitStatus]) -> Self {
+        let any = |state: CommitStatusState| statuses.iter().any(|s| s.state == state);
+        if any(CommitStatusState::Failed) || any(CommitStatusState::Stopped) {
+            Builds::Failed
+        } else if any(CommitStatusState::Inprogress) {
+            Builds::Running
+        } else if statuses.is_empty() {
+            Builds::None
+        } else {
+            Builds::Passed
+        }
+    }
+
+    fn label(self) -> String {
+        match self {
+            Builds::Passed => "passed".green().to_string(),
+            Builds::Running => "running".yellow().to_string(),
+            Builds::Failed => "failed".red().to_string(),
+            Builds::None => "-".to_string(),
+        }
+    }
+}
+
+/// An open pull request's place in the merge queue
+#[derive(Debug, Serialize)]
+struct QueueEntry {
+    id: u64,
+    title: String,
+    author: String,
+    created_on: DateTime<Utc>,
+    approvals: usize,
+    builds: Builds,
+    conflicts: bool,
+    /// What stops it merging; empty when ready
+    blockers: Vec<String>,
+}
+
+impl QueueEntry {
+    fn ready(&self) -> bool {
+        self.blockers.is_empty()
+    }
+}
+
+impl Porcelain for QueueEntry {
+    fn porcelain(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+/// A stale pull request to comment on and decline
+#[derive(Clone)]
+struct Decline {
+    id: u64,
+    message: String,
+}
+
+impl fmt::Display for Decline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.id)
+    }
+}
+
 impl PrCommands {
     pub async fn run(self) -> Result<()> {
         match self {
-            PrCommands::List { repo, state, limit } => {
-                let (workspace, repo_slug) = parse_repo(&repo)?;
+            PrCommands::List {
+                repo,
+                repos,
+                group,
+                state,
+                limit,
+                all,
+                author,
+                reviewer,
+                range,
+                updated_since,
+            } => {
+                let mut names: Vec<String> = repo.into_iter().chain(repos).collect();
+                if let Some(group) = &group {
+                    let config = Config::load()?;
+                    let members = config.groups.get(group).ok_or_else(|| {
+                        UsageError(format!(
+                            "No repository group '{}'; define it under [groups] in the config",
+                            group
+                        ))
+                    })?;
+                    names.extend(members.iter().cloned());
+                }
+                let mut seen = std::collections::HashSet::new();
+                names.retain(|name| seen.insert(name.clone()));
+                let repositories: Vec<String> = names
+                    .iter()
+                    .map(|name| parse_repo(name).map(|(ws, slug)| format!("{}/{}", ws, slug)))
+                    .collect::<Result<_>>()?;
+                let range = updated_since.apply(range, "pr list", &repositories)?;
+                let all = all || updated_since.wants_everything();
+                let mut filters: Vec<String> = range.filter("updated_on")?.into_iter().collect();
+                let state: Option<PullRequestState> = state.map(Into::into);
                 let client = BitbucketClient::from_stored().await?;
+                if let Some(author) = &author {
+                    let author = user::resolve(&client, author).await?;
+                    filters.push(user::filter("author", &author));
+                }
+                if let Some(reviewer) = &reviewer {
+                    let reviewer = user::resolve(&client, reviewer).await?;
+                    filters.push(user::filter("reviewers", &reviewer));
+                }
+                let query = (!filters.is_empty()).then(|| filters.join(" AND "));
+
+                if let [name] = names.as_slice()
+                    && group.is_none()
+                {
+                    let prs = if all {
+                        let (workspace, repo_slug) = parse_repo(name)?;
+                        client
+                            .stream_pull_requests(
+                                &workspace,
+                                &repo_slug,
+                                state.clone(),
+                                query.as_deref(),
+                            )
+                            .boxed()
+                    } else {
+                        let prs =
+                            list_prs(&client, name, &state, query.as_deref(), limit, false).await?;
+                        stream::iter(prs.into_iter().map(Ok)).boxed()
+                    };
+                    let mut newest = None;
+                    let prs = prs.inspect_ok(|pr| newest = newest.max(Some(pr.updated_on)));
+                    let count = output::stream(prs, |pr| PrRow::from(pr)).await?;
+                    if all {
+                        updated_since.listed("pr list", &repositories[0], newest);
+                    }
+                    if count == 0 && !output::is_structured() {
+                        output::note("No pull requests found");
+                    }
+                    return Ok(());
+                }
 
-                let prs = client
-                    .list_pull_requests(
-                        &workspace,
-                        &repo_slug,
-                        state.map(|s| s.into()),
-                        None,
-                        Some(limit),
-                    )
-                    .await?;
+                let listed = fanout::run(
+                    "Listing pull requests",
+                    names,
+                    fanout::DEFAULT_CONCURRENCY,
+                    |name| {
+                        let client = &client;
+                        let state = &state;
+                        let query = query.as_deref();
+                        async move { list_prs(client, &name, state, query, limit, all).await }
+                    },
+                )
+                .await
+                .finish("list pull requests in", "repositories")?;
+                if all {
+                    for (name, prs) in &listed {
+                        let (workspace, repo_slug) = parse_repo(name)?;
+                        let newest = prs.iter().map(|pr| pr.updated_on).max();
+                        let repository = format!("{}/{}", workspace, repo_slug);
+                        updated_since.listed("pr list", &repository, newest);
+                    }
+                }
+
+                // Most recently updated first across every repository
+                let mut prs: Vec<(String, PullRequest)> = listed
+                    .into_iter()
+                    .flat_map(|(name, prs)| prs.into_iter().map(move |pr| (name.clone(), pr)))
+                    .collect();
+                prs.sort_by_key(|(_, pr)| std::cmp::Reverse(pr.updated_on));
+                if !all {
+                    prs.truncate(limit as usize);
+                }
 
-                if prs.values.is_empty() {
-                    println!("No pull requests found");
+                let (names, prs): (Vec<String>, Vec<PullRequest>) = prs.into_iter().unzip();
+                if output::print(&prs)? {
                     return Ok(());
                 }
 
-                let rows: Vec<PrRow> = prs
-                    .values
-                    .iter()
-                    .map(|pr| PrRow {
-                        id: pr.id,
-                        title: pr.title.chars().take(50).collect(),
-                        author: pr.author.display_name.clone(),
-                        state: format_state(&pr.state),
-                        updated: pr.updated_on.format("%Y-%m-%d").to_string(),
+                let rows: Vec<RepoPrRow> = names
+                    .into_iter()
+                    .zip(&prs)
+                    .map(|(repo, pr)| RepoPrRow {
+                        repo,
+                        pr: PrRow::from(pr),
                     })
                     .collect();
+                if rows.is_empty() {
+                    output::note("No pull requests found");
+                    return Ok(());
+                }
 
-                let table = Table::new(rows).to_string();
-                println!("{}", table);
+                output::table(rows)?;
 
                 Ok(())
             }
 
-            PrCommands::View { repo, id, web } => {
+            PrCommands::View {
+                repo,
+                id,
+                web,
+                url,
+                raw,
+            } => {
                 let (workspace, repo_slug) = parse_repo(&repo)?;
+                if url {
+                    println!(
+                        "{}/{}/{}/pull-requests/{}",
+                        WEB_URL, workspace, repo_slug, id
+                    );
+                    return Ok(());
+                }
                 let client = BitbucketClient::from_stored().await?;
-                let pr = client.get_pull_request(&workspace, &repo_slug, id).await?;
 
-                if web {
-                    if let Some(links) = &pr.links {
-                        if let Some(html) = &links.html {
-                            open::that(&html.href)?;
-                            println!("Opened {} in browser", html.href.cyan());
-                            return Ok(());
-                        }
+                if web || output::is_structured() {
+                    let pr = client.get_pull_request(&workspace, &repo_slug, id).await?;
+                    if web {
+                        let Some(html) = pr.links.as_ref().and_then(|l| l.html.as_ref()) else {
+                            anyhow::bail!("Could not find PR URL");
+                        };
+                        open::that(&html.href)?;
+                        println!("Opened {} in browser", html.href.cyan());
+                        return Ok(());
                     }
-                    anyhow::bail!("Could not find PR URL");
+                    output::print(&pr)?;
+                    return Ok(());
                 }
 
+                // Everything the summary shows, requested at once; only the
+                // pull request itself has to succeed
+                let (pr, statuses, diffstat, comments) = tokio::join!(
+                    client.get_pull_request(&workspace, &repo_slug, id),
+                    client.list_pr_statuses(&workspace, &repo_slug, id),
+                    client.list_pr_diffstat(&workspace, &repo_slug, id),
+                    client.latest_pr_comments(&workspace, &repo_slug, id, LATEST_COMMENTS),
+                );
+                let pr = pr?;
+                let statuses = optional("statuses", statuses);
+                let diffstat = optional("diffstat", diffstat);
+                let comments = optional("comments", comments);
+
                 println!("{} {} #{}", format_state(&pr.state), pr.title.bold(), pr.id);
-                println!("{}", "─".repeat(60));
+                print!("{}", output::rule(60));
 
                 println!(
                     "{} {} → {}",
@@ -320,16 +887,8 @@ impl PrCommands {
                     pr.destination.branch.name.green()
                 );
                 println!("{} {}", "Author:".dimmed(), pr.author.display_name);
-                println!(
-                    "{} {}",
-                    "Created:".dimmed(),
-                    pr.created_on.format("%Y-%m-%d %H:%M")
-                );
-                println!(
-                    "{} {}",
-                    "Updated:".dimmed(),
-                    pr.updated_on.format("%Y-%m-%d %H:%M")
-                );
+                println!("{} {}", "Created:".dimmed(), format::date(&pr.created_on));
+                println!("{} {}", "Updated:".dimmed(), format::date(&pr.updated_on));
 
                 if let Some(count) = pr.comment_count {
                     println!("{} {}", "Comments:".dimmed(), count);
@@ -343,11 +902,18 @@ impl PrCommands {
 
                 // Show reviewers/approvals
                 if let Some(participants) = &pr.participants {
-                    let approvals: Vec<_> = participants
-                        .iter()
-                        .filter(|p| p.approved)
-                        .map(|p| p.user.display_name.clone())
-                        .collect();
+                    let names = |keep: &dyn Fn(&Participant) -> bool| -> Vec<String> {
+                        participants
+                            .iter()
+                            .filter(|p| keep(p))
+                            .map(|p| p.user.display_name.clone())
+                            .collect()
+                    };
+                    let approvals = names(&|p| p.approved);
+                    let changes = names(&|p| p.state == Some(ParticipantState::ChangesRequested));
+                    let waiting = names(&|p| {
+                        p.role == ParticipantRole::Reviewer && !p.approved && p.state.is_none()
+                    });
 
                     if !approvals.is_empty() {
                         println!(
@@ -356,12 +922,79 @@ impl PrCommands {
                             approvals.join(", ").green()
                         );
                     }
+                    if !changes.is_empty() {
+                        println!(
+                            "{} {}",
+                            "Changes requested by:".dimmed(),
+                            changes.join(", ").yellow()
+                        );
+                    }
+                    if !waiting.is_empty() {
+                        println!("{} {}", "Waiting on:".dimmed(), waiting.join(", "));
+                    }
+                }
+
+                if let Some(statuses) = &statuses {
+                    let builds = Builds::of(statuses);
+                    if builds != Builds::None {
+                        let failing: Vec<&str> = statuses
+                            .iter()
+                            .filter(|s| {
+                                matches!(
+                                    s.state,
+                                    CommitStatusState::Failed | CommitStatusState::Stopped
+                                )
+                            })
+                            .map(|s| s.name.as_deref().unwrap_or(&s.key))
+                            .collect();
+                        let mut line = format!("{} ({})", builds.label(), statuses.len());
+                        if !failing.is_empty() {
+                            line.push_str(&format!(": {}", failing.join(", ")));
+                        }
+                        println!("{} {}", "Builds:".dimmed(), line);
+                    }
+                }
+
+                if let Some(diffstat) = &diffstat {
+                    let added: u64 = diffstat.iter().map(|d| d.lines_added).sum();
+                    let removed: u64 = diffstat.iter().map(|d| d.lines_removed).sum();
+                    println!(
+                        "{} {} {}, {} {}",
+                        "Changes:".dimmed(),
+                        diffstat.len(),
+                        if diffstat.len() == 1 { "file" } else { "files" },
+                        format!("+{}", added).green(),
+                        format!("-{}", removed).red()
+                    );
+                    if diffstat.iter().any(DiffStat::is_conflict) {
+                        println!("{} {}", "Conflicts:".dimmed(), "yes".red());
+                    }
                 }
 
                 if let Some(description) = &pr.description {
                     if !description.is_empty() {
                         println!();
-                        println!("{}", description);
+                        println!("{}", markdown::display(description, raw));
+                    }
+                }
+
+                let comments: Vec<&PullRequestComment> = comments
+                    .iter()
+                    .flatten()
+                    .filter(|c| c.deleted != Some(true))
+                    .collect();
+                if !comments.is_empty() {
+                    let now = Utc::now();
+                    println!();
+                    println!("{}", "Latest comments".bold());
+                    for comment in comments.iter().rev() {
+                        let first = comment.content.raw.lines().next().unwrap_or_default();
+                        println!(
+                            "  {} {} {}",
+                            comment.user.display_name.cyan(),
+                            format!("{} ·", format::relative(&comment.created_on, &now)).dimmed(),
+                            first.chars().take(80).collect::<String>()
+                        );
                     }
                 }
 
@@ -382,28 +1015,76 @@ impl PrCommands {
                 destination,
                 body,
                 close_source_branch,
+                keep_source_branch,
+                reviewers,
+                no_verify,
             } => {
                 let (workspace, repo_slug) = parse_repo(&repo)?;
+                let Config {
+                    pr: config,
+                    branch: branch_config,
+                    ..
+                } = Config::load()?;
+                if !no_verify {
+                    let draft = lint::Draft {
+                        repository: &repo,
+                        title: &title,
+                        description: body.as_deref().unwrap_or(""),
+                        source: &source,
+                        destination: destination.as_deref(),
+                    };
+                    lint::check(&draft, &config)?;
+                }
                 let client = BitbucketClient::from_stored().await?;
+                if !no_verify {
+                    let conventions = branch::Conventions::for_repository(
+                        &branch_config,
+                        &client,
+                        &workspace,
+                        &repo_slug,
+                    )
+                    .await?;
+                    branch::warn(&source, &conventions);
+                }
+                let mut reviewer_refs = Vec::new();
+                for reviewer in &reviewers {
+                    let found = user::resolve(&client, reviewer).await?;
+                    reviewer_refs.push(UserRef { uuid: found.uuid });
+                }
 
                 let request = CreatePullRequestRequest {
                     title,
                     source: PullRequestBranchRef {
-                        branch: BranchInfo { name: source },
+                        branch: BranchInfo {
+                            name: source,
+                            ..Default::default()
+                        },
                     },
                     destination: destination.map(|d| PullRequestBranchRef {
-                        branch: BranchInfo { name: d },
+                        branch: BranchInfo {
+                            name: d,
+                            ..Default::default()
+                        },
                     }),
                     description: body,
-                    close_source_branch: Some(close_source_branch),
-                    reviewers: None,
+                    close_source_branch: Some(closes_source_branch(
+                        close_source_branch,
+                        keep_source_branch,
+                        &config,
+                    )),
+                    reviewers: (!reviewer_refs.is_empty()).then_some(reviewer_refs),
                 };
 
                 let pr = client
                     .create_pull_request(&workspace, &repo_slug, &request)
                     .await?;
+                hooks::run(Hook::PostPrCreate, &repo, &json!({ "pull_request": pr })).await;
+
+                if output::print(&pr)? {
+                    return Ok(());
+                }
 
-                println!("{} Created pull request #{}", "✓".green(), pr.id);
+                output::success(format!("Created pull request #{}", pr.id));
 
                 if let Some(links) = &pr.links {
                     if let Some(html) = &links.html {
@@ -419,23 +1100,66 @@ impl PrCommands {
                 id,
                 strategy,
                 message,
+                no_edit,
                 close_source_branch,
+                keep_source_branch,
+                delete_local_branch,
             } => {
                 let (workspace, repo_slug) = parse_repo(&repo)?;
+                let config = Config::load()?.pr;
                 let client = BitbucketClient::from_stored().await?;
-
+                let pr = client.get_pull_request(&workspace, &repo_slug, id).await?;
+                let strategy = merge_strategy(strategy, &config, &pr.destination.branch)?;
+                let edit = strategy.squashes() && !no_edit && output::is_tty();
+
+                let message = match (message, &config.merge_message) {
+                    (Some(message), _) => Some(message),
+                    (None, template) if edit => {
+                        let draft = match template {
+                            Some(template) => merge_message(template, &pr),
+                            None => {
+                                let commits: Vec<Commit> = client
+                                    .stream_pr_commits(&workspace, &repo_slug, id)
+                                    .try_collect()
+                                    .await?;
+                                squash_message(&pr.title, pr.id, &commits)
+                            }
+                        };
+                        match edit_squash_message(&draft, &repo, id)? {
+                            Some(message) => Some(message),
+                            None => {
+                                output::note("Aborted: the squash message is empty");
+                                return Ok(());
+                            }
+                        }
+                    }
+                    (None, Some(template)) => Some(merge_message(template, &pr)),
+                    (None, None) => None,
+                };
                 let request = MergePullRequestRequest {
                     merge_type: Some("pullrequest".to_string()),
                     message,
-                    close_source_branch: Some(close_source_branch),
-                    merge_strategy: Some(strategy.into()),
+                    close_source_branch: Some(closes_source_branch(
+                        close_source_branch,
+                        keep_source_branch,
+                        &config,
+                    )),
+                    merge_strategy: Some(strategy),
                 };
 
                 let pr = client
                     .merge_pull_request(&workspace, &repo_slug, id, Some(&request))
                     .await?;
+                hooks::run(Hook::PostMerge, &repo, &json!({ "pull_request": pr })).await;
+
+                if !output::print(&pr)? {
+                    output::success(format!("Merged pull request #{}", pr.id));
+                }
 
-                println!("{} Merged pull request #{}", "✓".green(), pr.id);
+                if delete_local_branch {
+                    clean_up_after_merge(&workspace, &repo_slug, &pr)
+                        .context("Merged, but local cleanup failed")?;
+                }
 
                 Ok(())
             }
@@ -448,25 +1172,272 @@ impl PrCommands {
                     .approve_pull_request(&workspace, &repo_slug, id)
                     .await?;
 
-                println!("{} Approved pull request #{}", "✓".green(), id);
+                output::success(format!("Approved pull request #{}", id));
 
                 Ok(())
             }
 
             PrCommands::Decline { repo, id } => {
                 let (workspace, repo_slug) = parse_repo(&repo)?;
+                if !confirm::confirm(format!(
+                    "Decline pull request #{} in {}/{}?",
+                    id, workspace, repo_slug
+                ))? {
+                    output::note("Aborted");
+                    return Ok(());
+                }
                 let client = BitbucketClient::from_stored().await?;
 
                 client
                     .decline_pull_request(&workspace, &repo_slug, id)
                     .await?;
 
-                println!("{} Declined pull request #{}", "✓".green(), id);
+                output::success(format!("Declined pull request #{}", id));
 
                 Ok(())
             }
 
-            PrCommands::Checkout { repo, id } => {
+            PrCommands::Cleanup {
+                repo,
+                older_than,
+                no_activity,
+                dry_run,
+                comment,
+            } => {
+                let (workspace, repo_slug) = parse_repo(&repo)?;
+                let max_age = format::parse_age(&older_than).ok_or_else(|| {
+                    UsageError(format!(
+                        "Invalid --older-than '{}': use a number of hours, days or weeks, e.g. 90d",
+                        older_than
+                    ))
+                })?;
+                let client = BitbucketClient::from_stored().await?;
+
+                let open: Vec<PullRequest> = client
+                    .stream_pull_requests_with_participants(
+                        &workspace,
+                        &repo_slug,
+                        PullRequestState::Open,
+                    )
+                    .try_collect()
+                    .await?;
+                let cutoff = Utc::now() - max_age;
+
+                let checked = fanout::run(
+                    "Checking pull requests",
+                    open,
+                    fanout::DEFAULT_CONCURRENCY,
+                    |pr| {
+                        let client = &client;
+                        let workspace = &workspace;
+                        let repo_slug = &repo_slug;
+                        async move {
+                            stale_reasons(client, workspace, repo_slug, &pr, cutoff, no_activity)
+                                .await
+                        }
+                    },
+                )
+                .await;
+                if let Some((pr, error)) = checked.failed.into_iter().next() {
+                    return Err(error.context(format!("Failed to check pull request #{}", pr.id)));
+                }
+
+                let stale: Vec<StalePullRequest> = checked
+                    .succeeded
+                    .into_iter()
+                    .filter(|(_, reasons)| !reasons.is_empty())
+                    .map(|(pr, reasons)| StalePullRequest {
+                        id: pr.id,
+                        title: pr.title,
+                        author: pr.author.display_name,
+                        updated_on: pr.updated_on,
+                        reasons,
+                    })
+                    .collect();
+
+                if !output::print(&stale)? {
+                    if stale.is_empty() {
+                        output::note("No stale pull requests");
+                        return Ok(());
+                    }
+                    let now = Utc::now();
+                    output::table(
+                        stale
+                            .iter()
+                            .map(|pr| StaleRow {
+                                id: pr.id,
+                                title: pr.title.clone(),
+                                author: pr.author.clone(),
+                                updated: format::relative(&pr.updated_on, &now),
+                                reason: pr.reasons.join("; "),
+                            })
+                            .collect(),
+                    )?;
+                }
+                if dry_run || stale.is_empty() {
+                    return Ok(());
+                }
+
+                if !confirm::confirm(format!("Decline {} pull request(s)?", stale.len()))? {
+                    output::note("Aborted");
+                    return Ok(());
+                }
+
+                let declines: Vec<Decline> = stale
+                    .iter()
+                    .map(|pr| Decline {
+                        id: pr.id,
+                        message: comment.clone().unwrap_or_else(|| {
+                            format!(
+                                "Declining as stale: {}. Reopen it or open a new pull request \
+                                 if this is still needed.",
+                                pr.reasons.join("; ")
+                            )
+                        }),
+                    })
+                    .collect();
+                let outcome = fanout::run(
+                    "Declining pull requests",
+                    declines,
+                    fanout::DEFAULT_CONCURRENCY,
+                    |decline| {
+                        let client = &client;
+                        let workspace = &workspace;
+                        let repo_slug = &repo_slug;
+                        async move {
+                            client
+                                .add_pr_comment(
+                                    workspace,
+                                    repo_slug,
+                                    decline.id,
+                                    &decline.message,
+                                    None,
+                                )
+                                .await?;
+                            client
+                                .decline_pull_request(workspace, repo_slug, decline.id)
+                                .await?;
+                            Ok(())
+                        }
+                    },
+                )
+                .await;
+                for (decline, _) in &outcome.succeeded {
+                    output::success(format!("Declined pull request {}", decline));
+                }
+                outcome.finish("decline", "pull requests")?;
+
+                Ok(())
+            }
+
+            PrCommands::Queue {
+                repo,
+                approvals,
+                merge_next,
+                strategy,
+                close_source_branch,
+                keep_source_branch,
+            } => {
+                let (workspace, repo_slug) = parse_repo(&repo)?;
+                let config = Config::load()?.pr;
+                let client = BitbucketClient::from_stored().await?;
+
+                let open: Vec<PullRequest> = client
+                    .stream_pull_requests_with_participants(
+                        &workspace,
+                        &repo_slug,
+                        PullRequestState::Open,
+                    )
+                    .try_collect()
+                    .await?;
+
+                let assessed = fanout::run(
+                    "Checking pull requests",
+                    open,
+                    fanout::DEFAULT_CONCURRENCY,
+                    |pr| {
+                        let client = &client;
+                        let workspace = &workspace;
+                        let repo_slug = &repo_slug;
+                        async move { assess(client, workspace, repo_slug, pr, approvals).await }
+                    },
+                )
+                .await;
+                if let Some((pr, error)) = assessed.failed.into_iter().next() {
+                    return Err(error.context(format!("Failed to check pull request #{}", pr.id)));
+                }
+                let mut queue: Vec<QueueEntry> =
+                    assessed.succeeded.into_iter().map(|(_, e)| e).collect();
+                rank(&mut queue);
+
+                if !output::print(&queue)? {
+                    if queue.is_empty() {
+                        output::note("No open pull requests");
+                        return Ok(());
+                    }
+                    let now = Utc::now();
+                    output::table(
+                        queue
+                            .iter()
+                            .enumerate()
+                            .map(|(i, entry)| QueueRow {
+                                position: i + 1,
+                                id: entry.id,
+                                title: entry.title.clone(),
+                                approvals: format!("{}/{}", entry.approvals, approvals),
+                                builds: entry.builds.label(),
+                                age: format::relative(&entry.created_on, &now),
+                                status: if entry.ready() {
+                                    "ready".green().to_string()
+                                } else {
+                                    entry.blockers.join(", ")
+                                },
+                            })
+                            .collect(),
+                    )?;
+                }
+
+                if !merge_next {
+                    return Ok(());
+                }
+                let Some(next) = queue.first().filter(|entry| entry.ready()) else {
+                    let reason = queue
+                        .first()
+                        .map(|e| format!(": #{} {}", e.id, e.blockers.join(", ")))
+                        .unwrap_or_default();
+                    anyhow::bail!("No pull request is ready to merge{}", reason);
+                };
+
+                let pr = client
+                    .get_pull_request(&workspace, &repo_slug, next.id)
+                    .await?;
+                let strategy = merge_strategy(strategy, &config, &pr.destination.branch)?;
+                let message = config
+                    .merge_message
+                    .as_ref()
+                    .map(|template| merge_message(template, &pr));
+                let request = MergePullRequestRequest {
+                    merge_type: Some("pullrequest".to_string()),
+                    message,
+                    close_source_branch: Some(closes_source_branch(
+                        close_source_branch,
+                        keep_source_branch,
+                        &config,
+                    )),
+                    merge_strategy: Some(strategy),
+                };
+                client
+                    .merge_pull_request(&workspace, &repo_slug, next.id, Some(&request))
+                    .await?;
+                output::success(format!(
+                    "Merged pull request #{} from the head of the queue",
+                    next.id
+                ));
+
+                Ok(())
+            }
+
+            PrCommands::Checkout { repo, id, worktree } => {
                 let (workspace, repo_slug) = parse_repo(&repo)?;
                 let client = BitbucketClient::from_stored().await?;
 
@@ -474,6 +1445,7 @@ impl PrCommands {
                 let branch = &pr.source.branch.name;
 
                 println!("Fetching and checking out branch {}...", branch.cyan());
+                tracing::debug!(%branch, pr = id, "checking out pull request branch");
 
                 // Fetch the branch
                 let status = std::process::Command::new("git")
@@ -485,6 +1457,36 @@ impl PrCommands {
                     anyhow::bail!("git fetch failed");
                 }
 
+                if let Some(path) = worktree {
+                    let path = match path {
+                        Some(path) => path,
+                        None => {
+                            let template = Config::load()?.pr.worktree_path;
+                            let root = git::root()
+                                .ok_or_else(|| UsageError("Not in a git checkout".to_string()))?;
+                            PathBuf::from(root).join(worktree_path(
+                                template.as_deref().unwrap_or(DEFAULT_WORKTREE_PATH),
+                                &repo_slug,
+                                id,
+                                branch,
+                            ))
+                        }
+                    };
+                    let path_arg = path.to_string_lossy();
+                    let upstream = format!("origin/{}", branch);
+                    let args: Vec<&str> = if git::has_branch(branch) {
+                        vec!["worktree", "add", "--quiet", &path_arg, branch]
+                    } else {
+                        vec![
+                            "worktree", "add", "--quiet", "--track", "-b", branch, &path_arg,
+                            &upstream,
+                        ]
+                    };
+                    git::run(&args)?;
+                    output::success(format!("Checked out {} into {}", branch, path.display()));
+                    return Ok(());
+                }
+
                 // Checkout the branch
                 let status = std::process::Command::new("git")
                     .args(["checkout", branch])
@@ -492,7 +1494,7 @@ impl PrCommands {
                     .context("Failed to checkout branch")?;
 
                 if status.success() {
-                    println!("{} Checked out branch {}", "✓".green(), branch);
+                    output::success(format!("Checked out branch {}", branch));
                 } else {
                     // Try creating a tracking branch
                     let status = std::process::Command::new("git")
@@ -501,7 +1503,7 @@ impl PrCommands {
                         .context("Failed to create tracking branch")?;
 
                     if status.success() {
-                        println!("{} Created and checked out branch {}", "✓".green(), branch);
+                        output::success(format!("Created and checked out branch {}", branch));
                     } else {
                         anyhow::bail!("git checkout failed");
                     }
@@ -510,12 +1512,52 @@ impl PrCommands {
                 Ok(())
             }
 
-            PrCommands::Diff { repo, id } => {
+            PrCommands::Diff {
+                repo,
+                id,
+                output_file: file,
+                local,
+            } => {
                 let (workspace, repo_slug) = parse_repo(&repo)?;
                 let client = BitbucketClient::from_stored().await?;
 
+                if local {
+                    let root = git::root().ok_or_else(|| {
+                        UsageError("--local must be run in a git checkout".to_string())
+                    })?;
+                    let diff = client.get_pr_diff(&workspace, &repo_slug, id).await?;
+                    let checks = patch::check(&diff, Path::new(&root))?;
+                    if !output::print(&checks)? {
+                        print_patch_checks(&checks);
+                    }
+
+                    let conflicts = checks
+                        .iter()
+                        .filter(|c| c.outcome == patch::Outcome::Conflicts)
+                        .count();
+                    if conflicts > 0 {
+                        anyhow::bail!(
+                            "Pull request #{} conflicts with the working tree in {} file{}",
+                            id,
+                            conflicts,
+                            if conflicts == 1 { "" } else { "s" }
+                        );
+                    }
+                    return Ok(());
+                }
+
+                if let Some(path) = file {
+                    let downloaded =
+                        download::fetch(&format!("Diff of #{}", id), &path, None, |resume| {
+                            client.get_pr_diff_file(&workspace, &repo_slug, id, resume)
+                        })
+                        .await?;
+                    output::success(download::summary(&downloaded));
+                    return Ok(());
+                }
+
                 let diff = client.get_pr_diff(&workspace, &repo_slug, id).await?;
-                println!("{}", diff);
+                pager::page(&diff)?;
 
                 Ok(())
             }
@@ -525,10 +1567,20 @@ impl PrCommands {
                 let client = BitbucketClient::from_stored().await?;
 
                 client
-                    .add_pr_comment(&workspace, &repo_slug, id, &body)
-                    .await?;
-
-                println!("{} Added comment to pull request #{}", "✓".green(), id);
+                    .add_pr_comment(&workspace, &repo_slug, id, &body, None)
+                    .await
+                    .map_err(|e| {
+                        drafts::keep_on_failure(
+                            e,
+                            DraftTarget::Pr,
+                            &workspace,
+                            &repo_slug,
+                            id,
+                            &body,
+                        )
+                    })?;
+
+                output::success(format!("Added comment to pull request #{}", id));
 
                 Ok(())
             }
@@ -540,31 +1592,33 @@ impl PrCommands {
                 let comments = client.list_pr_comments(&workspace, &repo_slug, id).await?;
 
                 let mut values: Vec<_> = comments.values.into_iter().take(limit as usize).collect();
+                values.sort_by_key(|c| c.created_on);
 
-                if values.is_empty() {
-                    println!("No comments found");
+                if output::print(&values)? {
                     return Ok(());
                 }
 
-                values.sort_by_key(|c| c.created_on);
+                if values.is_empty() {
+                    output::note("No comments found");
+                    return Ok(());
+                }
 
                 let rows: Vec<CommentRow> = values
                     .iter()
                     .map(|c| CommentRow {
                         id: c.id,
                         author: c.user.display_name.clone(),
-                        created: c.created_on.format("%Y-%m-%d %H:%M").to_string(),
+                        created: format::date(&c.created_on),
                         comment_type: if c.inline.is_some() {
                             "inline".to_string()
                         } else {
                             "general".to_string()
                         },
-                        content: c.content.raw.chars().take(50).collect(),
+                        content: format::one_line(&c.content.raw),
                     })
                     .collect();
 
-                let table = Table::new(rows).to_string();
-                println!("{}", table);
+                output::table(rows)?;
 
                 Ok(())
             }
@@ -589,13 +1643,17 @@ impl PrCommands {
                     .list_pipelines_for_commit(&workspace, &repo_slug, &head_commit, scan_limit)
                     .await?;
 
+                if output::print(&pipelines)? {
+                    return Ok(());
+                }
+
                 if pipelines.is_empty() {
-                    println!(
+                    output::note(format!(
                         "No pipelines found for PR #{} head commit {} (scanned {} most recent).",
                         id,
                         head_commit.chars().take(12).collect::<String>(),
                         scan_limit.clamp(1, 100)
-                    );
+                    ));
                     return Ok(());
                 }
 
@@ -623,14 +1681,116 @@ impl PrCommands {
                                 .as_ref()
                                 .map(|c| c.hash.chars().take(12).collect())
                                 .unwrap_or_else(|| "-".to_string()),
-                            triggered: p.created_on.format("%Y-%m-%d %H:%M").to_string(),
+                            triggered: format::date(&p.created_on),
                             duration,
                         }
                     })
                     .collect();
 
-                println!("{}", Table::new(rows));
+                output::table(rows)?;
+
+                Ok(())
+            }
+
+            PrCommands::SuggestReviewers {
+                repo,
+                id,
+                source,
+                destination,
+                count,
+                depth,
+                apply,
+            } => {
+                let (workspace, repo_slug) = parse_repo(&repo)?;
+                let client = BitbucketClient::from_stored().await?;
+
+                // The author, and anyone already reviewing, needn't be suggested
+                let mut exclude = BTreeSet::new();
+                let (pr, files, revision) = match id {
+                    Some(id) => {
+                        let pr = client.get_pull_request(&workspace, &repo_slug, id).await?;
+                        let files = client.list_pr_diffstat(&workspace, &repo_slug, id).await?;
+                        exclude.insert(pr.author.uuid.clone());
+                        exclude.extend(pr.reviewers.iter().flatten().map(|r| r.uuid.clone()));
+                        let revision = pr.destination.branch.name.clone();
+                        (Some(pr), files, revision)
+                    }
+                    None => {
+                        let source = source.unwrap_or_default();
+                        let destination = match destination {
+                            Some(destination) => destination,
+                            None => {
+                                client
+                                    .get_repository(&workspace, &repo_slug)
+                                    .await?
+                                    .mainbranch
+                                    .context("Repository has no main branch. Pass --destination.")?
+                                    .name
+                            }
+                        };
+                        let files = client
+                            .list_diffstat(&workspace, &repo_slug, &destination, &source)
+                            .await?;
+                        exclude.insert(user::me(&client).await?.uuid);
+                        (None, files, destination)
+                    }
+                };
+                if files.is_empty() {
+                    output::note("No changed files to suggest reviewers for");
+                    return Ok(());
+                }
+
+                let mut suggestions = reviewers::suggest(
+                    &client, &workspace, &repo_slug, &revision, &files, depth, &exclude,
+                )
+                .await?;
+                suggestions.truncate(count);
 
+                if let (Some(pr), true) = (&pr, apply && !suggestions.is_empty()) {
+                    let mut refs: Vec<UserRef> = pr
+                        .reviewers
+                        .iter()
+                        .flatten()
+                        .map(|r| UserRef {
+                            uuid: r.uuid.clone(),
+                        })
+                        .collect();
+                    refs.extend(suggestions.iter().map(|s| UserRef {
+                        uuid: s.user.uuid.clone(),
+                    }));
+                    client
+                        .set_pr_reviewers(&workspace, &repo_slug, pr.id, &pr.title, &refs)
+                        .await?;
+                }
+
+                if output::print(&suggestions)? {
+                    return Ok(());
+                }
+                if suggestions.is_empty() {
+                    output::note(format!(
+                        "Nobody else with a Bitbucket account committed to the {} changed file(s) on {}",
+                        files.len(),
+                        revision
+                    ));
+                    return Ok(());
+                }
+                output::table(
+                    suggestions
+                        .iter()
+                        .map(reviewers::SuggestionRow::from)
+                        .collect(),
+                )?;
+                if let (Some(pr), true) = (&pr, apply) {
+                    let names: Vec<&str> = suggestions
+                        .iter()
+                        .map(|s| s.user.display_name.as_str())
+                        .collect();
+                    output::success(format!(
+                        "Added {} as reviewers of #{}",
+                        names.join(", "),
+                        pr.id
+                    ));
+                }
                 Ok(())
             }
 
@@ -646,22 +1806,22 @@ impl PrCommands {
                     .get_pr_comment(&workspace, &repo_slug, id, comment_id)
                     .await?;
 
+                if output::print(&comment)? {
+                    return Ok(());
+                }
+
                 println!("{} #{} on PR #{}", "Comment".bold(), comment.id, id);
-                println!("{}", "─".repeat(60));
+                print!("{}", output::rule(60));
 
                 println!("{} {}", "Author:".dimmed(), comment.user.display_name);
                 println!(
                     "{} {}",
                     "Created:".dimmed(),
-                    comment.created_on.format("%Y-%m-%d %H:%M")
+                    format::date(&comment.created_on)
                 );
 
                 if let Some(updated) = comment.updated_on {
-                    println!(
-                        "{} {}",
-                        "Updated:".dimmed(),
-                        updated.format("%Y-%m-%d %H:%M")
-                    );
+                    println!("{} {}", "Updated:".dimmed(), format::date(&updated));
                 }
 
                 if let Some(inline) = &comment.inline {
@@ -692,15 +1852,232 @@ impl PrCommands {
     }
 }
 
-fn parse_repo(repo: &str) -> Result<(String, String)> {
-    let parts: Vec<&str> = repo.split('/').collect();
-    if parts.len() != 2 {
-        anyhow::bail!(
-            "Invalid repository format. Expected 'workspace/repo-slug', got '{}'",
-            repo
-        );
+/// The value of a fetch the output can do without, logging why it's missing
+fn optional<T>(what: &str, result: crate::error::Result<T>) -> Option<T> {
+    result
+        .inspect_err(|e| tracing::warn!(error = %e, "failed to fetch pull request {}", what))
+        .ok()
+}
+
+/// Check an open pull request's approvals, builds and mergeability
+async fn assess(
+    client: &BitbucketClient,
+    workspace: &str,
+    repo_slug: &str,
+    pr: PullRequest,
+    required_approvals: usize,
+) -> Result<QueueEntry> {
+    let (statuses, diffstat) = tokio::try_join!(
+        client.list_pr_statuses(workspace, repo_slug, pr.id),
+        client.list_pr_diffstat(workspace, repo_slug, pr.id),
+    )?;
+
+    let participants = pr.participants.as_deref().unwrap_or_default();
+    let approvals = participants.iter().filter(|p| p.approved).count();
+    let changes_requested = participants
+        .iter()
+        .any(|p| p.state == Some(ParticipantState::ChangesRequested));
+    let builds = Builds::of(&statuses);
+    let conflicts = diffstat.iter().any(|d| d.is_conflict());
+
+    let mut blockers = Vec::new();
+    if approvals < required_approvals {
+        let missing = required_approvals - approvals;
+        blockers.push(format!(
+            "needs {} more {}",
+            missing,
+            if missing == 1 {
+                "approval"
+            } else {
+                "approvals"
+            }
+        ));
+    }
+    if changes_requested {
+        blockers.push("changes requested".to_string());
+    }
+    match builds {
+        Builds::Failed => blockers.push("builds failed".to_string()),
+        Builds::Running => blockers.push("builds running".to_string()),
+        Builds::Passed | Builds::None => {}
+    }
+    if conflicts {
+        blockers.push("merge conflicts".to_string());
+    }
+
+    Ok(QueueEntry {
+        id: pr.id,
+        title: pr.title,
+        author: pr.author.display_name,
+        created_on: pr.created_on,
+        approvals,
+        builds,
+        conflicts,
+        blockers,
+    })
+}
+
+/// Order the queue: ready first, then by fewest blockers, oldest first
+fn rank(queue: &mut [QueueEntry]) {
+    queue.sort_by_key(|entry| (entry.blockers.len(), entry.created_on));
+}
+
+/// Why an open pull request is stale, if it is: no activity since `cutoff`,
+/// a deleted source branch, or a source commit already in the destination
+async fn stale_reasons(
+    client: &BitbucketClient,
+    workspace: &str,
+    repo_slug: &str,
+    pr: &PullRequest,
+    cutoff: DateTime<Utc>,
+    no_activity: bool,
+) -> Result<Vec<String>> {
+    let mut reasons = Vec::new();
+
+    let reviewed = pr
+        .participants
+        .iter()
+        .flatten()
+        .any(|p| p.approved || p.state.is_some());
+    let quiet = pr.comment_count.unwrap_or(0) == 0 && !reviewed;
+    if pr.updated_on < cutoff && (!no_activity || quiet) {
+        let days = (Utc::now() - pr.updated_on).num_days();
+        reasons.push(format!("no updates in {} days", days));
+    }
+
+    // Branches of pull requests from forks live in the fork
+    let source_repo = pr
+        .source
+        .repository
+        .as_ref()
+        .map(|r| r.full_name.clone())
+        .unwrap_or_else(|| format!("{}/{}", workspace, repo_slug));
+    let (source_workspace, source_slug) = parse_repo(&source_repo)?;
+    let same_repo = source_repo == format!("{}/{}", workspace, repo_slug);
+
+    match client
+        .get_branch(&source_workspace, &source_slug, &pr.source.branch.name)
+        .await
+    {
+        Ok(_) => {}
+        Err(Error::NotFound(_)) => reasons.push("source branch deleted".to_string()),
+        Err(e) => return Err(e.into()),
+    }
+
+    if same_repo && let Some(source) = &pr.source.commit {
+        let destination = &pr.destination.branch.name;
+        let head = client.get_branch(workspace, repo_slug, destination).await?;
+        let base = client
+            .get_merge_base(workspace, repo_slug, &source.hash, &head.target.hash)
+            .await?;
+        if base.hash.starts_with(&source.hash) || source.hash.starts_with(&base.hash) {
+            reasons.push(format!("already merged into {}", destination));
+        }
+    }
+
+    Ok(reasons)
+}
+
+/// Leave the merged branch for the destination branch, bring that up to
+/// date, and delete the merged branch, when run in a clone of the repository
+fn clean_up_after_merge(workspace: &str, repo_slug: &str, pr: &PullRequest) -> Result<()> {
+    if git::origin_repo() != Some((workspace.to_string(), repo_slug.to_string())) {
+        output::note(format!(
+            "Not in a clone of {}/{}; skipping local cleanup",
+            workspace, repo_slug
+        ));
+        return Ok(());
+    }
+
+    let source = &pr.source.branch.name;
+    let destination = &pr.destination.branch.name;
+
+    // Drops origin/<source> if the merge closed the branch
+    git::run(&["fetch", "--quiet", "--prune", "origin"])?;
+
+    if git::current_branch().as_deref() == Some(source.as_str()) {
+        if git::has_branch(destination) {
+            git::run(&["checkout", "--quiet", destination])?;
+        } else {
+            let upstream = format!("origin/{}", destination);
+            git::run(&[
+                "checkout",
+                "--quiet",
+                "--track",
+                "-b",
+                destination,
+                &upstream,
+            ])?;
+        }
+    }
+    if git::current_branch().as_deref() == Some(destination.as_str()) {
+        git::run(&["pull", "--quiet", "--ff-only"])?;
+        output::success(format!("Switched to {} and pulled", destination.cyan()));
+    }
+
+    if git::has_branch(source) {
+        // Forced, as squash and rebase merges leave the branch looking unmerged
+        git::run(&["branch", "--quiet", "-D", source])?;
+        output::success(format!("Deleted local branch {}", source.cyan()));
+    }
+
+    Ok(())
+}
+
+/// Pull requests in `repo`, the first `limit` or all of them
+async fn list_prs(
+    client: &BitbucketClient,
+    repo: &str,
+    state: &Option<PullRequestState>,
+    filter: Option<&str>,
+    limit: u32,
+    all: bool,
+) -> Result<Vec<PullRequest>> {
+    let (workspace, repo_slug) = parse_repo(repo)?;
+    let prs = if all {
+        client
+            .stream_pull_requests(&workspace, &repo_slug, state.clone(), filter)
+            .try_collect()
+            .await?
+    } else {
+        client
+            .list_pull_requests(
+                &workspace,
+                &repo_slug,
+                state.clone(),
+                filter,
+                None,
+                Some(limit),
+            )
+            .await?
+            .values
+    };
+    Ok(prs)
+}
+
+/// Each file of `pr diff --local`, with the hunks that don't apply as is
+fn print_patch_checks(checks: &[patch::FileCheck]) {
+    let label = |outcome: patch::Outcome| match outcome {
+        patch::Outcome::Applies => format!("{} applies", Icon::Ok).green(),
+        patch::Outcome::Merges => format!("{} merges", Icon::Warning).yellow(),
+        patch::Outcome::Conflicts => format!("{} conflicts", Icon::Error).red(),
+    };
+    for file in checks {
+        println!("{}  {}", label(file.outcome), file.path);
+        for hunk in &file.hunks {
+            if hunk.outcome != patch::Outcome::Applies {
+                println!("    {}  {}", label(hunk.outcome), hunk.header.dimmed());
+            }
+        }
     }
-    Ok((parts[0].to_string(), parts[1].to_string()))
+
+    let count = |outcome| checks.iter().filter(|c| c.outcome == outcome).count();
+    output::note(format!(
+        "\n{} to apply, {} to merge with local changes, {} conflicting",
+        count(patch::Outcome::Applies),
+        count(patch::Outcome::Merges),
+        count(patch::Outcome::Conflicts)
+    ));
 }
 
 fn format_state(state: &PullRequestState) -> String {
@@ -709,5 +2086,73 @@ fn format_state(state: &PullRequestState) -> String {
         PullRequestState::Merged => "MERGED".purple().to_string(),
         PullRequestState::Declined => "DECLINED".red().to_string(),
         PullRequestState::Superseded => "SUPERSEDED".yellow().to_string(),
+        PullRequestState::Unknown => "UNKNOWN".dimmed().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn squash_messages_list_commits_oldest_first() {
+        let commit = |message: &str| -> Commit {
+            serde_json::from_value(json!({ "hash": "abc", "message": message })).unwrap()
+        };
+        let commits = [
+            commit("Handle the sign of B1\n\nIt's negative."),
+            commit("Add the routine"),
+        ];
+        assert_eq!(
+            squash_message("Add Bernoulli number routine", 7, &commits),
+            "Add Bernoulli number routine (#7)\n\n* Add the routine\n* Handle the sign of B1\n"
+        );
+        assert_eq!(
+            strip_comments("Title\n\n* one\n# Lines starting with '#'\n"),
+            "Title\n\n* one"
+        );
+    }
+
+    #[test]
+    fn merge_strategy_falls_back_to_the_repository_default_and_respects_its_list() {
+        let destination = BranchInfo {
+            name: "main".into(),
+            merge_strategies: vec!["squash".into(), "fast_forward".into()],
+            default_merge_strategy: Some("squash".into()),
+        };
+        let config = PrConfig::default();
+        assert_eq!(
+            merge_strategy(None, &config, &destination).unwrap(),
+            MergeStrategy::Squash
+        );
+        let error =
+            merge_strategy(Some(MergeStrategyArg::MergeCommit), &config, &destination).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The repository doesn't allow merge-commit merges into main; pass --strategy with one of: squash, fast-forward"
+        );
+
+        // A configured strategy the repository doesn't allow gives way
+        let config = PrConfig {
+            merge_strategy: Some(MergeStrategy::MergeCommit),
+            ..Default::default()
+        };
+        assert_eq!(
+            merge_strategy(None, &config, &destination).unwrap(),
+            MergeStrategy::Squash
+        );
+        assert_eq!(
+            merge_strategy(Some(MergeStrategyArg::FastForward), &config, &destination).unwrap(),
+            MergeStrategy::FastForward
+        );
+
+        let unrestricted = BranchInfo {
+            name: "main".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            merge_strategy(None, &config, &unrestricted).unwrap(),
+            MergeStrategy::MergeCommit
+        );
     }
 }
@@ -0,0 +1,97 @@
+//! `--since`/`--until` for list commands
+//!
+//! Both take an age such as `7d` (that long before now) or a date, and turn
+//! into a Bitbucket query language filter on a timestamp field, so the API
+//! does the filtering and `--limit` counts only items in the window.
+
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::Args;
+
+use super::{UsageError, format};
+
+/// One end of a window, `None` when open
+pub type Bound = Option<DateTime<Utc>>;
+
+#[derive(Args, Debug, Default, Clone)]
+pub struct DateRange {
+    /// Start of the window: an age such as 7d, 2w or 36h, or a date
+    #[arg(long, value_name = "AGE|DATE")]
+    pub since: Option<String>,
+
+    /// End of the window: an age such as 7d, 2w or 36h, or a date
+    #[arg(long, value_name = "AGE|DATE")]
+    pub until: Option<String>,
+}
+
+impl DateRange {
+    /// The parsed start and end of the window
+    pub fn bounds(&self) -> Result<(Bound, Bound)> {
+        let parse = |flag: &str, value: &Option<String>| -> Result<Bound> {
+            value
+                .as_deref()
+                .map(|value| {
+                    format::parse_time(value).ok_or_else(|| {
+                        UsageError(format!(
+                            "Invalid {} '{}': use an age such as 7d or a date such as 2024-06-01",
+                            flag, value
+                        ))
+                        .into()
+                    })
+                })
+                .transpose()
+        };
+        let since = parse("--since", &self.since)?;
+        let until = parse("--until", &self.until)?;
+        if let (Some(since), Some(until)) = (since, until)
+            && since >= until
+        {
+            anyhow::bail!(UsageError("--since must be before --until".to_string()));
+        }
+        Ok((since, until))
+    }
+
+    /// A filter such as `updated_on >= 2024-06-01T00:00:00Z` on `field`, or
+    /// `None` when neither end is set
+    pub fn filter(&self, field: &str) -> Result<Option<String>> {
+        let (since, until) = self.bounds()?;
+        let clauses: Vec<String> = [(">=", since), ("<", until)]
+            .into_iter()
+            .filter_map(|(op, at)| {
+                at.map(|at| {
+                    format!(
+                        "{} {} {}",
+                        field,
+                        op,
+                        at.to_rfc3339_opts(SecondsFormat::Secs, true)
+                    )
+                })
+            })
+            .collect();
+        Ok((!clauses.is_empty()).then(|| clauses.join(" AND ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_become_query_filters() {
+        let range = DateRange {
+            since: Some("2024-06-01".to_string()),
+            until: Some("2024-07-01T12:00:00Z".to_string()),
+        };
+        assert_eq!(
+            range.filter("updated_on").unwrap().as_deref(),
+            Some("updated_on >= 2024-06-01T00:00:00Z AND updated_on < 2024-07-01T12:00:00Z")
+        );
+        assert_eq!(DateRange::default().filter("updated_on").unwrap(), None);
+
+        let backwards = DateRange {
+            since: Some("2024-07-01".to_string()),
+            until: Some("2024-06-01".to_string()),
+        };
+        assert!(backwards.filter("updated_on").is_err());
+    }
+}
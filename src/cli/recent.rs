@@ -0,0 +1,200 @@
+//! Recently used repositories, for `bitbucket recent` and `-` as a repository
+//!
+//! The repository a successful command was run on, the first one named on
+//! its command line or else the checkout's, is remembered in
+//! `$XDG_STATE_HOME/bitbucket-cli/recent.json`, newest first, so `-` can stand
+//! for the last one and `bitbucket recent N` can switch back to an older one.
+//! Repositories a command fans out to beyond that aren't remembered.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use tabled::Tabled;
+
+use super::output::Porcelain;
+use super::{UsageError, format, output};
+use crate::config::{Config, xdg};
+
+const FILE: &str = "recent.json";
+
+/// Repositories remembered; older ones drop off
+const MAX: usize = 20;
+
+/// The repository this run was on, saved by [`save`] once it succeeds
+static TOUCHED: Mutex<Option<String>> = Mutex::new(None);
+
+#[derive(Args)]
+pub struct RecentArgs {
+    /// Make the Nth repository in the list the last used, so `-` means it
+    #[arg(value_name = "N", conflicts_with = "clear")]
+    pick: Option<usize>,
+
+    /// Forget every repository
+    #[arg(long)]
+    clear: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentRepo {
+    /// `workspace/repo`
+    pub repository: String,
+    pub used_on: DateTime<Utc>,
+}
+
+impl Porcelain for RecentRepo {
+    fn porcelain(&self) -> String {
+        self.repository.clone()
+    }
+}
+
+#[derive(Tabled)]
+struct RecentRow {
+    #[tabled(rename = "#")]
+    number: usize,
+    #[tabled(rename = "REPOSITORY")]
+    repository: String,
+    #[tabled(rename = "USED")]
+    used: String,
+}
+
+impl RecentArgs {
+    pub fn run(self) -> Result<()> {
+        if self.clear {
+            let path = path()?;
+            if path.exists() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+            output::success("Forgot recently used repositories");
+            return Ok(());
+        }
+
+        let mut recent = load()?;
+        if let Some(n) = self.pick {
+            if n == 0 || n > recent.len() {
+                anyhow::bail!(UsageError(format!(
+                    "No repository #{}; `bitbucket recent` lists {}",
+                    n,
+                    recent.len()
+                )));
+            }
+            let picked = recent.remove(n - 1);
+            recent.insert(
+                0,
+                RecentRepo {
+                    used_on: Utc::now(),
+                    ..picked
+                },
+            );
+            write(recent.clone())?;
+            output::success(format!("`-` now means {}", recent[0].repository));
+            return Ok(());
+        }
+
+        if output::print(&recent)? {
+            return Ok(());
+        }
+        if recent.is_empty() {
+            output::note("No repositories used yet");
+            return Ok(());
+        }
+        let now = Utc::now();
+        output::table(
+            recent
+                .iter()
+                .enumerate()
+                .map(|(i, r)| RecentRow {
+                    number: i + 1,
+                    repository: r.repository.clone(),
+                    used: format::relative(&r.used_on, &now),
+                })
+                .collect(),
+        )
+    }
+}
+
+fn path() -> Result<PathBuf> {
+    Ok(Config::state_dir()?.join(FILE))
+}
+
+/// Remember that this run used `workspace/slug`, unless it already used
+/// another
+pub fn touch(workspace: &str, slug: &str) {
+    if let Ok(mut touched) = TOUCHED.lock() {
+        touched.get_or_insert_with(|| format!("{}/{}", workspace, slug));
+    }
+}
+
+/// [`touch`], when `given` was named on the command line rather than found
+/// some other way, such as by listing a workspace
+pub fn touch_named(given: &str, workspace: &str, slug: &str) {
+    let named = std::env::args()
+        .skip(1)
+        .any(|arg| arg == given || arg.split_once('=').is_some_and(|(_, value)| value == given));
+    if named {
+        touch(workspace, slug);
+    }
+}
+
+/// The recently used repositories, newest first; none before the first save
+pub fn load() -> Result<Vec<RecentRepo>> {
+    let path = path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    parse(&path, &text)
+}
+
+fn parse(path: &Path, text: &str) -> Result<Vec<RecentRepo>> {
+    serde_json::from_str(text).with_context(|| format!("{} is corrupt", path.display()))
+}
+
+/// The repository `-` stands for
+pub fn last() -> Result<String> {
+    load()?
+        .into_iter()
+        .next()
+        .map(|r| r.repository)
+        .ok_or_else(|| {
+            UsageError(
+                "No recently used repository for '-'; pass workspace/repo-slug once first"
+                    .to_string(),
+            )
+            .into()
+        })
+}
+
+/// Put the repository this run used at the front of the list
+pub fn save() -> Result<()> {
+    let Some(repository) = TOUCHED.lock().ok().and_then(|t| t.clone()) else {
+        return Ok(());
+    };
+    let path = path()?;
+    xdg::update_locked(&path, |current| {
+        let mut recent = match current {
+            Some(text) => parse(&path, &text)?,
+            None => Vec::new(),
+        };
+        recent.retain(|r| r.repository != repository);
+        recent.insert(
+            0,
+            RecentRepo {
+                repository,
+                used_on: Utc::now(),
+            },
+        );
+        recent.truncate(MAX);
+        Ok(serde_json::to_string_pretty(&recent)?)
+    })
+}
+
+fn write(recent: Vec<RecentRepo>) -> Result<()> {
+    xdg::update_locked(&path()?, |_| Ok(serde_json::to_string_pretty(&recent)?))
+}
@@ -1,21 +1,62 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
 use anyhow::{Context, Result};
-use clap::Subcommand;
+use clap::{Subcommand, ValueEnum};
 use colored::Colorize;
-use tabled::{Table, Tabled};
+use futures::TryStreamExt;
+use indicatif::HumanBytes;
+use tabled::Tabled;
 
+use super::audit::glob_match;
+use super::browse::WEB_URL;
+use super::git::parse_repo;
+use super::output::ReportFormat;
+use super::{UsageError, confirm, download, fanout, format, git, markdown, output, pager};
 use crate::api::BitbucketClient;
-use crate::models::CreateRepositoryRequest;
+use crate::config::{CloneProtocol, Config};
+use crate::error::Error;
+use crate::models::{CreateRepositoryRequest, Repository, SourceEntry, User};
+
+/// How often to check whether a new fork exists yet
+const FORK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a fork may take before giving up on it
+const FORK_TIMEOUT: Duration = Duration::from_secs(120);
 
 #[derive(Subcommand)]
 pub enum RepoCommands {
-    /// List repositories in a workspace
+    /// List repositories in a workspace, or in every workspace with --mine
     List {
         /// Workspace slug
-        workspace: String,
+        #[arg(required_unless_present = "mine", conflicts_with = "mine")]
+        workspace: Option<String>,
+
+        /// List repositories across every workspace you belong to, grouped
+        /// by workspace
+        #[arg(long)]
+        mine: bool,
+
+        /// Only repositories you have this role on (default with --mine: member)
+        #[arg(long, value_enum)]
+        role: Option<RepoRole>,
 
         /// Number of results per page
         #[arg(short, long, default_value = "25")]
         limit: u32,
+
+        /// Fetch every page instead of stopping at --limit
+        #[arg(long, conflicts_with = "limit")]
+        all: bool,
+
+        /// Order of the repositories, applied by the API so --limit keeps the
+        /// first ones
+        #[arg(long, value_enum)]
+        sort: Option<RepoSort>,
+
+        /// Add up the size of every repository (fetches every page)
+        #[arg(long, conflicts_with = "limit")]
+        total: bool,
     },
 
     /// View repository details
@@ -26,6 +67,14 @@ pub enum RepoCommands {
         /// Open in browser
         #[arg(short, long)]
         web: bool,
+
+        /// Print the web URL instead, without fetching the repository
+        #[arg(long, conflicts_with_all = ["web", "readme"])]
+        url: bool,
+
+        /// Also show the README from the main branch
+        #[arg(long, conflicts_with = "web")]
+        readme: bool,
     },
 
     /// Clone a repository
@@ -36,6 +85,43 @@ pub enum RepoCommands {
         /// Directory to clone into
         #[arg(short, long)]
         dir: Option<String>,
+
+        /// Clone over HTTPS (default: `[defaults] clone_protocol`)
+        #[arg(long, conflicts_with = "ssh")]
+        https: bool,
+
+        /// Clone over SSH
+        #[arg(long)]
+        ssh: bool,
+
+        /// Only fetch this many commits of history
+        #[arg(long, value_name = "N")]
+        depth: Option<u32>,
+
+        /// Check out this branch instead of the main branch
+        #[arg(short, long)]
+        branch: Option<String>,
+
+        /// Also clone submodules
+        #[arg(long)]
+        recurse_submodules: bool,
+    },
+
+    /// Download a file from a repository's Downloads
+    Download {
+        /// Repository in format workspace/repo-slug
+        repo: String,
+
+        /// Name of the file
+        name: String,
+
+        /// Where to save it (default: the file's name in the current directory)
+        #[arg(short = 'O', long, value_name = "PATH")]
+        output_file: Option<PathBuf>,
+
+        /// Expected SHA-256, checked once the download finishes
+        #[arg(long, value_name = "HEX")]
+        sha256: Option<String>,
     },
 
     /// Create a new repository
@@ -75,19 +161,120 @@ pub enum RepoCommands {
         /// New repository name
         #[arg(short, long)]
         name: Option<String>,
+
+        /// Once the fork is ready, clone it and add the source as `upstream`
+        #[arg(long)]
+        clone: bool,
+
+        /// Directory to clone into (default: the fork's name)
+        #[arg(long, requires = "clone")]
+        dir: Option<String>,
+
+        /// Return as soon as the fork is requested, without waiting for it
+        #[arg(long, conflicts_with = "clone")]
+        no_wait: bool,
     },
 
-    /// Delete a repository
+    /// Delete repositories; several at once, or those in --workspace
+    /// matching --match, are listed and need the workspace name typed back
     Delete {
+        /// Repositories in format workspace/repo-slug
+        #[arg(required_unless_present = "pattern", conflicts_with = "pattern")]
+        repos: Vec<String>,
+
+        /// Delete every repository in --workspace whose slug matches this
+        /// glob, e.g. 'temp-*'
+        #[arg(long = "match", value_name = "GLOB")]
+        pattern: Option<String>,
+
+        /// Let --match '*' select every repository in the workspace
+        #[arg(long, requires = "pattern")]
+        allow_all: bool,
+
+        /// The text the prompt asks to type back (the workspace name), for
+        /// running without a terminal; --yes doesn't skip it
+        #[arg(long, value_name = "TEXT")]
+        confirm: Option<String>,
+    },
+
+    /// Watch repositories to get notifications for their activity
+    Watch {
+        /// Repositories in format workspace/repo-slug
+        #[arg(required = true)]
+        repos: Vec<String>,
+    },
+
+    /// Stop watching repositories
+    Unwatch {
+        /// Repositories in format workspace/repo-slug
+        #[arg(required = true)]
+        repos: Vec<String>,
+    },
+
+    /// List users watching a repository
+    Watchers {
         /// Repository in format workspace/repo-slug
         repo: String,
 
-        /// Skip confirmation prompt
-        #[arg(short, long)]
-        yes: bool,
+        /// Number of results
+        #[arg(short, long, default_value = "25")]
+        limit: u32,
+
+        /// Fetch every page instead of stopping at --limit
+        #[arg(long, conflicts_with = "limit")]
+        all: bool,
     },
 }
 
+/// Access the authenticated user has to a repository, as the API filters by
+#[derive(ValueEnum, Clone, Copy)]
+pub enum RepoRole {
+    Member,
+    Contributor,
+    Admin,
+    Owner,
+}
+
+impl RepoRole {
+    fn as_str(self) -> &'static str {
+        match self {
+            RepoRole::Member => "member",
+            RepoRole::Contributor => "contributor",
+            RepoRole::Admin => "admin",
+            RepoRole::Owner => "owner",
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum RepoSort {
+    /// Alphabetically by full name
+    Name,
+    /// Most recently updated first
+    Updated,
+    /// Largest first
+    Size,
+}
+
+impl RepoSort {
+    /// The API's `sort` parameter for it
+    fn field(self) -> &'static str {
+        match self {
+            RepoSort::Name => "full_name",
+            RepoSort::Updated => "-updated_on",
+            RepoSort::Size => "-size",
+        }
+    }
+
+    fn compare(self, a: &Repository, b: &Repository) -> std::cmp::Ordering {
+        match self {
+            RepoSort::Name => a.full_name.cmp(&b.full_name),
+            RepoSort::Updated => b.updated_on.cmp(&a.updated_on),
+            RepoSort::Size => b.size.cmp(&a.size),
+        }
+    }
+}
+
 #[derive(Tabled)]
 struct RepoRow {
     #[tabled(rename = "NAME")]
@@ -96,64 +283,181 @@ struct RepoRow {
     description: String,
     #[tabled(rename = "PRIVATE")]
     private: String,
+    #[tabled(rename = "SIZE")]
+    size: String,
     #[tabled(rename = "UPDATED")]
     updated: String,
 }
 
+impl From<&Repository> for RepoRow {
+    fn from(r: &Repository) -> Self {
+        Self {
+            name: r.full_name.clone(),
+            description: format::one_line(r.description.as_deref().unwrap_or_default()),
+            private: if r.is_private.unwrap_or(false) {
+                "Yes"
+            } else {
+                "No"
+            }
+            .to_string(),
+            size: r
+                .size
+                .map(|bytes| HumanBytes(bytes).to_string())
+                .unwrap_or_default(),
+            updated: r.updated_on.map(|d| format::date(&d)).unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct WatcherRow {
+    #[tabled(rename = "NAME")]
+    name: String,
+    #[tabled(rename = "NICKNAME")]
+    nickname: String,
+    #[tabled(rename = "ACCOUNT ID")]
+    account_id: String,
+}
+
+impl From<&User> for WatcherRow {
+    fn from(u: &User) -> Self {
+        Self {
+            name: u.display_name.clone(),
+            nickname: u.nickname.clone().unwrap_or_default(),
+            account_id: u.account_id.clone().unwrap_or_default(),
+        }
+    }
+}
+
+fn note_none_found(workspace: Option<&str>) {
+    match workspace {
+        Some(workspace) => output::note(format!(
+            "No repositories found in workspace '{}'",
+            workspace
+        )),
+        None => output::note("No repositories found"),
+    }
+}
+
+/// The `--total` line: the size of `count` repositories together
+fn note_total(bytes: u64, count: usize) {
+    output::note(format!(
+        "\n{} {} across {} repositor{}",
+        "Total:".bold(),
+        HumanBytes(bytes),
+        count,
+        if count == 1 { "y" } else { "ies" }
+    ));
+}
+
 impl RepoCommands {
-    pub async fn run(self) -> Result<()> {
+    /// Run the command; `workspace` is the global `--workspace`, which
+    /// `delete --match` searches
+    pub async fn run(self, workspace: Option<String>) -> Result<()> {
         match self {
-            RepoCommands::List { workspace, limit } => {
+            RepoCommands::List {
+                workspace,
+                mine,
+                role,
+                limit,
+                all,
+                sort,
+                total,
+            } => {
                 let client = BitbucketClient::from_stored().await?;
-                let repos = client
-                    .list_repositories(&workspace, None, Some(limit))
-                    .await?;
+                let role = role.or(mine.then_some(RepoRole::Member));
+                let all = all || total;
 
-                if repos.values.is_empty() {
-                    println!("No repositories found in workspace '{}'", workspace);
+                let role = role.map(RepoRole::as_str);
+                let api_sort = sort.map(RepoSort::field);
+                // Sorting and grouping need every repository first
+                if all && !mine && sort.is_none() && output::streams() {
+                    let mut bytes = 0;
+                    let repos = client
+                        .stream_repositories_matching(workspace.as_deref(), role, None)
+                        .inspect_ok(|r| bytes += r.size.unwrap_or(0));
+                    let count = output::stream(repos, |r| RepoRow::from(r)).await?;
+                    if count == 0 && !output::is_structured() {
+                        note_none_found(workspace.as_deref());
+                    } else if total {
+                        note_total(bytes, count);
+                    }
+                    return Ok(());
+                }
+                let (mut repos, has_more): (Vec<Repository>, bool) = if all {
+                    let repos = client
+                        .stream_repositories_matching(workspace.as_deref(), role, api_sort)
+                        .try_collect()
+                        .await?;
+                    (repos, false)
+                } else {
+                    let page = client
+                        .list_repositories_matching(workspace.as_deref(), role, api_sort, limit)
+                        .await?;
+                    (page.values, page.next.is_some())
+                };
+                // --mine keeps each workspace's repositories together
+                match (mine, sort) {
+                    (true, sort) => repos.sort_by(|a, b| {
+                        workspace_of(a)
+                            .cmp(workspace_of(b))
+                            .then_with(|| sort.unwrap_or(RepoSort::Name).compare(a, b))
+                    }),
+                    (false, Some(sort)) => repos.sort_by(|a, b| sort.compare(a, b)),
+                    (false, None) => {}
+                }
+
+                if output::print(&repos)? {
+                    return Ok(());
+                }
+
+                if repos.is_empty() {
+                    note_none_found(workspace.as_deref());
                     return Ok(());
                 }
 
-                let rows: Vec<RepoRow> = repos
-                    .values
-                    .iter()
-                    .map(|r| RepoRow {
-                        name: r.full_name.clone(),
-                        description: r
-                            .description
-                            .clone()
-                            .unwrap_or_default()
-                            .chars()
-                            .take(40)
-                            .collect::<String>(),
-                        private: if r.is_private.unwrap_or(false) {
-                            "Yes"
-                        } else {
-                            "No"
+                if mine && output::format() == ReportFormat::Table && output::is_tty() {
+                    // One table per workspace, under its name; piped rows
+                    // carry the workspace in their full name already
+                    for (i, group) in repos
+                        .chunk_by(|a, b| workspace_of(a) == workspace_of(b))
+                        .enumerate()
+                    {
+                        if i > 0 {
+                            println!();
                         }
-                        .to_string(),
-                        updated: r
-                            .updated_on
-                            .map(|d| d.format("%Y-%m-%d").to_string())
-                            .unwrap_or_default(),
-                    })
-                    .collect();
-
-                let table = Table::new(rows).to_string();
-                println!("{}", table);
-
-                if repos.next.is_some() {
-                    println!(
-                        "\n{} More repositories available. Use --limit to see more.",
+                        println!("{}", workspace_of(&group[0]).bold());
+                        output::table(group.iter().map(RepoRow::from).collect())?;
+                    }
+                } else {
+                    output::table(repos.iter().map(RepoRow::from).collect())?;
+                }
+
+                if total {
+                    note_total(repos.iter().filter_map(|r| r.size).sum(), repos.len());
+                }
+
+                if has_more {
+                    output::note(format!(
+                        "\n{} More repositories available. Use --limit or --all to see more.",
                         "ℹ".blue()
-                    );
+                    ));
                 }
 
                 Ok(())
             }
 
-            RepoCommands::View { repo, web } => {
+            RepoCommands::View {
+                repo,
+                web,
+                url,
+                readme,
+            } => {
                 let (workspace, repo_slug) = parse_repo(&repo)?;
+                if url {
+                    println!("{}/{}/{}", WEB_URL, workspace, repo_slug);
+                    return Ok(());
+                }
                 let client = BitbucketClient::from_stored().await?;
                 let repository = client.get_repository(&workspace, &repo_slug).await?;
 
@@ -168,8 +472,12 @@ impl RepoCommands {
                     anyhow::bail!("Could not find repository URL");
                 }
 
+                if output::print(&repository)? {
+                    return Ok(());
+                }
+
                 println!("{}", repository.full_name.bold());
-                println!("{}", "─".repeat(50));
+                print!("{}", output::rule(50));
 
                 if let Some(desc) = &repository.description {
                     if !desc.is_empty() {
@@ -209,11 +517,11 @@ impl RepoCommands {
                 }
 
                 if let Some(created) = repository.created_on {
-                    println!("{} {}", "Created:".dimmed(), created.format("%Y-%m-%d"));
+                    println!("{} {}", "Created:".dimmed(), format::date(&created));
                 }
 
                 if let Some(updated) = repository.updated_on {
-                    println!("{} {}", "Updated:".dimmed(), updated.format("%Y-%m-%d"));
+                    println!("{} {}", "Updated:".dimmed(), format::date(&updated));
                 }
 
                 if let Some(links) = &repository.links {
@@ -228,33 +536,67 @@ impl RepoCommands {
                     }
                 }
 
+                if readme {
+                    print_readme(&client, &workspace, &repo_slug, &repository).await?;
+                }
+
                 Ok(())
             }
 
-            RepoCommands::Clone { repo, dir } => {
+            RepoCommands::Clone {
+                repo,
+                dir,
+                https,
+                ssh,
+                depth,
+                branch,
+                recurse_submodules,
+            } => {
                 let (workspace, repo_slug) = parse_repo(&repo)?;
+                let protocol = if https {
+                    CloneProtocol::Https
+                } else if ssh {
+                    CloneProtocol::Ssh
+                } else {
+                    Config::load()
+                        .map(|c| c.defaults.clone_protocol)
+                        .unwrap_or_default()
+                };
                 let client = BitbucketClient::from_stored().await?;
                 let repository = client.get_repository(&workspace, &repo_slug).await?;
 
                 let clone_url = repository
-                    .links
-                    .as_ref()
-                    .and_then(|l| l.clone.as_ref())
-                    .and_then(|links| links.iter().find(|l| l.name == "ssh" || l.name == "https"))
-                    .map(|l| &l.href)
-                    .context("Could not find clone URL")?;
+                    .clone_url(protocol.link_name())
+                    .with_context(|| {
+                        format!("Could not find {} clone URL", protocol.link_name())
+                    })?;
 
                 let target_dir = dir.unwrap_or_else(|| repo_slug.clone());
 
+                let mut args = vec!["clone".to_string()];
+                if let Some(depth) = depth {
+                    args.push(format!("--depth={}", depth));
+                }
+                if let Some(branch) = &branch {
+                    args.push(format!("--branch={}", branch));
+                }
+                if recurse_submodules {
+                    args.push("--recurse-submodules".to_string());
+                }
+                args.push("--".to_string());
+                args.push(clone_url.to_string());
+                args.push(target_dir.clone());
+
                 println!("Cloning {} into {}...", repo.cyan(), target_dir);
+                tracing::debug!(?args, "running git clone");
 
                 let status = std::process::Command::new("git")
-                    .args(["clone", clone_url, &target_dir])
+                    .args(&args)
                     .status()
                     .context("Failed to run git clone")?;
 
                 if status.success() {
-                    println!("{} Successfully cloned repository", "✓".green());
+                    output::success("Successfully cloned repository");
                 } else {
                     anyhow::bail!("git clone failed");
                 }
@@ -262,6 +604,25 @@ impl RepoCommands {
                 Ok(())
             }
 
+            RepoCommands::Download {
+                repo,
+                name,
+                output_file: path,
+                sha256,
+            } => {
+                let (workspace, repo_slug) = parse_repo(&repo)?;
+                let client = BitbucketClient::from_stored().await?;
+
+                let path = path.unwrap_or_else(|| PathBuf::from(&name));
+                let downloaded = download::fetch(&name, &path, sha256.as_deref(), |resume| {
+                    client.get_download(&workspace, &repo_slug, &name, resume)
+                })
+                .await?;
+                output::success(download::summary(&downloaded));
+
+                Ok(())
+            }
+
             RepoCommands::Create {
                 workspace,
                 name,
@@ -296,11 +657,14 @@ impl RepoCommands {
                     .create_repository(&workspace, &slug, &request)
                     .await?;
 
-                println!(
-                    "{} Created repository {}",
-                    "✓".green(),
+                if output::print(&repository)? {
+                    return Ok(());
+                }
+
+                output::success(format!(
+                    "Created repository {}",
                     repository.full_name.cyan()
-                );
+                ));
 
                 if let Some(links) = &repository.links {
                     if let Some(html) = &links.html {
@@ -315,11 +679,14 @@ impl RepoCommands {
                 repo,
                 workspace,
                 name,
+                clone,
+                dir,
+                no_wait,
             } => {
                 let (src_workspace, src_repo) = parse_repo(&repo)?;
                 let client = BitbucketClient::from_stored().await?;
 
-                let forked = client
+                let mut forked = client
                     .fork_repository(
                         &src_workspace,
                         &src_repo,
@@ -327,35 +694,144 @@ impl RepoCommands {
                         name.as_deref(),
                     )
                     .await?;
+                if !no_wait {
+                    forked = wait_for_fork(&client, &forked.full_name).await?;
+                }
+
+                if output::print(&forked)? {
+                    return Ok(());
+                }
+
+                output::success(format!("Forked to {}", forked.full_name.cyan()));
 
-                println!("{} Forked to {}", "✓".green(), forked.full_name.cyan());
+                if clone {
+                    let protocol = Config::load()
+                        .map(|c| c.defaults.clone_protocol)
+                        .unwrap_or_default();
+                    let source = client.get_repository(&src_workspace, &src_repo).await?;
+                    let url_of = |repository: &Repository| {
+                        repository
+                            .clone_url(protocol.link_name())
+                            .map(str::to_string)
+                            .with_context(|| {
+                                format!(
+                                    "Could not find {} clone URL for {}",
+                                    protocol.link_name(),
+                                    repository.full_name
+                                )
+                            })
+                    };
+                    let fork_url = url_of(&forked)?;
+                    let upstream_url = url_of(&source)?;
+                    let target_dir = match dir {
+                        Some(dir) => dir,
+                        None => parse_repo(&forked.full_name)?.1,
+                    };
+
+                    println!("Cloning {} into {}...", forked.full_name.cyan(), target_dir);
+                    let status = std::process::Command::new("git")
+                        .args(["clone", "--", &fork_url, &target_dir])
+                        .status()
+                        .context("Failed to run git clone")?;
+                    if !status.success() {
+                        anyhow::bail!("git clone failed");
+                    }
+                    git::run(&[
+                        "-C",
+                        &target_dir,
+                        "remote",
+                        "add",
+                        "upstream",
+                        &upstream_url,
+                    ])?;
+                    output::success(format!(
+                        "Cloned into {} with upstream {}",
+                        target_dir,
+                        repo.cyan()
+                    ));
+                }
 
                 Ok(())
             }
 
-            RepoCommands::Delete { repo, yes } => {
-                let (workspace, repo_slug) = parse_repo(&repo)?;
+            RepoCommands::Delete {
+                repos,
+                pattern,
+                allow_all,
+                confirm,
+            } => {
+                if let [repo] = repos.as_slice()
+                    && confirm.is_none()
+                {
+                    let (workspace, repo_slug) = parse_repo(repo)?;
+                    let full_name = format!("{}/{}", workspace, repo_slug);
 
-                if !yes {
-                    use dialoguer::Confirm;
-                    let confirmed = Confirm::new()
-                        .with_prompt(format!(
-                            "Are you sure you want to delete {}? This cannot be undone!",
-                            repo.red()
-                        ))
-                        .default(false)
-                        .interact()?;
-
-                    if !confirmed {
-                        println!("Aborted");
+                    if !confirm::confirm(format!(
+                        "Are you sure you want to delete {}? This cannot be undone!",
+                        full_name.red()
+                    ))? {
+                        output::note("Aborted");
                         return Ok(());
                     }
+
+                    let client = BitbucketClient::from_stored().await?;
+                    client.delete_repository(&workspace, &repo_slug).await?;
+
+                    output::success(format!("Deleted repository {}", full_name));
+
+                    return Ok(());
                 }
 
+                delete_many(repos, pattern, allow_all, confirm, workspace).await
+            }
+
+            RepoCommands::Watch { repos } => set_watching(&repos, true).await,
+
+            RepoCommands::Unwatch { repos } => set_watching(&repos, false).await,
+
+            RepoCommands::Watchers { repo, limit, all } => {
+                let (workspace, repo_slug) = parse_repo(&repo)?;
                 let client = BitbucketClient::from_stored().await?;
-                client.delete_repository(&workspace, &repo_slug).await?;
 
-                println!("{} Deleted repository {}", "✓".green(), repo);
+                if all && output::streams() {
+                    let watchers = client.stream_watchers(&workspace, &repo_slug);
+                    let count = output::stream(watchers, |u| WatcherRow::from(u)).await?;
+                    if count == 0 && !output::is_structured() {
+                        output::note(format!("No one is watching {}", repo));
+                    }
+                    return Ok(());
+                }
+                let (watchers, has_more): (Vec<User>, bool) = if all {
+                    let watchers = client
+                        .stream_watchers(&workspace, &repo_slug)
+                        .try_collect()
+                        .await?;
+                    (watchers, false)
+                } else {
+                    let page = client
+                        .list_watchers(&workspace, &repo_slug, Some(limit))
+                        .await?;
+                    (page.values, page.next.is_some())
+                };
+
+                if output::print(&watchers)? {
+                    return Ok(());
+                }
+
+                let rows: Vec<WatcherRow> = watchers.iter().map(WatcherRow::from).collect();
+                if rows.is_empty() {
+                    output::note(format!("No one is watching {}", repo));
+                    return Ok(());
+                }
+
+                output::table(rows)?;
+
+                if has_more {
+                    output::note(format!(
+                        "\n{} More watchers available. Use --limit or --all to see more.",
+                        "ℹ".blue()
+                    ));
+                }
 
                 Ok(())
             }
@@ -363,13 +839,212 @@ impl RepoCommands {
     }
 }
 
-fn parse_repo(repo: &str) -> Result<(String, String)> {
-    let parts: Vec<&str> = repo.split('/').collect();
-    if parts.len() != 2 {
-        anyhow::bail!(
-            "Invalid repository format. Expected 'workspace/repo-slug', got '{}'",
-            repo
-        );
+/// Delete `repos`, or every repository in `workspace` matching `pattern`,
+/// once the user has seen the list and typed the workspace name (or passed
+/// it as `confirm`)
+async fn delete_many(
+    mut repos: Vec<String>,
+    pattern: Option<String>,
+    allow_all: bool,
+    confirm: Option<String>,
+    workspace: Option<String>,
+) -> Result<()> {
+    if let Some(pattern) = &pattern
+        && pattern.chars().all(|c| c == '*')
+        && !allow_all
+    {
+        anyhow::bail!(UsageError(format!(
+            "--match '{}' selects every repository in the workspace; add --allow-all if that's what you mean",
+            pattern
+        )));
+    }
+    let client = BitbucketClient::from_stored().await?;
+    if let Some(pattern) = &pattern {
+        let workspace = workspace.ok_or_else(|| {
+            UsageError("--match needs the workspace to search, with --workspace".to_string())
+        })?;
+        let all: Vec<Repository> = client.stream_repositories(&workspace).try_collect().await?;
+        repos = all
+            .into_iter()
+            .filter(|r| glob_match(pattern, r.full_name.split_once('/').map_or("", |(_, s)| s)))
+            .map(|r| r.full_name)
+            .collect();
+        if repos.is_empty() {
+            output::note(format!(
+                "No repositories in {} match '{}'",
+                workspace, pattern
+            ));
+            return Ok(());
+        }
+    }
+    // Resolved, so `-` shows as the repository it stands for
+    let mut resolved = Vec::new();
+    let mut workspaces = std::collections::BTreeSet::new();
+    for repo in &repos {
+        let (workspace, repo_slug) = parse_repo(repo)?;
+        resolved.push(format!("{}/{}", workspace, repo_slug));
+        workspaces.insert(workspace);
+    }
+    let mut seen = std::collections::HashSet::new();
+    resolved.retain(|repo| seen.insert(repo.clone()));
+    let repos = resolved;
+
+    println!("Repositories to delete ({}):", repos.len());
+    for repo in &repos {
+        println!("  {}", repo.red());
+    }
+    // One workspace's name, or the count when they span several
+    let expected = match workspaces.iter().collect::<Vec<_>>().as_slice() {
+        [workspace] => workspace.to_string(),
+        _ => repos.len().to_string(),
+    };
+    if !confirm::confirm_typed("This cannot be undone!", &expected, confirm.as_deref())? {
+        output::note("Aborted");
+        return Ok(());
+    }
+
+    let outcome = fanout::run("Deleting", repos, fanout::DEFAULT_CONCURRENCY, |repo| {
+        let client = &client;
+        async move {
+            let (workspace, repo_slug) = parse_repo(&repo)?;
+            client
+                .delete_repository(&workspace, &repo_slug)
+                .await
+                .map_err(Into::into)
+        }
+    })
+    .await;
+    for (repo, ()) in &outcome.succeeded {
+        output::success(format!("Deleted repository {}", repo));
+    }
+    outcome.finish("delete", "repositories")?;
+    Ok(())
+}
+
+/// Watch or unwatch each repository as the authenticated user, carrying on
+/// past failures so one bad slug doesn't stop a bulk change
+async fn set_watching(repos: &[String], watch: bool) -> Result<()> {
+    for repo in repos {
+        parse_repo(repo)?;
+    }
+
+    let client = BitbucketClient::from_stored().await?;
+    let me = client.get_current_user().await?;
+
+    let outcome = fanout::run(
+        if watch { "Watching" } else { "Unwatching" },
+        repos.to_vec(),
+        fanout::DEFAULT_CONCURRENCY,
+        |repo| {
+            let (client, me) = (&client, &me);
+            async move {
+                let (workspace, repo_slug) = parse_repo(&repo)?;
+                if watch {
+                    client
+                        .watch_repository(&workspace, &repo_slug, &me.uuid)
+                        .await
+                } else {
+                    client
+                        .unwatch_repository(&workspace, &repo_slug, &me.uuid)
+                        .await
+                }
+                .map_err(Into::into)
+            }
+        },
+    )
+    .await;
+
+    for (repo, ()) in &outcome.succeeded {
+        if watch {
+            output::success(format!("Watching {}", repo.cyan()));
+        } else {
+            output::success(format!("Stopped watching {}", repo.cyan()));
+        }
+    }
+    outcome.finish(if watch { "watch" } else { "unwatch" }, "repositories")?;
+    Ok(())
+}
+
+/// README names in order of preference, compared case-insensitively
+const README_NAMES: &[&str] = &[
+    "README.md",
+    "README.markdown",
+    "README.rst",
+    "README.txt",
+    "README",
+];
+
+/// Print the README at the top of the main branch, rendering Markdown
+async fn print_readme(
+    client: &BitbucketClient,
+    workspace: &str,
+    repo_slug: &str,
+    repository: &Repository,
+) -> Result<()> {
+    let branch = repository
+        .mainbranch
+        .as_ref()
+        .map(|b| b.name.as_str())
+        .ok_or_else(|| anyhow::anyhow!("{} has no main branch yet", repository.full_name))?;
+    let entries = client
+        .list_source_root(workspace, repo_slug, branch)
+        .await?;
+    let Some(path) = find_readme(&entries) else {
+        output::note(format!("No README on {}", branch));
+        return Ok(());
+    };
+
+    let text = client
+        .get_source_file(workspace, repo_slug, branch, path)
+        .await?;
+    let lower = path.to_ascii_lowercase();
+    let text = if lower.ends_with(".md") || lower.ends_with(".markdown") {
+        markdown::display(&text, false)
+    } else {
+        text
+    };
+
+    println!();
+    println!("{}", path.bold());
+    print!("{}", output::rule(50));
+    pager::page(&text)
+}
+
+fn find_readme(entries: &[SourceEntry]) -> Option<&str> {
+    README_NAMES.iter().find_map(|name| {
+        entries
+            .iter()
+            .find(|e| e.is_file() && e.path.eq_ignore_ascii_case(name))
+            .map(|e| e.path.as_str())
+    })
+}
+
+/// The workspace part of a repository's full name
+fn workspace_of(repository: &Repository) -> &str {
+    repository
+        .full_name
+        .split_once('/')
+        .map_or(repository.full_name.as_str(), |(workspace, _)| workspace)
+}
+
+/// Poll a new fork until the API serves it: forking finishes in the
+/// background, and the repository is a 404 until it has
+async fn wait_for_fork(client: &BitbucketClient, full_name: &str) -> Result<Repository> {
+    let (workspace, repo_slug) = parse_repo(full_name)?;
+    let started = Instant::now();
+    loop {
+        match client.get_repository(&workspace, &repo_slug).await {
+            Ok(repository) => return Ok(repository),
+            Err(Error::NotFound(_)) if started.elapsed() < FORK_TIMEOUT => {
+                tracing::debug!(%full_name, "fork not ready yet");
+                tokio::time::sleep(FORK_POLL_INTERVAL).await;
+            }
+            Err(Error::NotFound(_)) => anyhow::bail!(
+                "{} was still not available after {}s",
+                full_name,
+                FORK_TIMEOUT.as_secs()
+            ),
+            Err(e) => return Err(e.into()),
+        }
     }
-    Ok((parts[0].to_string(), parts[1].to_string()))
 }
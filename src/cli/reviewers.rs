@@ -0,0 +1,244 @@
+//! Reviewer suggestions for `pr suggest-reviewers`
+//!
+//! Whoever recently committed to the files a change touches knows them best;
+//! that familiarity is weighed against how many open pull requests in the
+//! repository are already waiting on each of them.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use futures::{StreamExt, TryStreamExt};
+use serde::Serialize;
+use tabled::Tabled;
+
+use super::output::Porcelain;
+use crate::api::BitbucketClient;
+use crate::models::{DiffStat, FileCommit, ParticipantRole, PullRequestState, User};
+
+/// Changed files whose history is looked at; the rest add little
+const MAX_FILES: usize = 30;
+/// File histories fetched at once
+const CONCURRENCY: usize = 4;
+
+/// Someone who could review the change
+#[derive(Debug, Clone, Serialize)]
+pub struct Suggestion {
+    pub user: User,
+    /// Recent commits to the changed files
+    pub commits: usize,
+    /// Changed files they committed to
+    pub files: usize,
+    /// Open pull requests in the repository waiting on their approval
+    pub open_reviews: usize,
+}
+
+impl Suggestion {
+    /// Familiarity with the files, discounted by review load
+    fn score(&self) -> f64 {
+        self.commits as f64 / (1 + self.open_reviews) as f64
+    }
+}
+
+impl Porcelain for Suggestion {
+    fn porcelain(&self) -> String {
+        self.user.uuid.clone()
+    }
+}
+
+#[derive(Tabled)]
+pub struct SuggestionRow {
+    #[tabled(rename = "REVIEWER")]
+    reviewer: String,
+    #[tabled(rename = "COMMITS")]
+    commits: usize,
+    #[tabled(rename = "FILES")]
+    files: usize,
+    #[tabled(rename = "OPEN REVIEWS")]
+    open_reviews: usize,
+}
+
+impl From<&Suggestion> for SuggestionRow {
+    fn from(suggestion: &Suggestion) -> Self {
+        Self {
+            reviewer: suggestion.user.display_name.clone(),
+            commits: suggestion.commits,
+            files: suggestion.files,
+            open_reviews: suggestion.open_reviews,
+        }
+    }
+}
+
+/// Reviewers for a change to `files`, best first, judged by the last `depth`
+/// commits to each on `revision`, leaving out the UUIDs in `exclude`
+pub async fn suggest(
+    client: &BitbucketClient,
+    workspace: &str,
+    repo_slug: &str,
+    revision: &str,
+    files: &[DiffStat],
+    depth: usize,
+    exclude: &BTreeSet<String>,
+) -> Result<Vec<Suggestion>> {
+    let paths: BTreeSet<&str> = files
+        .iter()
+        .filter_map(|f| f.old.as_ref().or(f.new.as_ref()))
+        .map(|f| f.path.as_str())
+        .collect();
+    if paths.len() > MAX_FILES {
+        tracing::debug!(
+            files = paths.len(),
+            "only looking at the first {}",
+            MAX_FILES
+        );
+    }
+
+    let histories: Vec<Vec<FileCommit>> = futures::stream::iter(paths.into_iter().take(MAX_FILES))
+        .map(|path| async move {
+            let history = client
+                .stream_file_history(workspace, repo_slug, revision, path)
+                .take(depth)
+                .try_collect::<Vec<FileCommit>>()
+                .await;
+            match history {
+                Ok(history) => Ok(history),
+                // Files the change adds have no history yet
+                Err(crate::Error::NotFound(_)) => Ok(Vec::new()),
+                Err(e) => Err(e),
+            }
+        })
+        .buffer_unordered(CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    let mut candidates = tally(&histories, exclude);
+    if candidates.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let open_reviews = open_reviews(client, workspace, repo_slug).await?;
+    for candidate in &mut candidates {
+        candidate.open_reviews = open_reviews.get(&candidate.user.uuid).copied().unwrap_or(0);
+    }
+    rank(&mut candidates);
+    Ok(candidates)
+}
+
+/// Commits and files per author with a Bitbucket account, in no particular
+/// order
+fn tally(histories: &[Vec<FileCommit>], exclude: &BTreeSet<String>) -> Vec<Suggestion> {
+    let mut by_user: BTreeMap<String, Suggestion> = BTreeMap::new();
+    for history in histories {
+        let mut seen_in_file = BTreeSet::new();
+        for entry in history {
+            let Some(user) = entry.commit.author.as_ref().and_then(|a| a.user.as_ref()) else {
+                continue;
+            };
+            if exclude.contains(&user.uuid) {
+                continue;
+            }
+            let suggestion = by_user
+                .entry(user.uuid.clone())
+                .or_insert_with(|| Suggestion {
+                    user: user.clone(),
+                    commits: 0,
+                    files: 0,
+                    open_reviews: 0,
+                });
+            suggestion.commits += 1;
+            if seen_in_file.insert(user.uuid.clone()) {
+                suggestion.files += 1;
+            }
+        }
+    }
+    by_user.into_values().collect()
+}
+
+/// Best first: most familiar for their load, then least loaded
+fn rank(candidates: &mut [Suggestion]) {
+    candidates.sort_by(|a, b| {
+        b.score()
+            .total_cmp(&a.score())
+            .then(a.open_reviews.cmp(&b.open_reviews))
+            .then(a.user.display_name.cmp(&b.user.display_name))
+    });
+}
+
+/// How many open pull requests each reviewer has yet to approve, by UUID
+async fn open_reviews(
+    client: &BitbucketClient,
+    workspace: &str,
+    repo_slug: &str,
+) -> Result<BTreeMap<String, usize>> {
+    let open: Vec<_> = client
+        .stream_pull_requests_with_participants(workspace, repo_slug, PullRequestState::Open)
+        .try_collect()
+        .await?;
+    let mut counts = BTreeMap::new();
+    for participant in open.iter().flat_map(|pr| pr.participants.iter().flatten()) {
+        if participant.role == ParticipantRole::Reviewer && !participant.approved {
+            *counts.entry(participant.user.uuid.clone()).or_insert(0) += 1;
+        }
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Commit, CommitAuthor};
+
+    fn user(name: &str) -> User {
+        serde_json::from_value(serde_json::json!({
+            "uuid": format!("{{{}}}", name),
+            "display_name": name,
+            "type": "user",
+        }))
+        .unwrap()
+    }
+
+    fn by(name: &str) -> FileCommit {
+        FileCommit {
+            path: "src/mill.rs".into(),
+            commit: Commit {
+                hash: "1a2b3c".into(),
+                message: None,
+                author: Some(CommitAuthor {
+                    raw: None,
+                    user: Some(user(name)),
+                }),
+                date: None,
+                links: None,
+            },
+        }
+    }
+
+    #[test]
+    fn familiarity_is_weighed_against_open_reviews() {
+        let histories = vec![
+            vec![by("ada"), by("ada"), by("charles"), by("me")],
+            vec![by("ada"), by("charles"), by("mary")],
+        ];
+        let exclude = BTreeSet::from(["{me}".to_string()]);
+        let mut candidates = tally(&histories, &exclude);
+        let ada = candidates
+            .iter()
+            .find(|c| c.user.display_name == "ada")
+            .unwrap();
+        assert_eq!((ada.commits, ada.files), (3, 2));
+        assert_eq!(candidates.len(), 3);
+
+        for candidate in &mut candidates {
+            candidate.open_reviews = if candidate.user.display_name == "ada" {
+                3
+            } else {
+                0
+            };
+        }
+        rank(&mut candidates);
+        let order: Vec<&str> = candidates
+            .iter()
+            .map(|c| c.user.display_name.as_str())
+            .collect();
+        assert_eq!(order, ["charles", "mary", "ada"]);
+    }
+}
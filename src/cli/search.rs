@@ -0,0 +1,314 @@
+//! Pull request and issue search across repositories (`bitbucket search`)
+//!
+//! The API searches one repository at a time, so the query becomes a
+//! Bitbucket query language filter run against every repository in the
+//! workspace (or every workspace) in parallel, and the matches are merged
+//! newest first with the repository each came from.
+
+use std::cmp::Reverse;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::{Args, Subcommand};
+use futures::{StreamExt, TryStreamExt};
+use serde::Serialize;
+use tabled::Tabled;
+
+use super::output::Porcelain;
+use super::pr::{PrRow, PrState};
+use super::{UsageError, fanout, format, output};
+use crate::api::BitbucketClient;
+use crate::config::Config;
+use crate::models::{Issue, PullRequest, PullRequestState, Repository};
+
+#[derive(Subcommand)]
+pub enum SearchCommands {
+    /// Find pull requests whose title or description mention QUERY
+    Prs {
+        #[command(flatten)]
+        scope: Scope,
+
+        /// Only pull requests in this state (default: any)
+        #[arg(short, long, value_enum)]
+        state: Option<PrState>,
+    },
+
+    /// Find issues whose title or description mention QUERY
+    Issues {
+        #[command(flatten)]
+        scope: Scope,
+    },
+}
+
+#[derive(Args)]
+pub struct Scope {
+    /// Text to look for
+    query: String,
+
+    /// Search every workspace you belong to instead of --workspace
+    #[arg(long)]
+    all_workspaces: bool,
+
+    /// Maximum matches to show
+    #[arg(short, long, default_value = "30")]
+    limit: usize,
+}
+
+/// A match and the repository it's in
+#[derive(Debug, Serialize)]
+struct Hit<T> {
+    repository: String,
+    #[serde(flatten)]
+    item: T,
+}
+
+impl<T: Identified> Porcelain for Hit<T> {
+    fn porcelain(&self) -> String {
+        format!("{}#{}", self.repository, self.item.id())
+    }
+}
+
+trait Identified {
+    fn id(&self) -> u64;
+    fn updated(&self) -> DateTime<Utc>;
+}
+
+impl Identified for PullRequest {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn updated(&self) -> DateTime<Utc> {
+        self.updated_on
+    }
+}
+
+impl Identified for Issue {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn updated(&self) -> DateTime<Utc> {
+        self.updated_on.unwrap_or(self.created_on)
+    }
+}
+
+#[derive(Tabled)]
+struct PrHitRow {
+    #[tabled(rename = "REPO")]
+    repo: String,
+    #[tabled(inline)]
+    pr: PrRow,
+}
+
+#[derive(Tabled)]
+struct IssueHitRow {
+    #[tabled(rename = "REPO")]
+    repo: String,
+    #[tabled(rename = "ID")]
+    id: u64,
+    #[tabled(rename = "TITLE")]
+    title: String,
+    #[tabled(rename = "STATE")]
+    state: String,
+    #[tabled(rename = "KIND")]
+    kind: String,
+    #[tabled(rename = "UPDATED")]
+    updated: String,
+}
+
+impl SearchCommands {
+    /// `workspace` is the global `--workspace`
+    pub async fn run(self, workspace: Option<String>) -> Result<()> {
+        match self {
+            SearchCommands::Prs { scope, state } => {
+                let client = BitbucketClient::from_stored().await?;
+                let filter = text_filter(&scope.query, &["title", "description"]);
+                let states: Vec<PullRequestState> = match state {
+                    Some(state) => vec![state.into()],
+                    None => vec![
+                        PullRequestState::Open,
+                        PullRequestState::Merged,
+                        PullRequestState::Declined,
+                        PullRequestState::Superseded,
+                    ],
+                };
+
+                let repositories = repositories(&client, &scope, workspace).await?;
+                let mut outcome = fanout::run(
+                    "Searching pull requests",
+                    repositories.iter().map(full_name).collect(),
+                    fanout::DEFAULT_CONCURRENCY,
+                    |name: String| {
+                        let (client, filter, states, limit) =
+                            (&client, &filter, &states, scope.limit);
+                        async move {
+                            let (workspace, repo_slug) =
+                                name.split_once('/').unwrap_or((&name, ""));
+                            // Newest first, so no repository needs more than --limit
+                            let prs: Vec<PullRequest> = client
+                                .search_pull_requests_in(workspace, repo_slug, states, filter)
+                                .take(limit)
+                                .try_collect()
+                                .await?;
+                            Ok(prs)
+                        }
+                    },
+                )
+                .await;
+
+                let hits = merge(&mut outcome, scope.limit);
+                if hits.is_empty() && !output::is_structured() {
+                    output::note(format!("No pull requests mention '{}'", scope.query));
+                } else if !output::print(&hits)? {
+                    output::table(
+                        hits.iter()
+                            .map(|hit| PrHitRow {
+                                repo: hit.repository.clone(),
+                                pr: PrRow::from(&hit.item),
+                            })
+                            .collect(),
+                    )?;
+                }
+                outcome.finish("search", "repositories")?;
+                Ok(())
+            }
+
+            SearchCommands::Issues { scope } => {
+                let client = BitbucketClient::from_stored().await?;
+                let filter = text_filter(&scope.query, &["title", "content.raw"]);
+
+                let repositories: Vec<Repository> = repositories(&client, &scope, workspace)
+                    .await?
+                    .into_iter()
+                    .filter(|r| r.has_issues.unwrap_or(true))
+                    .collect();
+                let mut outcome = fanout::run(
+                    "Searching issues",
+                    repositories.iter().map(full_name).collect(),
+                    fanout::DEFAULT_CONCURRENCY,
+                    |name: String| {
+                        let (client, filter, limit) = (&client, &filter, scope.limit);
+                        async move {
+                            let (workspace, repo_slug) =
+                                name.split_once('/').unwrap_or((&name, ""));
+                            let issues: Vec<Issue> = client
+                                .search_issues(workspace, repo_slug, filter)
+                                .take(limit)
+                                .try_collect()
+                                .await?;
+                            Ok(issues)
+                        }
+                    },
+                )
+                .await;
+
+                let hits = merge(&mut outcome, scope.limit);
+                if hits.is_empty() && !output::is_structured() {
+                    output::note(format!("No issues mention '{}'", scope.query));
+                } else if !output::print(&hits)? {
+                    output::table(
+                        hits.iter()
+                            .map(|hit| IssueHitRow {
+                                repo: hit.repository.clone(),
+                                id: hit.item.id,
+                                title: hit.item.title.clone(),
+                                state: hit.item.state.to_string(),
+                                kind: hit.item.kind.to_string(),
+                                updated: format::date(&hit.item.updated()),
+                            })
+                            .collect(),
+                    )?;
+                }
+                outcome.finish("search", "repositories")?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The repositories to search: those of `--workspace` (or the configured
+/// default), or of every workspace with `--all-workspaces`
+async fn repositories(
+    client: &BitbucketClient,
+    scope: &Scope,
+    workspace: Option<String>,
+) -> Result<Vec<Repository>> {
+    let workspaces: Vec<String> = if scope.all_workspaces {
+        let mut slugs: Vec<String> = client
+            .stream_workspaces()
+            .try_filter_map(|membership| async move { Ok(membership.workspace.map(|w| w.slug)) })
+            .try_collect()
+            .await?;
+        slugs.sort();
+        slugs.dedup();
+        slugs
+    } else {
+        let workspace = workspace
+            .or_else(|| {
+                Config::load()
+                    .ok()
+                    .and_then(|c| c.default_workspace().map(str::to_string))
+            })
+            .ok_or_else(|| UsageError("Pass --workspace or --all-workspaces".to_string()))?;
+        vec![workspace]
+    };
+
+    let mut repositories = Vec::new();
+    for workspace in &workspaces {
+        let found: Vec<Repository> = client.stream_repositories(workspace).try_collect().await?;
+        repositories.extend(found);
+    }
+    Ok(repositories)
+}
+
+fn full_name(repository: &Repository) -> String {
+    repository.full_name.clone()
+}
+
+/// The matches from every repository searched, most recently updated first
+fn merge<T: Identified>(
+    outcome: &mut fanout::Outcome<String, Vec<T>>,
+    limit: usize,
+) -> Vec<Hit<T>> {
+    let mut hits: Vec<Hit<T>> = outcome
+        .succeeded
+        .drain(..)
+        .flat_map(|(repository, items)| {
+            items.into_iter().map(move |item| Hit {
+                repository: repository.clone(),
+                item,
+            })
+        })
+        .collect();
+    hits.sort_by_key(|hit| Reverse(hit.item.updated()));
+    hits.truncate(limit);
+    hits
+}
+
+/// A filter matching `query` as text in any of `fields`, ignoring case
+fn text_filter(query: &str, fields: &[&str]) -> String {
+    let quoted = format!("\"{}\"", query.replace('\\', "\\\\").replace('"', "\\\""));
+    fields
+        .iter()
+        .map(|field| format!("{} ~ {}", field, quoted))
+        .collect::<Vec<_>>()
+        .join(" OR ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_become_quoted_text_filters() {
+        assert_eq!(
+            text_filter("note G", &["title", "description"]),
+            r#"title ~ "note G" OR description ~ "note G""#
+        );
+        assert_eq!(
+            text_filter(r#"say "hi""#, &["title"]),
+            r#"title ~ "say \"hi\"""#
+        );
+    }
+}
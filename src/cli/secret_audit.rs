@@ -0,0 +1,262 @@
+//! Finding secrets left in the open, for `audit secrets`
+//!
+//! Two places are looked at: Pipelines variables, workspace and repository
+//! alike, whose names say they hold a secret but that aren't secured; and
+//! files that workspace code search turns up for a few well-known kinds of
+//! credential. Code search only narrows the files down; each matched line is
+//! checked against the rule's pattern before it's reported. A rule looks at
+//! no more than [`FILES_PER_RULE`] files, so a noisy query can't page
+//! through the whole workspace.
+
+use std::collections::BTreeSet;
+use std::sync::LazyLock;
+
+use futures::{StreamExt, TryStreamExt};
+use regex::Regex;
+use serde::Serialize;
+use tabled::Tabled;
+
+use super::output::Porcelain;
+use crate::api::BitbucketClient;
+use crate::error::Result;
+use crate::models::{CodeSearchResult, PipelineVariable};
+
+/// Words that make a variable name look like it holds a secret
+const SECRET_WORDS: &[&str] = &[
+    "TOKEN",
+    "KEY",
+    "PASSWORD",
+    "PASSWD",
+    "SECRET",
+    "CREDENTIALS",
+];
+
+/// Most code search results looked at for each rule
+pub const FILES_PER_RULE: usize = 200;
+
+/// A kind of credential code search looks for
+struct Rule {
+    name: &'static str,
+    /// What's sent to code search
+    query: &'static str,
+    /// What a matched line must contain to be reported
+    pattern: Regex,
+}
+
+static RULES: LazyLock<Vec<Rule>> = LazyLock::new(|| {
+    [
+        (
+            "private key",
+            "\"PRIVATE KEY\"",
+            r"-----BEGIN (RSA |DSA |EC |OPENSSH |PGP |ENCRYPTED )?PRIVATE KEY",
+        ),
+        (
+            "AWS secret access key",
+            "aws_secret_access_key",
+            r#"(?i)aws_secret_access_key["']?\s*[:=]\s*["']?[A-Za-z0-9/+]{40}"#,
+        ),
+        ("Slack token", "xoxb", r"xox[abprs]-[0-9A-Za-z-]{10,}"),
+        (
+            "hard-coded password",
+            "password",
+            r#"(?i)\b(password|passwd|pwd)["']?\s*[:=]\s*["'][^"'\s]{4,}["']"#,
+        ),
+    ]
+    .into_iter()
+    .map(|(name, query, pattern)| Rule {
+        name,
+        query,
+        pattern: Regex::new(pattern).expect("valid secret pattern"),
+    })
+    .collect()
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FindingKind {
+    Variable,
+    File,
+}
+
+/// Something that looks like a secret nobody should be able to read
+#[derive(Debug, Clone, Serialize)]
+pub struct SecretFinding {
+    pub kind: FindingKind,
+    /// `workspace/repo`; `None` for a workspace variable
+    pub repository: Option<String>,
+    /// Variable name, or `path:line`
+    pub location: String,
+    pub finding: String,
+}
+
+impl Porcelain for SecretFinding {
+    fn porcelain(&self) -> String {
+        match &self.repository {
+            Some(repository) => format!("{}:{}", repository, self.location),
+            None => self.location.clone(),
+        }
+    }
+}
+
+#[derive(Tabled)]
+pub struct SecretFindingRow {
+    #[tabled(rename = "KIND")]
+    kind: String,
+    #[tabled(rename = "REPOSITORY")]
+    repository: String,
+    #[tabled(rename = "LOCATION")]
+    location: String,
+    #[tabled(rename = "FINDING")]
+    finding: String,
+}
+
+impl From<&SecretFinding> for SecretFindingRow {
+    fn from(f: &SecretFinding) -> Self {
+        Self {
+            kind: match f.kind {
+                FindingKind::Variable => "variable",
+                FindingKind::File => "file",
+            }
+            .to_string(),
+            repository: f.repository.clone().unwrap_or("(workspace)".into()),
+            location: f.location.clone(),
+            finding: f.finding.clone(),
+        }
+    }
+}
+
+/// Whether a variable called `key` looks like it holds a secret, judged by
+/// the words in it: `DEPLOY_TOKEN` and `apiKey` do, `MONKEY_COUNT` doesn't
+pub fn looks_secret(key: &str) -> bool {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut previous_lower = false;
+    for c in key.chars() {
+        if !c.is_ascii_alphanumeric() || (c.is_ascii_uppercase() && previous_lower) {
+            words.push(std::mem::take(&mut word));
+        }
+        if c.is_ascii_alphanumeric() {
+            word.push(c.to_ascii_uppercase());
+        }
+        previous_lower = c.is_ascii_lowercase();
+    }
+    words.push(word);
+    words
+        .iter()
+        .any(|w| SECRET_WORDS.contains(&w.as_str()) || w == "APIKEY")
+}
+
+/// The variables in `variables` that look secret but aren't secured
+pub fn unsecured_variables(
+    repository: Option<&str>,
+    variables: &[PipelineVariable],
+) -> Vec<SecretFinding> {
+    variables
+        .iter()
+        .filter(|v| !v.secured && looks_secret(&v.key))
+        .map(|v| SecretFinding {
+            kind: FindingKind::Variable,
+            repository: repository.map(str::to_string),
+            location: v.key.clone(),
+            finding: "unsecured variable with a secret-like name".to_string(),
+        })
+        .collect()
+}
+
+/// What code search turned up
+#[derive(Debug, Default)]
+pub struct CodeScan {
+    pub findings: Vec<SecretFinding>,
+    /// Rules that stopped at [`FILES_PER_RULE`] with more files to look at
+    pub capped: Vec<&'static str>,
+}
+
+/// Search `workspace`'s code for each kind of credential
+pub async fn scan_code(client: &BitbucketClient, workspace: &str) -> Result<CodeScan> {
+    let mut scan = CodeScan::default();
+    for rule in RULES.iter() {
+        let mut results: Vec<CodeSearchResult> = client
+            .stream_code_search(workspace, rule.query)
+            .take(FILES_PER_RULE + 1)
+            .try_collect()
+            .await?;
+        if results.len() > FILES_PER_RULE {
+            results.truncate(FILES_PER_RULE);
+            scan.capped.push(rule.name);
+        }
+        scan.findings.extend(code_findings(rule, &results));
+    }
+    Ok(scan)
+}
+
+/// Each matched line that `rule`'s pattern confirms, once
+fn code_findings(rule: &Rule, results: &[CodeSearchResult]) -> Vec<SecretFinding> {
+    let mut seen = BTreeSet::new();
+    let mut findings = Vec::new();
+    for result in results {
+        let repository = result
+            .file
+            .commit
+            .as_ref()
+            .and_then(|c| c.repository.as_ref())
+            .map(|r| r.full_name.clone());
+        let lines = result
+            .content_matches
+            .iter()
+            .flat_map(|m| &m.lines)
+            .filter(|l| l.segments.iter().any(|s| s.matched));
+        for line in lines {
+            if !rule.pattern.is_match(&line.text()) {
+                continue;
+            }
+            let location = format!("{}:{}", result.file.path, line.line);
+            if seen.insert((repository.clone(), location.clone())) {
+                findings.push(SecretFinding {
+                    kind: FindingKind::File,
+                    repository: repository.clone(),
+                    location,
+                    finding: rule.name.to_string(),
+                });
+            }
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_names_are_told_by_their_words() {
+        for key in [
+            "DEPLOY_TOKEN",
+            "aws-key",
+            "apiKey",
+            "API_KEY",
+            "DB_PASSWORD",
+        ] {
+            assert!(looks_secret(key), "{}", key);
+        }
+        for key in ["MONKEY_COUNT", "KEYBOARD_LAYOUT", "TOKENIZER", "NODE_ENV"] {
+            assert!(!looks_secret(key), "{}", key);
+        }
+    }
+
+    #[test]
+    fn matched_lines_are_confirmed_by_the_pattern() {
+        let results: Vec<CodeSearchResult> = serde_json::from_value(serde_json::json!([{
+            "file": { "path": "config/db.yml", "commit": { "repository": { "full_name": "acme/engine" } } },
+            "content_matches": [{ "lines": [
+                { "line": 3, "segments": [{ "text": "  " }, { "text": "password", "match": true }, { "text": ": \"hunter22\"" }] },
+                { "line": 4, "segments": [{ "text": "  " }, { "text": "password", "match": true }, { "text": ": ${DB_PASSWORD}" }] },
+                { "line": 5, "segments": [{ "text": "  host: db" }] },
+            ] }],
+        }]))
+        .unwrap();
+        let rule = RULES.iter().find(|r| r.query == "password").unwrap();
+        let findings = code_findings(rule, &results);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].porcelain(), "acme/engine:config/db.yml:3");
+    }
+}
@@ -0,0 +1,133 @@
+//! Whether Bitbucket Cloud itself is having trouble, from its status page
+//!
+//! `bitbucket service-status` shows every component and open incident. When
+//! a command fails with a server error, the same page is checked so the
+//! error can say whether Bitbucket has already reported an outage.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use colored::{ColoredString, Colorize};
+use tabled::Tabled;
+
+use super::icons::Icon;
+use super::output;
+use crate::Error;
+use crate::api::status;
+use crate::config::Config;
+use crate::models::StatusIncident;
+
+/// How long `service-status` waits for the status page
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How long a failed command waits for it, so reporting the failure isn't
+/// held up much
+const FAILURE_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Tabled)]
+struct ComponentRow {
+    #[tabled(rename = "COMPONENT")]
+    name: String,
+    #[tabled(rename = "STATUS")]
+    status: String,
+}
+
+/// Print Bitbucket Cloud's component statuses and open incidents
+pub async fn run() -> Result<()> {
+    let network = Config::load().unwrap_or_default().network;
+    let summary = status::service_status(&network, TIMEOUT).await?;
+    if output::print(&summary)? {
+        return Ok(());
+    }
+
+    println!(
+        "{} {}",
+        "Bitbucket Cloud:".bold(),
+        indicator(&summary.status.indicator, &summary.status.description)
+    );
+    output::table(
+        summary
+            .components
+            .iter()
+            .filter(|c| !c.group)
+            .map(|c| ComponentRow {
+                name: c.name.clone(),
+                status: component_status(&c.status).to_string(),
+            })
+            .collect(),
+    )?;
+
+    if summary.incidents.is_empty() {
+        output::note(format!("\n{} No open incidents", Icon::Ok.glyph().green()));
+    } else {
+        println!("\n{}", "Incidents:".bold());
+        for incident in &summary.incidents {
+            println!("  {}", describe(incident));
+        }
+    }
+    Ok(())
+}
+
+/// After `error`, say whether Bitbucket has reported an outage; only for
+/// server errors, since anything else is about this request
+pub async fn explain_failure(error: &anyhow::Error) {
+    let server_error = error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<Error>())
+        .any(|e| e.status().is_some_and(|status| status.is_server_error()));
+    if !server_error || crate::api::is_offline() || crate::api::recording::is_replaying() {
+        return;
+    }
+
+    let network = Config::load().unwrap_or_default().network;
+    match status::service_status(&network, FAILURE_TIMEOUT).await {
+        Ok(summary) if summary.status.indicator != "none" || !summary.incidents.is_empty() => {
+            eprintln!(
+                "{} Bitbucket reports: {}",
+                Icon::Warning.glyph().yellow(),
+                summary.status.description
+            );
+            for incident in &summary.incidents {
+                eprintln!("  {}", describe(incident));
+            }
+        }
+        Ok(_) => eprintln!(
+            "Bitbucket's status page reports no problems; run `bitbucket service-status` for details"
+        ),
+        Err(e) => tracing::debug!("could not check the status page: {}", e),
+    }
+}
+
+fn describe(incident: &StatusIncident) -> String {
+    let link = incident
+        .shortlink
+        .as_deref()
+        .map(|l| format!(" {}", l.cyan()))
+        .unwrap_or_default();
+    format!(
+        "{} {} ({}, {} impact){}",
+        Icon::Warning.glyph().yellow(),
+        incident.name,
+        incident.status.replace('_', " "),
+        incident.impact,
+        link
+    )
+}
+
+fn indicator(indicator: &str, description: &str) -> ColoredString {
+    match indicator {
+        "none" => description.green(),
+        "minor" => description.yellow(),
+        _ => description.red(),
+    }
+}
+
+fn component_status(status: &str) -> ColoredString {
+    let text = status.replace('_', " ");
+    match status {
+        "operational" => text.green(),
+        "degraded_performance" => text.yellow(),
+        "under_maintenance" => text.blue(),
+        _ => text.red(),
+    }
+}
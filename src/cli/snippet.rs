@@ -0,0 +1,393 @@
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Subcommand, ValueEnum};
+use colored::Colorize;
+use futures::TryStreamExt;
+use tabled::Tabled;
+
+use super::{UsageError, confirm, format, output};
+use crate::api::BitbucketClient;
+use crate::models::Snippet;
+
+#[derive(Subcommand)]
+pub enum SnippetCommands {
+    /// List snippets in a workspace
+    List {
+        /// Workspace slug
+        workspace: String,
+
+        /// Only snippets where you have this role
+        #[arg(long, value_enum)]
+        role: Option<SnippetRole>,
+
+        /// Number of results
+        #[arg(short, long, default_value = "25")]
+        limit: u32,
+
+        /// Fetch every page instead of stopping at --limit
+        #[arg(long, conflicts_with = "limit")]
+        all: bool,
+    },
+
+    /// View a snippet
+    View {
+        /// Workspace slug
+        workspace: String,
+
+        /// Snippet ID
+        id: String,
+
+        /// Print the raw contents of the snippet's files instead
+        #[arg(long)]
+        raw: bool,
+
+        /// Only this file (with --raw)
+        #[arg(long, requires = "raw")]
+        file: Option<String>,
+
+        /// Open in browser
+        #[arg(long, conflicts_with = "raw")]
+        web: bool,
+    },
+
+    /// Create a snippet from files, or from stdin when none are given
+    Create {
+        /// Workspace slug
+        workspace: String,
+
+        /// Files to include ("-" reads stdin)
+        files: Vec<PathBuf>,
+
+        /// Snippet title (default: the first file name)
+        #[arg(short, long)]
+        title: Option<String>,
+
+        /// File name for content read from stdin
+        #[arg(long, default_value = "snippet.txt")]
+        filename: String,
+
+        /// Make the snippet private
+        #[arg(long)]
+        private: bool,
+    },
+
+    /// Download a snippet's files into a directory
+    Download {
+        /// Workspace slug
+        workspace: String,
+
+        /// Snippet ID
+        id: String,
+
+        /// Directory to write into
+        #[arg(short, long, default_value = ".")]
+        dir: PathBuf,
+    },
+
+    /// Delete a snippet
+    Delete {
+        /// Workspace slug
+        workspace: String,
+
+        /// Snippet ID
+        id: String,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum SnippetRole {
+    Owner,
+    Contributor,
+    Member,
+}
+
+impl SnippetRole {
+    fn as_str(self) -> &'static str {
+        match self {
+            SnippetRole::Owner => "owner",
+            SnippetRole::Contributor => "contributor",
+            SnippetRole::Member => "member",
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct SnippetRow {
+    #[tabled(rename = "ID")]
+    id: String,
+    #[tabled(rename = "TITLE")]
+    title: String,
+    #[tabled(rename = "FILES")]
+    files: usize,
+    #[tabled(rename = "PRIVATE")]
+    private: String,
+    #[tabled(rename = "UPDATED")]
+    updated: String,
+}
+
+impl From<&Snippet> for SnippetRow {
+    fn from(s: &Snippet) -> Self {
+        Self {
+            id: s.encoded_id().to_string(),
+            title: s.title.clone(),
+            files: s.files.len(),
+            private: if s.is_private.unwrap_or(false) {
+                "Yes"
+            } else {
+                "No"
+            }
+            .to_string(),
+            updated: s.updated_on.map(|d| format::date(&d)).unwrap_or_default(),
+        }
+    }
+}
+
+impl SnippetCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            SnippetCommands::List {
+                workspace,
+                role,
+                limit,
+                all,
+            } => {
+                let client = BitbucketClient::from_stored().await?;
+                let role = role.map(SnippetRole::as_str);
+
+                if all && output::streams() {
+                    let snippets = client.stream_snippets(&workspace, role);
+                    let count = output::stream(snippets, |s| SnippetRow::from(s)).await?;
+                    if count == 0 && !output::is_structured() {
+                        output::note(format!("No snippets found in workspace '{}'", workspace));
+                    }
+                    return Ok(());
+                }
+                let (snippets, has_more): (Vec<Snippet>, bool) = if all {
+                    let snippets = client
+                        .stream_snippets(&workspace, role)
+                        .try_collect()
+                        .await?;
+                    (snippets, false)
+                } else {
+                    let page = client.list_snippets(&workspace, role, Some(limit)).await?;
+                    (page.values, page.next.is_some())
+                };
+
+                if output::print(&snippets)? {
+                    return Ok(());
+                }
+
+                let rows: Vec<SnippetRow> = snippets.iter().map(SnippetRow::from).collect();
+                if rows.is_empty() {
+                    output::note(format!("No snippets found in workspace '{}'", workspace));
+                    return Ok(());
+                }
+
+                output::table(rows)?;
+
+                if has_more {
+                    output::note(format!(
+                        "\n{} More snippets available. Use --limit or --all to see more.",
+                        "ℹ".blue()
+                    ));
+                }
+
+                Ok(())
+            }
+
+            SnippetCommands::View {
+                workspace,
+                id,
+                raw,
+                file,
+                web,
+            } => {
+                let client = BitbucketClient::from_stored().await?;
+                let snippet = client.get_snippet(&workspace, &id).await?;
+
+                if web {
+                    if let Some(html) = snippet.links.as_ref().and_then(|l| l.html.as_ref()) {
+                        open::that(&html.href)?;
+                        println!("Opened {} in browser", html.href.cyan());
+                        return Ok(());
+                    }
+                    anyhow::bail!("Could not find snippet URL");
+                }
+
+                if raw {
+                    let names: Vec<&String> = match &file {
+                        Some(name) => {
+                            if !snippet.files.contains_key(name) {
+                                anyhow::bail!(UsageError(format!(
+                                    "Snippet {} has no file '{}'",
+                                    id, name
+                                )));
+                            }
+                            vec![name]
+                        }
+                        None => snippet.files.keys().collect(),
+                    };
+
+                    // Like `head`, label each file only when printing several
+                    let label = names.len() > 1;
+                    for (i, name) in names.into_iter().enumerate() {
+                        let contents = client.get_snippet_file(&workspace, &id, name).await?;
+                        if label {
+                            if i > 0 {
+                                println!();
+                            }
+                            println!("==> {} <==", name);
+                        }
+                        let mut stdout = std::io::stdout().lock();
+                        stdout.write_all(&contents)?;
+                        if !contents.ends_with(b"\n") {
+                            stdout.write_all(b"\n")?;
+                        }
+                    }
+                    return Ok(());
+                }
+
+                if output::print(&snippet)? {
+                    return Ok(());
+                }
+
+                println!("{}", snippet.title.bold());
+                print!("{}", output::rule(50));
+                println!("{} {}", "ID:".dimmed(), snippet.encoded_id());
+                if let Some(owner) = &snippet.owner {
+                    println!("{} {}", "Owner:".dimmed(), owner.display_name);
+                }
+                println!(
+                    "{} {}",
+                    "Private:".dimmed(),
+                    if snippet.is_private.unwrap_or(false) {
+                        "Yes"
+                    } else {
+                        "No"
+                    }
+                );
+                if let Some(created) = snippet.created_on {
+                    println!("{} {}", "Created:".dimmed(), format::date(&created));
+                }
+                if let Some(updated) = snippet.updated_on {
+                    println!("{} {}", "Updated:".dimmed(), format::date(&updated));
+                }
+
+                if !snippet.files.is_empty() {
+                    println!();
+                    println!("{}", "Files:".dimmed());
+                    for name in snippet.files.keys() {
+                        println!("  {}", name);
+                    }
+                }
+
+                if let Some(html) = snippet.links.as_ref().and_then(|l| l.html.as_ref()) {
+                    println!();
+                    println!("{} {}", "Web:".dimmed(), html.href.cyan());
+                }
+
+                Ok(())
+            }
+
+            SnippetCommands::Create {
+                workspace,
+                files,
+                title,
+                filename,
+                private,
+            } => {
+                let files = read_files(&files, &filename)?;
+                let title = title.unwrap_or_else(|| files[0].0.clone());
+
+                let client = BitbucketClient::from_stored().await?;
+                let snippet = client
+                    .create_snippet(&workspace, &title, private, &files)
+                    .await?;
+
+                if output::print(&snippet)? {
+                    return Ok(());
+                }
+
+                output::success(format!("Created snippet {}", snippet.encoded_id()));
+                if let Some(html) = snippet.links.as_ref().and_then(|l| l.html.as_ref()) {
+                    println!("{}", html.href.cyan());
+                }
+
+                Ok(())
+            }
+
+            SnippetCommands::Download { workspace, id, dir } => {
+                let client = BitbucketClient::from_stored().await?;
+                let snippet = client.get_snippet(&workspace, &id).await?;
+
+                std::fs::create_dir_all(&dir)
+                    .with_context(|| format!("Failed to create {}", dir.display()))?;
+
+                for name in snippet.files.keys() {
+                    // Never let a file name from the API escape the target directory
+                    let Some(file_name) = std::path::Path::new(name).file_name() else {
+                        tracing::warn!(%name, "skipping snippet file with unusable name");
+                        continue;
+                    };
+                    let contents = client.get_snippet_file(&workspace, &id, name).await?;
+                    let path = dir.join(file_name);
+                    std::fs::write(&path, contents)
+                        .with_context(|| format!("Failed to write {}", path.display()))?;
+                    output::success(format!("Downloaded {}", path.display()));
+                }
+
+                Ok(())
+            }
+
+            SnippetCommands::Delete { workspace, id } => {
+                if !confirm::confirm(format!(
+                    "Are you sure you want to delete snippet {}? This cannot be undone!",
+                    id.red()
+                ))? {
+                    output::note("Aborted");
+                    return Ok(());
+                }
+
+                let client = BitbucketClient::from_stored().await?;
+                client.delete_snippet(&workspace, &id).await?;
+
+                output::success(format!("Deleted snippet {}", id));
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Read snippet files as (name, contents); no paths or "-" means stdin
+fn read_files(paths: &[PathBuf], stdin_name: &str) -> Result<Vec<(String, Vec<u8>)>> {
+    let read_stdin = || -> Result<(String, Vec<u8>)> {
+        let mut contents = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut contents)
+            .context("Failed to read stdin")?;
+        Ok((stdin_name.to_string(), contents))
+    };
+
+    if paths.is_empty() {
+        return Ok(vec![read_stdin()?]);
+    }
+
+    paths
+        .iter()
+        .map(|path| {
+            if path.as_os_str() == "-" {
+                return read_stdin();
+            }
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .with_context(|| format!("Not a file: {}", path.display()))?;
+            let contents = std::fs::read(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            Ok((name, contents))
+        })
+        .collect()
+}
@@ -0,0 +1,418 @@
+//! Pull request and issue metrics for a workspace, for team retrospectives
+//!
+//! Cycle time runs from a pull request's creation to its merge, and review
+//! latency from its creation to the first approval, request for changes or
+//! comment by someone other than the author. The API has no merge or close
+//! timestamp, so a pull request's or issue's last update stands in for it;
+//! edits after merging or closing skew those figures.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::Args;
+use colored::Colorize;
+use futures::TryStreamExt;
+use serde::Serialize;
+use tabled::Tabled;
+
+use super::output::{Porcelain, ReportFormat};
+use super::pipeline::format_duration;
+use super::{UsageError, fanout, format, output};
+use crate::api::BitbucketClient;
+use crate::models::{
+    Issue, IssueState, PullRequest, PullRequestActivity, PullRequestState, Repository,
+};
+
+#[derive(Args)]
+pub struct StatsArgs {
+    /// Workspace to report on
+    workspace: String,
+
+    /// Start of the window: an age such as 30d, 2w or 36h, or a date
+    #[arg(long, default_value = "30d", value_name = "AGE|DATE")]
+    since: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Stats {
+    workspace: String,
+    since: DateTime<Utc>,
+    pull_requests: PullRequestStats,
+    issues: IssueStats,
+    /// Merged pull requests by author, most first
+    authors: Vec<AuthorStats>,
+}
+
+#[derive(Debug, Serialize)]
+struct PullRequestStats {
+    merged: usize,
+    cycle_time: Summary,
+    review_latency: Summary,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Summary {
+    samples: usize,
+    median_hours: Option<f64>,
+    mean_hours: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct IssueStats {
+    opened: usize,
+    closed: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct AuthorStats {
+    author: String,
+    merged: usize,
+    median_cycle_hours: Option<f64>,
+}
+
+impl Porcelain for Stats {
+    /// Authors with merged pull requests, one per line
+    fn porcelain(&self) -> String {
+        self.authors
+            .iter()
+            .map(|a| a.author.clone())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[derive(Tabled)]
+struct MetricRow {
+    #[tabled(rename = "METRIC")]
+    metric: &'static str,
+    #[tabled(rename = "VALUE")]
+    value: String,
+}
+
+/// A figure in the CSV report
+#[derive(Tabled)]
+struct FigureRow {
+    #[tabled(rename = "SECTION")]
+    section: &'static str,
+    #[tabled(rename = "NAME")]
+    name: String,
+    #[tabled(rename = "VALUE")]
+    value: String,
+}
+
+#[derive(Tabled)]
+struct AuthorRow {
+    #[tabled(rename = "AUTHOR")]
+    author: String,
+    #[tabled(rename = "MERGED")]
+    merged: usize,
+    #[tabled(rename = "MEDIAN CYCLE TIME")]
+    cycle_time: String,
+}
+
+impl StatsArgs {
+    pub async fn run(self) -> Result<()> {
+        let since = format::parse_time(&self.since).ok_or_else(|| {
+            UsageError(format!(
+                "Invalid --since '{}': use an age such as 30d or a date such as 2024-06-01",
+                self.since
+            ))
+        })?;
+        let client = BitbucketClient::from_stored().await?;
+
+        let repositories: Vec<Repository> = client
+            .stream_repositories(&self.workspace)
+            .try_collect()
+            .await?;
+        let mut outcome = fanout::run(
+            "Collecting activity",
+            repositories.iter().map(|r| r.full_name.clone()).collect(),
+            fanout::DEFAULT_CONCURRENCY,
+            |name: String| {
+                let client = &client;
+                let has_issues = repositories
+                    .iter()
+                    .find(|r| r.full_name == name)
+                    .and_then(|r| r.has_issues)
+                    .unwrap_or(true);
+                async move { collect(client, &name, since, has_issues).await }
+            },
+        )
+        .await;
+
+        let mut prs = Vec::new();
+        let mut issues = Vec::new();
+        for (_, (repo_prs, repo_issues)) in outcome.succeeded.drain(..) {
+            prs.extend(repo_prs);
+            issues.extend(repo_issues);
+        }
+        let stats = compute(self.workspace.clone(), since, &prs, &issues);
+
+        if !output::print(&stats)? {
+            match output::format() {
+                ReportFormat::Csv => output::table(figures(&stats))?,
+                _ => print_table(&stats)?,
+            }
+        }
+        outcome.finish("collect activity from", "repositories")?;
+
+        Ok(())
+    }
+}
+
+/// A merged pull request and when someone other than its author first
+/// reviewed it
+struct MergedPr {
+    pr: PullRequest,
+    first_review: Option<DateTime<Utc>>,
+}
+
+/// Pull requests merged and issues updated in one repository since `since`
+async fn collect(
+    client: &BitbucketClient,
+    repository: &str,
+    since: DateTime<Utc>,
+    has_issues: bool,
+) -> Result<(Vec<MergedPr>, Vec<Issue>)> {
+    let (workspace, repo_slug) = repository.split_once('/').unwrap_or((repository, ""));
+    let filter = format!(
+        "updated_on >= {}",
+        since.to_rfc3339_opts(SecondsFormat::Secs, true)
+    );
+
+    let merged: Vec<PullRequest> = client
+        .search_pull_requests(workspace, repo_slug, PullRequestState::Merged, &filter)
+        .try_collect()
+        .await?;
+    let prs = futures::future::try_join_all(merged.into_iter().map(|pr| async move {
+        let first_review = first_review(client, workspace, repo_slug, &pr).await?;
+        Ok::<_, anyhow::Error>(MergedPr { pr, first_review })
+    }))
+    .await?;
+    let issues = if has_issues {
+        client
+            .search_issues(workspace, repo_slug, &filter)
+            .try_collect()
+            .await?
+    } else {
+        Vec::new()
+    };
+    Ok((prs, issues))
+}
+
+/// The earliest approval, request for changes or comment on `pr` by
+/// someone other than its author
+async fn first_review(
+    client: &BitbucketClient,
+    workspace: &str,
+    repo_slug: &str,
+    pr: &PullRequest,
+) -> Result<Option<DateTime<Utc>>> {
+    let activity: Vec<PullRequestActivity> = client
+        .stream_pr_activity(workspace, repo_slug, pr.id)
+        .try_collect()
+        .await?;
+    Ok(activity
+        .iter()
+        .filter_map(PullRequestActivity::review)
+        .filter(|(user, _)| user.uuid != pr.author.uuid)
+        .map(|(_, at)| at)
+        .min())
+}
+
+fn compute(workspace: String, since: DateTime<Utc>, prs: &[MergedPr], issues: &[Issue]) -> Stats {
+    let hours = |from: DateTime<Utc>, to: DateTime<Utc>| (to - from).num_seconds() as f64 / 3600.0;
+
+    let cycle_times: Vec<f64> = prs
+        .iter()
+        .map(|m| hours(m.pr.created_on, m.pr.updated_on))
+        .collect();
+    let review_latencies: Vec<f64> = prs
+        .iter()
+        .filter_map(|m| Some(hours(m.pr.created_on, m.first_review?).max(0.0)))
+        .collect();
+
+    let mut by_author: BTreeMap<&str, Vec<f64>> = BTreeMap::new();
+    for (MergedPr { pr, .. }, cycle_time) in prs.iter().zip(&cycle_times) {
+        by_author
+            .entry(pr.author.display_name.as_str())
+            .or_default()
+            .push(*cycle_time);
+    }
+    let mut authors: Vec<AuthorStats> = by_author
+        .into_iter()
+        .map(|(author, cycle_times)| AuthorStats {
+            author: author.to_string(),
+            merged: cycle_times.len(),
+            median_cycle_hours: summarize(&cycle_times).median_hours,
+        })
+        .collect();
+    authors.sort_by(|a, b| b.merged.cmp(&a.merged).then(a.author.cmp(&b.author)));
+
+    let closed = issues
+        .iter()
+        .filter(|issue| {
+            !matches!(
+                issue.state,
+                IssueState::New | IssueState::Open | IssueState::OnHold | IssueState::Unknown
+            ) && issue.updated_on.is_some_and(|at| at >= since)
+        })
+        .count();
+
+    Stats {
+        workspace,
+        since,
+        pull_requests: PullRequestStats {
+            merged: prs.len(),
+            cycle_time: summarize(&cycle_times),
+            review_latency: summarize(&review_latencies),
+        },
+        issues: IssueStats {
+            opened: issues.iter().filter(|i| i.created_on >= since).count(),
+            closed,
+        },
+        authors,
+    }
+}
+
+fn summarize(values: &[f64]) -> Summary {
+    if values.is_empty() {
+        return Summary::default();
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let middle = sorted.len() / 2;
+    let median = if sorted.len() % 2 == 0 {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+        sorted[middle]
+    };
+    Summary {
+        samples: sorted.len(),
+        median_hours: Some(median),
+        mean_hours: Some(sorted.iter().sum::<f64>() / sorted.len() as f64),
+    }
+}
+
+fn duration(hours: Option<f64>) -> String {
+    match hours {
+        Some(hours) => format_duration((hours * 3600.0).round() as u64),
+        None => "-".to_string(),
+    }
+}
+
+fn print_table(stats: &Stats) -> Result<()> {
+    if output::is_tty() {
+        println!(
+            "{} since {}\n",
+            stats.workspace.bold(),
+            format::date(&stats.since)
+        );
+    }
+
+    let prs = &stats.pull_requests;
+    output::table(vec![
+        MetricRow {
+            metric: "Merged pull requests",
+            value: prs.merged.to_string(),
+        },
+        MetricRow {
+            metric: "Cycle time (median)",
+            value: duration(prs.cycle_time.median_hours),
+        },
+        MetricRow {
+            metric: "Cycle time (mean)",
+            value: duration(prs.cycle_time.mean_hours),
+        },
+        MetricRow {
+            metric: "Review latency (median)",
+            value: duration(prs.review_latency.median_hours),
+        },
+        MetricRow {
+            metric: "Review latency (mean)",
+            value: duration(prs.review_latency.mean_hours),
+        },
+        MetricRow {
+            metric: "Issues opened",
+            value: stats.issues.opened.to_string(),
+        },
+        MetricRow {
+            metric: "Issues closed",
+            value: stats.issues.closed.to_string(),
+        },
+    ])?;
+
+    if !stats.authors.is_empty() {
+        println!();
+        output::table(
+            stats
+                .authors
+                .iter()
+                .map(|a| AuthorRow {
+                    author: a.author.clone(),
+                    merged: a.merged,
+                    cycle_time: duration(a.median_cycle_hours),
+                })
+                .collect(),
+        )?;
+    }
+    Ok(())
+}
+
+/// One row per figure, so the whole report fits a single sheet
+fn figures(stats: &Stats) -> Vec<FigureRow> {
+    let hours = |value: Option<f64>| value.map(|h| format!("{:.2}", h)).unwrap_or_default();
+    let prs = &stats.pull_requests;
+    let figure = |section: &'static str, name: &str, value: String| FigureRow {
+        section,
+        name: name.to_string(),
+        value,
+    };
+
+    let mut rows = vec![
+        figure("pull_requests", "merged", prs.merged.to_string()),
+        figure(
+            "pull_requests",
+            "cycle_time_median_hours",
+            hours(prs.cycle_time.median_hours),
+        ),
+        figure(
+            "pull_requests",
+            "cycle_time_mean_hours",
+            hours(prs.cycle_time.mean_hours),
+        ),
+        figure(
+            "pull_requests",
+            "review_latency_median_hours",
+            hours(prs.review_latency.median_hours),
+        ),
+        figure(
+            "pull_requests",
+            "review_latency_mean_hours",
+            hours(prs.review_latency.mean_hours),
+        ),
+        figure("issues", "opened", stats.issues.opened.to_string()),
+        figure("issues", "closed", stats.issues.closed.to_string()),
+    ];
+    rows.extend(
+        stats
+            .authors
+            .iter()
+            .map(|a| figure("merged_by", &a.author, a.merged.to_string())),
+    );
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn medians_handle_even_and_odd_counts() {
+        assert_eq!(summarize(&[3.0, 1.0, 2.0]).median_hours, Some(2.0));
+        assert_eq!(summarize(&[4.0, 1.0, 2.0, 3.0]).median_hours, Some(2.5));
+        assert_eq!(summarize(&[4.0, 1.0, 2.0, 3.0]).mean_hours, Some(2.5));
+        assert_eq!(summarize(&[]).median_hours, None);
+    }
+}
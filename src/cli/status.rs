@@ -0,0 +1,339 @@
+//! One-screen health summary for a workspace or repository
+//!
+//! Meant for cron jobs and MOTD scripts: it reads only, prints a compact
+//! summary, and `--output json` gives the same data in a stable shape.
+//! `--output csv` gives one row per repository.
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::Args;
+use colored::Colorize;
+use futures::TryStreamExt;
+use serde::Serialize;
+use tabled::Tabled;
+
+use super::output::{Porcelain, ReportFormat};
+use super::{UsageError, fanout, format, output};
+use crate::api::BitbucketClient;
+use crate::models::{
+    IssuePriority, ParticipantState, PipelineResultName, PullRequest, PullRequestState,
+};
+
+/// Recent pipelines scanned per repository for failing branches
+const PIPELINE_SCAN: u32 = 50;
+
+const BLOCKER_FILTER: &str = r#"priority = "blocker" AND (state = "new" OR state = "open")"#;
+
+#[derive(Args)]
+pub struct StatusArgs {
+    /// Workspace, or repository in format workspace/repo-slug
+    target: String,
+
+    /// For a workspace, how many of the most recently updated repositories to check
+    #[arg(long, default_value = "10", value_name = "N")]
+    repos: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct StatusReport {
+    target: String,
+    generated_on: DateTime<Utc>,
+    open_pull_requests: usize,
+    oldest_unreviewed: Option<UnreviewedPullRequest>,
+    failing_pipelines: Vec<FailingPipeline>,
+    blocker_issues: Vec<BlockerIssue>,
+    repositories: Vec<RepoStatus>,
+}
+
+#[derive(Debug, Serialize, Tabled)]
+struct RepoStatus {
+    #[tabled(rename = "REPOSITORY")]
+    repository: String,
+    #[tabled(rename = "OPEN PULL REQUESTS")]
+    open_pull_requests: usize,
+    #[tabled(rename = "UNREVIEWED PULL REQUESTS")]
+    unreviewed_pull_requests: usize,
+    #[tabled(rename = "FAILING PIPELINES")]
+    failing_pipelines: usize,
+    #[tabled(rename = "BLOCKER ISSUES")]
+    blocker_issues: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct UnreviewedPullRequest {
+    repository: String,
+    id: u64,
+    title: String,
+    author: String,
+    created_on: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+struct FailingPipeline {
+    repository: String,
+    build_number: u64,
+    ref_name: Option<String>,
+    result: String,
+    created_on: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+struct BlockerIssue {
+    repository: String,
+    id: u64,
+    title: String,
+}
+
+/// Everything gathered for one repository
+struct RepoSummary {
+    status: RepoStatus,
+    oldest_unreviewed: Option<UnreviewedPullRequest>,
+    failing_pipelines: Vec<FailingPipeline>,
+    blocker_issues: Vec<BlockerIssue>,
+}
+
+impl Porcelain for StatusReport {
+    /// Repositories that need attention, one per line
+    fn porcelain(&self) -> String {
+        self.repositories
+            .iter()
+            .filter(|r| r.failing_pipelines > 0 || r.blocker_issues > 0)
+            .map(|r| r.repository.clone())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl StatusArgs {
+    pub async fn run(self) -> Result<()> {
+        let client = BitbucketClient::from_stored().await?;
+
+        let repos = match self.target.split_once('/') {
+            Some((workspace, repo_slug)) if !workspace.is_empty() && !repo_slug.is_empty() => {
+                vec![format!("{}/{}", workspace, repo_slug)]
+            }
+            None if !self.target.is_empty() => client
+                .list_recently_updated_repositories(&self.target, self.repos.clamp(1, 100))
+                .await?
+                .values
+                .into_iter()
+                .map(|r| r.full_name)
+                .collect(),
+            _ => anyhow::bail!(UsageError(format!(
+                "Expected a workspace or 'workspace/repo-slug', got '{}'",
+                self.target
+            ))),
+        };
+
+        let mut outcome = fanout::run(
+            "Checking repositories",
+            repos,
+            fanout::DEFAULT_CONCURRENCY,
+            |repo| {
+                let client = &client;
+                async move {
+                    let (workspace, repo_slug) = repo.split_once('/').unwrap_or((&repo, ""));
+                    summarize(client, workspace, repo_slug).await
+                }
+            },
+        )
+        .await;
+
+        // Report on the repositories that could be checked, then the rest
+        if !outcome.succeeded.is_empty() || outcome.failed.is_empty() {
+            let summaries = outcome.succeeded.drain(..).map(|(_, summary)| summary);
+            let report = StatusReport::from_summaries(self.target.clone(), summaries);
+
+            if !output::print(&report)? {
+                match output::format() {
+                    ReportFormat::Csv => output::table(report.repositories.iter().collect())?,
+                    _ => print_text(&report),
+                }
+            }
+        }
+        outcome.finish("check", "repositories")?;
+
+        Ok(())
+    }
+}
+
+impl StatusReport {
+    fn from_summaries(target: String, summaries: impl IntoIterator<Item = RepoSummary>) -> Self {
+        let mut report = StatusReport {
+            target,
+            generated_on: Utc::now(),
+            open_pull_requests: 0,
+            oldest_unreviewed: None,
+            failing_pipelines: Vec::new(),
+            blocker_issues: Vec::new(),
+            repositories: Vec::new(),
+        };
+
+        for summary in summaries {
+            report.open_pull_requests += summary.status.open_pull_requests;
+            if let Some(pr) = summary.oldest_unreviewed {
+                let older = report
+                    .oldest_unreviewed
+                    .as_ref()
+                    .is_none_or(|oldest| pr.created_on < oldest.created_on);
+                if older {
+                    report.oldest_unreviewed = Some(pr);
+                }
+            }
+            report.failing_pipelines.extend(summary.failing_pipelines);
+            report.blocker_issues.extend(summary.blocker_issues);
+            report.repositories.push(summary.status);
+        }
+
+        report
+    }
+}
+
+async fn summarize(
+    client: &BitbucketClient,
+    workspace: &str,
+    repo_slug: &str,
+) -> Result<RepoSummary> {
+    let repository = format!("{}/{}", workspace, repo_slug);
+
+    let prs: Vec<PullRequest> = client
+        .stream_pull_requests_with_participants(workspace, repo_slug, PullRequestState::Open)
+        .try_collect()
+        .await?;
+    let unreviewed: Vec<&PullRequest> = prs.iter().filter(|pr| !is_reviewed(pr)).collect();
+    let oldest_unreviewed =
+        unreviewed
+            .iter()
+            .min_by_key(|pr| pr.created_on)
+            .map(|pr| UnreviewedPullRequest {
+                repository: repository.clone(),
+                id: pr.id,
+                title: pr.title.clone(),
+                author: pr.author.display_name.clone(),
+                created_on: pr.created_on,
+            });
+
+    // Only the latest completed run on each branch counts: a branch that
+    // failed and was fixed since is not failing
+    let pipelines = client
+        .list_pipelines(workspace, repo_slug, None, None, Some(PIPELINE_SCAN))
+        .await?
+        .values;
+    let mut seen = HashSet::new();
+    let failing_pipelines: Vec<FailingPipeline> = pipelines
+        .into_iter()
+        .filter_map(|p| {
+            let result = p.state.result.as_ref()?.name.clone();
+            if !seen.insert(p.target.ref_name.clone()) {
+                return None;
+            }
+            matches!(
+                result,
+                PipelineResultName::Failed | PipelineResultName::Error
+            )
+            .then(|| FailingPipeline {
+                repository: repository.clone(),
+                build_number: p.build_number,
+                ref_name: p.target.ref_name.clone(),
+                result: result.to_string(),
+                created_on: p.created_on,
+            })
+        })
+        .collect();
+
+    // Repositories without an issue tracker have no blockers
+    let blocker_issues: Vec<BlockerIssue> = match client
+        .search_issues(workspace, repo_slug, BLOCKER_FILTER)
+        .try_collect::<Vec<_>>()
+        .await
+    {
+        Ok(issues) => issues
+            .into_iter()
+            .filter(|issue| issue.priority == IssuePriority::Blocker)
+            .map(|issue| BlockerIssue {
+                repository: repository.clone(),
+                id: issue.id,
+                title: issue.title,
+            })
+            .collect(),
+        Err(crate::Error::NotFound(_)) => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(RepoSummary {
+        status: RepoStatus {
+            repository,
+            open_pull_requests: prs.len(),
+            unreviewed_pull_requests: unreviewed.len(),
+            failing_pipelines: failing_pipelines.len(),
+            blocker_issues: blocker_issues.len(),
+        },
+        oldest_unreviewed,
+        failing_pipelines,
+        blocker_issues,
+    })
+}
+
+/// Whether any participant has approved or requested changes
+fn is_reviewed(pr: &PullRequest) -> bool {
+    pr.participants
+        .iter()
+        .flatten()
+        .any(|p| p.approved || matches!(p.state, Some(ParticipantState::ChangesRequested)))
+}
+
+fn print_text(report: &StatusReport) {
+    let now = Utc::now();
+
+    println!("{} {}", "Status for".bold(), report.target.bold());
+    print!("{}", output::rule(40));
+
+    println!("Open pull requests:  {}", report.open_pull_requests);
+    match &report.oldest_unreviewed {
+        Some(pr) => println!(
+            "Oldest un-reviewed:  {}#{} {} ({}, opened {})",
+            pr.repository,
+            pr.id,
+            pr.title,
+            pr.author,
+            format::relative(&pr.created_on, &now)
+        ),
+        None => println!("Oldest un-reviewed:  none"),
+    }
+
+    let failing = report.failing_pipelines.len();
+    let label = format!("{}", failing);
+    println!(
+        "Failing pipelines:   {}",
+        if failing > 0 {
+            label.red()
+        } else {
+            label.green()
+        }
+    );
+    for p in &report.failing_pipelines {
+        println!(
+            "  {} #{} on {} ({})",
+            p.repository,
+            p.build_number,
+            p.ref_name.as_deref().unwrap_or("-"),
+            p.result.to_lowercase()
+        );
+    }
+
+    let blockers = report.blocker_issues.len();
+    let label = format!("{}", blockers);
+    println!(
+        "Blocker issues:      {}",
+        if blockers > 0 {
+            label.red()
+        } else {
+            label.green()
+        }
+    );
+    for issue in &report.blocker_issues {
+        println!("  {}#{} {}", issue.repository, issue.id, issue.title);
+    }
+}
@@ -0,0 +1,155 @@
+//! Cutting a step's log down to the part worth reading, for `pipeline logs`
+//!
+//! The log is downloaded once and filtered locally: by a pattern with some
+//! context, its first or last lines, or the lines around the first thing
+//! that looks like a failure.
+
+use regex::Regex;
+
+/// Lines `--errors` shows on each side of the first failure
+pub const ERROR_CONTEXT: usize = 10;
+
+/// What looks like a build failing, matched case-insensitively
+const FAILURE_MARKERS: &str = r"(?i)\berror\b|\bfailed\b|\bfailure\b|panicked at|\bexception\b|fatal:|command not found|exit(ed)? (with )?(code|status) [1-9]";
+/// Summaries that mention failing without anything having failed
+const NOT_FAILURES: &str = r"(?i)\b0 (failed|failures|errors?)\b";
+
+/// A line kept by a filter, numbered from 1
+#[derive(Debug, PartialEq, Eq)]
+pub struct Kept<'a> {
+    pub number: usize,
+    pub text: &'a str,
+    /// Whether the line matched, rather than being kept as context
+    pub matched: bool,
+}
+
+/// Lines matching `pattern`, with `context` lines on each side; `None`
+/// between kept lines marks a gap
+pub fn grep<'a>(log: &'a str, pattern: &Regex, context: usize) -> Vec<Option<Kept<'a>>> {
+    let lines: Vec<&str> = log.lines().collect();
+    let matches: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| pattern.is_match(line))
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut kept = Vec::new();
+    let mut next = 0;
+    for (n, &i) in matches.iter().enumerate() {
+        let start = i.saturating_sub(context).max(next);
+        let end = (i + context + 1).min(lines.len());
+        if start > next && n > 0 {
+            kept.push(None);
+        }
+        for (j, text) in lines.iter().enumerate().take(end).skip(start) {
+            kept.push(Some(Kept {
+                number: j + 1,
+                text,
+                matched: matches.binary_search(&j).is_ok(),
+            }));
+        }
+        next = end;
+    }
+    kept
+}
+
+/// The lines around the first failure marker, or `None` when nothing in
+/// the log looks like one
+pub fn first_failure(log: &str) -> Option<Vec<Kept<'_>>> {
+    let markers = Regex::new(FAILURE_MARKERS).expect("failure markers are a valid pattern");
+    let not_failures = Regex::new(NOT_FAILURES).expect("non-failures are a valid pattern");
+    let lines: Vec<&str> = log.lines().collect();
+    let at = lines
+        .iter()
+        .position(|line| markers.is_match(line) && !not_failures.is_match(line))?;
+    let start = at.saturating_sub(ERROR_CONTEXT);
+    let end = (at + ERROR_CONTEXT + 1).min(lines.len());
+    Some(
+        (start..end)
+            .map(|i| Kept {
+                number: i + 1,
+                text: lines[i],
+                matched: i == at,
+            })
+            .collect(),
+    )
+}
+
+/// The first `count` lines
+pub fn head(log: &str, count: usize) -> Vec<Kept<'_>> {
+    log.lines()
+        .take(count)
+        .enumerate()
+        .map(|(i, text)| Kept {
+            number: i + 1,
+            text,
+            matched: false,
+        })
+        .collect()
+}
+
+/// The last `count` lines
+pub fn tail(log: &str, count: usize) -> Vec<Kept<'_>> {
+    let lines: Vec<&str> = log.lines().collect();
+    let start = lines.len().saturating_sub(count);
+    lines[start..]
+        .iter()
+        .enumerate()
+        .map(|(i, text)| Kept {
+            number: start + i + 1,
+            text,
+            matched: false,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grep_keeps_context_and_marks_gaps() {
+        let log = "a\nerror one\nb\nc\nd\ne\nerror two\nf\n";
+        let kept = grep(log, &Regex::new("error").unwrap(), 1);
+        let numbers: Vec<Option<(usize, bool)>> = kept
+            .iter()
+            .map(|k| k.as_ref().map(|k| (k.number, k.matched)))
+            .collect();
+        assert_eq!(
+            numbers,
+            [
+                Some((1, false)),
+                Some((2, true)),
+                Some((3, false)),
+                None,
+                Some((6, false)),
+                Some((7, true)),
+                Some((8, false)),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_first_failure_is_found_with_its_surroundings() {
+        let mut log: Vec<String> = (1..=30).map(|i| format!("step {}", i)).collect();
+        log[24] = "thread 'main' panicked at src/mill.rs:12".to_string();
+        log[27] = "error: could not compile".to_string();
+        let log = log.join("\n");
+        let kept = first_failure(&log).unwrap();
+        assert_eq!(kept.first().unwrap().number, 15);
+        assert_eq!(kept.last().unwrap().number, 30);
+        assert_eq!(kept.iter().find(|k| k.matched).unwrap().number, 25);
+
+        assert!(first_failure("all good\ntest result: ok. 3 passed; 0 failed\n").is_none());
+    }
+
+    #[test]
+    fn head_and_tail_keep_line_numbers() {
+        let log = "a\nb\nc\nd\n";
+        assert_eq!(head(log, 2).last().unwrap().number, 2);
+        let last = tail(log, 2);
+        assert_eq!((last[0].number, last[0].text), (3, "c"));
+        assert_eq!(tail(log, 10).len(), 4);
+    }
+}
@@ -0,0 +1,386 @@
+//! Interactive issue triage (`bitbucket issue triage`)
+//!
+//! Steps through new issues oldest first, one key per action. Kind,
+//! priority, assignee and comments are applied as soon as they're chosen;
+//! accepting or closing an issue moves on to the next.
+
+use std::io::IsTerminal;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use dialoguer::{Input, Select};
+use futures::TryStreamExt;
+
+use super::cache::Metadata;
+use super::issue::print_issue;
+use super::{UsageError, output};
+use crate::api::BitbucketClient;
+use crate::models::{
+    Issue, IssueKind, IssuePriority, IssueState, UpdateIssueRequest, User, UserAccountId,
+};
+
+const KINDS: [IssueKind; 4] = [
+    IssueKind::Bug,
+    IssueKind::Enhancement,
+    IssueKind::Proposal,
+    IssueKind::Task,
+];
+
+const PRIORITIES: [IssuePriority; 5] = [
+    IssuePriority::Trivial,
+    IssuePriority::Minor,
+    IssuePriority::Major,
+    IssuePriority::Critical,
+    IssuePriority::Blocker,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Kind,
+    Priority,
+    Assign,
+    Comment,
+    Accept,
+    Duplicate,
+    Wontfix,
+    Skip,
+    Quit,
+}
+
+impl Action {
+    fn from_key(key: KeyCode) -> Option<Self> {
+        match key {
+            KeyCode::Char('k') => Some(Action::Kind),
+            KeyCode::Char('p') => Some(Action::Priority),
+            KeyCode::Char('a') => Some(Action::Assign),
+            KeyCode::Char('c') => Some(Action::Comment),
+            KeyCode::Char('o') => Some(Action::Accept),
+            KeyCode::Char('d') => Some(Action::Duplicate),
+            KeyCode::Char('w') => Some(Action::Wontfix),
+            KeyCode::Char('n') | KeyCode::Char(' ') | KeyCode::Enter => Some(Action::Skip),
+            KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Tally {
+    accepted: usize,
+    closed: usize,
+    skipped: usize,
+}
+
+/// Triage the new issues in a repository
+pub async fn run(workspace: &str, repo_slug: &str) -> Result<()> {
+    if !std::io::stdin().is_terminal() || !output::is_tty() {
+        anyhow::bail!(UsageError(
+            "issue triage needs an interactive terminal".to_string()
+        ));
+    }
+
+    let client = BitbucketClient::from_stored().await?;
+    let mut issues: Vec<Issue> = client
+        .stream_issues(workspace, repo_slug, Some(IssueState::New), None)
+        .try_collect()
+        .await?;
+    if issues.is_empty() {
+        output::note("No new issues to triage");
+        return Ok(());
+    }
+    issues.sort_by_key(|issue| issue.created_on);
+
+    // Loaded on the first assignment from the metadata cache or the API
+    let mut members: Option<Vec<User>> = None;
+    let mut tally = Tally::default();
+    let total = issues.len();
+
+    'issues: for (index, mut issue) in issues.into_iter().enumerate() {
+        println!();
+        println!("{}", format!("[{}/{}]", index + 1, total).dimmed());
+        print_issue(&issue, false)?;
+
+        loop {
+            println!();
+            println!(
+                "{}",
+                "[k]ind [p]riority [a]ssign [c]omment [o]pen [d]uplicate [w]ontfix [n]ext [q]uit"
+                    .dimmed()
+            );
+            let Some(action) = read_action()? else {
+                continue;
+            };
+
+            match action {
+                Action::Kind => {
+                    let current = KINDS.iter().position(|k| *k == issue.kind).unwrap_or(0);
+                    if let Some(choice) = choose("Kind", &KINDS, current)? {
+                        let request = UpdateIssueRequest {
+                            kind: Some(KINDS[choice].clone()),
+                            ..Default::default()
+                        };
+                        issue = edit(&client, workspace, repo_slug, &issue, &request).await?;
+                        output::success(format!("Kind set to {}", issue.kind));
+                    }
+                }
+                Action::Priority => {
+                    let current = PRIORITIES
+                        .iter()
+                        .position(|p| *p == issue.priority)
+                        .unwrap_or(1);
+                    if let Some(choice) = choose("Priority", &PRIORITIES, current)? {
+                        let request = UpdateIssueRequest {
+                            priority: Some(PRIORITIES[choice].clone()),
+                            ..Default::default()
+                        };
+                        issue = edit(&client, workspace, repo_slug, &issue, &request).await?;
+                        output::success(format!("Priority set to {}", issue.priority));
+                    }
+                }
+                Action::Assign => {
+                    if members.is_none() {
+                        let cached = Metadata::current()
+                            .members_of(workspace)
+                            .map(<[User]>::to_vec);
+                        members = Some(match cached {
+                            Some(cached) => cached,
+                            None => {
+                                client
+                                    .stream_workspace_members(workspace)
+                                    .map_ok(|m| m.user)
+                                    .try_collect()
+                                    .await?
+                            }
+                        });
+                    }
+                    let Some(user) = pick_member(members.as_deref().unwrap_or_default())? else {
+                        continue;
+                    };
+                    let Some(account_id) = user.account_id.clone() else {
+                        output::note(format!("{} has no account ID to assign", user.display_name));
+                        continue;
+                    };
+                    let request = UpdateIssueRequest {
+                        assignee: Some(UserAccountId { account_id }),
+                        ..Default::default()
+                    };
+                    issue = edit(&client, workspace, repo_slug, &issue, &request).await?;
+                    output::success(format!("Assigned to {}", user.display_name));
+                }
+                Action::Comment => {
+                    let body = prompt("Comment")?;
+                    if !body.is_empty() {
+                        client
+                            .add_issue_comment(workspace, repo_slug, issue.id, &body)
+                            .await?;
+                        output::success("Comment added");
+                    }
+                }
+                Action::Accept => {
+                    set_state(&client, workspace, repo_slug, &issue, IssueState::Open).await?;
+                    output::success(format!("Opened issue #{}", issue.id));
+                    tally.accepted += 1;
+                    continue 'issues;
+                }
+                Action::Duplicate => {
+                    let original = prompt("Duplicate of issue # (optional)")?;
+                    let original = original.trim_start_matches('#');
+                    if !original.is_empty() {
+                        let Ok(original) = original.parse::<u64>() else {
+                            output::note(format!("'{}' is not an issue number", original));
+                            continue;
+                        };
+                        client
+                            .add_issue_comment(
+                                workspace,
+                                repo_slug,
+                                issue.id,
+                                &format!("Duplicate of #{}", original),
+                            )
+                            .await?;
+                    }
+                    set_state(&client, workspace, repo_slug, &issue, IssueState::Duplicate).await?;
+                    output::success(format!("Closed issue #{} as a duplicate", issue.id));
+                    tally.closed += 1;
+                    continue 'issues;
+                }
+                Action::Wontfix => {
+                    let reason = prompt("Reason (optional)")?;
+                    if !reason.is_empty() {
+                        client
+                            .add_issue_comment(workspace, repo_slug, issue.id, &reason)
+                            .await?;
+                    }
+                    set_state(&client, workspace, repo_slug, &issue, IssueState::Wontfix).await?;
+                    output::success(format!("Closed issue #{} as won't fix", issue.id));
+                    tally.closed += 1;
+                    continue 'issues;
+                }
+                Action::Skip => {
+                    tally.skipped += 1;
+                    continue 'issues;
+                }
+                Action::Quit => break 'issues,
+            }
+        }
+    }
+
+    println!();
+    output::success(format!(
+        "{} opened, {} closed, {} skipped",
+        tally.accepted, tally.closed, tally.skipped
+    ));
+    Ok(())
+}
+
+/// Wait for a key press, `None` for keys with no action
+fn read_action() -> Result<Option<Action>> {
+    terminal::enable_raw_mode().context("Failed to read the keyboard")?;
+    let key = loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => break Ok(key),
+            Ok(_) => continue,
+            Err(e) => break Err(e),
+        }
+    };
+    terminal::disable_raw_mode().context("Failed to restore the terminal")?;
+    let key = key.context("Failed to read the keyboard")?;
+
+    // Raw mode swallows Ctrl-C, so treat it as quitting
+    if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+        return Ok(Some(Action::Quit));
+    }
+    let code = match key.code {
+        KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+        code => code,
+    };
+    Ok(Action::from_key(code))
+}
+
+fn choose<T: ToString>(prompt: &str, items: &[T], current: usize) -> Result<Option<usize>> {
+    let items: Vec<String> = items.iter().map(T::to_string).collect();
+    Select::new()
+        .with_prompt(prompt)
+        .items(&items)
+        .default(current)
+        .interact_opt()
+        .with_context(|| format!("Failed to read {}", prompt.to_lowercase()))
+}
+
+fn prompt(prompt: &str) -> Result<String> {
+    let value: String = Input::new()
+        .with_prompt(prompt)
+        .allow_empty(true)
+        .interact_text()
+        .with_context(|| format!("Failed to read {}", prompt.to_lowercase()))?;
+    Ok(value.trim().to_string())
+}
+
+/// Ask for a name and pick the workspace member it matches
+fn pick_member(members: &[User]) -> Result<Option<User>> {
+    let query = prompt("Assign to (name)")?;
+    if query.is_empty() {
+        return Ok(None);
+    }
+    let found = matching(members, &query);
+    match found.as_slice() {
+        [] => {
+            output::note(format!("No workspace member matches '{}'", query));
+            Ok(None)
+        }
+        [user] => Ok(Some((*user).clone())),
+        _ => {
+            let names: Vec<&str> = found.iter().map(|u| u.display_name.as_str()).collect();
+            let choice = choose("Which one", &names, 0)?;
+            Ok(choice.map(|i| found[i].clone()))
+        }
+    }
+}
+
+/// Members whose display name or nickname contains `query`, ignoring case
+fn matching<'a>(members: &'a [User], query: &str) -> Vec<&'a User> {
+    let query = query.to_lowercase();
+    members
+        .iter()
+        .filter(|user| {
+            user.display_name.to_lowercase().contains(&query)
+                || user
+                    .nickname
+                    .as_ref()
+                    .is_some_and(|n| n.to_lowercase().contains(&query))
+        })
+        .collect()
+}
+
+async fn edit(
+    client: &BitbucketClient,
+    workspace: &str,
+    repo_slug: &str,
+    issue: &Issue,
+    request: &UpdateIssueRequest,
+) -> Result<Issue> {
+    client
+        .edit_issue(workspace, repo_slug, issue.id, request)
+        .await
+        .with_context(|| format!("Failed to update issue #{}", issue.id))
+}
+
+async fn set_state(
+    client: &BitbucketClient,
+    workspace: &str,
+    repo_slug: &str,
+    issue: &Issue,
+    state: IssueState,
+) -> Result<Issue> {
+    let request = UpdateIssueRequest {
+        state: Some(state),
+        ..Default::default()
+    };
+    edit(client, workspace, repo_slug, issue, &request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(display_name: &str, nickname: &str) -> User {
+        serde_json::from_value(serde_json::json!({
+            "uuid": format!("{{{}}}", nickname),
+            "display_name": display_name,
+            "nickname": nickname,
+            "type": "user",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn members_match_by_name_or_nickname() {
+        let members = [
+            user("Ada Lovelace", "ada"),
+            user("Charles Babbage", "cbabbage"),
+        ];
+        let names = |query| -> Vec<String> {
+            matching(&members, query)
+                .iter()
+                .map(|u| u.display_name.clone())
+                .collect()
+        };
+        assert_eq!(names("LOVE"), ["Ada Lovelace"]);
+        assert_eq!(names("cbab"), ["Charles Babbage"]);
+        assert_eq!(names("a").len(), 2);
+        assert!(names("grace").is_empty());
+    }
+
+    #[test]
+    fn keys_map_to_actions() {
+        assert_eq!(
+            Action::from_key(KeyCode::Char('d')),
+            Some(Action::Duplicate)
+        );
+        assert_eq!(Action::from_key(KeyCode::Enter), Some(Action::Skip));
+        assert_eq!(Action::from_key(KeyCode::Esc), Some(Action::Quit));
+        assert_eq!(Action::from_key(KeyCode::Char('x')), None);
+    }
+}
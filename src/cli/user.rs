@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use colored::Colorize;
+use tokio::sync::OnceCell;
+
+use super::output;
+use crate::api::BitbucketClient;
+use crate::models::User;
+
+/// Stands for the authenticated user wherever a user is accepted
+pub const ME: &str = "@me";
+
+/// The authenticated user, fetched once per run
+static CURRENT: OnceCell<User> = OnceCell::const_new();
+
+/// Users already looked up this run, by how they were given
+static RESOLVED: Mutex<Option<HashMap<String, User>>> = Mutex::new(None);
+
+#[derive(Subcommand)]
+pub enum UserCommands {
+    /// View a user's profile (default: the authenticated user)
+    View {
+        /// Account ID or UUID of the user
+        account: Option<String>,
+
+        /// Open in browser
+        #[arg(long)]
+        web: bool,
+    },
+}
+
+impl UserCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            UserCommands::View { account, web } => {
+                let client = BitbucketClient::from_stored().await?;
+                let user = resolve(&client, account.as_deref().unwrap_or(ME)).await?;
+
+                if web {
+                    if let Some(html) = user.links.as_ref().and_then(|l| l.html.as_ref()) {
+                        open::that(&html.href)?;
+                        println!("Opened {} in browser", html.href.cyan());
+                        return Ok(());
+                    }
+                    anyhow::bail!("Could not find profile URL");
+                }
+
+                if output::print(&user)? {
+                    return Ok(());
+                }
+
+                print_user(&user);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn print_user(user: &User) {
+    println!("{}", user.display_name.bold());
+    print!("{}", output::rule(50));
+
+    if let Some(nickname) = user.nickname.as_ref().or(user.username.as_ref()) {
+        println!("{} {}", "Nickname:".dimmed(), nickname);
+    }
+    if let Some(account_id) = &user.account_id {
+        println!("{} {}", "Account ID:".dimmed(), account_id);
+    }
+    println!("{} {}", "UUID:".dimmed(), user.uuid);
+
+    if let Some(html) = user.links.as_ref().and_then(|l| l.html.as_ref()) {
+        println!();
+        println!("{} {}", "Web:".dimmed(), html.href.cyan());
+    }
+}
+
+/// The authenticated user, asking `/user` only the first time
+pub async fn me(client: &BitbucketClient) -> Result<User> {
+    let user = CURRENT
+        .get_or_try_init(|| client.get_current_user())
+        .await
+        .context("Failed to look up the authenticated user")?;
+    Ok(user.clone())
+}
+
+/// Look up a user given as `@me`, an account ID or a UUID
+pub async fn resolve(client: &BitbucketClient, user: &str) -> Result<User> {
+    if user == ME {
+        return me(client).await;
+    }
+    let cached = RESOLVED
+        .lock()
+        .expect("user cache poisoned")
+        .as_ref()
+        .and_then(|users| users.get(user).cloned());
+    if let Some(found) = cached {
+        return Ok(found);
+    }
+
+    let found = client
+        .get_user(&selected_user(user))
+        .await
+        .with_context(|| format!("Failed to look up user '{}'", user))?;
+    RESOLVED
+        .lock()
+        .expect("user cache poisoned")
+        .get_or_insert_with(HashMap::new)
+        .insert(user.to_string(), found.clone());
+    Ok(found)
+}
+
+/// A query language condition matching `user` in `field`, e.g. `author`
+pub fn filter(field: &str, user: &User) -> String {
+    format!("{}.uuid = \"{}\"", field, user.uuid)
+}
+
+/// The API wants UUIDs in braces; accept them bare too
+fn selected_user(account: &str) -> String {
+    let is_bare_uuid = account.len() == 36 && account.chars().filter(|&c| c == '-').count() == 4;
+    if is_bare_uuid {
+        format!("{{{}}}", account)
+    } else {
+        account.to_string()
+    }
+}
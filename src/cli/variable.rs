@@ -0,0 +1,354 @@
+//! Pipelines variables across a workspace and its repositories
+//!
+//! Repository variables override workspace variables with the same key, so
+//! `variable list` shows both levels together with which value a pipeline
+//! actually sees. Secured values are never returned by the API and show
+//! masked.
+
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use colored::Colorize;
+use serde::Serialize;
+use tabled::Tabled;
+
+use super::git::parse_repo;
+use super::icons::Icon;
+use super::output::Porcelain;
+use super::{UsageError, fanout, git, output};
+use crate::api::BitbucketClient;
+use crate::config::Config;
+use crate::models::{PipelineVariable, PipelineVariableRequest};
+
+const MASK: &str = "••••••••";
+
+#[derive(Subcommand)]
+pub enum VariableCommands {
+    /// List workspace variables, merged with a repository's own when
+    /// --repo is given or inferred from the current checkout
+    List,
+
+    /// Copy a repository's variables to other repositories
+    Copy {
+        /// Repository to copy from, in format workspace/repo-slug
+        source: String,
+
+        /// Repositories to copy to, in format workspace/repo-slug
+        #[arg(required = true)]
+        targets: Vec<String>,
+
+        /// Only these keys, comma-separated
+        #[arg(long, value_delimiter = ',')]
+        keys: Vec<String>,
+
+        /// Replace variables that already exist in a target
+        #[arg(long)]
+        overwrite: bool,
+    },
+}
+
+/// Where a variable is defined
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+enum Scope {
+    Repository,
+    Workspace,
+}
+
+/// A variable with the level it comes from
+#[derive(Debug, Serialize)]
+struct ScopedVariable {
+    key: String,
+    value: Option<String>,
+    secured: bool,
+    scope: Scope,
+    /// Hidden from pipelines by a repository variable with the same key
+    overridden: bool,
+}
+
+impl Porcelain for ScopedVariable {
+    fn porcelain(&self) -> String {
+        self.key.clone()
+    }
+}
+
+#[derive(Tabled)]
+struct VariableRow {
+    #[tabled(rename = "KEY")]
+    key: String,
+    #[tabled(rename = "VALUE")]
+    value: String,
+    #[tabled(rename = "SCOPE")]
+    scope: String,
+    #[tabled(rename = "STATUS")]
+    status: String,
+}
+
+impl From<&ScopedVariable> for VariableRow {
+    fn from(variable: &ScopedVariable) -> Self {
+        Self {
+            key: variable.key.clone(),
+            value: if variable.secured {
+                MASK.to_string()
+            } else {
+                variable.value.clone().unwrap_or_default()
+            },
+            scope: match variable.scope {
+                Scope::Repository => "repository".to_string(),
+                Scope::Workspace => "workspace".to_string(),
+            },
+            status: if variable.overridden {
+                "overridden".dimmed().to_string()
+            } else {
+                "active".green().to_string()
+            },
+        }
+    }
+}
+
+/// What copying did to one target
+#[derive(Default)]
+struct CopySummary {
+    created: usize,
+    updated: usize,
+    kept: usize,
+}
+
+impl VariableCommands {
+    /// `workspace` and `repo` are the global `--workspace` and `--repo`
+    pub async fn run(self, workspace: Option<String>, repo: Option<String>) -> Result<()> {
+        match self {
+            VariableCommands::List => {
+                let (workspace, repo_slug) = scope(workspace, repo)?;
+                let client = BitbucketClient::from_stored().await?;
+
+                let workspace_variables = client.list_workspace_variables(&workspace).await?;
+                let repository_variables = match &repo_slug {
+                    Some(slug) => client.list_repository_variables(&workspace, slug).await?,
+                    None => Vec::new(),
+                };
+                let variables = merge(workspace_variables, repository_variables);
+
+                if output::print(&variables)? {
+                    return Ok(());
+                }
+
+                if variables.is_empty() {
+                    output::note("No variables found");
+                    return Ok(());
+                }
+
+                output::table(variables.iter().map(VariableRow::from).collect())?;
+
+                Ok(())
+            }
+
+            VariableCommands::Copy {
+                source,
+                targets,
+                keys,
+                overwrite,
+            } => {
+                let (workspace, repo_slug) = parse_repo(&source)?;
+                for target in &targets {
+                    parse_repo(target)?;
+                }
+                let client = BitbucketClient::from_stored().await?;
+
+                let (secured, variables): (Vec<_>, Vec<_>) = client
+                    .list_repository_variables(&workspace, &repo_slug)
+                    .await?
+                    .into_iter()
+                    .filter(|v| keys.is_empty() || keys.contains(&v.key))
+                    .partition(|v| v.secured);
+
+                if !secured.is_empty() {
+                    let secured: Vec<&str> = secured.iter().map(|v| v.key.as_str()).collect();
+                    output::note(format!(
+                        "{} Skipping secured {}, whose values can't be read: {}",
+                        Icon::Warning.glyph().yellow(),
+                        if secured.len() == 1 {
+                            "variable"
+                        } else {
+                            "variables"
+                        },
+                        secured.join(", ")
+                    ));
+                }
+                if variables.is_empty() {
+                    output::note("No variables to copy");
+                    return Ok(());
+                }
+
+                let outcome = fanout::run(
+                    "Copying variables",
+                    targets,
+                    fanout::DEFAULT_CONCURRENCY,
+                    |target| {
+                        let client = &client;
+                        let variables = &variables;
+                        async move { copy_to(client, &target, variables, overwrite).await }
+                    },
+                )
+                .await;
+
+                for (target, summary) in &outcome.succeeded {
+                    let mut message = format!(
+                        "Copied to {}: {} created, {} updated",
+                        target.cyan(),
+                        summary.created,
+                        summary.updated
+                    );
+                    if summary.kept > 0 {
+                        message.push_str(&format!(
+                            ", {} already set (pass --overwrite to replace)",
+                            summary.kept
+                        ));
+                    }
+                    output::success(message);
+                }
+                outcome.finish("copy variables to", "repositories")?;
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Combine both levels, sorted by key with the repository's first, marking
+/// workspace variables a repository variable overrides
+fn merge(
+    workspace_variables: Vec<PipelineVariable>,
+    repository_variables: Vec<PipelineVariable>,
+) -> Vec<ScopedVariable> {
+    let scoped = |variable: PipelineVariable, scope: Scope, overridden: bool| ScopedVariable {
+        key: variable.key,
+        value: variable.value,
+        secured: variable.secured,
+        scope,
+        overridden,
+    };
+
+    let mut merged: Vec<ScopedVariable> = workspace_variables
+        .into_iter()
+        .map(|v| {
+            let overridden = repository_variables.iter().any(|r| r.key == v.key);
+            scoped(v, Scope::Workspace, overridden)
+        })
+        .collect();
+    merged.extend(
+        repository_variables
+            .into_iter()
+            .map(|v| scoped(v, Scope::Repository, false)),
+    );
+    merged.sort_by(|a, b| a.key.cmp(&b.key).then(a.scope.cmp(&b.scope)));
+    merged
+}
+
+/// Create or update `variables` in `target`
+async fn copy_to(
+    client: &BitbucketClient,
+    target: &str,
+    variables: &[PipelineVariable],
+    overwrite: bool,
+) -> Result<CopySummary> {
+    let (workspace, repo_slug) = parse_repo(target)?;
+    let existing = client
+        .list_repository_variables(&workspace, &repo_slug)
+        .await?;
+
+    let mut summary = CopySummary::default();
+    for variable in variables {
+        let request = PipelineVariableRequest {
+            key: variable.key.clone(),
+            value: variable.value.clone().unwrap_or_default(),
+            secured: variable.secured,
+        };
+        match existing.iter().find(|e| e.key == variable.key) {
+            Some(current) if overwrite => {
+                let uuid = current
+                    .uuid
+                    .as_deref()
+                    .filter(|uuid| !uuid.is_empty())
+                    .with_context(|| {
+                        format!("{} in {} has no UUID to update it by", current.key, target)
+                    })?;
+                client
+                    .update_repository_variable(&workspace, &repo_slug, uuid, &request)
+                    .await?;
+                summary.updated += 1;
+            }
+            Some(_) => summary.kept += 1,
+            None => {
+                client
+                    .create_repository_variable(&workspace, &repo_slug, &request)
+                    .await?;
+                summary.created += 1;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// Resolve which workspace, and optionally repository, to list: `--repo`
+/// as workspace/slug or a slug within `--workspace`, else `--workspace`
+/// alone, else the current checkout
+fn scope(workspace: Option<String>, repo: Option<String>) -> Result<(String, Option<String>)> {
+    match (workspace, repo) {
+        (workspace, Some(repo)) => match repo.split_once('/') {
+            Some(_) => {
+                let (repo_workspace, slug) = parse_repo(&repo)?;
+                if workspace.is_some_and(|w| w != repo_workspace) {
+                    anyhow::bail!(UsageError(format!("--repo {} is not in --workspace", repo)));
+                }
+                Ok((repo_workspace, Some(slug)))
+            }
+            None => {
+                let workspace = workspace
+                    .or_else(|| Config::load().ok().and_then(|c| c.defaults.workspace))
+                    .ok_or_else(|| {
+                        UsageError("Pass --workspace, or --repo as workspace/repo-slug".into())
+                    })?;
+                Ok((workspace, Some(repo)))
+            }
+        },
+        (Some(workspace), None) => Ok((workspace, None)),
+        (None, None) => {
+            let (workspace, slug) = git::repo_or_origin(None)?;
+            Ok((workspace, Some(slug)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variable(key: &str, value: Option<&str>) -> PipelineVariable {
+        PipelineVariable {
+            uuid: None,
+            key: key.to_string(),
+            value: value.map(str::to_string),
+            secured: value.is_none(),
+        }
+    }
+
+    #[test]
+    fn repository_variables_override_workspace_ones() {
+        let merged = merge(
+            vec![variable("REGION", Some("eu")), variable("TOKEN", None)],
+            vec![variable("REGION", Some("us"))],
+        );
+        let summary: Vec<(&str, Scope, bool)> = merged
+            .iter()
+            .map(|v| (v.key.as_str(), v.scope, v.overridden))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("REGION", Scope::Repository, false),
+                ("REGION", Scope::Workspace, true),
+                ("TOKEN", Scope::Workspace, false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,568 @@
+//! Local development against repository webhooks
+//!
+//! `webhook forward` listens on a local port, registers a temporary webhook
+//! pointing at a public relay for that port (a tunnel such as ngrok or
+//! cloudflared), prints each delivery and passes it on to a local server.
+//! The webhook is removed again when the command exits.
+//!
+//! Event names are checked against Bitbucket's catalog, which `webhook
+//! events` lists, so a typo fails instead of subscribing to nothing.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use clap::Subcommand;
+use colored::Colorize;
+use futures::TryStreamExt;
+use serde_json::Value;
+use tabled::Tabled;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use super::icons::Icon;
+use super::output::Porcelain;
+use super::{UsageError, git, interrupt, output};
+use crate::api::BitbucketClient;
+use crate::models::{CreateWebhookRequest, HookEvent, WEBHOOK_EVENTS};
+
+/// Delivery headers passed on to the local server
+const FORWARDED_HEADERS: &[&str] = &[
+    "content-type",
+    "user-agent",
+    "x-event-key",
+    "x-hook-uuid",
+    "x-request-uuid",
+    "x-attempt-number",
+    "x-hub-signature",
+];
+
+/// Largest delivery body accepted; Bitbucket's own payloads are far smaller
+const MAX_BODY: usize = 10 * 1024 * 1024;
+
+/// Largest request line and headers accepted, together
+const MAX_HEADERS: u64 = 64 * 1024;
+
+/// How long a connection may take to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Subcommand)]
+pub enum WebhookCommands {
+    /// Forward webhook deliveries for a repository to a local server
+    Forward {
+        /// Events to subscribe to, comma-separated; wildcards such as
+        /// pullrequest:* are expanded. Without it, pick them from a list
+        #[arg(long, value_delimiter = ',')]
+        events: Vec<String>,
+
+        /// Local URL to pass each delivery on to (default: only print events)
+        #[arg(long)]
+        url: Option<String>,
+
+        /// Public URL that reaches the local listener, e.g. from a tunnel
+        #[arg(long, value_name = "URL")]
+        relay: String,
+
+        /// Port for the local listener
+        #[arg(long, default_value = "8765")]
+        port: u16,
+
+        /// Also print each delivery's JSON payload
+        #[arg(long)]
+        payload: bool,
+    },
+
+    /// List the events a repository webhook can subscribe to
+    Events,
+}
+
+impl Porcelain for HookEvent {
+    fn porcelain(&self) -> String {
+        self.event.clone()
+    }
+}
+
+#[derive(Tabled)]
+struct HookEventRow {
+    #[tabled(rename = "EVENT")]
+    event: String,
+    #[tabled(rename = "CATEGORY")]
+    category: String,
+    #[tabled(rename = "DESCRIPTION")]
+    description: String,
+}
+
+impl From<&HookEvent> for HookEventRow {
+    fn from(event: &HookEvent) -> Self {
+        Self {
+            event: event.event.clone(),
+            category: event.category.clone().unwrap_or_default(),
+            description: event
+                .description
+                .clone()
+                .or_else(|| event.label.clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// A webhook delivery received by the listener
+#[derive(Debug)]
+struct Delivery {
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Delivery {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl WebhookCommands {
+    /// `repo` is the global `--repo`, otherwise the repository of the
+    /// current checkout
+    pub async fn run(self, repo: Option<String>) -> Result<()> {
+        match self {
+            WebhookCommands::Forward {
+                events,
+                url,
+                relay,
+                port,
+                payload,
+            } => {
+                let (workspace, repo_slug) = git::repo_or_origin(repo)?;
+                let client = BitbucketClient::from_stored().await?;
+                let catalog = catalog_or_builtin(&client).await;
+                let events = if events.is_empty() {
+                    choose_events(&catalog)?
+                } else {
+                    let known: Vec<&str> = catalog.iter().map(|e| e.event.as_str()).collect();
+                    expand_events(&events, &known)?
+                };
+
+                let listener = TcpListener::bind(("127.0.0.1", port))
+                    .await
+                    .with_context(|| format!("Failed to listen on port {}", port))?;
+
+                let request = CreateWebhookRequest {
+                    description: "bitbucket webhook forward (temporary)".to_string(),
+                    url: relay.clone(),
+                    active: true,
+                    events: events.clone(),
+                };
+                let hook = client
+                    .create_webhook(&workspace, &repo_slug, &request)
+                    .await?;
+
+                output::note(format!(
+                    "{} Forwarding {} from {}/{} via {} to {}. Press Ctrl-C to stop.",
+                    "→".blue(),
+                    events.join(", "),
+                    workspace,
+                    repo_slug,
+                    relay,
+                    url.as_deref().unwrap_or("nowhere (printing only)")
+                ));
+
+                let result = serve(listener, url.as_deref(), payload).await;
+
+                // Clean up even when the listener failed
+                client
+                    .delete_webhook(&workspace, &repo_slug, &hook.uuid)
+                    .await
+                    .context("Failed to remove the temporary webhook")?;
+                output::note(format!(
+                    "{} Removed temporary webhook",
+                    Icon::Ok.glyph().green()
+                ));
+
+                result
+            }
+
+            WebhookCommands::Events => {
+                let client = BitbucketClient::from_stored().await?;
+                let catalog: Vec<HookEvent> = client
+                    .stream_hook_events("repository")
+                    .try_collect()
+                    .await?;
+
+                if output::print(&catalog)? {
+                    return Ok(());
+                }
+                output::table(catalog.iter().map(HookEventRow::from).collect())?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Bitbucket's catalog of repository events, or the built-in list when it
+/// can't be fetched
+async fn catalog_or_builtin(client: &BitbucketClient) -> Vec<HookEvent> {
+    match client
+        .stream_hook_events("repository")
+        .try_collect::<Vec<_>>()
+        .await
+    {
+        Ok(catalog) if !catalog.is_empty() => catalog,
+        result => {
+            if let Err(e) = result {
+                tracing::debug!(error = %e, "falling back to the built-in webhook events");
+            }
+            WEBHOOK_EVENTS
+                .iter()
+                .map(|event| HookEvent {
+                    event: event.to_string(),
+                    category: None,
+                    label: None,
+                    description: None,
+                })
+                .collect()
+        }
+    }
+}
+
+/// Ask which events to subscribe to
+fn choose_events(catalog: &[HookEvent]) -> Result<Vec<String>> {
+    if !output::is_tty() {
+        anyhow::bail!(UsageError(
+            "Pass --events, or run in a terminal to pick them from a list".to_string()
+        ));
+    }
+    let items: Vec<String> = catalog
+        .iter()
+        .map(|e| match &e.description {
+            Some(description) => format!("{} - {}", e.event, description),
+            None => e.event.clone(),
+        })
+        .collect();
+    let chosen = dialoguer::MultiSelect::new()
+        .with_prompt("Events to forward (space to pick, enter to confirm)")
+        .items(&items)
+        .interact()?;
+    if chosen.is_empty() {
+        anyhow::bail!(UsageError("No events chosen".to_string()));
+    }
+    Ok(chosen
+        .into_iter()
+        .map(|i| catalog[i].event.clone())
+        .collect())
+}
+
+/// Expand wildcard patterns against the `known` events, keeping their order
+fn expand_events(patterns: &[String], known: &[&str]) -> Result<Vec<String>> {
+    let mut events: Vec<String> = Vec::new();
+
+    for pattern in patterns {
+        let pattern = pattern.trim();
+        let matched: Vec<&str> = match pattern.strip_suffix('*') {
+            Some(prefix) => known
+                .iter()
+                .copied()
+                .filter(|event| event.starts_with(prefix))
+                .collect(),
+            None => known
+                .iter()
+                .copied()
+                .filter(|event| *event == pattern)
+                .collect(),
+        };
+
+        if matched.is_empty() {
+            anyhow::bail!(UsageError(unknown_event(pattern, known)));
+        }
+        for event in matched {
+            if !events.iter().any(|e| e == event) {
+                events.push(event.to_string());
+            }
+        }
+    }
+
+    Ok(events)
+}
+
+/// Why `name` was rejected, with the events it was most likely meant to be
+fn unknown_event(name: &str, known: &[&str]) -> String {
+    let close: Vec<&str> = known
+        .iter()
+        .copied()
+        .filter(|event| event.starts_with(name) || name.starts_with(event))
+        .collect();
+    if !close.is_empty() {
+        return format!(
+            "Unknown webhook event '{}'. Did you mean {}?",
+            name,
+            close.join(" or ")
+        );
+    }
+
+    let category = name.split_once(':').map_or(name, |(category, _)| category);
+    let same_category: Vec<&str> = known
+        .iter()
+        .copied()
+        .filter(|event| event.split_once(':').is_some_and(|(c, _)| c == category))
+        .collect();
+    let (which, listed) = if same_category.is_empty() {
+        (String::new(), known.to_vec())
+    } else {
+        (format!("{} ", category), same_category)
+    };
+    format!(
+        "Unknown webhook event '{}'. Known {}events: {}",
+        name,
+        which,
+        listed.join(", ")
+    )
+}
+
+/// Accept deliveries until Ctrl-C or the listener fails. Ctrl-C is taken
+/// from the global handler so the caller gets to remove the webhook.
+async fn serve(listener: TcpListener, url: Option<&str>, payload: bool) -> Result<()> {
+    let _deferred = interrupt::defer();
+    let http = reqwest::Client::new();
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = &mut ctrl_c => return Ok(()),
+        };
+        // One connection per task, so a slow client doesn't hold up the rest
+        let http = http.clone();
+        let url = url.map(str::to_string);
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, &http, url.as_deref(), payload).await {
+                tracing::warn!(%peer, error = %e, "failed to handle webhook delivery");
+                eprintln!("{} {:#}", Icon::Error.glyph().red(), e);
+            }
+        });
+    }
+}
+
+async fn handle(
+    mut stream: TcpStream,
+    http: &reqwest::Client,
+    url: Option<&str>,
+    payload: bool,
+) -> Result<()> {
+    let read = tokio::time::timeout(
+        READ_TIMEOUT,
+        read_delivery(&mut BufReader::new(&mut stream)),
+    )
+    .await
+    .map_err(|_| anyhow::anyhow!("Timed out reading a delivery"));
+    let delivery = match read.and_then(|delivery| delivery) {
+        Ok(delivery) => delivery,
+        Err(e) => {
+            if e.downcast_ref::<TooLarge>().is_some() {
+                respond(&mut stream, 413, "Payload Too Large").await?;
+            }
+            return Err(e);
+        }
+    };
+    let event = delivery
+        .header("x-event-key")
+        .unwrap_or("unknown")
+        .to_string();
+    let body: Value = serde_json::from_slice(&delivery.body).unwrap_or(Value::Null);
+
+    let status = match url {
+        Some(url) => {
+            let mut request = http.post(url).body(delivery.body.clone());
+            for (name, value) in &delivery.headers {
+                if FORWARDED_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+                    request = request.header(name, value);
+                }
+            }
+            match request.send().await {
+                Ok(response) => response.status().as_u16(),
+                Err(e) => {
+                    eprintln!(
+                        "{} Could not reach {}: {}",
+                        Icon::Error.glyph().red(),
+                        url,
+                        e
+                    );
+                    502
+                }
+            }
+        }
+        None => 200,
+    };
+
+    let status_label = if status < 400 {
+        status.to_string().green()
+    } else {
+        status.to_string().red()
+    };
+    println!(
+        "{} {} {} {}",
+        Local::now().format("%H:%M:%S").to_string().dimmed(),
+        event.cyan(),
+        describe(&event, &body),
+        status_label
+    );
+    if payload {
+        println!("{}", serde_json::to_string_pretty(&body)?);
+    }
+
+    respond(
+        &mut stream,
+        status,
+        if status < 400 { "OK" } else { "Error" },
+    )
+    .await
+}
+
+/// Answer with an empty response and close the connection
+async fn respond(stream: &mut TcpStream, status: u16, reason: &str) -> Result<()> {
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        status, reason
+    );
+    stream.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+/// A delivery whose body is over [`MAX_BODY`]
+#[derive(Debug)]
+struct TooLarge(usize);
+
+impl std::fmt::Display for TooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Rejected a {} byte delivery; the limit is {} bytes",
+            self.0, MAX_BODY
+        )
+    }
+}
+
+impl std::error::Error for TooLarge {}
+
+/// Read an HTTP request's headers and body, refusing oversized ones
+async fn read_delivery<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Delivery> {
+    let mut head = (&mut *reader).take(MAX_HEADERS);
+    let mut request_line = String::new();
+    head.read_line(&mut request_line).await?;
+    if request_line.trim().is_empty() {
+        anyhow::bail!("Empty request");
+    }
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if head.read_line(&mut line).await? == 0 {
+            if head.limit() == 0 {
+                anyhow::bail!("Request headers are over {} bytes", MAX_HEADERS);
+            }
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let length = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or(0);
+    if length > MAX_BODY {
+        return Err(TooLarge(length).into());
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+
+    Ok(Delivery { headers, body })
+}
+
+/// A short description of what a delivery is about
+fn describe(event: &str, body: &Value) -> String {
+    let (kind, _) = event.split_once(':').unwrap_or((event, ""));
+    let item = match kind {
+        "pullrequest" => body
+            .get("pullrequest")
+            .map(|pr| format!("#{} {}", pr["id"], pr["title"].as_str().unwrap_or(""))),
+        "issue" => body
+            .get("issue")
+            .map(|issue| format!("#{} {}", issue["id"], issue["title"].as_str().unwrap_or(""))),
+        "repo" if event == "repo:push" => body["push"]["changes"].as_array().map(|changes| {
+            let branches: Vec<&str> = changes
+                .iter()
+                .filter_map(|c| c["new"]["name"].as_str())
+                .collect();
+            branches.join(", ")
+        }),
+        _ => None,
+    };
+
+    let repo = body["repository"]["full_name"].as_str().unwrap_or("");
+    match item {
+        Some(item) if !item.is_empty() => format!("{} {}", repo, item),
+        _ => repo.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcards_expand_to_known_events() {
+        let events = expand_events(
+            &["repo:push".into(), "pullrequest:comment_*".into()],
+            WEBHOOK_EVENTS,
+        )
+        .unwrap();
+        assert_eq!(
+            events,
+            [
+                "repo:push",
+                "pullrequest:comment_created",
+                "pullrequest:comment_updated",
+                "pullrequest:comment_deleted",
+                "pullrequest:comment_resolved",
+                "pullrequest:comment_reopened",
+            ]
+        );
+        assert!(expand_events(&["pullrequest:merged".into()], WEBHOOK_EVENTS).is_err());
+    }
+
+    #[test]
+    fn unknown_events_suggest_what_was_meant() {
+        assert_eq!(
+            unknown_event("pullrequest:create", WEBHOOK_EVENTS),
+            "Unknown webhook event 'pullrequest:create'. Did you mean pullrequest:created?"
+        );
+        assert_eq!(
+            unknown_event("issue:closed", WEBHOOK_EVENTS),
+            "Unknown webhook event 'issue:closed'. Known issue events: issue:created, issue:updated, issue:comment_created"
+        );
+    }
+
+    #[tokio::test]
+    async fn deliveries_are_read_with_their_body() {
+        let request = b"POST /hook HTTP/1.1\r\nX-Event-Key: pullrequest:created\r\n\
+                        Content-Length: 11\r\n\r\n{\"id\": 42}\nignored";
+        let delivery = read_delivery(&mut &request[..]).await.unwrap();
+        assert_eq!(delivery.header("x-event-key"), Some("pullrequest:created"));
+        assert_eq!(delivery.body, b"{\"id\": 42}\n");
+    }
+
+    #[tokio::test]
+    async fn oversized_deliveries_are_refused_before_reading_the_body() {
+        let request = b"POST /hook HTTP/1.1\r\nContent-Length: 99999999999\r\n\r\n";
+        let error = read_delivery(&mut &request[..]).await.unwrap_err();
+        assert!(error.downcast_ref::<TooLarge>().is_some());
+    }
+}
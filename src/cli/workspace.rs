@@ -0,0 +1,127 @@
+use anyhow::Result;
+use clap::Subcommand;
+use colored::Colorize;
+use futures::TryStreamExt;
+use tabled::Tabled;
+
+use super::output;
+use crate::api::BitbucketClient;
+use crate::models::WorkspaceMembership;
+
+#[derive(Subcommand)]
+pub enum WorkspaceCommands {
+    /// List members of a workspace
+    Members {
+        /// Workspace slug
+        workspace: String,
+
+        /// Only members whose name or nickname contains this text
+        #[arg(short, long)]
+        search: Option<String>,
+
+        /// Number of results
+        #[arg(short, long, default_value = "50")]
+        limit: u32,
+
+        /// Fetch every page instead of stopping at --limit
+        #[arg(long, conflicts_with = "limit")]
+        all: bool,
+    },
+}
+
+#[derive(Tabled)]
+struct MemberRow {
+    #[tabled(rename = "NAME")]
+    name: String,
+    #[tabled(rename = "NICKNAME")]
+    nickname: String,
+    #[tabled(rename = "ACCOUNT ID")]
+    account_id: String,
+    #[tabled(rename = "UUID")]
+    uuid: String,
+}
+
+impl From<&WorkspaceMembership> for MemberRow {
+    fn from(m: &WorkspaceMembership) -> Self {
+        Self {
+            name: m.user.display_name.clone(),
+            nickname: m.user.nickname.clone().unwrap_or_default(),
+            account_id: m.user.account_id.clone().unwrap_or_default(),
+            uuid: m.user.uuid.clone(),
+        }
+    }
+}
+
+impl WorkspaceCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            WorkspaceCommands::Members {
+                workspace,
+                search,
+                limit,
+                all,
+            } => {
+                let client = BitbucketClient::from_stored().await?;
+                let search = search.map(|s| s.to_lowercase());
+                let matches = |m: &WorkspaceMembership| {
+                    search.as_ref().is_none_or(|search| {
+                        m.user.display_name.to_lowercase().contains(search)
+                            || m.user
+                                .nickname
+                                .as_ref()
+                                .is_some_and(|n| n.to_lowercase().contains(search))
+                    })
+                };
+
+                if all && output::streams() {
+                    let members = client
+                        .stream_workspace_members(&workspace)
+                        .try_filter(|m| futures::future::ready(matches(m)));
+                    let count = output::stream(members, |m| MemberRow::from(m)).await?;
+                    if count == 0 && !output::is_structured() {
+                        output::note(format!("No members found in workspace '{}'", workspace));
+                    }
+                    return Ok(());
+                }
+
+                // The API can't filter members, so a search looks through all of them
+                let (mut members, has_more): (Vec<WorkspaceMembership>, bool) =
+                    if all || search.is_some() {
+                        let members = client
+                            .stream_workspace_members(&workspace)
+                            .try_collect()
+                            .await?;
+                        (members, false)
+                    } else {
+                        let page = client
+                            .list_workspace_members(&workspace, Some(limit))
+                            .await?;
+                        (page.values, page.next.is_some())
+                    };
+
+                members.retain(matches);
+
+                if output::print(&members)? {
+                    return Ok(());
+                }
+
+                let rows: Vec<MemberRow> = members.iter().map(MemberRow::from).collect();
+                if rows.is_empty() {
+                    output::note(format!("No members found in workspace '{}'", workspace));
+                    return Ok(());
+                }
+
+                output::table(rows)?;
+
+                if has_more {
+                    output::note(format!(
+                        "\n{} More members available. Use --limit or --all to see more.",
+                        "ℹ".blue()
+                    ));
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
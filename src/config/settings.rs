@@ -1,11 +1,30 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::cli::output::ReportFormat;
+use crate::models::MergeStrategy;
 
 const APP_NAME: &str = "bitbucket-cli";
 const CONFIG_FILE: &str = "config.toml";
 
+/// The profile `--profile` asked for, instead of the one for the directory
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+/// Use `[profile.<name>]` whatever the current directory
+pub fn select_profile(name: &str) {
+    let _ = PROFILE.set(name.to_string());
+}
+
+/// The profile `--profile` asked for, if any
+pub fn selected_profile() -> Option<&'static str> {
+    PROFILE.get().map(String::as_str)
+}
+
 /// XDG Base Directory helper functions
 ///
 /// On Linux, these follow the XDG Base Directory Specification:
@@ -102,6 +121,63 @@ pub mod xdg {
         }
         Ok(())
     }
+
+    /// Replace the file at `path` with what `update` makes of its current
+    /// contents (`None` when it doesn't exist yet). An exclusive lock on
+    /// `<path>.lock` is held throughout, so concurrent runs don't lose each
+    /// other's changes, and the new contents are renamed into place, so a
+    /// reader never sees half a file.
+    pub fn update_locked(
+        path: &Path,
+        update: impl FnOnce(Option<String>) -> Result<String>,
+    ) -> Result<()> {
+        let dir = path
+            .parent()
+            .with_context(|| format!("{} has no parent directory", path.display()))?;
+        ensure_dir(&dir.to_path_buf())?;
+        let _lock = lock(path)?;
+
+        let current = match fs::read_to_string(path) {
+            Ok(text) => Some(text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+        let contents = update(current)?;
+
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+        fs::write(&temporary, contents)
+            .with_context(|| format!("Failed to write {}", temporary.display()))?;
+        fs::rename(&temporary, path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// An exclusive lock on `<path>.lock`, released when dropped
+    pub fn lock(path: &Path) -> Result<fs::File> {
+        use fs2::FileExt;
+
+        let lock_path = lock_path(path);
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .with_context(|| format!("Failed to open {}", lock_path.display()))?;
+        file.lock_exclusive()
+            .with_context(|| format!("Failed to lock {}", lock_path.display()))?;
+        Ok(file)
+    }
+
+    /// The lock file [`lock`] uses for `path`
+    pub fn lock_path(path: &Path) -> PathBuf {
+        let mut lock_path = path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        PathBuf::from(lock_path)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -109,9 +185,85 @@ pub struct Config {
     #[serde(default)]
     pub auth: AuthConfig,
     #[serde(default)]
+    pub oauth: OAuthConfig,
+    #[serde(default)]
     pub defaults: DefaultsConfig,
     #[serde(default)]
     pub display: DisplayConfig,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    #[serde(default)]
+    pub labels: LabelsConfig,
+    #[serde(default)]
+    pub audit: AuditConfig,
+    #[serde(default)]
+    pub notify: NotifyConfig,
+    #[serde(default)]
+    pub pr: PrConfig,
+    #[serde(default)]
+    pub tui: TuiConfig,
+    #[serde(default)]
+    pub branch: BranchConfig,
+    /// Named lists of `workspace/repo` for commands that combine repositories
+    #[serde(default)]
+    pub groups: BTreeMap<String, Vec<String>>,
+    /// Defaults for a client or project, by name
+    #[serde(
+        default,
+        rename = "profile",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    /// The profile applied on load, if any
+    #[serde(skip)]
+    pub active_profile: Option<String>,
+}
+
+/// Defaults that apply under `--profile <name>`, or anywhere below one of
+/// `paths`, over the rest of the config
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ProfileConfig {
+    /// Absolute directories the profile applies in, with `~` for the home
+    /// directory
+    pub paths: Vec<String>,
+    /// Workspace used when `--workspace` isn't given
+    pub workspace: Option<String>,
+    /// Repository used outside a checkout, as in `[defaults]`
+    pub repository: Option<String>,
+    /// Format used when `--output` isn't given
+    pub output: Option<ReportFormat>,
+    /// Keys of `[pr]` to override
+    pub pr: toml::Table,
+}
+
+impl ProfileConfig {
+    /// How specific the profile is to `dir`: the length of its longest path
+    /// `dir` is in, or `None` if it's in none of them
+    fn depth(&self, dir: &Path) -> Option<usize> {
+        // Through symlinks on both sides, where they exist
+        let real = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let dir = real(dir);
+        self.paths()
+            .map(|path| real(&path))
+            .filter(|path| dir.starts_with(path))
+            .map(|path| path.components().count())
+            .max()
+    }
+
+    /// `paths`, with `~` expanded
+    fn paths(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.paths.iter().map(|path| {
+            let home = || dirs::home_dir().unwrap_or_default();
+            if path == "~" {
+                home()
+            } else if let Some(rest) = path.strip_prefix("~/") {
+                home().join(rest)
+            } else {
+                PathBuf::from(path)
+            }
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -120,11 +272,23 @@ pub struct AuthConfig {
     pub default_workspace: Option<String>,
 }
 
+/// The local server `auth login --oauth` receives the callback on
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct OAuthConfig {
+    /// Ports to try in order, instead of the built-in ones. The OAuth
+    /// consumer's callback URL has to name the one that's used.
+    pub callback_ports: Vec<u16>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DefaultsConfig {
     pub workspace: Option<String>,
     pub repository: Option<String>,
     pub branch: Option<String>,
+    /// Which clone URL `repo clone` uses
+    #[serde(default)]
+    pub clone_protocol: CloneProtocol,
 }
 
 impl Default for DefaultsConfig {
@@ -133,6 +297,25 @@ impl Default for DefaultsConfig {
             workspace: None,
             repository: None,
             branch: Some("main".to_string()),
+            clone_protocol: CloneProtocol::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CloneProtocol {
+    #[default]
+    Https,
+    Ssh,
+}
+
+impl CloneProtocol {
+    /// Name of the repository clone link for this protocol
+    pub fn link_name(self) -> &'static str {
+        match self {
+            CloneProtocol::Https => "https",
+            CloneProtocol::Ssh => "ssh",
         }
     }
 }
@@ -142,6 +325,9 @@ pub struct DisplayConfig {
     pub color: bool,
     pub pager: bool,
     pub date_format: String,
+    /// Which glyphs mark statuses and kinds of item in output and the TUI
+    #[serde(default)]
+    pub icons: IconSet,
 }
 
 impl Default for DisplayConfig {
@@ -150,10 +336,168 @@ impl Default for DisplayConfig {
             color: true,
             pager: true,
             date_format: "%Y-%m-%d %H:%M".to_string(),
+            icons: IconSet::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IconSet {
+    /// Emoji, and Unicode symbols for statuses
+    #[default]
+    Emoji,
+    /// Glyphs from a patched Nerd Font
+    NerdFont,
+    /// Plain ASCII, for terminals that draw emoji at the wrong width
+    Ascii,
+}
+
+/// HTTP settings applied to every request made against the Bitbucket API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Seconds to wait for a TCP/TLS connection to be established
+    pub connect_timeout: u64,
+    /// Seconds to wait for a complete response (0 disables the limit)
+    pub timeout: u64,
+    /// Most requests a client will have in flight at once
+    pub max_concurrent_requests: usize,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: 10,
+            timeout: 60,
+            max_concurrent_requests: 8,
         }
     }
 }
 
+impl NetworkConfig {
+    /// Connection timeout as a `Duration`
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout)
+    }
+
+    /// Overall request timeout, or `None` when disabled
+    pub fn request_timeout(&self) -> Option<Duration> {
+        (self.timeout > 0).then(|| Duration::from_secs(self.timeout))
+    }
+
+    /// Concurrent request limit, never less than one
+    pub fn max_concurrent_requests(&self) -> usize {
+        self.max_concurrent_requests.max(1)
+    }
+}
+
+/// How issue labels are stored, since Bitbucket Cloud has no labels of its own
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct LabelsConfig {
+    pub strategy: LabelStrategy,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelStrategy {
+    /// Bracketed prefixes on the title, e.g. `[bug] [ui] Crash on start`
+    #[default]
+    Title,
+    /// The issue's component; one label per issue
+    Component,
+}
+
+/// The local audit log of changes made through the CLI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+    pub enabled: bool,
+    /// Key to sign entries with: an SSH private key path, or a GPG key ID
+    pub signing_key: Option<String>,
+    pub signing_format: SigningFormat,
+    /// `ssh-keygen` allowed signers file that SSH signatures are verified
+    /// against
+    pub allowed_signers: Option<String>,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            signing_key: None,
+            signing_format: SigningFormat::Ssh,
+            allowed_signers: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SigningFormat {
+    #[default]
+    Ssh,
+    Gpg,
+}
+
+/// Where to report the outcome of commands that wait for something to finish
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct NotifyConfig {
+    /// Slack incoming webhook, or any URL that accepts a JSON POST
+    pub webhook: Option<String>,
+    /// Shell command to run, given the outcome in `BITBUCKET_NOTIFY_*` variables
+    pub command: Option<String>,
+    /// Only notify when the command failed
+    pub failures_only: bool,
+}
+
+/// Checks `pr create` runs on the drafted pull request before submitting
+/// it, and defaults for creating and merging
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct PrConfig {
+    /// Refuse pull requests without a description
+    pub require_description: bool,
+    /// Regex the title or description must match, such as a ticket key
+    pub ticket_pattern: Option<String>,
+    /// Longest title allowed, in characters
+    pub max_title_length: Option<usize>,
+    /// Shell command given the draft as JSON on stdin; failing blocks creation
+    pub lint_command: Option<String>,
+    /// Close the source branch on merge unless `--keep-source-branch`
+    pub close_source_branch: bool,
+    /// Strategy `pr merge` uses without `--strategy`
+    pub merge_strategy: Option<MergeStrategy>,
+    /// Merge commit message without `--message`, with `{title}`, `{id}`,
+    /// `{source}`, `{destination}` and `{author}` filled in
+    pub merge_message: Option<String>,
+    /// Where `pr checkout --worktree` puts the worktree, relative to the
+    /// checkout's root, with `{repo}`, `{id}` and `{branch}` filled in
+    /// (default: `../{repo}-pr-{id}`)
+    pub worktree_path: Option<String>,
+}
+
+/// Naming conventions `branch new` enforces and `pr create` warns about
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct BranchConfig {
+    /// Prefixes a branch name must start with (default: those of the
+    /// repository's branching model)
+    pub prefixes: Vec<String>,
+    /// Regex the name must match, such as a ticket key
+    pub ticket_pattern: Option<String>,
+}
+
+/// Settings for `bitbucket tui`
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct TuiConfig {
+    /// Directory `c` clones repositories into (default: where the TUI started)
+    pub clone_dir: Option<PathBuf>,
+}
+
 impl Config {
     /// Get the configuration directory path (XDG compliant)
     ///
@@ -192,8 +536,37 @@ impl Config {
         xdg::state_dir()
     }
 
-    /// Load configuration from file, or create default if it doesn't exist
+    /// Load configuration from file, or create default if it doesn't exist,
+    /// with the profile for `--profile` or the current directory applied
     pub fn load() -> Result<Self> {
+        let mut config = Self::load_file()?;
+        let name = match PROFILE.get() {
+            Some(name) if !config.profiles.contains_key(name) => {
+                let known: Vec<&str> = config.profiles.keys().map(String::as_str).collect();
+                anyhow::bail!(
+                    "No [profile.{}] in the config (profiles: {})",
+                    name,
+                    if known.is_empty() {
+                        "none".to_string()
+                    } else {
+                        known.join(", ")
+                    }
+                );
+            }
+            Some(name) => Some(name.clone()),
+            None => std::env::current_dir()
+                .ok()
+                .and_then(|dir| config.profile_for(&dir)),
+        };
+        if let Some(name) = name {
+            config.apply_profile(&name)?;
+        }
+        Ok(config)
+    }
+
+    /// The configuration as written, without a profile applied; what
+    /// [`Config::save`] should be given
+    pub fn load_file() -> Result<Self> {
         let config_path = Self::config_path()?;
 
         if !config_path.exists() {
@@ -205,10 +578,64 @@ impl Config {
 
         let config: Config = toml::from_str(&contents)
             .with_context(|| format!("Failed to parse config file: {:?}", config_path))?;
+        config.check_profiles()?;
 
         Ok(config)
     }
 
+    /// Refuse profile paths that would depend on the directory the CLI runs in
+    fn check_profiles(&self) -> Result<()> {
+        for (name, profile) in &self.profiles {
+            for (path, expanded) in profile.paths.iter().zip(profile.paths()) {
+                if !expanded.is_absolute() {
+                    anyhow::bail!(
+                        "[profile.{}] paths: '{}' isn't absolute; start it with / or ~",
+                        name,
+                        path
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The profile whose paths most closely contain `dir`
+    pub fn profile_for(&self, dir: &Path) -> Option<String> {
+        self.profiles
+            .iter()
+            .filter_map(|(name, profile)| profile.depth(dir).map(|depth| (depth, name)))
+            .max_by_key(|(depth, _)| *depth)
+            .map(|(_, name)| name.clone())
+    }
+
+    /// Lay `[profile.<name>]` over the rest of the config
+    fn apply_profile(&mut self, name: &str) -> Result<()> {
+        let Some(profile) = self.profiles.get(name).cloned() else {
+            return Ok(());
+        };
+        tracing::debug!(profile = name, "applying config profile");
+        if profile.workspace.is_some() {
+            self.defaults.workspace = profile.workspace;
+        }
+        if profile.repository.is_some() {
+            self.defaults.repository = profile.repository;
+        }
+        if !profile.pr.is_empty() {
+            let mut pr = toml::Table::try_from(&self.pr)?;
+            pr.extend(profile.pr);
+            self.pr = pr
+                .try_into()
+                .with_context(|| format!("Invalid [profile.{}.pr]", name))?;
+        }
+        self.active_profile = Some(name.to_string());
+        Ok(())
+    }
+
+    /// The active profile's `--output` default
+    pub fn output(&self) -> Option<ReportFormat> {
+        self.profiles.get(self.active_profile.as_deref()?)?.output
+    }
+
     /// Save configuration to file
     pub fn save(&self) -> Result<()> {
         let config_dir = Self::config_dir()?;
@@ -271,6 +698,115 @@ mod tests {
         assert_eq!(config.display.color, deserialized.display.color);
     }
 
+    #[test]
+    fn test_label_strategy_defaults_to_title() {
+        assert_eq!(Config::default().labels.strategy, LabelStrategy::Title);
+        let config: Config = toml::from_str("[labels]\nstrategy = \"component\"\n").unwrap();
+        assert_eq!(config.labels.strategy, LabelStrategy::Component);
+    }
+
+    #[test]
+    fn test_icon_sets_use_kebab_case() {
+        assert_eq!(Config::default().display.icons, IconSet::Emoji);
+        let config: Config = toml::from_str(
+            "[display]\ncolor = true\npager = true\ndate_format = \"%F\"\nicons = \"nerd-font\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.display.icons, IconSet::NerdFont);
+    }
+
+    #[test]
+    fn test_clone_protocol_defaults_to_https() {
+        assert_eq!(
+            Config::default().defaults.clone_protocol,
+            CloneProtocol::Https
+        );
+        let config: Config = toml::from_str("[defaults]\nclone_protocol = \"ssh\"\n").unwrap();
+        assert_eq!(config.defaults.clone_protocol, CloneProtocol::Ssh);
+        assert_eq!(config.defaults.branch, None);
+    }
+
+    #[test]
+    fn test_profile_for_the_closest_path_applies_over_the_config() {
+        let mut config: Config = toml::from_str(
+            "[pr]\nclose_source_branch = true\n\n\
+             [profile.acme]\npaths = [\"/work/acme\"]\nworkspace = \"acme\"\n\n\
+             [profile.engine]\npaths = [\"/work/acme/engine\"]\nworkspace = \"acme\"\n\
+             repository = \"engine\"\noutput = \"json\"\n\n\
+             [profile.engine.pr]\nmerge_strategy = \"squash\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.profile_for(Path::new("/work/acme/mill")).as_deref(),
+            Some("acme")
+        );
+        assert_eq!(
+            config
+                .profile_for(Path::new("/work/acme/engine/src"))
+                .as_deref(),
+            Some("engine")
+        );
+        assert_eq!(config.profile_for(Path::new("/work/acmes")), None);
+
+        config.apply_profile("engine").unwrap();
+        assert_eq!(config.defaults.workspace.as_deref(), Some("acme"));
+        assert_eq!(config.defaults.repository.as_deref(), Some("engine"));
+        assert_eq!(config.output(), Some(ReportFormat::Json));
+        assert!(matches!(
+            config.pr.merge_strategy,
+            Some(MergeStrategy::Squash)
+        ));
+        assert!(config.pr.close_source_branch);
+    }
+
+    #[test]
+    fn test_profile_paths_expand_home_and_must_be_absolute() {
+        let home = dirs::home_dir().unwrap();
+        let config: Config = toml::from_str(
+            "[profile.home]\npaths = [\"~\"]\n\n[profile.work]\npaths = [\"~/work\"]\n",
+        )
+        .unwrap();
+        config.check_profiles().unwrap();
+        assert_eq!(
+            config.profile_for(&home.join("notes")).as_deref(),
+            Some("home")
+        );
+        assert_eq!(
+            config.profile_for(&home.join("work/engine")).as_deref(),
+            Some("work")
+        );
+
+        let config: Config = toml::from_str("[profile.acme]\npaths = [\"work/acme\"]\n").unwrap();
+        assert!(config.check_profiles().is_err());
+    }
+
+    #[test]
+    fn test_network_config_defaults_when_missing() {
+        let config: Config = toml::from_str("[network]\nconnect_timeout = 5\n").unwrap();
+        assert_eq!(config.network.connect_timeout, 5);
+        assert_eq!(config.network.timeout, 60);
+        assert_eq!(config.network.max_concurrent_requests(), 8);
+
+        let disabled = NetworkConfig {
+            timeout: 0,
+            ..Default::default()
+        };
+        assert!(disabled.request_timeout().is_none());
+
+        let zero = NetworkConfig {
+            max_concurrent_requests: 0,
+            ..Default::default()
+        };
+        assert_eq!(zero.max_concurrent_requests(), 1);
+    }
+
+    #[test]
+    fn test_oauth_callback_ports() {
+        assert!(Config::default().oauth.callback_ports.is_empty());
+        let config: Config = toml::from_str("[oauth]\ncallback_ports = [5173, 4000]\n").unwrap();
+        assert_eq!(config.oauth.callback_ports, [5173, 4000]);
+    }
+
     #[test]
     fn test_xdg_directories() {
         // These should not panic and should return valid paths
@@ -0,0 +1,143 @@
+//! Error type for the library surface (`api`, `models`, `auth`)
+//!
+//! The command-line layer wraps these in `anyhow` for reporting; embedders can
+//! match on the variants instead of inspecting message strings.
+
+use reqwest::StatusCode;
+
+/// Result type used throughout the library surface
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors returned by the Bitbucket client and credential handling
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// No credentials are stored
+    #[error("Not authenticated. Run 'bitbucket auth login' first.")]
+    NotAuthenticated,
+
+    /// The API rejected the credentials (HTTP 401)
+    #[error("Authentication failed. Try running 'bitbucket auth login' again.")]
+    Unauthorized,
+
+    /// The credentials lack permission for the resource (HTTP 403)
+    #[error("Access denied. You don't have permission to access this resource.")]
+    Forbidden,
+
+    /// The resource does not exist (HTTP 404, or a lookup came back empty)
+    #[error("{0} not found.")]
+    NotFound(String),
+
+    /// Too many requests (HTTP 429)
+    #[error("Rate limit exceeded. Please wait and try again.")]
+    RateLimited,
+
+    /// Any other unsuccessful API response
+    #[error("API error ({status}): {message}")]
+    Api { status: StatusCode, message: String },
+
+    /// The request did not complete within the configured timeout
+    #[error(
+        "Request timed out. Check your connection or raise the [network] timeouts in the config file."
+    )]
+    Timeout,
+
+    /// Transport-level failure (DNS, TLS, connection reset, ...)
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// A response or stored value was not the JSON we expected
+    #[error("Failed to parse JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// A response did not match the expected shape
+    #[error(
+        "Unexpected response from {url}: {source} (at `{path}`{}){}",
+        found(.value),
+        saved(.dump)
+    )]
+    Decode {
+        url: String,
+        path: String,
+        value: Option<String>,
+        #[source]
+        source: serde_json::Error,
+        dump: Option<std::path::PathBuf>,
+    },
+
+    /// Offline mode cannot satisfy the request
+    #[error("{0}")]
+    Offline(String),
+
+    /// A request missing from the recording being replayed
+    #[error("{0}")]
+    Replay(String),
+
+    /// The OAuth or API key sign-in flow failed
+    #[error("{0}")]
+    Auth(String),
+
+    /// Reading or writing stored credentials failed
+    #[error("{0}")]
+    CredentialStore(String),
+
+    /// Loading configuration or locating XDG directories failed
+    #[error("{0}")]
+    Config(String),
+
+    /// Local file I/O failed
+    #[error("{context}: {source}")]
+    Io {
+        context: &'static str,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+fn found(value: &Option<String>) -> String {
+    value
+        .as_ref()
+        .map(|v| format!(", found {}", v))
+        .unwrap_or_default()
+}
+
+fn saved(dump: &Option<std::path::PathBuf>) -> String {
+    match dump {
+        Some(path) => format!("\nThe response body was saved to {}", path.display()),
+        None => "\nRun with --debug to save the response body for a bug report.".to_string(),
+    }
+}
+
+impl Error {
+    /// Wrap an I/O error with a short description of what was being done
+    pub(crate) fn io(context: &'static str) -> impl FnOnce(std::io::Error) -> Self {
+        move |source| Error::Io { context, source }
+    }
+
+    /// Flatten an error from the (anyhow-based) config layer
+    pub(crate) fn config(error: anyhow::Error) -> Self {
+        Error::Config(format!("{:#}", error))
+    }
+
+    /// Whether the request might succeed if sent again unchanged: a network
+    /// failure, a timeout, rate limiting or a server error
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Http(_) | Error::Timeout | Error::RateLimited => true,
+            Error::Api { status, .. } => status.is_server_error(),
+            _ => false,
+        }
+    }
+
+    /// HTTP status of the API response that caused this error, if any
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::Unauthorized => Some(StatusCode::UNAUTHORIZED),
+            Error::Forbidden => Some(StatusCode::FORBIDDEN),
+            Error::NotFound(_) => Some(StatusCode::NOT_FOUND),
+            Error::RateLimited => Some(StatusCode::TOO_MANY_REQUESTS),
+            Error::Api { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+}
@@ -1,9 +1,52 @@
+//! Bitbucket Cloud client library and command-line interface
+//!
+//! The [`api`], [`models`], and [`auth`] modules form the embeddable library
+//! surface: a typed async client for the Bitbucket Cloud REST API, the
+//! request/response models it uses, and credential storage plus the OAuth and
+//! API key sign-in flows. They report failures through [`Error`] and never
+//! print or prompt, so they can be used from other tools:
+//!
+//! ```no_run
+//! use bitbucket_cli::api::BitbucketClient;
+//! use bitbucket_cli::auth::Credential;
+//!
+//! # async fn example() -> bitbucket_cli::Result<()> {
+//! let client = BitbucketClient::new(Credential::ApiKey {
+//!     username: "me".into(),
+//!     api_key: "ATATT...".into(),
+//! })?;
+//! let repos = client.list_repositories("my-workspace", None, Some(10)).await?;
+//! for repo in repos.values {
+//!     println!("{}", repo.full_name);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`api::middleware`] lets a tool wrap every request the client sends, e.g.
+//! to add headers or metrics, with [`api::BitbucketClient::with_middleware`].
+//!
+//! [`config`] holds the settings file types, of which only
+//! [`config::NetworkConfig`] is part of the client API. The remaining modules
+//! (`cli`, `tui`, `logging`, `audit`) implement the `bitbucket` binary and are not
+//! covered by semver guarantees.
+
 // Allow dead code for API methods designed for future use
 #![allow(dead_code)]
 
 pub mod api;
+#[doc(hidden)]
+pub mod audit;
 pub mod auth;
+pub mod error;
+pub mod models;
+
+#[doc(hidden)]
 pub mod cli;
 pub mod config;
-pub mod models;
+#[doc(hidden)]
+pub mod logging;
+#[doc(hidden)]
 pub mod tui;
+
+pub use error::{Error, Result};
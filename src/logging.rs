@@ -0,0 +1,215 @@
+//! Diagnostic logging
+//!
+//! Every run appends debug-level events to a daily-rotated log file in the
+//! XDG state directory (`$XDG_STATE_HOME/bitbucket-cli/logs`) so users can
+//! attach it to bug reports. Passing `--debug` additionally mirrors events to
+//! stderr. The `BITBUCKET_LOG` environment variable overrides the default
+//! filter using `tracing-subscriber` directive syntax.
+//!
+//! Credentials never reach either sink: all output passes through [`redact`]
+//! before it is written.
+
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use anyhow::Result;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::prelude::*;
+
+use crate::config::{Config, xdg};
+
+const LOG_ENV: &str = "BITBUCKET_LOG";
+const DEFAULT_FILTER: &str = "warn,bitbucket_cli=debug,bitbucket=debug";
+const LOG_FILE_PREFIX: &str = "bitbucket";
+const MAX_LOG_FILES: usize = 7;
+
+/// Keys whose values are masked wherever they appear in log output
+const SECRET_KEYS: &[&str] = &[
+    "access_token",
+    "refresh_token",
+    "client_secret",
+    "api_key",
+    "password",
+];
+
+/// Schemes whose credentials follow them in an `Authorization` header
+const AUTH_SCHEMES: &[&str] = &["Bearer ", "Basic "];
+
+const MASK: &str = "[REDACTED]";
+
+/// Get the directory debug logs are written to
+pub fn log_dir() -> Result<PathBuf> {
+    Ok(Config::state_dir()?.join("logs"))
+}
+
+/// Install the global tracing subscriber.
+///
+/// The returned guard flushes the log file when dropped and must be held
+/// for the lifetime of the program. Logging is best-effort: if the state
+/// directory cannot be created the file sink is skipped silently.
+pub fn init(debug: bool) -> Option<WorkerGuard> {
+    let (file_writer, guard) = match file_appender() {
+        Some(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            (Some(writer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    let file_layer = file_writer.map(|writer| {
+        tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_writer(move || RedactingWriter(writer.clone()))
+            .with_filter(filter())
+    });
+
+    let stderr_layer = debug.then(|| {
+        tracing_subscriber::fmt::layer()
+            .with_target(false)
+            .with_writer(|| RedactingWriter(io::stderr()))
+            .with_filter(filter())
+    });
+
+    let _ = tracing_subscriber::registry()
+        .with(file_layer)
+        .with(stderr_layer)
+        .try_init();
+
+    guard
+}
+
+fn filter() -> EnvFilter {
+    EnvFilter::try_from_env(LOG_ENV).unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER))
+}
+
+fn file_appender() -> Option<RollingFileAppender> {
+    let dir = log_dir().ok()?;
+    xdg::ensure_dir(&dir).ok()?;
+
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix("log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(dir)
+        .ok()
+}
+
+/// Writer adapter that masks credentials before forwarding output
+struct RedactingWriter<W: Write>(W);
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        self.0.write_all(redact(&text).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Mask tokens, secrets, and authorization header values in `input`.
+///
+/// Handles `Bearer`/`Basic` credentials as well as `key=value`, `key: value`,
+/// and JSON `"key": "value"` forms of the keys in [`SECRET_KEYS`].
+pub fn redact(input: &str) -> Cow<'_, str> {
+    let lower = input.to_ascii_lowercase();
+    let needs_redaction = AUTH_SCHEMES.iter().any(|s| input.contains(s))
+        || SECRET_KEYS.iter().any(|k| lower.contains(k));
+    if !needs_redaction {
+        return Cow::Borrowed(input);
+    }
+
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+
+    for scheme in AUTH_SCHEMES {
+        for (start, _) in input.match_indices(scheme) {
+            let value_start = start + scheme.len();
+            spans.push((value_start, value_end(input, value_start)));
+        }
+    }
+
+    for key in SECRET_KEYS {
+        for (start, _) in lower.match_indices(key) {
+            let mut value_start = start + key.len();
+            let rest = &input[value_start..];
+            let skipped = rest.len() - rest.trim_start_matches(['"', '\'', ' ']).len();
+            let rest = &rest[skipped..];
+            // Only treat the key as a secret when a value is assigned to it
+            if !rest.starts_with([':', '=']) {
+                continue;
+            }
+            value_start += skipped + 1;
+            let rest = &input[value_start..];
+            value_start += rest.len() - rest.trim_start_matches(['"', '\'', ' ']).len();
+            spans.push((value_start, value_end(input, value_start)));
+        }
+    }
+
+    spans.retain(|(start, end)| end > start);
+    if spans.is_empty() {
+        return Cow::Borrowed(input);
+    }
+    spans.sort_unstable();
+
+    let mut output = String::with_capacity(input.len());
+    let mut cursor = 0;
+    for (start, end) in spans {
+        if start < cursor {
+            continue;
+        }
+        output.push_str(&input[cursor..start]);
+        output.push_str(MASK);
+        cursor = end;
+    }
+    output.push_str(&input[cursor..]);
+
+    Cow::Owned(output)
+}
+
+/// Find where a secret value starting at `start` ends
+fn value_end(input: &str, start: usize) -> usize {
+    input[start..]
+        .find(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '&' | ',' | '}' | ';'))
+        .map(|offset| start + offset)
+        .unwrap_or(input.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::redact;
+
+    #[test]
+    fn masks_authorization_header_values() {
+        assert_eq!(
+            redact("authorization: Bearer abc.def-123 next"),
+            "authorization: Bearer [REDACTED] next"
+        );
+        assert_eq!(redact("Basic dXNlcjpwYXNz"), "Basic [REDACTED]");
+    }
+
+    #[test]
+    fn masks_json_and_query_secrets() {
+        assert_eq!(
+            redact(r#"{"access_token": "tok", "scopes": "repo"}"#),
+            r#"{"access_token": "[REDACTED]", "scopes": "repo"}"#
+        );
+        assert_eq!(
+            redact("grant_type=refresh_token&refresh_token=xyz&client_secret=s3"),
+            "grant_type=refresh_token&refresh_token=[REDACTED]&client_secret=[REDACTED]"
+        );
+    }
+
+    #[test]
+    fn leaves_plain_text_untouched() {
+        let text = "GET /repositories/ws/repo 200 in 120ms";
+        assert_eq!(redact(text), text);
+        // A key mentioned without a value is not a secret
+        assert_eq!(redact("refreshing access_token"), "refreshing access_token");
+    }
+}
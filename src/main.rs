@@ -1,27 +1,177 @@
-use bitbucket_cli::{cli, tui};
+use bitbucket_cli::config::Config;
+use bitbucket_cli::{api, audit, cli, logging, tui};
 
 use anyhow::Result;
+use chrono::{Local, Utc};
 use clap::Parser;
 use colored::Colorize;
 
+use cli::icons::Icon;
 use cli::{Cli, Commands};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    let log_guard = logging::init(cli.debug);
+    tracing::info!(
+        version = env!("CARGO_PKG_VERSION"),
+        command = cli.command.name(),
+        offline = cli.offline,
+        "starting"
+    );
+    api::set_offline(cli.offline);
+    let recording = match (&cli.record, &cli.replay) {
+        (Some(path), _) => api::recording::start_recording(path),
+        (_, Some(path)) => api::recording::start_replay(path),
+        _ => Ok(()),
+    };
+    if let Err(e) = recording {
+        eprintln!("{} {:#}", "Error:".red().bold(), e);
+        std::process::exit(cli::exit_code::USAGE);
+    }
+    api::decode::set_dump_bodies(cli.debug);
+    cli::output::set_quiet(cli.quiet);
+    cli::output::set_full(cli.full);
+    cli::confirm::set_assume_yes(cli.yes);
+    if let Some(name) = &cli.profile {
+        bitbucket_cli::config::select_profile(name);
+    }
+    // A broken config is reported by the command that needs it, but a
+    // profile asked for by name has to exist
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) if cli.profile.is_some() => {
+            eprintln!("{} {:#}", "Error:".red().bold(), e);
+            std::process::exit(cli::exit_code::USAGE);
+        }
+        Err(_) => Config::default(),
+    };
+    if config.active_profile.is_some() {
+        cli.workspace = cli.workspace.or_else(|| config.defaults.workspace.clone());
+    }
+    if let Some(workspace) = &cli.workspace {
+        cli::cache::select_workspace(workspace);
+    }
+    cli::output::set_format(
+        cli.output.or_else(|| config.output()),
+        std::mem::take(&mut cli.columns),
+    );
+    cli::pager::set_enabled(config.display.pager && !cli.no_pager);
+    if let Err(e) = cli::format::init(&config.display) {
+        eprintln!("{} {:#}", "Error:".red().bold(), e);
+        std::process::exit(cli::exit_code::USAGE);
+    }
+    if let Err(e) = cli::output::init(cli.jq.take(), cli.template.take()) {
+        eprintln!("{} {:#}", "Error:".red().bold(), e);
+        std::process::exit(cli::exit_code::USAGE);
+    }
+
+    // Abort in-flight requests and exit on Ctrl-C. This runs on its own task so
+    // it fires even while a command is blocked on a prompt. The TUI puts the
+    // terminal in raw mode, which delivers Ctrl-C as a key event instead, but
+    // a SIGINT sent some other way still lands here. Commands that must clean
+    // up first defer it with `cli::interrupt::defer` and stop on their own.
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if cli::interrupt::is_deferred() {
+                continue;
+            }
+            let _ = tui::restore_terminal();
+            eprintln!("\n{}", "Interrupted".yellow());
+            std::process::exit(130);
+        }
+    });
+
+    let command_name = cli.command.name();
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
     let result = match cli.command {
         Commands::Auth { command } => command.run().await,
-        Commands::Repo { command } => command.run().await,
+        Commands::Repo { command } => command.run(cli.workspace).await,
         Commands::Pr { command } => command.run().await,
         Commands::Issue { command } => command.run().await,
         Commands::Pipeline { command } => command.run().await,
+        Commands::Branch { command } => command.run().await,
+        Commands::Commit { command } => command.run().await,
+        Commands::Compare(args) => args.run().await,
+        Commands::Insights { command } => command.run().await,
+        Commands::Snippet { command } => command.run().await,
+        Commands::User { command } => command.run().await,
+        Commands::Variable { command } => command.run(cli.workspace, cli.repo).await,
+        Commands::Webhook { command } => command.run(cli.repo).await,
+        Commands::Workspace { command } => command.run().await,
+        Commands::Changelog(args) => args.run().await,
+        Commands::Audit { command } => command.run(cli.workspace).await,
+        Commands::Status(args) => args.run().await,
+        Commands::Search { command } => command.run(cli.workspace).await,
+        Commands::Stats(args) => args.run().await,
+        Commands::Recent(args) => args.run(),
+        Commands::Drafts { command } => command.run().await,
+        Commands::Doctor => cli::doctor::run().await,
+        Commands::ServiceStatus => cli::service_status::run().await,
+        Commands::Cache { command } => command.run().await,
         Commands::Tui => tui::run_tui(cli.workspace).await,
+        Commands::Browse(args) => args.run(cli.repo),
+        Commands::Ext { command } => command.run().await,
+        Commands::External(args) => match cli::ext::dispatch(args, cli.workspace, cli.repo).await {
+            Ok(code) => {
+                drop(log_guard);
+                std::process::exit(code);
+            }
+            Err(e) => Err(e),
+        },
     };
 
+    if result.is_ok()
+        && let Err(e) = cli::recent::save()
+    {
+        tracing::warn!("recent repositories: {:#}", e);
+    }
+    if result.is_ok()
+        && let Err(e) = cli::last_run::save()
+    {
+        // A script relying on last-run would otherwise list the same again
+        tracing::warn!("last runs: {:#}", e);
+        eprintln!(
+            "{} Failed to save the last run: {:#}",
+            Icon::Warning.glyph().yellow(),
+            e
+        );
+    }
+
+    let exit_code = result.as_ref().map_or_else(cli::exit_code_for, |()| 0);
+    let error = result.as_ref().err().map(|e| format!("{:#}", e));
+    if let Err(e) = audit::record(command_name, &args, exit_code, error) {
+        tracing::warn!("audit log: {:#}", e);
+        eprintln!("{} {:#}", Icon::Warning.glyph().yellow(), e);
+    }
+
+    if let Some(fetched_at) = api::snapshot::oldest_served() {
+        let age = (Utc::now() - fetched_at).num_minutes().max(0);
+        eprintln!(
+            "{} Offline: showing cached data from {} ({}h {}m old)",
+            Icon::Warning.glyph().yellow(),
+            fetched_at.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+            age / 60,
+            age % 60
+        );
+    }
+
+    if let (Ok(()), Some(flag)) = (&result, cli::output::unused_flag()) {
+        eprintln!(
+            "{} {} has no effect on this command",
+            Icon::Warning.glyph().yellow(),
+            flag
+        );
+    }
+
     if let Err(e) = result {
+        tracing::error!("{:#}", e);
         eprintln!("{} {}", "Error:".red().bold(), e);
-        std::process::exit(1);
+        cli::service_status::explain_failure(&e).await;
+        // `exit` skips destructors, so flush the log file first
+        drop(log_guard);
+        std::process::exit(cli::exit_code_for(&e));
     }
 
     Ok(())
@@ -0,0 +1,133 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A Code Insights report attached to a commit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    pub uuid: Option<String>,
+    pub external_id: Option<String>,
+    pub title: String,
+    pub details: Option<String>,
+    pub report_type: Option<ReportType>,
+    pub reporter: Option<String>,
+    pub link: Option<String>,
+    pub result: Option<ReportResult>,
+    #[serde(default)]
+    pub data: Vec<ReportData>,
+    pub created_on: Option<DateTime<Utc>>,
+    pub updated_on: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ReportType {
+    Security,
+    Coverage,
+    Test,
+    Bug,
+    /// A value this version does not know about
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ReportResult {
+    Passed,
+    Failed,
+    Pending,
+    /// A value this version does not know about
+    #[serde(other)]
+    Unknown,
+}
+
+impl std::fmt::Display for ReportResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReportResult::Passed => write!(f, "PASSED"),
+            ReportResult::Failed => write!(f, "FAILED"),
+            ReportResult::Pending => write!(f, "PENDING"),
+            ReportResult::Unknown => write!(f, "UNKNOWN"),
+        }
+    }
+}
+
+/// A metric shown on a report, such as coverage or a count of findings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportData {
+    pub title: String,
+    #[serde(rename = "type")]
+    pub data_type: String,
+    pub value: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreateReportRequest {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+    pub report_type: ReportType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reporter: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    pub result: ReportResult,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub data: Vec<ReportData>,
+}
+
+/// A finding on a line of a file, shown in pull request diffs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Annotation {
+    /// Unique within the report; re-publishing an ID replaces the annotation
+    pub external_id: String,
+    pub annotation_type: AnnotationType,
+    pub summary: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<AnnotationResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AnnotationType {
+    Vulnerability,
+    CodeSmell,
+    Bug,
+    /// A value this version does not know about
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+    /// A value this version does not know about
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum AnnotationResult {
+    Passed,
+    Failed,
+    Skipped,
+    Ignored,
+    /// A value this version does not know about
+    #[serde(other)]
+    Unknown,
+}
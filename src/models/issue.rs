@@ -1,6 +1,7 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use super::repo::Repository;
 use super::user::{Link, User};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +23,7 @@ pub struct Issue {
     pub updated_on: Option<DateTime<Utc>>,
     pub edited_on: Option<DateTime<Utc>>,
     pub links: Option<IssueLinks>,
+    pub repository: Option<Repository>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,6 +45,9 @@ pub enum IssueState {
     Duplicate,
     Wontfix,
     Closed,
+    /// A value this version does not know about
+    #[serde(other)]
+    Unknown,
 }
 
 impl std::fmt::Display for IssueState {
@@ -56,6 +61,7 @@ impl std::fmt::Display for IssueState {
             IssueState::Duplicate => write!(f, "duplicate"),
             IssueState::Wontfix => write!(f, "wontfix"),
             IssueState::Closed => write!(f, "closed"),
+            IssueState::Unknown => write!(f, "unknown"),
         }
     }
 }
@@ -67,6 +73,9 @@ pub enum IssueKind {
     Enhancement,
     Proposal,
     Task,
+    /// A value this version does not know about
+    #[serde(other)]
+    Unknown,
 }
 
 impl std::fmt::Display for IssueKind {
@@ -76,6 +85,7 @@ impl std::fmt::Display for IssueKind {
             IssueKind::Enhancement => write!(f, "enhancement"),
             IssueKind::Proposal => write!(f, "proposal"),
             IssueKind::Task => write!(f, "task"),
+            IssueKind::Unknown => write!(f, "unknown"),
         }
     }
 }
@@ -88,6 +98,9 @@ pub enum IssuePriority {
     Major,
     Critical,
     Blocker,
+    /// A value this version does not know about
+    #[serde(other)]
+    Unknown,
 }
 
 impl std::fmt::Display for IssuePriority {
@@ -98,6 +111,7 @@ impl std::fmt::Display for IssuePriority {
             IssuePriority::Major => write!(f, "major"),
             IssuePriority::Critical => write!(f, "critical"),
             IssuePriority::Blocker => write!(f, "blocker"),
+            IssuePriority::Unknown => write!(f, "unknown"),
         }
     }
 }
@@ -143,6 +157,19 @@ pub struct CreateIssueRequest {
     pub version: Option<VersionName>,
 }
 
+/// Fields to change on an issue; those left `None` are kept
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UpdateIssueRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<IssueKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<IssuePriority>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<UserAccountId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<IssueState>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IssueContentRequest {
     pub raw: String,
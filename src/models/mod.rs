@@ -1,11 +1,21 @@
+pub mod insights;
 pub mod issue;
 pub mod pipeline;
 pub mod pr;
 pub mod repo;
+pub mod snippet;
+pub mod status;
 pub mod user;
+pub mod variable;
+pub mod webhook;
 
+pub use insights::*;
 pub use issue::*;
 pub use pipeline::*;
 pub use pr::*;
 pub use repo::*;
+pub use snippet::*;
+pub use status::*;
 pub use user::*;
+pub use variable::*;
+pub use webhook::*;
@@ -27,6 +27,32 @@ pub struct PipelineTarget {
     pub ref_name: Option<String>,
     pub selector: Option<PipelineSelector>,
     pub commit: Option<PipelineCommit>,
+    /// The pull request a `pipeline_pullrequest_target` ran for
+    pub pullrequest: Option<PipelinePullRequest>,
+}
+
+/// What the pipeline ran on: "PR #42", "tag v1.2.3", a branch name, or the
+/// short hash of a commit target
+impl std::fmt::Display for PipelineTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(pr) = &self.pullrequest {
+            return write!(f, "PR #{}", pr.id);
+        }
+        match (self.ref_type.as_deref(), self.ref_name.as_deref()) {
+            (Some("tag"), Some(name)) => write!(f, "tag {}", name),
+            (_, Some(name)) => f.write_str(name),
+            _ => match &self.commit {
+                Some(commit) => f.write_str(commit.hash.get(..7).unwrap_or(&commit.hash)),
+                None => f.write_str("-"),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelinePullRequest {
+    pub id: u64,
+    pub title: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -70,6 +96,9 @@ pub enum PipelineStateName {
     Completed,
     Halted,
     Paused,
+    /// A value this version does not know about
+    #[serde(other)]
+    Unknown,
 }
 
 impl std::fmt::Display for PipelineStateName {
@@ -80,6 +109,7 @@ impl std::fmt::Display for PipelineStateName {
             PipelineStateName::Completed => write!(f, "COMPLETED"),
             PipelineStateName::Halted => write!(f, "HALTED"),
             PipelineStateName::Paused => write!(f, "PAUSED"),
+            PipelineStateName::Unknown => write!(f, "UNKNOWN"),
         }
     }
 }
@@ -99,6 +129,9 @@ pub enum PipelineResultName {
     Error,
     Stopped,
     Expired,
+    /// A value this version does not know about
+    #[serde(other)]
+    Unknown,
 }
 
 impl std::fmt::Display for PipelineResultName {
@@ -109,6 +142,7 @@ impl std::fmt::Display for PipelineResultName {
             PipelineResultName::Error => write!(f, "ERROR"),
             PipelineResultName::Stopped => write!(f, "STOPPED"),
             PipelineResultName::Expired => write!(f, "EXPIRED"),
+            PipelineResultName::Unknown => write!(f, "UNKNOWN"),
         }
     }
 }
@@ -33,6 +33,9 @@ pub enum PullRequestState {
     Merged,
     Declined,
     Superseded,
+    /// A value this version does not know about
+    #[serde(other)]
+    Unknown,
 }
 
 impl std::fmt::Display for PullRequestState {
@@ -42,6 +45,7 @@ impl std::fmt::Display for PullRequestState {
             PullRequestState::Merged => write!(f, "MERGED"),
             PullRequestState::Declined => write!(f, "DECLINED"),
             PullRequestState::Superseded => write!(f, "SUPERSEDED"),
+            PullRequestState::Unknown => write!(f, "UNKNOWN"),
         }
     }
 }
@@ -53,9 +57,16 @@ pub struct PullRequestEndpoint {
     pub repository: Option<Repository>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct BranchInfo {
     pub name: String,
+    /// Strategies the repository allows merging into the branch with, by
+    /// API name; only on a pull request's destination
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub merge_strategies: Vec<String>,
+    /// The repository's default for merging into the branch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_merge_strategy: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,6 +83,14 @@ pub struct Commit {
     pub links: Option<CommitLinks>,
 }
 
+/// A commit that changed a file, and the file's path in it, which differs
+/// before a rename
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileCommit {
+    pub path: String,
+    pub commit: Commit,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CommitAuthor {
     pub raw: Option<String>,
@@ -97,6 +116,9 @@ pub struct Participant {
 pub enum ParticipantRole {
     Participant,
     Reviewer,
+    /// A value this version does not know about
+    #[serde(other)]
+    Unknown,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -152,12 +174,51 @@ pub struct MergePullRequestRequest {
     pub merge_strategy: Option<MergeStrategy>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum MergeStrategy {
     MergeCommit,
     Squash,
     FastForward,
+    SquashFastForward,
+    RebaseFastForward,
+    RebaseMerge,
+}
+
+impl MergeStrategy {
+    /// The API's name for it
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MergeStrategy::MergeCommit => "merge_commit",
+            MergeStrategy::Squash => "squash",
+            MergeStrategy::FastForward => "fast_forward",
+            MergeStrategy::SquashFastForward => "squash_fast_forward",
+            MergeStrategy::RebaseFastForward => "rebase_fast_forward",
+            MergeStrategy::RebaseMerge => "rebase_merge",
+        }
+    }
+
+    /// Whether the pull request's commits become one, with a message of its own
+    pub fn squashes(&self) -> bool {
+        matches!(
+            self,
+            MergeStrategy::Squash | MergeStrategy::SquashFastForward
+        )
+    }
+
+    /// The strategy an API name stands for; `None` for ones this client
+    /// can't request
+    pub fn from_api(name: &str) -> Option<Self> {
+        match name {
+            "merge_commit" => Some(MergeStrategy::MergeCommit),
+            "squash" => Some(MergeStrategy::Squash),
+            "fast_forward" => Some(MergeStrategy::FastForward),
+            "squash_fast_forward" => Some(MergeStrategy::SquashFastForward),
+            "rebase_fast_forward" => Some(MergeStrategy::RebaseFastForward),
+            "rebase_merge" => Some(MergeStrategy::RebaseMerge),
+            _ => None,
+        }
+    }
 }
 
 impl Default for MergePullRequestRequest {
@@ -184,6 +245,41 @@ pub struct PullRequestComment {
     pub links: Option<CommentLinks>,
 }
 
+/// One entry of a pull request's activity; only reviews are read
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequestActivity {
+    pub approval: Option<ActivityReview>,
+    pub changes_requested: Option<ActivityReview>,
+    pub comment: Option<ActivityComment>,
+}
+
+/// An approval or request for changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityReview {
+    pub date: DateTime<Utc>,
+    pub user: User,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityComment {
+    pub created_on: DateTime<Utc>,
+    pub user: User,
+}
+
+impl PullRequestActivity {
+    /// Who reviewed and when, if this entry is a review
+    pub fn review(&self) -> Option<(&User, DateTime<Utc>)> {
+        self.approval
+            .as_ref()
+            .or(self.changes_requested.as_ref())
+            .map(|r| (&r.user, r.date))
+            .or_else(|| self.comment.as_ref().map(|c| (&c.user, c.created_on)))
+    }
+}
+
+/// Comments on commits have the same shape as pull request comments
+pub type CommitComment = PullRequestComment;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CommentContent {
     pub raw: String,
@@ -209,3 +305,52 @@ pub struct CommentLinks {
     pub self_link: Option<Link>,
     pub html: Option<Link>,
 }
+
+/// A build status reported against a commit, e.g. by Pipelines or CI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitStatus {
+    pub key: String,
+    pub name: Option<String>,
+    pub state: CommitStatusState,
+    pub url: Option<String>,
+    pub updated_on: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum CommitStatusState {
+    Successful,
+    Failed,
+    Inprogress,
+    Stopped,
+    /// A value this version does not know about
+    #[serde(other)]
+    Unknown,
+}
+
+/// How one file changes in a diff
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffStat {
+    /// `added`, `removed`, `modified`, `renamed`, or for pull requests that
+    /// can't merge cleanly, `merge conflict`
+    pub status: String,
+    #[serde(default)]
+    pub lines_added: u64,
+    #[serde(default)]
+    pub lines_removed: u64,
+    pub old: Option<DiffStatFile>,
+    pub new: Option<DiffStatFile>,
+}
+
+impl DiffStat {
+    pub fn is_conflict(&self) -> bool {
+        self.status == "merge conflict"
+            || self.status == "local deleted"
+            || self.status == "remote deleted"
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffStatFile {
+    pub path: String,
+}
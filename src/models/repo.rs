@@ -1,6 +1,7 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use super::pr::Commit;
 use super::user::{Link, User, Workspace};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,6 +29,34 @@ pub struct Repository {
     pub repo_type: Option<String>,
 }
 
+impl Repository {
+    /// The clone URL named `name` ("https" or "ssh")
+    pub fn clone_url(&self, name: &str) -> Option<&str> {
+        self.links
+            .as_ref()?
+            .clone
+            .as_ref()?
+            .iter()
+            .find(|link| link.name == name)
+            .map(|link| link.href.as_str())
+    }
+}
+
+/// A file in a repository's Downloads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Download {
+    pub name: String,
+    pub size: Option<u64>,
+    pub created_on: Option<DateTime<Utc>>,
+    pub links: Option<DownloadLinks>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadLinks {
+    #[serde(rename = "self")]
+    pub self_link: Option<Link>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RepositoryLinks {
     #[serde(rename = "self")]
@@ -59,6 +88,56 @@ pub struct Branch {
     pub branch_type: Option<String>,
 }
 
+/// A branch or tag with the commit it points at
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ref {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ref_type: Option<String>,
+    pub target: Commit,
+    /// Annotated tags only
+    pub message: Option<String>,
+    /// Annotated tags only
+    pub date: Option<DateTime<Utc>>,
+}
+
+/// A rule on which branches it applies to and what it requires or forbids
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchRestriction {
+    pub id: u64,
+    /// e.g. `require_approvals_to_merge`, `require_passing_builds_to_merge`, `push`
+    pub kind: String,
+    /// `glob` for `pattern`, or `branching_model` for `branch_type`
+    pub branch_match_kind: Option<String>,
+    pub pattern: Option<String>,
+    pub branch_type: Option<String>,
+    /// The count for `require_*` kinds
+    pub value: Option<u32>,
+}
+
+/// The branches a repository's branching model names
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchingModel {
+    pub development: Option<BranchingModelBranch>,
+    pub production: Option<BranchingModelBranch>,
+    /// The enabled kinds of branch and their prefixes
+    #[serde(default)]
+    pub branch_types: Vec<BranchType>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchingModelBranch {
+    pub name: Option<String>,
+    pub branch: Option<Branch>,
+}
+
+/// A kind of branch in a branching model, e.g. `feature` with `feature/`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchType {
+    pub kind: String,
+    pub prefix: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Project {
     pub uuid: String,
@@ -88,6 +167,22 @@ pub struct CreateRepositoryRequest {
     pub has_wiki: Option<bool>,
 }
 
+/// A file or directory listed by the `src` endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceEntry {
+    pub path: String,
+    /// `commit_file` or `commit_directory`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub size: Option<u64>,
+}
+
+impl SourceEntry {
+    pub fn is_file(&self) -> bool {
+        self.kind == "commit_file"
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProjectKey {
     pub key: String,
@@ -108,3 +203,54 @@ impl Default for CreateRepositoryRequest {
         }
     }
 }
+
+/// A file found by workspace code search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeSearchResult {
+    pub file: SearchedFile,
+    #[serde(default)]
+    pub content_matches: Vec<ContentMatch>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchedFile {
+    pub path: String,
+    pub commit: Option<SearchedCommit>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchedCommit {
+    pub repository: Option<RepositoryRef>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepositoryRef {
+    pub full_name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentMatch {
+    #[serde(default)]
+    pub lines: Vec<MatchedLine>,
+}
+
+/// A line around a match, split into segments that did and didn't match
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchedLine {
+    pub line: u64,
+    #[serde(default)]
+    pub segments: Vec<LineSegment>,
+}
+
+impl MatchedLine {
+    pub fn text(&self) -> String {
+        self.segments.iter().map(|s| s.text.as_str()).collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineSegment {
+    pub text: String,
+    #[serde(default, rename = "match")]
+    pub matched: bool,
+}
@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::user::{Link, User};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snippet {
+    /// Snippet ID; the API has returned it both as a number and as the
+    /// encoded string used in URLs
+    #[serde(deserialize_with = "string_or_number")]
+    pub id: String,
+    pub title: String,
+    pub scm: Option<String>,
+    pub is_private: Option<bool>,
+    pub created_on: Option<DateTime<Utc>>,
+    pub updated_on: Option<DateTime<Utc>>,
+    pub owner: Option<User>,
+    pub creator: Option<User>,
+    #[serde(default)]
+    pub files: BTreeMap<String, SnippetFile>,
+    pub links: Option<SnippetLinks>,
+}
+
+impl Snippet {
+    /// The ID used in snippet URLs, taken from the self link when present
+    pub fn encoded_id(&self) -> &str {
+        self.links
+            .as_ref()
+            .and_then(|links| links.self_link.as_ref())
+            .and_then(|link| link.href.trim_end_matches('/').rsplit('/').next())
+            .filter(|id| !id.is_empty())
+            .unwrap_or(&self.id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnippetLinks {
+    #[serde(rename = "self")]
+    pub self_link: Option<Link>,
+    pub html: Option<Link>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnippetFile {
+    pub links: Option<SnippetFileLinks>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnippetFileLinks {
+    #[serde(rename = "self")]
+    pub self_link: Option<Link>,
+    pub html: Option<Link>,
+}
+
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Id {
+        String(String),
+        Number(u64),
+    }
+
+    Ok(match Id::deserialize(deserializer)? {
+        Id::String(id) => id,
+        Id::Number(id) => id.to_string(),
+    })
+}
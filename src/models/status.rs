@@ -0,0 +1,43 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Bitbucket Cloud's status page summary, from Atlassian Statuspage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusSummary {
+    pub status: StatusIndicator,
+    #[serde(default)]
+    pub components: Vec<StatusComponent>,
+    /// Incidents not yet resolved
+    #[serde(default)]
+    pub incidents: Vec<StatusIncident>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusIndicator {
+    /// `none`, `minor`, `major` or `critical`
+    pub indicator: String,
+    /// e.g. "All Systems Operational"
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusComponent {
+    pub name: String,
+    /// `operational`, `degraded_performance`, `partial_outage`,
+    /// `major_outage` or `under_maintenance`
+    pub status: String,
+    /// Whether this only groups other components
+    #[serde(default)]
+    pub group: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusIncident {
+    pub name: String,
+    /// `investigating`, `identified`, `monitoring`, ...
+    pub status: String,
+    /// `none`, `minor`, `major` or `critical`
+    pub impact: String,
+    pub shortlink: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
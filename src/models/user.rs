@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 pub struct User {
     pub uuid: String,
     pub username: Option<String>,
+    pub nickname: Option<String>,
     pub display_name: String,
     pub account_id: Option<String>,
     #[serde(rename = "type")]
@@ -39,6 +40,12 @@ pub struct WorkspaceLinks {
     pub avatar: Option<Link>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceMembership {
+    pub user: User,
+    pub workspace: Option<Workspace>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Paginated<T> {
     pub size: Option<u32>,
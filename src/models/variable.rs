@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+/// A Pipelines variable, defined on a workspace or a repository
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineVariable {
+    pub uuid: Option<String>,
+    pub key: String,
+    /// Absent for secured variables, which can't be read back
+    pub value: Option<String>,
+    #[serde(default)]
+    pub secured: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PipelineVariableRequest {
+    pub key: String,
+    pub value: String,
+    pub secured: bool,
+}
@@ -0,0 +1,58 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Events a repository webhook can subscribe to, for when Bitbucket's
+/// catalog can't be fetched
+pub const WEBHOOK_EVENTS: &[&str] = &[
+    "repo:push",
+    "repo:fork",
+    "repo:updated",
+    "repo:commit_comment_created",
+    "repo:commit_status_created",
+    "repo:commit_status_updated",
+    "issue:created",
+    "issue:updated",
+    "issue:comment_created",
+    "pullrequest:created",
+    "pullrequest:updated",
+    "pullrequest:approved",
+    "pullrequest:unapproved",
+    "pullrequest:changes_request_created",
+    "pullrequest:changes_request_removed",
+    "pullrequest:fulfilled",
+    "pullrequest:rejected",
+    "pullrequest:comment_created",
+    "pullrequest:comment_updated",
+    "pullrequest:comment_deleted",
+    "pullrequest:comment_resolved",
+    "pullrequest:comment_reopened",
+];
+
+/// An entry in Bitbucket's catalog of webhook events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookEvent {
+    pub event: String,
+    pub category: Option<String>,
+    pub label: Option<String>,
+    pub description: Option<String>,
+}
+
+/// A repository webhook subscription
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub uuid: String,
+    pub url: String,
+    pub description: Option<String>,
+    pub active: bool,
+    #[serde(default)]
+    pub events: Vec<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreateWebhookRequest {
+    pub description: String,
+    pub url: String,
+    pub active: bool,
+    pub events: Vec<String>,
+}
@@ -0,0 +1,804 @@
+//! Changes made from the TUI
+//!
+//! Keys and modals queue an [`Action`]; the main loop runs it with
+//! [`App::perform`]. The change is shown straight away and put back if the
+//! API call fails.
+
+use futures::{StreamExt, TryStreamExt};
+
+use super::app::App;
+use super::diff::DiffView;
+use super::export::{self, ExportFormat};
+use super::modal::Modal;
+use super::views::View;
+use crate::models::{
+    InlineComment, Issue, IssueState, Pipeline, PipelineStateName, PullRequest,
+    TriggerPipelineRequest, UpdateIssueRequest, UserAccountId,
+};
+
+/// How many requests a batch action has in flight at once
+const BATCH_CONCURRENCY: usize = 4;
+
+/// Something to do to the item at `index` in its view's list
+pub enum Action {
+    CommentOnIssue {
+        index: usize,
+        body: String,
+    },
+    AssignIssueToMe {
+        index: usize,
+    },
+    SetIssueState {
+        index: usize,
+        state: IssueState,
+    },
+    VoteForIssue {
+        index: usize,
+    },
+    WatchIssue {
+        index: usize,
+    },
+    /// Fetch the pull request's diff and inline comments and show them
+    OpenDiff {
+        index: usize,
+    },
+    /// Comment on a line of the open diff
+    CommentOnDiffLine {
+        inline: InlineComment,
+        body: String,
+    },
+    /// Fetch the branches of the pipeline's repository and ask which to run on
+    ChooseBranch {
+        index: usize,
+    },
+    /// Ask whether to run the default or a custom pipeline on `branch`
+    ChoosePipeline {
+        repository: String,
+        branch: String,
+    },
+    TriggerPipeline {
+        repository: String,
+        branch: String,
+        /// A custom pipeline's name; the branch's default pipeline when `None`
+        pipeline: Option<String>,
+    },
+    StopPipeline {
+        index: usize,
+    },
+    /// Apply `kind` to every item at `indices`
+    Batch {
+        kind: BatchKind,
+        indices: Vec<usize>,
+    },
+    /// Write the current view's list to a file
+    Export {
+        format: ExportFormat,
+    },
+    /// Open another modal, for prompts that lead on to further prompts
+    Prompt(Box<Modal>),
+}
+
+/// Actions that can be applied to several marked items at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchKind {
+    Approve,
+    Decline,
+    Close,
+    Rerun,
+}
+
+impl BatchKind {
+    /// The batch actions for the items in `view`
+    pub fn for_view(view: View) -> &'static [BatchKind] {
+        match view {
+            View::PullRequests => &[BatchKind::Approve, BatchKind::Decline],
+            View::Issues => &[BatchKind::Close],
+            View::Pipelines => &[BatchKind::Rerun],
+            View::Dashboard | View::Repositories => &[],
+        }
+    }
+
+    /// Menu label for applying this to `count` items
+    pub fn label(self, count: usize) -> String {
+        let (verb, noun) = match self {
+            BatchKind::Approve => ("Approve", "pull request"),
+            BatchKind::Decline => ("Decline", "pull request"),
+            BatchKind::Close => ("Close", "issue"),
+            BatchKind::Rerun => ("Re-run", "pipeline"),
+        };
+        format!(
+            "{} {} {}{}",
+            verb,
+            count,
+            noun,
+            if count == 1 { "" } else { "s" }
+        )
+    }
+
+    fn past_tense(self) -> &'static str {
+        match self {
+            BatchKind::Approve => "approved",
+            BatchKind::Decline => "declined",
+            BatchKind::Close => "closed",
+            BatchKind::Rerun => "re-run",
+        }
+    }
+}
+
+/// What a batch action did to one item, for the result list
+enum Done {
+    Approved,
+    Declined(Box<PullRequest>),
+    Closed(Box<Issue>),
+    Rerun(Box<Pipeline>),
+}
+
+/// A request running `pipeline`'s pipeline again on the same branch or tag
+fn rerun_request(pipeline: &Pipeline) -> Option<TriggerPipelineRequest> {
+    let target = &pipeline.target;
+    let ref_name = target.ref_name.as_deref()?;
+    let mut request = match &target.selector {
+        Some(selector) if selector.selector_type == "custom" => {
+            TriggerPipelineRequest::for_branch_with_pipeline(ref_name, selector.pattern.as_deref()?)
+        }
+        _ => TriggerPipelineRequest::for_branch(ref_name),
+    };
+    if let Some(ref_type) = &target.ref_type {
+        request.target.ref_type = ref_type.clone();
+    }
+    Some(request)
+}
+
+/// Whether `pipeline` is still going and so can be stopped
+pub fn is_running(pipeline: &Pipeline) -> bool {
+    matches!(
+        pipeline.state.name,
+        PipelineStateName::Pending | PipelineStateName::InProgress | PipelineStateName::Paused
+    )
+}
+
+/// States offered by the issue state menu
+pub const ISSUE_STATES: [IssueState; 7] = [
+    IssueState::New,
+    IssueState::Open,
+    IssueState::Resolved,
+    IssueState::OnHold,
+    IssueState::Invalid,
+    IssueState::Duplicate,
+    IssueState::Wontfix,
+];
+
+impl App {
+    /// Run `action`, calling `redraw` once the optimistic change is made so
+    /// it shows while the request is in flight
+    pub async fn perform(&mut self, action: Action, mut redraw: impl FnMut(&App)) {
+        self.clear_error();
+        match action {
+            Action::CommentOnIssue { index, body } => {
+                let Some((issue, workspace, repo_slug)) = self.issue_at(index) else {
+                    return;
+                };
+                self.set_status(&format!("Commenting on #{}...", issue.id));
+                redraw(self);
+                let result = match &self.client {
+                    Some(client) => {
+                        client
+                            .add_issue_comment(&workspace, &repo_slug, issue.id, &body)
+                            .await
+                    }
+                    None => return,
+                };
+                match result {
+                    Ok(_) => self.set_status(&format!("Commented on #{}", issue.id)),
+                    Err(e) => self.set_error(&format!("Failed to comment: {}", e)),
+                }
+            }
+
+            Action::AssignIssueToMe { index } => {
+                let Some(me) = self.current_user().await else {
+                    return;
+                };
+                let Some(account_id) = me.account_id.clone() else {
+                    self.set_error("Your account has no account ID to assign");
+                    return;
+                };
+                let request = UpdateIssueRequest {
+                    assignee: Some(UserAccountId { account_id }),
+                    ..Default::default()
+                };
+                self.edit_issue(index, request, redraw, |issue| {
+                    issue.assignee = Some(me);
+                })
+                .await;
+            }
+
+            Action::SetIssueState { index, state } => {
+                let request = UpdateIssueRequest {
+                    state: Some(state.clone()),
+                    ..Default::default()
+                };
+                self.edit_issue(index, request, redraw, |issue| issue.state = state)
+                    .await;
+            }
+
+            Action::VoteForIssue { index } => {
+                let Some((issue, workspace, repo_slug)) = self.issue_at(index) else {
+                    return;
+                };
+                self.issues[index].votes = Some(issue.votes.unwrap_or(0) + 1);
+                self.set_status(&format!("Voting for #{}...", issue.id));
+                redraw(self);
+                let Some(client) = &self.client else { return };
+                match client.vote_issue(&workspace, &repo_slug, issue.id).await {
+                    Ok(()) => self.set_status(&format!("Voted for #{}", issue.id)),
+                    Err(e) => self.undo(index, issue, &format!("Failed to vote: {}", e)),
+                }
+            }
+
+            Action::WatchIssue { index } => {
+                let Some((issue, workspace, repo_slug)) = self.issue_at(index) else {
+                    return;
+                };
+                self.issues[index].watches = Some(issue.watches.unwrap_or(0) + 1);
+                self.set_status(&format!("Watching #{}...", issue.id));
+                redraw(self);
+                let Some(client) = &self.client else { return };
+                match client.watch_issue(&workspace, &repo_slug, issue.id).await {
+                    Ok(()) => self.set_status(&format!("Watching #{}", issue.id)),
+                    Err(e) => self.undo(index, issue, &format!("Failed to watch: {}", e)),
+                }
+            }
+
+            Action::OpenDiff { index } => {
+                let Some((pr, workspace, repo_slug)) = self.pull_request_at(index) else {
+                    return;
+                };
+                self.set_status(&format!("Loading the diff of #{}...", pr.id));
+                redraw(self);
+                let Some(client) = &self.client else { return };
+                let (diff, comments) = tokio::join!(
+                    client.get_pr_diff(&workspace, &repo_slug, pr.id),
+                    client
+                        .stream_pr_comments(&workspace, &repo_slug, pr.id)
+                        .try_collect::<Vec<_>>(),
+                );
+                match (diff, comments) {
+                    (Ok(diff), Ok(comments)) => {
+                        self.clear_status();
+                        self.diff = Some(DiffView::new(
+                            format!("{}/{}", workspace, repo_slug),
+                            pr.id,
+                            &diff,
+                            comments,
+                        ));
+                    }
+                    (Err(e), _) | (_, Err(e)) => {
+                        self.clear_status();
+                        self.set_error(&format!("Failed to load the diff of #{}: {}", pr.id, e));
+                    }
+                }
+            }
+
+            Action::CommentOnDiffLine { inline, body } => {
+                let Some(diff) = &self.diff else { return };
+                let Some((workspace, repo_slug)) = diff.repository.split_once('/') else {
+                    return;
+                };
+                let (workspace, repo_slug, pr_id) =
+                    (workspace.to_string(), repo_slug.to_string(), diff.pr_id);
+                self.set_status(&format!("Commenting on {}...", inline.path));
+                redraw(self);
+                let Some(client) = &self.client else { return };
+                match client
+                    .add_pr_comment(&workspace, &repo_slug, pr_id, &body, Some(&inline))
+                    .await
+                {
+                    Ok(mut comment) => {
+                        self.toast(&format!("Commented on {}", inline.path));
+                        // Shown under the line even if the response leaves the anchor out
+                        comment.inline = comment.inline.or(Some(inline));
+                        if let Some(diff) = self.diff.as_mut().filter(|d| d.pr_id == pr_id) {
+                            diff.comments.push(comment);
+                        }
+                    }
+                    Err(e) => {
+                        self.clear_status();
+                        self.set_error(&format!("Failed to comment: {}", e));
+                    }
+                }
+            }
+
+            Action::ChooseBranch { index } => {
+                let Some((_, workspace, repo_slug)) = self.pipeline_at(index) else {
+                    return;
+                };
+                self.set_status(&format!(
+                    "Loading branches of {}/{}...",
+                    workspace, repo_slug
+                ));
+                redraw(self);
+                let Some(client) = &self.client else { return };
+                let (branches, main) = tokio::join!(
+                    client
+                        .stream_branches(&workspace, &repo_slug)
+                        .try_collect::<Vec<_>>(),
+                    client.get_main_branch(&workspace, &repo_slug),
+                );
+                let mut branches = match branches {
+                    Ok(branches) => branches,
+                    Err(e) => {
+                        self.clear_status();
+                        self.set_error(&format!("Failed to load branches: {}", e));
+                        return;
+                    }
+                };
+                // The main branch is the likeliest choice, so it goes first
+                if let Ok(main) = main {
+                    branches.sort_by_key(|branch| branch.name != main.name);
+                }
+
+                let repository = format!("{}/{}", workspace, repo_slug);
+                let items = branches
+                    .into_iter()
+                    .map(|branch| {
+                        let choose = Action::ChoosePipeline {
+                            repository: repository.clone(),
+                            branch: branch.name.clone(),
+                        };
+                        (branch.name, choose)
+                    })
+                    .collect();
+                self.clear_status();
+                self.modal = Some(Modal::menu(
+                    format!("Trigger a pipeline in {}", repository),
+                    items,
+                    0,
+                ));
+            }
+
+            Action::ChoosePipeline { repository, branch } => {
+                let title = format!("Which pipeline on {}?", branch);
+                let custom_title = format!("Custom pipeline to run on {}", branch);
+                let trigger = move |pipeline: Option<String>| {
+                    let question = match &pipeline {
+                        Some(name) => format!("Run custom pipeline '{}' on {}?", name, branch),
+                        None => format!("Run the default pipeline on {}?", branch),
+                    };
+                    Modal::confirm(
+                        format!("Trigger in {}", repository),
+                        question,
+                        Action::TriggerPipeline {
+                            repository: repository.clone(),
+                            branch: branch.clone(),
+                            pipeline,
+                        },
+                    )
+                };
+                let default = trigger(None);
+                let custom = Modal::input(
+                    custom_title,
+                    Box::new(move |name| Action::Prompt(Box::new(trigger(Some(name))))),
+                );
+                self.modal = Some(Modal::menu(
+                    title,
+                    vec![
+                        (
+                            "Default pipeline".to_string(),
+                            Action::Prompt(Box::new(default)),
+                        ),
+                        (
+                            "Custom pipeline...".to_string(),
+                            Action::Prompt(Box::new(custom)),
+                        ),
+                    ],
+                    0,
+                ));
+            }
+
+            Action::TriggerPipeline {
+                repository,
+                branch,
+                pipeline,
+            } => {
+                let Some((workspace, repo_slug)) = repository.split_once('/') else {
+                    return;
+                };
+                let request = match &pipeline {
+                    Some(name) => TriggerPipelineRequest::for_branch_with_pipeline(&branch, name),
+                    None => TriggerPipelineRequest::for_branch(&branch),
+                };
+                self.set_status(&format!("Triggering a pipeline on {}...", branch));
+                redraw(self);
+                let Some(client) = &self.client else { return };
+                match client
+                    .trigger_pipeline(workspace, repo_slug, &request)
+                    .await
+                {
+                    Ok(mut triggered) => {
+                        self.toast(&format!(
+                            "Triggered pipeline #{} on {}",
+                            triggered.build_number, branch
+                        ));
+                        if triggered.repository.is_none() {
+                            triggered.repository = self
+                                .pipelines
+                                .iter()
+                                .filter_map(|p| p.repository.as_ref())
+                                .find(|r| r.full_name == repository)
+                                .cloned();
+                        }
+                        self.prepend_pipelines(vec![triggered]);
+                    }
+                    Err(e) => {
+                        self.clear_status();
+                        self.set_error(&format!("Failed to trigger a pipeline: {}", e));
+                    }
+                }
+            }
+
+            Action::StopPipeline { index } => {
+                let Some((pipeline, workspace, repo_slug)) = self.pipeline_at(index) else {
+                    return;
+                };
+                self.set_status(&format!("Stopping pipeline #{}...", pipeline.build_number));
+                redraw(self);
+                let Some(client) = &self.client else { return };
+                if let Err(e) = client
+                    .stop_pipeline(&workspace, &repo_slug, &pipeline.uuid)
+                    .await
+                {
+                    self.clear_status();
+                    self.set_error(&format!(
+                        "Failed to stop pipeline #{}: {}",
+                        pipeline.build_number, e
+                    ));
+                    return;
+                }
+                // Show the state it's in now it's been asked to stop
+                if let Ok(mut stopped) = client
+                    .get_pipeline(&workspace, &repo_slug, &pipeline.uuid)
+                    .await
+                    && let Some(slot) = self.pipelines.get_mut(index)
+                    && slot.uuid == pipeline.uuid
+                {
+                    stopped.repository = stopped.repository.or(pipeline.repository);
+                    *slot = stopped;
+                }
+                self.toast(&format!("Stopped pipeline #{}", pipeline.build_number));
+            }
+
+            Action::Batch { kind, indices } => {
+                let targets: Vec<(usize, String, String, String)> = indices
+                    .into_iter()
+                    .filter_map(|index| {
+                        let (label, repository) = match kind {
+                            BatchKind::Approve | BatchKind::Decline => {
+                                let pr = self.pull_requests.get(index)?;
+                                (format!("#{}", pr.id), pr.destination.repository.as_ref())
+                            }
+                            BatchKind::Close => {
+                                let issue = self.issues.get(index)?;
+                                (format!("#{}", issue.id), issue.repository.as_ref())
+                            }
+                            BatchKind::Rerun => {
+                                let pipeline = self.pipelines.get(index)?;
+                                (
+                                    format!("pipeline #{}", pipeline.build_number),
+                                    pipeline.repository.as_ref(),
+                                )
+                            }
+                        };
+                        let (workspace, repo_slug) = repository?.full_name.split_once('/')?;
+                        Some((
+                            index,
+                            format!("{}/{} {}", workspace, repo_slug, label),
+                            workspace.to_string(),
+                            repo_slug.to_string(),
+                        ))
+                    })
+                    .collect();
+                self.marked.clear();
+                self.set_status(&format!("{}...", kind.label(targets.len())));
+                redraw(self);
+
+                let Some(client) = &self.client else { return };
+                let (pull_requests, issues, pipelines) =
+                    (&self.pull_requests, &self.issues, &self.pipelines);
+                let results: Vec<(usize, String, Result<Done, String>)> =
+                    futures::stream::iter(targets)
+                        .map(|(index, label, workspace, repo_slug)| async move {
+                            let result = match kind {
+                                BatchKind::Approve => client
+                                    .approve_pull_request(
+                                        &workspace,
+                                        &repo_slug,
+                                        pull_requests[index].id,
+                                    )
+                                    .await
+                                    .map(|()| Done::Approved),
+                                BatchKind::Decline => client
+                                    .decline_pull_request(
+                                        &workspace,
+                                        &repo_slug,
+                                        pull_requests[index].id,
+                                    )
+                                    .await
+                                    .map(|pr| Done::Declined(Box::new(pr))),
+                                BatchKind::Close => {
+                                    let request = UpdateIssueRequest {
+                                        state: Some(IssueState::Closed),
+                                        ..Default::default()
+                                    };
+                                    client
+                                        .edit_issue(
+                                            &workspace,
+                                            &repo_slug,
+                                            issues[index].id,
+                                            &request,
+                                        )
+                                        .await
+                                        .map(|issue| Done::Closed(Box::new(issue)))
+                                }
+                                BatchKind::Rerun => match rerun_request(&pipelines[index]) {
+                                    Some(request) => client
+                                        .trigger_pipeline(&workspace, &repo_slug, &request)
+                                        .await
+                                        .map(|pipeline| Done::Rerun(Box::new(pipeline))),
+                                    None => {
+                                        return (
+                                            index,
+                                            label,
+                                            Err("it didn't run on a branch or tag".to_string()),
+                                        );
+                                    }
+                                },
+                            };
+                            (index, label, result.map_err(|e| e.to_string()))
+                        })
+                        .buffer_unordered(BATCH_CONCURRENCY)
+                        .collect()
+                        .await;
+
+                let mut results = results;
+                results.sort_by_key(|(index, ..)| *index);
+                let mut lines = Vec::new();
+                let mut failed = 0;
+                let mut reruns = Vec::new();
+                for (index, label, result) in results {
+                    match result {
+                        Ok(done) => {
+                            match done {
+                                Done::Approved => {}
+                                Done::Declined(mut pr) => {
+                                    pr.destination.repository = pr.destination.repository.or(self
+                                        .pull_requests[index]
+                                        .destination
+                                        .repository
+                                        .take());
+                                    self.pull_requests[index] = *pr;
+                                }
+                                Done::Closed(mut issue) => {
+                                    issue.repository =
+                                        issue.repository.or(self.issues[index].repository.take());
+                                    self.issues[index] = *issue;
+                                }
+                                Done::Rerun(mut pipeline) => {
+                                    pipeline.repository = pipeline
+                                        .repository
+                                        .or(self.pipelines[index].repository.clone());
+                                    reruns.push(*pipeline);
+                                }
+                            }
+                            lines.push((true, format!("{} {}", label, kind.past_tense())));
+                        }
+                        Err(e) => {
+                            failed += 1;
+                            lines.push((false, format!("{}: {}", label, e)));
+                        }
+                    }
+                }
+                // New runs go on top, as a single trigger's does
+                self.prepend_pipelines(reruns);
+
+                let total = lines.len();
+                if failed == 0 {
+                    self.toast(&format!("{} of {} done", total, total));
+                } else {
+                    self.clear_status();
+                    self.set_error(&format!("{} of {} failed", failed, total));
+                }
+                self.modal = Some(Modal::report(kind.label(total), lines));
+            }
+
+            Action::Export { format } => match export::export(self, format) {
+                Ok(Some((path, count))) => {
+                    self.toast(&format!("Exported {} rows to {}", count, path.display()));
+                }
+                Ok(None) => self.toast("Nothing to export in this view"),
+                Err(e) => self.set_error(&format!("Failed to export: {:#}", e)),
+            },
+
+            Action::Prompt(modal) => self.modal = Some(*modal),
+        }
+    }
+
+    /// Put new runs at the top of the pipeline list, marks moving with them
+    fn prepend_pipelines(&mut self, pipelines: Vec<Pipeline>) {
+        let added = pipelines.len();
+        self.pipelines.splice(0..0, pipelines);
+        if self.current_view == View::Pipelines {
+            self.marked = self.marked.iter().map(|index| index + added).collect();
+        }
+    }
+
+    /// The pipeline at `index` with its workspace and repository slug
+    fn pipeline_at(&mut self, index: usize) -> Option<(Pipeline, String, String)> {
+        let pipeline = self.pipelines.get(index)?.clone();
+        let full_name = pipeline.repository.as_ref().map(|r| r.full_name.clone());
+        match full_name.as_deref().and_then(|name| name.split_once('/')) {
+            Some((workspace, repo_slug)) => {
+                Some((pipeline, workspace.to_string(), repo_slug.to_string()))
+            }
+            None => {
+                self.set_error(&format!(
+                    "Don't know which repository pipeline #{} is in",
+                    pipeline.build_number
+                ));
+                None
+            }
+        }
+    }
+
+    /// The pull request at `index` with its workspace and repository slug
+    fn pull_request_at(&mut self, index: usize) -> Option<(PullRequest, String, String)> {
+        let pr = self.pull_requests.get(index)?.clone();
+        let full_name = pr
+            .destination
+            .repository
+            .as_ref()
+            .map(|r| r.full_name.clone());
+        match full_name.as_deref().and_then(|name| name.split_once('/')) {
+            Some((workspace, repo_slug)) => {
+                Some((pr, workspace.to_string(), repo_slug.to_string()))
+            }
+            None => {
+                self.set_error(&format!("Don't know which repository #{} is in", pr.id));
+                None
+            }
+        }
+    }
+
+    /// The issue at `index` with its workspace and repository slug
+    fn issue_at(&mut self, index: usize) -> Option<(Issue, String, String)> {
+        let issue = self.issues.get(index)?.clone();
+        let full_name = issue.repository.as_ref().map(|r| r.full_name.clone());
+        match full_name.as_deref().and_then(|name| name.split_once('/')) {
+            Some((workspace, repo_slug)) => {
+                Some((issue, workspace.to_string(), repo_slug.to_string()))
+            }
+            None => {
+                self.set_error(&format!("Don't know which repository #{} is in", issue.id));
+                None
+            }
+        }
+    }
+
+    /// Apply `change` locally, then send `request`, keeping what the API
+    /// returns or restoring the issue if it fails
+    async fn edit_issue(
+        &mut self,
+        index: usize,
+        request: UpdateIssueRequest,
+        mut redraw: impl FnMut(&App),
+        change: impl FnOnce(&mut Issue),
+    ) {
+        let Some((issue, workspace, repo_slug)) = self.issue_at(index) else {
+            return;
+        };
+        change(&mut self.issues[index]);
+        self.set_status(&format!("Updating #{}...", issue.id));
+        redraw(self);
+
+        let Some(client) = &self.client else { return };
+        match client
+            .edit_issue(&workspace, &repo_slug, issue.id, &request)
+            .await
+        {
+            Ok(mut updated) => {
+                updated.repository = updated.repository.or(issue.repository);
+                self.set_status(&format!("Updated #{}", updated.id));
+                self.issues[index] = updated;
+            }
+            Err(e) => {
+                let error = format!("Failed to update #{}: {}", issue.id, e);
+                self.undo(index, issue, &error);
+            }
+        }
+    }
+
+    fn undo(&mut self, index: usize, issue: Issue, error: &str) {
+        if let Some(slot) = self.issues.get_mut(index) {
+            *slot = issue;
+        }
+        self.clear_status();
+        self.set_error(error);
+    }
+
+    /// The signed-in user, fetched on first use
+    async fn current_user(&mut self) -> Option<crate::models::User> {
+        if self.me.is_none() {
+            let client = self.client.as_ref()?;
+            match client.get_current_user().await {
+                Ok(user) => self.me = Some(user),
+                Err(e) => {
+                    self.set_error(&format!("Failed to look up your account: {}", e));
+                    return None;
+                }
+            }
+        }
+        self.me.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pipeline(target: serde_json::Value) -> Pipeline {
+        serde_json::from_value(serde_json::json!({
+            "uuid": "{1}",
+            "build_number": 7,
+            "target": target,
+            "state": { "name": "COMPLETED", "type": "pipeline_state_completed" },
+            "created_on": "2024-06-05T08:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn reruns_keep_the_ref_and_custom_pipeline() {
+        let tag = pipeline(serde_json::json!({
+            "type": "pipeline_ref_target",
+            "ref_type": "tag",
+            "ref_name": "v1.2",
+            "selector": { "type": "custom", "pattern": "release" },
+        }));
+        let target = rerun_request(&tag).unwrap().target;
+        assert_eq!(
+            (target.ref_type.as_str(), target.ref_name.as_str()),
+            ("tag", "v1.2")
+        );
+        assert_eq!(target.selector.unwrap().pattern, "release");
+
+        let branch = pipeline(serde_json::json!({
+            "type": "pipeline_ref_target",
+            "ref_type": "branch",
+            "ref_name": "main",
+            "selector": { "type": "branches", "pattern": "main" },
+        }));
+        assert!(rerun_request(&branch).unwrap().target.selector.is_none());
+
+        let commit = pipeline(serde_json::json!({ "type": "pipeline_commit_target" }));
+        assert!(rerun_request(&commit).is_none());
+    }
+
+    #[test]
+    fn marks_follow_their_pipelines_when_runs_are_added() {
+        let ref_target = serde_json::json!({ "type": "pipeline_commit_target" });
+        let mut app = App::new();
+        app.current_view = View::Pipelines;
+        app.pipelines = vec![pipeline(ref_target.clone()), pipeline(ref_target.clone())];
+        app.pipelines[1].build_number = 8;
+        app.marked.insert(1);
+
+        app.prepend_pipelines(vec![pipeline(ref_target)]);
+        assert_eq!(app.marked.iter().copied().collect::<Vec<_>>(), [2]);
+        assert_eq!(app.pipelines[2].build_number, 8);
+    }
+
+    #[test]
+    fn batch_labels_count_their_items() {
+        assert_eq!(BatchKind::Approve.label(1), "Approve 1 pull request");
+        assert_eq!(BatchKind::Rerun.label(3), "Re-run 3 pipelines");
+    }
+}
@@ -1,17 +1,50 @@
 use anyhow::Result;
+use chrono::{DateTime, Local};
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture},
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
-use ratatui::{Terminal, backend::CrosstermBackend};
+use futures::stream::{BoxStream, StreamExt};
+use ratatui::{
+    Terminal,
+    backend::{Backend, CrosstermBackend},
+};
+use std::collections::{BTreeSet, HashMap, VecDeque};
 use std::io;
+use std::sync::Once;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
+use super::action::{self, Action, BatchKind, ISSUE_STATES};
+use super::clone::{self, CloneJob};
+use super::diff::DiffView;
 use super::event::{Event, EventHandler};
+use super::export::ExportFormat;
+use super::modal::{self, Modal};
 use super::ui;
-use super::views::{View, ViewState};
+use super::unread::Seen;
+use super::views::{PipelineFilter, View, ViewState};
 use crate::api::BitbucketClient;
-use crate::models::{Issue, Pipeline, PullRequest, Repository};
+use crate::cli::cache::Metadata;
+use crate::cli::clipboard;
+use crate::config::Config;
+use crate::models::{Issue, Pipeline, PullRequest, Repository, User};
+
+/// Repositories fetched per batch while scrolling
+const REPOSITORY_BATCH: usize = 50;
+/// How close to the end of the list the selection gets before the next batch loads
+const SCROLL_AHEAD: usize = 5;
+/// Repositories whose pull requests, issues or pipelines load at once
+const LOAD_CONCURRENCY: usize = 8;
+/// Frames of the spinner shown on rows still loading
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+/// How long each spinner frame shows
+const SPINNER_FRAME: Duration = Duration::from_millis(100);
+/// How long a toast stays in the status line
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+/// How often keys are checked for while data loads
+const INPUT_POLL: Duration = Duration::from_millis(50);
 
 /// Application state
 pub struct App {
@@ -25,18 +58,53 @@ pub struct App {
     pub client: Option<BitbucketClient>,
     /// Current workspace
     pub workspace: Option<String>,
+    /// Workspaces `w` cycles through, from the metadata cache
+    pub workspaces: Vec<String>,
     /// Status message
     pub status: Option<String>,
+    /// When the status message, if it's a toast, goes away
+    toast_until: Option<Instant>,
     /// Is loading data
     pub loading: bool,
+    /// Repositories whose items for a view are still loading
+    pub pending: Vec<(View, String)>,
+    /// When the app started, to animate spinners from
+    started: Instant,
     /// Error message
     pub error: Option<String>,
+    /// Prompt taking keys over the current view
+    pub modal: Option<Modal>,
+    /// Changes waiting for the main loop to run them
+    pub actions: Vec<Action>,
+    /// Rows of the current view marked with space for a batch action
+    pub marked: BTreeSet<usize>,
+    /// Set by `r` (or a workspace switch) for the main loop to reload the view
+    pub refresh_requested: bool,
+    /// Set by `R` for the main loop to reload every view
+    pub refresh_all_requested: bool,
+    /// When each view's data last finished loading, shown in its tab
+    pub refreshed: HashMap<View, DateTime<Local>>,
+    /// The signed-in user, once something has needed it
+    pub me: Option<User>,
+    /// The clone started with `c`, shown in a panel until dismissed
+    pub clone: Option<CloneJob>,
+    /// The pull request diff opened with `d`, shown over the list until closed
+    pub diff: Option<DiffView>,
+    /// Which loaded pipelines the Pipelines view shows
+    pub pipeline_filter: PipelineFilter,
+    /// When each pull request and issue was last opened
+    pub seen: Seen,
+    /// Show only pull requests and issues updated since they were last opened
+    pub unread_only: bool,
 
     // Data
     pub repositories: Vec<Repository>,
     pub pull_requests: Vec<PullRequest>,
     pub issues: Vec<Issue>,
     pub pipelines: Vec<Pipeline>,
+
+    /// Repositories not yet loaded, fetched as the list is scrolled
+    repository_stream: Option<BoxStream<'static, crate::Result<Repository>>>,
 }
 
 impl App {
@@ -47,13 +115,30 @@ impl App {
             view_state: ViewState::default(),
             client: None,
             workspace: None,
+            workspaces: Vec::new(),
             status: None,
+            toast_until: None,
             loading: false,
+            pending: Vec::new(),
+            started: Instant::now(),
             error: None,
+            modal: None,
+            actions: Vec::new(),
+            marked: BTreeSet::new(),
+            refresh_requested: false,
+            refresh_all_requested: false,
+            refreshed: HashMap::new(),
+            me: None,
+            clone: None,
+            diff: None,
+            pipeline_filter: PipelineFilter::default(),
+            seen: Seen::load(),
+            unread_only: false,
             repositories: Vec::new(),
             pull_requests: Vec::new(),
             issues: Vec::new(),
             pipelines: Vec::new(),
+            repository_stream: None,
         }
     }
 
@@ -69,14 +154,64 @@ impl App {
         self
     }
 
+    /// Move to the next cached workspace, dropping data loaded for the
+    /// current one; false when there's nowhere to switch to
+    pub fn next_workspace(&mut self) -> bool {
+        let next = match &self.workspace {
+            Some(current) => self
+                .workspaces
+                .iter()
+                .position(|ws| ws == current)
+                .map_or(0, |i| (i + 1) % self.workspaces.len()),
+            None => 0,
+        };
+        let Some(next) = self.workspaces.get(next).cloned() else {
+            return false;
+        };
+        if self.workspace.as_ref() == Some(&next) {
+            return false;
+        }
+
+        tracing::debug!(workspace = %next, "switching workspace");
+        self.workspace = Some(next);
+        self.repositories.clear();
+        self.pull_requests.clear();
+        self.issues.clear();
+        self.pipelines.clear();
+        self.repository_stream = None;
+        self.refreshed.clear();
+        self.view_state = ViewState::default();
+        self.pipeline_filter = PipelineFilter::default();
+        self.marked.clear();
+        true
+    }
+
     /// Set status message
     pub fn set_status(&mut self, message: &str) {
         self.status = Some(message.to_string());
+        self.toast_until = None;
+    }
+
+    /// Set a status message that clears itself after a few seconds
+    pub fn toast(&mut self, message: &str) {
+        self.status = Some(message.to_string());
+        self.toast_until = Some(Instant::now() + TOAST_DURATION);
+    }
+
+    /// Clear a toast whose time is up
+    pub fn expire_toast(&mut self) {
+        if self
+            .toast_until
+            .is_some_and(|until| Instant::now() >= until)
+        {
+            self.clear_status();
+        }
     }
 
     /// Clear status message
     pub fn clear_status(&mut self) {
         self.status = None;
+        self.toast_until = None;
     }
 
     /// Set error message
@@ -91,8 +226,10 @@ impl App {
 
     /// Switch to a different view
     pub fn switch_view(&mut self, view: View) {
+        tracing::debug!(?view, "switching view");
         self.current_view = view;
         self.view_state.selected_index = 0;
+        self.marked.clear();
         self.clear_error();
     }
 
@@ -100,6 +237,20 @@ impl App {
     pub fn handle_key(&mut self, key: crossterm::event::KeyEvent) {
         use crossterm::event::KeyCode;
 
+        if let Some(open) = self.modal.take() {
+            let (open, outcome) = open.handle_key(key);
+            self.modal = open;
+            if let modal::Outcome::Done(action) = outcome {
+                self.actions.push(action);
+            }
+            return;
+        }
+
+        if self.diff.is_some() {
+            self.handle_diff_key(key.code);
+            return;
+        }
+
         // Global keys
         match key.code {
             KeyCode::Char('q') => {
@@ -128,6 +279,10 @@ impl App {
             }
             KeyCode::Esc => {
                 self.clear_error();
+                self.marked.clear();
+                if self.clone.as_ref().is_some_and(|job| !job.is_running()) {
+                    self.clone = None;
+                }
                 return;
             }
             _ => {}
@@ -139,20 +294,404 @@ impl App {
                 self.view_state.previous();
             }
             KeyCode::Down | KeyCode::Char('j') => {
-                let max = match self.current_view {
-                    View::Dashboard => 4,
-                    View::Repositories => self.repositories.len(),
-                    View::PullRequests => self.pull_requests.len(),
-                    View::Issues => self.issues.len(),
-                    View::Pipelines => self.pipelines.len(),
-                };
-                self.view_state.next(max);
+                self.view_state.next(self.list_len());
+            }
+            KeyCode::Char(' ') if !BatchKind::for_view(self.current_view).is_empty() => {
+                if let Some(index) = self.selected_item() {
+                    if !self.marked.remove(&index) {
+                        self.marked.insert(index);
+                    }
+                    self.view_state.next(self.list_len());
+                }
+            }
+            KeyCode::Char('b') if !BatchKind::for_view(self.current_view).is_empty() => {
+                self.open_batch_menu();
             }
             KeyCode::Enter => {
                 self.handle_select();
             }
             KeyCode::Char('r') => {
-                // Refresh will be handled in main loop
+                self.refresh_requested = true;
+            }
+            KeyCode::Char('R') => {
+                self.refresh_all_requested = true;
+            }
+            KeyCode::Char('w') if self.client.is_some() => {
+                self.refresh_requested |= self.next_workspace();
+            }
+            KeyCode::Char('E') if self.current_view != View::Dashboard => {
+                self.modal = Some(Modal::menu(
+                    "Export as",
+                    vec![
+                        (
+                            "JSON".to_string(),
+                            Action::Export {
+                                format: ExportFormat::Json,
+                            },
+                        ),
+                        (
+                            "CSV".to_string(),
+                            Action::Export {
+                                format: ExportFormat::Csv,
+                            },
+                        ),
+                    ],
+                    0,
+                ));
+            }
+            _ if self.current_view == View::PullRequests => self.handle_pull_request_key(key.code),
+            _ if self.current_view == View::Issues => self.handle_issue_key(key.code),
+            _ if self.current_view == View::Pipelines => self.handle_pipeline_key(key.code),
+            _ if self.current_view == View::Repositories => self.handle_repository_key(key.code),
+            _ => {}
+        }
+    }
+
+    /// How many rows the current view's list has
+    fn list_len(&self) -> usize {
+        match self.current_view {
+            View::Dashboard => 4,
+            View::Repositories => self.repositories.len(),
+            View::PullRequests => self.visible_pull_requests().len(),
+            View::Issues => self.visible_issues().len(),
+            View::Pipelines => self.visible_pipelines().len(),
+        }
+    }
+
+    /// Indices into `pipelines` of those the filter lets through, in order
+    pub fn visible_pipelines(&self) -> Vec<usize> {
+        self.pipelines
+            .iter()
+            .enumerate()
+            .filter(|(_, pipeline)| self.pipeline_filter.matches(pipeline))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Indices into `pull_requests` of those shown, in order
+    pub fn visible_pull_requests(&self) -> Vec<usize> {
+        self.pull_requests
+            .iter()
+            .enumerate()
+            .filter(|(_, pr)| !self.unread_only || self.seen.is_unread(*pr))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Indices into `issues` of those shown, in order
+    pub fn visible_issues(&self) -> Vec<usize> {
+        self.issues
+            .iter()
+            .enumerate()
+            .filter(|(_, issue)| !self.unread_only || self.seen.is_unread(*issue))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Index into the current view's items of the selected row, skipping
+    /// what the pipeline and unread filters hide
+    fn selected_item(&self) -> Option<usize> {
+        let row = self.view_state.selected_index;
+        match self.current_view {
+            View::PullRequests => self.visible_pull_requests().get(row).copied(),
+            View::Issues => self.visible_issues().get(row).copied(),
+            View::Pipelines => self.visible_pipelines().get(row).copied(),
+            _ => (row < self.list_len()).then_some(row),
+        }
+    }
+
+    /// Show only unread pull requests and issues, or all of them again
+    fn toggle_unread_only(&mut self) {
+        self.unread_only = !self.unread_only;
+        self.view_state.reset();
+        self.marked.clear();
+        self.toast(if self.unread_only {
+            "Showing unread only"
+        } else {
+            "Showing all"
+        });
+    }
+
+    /// Remember the pull request or issue at `index` of the current view as
+    /// read as it is now
+    fn mark_read(&mut self, index: usize) {
+        let changed = match self.current_view {
+            View::PullRequests => self
+                .pull_requests
+                .get(index)
+                .is_some_and(|pr| self.seen.mark_read(pr)),
+            View::Issues => self
+                .issues
+                .get(index)
+                .is_some_and(|issue| self.seen.mark_read(issue)),
+            _ => false,
+        };
+        if changed && let Err(e) = self.seen.save() {
+            tracing::warn!("could not save read items: {:#}", e);
+        }
+    }
+
+    /// Show only the pipelines `filter` lets through
+    fn set_pipeline_filter(&mut self, filter: PipelineFilter) {
+        self.pipeline_filter = filter;
+        self.view_state.reset();
+        self.marked.clear();
+        let message = match self.pipeline_filter.describe() {
+            Some(shown) => format!("Pipelines: {}", shown),
+            None => "Showing all pipelines".to_string(),
+        };
+        self.toast(&message);
+    }
+
+    /// Offer the current view's batch actions for the marked rows
+    fn open_batch_menu(&mut self) {
+        if self.marked.is_empty() {
+            self.set_error("Mark rows with space first");
+            return;
+        }
+        let indices: Vec<usize> = self.marked.iter().copied().collect();
+        let items = BatchKind::for_view(self.current_view)
+            .iter()
+            .map(|kind| {
+                (
+                    kind.label(indices.len()),
+                    Action::Batch {
+                        kind: *kind,
+                        indices: indices.clone(),
+                    },
+                )
+            })
+            .collect();
+        self.modal = Some(Modal::menu(format!("{} marked", indices.len()), items, 0));
+    }
+
+    /// Keys acting on the selected pull request
+    fn handle_pull_request_key(&mut self, code: crossterm::event::KeyCode) {
+        use crossterm::event::KeyCode;
+
+        if code == KeyCode::Char('U') {
+            self.toggle_unread_only();
+            return;
+        }
+        let Some(index) = self.selected_item() else {
+            return;
+        };
+        if code == KeyCode::Char('d') {
+            self.mark_read(index);
+            self.actions.push(Action::OpenDiff { index });
+        }
+    }
+
+    /// Keys for the open diff, which has them all until it's closed
+    fn handle_diff_key(&mut self, code: crossterm::event::KeyCode) {
+        use crossterm::event::KeyCode;
+
+        let Some(diff) = self.diff.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.diff = None;
+                self.clear_error();
+            }
+            KeyCode::Up | KeyCode::Char('k') => diff.previous(),
+            KeyCode::Down | KeyCode::Char('j') => diff.next(),
+            KeyCode::PageUp => diff.page_up(),
+            KeyCode::PageDown => diff.page_down(),
+            KeyCode::Char('c') => match diff.anchor() {
+                Some(inline) => {
+                    let title = match (inline.to, inline.from) {
+                        (Some(line), _) | (None, Some(line)) => {
+                            format!("Comment on {}:{}", inline.path, line)
+                        }
+                        (None, None) => format!("Comment on {}", inline.path),
+                    };
+                    self.modal = Some(Modal::input(
+                        title,
+                        Box::new(move |body| Action::CommentOnDiffLine { inline, body }),
+                    ));
+                }
+                None => self.set_error("Pick an added, removed or unchanged line to comment on"),
+            },
+            _ => {}
+        }
+    }
+
+    /// Keys acting on the selected issue
+    fn handle_issue_key(&mut self, code: crossterm::event::KeyCode) {
+        use crossterm::event::KeyCode;
+
+        if code == KeyCode::Char('U') {
+            self.toggle_unread_only();
+            return;
+        }
+        let Some(index) = self.selected_item() else {
+            return;
+        };
+        let issue = &self.issues[index];
+        match code {
+            KeyCode::Char('c') => {
+                self.modal = Some(Modal::input(
+                    format!("Comment on #{}", issue.id),
+                    Box::new(move |body| Action::CommentOnIssue { index, body }),
+                ));
+            }
+            KeyCode::Char('a') => self.actions.push(Action::AssignIssueToMe { index }),
+            KeyCode::Char('s') => {
+                let current = ISSUE_STATES
+                    .iter()
+                    .position(|s| *s == issue.state)
+                    .unwrap_or(0);
+                let items = ISSUE_STATES
+                    .iter()
+                    .map(|state| {
+                        (
+                            state.to_string(),
+                            Action::SetIssueState {
+                                index,
+                                state: state.clone(),
+                            },
+                        )
+                    })
+                    .collect();
+                self.modal = Some(Modal::menu(
+                    format!("Set state of #{}", issue.id),
+                    items,
+                    current,
+                ));
+            }
+            KeyCode::Char('v') => self.actions.push(Action::VoteForIssue { index }),
+            KeyCode::Char('W') => self.actions.push(Action::WatchIssue { index }),
+            _ => {}
+        }
+    }
+
+    /// Keys acting on the selected repository
+    fn handle_repository_key(&mut self, code: crossterm::event::KeyCode) {
+        use crossterm::event::KeyCode;
+
+        let Some(repo) = self.repositories.get(self.view_state.selected_index) else {
+            return;
+        };
+        let protocol = Config::load()
+            .map(|c| c.defaults.clone_protocol)
+            .unwrap_or_default();
+        match code {
+            KeyCode::Char('c') => {
+                if let Some(job) = self.clone.as_ref().filter(|job| job.is_running()) {
+                    let error = format!("Still cloning {}", job.repository);
+                    self.set_error(&error);
+                    return;
+                }
+                let Some(url) = repo.clone_url(protocol.link_name()) else {
+                    let error = format!(
+                        "{} has no {} clone URL",
+                        repo.full_name,
+                        protocol.link_name()
+                    );
+                    self.set_error(&error);
+                    return;
+                };
+                let slug = repo.slug.as_deref().unwrap_or(&repo.name);
+                let target = clone::target_for(slug);
+                if target.exists() {
+                    let error = format!("{} already exists", target.display());
+                    self.set_error(&error);
+                    return;
+                }
+                self.clone = Some(CloneJob::start(repo.full_name.clone(), url, target));
+            }
+            KeyCode::Char('o') => {
+                let Some(html) = repo.links.as_ref().and_then(|l| l.html.as_ref()) else {
+                    let error = format!("{} has no web link", repo.full_name);
+                    self.set_error(&error);
+                    return;
+                };
+                let message = match open::that(&html.href) {
+                    Ok(()) => Ok(format!("Opened {}", repo.full_name)),
+                    Err(e) => Err(format!("Failed to open a browser: {}", e)),
+                };
+                match message {
+                    Ok(message) => self.toast(&message),
+                    Err(error) => self.set_error(&error),
+                }
+            }
+            KeyCode::Char('g') => {
+                let Some(url) = repo.clone_url(protocol.link_name()) else {
+                    let error = format!(
+                        "{} has no {} clone URL",
+                        repo.full_name,
+                        protocol.link_name()
+                    );
+                    self.set_error(&error);
+                    return;
+                };
+                let message = match clipboard::copy_text(url) {
+                    Ok(()) => Ok(format!("Copied {}", url)),
+                    Err(e) => Err(format!("{:#}", e)),
+                };
+                match message {
+                    Ok(message) => self.toast(&message),
+                    Err(error) => self.set_error(&error),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Keys acting on the selected pipeline
+    fn handle_pipeline_key(&mut self, code: crossterm::event::KeyCode) {
+        use crossterm::event::KeyCode;
+
+        let selected = self.selected_item();
+        match code {
+            KeyCode::Char('f') => {
+                let filter = PipelineFilter {
+                    status: self.pipeline_filter.status.next(),
+                    ..self.pipeline_filter.clone()
+                };
+                self.set_pipeline_filter(filter);
+                return;
+            }
+            KeyCode::Char('F') => {
+                let repository = match &self.pipeline_filter.repository {
+                    Some(_) => None,
+                    None => selected
+                        .and_then(|index| self.pipelines[index].repository.as_ref())
+                        .map(|r| r.full_name.clone()),
+                };
+                if repository.is_none() && self.pipeline_filter.repository.is_none() {
+                    return;
+                }
+                let filter = PipelineFilter {
+                    repository,
+                    ..self.pipeline_filter.clone()
+                };
+                self.set_pipeline_filter(filter);
+                return;
+            }
+            _ => {}
+        }
+
+        let Some(index) = selected else {
+            return;
+        };
+        let pipeline = &self.pipelines[index];
+        match code {
+            KeyCode::Char('t') => self.actions.push(Action::ChooseBranch { index }),
+            KeyCode::Char('x') if action::is_running(pipeline) => {
+                self.modal = Some(Modal::confirm(
+                    "Stop pipeline",
+                    format!(
+                        "Stop pipeline #{} on {}?",
+                        pipeline.build_number, pipeline.target
+                    ),
+                    Action::StopPipeline { index },
+                ));
+            }
+            KeyCode::Char('x') => {
+                let error = format!("Pipeline #{} isn't running", pipeline.build_number);
+                self.set_error(&error);
             }
             _ => {}
         }
@@ -177,17 +716,21 @@ impl App {
                 }
             }
             View::PullRequests => {
-                if let Some(pr) = self.pull_requests.get(self.view_state.selected_index) {
+                if let Some(index) = self.selected_item() {
+                    self.mark_read(index);
+                    let pr = &self.pull_requests[index];
                     self.set_status(&format!("Selected PR #{}: {}", pr.id, pr.title));
                 }
             }
             View::Issues => {
-                if let Some(issue) = self.issues.get(self.view_state.selected_index) {
+                if let Some(index) = self.selected_item() {
+                    self.mark_read(index);
+                    let issue = &self.issues[index];
                     self.set_status(&format!("Selected Issue #{}: {}", issue.id, issue.title));
                 }
             }
             View::Pipelines => {
-                if let Some(pipeline) = self.pipelines.get(self.view_state.selected_index) {
+                if let Some(pipeline) = self.selected_item().map(|index| &self.pipelines[index]) {
                     self.set_status(&format!("Selected Pipeline #{}", pipeline.build_number));
                 }
             }
@@ -199,113 +742,245 @@ impl App {
         self.running = false;
     }
 
-    /// Load repositories
-    pub async fn load_repositories(&mut self) -> Result<()> {
+    /// Load the first batch of repositories
+    #[tracing::instrument(skip(self, redraw))]
+    pub async fn load_repositories(&mut self, redraw: impl FnMut(&App)) -> Result<()> {
         if let (Some(client), Some(workspace)) = (&self.client, &self.workspace) {
-            self.loading = true;
-            match client.list_repositories(workspace, None, Some(50)).await {
-                Ok(result) => {
-                    self.repositories = result.values;
-                    self.clear_error();
-                }
-                Err(e) => {
-                    self.set_error(&format!("Failed to load repositories: {}", e));
-                }
+            self.repository_stream = Some(client.stream_repositories(workspace).boxed());
+            self.repositories.clear();
+            self.clear_error();
+            self.load_more_repositories(redraw).await;
+            if self.error.is_none() {
+                self.refreshed.insert(View::Repositories, Local::now());
             }
-            self.loading = false;
         } else {
             self.set_error("No workspace configured");
         }
         Ok(())
     }
 
-    /// Load pull requests for the current workspace
-    pub async fn load_pull_requests(&mut self) -> Result<()> {
-        if let (Some(client), Some(workspace)) = (&self.client, &self.workspace) {
-            self.loading = true;
-            self.pull_requests.clear();
-
-            // Load PRs from all repositories
-            if let Ok(repos) = client.list_repositories(workspace, None, Some(50)).await {
-                for repo in repos.values {
-                    let repo_slug = repo.slug.as_deref().unwrap_or(&repo.name);
-                    if let Ok(prs) = client
-                        .list_pull_requests(workspace, repo_slug, None, None, Some(10))
-                        .await
-                    {
-                        self.pull_requests.extend(prs.values);
-                    }
+    /// Whether the selection is close enough to the end of the repository
+    /// list that the next batch should be fetched
+    pub fn wants_more_repositories(&self) -> bool {
+        self.current_view == View::Repositories
+            && self.repository_stream.is_some()
+            && self.view_state.selected_index + SCROLL_AHEAD >= self.repositories.len()
+    }
+
+    /// Whether more repositories remain to be fetched
+    pub fn has_more_repositories(&self) -> bool {
+        self.repository_stream.is_some()
+    }
+
+    /// Append the next batch of repositories from the stream, drawing each
+    /// as it arrives
+    pub async fn load_more_repositories(&mut self, mut redraw: impl FnMut(&App)) {
+        let Some(mut stream) = self.repository_stream.take() else {
+            return;
+        };
+
+        self.loading = true;
+        redraw(self);
+        let mut exhausted = false;
+        for _ in 0..REPOSITORY_BATCH {
+            match stream.next().await {
+                Some(Ok(repo)) => {
+                    self.repositories.push(repo);
+                    redraw(self);
+                }
+                Some(Err(e)) => {
+                    tracing::warn!("failed to load repositories: {:#}", e);
+                    self.error = Some(format!("Failed to load repositories: {}", e));
+                    exhausted = true;
+                    break;
+                }
+                None => {
+                    exhausted = true;
+                    break;
                 }
             }
-
-            self.clear_error();
-            self.loading = false;
-        } else {
-            self.set_error("No workspace configured");
         }
+        if !exhausted {
+            self.repository_stream = Some(stream);
+        }
+        self.loading = false;
+    }
+
+    /// Load pull requests for the current workspace
+    #[tracing::instrument(skip(self, redraw))]
+    pub async fn load_pull_requests(&mut self, redraw: impl FnMut(&App)) -> Result<()> {
+        self.pull_requests.clear();
+        self.load_per_repository(
+            View::PullRequests,
+            |client, workspace, repo_slug| async move {
+                let prs = client
+                    .list_pull_requests(&workspace, &repo_slug, None, None, None, Some(10))
+                    .await?;
+                Ok(prs.values)
+            },
+            |app, repo, prs: Vec<PullRequest>| {
+                app.pull_requests.extend(prs.into_iter().map(|mut pr| {
+                    pr.destination
+                        .repository
+                        .get_or_insert_with(|| repo.clone());
+                    pr
+                }));
+            },
+            redraw,
+        )
+        .await;
         Ok(())
     }
 
     /// Load issues for the current workspace
-    pub async fn load_issues(&mut self) -> Result<()> {
-        if let (Some(client), Some(workspace)) = (&self.client, &self.workspace) {
-            self.loading = true;
-            self.issues.clear();
-
-            // Load issues from all repositories
-            if let Ok(repos) = client.list_repositories(workspace, None, Some(50)).await {
-                for repo in repos.values {
-                    let repo_slug = repo.slug.as_deref().unwrap_or(&repo.name);
-                    if let Ok(issues) = client
-                        .list_issues(workspace, repo_slug, None, None, Some(10))
-                        .await
-                    {
-                        self.issues.extend(issues.values);
-                    }
-                }
-            }
-
-            self.clear_error();
-            self.loading = false;
-        } else {
-            self.set_error("No workspace configured");
-        }
+    #[tracing::instrument(skip(self, redraw))]
+    pub async fn load_issues(&mut self, redraw: impl FnMut(&App)) -> Result<()> {
+        self.issues.clear();
+        self.load_per_repository(
+            View::Issues,
+            |client, workspace, repo_slug| async move {
+                let issues = client
+                    .list_issues(&workspace, &repo_slug, None, None, None, Some(10))
+                    .await?;
+                Ok(issues.values)
+            },
+            |app, repo, issues: Vec<Issue>| {
+                // Actions need to know which repository an issue is in
+                app.issues.extend(issues.into_iter().map(|mut issue| {
+                    issue.repository.get_or_insert_with(|| repo.clone());
+                    issue
+                }));
+            },
+            redraw,
+        )
+        .await;
         Ok(())
     }
 
     /// Load pipelines for the current workspace
-    pub async fn load_pipelines(&mut self) -> Result<()> {
-        if let (Some(client), Some(workspace)) = (&self.client, &self.workspace) {
-            self.loading = true;
-            self.pipelines.clear();
-
-            // Load pipelines from all repositories
-            if let Ok(repos) = client.list_repositories(workspace, None, Some(50)).await {
-                for repo in repos.values {
-                    let repo_slug = repo.slug.as_deref().unwrap_or(&repo.name);
-                    if let Ok(pipelines) = client
-                        .list_pipelines(workspace, repo_slug, None, Some(10))
-                        .await
-                    {
-                        self.pipelines.extend(pipelines.values);
+    #[tracing::instrument(skip(self, redraw))]
+    pub async fn load_pipelines(&mut self, redraw: impl FnMut(&App)) -> Result<()> {
+        self.pipelines.clear();
+        self.load_per_repository(
+            View::Pipelines,
+            |client, workspace, repo_slug| async move {
+                let pipelines = client
+                    .list_pipelines(&workspace, &repo_slug, None, None, Some(10))
+                    .await?;
+                Ok(pipelines.values)
+            },
+            |app, repo, pipelines: Vec<Pipeline>| {
+                app.pipelines
+                    .extend(pipelines.into_iter().map(|mut pipeline| {
+                        pipeline.repository.get_or_insert_with(|| repo.clone());
+                        pipeline
+                    }));
+            },
+            redraw,
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Fetch `view`'s items from each repository in the workspace at once,
+    /// handing each repository's to `store` as they arrive. Repositories
+    /// still loading are kept in `pending` for the view to show as spinner
+    /// rows, and `redraw` runs often enough to animate them.
+    async fn load_per_repository<T, Fut>(
+        &mut self,
+        view: View,
+        fetch: impl Fn(BitbucketClient, String, String) -> Fut,
+        mut store: impl FnMut(&mut App, &Repository, Vec<T>),
+        mut redraw: impl FnMut(&App),
+    ) where
+        Fut: Future<Output = crate::Result<Vec<T>>>,
+    {
+        let (Some(client), Some(workspace)) = (self.client.clone(), self.workspace.clone()) else {
+            self.set_error("No workspace configured");
+            return;
+        };
+
+        let repos = self.loaded_repositories(&mut redraw).await;
+        if repos.is_empty() {
+            return;
+        }
+        self.pending
+            .extend(repos.iter().map(|repo| (view, repo.full_name.clone())));
+        redraw(self);
+
+        let mut results = futures::stream::iter(repos)
+            .map(|repo| {
+                let repo_slug = repo.slug.clone().unwrap_or_else(|| repo.name.clone());
+                let fetched = fetch(client.clone(), workspace.clone(), repo_slug);
+                async move { (repo, fetched.await) }
+            })
+            .buffer_unordered(LOAD_CONCURRENCY);
+        let mut spinner = tokio::time::interval(SPINNER_FRAME);
+        loop {
+            tokio::select! {
+                next = results.next() => {
+                    let Some((repo, result)) = next else { break };
+                    self.pending
+                        .retain(|(v, name)| !(*v == view && *name == repo.full_name));
+                    match result {
+                        Ok(items) => store(self, &repo, items),
+                        Err(e) => tracing::warn!(
+                            repository = %repo.full_name,
+                            "failed to load {:?}: {:#}",
+                            view,
+                            e
+                        ),
                     }
+                    redraw(self);
                 }
+                _ = spinner.tick() => redraw(self),
             }
+        }
 
-            self.clear_error();
-            self.loading = false;
-        } else {
-            self.set_error("No workspace configured");
+        self.pending.retain(|(v, _)| *v != view);
+        self.clear_error();
+        self.refreshed.insert(view, Local::now());
+    }
+
+    /// The repositories the other views load their items from: the first
+    /// batch of the Repositories view, fetched once and shared by all of them
+    async fn loaded_repositories(&mut self, redraw: impl FnMut(&App)) -> Vec<Repository> {
+        if !self.refreshed.contains_key(&View::Repositories) {
+            let _ = self.load_repositories(redraw).await;
         }
-        Ok(())
+        self.repositories
+            .iter()
+            .take(REPOSITORY_BATCH)
+            .cloned()
+            .collect()
+    }
+
+    /// Whether `view` has repositories still loading
+    pub fn is_pending(&self, view: View) -> bool {
+        self.pending.iter().any(|(v, _)| *v == view)
+    }
+
+    /// Repositories still loading for `view`
+    pub fn pending_in(&self, view: View) -> impl Iterator<Item = &str> {
+        self.pending
+            .iter()
+            .filter(move |(v, _)| *v == view)
+            .map(|(_, name)| name.as_str())
     }
 
-    /// Load all data
-    pub async fn load_all_data(&mut self) -> Result<()> {
-        self.load_repositories().await?;
-        self.load_pull_requests().await?;
-        self.load_issues().await?;
-        self.load_pipelines().await?;
+    /// The current frame of the loading spinner
+    pub fn spinner(&self) -> &'static str {
+        let frame = self.started.elapsed().as_millis() / SPINNER_FRAME.as_millis();
+        SPINNER[frame as usize % SPINNER.len()]
+    }
+
+    /// Load all data, each view filling in as its repositories respond. The
+    /// repository list is fetched once, here, and shared by the other views.
+    pub async fn load_all_data(&mut self, mut redraw: impl FnMut(&App)) -> Result<()> {
+        self.load_repositories(&mut redraw).await?;
+        self.load_pull_requests(&mut redraw).await?;
+        self.load_issues(&mut redraw).await?;
+        self.load_pipelines(&mut redraw).await?;
         Ok(())
     }
 }
@@ -319,10 +994,11 @@ impl Default for App {
 /// Run the TUI application
 pub async fn run_tui(workspace: Option<String>) -> Result<()> {
     // Setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
+    take_terminal()?;
+    // Gives the terminal back however this returns, early or by unwinding
+    let _guard = TerminalGuard;
+    watch_signals();
+    let backend = CrosstermBackend::new(io::stdout());
     let mut terminal = Terminal::new(backend)?;
 
     // Create app
@@ -332,7 +1008,8 @@ pub async fn run_tui(workspace: Option<String>) -> Result<()> {
     match BitbucketClient::from_stored().await {
         Ok(client) => {
             app = app.with_client(client);
-            if let Some(ws) = workspace {
+            app.workspaces = Metadata::current().workspaces;
+            if let Some(ws) = workspace.or_else(|| app.workspaces.first().cloned()) {
                 app = app.with_workspace(ws);
             } else {
                 app.set_error("No workspace specified. Use: bitbucket tui --workspace <workspace>");
@@ -343,62 +1020,126 @@ pub async fn run_tui(workspace: Option<String>) -> Result<()> {
         }
     }
 
+    // Created before anything loads, so keys work while it does
+    let event_handler = EventHandler::new(250);
+    // Keys pressed while loading, handled once it's done
+    let mut held = VecDeque::new();
+
     // Load initial data if we have a workspace
     if app.workspace.is_some() && app.client.is_some() {
         app.set_status("Loading data...");
         terminal.draw(|f| ui::draw(f, &app))?;
 
-        if let Err(e) = app.load_repositories().await {
-            app.set_error(&format!("Failed to load data: {}", e));
-        } else {
-            app.set_status("Data loaded. Press 'r' to refresh.");
+        let loaded = taking_keys(
+            app.load_all_data(redrawer(&mut terminal)),
+            &event_handler,
+            &mut held,
+        )
+        .await;
+        match loaded {
+            None => app.running = false,
+            Some(Err(e)) => app.set_error(&format!("Failed to load data: {}", e)),
+            Some(Ok(())) => {
+                app.set_status("Data loaded. Press 'r' to refresh, 'R' to refresh everything.")
+            }
         }
     }
 
-    // Create event handler
-    let event_handler = EventHandler::new(250);
-    let mut should_refresh = false;
-
     // Main loop
     while app.running {
+        // Take in git's output from a clone started with `c`
+        if let Some(job) = app.clone.as_mut() {
+            let was_running = job.is_running();
+            job.poll();
+            match &job.finished {
+                Some(Ok(())) if was_running => {
+                    let message = format!("Cloned into {}", job.target.display());
+                    app.toast(&message);
+                }
+                Some(Err(e)) if was_running => {
+                    let error = format!("Clone failed: {}", e);
+                    app.set_error(&error);
+                }
+                _ => {}
+            }
+        }
+
         // Draw UI
         terminal.draw(|f| ui::draw(f, &app))?;
 
         // Handle refresh if requested
-        if should_refresh && app.workspace.is_some() && app.client.is_some() {
-            should_refresh = false;
+        if app.refresh_all_requested && app.workspace.is_some() && app.client.is_some() {
+            app.refresh_all_requested = false;
+            app.refresh_requested = false;
+            app.marked.clear();
+            app.set_status("Refreshing all views...");
+            terminal.draw(|f| ui::draw(f, &app))?;
+
+            let loaded = taking_keys(
+                app.load_all_data(redrawer(&mut terminal)),
+                &event_handler,
+                &mut held,
+            )
+            .await;
+            match loaded {
+                None => break,
+                Some(Err(e)) => {
+                    app.set_status("Refresh failed");
+                    app.set_error(&format!("Failed to refresh: {}", e));
+                }
+                // A view that failed to load has said so already
+                Some(Ok(())) if app.error.is_some() => app.set_status("Refresh failed"),
+                Some(Ok(())) => app.set_status("Refreshed all views"),
+            }
+        }
+        if app.refresh_requested && app.workspace.is_some() && app.client.is_some() {
+            app.refresh_requested = false;
+            app.marked.clear();
             app.set_status("Refreshing...");
             terminal.draw(|f| ui::draw(f, &app))?;
 
-            match app.current_view {
+            let redraw = redrawer(&mut terminal);
+            let loaded = match app.current_view {
                 View::Dashboard | View::Repositories => {
-                    let _ = app.load_repositories().await;
+                    taking_keys(app.load_repositories(redraw), &event_handler, &mut held).await
                 }
                 View::PullRequests => {
-                    let _ = app.load_pull_requests().await;
+                    taking_keys(app.load_pull_requests(redraw), &event_handler, &mut held).await
                 }
                 View::Issues => {
-                    let _ = app.load_issues().await;
+                    taking_keys(app.load_issues(redraw), &event_handler, &mut held).await
                 }
                 View::Pipelines => {
-                    let _ = app.load_pipelines().await;
+                    taking_keys(app.load_pipelines(redraw), &event_handler, &mut held).await
                 }
+            };
+            if loaded.is_none() {
+                break;
             }
 
             app.set_status("Refreshed");
         }
 
-        // Handle events
-        match event_handler.next()? {
+        // Fetch the next batch of repositories as the list is scrolled
+        if app.wants_more_repositories() {
+            app.load_more_repositories(redrawer(&mut terminal)).await;
+            continue;
+        }
+
+        // Handle events, keys held back while loading first
+        let event = match held.pop_front() {
+            Some(key) => Event::Key(key),
+            None => event_handler.next()?,
+        };
+        match event {
             Event::Key(key) => {
-                // Check if refresh was requested
-                if let crossterm::event::KeyCode::Char('r') = key.code {
-                    should_refresh = true;
-                }
                 app.handle_key(key);
+                for action in std::mem::take(&mut app.actions) {
+                    app.perform(action, redrawer(&mut terminal)).await;
+                }
             }
             Event::Tick => {
-                // Periodic tick for animations, etc.
+                app.expire_toast();
             }
             Event::Resize(_, _) => {
                 // Terminal will redraw automatically
@@ -408,13 +1149,111 @@ pub async fn run_tui(workspace: Option<String>) -> Result<()> {
     }
 
     // Restore terminal
+    restore_terminal()?;
+    terminal.show_cursor()?;
+
+    Ok(())
+}
+
+/// Run `load` while still reading keys: `q` abandons it, giving `None`, and
+/// other keys are held back for the main loop. Ctrl-C is handled by the event
+/// thread itself.
+async fn taking_keys<T>(
+    load: impl Future<Output = T>,
+    events: &EventHandler,
+    held: &mut VecDeque<crossterm::event::KeyEvent>,
+) -> Option<T> {
+    let quit = async {
+        let mut poll = tokio::time::interval(INPUT_POLL);
+        loop {
+            poll.tick().await;
+            while let Some(event) = events.try_next() {
+                if let Event::Key(key) = event {
+                    if key.code == crossterm::event::KeyCode::Char('q') {
+                        return;
+                    }
+                    held.push_back(key);
+                }
+            }
+        }
+    };
+    tokio::select! {
+        output = load => Some(output),
+        _ = quit => None,
+    }
+}
+
+/// A callback drawing the app, for work that shows progress as it goes
+fn redrawer<B: Backend>(terminal: &mut Terminal<B>) -> impl FnMut(&App) + '_ {
+    move |app| {
+        let _ = terminal.draw(|f| ui::draw(f, app));
+    }
+}
+
+/// Whether the TUI has the terminal in raw mode on the alternate screen
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Enter raw mode and the alternate screen, with a panic hook that restores
+/// the terminal before the panic message is printed, so it stays readable
+fn take_terminal() -> Result<()> {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let _ = restore_terminal();
+            previous(info);
+        }));
+    });
+
+    enable_raw_mode()?;
+    TAKEN.store(true, Ordering::SeqCst);
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    Ok(())
+}
+
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = restore_terminal();
+    }
+}
+
+/// Restore the terminal and exit on SIGTERM or SIGHUP. Ctrl-C arrives as a
+/// key in raw mode, and a SIGINT sent some other way is handled in `main`.
+fn watch_signals() {
+    #[cfg(unix)]
+    tokio::spawn(async {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let (Ok(mut term), Ok(mut hangup)) = (
+            signal(SignalKind::terminate()),
+            signal(SignalKind::hangup()),
+        ) else {
+            return;
+        };
+        let code = tokio::select! {
+            _ = term.recv() => 143,
+            _ = hangup.recv() => 129,
+        };
+        let _ = restore_terminal();
+        std::process::exit(code);
+    });
+}
+
+/// Leave raw mode and the alternate screen, returning the terminal to
+/// normal. Does nothing when the TUI doesn't have the terminal, so it's safe
+/// to call from every exit path.
+pub fn restore_terminal() -> Result<()> {
+    if !TAKEN.swap(false, Ordering::SeqCst) {
+        return Ok(());
+    }
     disable_raw_mode()?;
     execute!(
-        terminal.backend_mut(),
+        io::stdout(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        crossterm::cursor::Show
     )?;
-    terminal.show_cursor()?;
-
     Ok(())
 }
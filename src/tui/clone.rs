@@ -0,0 +1,211 @@
+//! `git clone` run from the Repositories view
+//!
+//! Git runs in the background while the TUI keeps drawing; its progress is
+//! read from stderr and shown in a panel under the list. The terminal
+//! belongs to the TUI, so git and ssh are told never to prompt on it: a clone
+//! that needs credentials they can't find fails, and says so in the panel.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+
+use crate::config::Config;
+
+/// Lines of git output kept for the panel
+const KEPT_LINES: usize = 200;
+
+/// What git or ssh print when they gave up instead of prompting
+const NEEDS_CREDENTIALS: &[&str] = &[
+    "terminal prompts disabled",
+    "Permission denied (publickey",
+    "Host key verification failed",
+];
+
+const NO_PROMPT_HINT: &str = "git clone needs credentials it can't ask for here; set up a \
+                              credential helper or ssh-agent, or clone from a shell";
+
+enum Message {
+    Output(Vec<u8>),
+    Exited(Result<(), String>),
+}
+
+/// A clone in progress, or finished and still on screen
+pub struct CloneJob {
+    /// `workspace/repo` being cloned
+    pub repository: String,
+    pub target: PathBuf,
+    /// Git's output so far
+    lines: Vec<String>,
+    /// Whether git finished, and how
+    pub finished: Option<Result<(), String>>,
+    /// Set after a `\r`, when git's next output redraws the last line
+    overwrite: bool,
+    rx: mpsc::UnboundedReceiver<Message>,
+}
+
+impl CloneJob {
+    /// Start `git clone` of `url` into `target`
+    pub fn start(repository: String, url: &str, target: PathBuf) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut command = tokio::process::Command::new("git");
+        command
+            .args(["clone", "--progress", "--", url])
+            .arg(&target)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .env("GIT_SSH_COMMAND", batch_ssh_command())
+            .kill_on_drop(true);
+        tracing::debug!(%repository, target = %target.display(), "cloning from the TUI");
+
+        tokio::spawn(async move {
+            let result = run(command, &tx).await;
+            let _ = tx.send(Message::Exited(result));
+        });
+
+        Self {
+            repository,
+            target,
+            lines: Vec::new(),
+            finished: None,
+            overwrite: false,
+            rx,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.finished.is_none()
+    }
+
+    /// Take in whatever git has written since the last call
+    pub fn poll(&mut self) {
+        while let Ok(message) = self.rx.try_recv() {
+            match message {
+                Message::Output(bytes) => self.push_output(&String::from_utf8_lossy(&bytes)),
+                Message::Exited(result) => self.finished = Some(result),
+            }
+        }
+    }
+
+    /// Add `chunk` to the lines, with a `\r` meaning the line is redrawn
+    /// (as git's progress counters are) rather than a new one started
+    fn push_output(&mut self, chunk: &str) {
+        for c in chunk.chars() {
+            match c {
+                '\n' => {
+                    self.lines.push(String::new());
+                    self.overwrite = false;
+                }
+                '\r' => self.overwrite = true,
+                c => {
+                    if self.lines.is_empty() {
+                        self.lines.push(String::new());
+                    }
+                    let last = self.lines.last_mut().expect("a line was just added");
+                    if std::mem::take(&mut self.overwrite) {
+                        last.clear();
+                    }
+                    last.push(c);
+                }
+            }
+        }
+        if self.lines.len() > KEPT_LINES {
+            self.lines.drain(..self.lines.len() - KEPT_LINES);
+        }
+    }
+
+    /// The output lines to show, without the empty one after a final newline
+    pub fn output(&self) -> &[String] {
+        match self.lines.split_last() {
+            Some((last, rest)) if last.is_empty() => rest,
+            _ => &self.lines,
+        }
+    }
+}
+
+async fn run(
+    mut command: tokio::process::Command,
+    tx: &mpsc::UnboundedSender<Message>,
+) -> Result<(), String> {
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    let mut needs_credentials = false;
+    if let Some(mut stderr) = child.stderr.take() {
+        let mut buffer = [0u8; 4096];
+        while let Ok(read) = stderr.read(&mut buffer).await {
+            if read == 0 {
+                break;
+            }
+            let text = String::from_utf8_lossy(&buffer[..read]);
+            needs_credentials |= NEEDS_CREDENTIALS.iter().any(|s| text.contains(s));
+            let _ = tx.send(Message::Output(buffer[..read].to_vec()));
+        }
+    }
+    match child.wait().await {
+        Ok(status) if status.success() => Ok(()),
+        Ok(_) if needs_credentials => Err(NO_PROMPT_HINT.to_string()),
+        Ok(status) => Err(format!("git clone exited with {}", status)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// The ssh command git should use: the user's own, if set, but never asking
+/// for a passphrase or host key confirmation
+fn batch_ssh_command() -> String {
+    let ssh = std::env::var("GIT_SSH_COMMAND")
+        .ok()
+        .filter(|command| !command.trim().is_empty())
+        .unwrap_or_else(|| "ssh".to_string());
+    format!("{} -o BatchMode=yes", ssh)
+}
+
+/// Where `repo_slug` gets cloned: under `[tui] clone_dir`, or the current
+/// directory
+pub fn target_for(repo_slug: &str) -> PathBuf {
+    let dir = Config::load().ok().and_then(|c| c.tui.clone_dir);
+    match dir {
+        Some(dir) => expand_home(&dir).join(repo_slug),
+        None => PathBuf::from(repo_slug),
+    }
+}
+
+/// `dir` with a leading `~` replaced by the home directory
+fn expand_home(dir: &Path) -> PathBuf {
+    match (dir.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => dir.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_lines_are_redrawn_in_place() {
+        let (_tx, rx) = mpsc::unbounded_channel();
+        let mut job = CloneJob {
+            repository: "team/app".to_string(),
+            target: PathBuf::from("app"),
+            lines: Vec::new(),
+            finished: None,
+            overwrite: false,
+            rx,
+        };
+        job.push_output("Cloning into 'app'...\nReceiving objects:  10%");
+        job.push_output("\rReceiving objects:  55%\r");
+        job.push_output("Receiving objects: 100%, done.\r\nResolving deltas: 100%\n");
+        assert_eq!(
+            job.output(),
+            [
+                "Cloning into 'app'...",
+                "Receiving objects: 100%, done.",
+                "Resolving deltas: 100%",
+            ]
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! A pull request's diff, opened with `d` in the Pull Requests view
+//!
+//! Inline comments show under the lines they're on, and `c` comments on the
+//! selected line.
+
+use crate::models::{InlineComment, PullRequestComment};
+
+/// Lines `PgUp`/`PgDn` move by
+const PAGE: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    /// `diff --git`, starting a file
+    File,
+    /// Between `diff --git` and the first hunk: modes, `index`, `---`/`+++`
+    Meta,
+    /// `@@ ... @@`
+    Hunk,
+    Added,
+    Removed,
+    Context,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub text: String,
+    pub kind: LineKind,
+    /// The file the line belongs to, as in the `b/` side of `diff --git`
+    pub path: Option<String>,
+    /// Line number in the old file, for removed and context lines
+    pub old: Option<u32>,
+    /// Line number in the new file, for added and context lines
+    pub new: Option<u32>,
+}
+
+/// The diff panel's contents and selection
+pub struct DiffView {
+    /// `workspace/repo` the pull request is in
+    pub repository: String,
+    pub pr_id: u64,
+    pub lines: Vec<DiffLine>,
+    /// The pull request's inline comments
+    pub comments: Vec<PullRequestComment>,
+    pub selected: usize,
+}
+
+impl DiffView {
+    pub fn new(
+        repository: String,
+        pr_id: u64,
+        diff: &str,
+        comments: Vec<PullRequestComment>,
+    ) -> Self {
+        Self {
+            repository,
+            pr_id,
+            lines: parse(diff),
+            comments: comments
+                .into_iter()
+                .filter(|c| c.inline.is_some() && c.deleted != Some(true))
+                .collect(),
+            selected: 0,
+        }
+    }
+
+    pub fn next(&mut self) {
+        self.selected = (self.selected + 1).min(self.lines.len().saturating_sub(1));
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn page_down(&mut self) {
+        self.selected = (self.selected + PAGE).min(self.lines.len().saturating_sub(1));
+    }
+
+    pub fn page_up(&mut self) {
+        self.selected = self.selected.saturating_sub(PAGE);
+    }
+
+    /// Where a comment on the selected line goes; `None` on a header
+    pub fn anchor(&self) -> Option<InlineComment> {
+        let line = self.lines.get(self.selected)?;
+        let path = line.path.clone()?;
+        match line.kind {
+            LineKind::Added | LineKind::Context => Some(InlineComment {
+                from: None,
+                to: line.new,
+                path,
+            }),
+            LineKind::Removed => Some(InlineComment {
+                from: line.old,
+                to: None,
+                path,
+            }),
+            LineKind::File | LineKind::Meta | LineKind::Hunk => None,
+        }
+    }
+
+    /// The inline comments on `line`, oldest first
+    pub fn comments_on<'a>(
+        &'a self,
+        line: &'a DiffLine,
+    ) -> impl Iterator<Item = &'a PullRequestComment> + 'a {
+        self.comments.iter().filter(move |comment| {
+            let Some(inline) = &comment.inline else {
+                return false;
+            };
+            if line.path.as_deref() != Some(inline.path.as_str()) {
+                return false;
+            }
+            match (line.kind, inline.to) {
+                (LineKind::Added | LineKind::Context, Some(to)) => line.new == Some(to),
+                (LineKind::Removed, None) => inline.from.is_some() && line.old == inline.from,
+                _ => false,
+            }
+        })
+    }
+}
+
+/// Split a unified diff into lines, numbering each from its hunk header
+pub fn parse(diff: &str) -> Vec<DiffLine> {
+    let mut lines = Vec::new();
+    let mut path: Option<String> = None;
+    let mut in_hunk = false;
+    let (mut old, mut new) = (0, 0);
+
+    for text in diff.lines() {
+        let mut line = DiffLine {
+            text: text.to_string(),
+            kind: LineKind::Meta,
+            path: None,
+            old: None,
+            new: None,
+        };
+        if text.starts_with("diff --git ") {
+            path = text.rsplit_once(" b/").map(|(_, p)| p.to_string());
+            in_hunk = false;
+            line.kind = LineKind::File;
+        } else if let Some((from, to)) = hunk_start(text) {
+            (old, new) = (from, to);
+            in_hunk = true;
+            line.kind = LineKind::Hunk;
+        } else if in_hunk {
+            match text.chars().next() {
+                Some('+') => {
+                    line.kind = LineKind::Added;
+                    line.new = Some(new);
+                    new += 1;
+                }
+                Some('-') => {
+                    line.kind = LineKind::Removed;
+                    line.old = Some(old);
+                    old += 1;
+                }
+                Some(' ') | None => {
+                    line.kind = LineKind::Context;
+                    line.old = Some(old);
+                    line.new = Some(new);
+                    old += 1;
+                    new += 1;
+                }
+                // "\ No newline at end of file"
+                _ => {}
+            }
+        }
+        line.path = path.clone();
+        lines.push(line);
+    }
+    lines
+}
+
+/// The first old and new line numbers of a `@@ -a,b +c,d @@` header
+fn hunk_start(text: &str) -> Option<(u32, u32)> {
+    let mut ranges = text.strip_prefix("@@ -")?.split(' ');
+    let start = |range: &str| range.split(',').next()?.parse::<u32>().ok();
+    let old = start(ranges.next()?)?;
+    let new = start(ranges.next()?.strip_prefix('+')?)?;
+    Some((old, new))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "diff --git a/src/lib.rs b/src/lib.rs\n\
+        index 1111111..2222222 100644\n\
+        --- a/src/lib.rs\n\
+        +++ b/src/lib.rs\n\
+        @@ -10,3 +10,3 @@ fn main() {\n \
+        let a = 1;\n\
+        -let b = 2;\n\
+        +let b = 3;\n \
+        let c = a + b;\n";
+
+    #[test]
+    fn lines_are_numbered_from_their_hunk() {
+        let lines = parse(DIFF);
+        let numbered: Vec<(LineKind, Option<u32>, Option<u32>)> =
+            lines[5..].iter().map(|l| (l.kind, l.old, l.new)).collect();
+        assert_eq!(
+            numbered,
+            [
+                (LineKind::Context, Some(10), Some(10)),
+                (LineKind::Removed, Some(11), None),
+                (LineKind::Added, None, Some(11)),
+                (LineKind::Context, Some(12), Some(12)),
+            ]
+        );
+        assert_eq!(lines[3].kind, LineKind::Meta);
+        assert!(
+            lines
+                .iter()
+                .all(|l| l.path.as_deref() == Some("src/lib.rs"))
+        );
+    }
+
+    #[test]
+    fn comments_anchor_to_the_side_the_line_is_on() {
+        let mut view = DiffView::new("acme/engine".into(), 42, DIFF, Vec::new());
+        view.selected = 4;
+        assert!(view.anchor().is_none());
+
+        view.selected = 6;
+        let removed = view.anchor().unwrap();
+        assert_eq!((removed.from, removed.to), (Some(11), None));
+
+        view.selected = 7;
+        let added = view.anchor().unwrap();
+        assert_eq!((added.from, added.to), (None, Some(11)));
+        assert_eq!(added.path, "src/lib.rs");
+    }
+}
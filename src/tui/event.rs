@@ -1,5 +1,5 @@
 use anyhow::Result;
-use crossterm::event::{self, KeyEvent, MouseEvent};
+use crossterm::event::{self, KeyCode, KeyEvent, KeyModifiers, MouseEvent};
 use std::sync::mpsc;
 use std::thread;
 use std::time::Duration;
@@ -35,6 +35,13 @@ impl EventHandler {
                 // Poll for events with timeout
                 if event::poll(tick_rate).unwrap_or(false) {
                     match event::read() {
+                        Ok(event::Event::Key(key)) if is_interrupt(&key) => {
+                            // Raw mode swallows SIGINT, so treat Ctrl-C as an interrupt
+                            // here: restore the terminal and exit even if the main loop
+                            // is blocked on an in-flight request.
+                            let _ = super::restore_terminal();
+                            std::process::exit(130);
+                        }
                         Ok(event::Event::Key(key)) if tx.send(Event::Key(key)).is_err() => {
                             break;
                         }
@@ -63,4 +70,14 @@ impl EventHandler {
     pub fn next(&self) -> Result<Event> {
         Ok(self.rx.recv()?)
     }
+
+    /// The next event if one is waiting, without blocking
+    pub fn try_next(&self) -> Option<Event> {
+        self.rx.try_recv().ok()
+    }
+}
+
+/// Check whether a key event is Ctrl-C
+fn is_interrupt(key: &KeyEvent) -> bool {
+    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)
 }
@@ -0,0 +1,219 @@
+//! `E` writes the list on screen to a JSON or CSV file in the downloads
+//! directory, to carry on with it in a script or spreadsheet
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+
+use super::app::App;
+use super::views::View;
+use crate::cli::output::csv_field;
+use crate::models::{Issue, Pipeline, PullRequest, Repository};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+        }
+    }
+}
+
+/// Write the current view's items as `format`, returning where they went
+/// and how many there were; `None` for a view without a list
+pub fn export(app: &App, format: ExportFormat) -> Result<Option<(PathBuf, usize)>> {
+    let Some((json, headers, rows)) = contents(app)? else {
+        return Ok(None);
+    };
+    let text = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&json)?,
+        ExportFormat::Csv => to_csv(headers, &rows),
+    };
+
+    let dir = dirs::download_dir()
+        .or_else(dirs::home_dir)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let path = target(&dir, app, format);
+    std::fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+    tracing::debug!(path = %path.display(), count = rows.len(), "exported TUI list");
+    Ok(Some((path, rows.len())))
+}
+
+type Contents = (serde_json::Value, &'static [&'static str], Vec<Vec<String>>);
+
+/// The view's items as JSON, and as CSV headers and rows
+fn contents(app: &App) -> Result<Option<Contents>> {
+    let name = |repository: Option<&Repository>| {
+        repository.map(|r| r.full_name.clone()).unwrap_or_default()
+    };
+    let contents = match app.current_view {
+        View::Dashboard => return Ok(None),
+        View::Repositories => (
+            serde_json::to_value(&app.repositories)?,
+            &["NAME", "DESCRIPTION", "PRIVATE", "UPDATED"][..],
+            app.repositories
+                .iter()
+                .map(|r| {
+                    vec![
+                        r.full_name.clone(),
+                        r.description.clone().unwrap_or_default(),
+                        r.is_private.unwrap_or(false).to_string(),
+                        r.updated_on.map(|d| d.to_rfc3339()).unwrap_or_default(),
+                    ]
+                })
+                .collect(),
+        ),
+        View::PullRequests => {
+            let pull_requests: Vec<&PullRequest> = app
+                .visible_pull_requests()
+                .into_iter()
+                .map(|i| &app.pull_requests[i])
+                .collect();
+            (
+                serde_json::to_value(&pull_requests)?,
+                &["REPOSITORY", "ID", "TITLE", "STATE", "AUTHOR", "UPDATED"][..],
+                pull_requests
+                    .iter()
+                    .map(|pr| {
+                        vec![
+                            name(pr.destination.repository.as_ref()),
+                            pr.id.to_string(),
+                            pr.title.clone(),
+                            pr.state.to_string(),
+                            pr.author.display_name.clone(),
+                            pr.updated_on.to_rfc3339(),
+                        ]
+                    })
+                    .collect(),
+            )
+        }
+        View::Issues => {
+            let issues: Vec<&Issue> = app
+                .visible_issues()
+                .into_iter()
+                .map(|i| &app.issues[i])
+                .collect();
+            (
+                serde_json::to_value(&issues)?,
+                &[
+                    "REPOSITORY",
+                    "ID",
+                    "TITLE",
+                    "STATE",
+                    "KIND",
+                    "PRIORITY",
+                    "ASSIGNEE",
+                ][..],
+                issues
+                    .iter()
+                    .map(|issue| {
+                        vec![
+                            name(issue.repository.as_ref()),
+                            issue.id.to_string(),
+                            issue.title.clone(),
+                            issue.state.to_string(),
+                            issue.kind.to_string(),
+                            issue.priority.to_string(),
+                            issue
+                                .assignee
+                                .as_ref()
+                                .map(|u| u.display_name.clone())
+                                .unwrap_or_default(),
+                        ]
+                    })
+                    .collect(),
+            )
+        }
+        View::Pipelines => {
+            let pipelines: Vec<&Pipeline> = app
+                .visible_pipelines()
+                .into_iter()
+                .map(|i| &app.pipelines[i])
+                .collect();
+            (
+                serde_json::to_value(&pipelines)?,
+                &[
+                    "REPOSITORY",
+                    "BUILD",
+                    "STATE",
+                    "RESULT",
+                    "TARGET",
+                    "CREATED",
+                ][..],
+                pipelines
+                    .iter()
+                    .map(|p| {
+                        vec![
+                            name(p.repository.as_ref()),
+                            p.build_number.to_string(),
+                            p.state.name.to_string(),
+                            p.state
+                                .result
+                                .as_ref()
+                                .map(|r| r.name.to_string())
+                                .unwrap_or_default(),
+                            p.target.to_string(),
+                            p.created_on.to_rfc3339(),
+                        ]
+                    })
+                    .collect(),
+            )
+        }
+    };
+    Ok(Some(contents))
+}
+
+fn to_csv(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut out = headers.join(",");
+    out.push('\n');
+    for row in rows {
+        let fields: Vec<String> = row.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// `bitbucket-<view>-<workspace>-<time>.<ext>` in `dir`
+fn target(dir: &Path, app: &App, format: ExportFormat) -> PathBuf {
+    let view = match app.current_view {
+        View::Dashboard => "dashboard",
+        View::Repositories => "repositories",
+        View::PullRequests => "pull-requests",
+        View::Issues => "issues",
+        View::Pipelines => "pipelines",
+    };
+    let workspace = app
+        .workspace
+        .as_deref()
+        .map(|ws| format!("-{}", ws))
+        .unwrap_or_default();
+    dir.join(format!(
+        "bitbucket-{}{}-{}.{}",
+        view,
+        workspace,
+        Utc::now().format("%Y%m%d-%H%M%S"),
+        format.extension()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_quotes_fields_that_need_it() {
+        let rows = vec![vec!["7".to_string(), "Fix \"carry\", again".to_string()]];
+        assert_eq!(
+            to_csv(&["ID", "TITLE"], &rows),
+            "ID,TITLE\n7,\"Fix \"\"carry\"\", again\"\n"
+        );
+    }
+}
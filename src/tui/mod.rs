@@ -1,6 +1,12 @@
+pub mod action;
 pub mod app;
+pub mod clone;
+pub mod diff;
 pub mod event;
+pub mod export;
+pub mod modal;
 pub mod ui;
+pub mod unread;
 pub mod views;
 
 pub use app::*;
@@ -0,0 +1,238 @@
+//! Prompts drawn over the current view
+//!
+//! While a modal is open it gets every key. Confirming it hands back the
+//! [`Action`] to run; cancelling it hands back nothing.
+
+use crossterm::event::{KeyCode, KeyEvent};
+
+use super::action::Action;
+
+/// Builds the action for the text typed into an input box
+pub type Submit = Box<dyn FnOnce(String) -> Action + Send>;
+
+pub enum Modal {
+    /// A line of text, e.g. a comment
+    Input {
+        title: String,
+        value: String,
+        submit: Submit,
+    },
+    /// One of several choices, each with the action it runs
+    Menu {
+        title: String,
+        items: Vec<(String, Action)>,
+        selected: usize,
+    },
+    /// A yes/no question guarding `action`
+    Confirm {
+        title: String,
+        question: String,
+        action: Box<Action>,
+    },
+    /// How each item of a batch action went; any key closes it
+    Report {
+        title: String,
+        /// Whether it succeeded, and what to say about it
+        lines: Vec<(bool, String)>,
+    },
+}
+
+/// What a key did to an open modal
+pub enum Outcome {
+    /// Still open
+    Open,
+    /// Closed without doing anything
+    Cancelled,
+    /// Closed, with this to run
+    Done(Action),
+}
+
+impl Modal {
+    pub fn input(title: impl Into<String>, submit: Submit) -> Self {
+        Modal::Input {
+            title: title.into(),
+            value: String::new(),
+            submit,
+        }
+    }
+
+    pub fn menu(title: impl Into<String>, items: Vec<(String, Action)>, selected: usize) -> Self {
+        Modal::Menu {
+            title: title.into(),
+            selected: selected.min(items.len().saturating_sub(1)),
+            items,
+        }
+    }
+
+    pub fn confirm(title: impl Into<String>, question: impl Into<String>, action: Action) -> Self {
+        Modal::Confirm {
+            title: title.into(),
+            question: question.into(),
+            action: Box::new(action),
+        }
+    }
+
+    pub fn report(title: impl Into<String>, lines: Vec<(bool, String)>) -> Self {
+        Modal::Report {
+            title: title.into(),
+            lines,
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        match self {
+            Modal::Input { title, .. }
+            | Modal::Menu { title, .. }
+            | Modal::Confirm { title, .. }
+            | Modal::Report { title, .. } => title,
+        }
+    }
+
+    /// Handle a key, consuming the modal when it closes
+    pub fn handle_key(mut self, key: KeyEvent) -> (Option<Self>, Outcome) {
+        if key.code == KeyCode::Esc {
+            return (None, Outcome::Cancelled);
+        }
+
+        match &mut self {
+            Modal::Input { value, .. } => match key.code {
+                KeyCode::Char(c) => value.push(c),
+                KeyCode::Backspace => {
+                    value.pop();
+                }
+                KeyCode::Enter if value.trim().is_empty() => {
+                    return (None, Outcome::Cancelled);
+                }
+                KeyCode::Enter => {
+                    let Modal::Input { value, submit, .. } = self else {
+                        unreachable!()
+                    };
+                    return (None, Outcome::Done(submit(value.trim().to_string())));
+                }
+                _ => {}
+            },
+            Modal::Menu {
+                items, selected, ..
+            } => match key.code {
+                KeyCode::Up | KeyCode::Char('k') => *selected = selected.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') if *selected + 1 < items.len() => {
+                    *selected += 1;
+                }
+                KeyCode::Enter if items.is_empty() => return (None, Outcome::Cancelled),
+                KeyCode::Enter => {
+                    let Modal::Menu {
+                        mut items,
+                        selected,
+                        ..
+                    } = self
+                    else {
+                        unreachable!()
+                    };
+                    let (_, action) = items.swap_remove(selected);
+                    return (None, Outcome::Done(action));
+                }
+                _ => {}
+            },
+            Modal::Confirm { .. } => match key.code {
+                KeyCode::Char('y') | KeyCode::Enter => {
+                    let Modal::Confirm { action, .. } = self else {
+                        unreachable!()
+                    };
+                    return (None, Outcome::Done(*action));
+                }
+                KeyCode::Char('n') => return (None, Outcome::Cancelled),
+                _ => {}
+            },
+            Modal::Report { .. } => return (None, Outcome::Cancelled),
+        }
+        (Some(self), Outcome::Open)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn type_keys(mut modal: Modal, codes: &[KeyCode]) -> (Option<Modal>, Outcome) {
+        let mut outcome = Outcome::Open;
+        for code in codes {
+            let (next, result) = modal.handle_key(key(*code));
+            outcome = result;
+            match next {
+                Some(next) => modal = next,
+                None => return (None, outcome),
+            }
+        }
+        (Some(modal), outcome)
+    }
+
+    #[test]
+    fn input_submits_typed_text() {
+        let modal = Modal::input(
+            "Comment",
+            Box::new(|body| Action::CommentOnIssue { index: 2, body }),
+        );
+        let (modal, outcome) = type_keys(
+            modal,
+            &[
+                KeyCode::Char('o'),
+                KeyCode::Char('k'),
+                KeyCode::Char('x'),
+                KeyCode::Backspace,
+                KeyCode::Enter,
+            ],
+        );
+        assert!(modal.is_none());
+        match outcome {
+            Outcome::Done(Action::CommentOnIssue { index, body }) => {
+                assert_eq!((index, body.as_str()), (2, "ok"));
+            }
+            _ => panic!("expected a comment"),
+        }
+    }
+
+    #[test]
+    fn menus_pick_the_highlighted_action() {
+        let modal = Modal::menu(
+            "Vote",
+            vec![
+                ("Vote".to_string(), Action::VoteForIssue { index: 0 }),
+                ("Watch".to_string(), Action::WatchIssue { index: 0 }),
+            ],
+            0,
+        );
+        let (_, outcome) = type_keys(modal, &[KeyCode::Down, KeyCode::Down, KeyCode::Enter]);
+        assert!(matches!(
+            outcome,
+            Outcome::Done(Action::WatchIssue { index: 0 })
+        ));
+
+        let modal = Modal::menu("Empty", Vec::new(), 0);
+        let (modal, outcome) = type_keys(modal, &[KeyCode::Esc]);
+        assert!(modal.is_none());
+        assert!(matches!(outcome, Outcome::Cancelled));
+    }
+
+    #[test]
+    fn confirmations_need_a_yes() {
+        let stop = || Modal::confirm("Stop", "Stop #7?", Action::StopPipeline { index: 3 });
+
+        let (modal, outcome) = type_keys(stop(), &[KeyCode::Char('x')]);
+        assert!(modal.is_some());
+        assert!(matches!(outcome, Outcome::Open));
+
+        let (_, outcome) = type_keys(stop(), &[KeyCode::Char('n')]);
+        assert!(matches!(outcome, Outcome::Cancelled));
+
+        let (_, outcome) = type_keys(stop(), &[KeyCode::Char('y')]);
+        assert!(matches!(
+            outcome,
+            Outcome::Done(Action::StopPipeline { index: 3 })
+        ));
+    }
+}
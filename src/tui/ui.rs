@@ -3,11 +3,20 @@ use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Tabs},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Tabs},
 };
 
+use super::action::BatchKind;
 use super::app::App;
+use super::clone::CloneJob;
+use super::diff::{DiffView, LineKind};
+use super::modal::Modal;
+use super::unread::Trackable;
 use super::views::View;
+use crate::cli::icons::Icon;
+
+/// Lines the clone output panel takes, borders included
+const CLONE_PANEL_HEIGHT: u16 = 8;
 
 /// Draw the application
 pub fn draw(f: &mut Frame, app: &App) {
@@ -21,12 +30,292 @@ pub fn draw(f: &mut Frame, app: &App) {
         .split(f.area());
 
     draw_header(f, app, chunks[0]);
-    draw_main(f, app, chunks[1]);
+    match (&app.diff, &app.clone) {
+        (Some(diff), _) => draw_diff(f, diff, chunks[1]),
+        (None, Some(job)) => {
+            let main = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(0), Constraint::Length(CLONE_PANEL_HEIGHT)])
+                .split(chunks[1]);
+            draw_main(f, app, main[0]);
+            draw_clone(f, job, main[1]);
+        }
+        (None, None) => draw_main(f, app, chunks[1]),
+    }
     draw_footer(f, app, chunks[2]);
+
+    if let Some(modal) = &app.modal {
+        draw_modal(f, modal, f.area());
+    }
+}
+
+fn draw_clone(f: &mut Frame, job: &CloneJob, area: Rect) {
+    let (title, color) = match &job.finished {
+        None => (
+            format!(" Cloning {} into {} ", job.repository, job.target.display()),
+            Color::Yellow,
+        ),
+        Some(Ok(())) => (
+            format!(
+                " Cloned {} into {} (Esc to close) ",
+                job.repository,
+                job.target.display()
+            ),
+            Color::Green,
+        ),
+        Some(Err(_)) => (
+            format!(" Cloning {} failed (Esc to close) ", job.repository),
+            Color::Red,
+        ),
+    };
+    let output = job.output();
+    let shown = output
+        .len()
+        .saturating_sub(area.height.saturating_sub(2) as usize);
+    let mut lines: Vec<Line> = output[shown..]
+        .iter()
+        .map(|l| Line::raw(l.as_str()))
+        .collect();
+    if let Some(Err(e)) = &job.finished {
+        lines.push(Line::styled(e.as_str(), Style::default().fg(Color::Red)));
+    }
+    let panel = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(color))
+            .title(title),
+    );
+    f.render_widget(panel, area);
+}
+
+/// The diff with each line's old and new numbers, and inline comments
+/// under the lines they're on
+fn draw_diff(f: &mut Frame, diff: &DiffView, area: Rect) {
+    let number = |n: Option<u32>| {
+        n.map(|n| format!("{:>5}", n))
+            .unwrap_or_else(|| " ".repeat(5))
+    };
+    let mut items = Vec::new();
+    let mut selected_row = 0;
+    for (index, line) in diff.lines.iter().enumerate() {
+        if index == diff.selected {
+            selected_row = items.len();
+        }
+        let style = match line.kind {
+            LineKind::File => Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+            LineKind::Meta => Style::default().fg(Color::DarkGray),
+            LineKind::Hunk => Style::default().fg(Color::Cyan),
+            LineKind::Added => Style::default().fg(Color::Green),
+            LineKind::Removed => Style::default().fg(Color::Red),
+            LineKind::Context => Style::default(),
+        };
+        items.push(ListItem::new(Line::from(vec![
+            Span::styled(
+                format!("{} {} ", number(line.old), number(line.new)),
+                Style::default().fg(Color::DarkGray),
+            ),
+            Span::styled(line.text.as_str(), style),
+        ])));
+
+        for comment in diff.comments_on(line) {
+            let mut text = vec![Line::styled(
+                format!("{:>12}┃ {}", "", comment.user.display_name),
+                Style::default()
+                    .fg(Color::Magenta)
+                    .add_modifier(Modifier::BOLD),
+            )];
+            text.extend(comment.content.raw.lines().map(|l| {
+                Line::styled(
+                    format!("{:>12}┃ {}", "", l),
+                    Style::default().fg(Color::Magenta),
+                )
+            }));
+            items.push(ListItem::new(text));
+        }
+    }
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            " #{} in {} (Esc to close) ",
+            diff.pr_id, diff.repository
+        )))
+        .highlight_style(Style::default().bg(Color::DarkGray));
+    let mut state = ratatui::widgets::ListState::default();
+    state.select(Some(selected_row));
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_modal(f: &mut Frame, modal: &Modal, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .title(format!(" {} ", modal.title()));
+
+    match modal {
+        Modal::Input { value, .. } => {
+            let area = centered(area, 60, 3);
+            f.render_widget(Clear, area);
+            let text = Line::from(vec![
+                Span::raw(value.as_str()),
+                Span::styled("█", Style::default().fg(Color::Cyan)),
+            ]);
+            f.render_widget(Paragraph::new(text).block(block), area);
+        }
+        Modal::Menu {
+            items, selected, ..
+        } => {
+            let area = centered(area, 40, items.len() as u16 + 2);
+            f.render_widget(Clear, area);
+            let list = List::new(
+                items
+                    .iter()
+                    .map(|(label, _)| ListItem::new(label.as_str()))
+                    .collect::<Vec<_>>(),
+            )
+            .block(block)
+            .highlight_style(
+                Style::default()
+                    .bg(Color::DarkGray)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol("▶ ");
+            let mut state = ratatui::widgets::ListState::default();
+            state.select(Some(*selected));
+            f.render_stateful_widget(list, area, &mut state);
+        }
+        Modal::Confirm { question, .. } => {
+            let area = centered(area, 50, 3);
+            f.render_widget(Clear, area);
+            let text = Line::from(vec![
+                Span::raw(question.as_str()),
+                Span::styled(" [y/n]", Style::default().fg(Color::Cyan)),
+            ]);
+            f.render_widget(Paragraph::new(text).block(block), area);
+        }
+        Modal::Report { lines, .. } => {
+            let area = centered(area, 70, lines.len() as u16 + 2);
+            f.render_widget(Clear, area);
+            let items: Vec<ListItem> = lines
+                .iter()
+                .map(|(ok, line)| {
+                    let (icon, color) = if *ok {
+                        (Icon::Ok, Color::Green)
+                    } else {
+                        (Icon::Error, Color::Red)
+                    };
+                    ListItem::new(Line::from(vec![
+                        Span::styled(format!("{} ", icon), Style::default().fg(color)),
+                        Span::raw(line.as_str()),
+                    ]))
+                })
+                .collect();
+            f.render_widget(List::new(items).block(block), area);
+        }
+    }
+}
+
+/// The marker in front of row `index`: filled when it's marked for a batch
+/// action, and only once something in the view is
+fn mark(app: &App, index: usize) -> Option<Span<'static>> {
+    if app.marked.is_empty() {
+        None
+    } else if app.marked.contains(&index) {
+        Some(Span::styled(
+            format!("{} ", Icon::Marked),
+            Style::default().fg(Color::Magenta),
+        ))
+    } else {
+        Some(Span::raw(format!("{} ", Icon::Unmarked)))
+    }
+}
+
+/// The unread marker in front of a pull request or issue, or as much space
+fn unread(app: &App, item: &impl Trackable) -> Span<'static> {
+    if app.seen.is_unread(item) {
+        Span::styled(
+            format!("{} ", Icon::Unread),
+            Style::default().fg(Color::Blue),
+        )
+    } else {
+        Span::raw(" ".repeat(Span::raw(Icon::Unread.glyph()).width() + 1))
+    }
+}
+
+/// The title of a view the unread filter applies to
+fn unread_title(app: &App, title: &str) -> String {
+    if app.unread_only {
+        format!("{}: unread", title)
+    } else {
+        title.to_string()
+    }
+}
+
+/// A dashboard count, with a spinner while more are loading
+fn count(app: &App, n: usize, loading: bool) -> String {
+    if loading {
+        format!(" ({}) {}", n, app.spinner())
+    } else {
+        format!(" ({})", n)
+    }
+}
+
+/// Spinner rows for the repositories `view` is still waiting on
+fn pending_rows(app: &App, view: View) -> Vec<ListItem<'static>> {
+    let spinner = app.spinner();
+    app.pending_in(view)
+        .map(|name| {
+            ListItem::new(Line::styled(
+                format!("{} {}", spinner, name),
+                Style::default().fg(Color::DarkGray),
+            ))
+        })
+        .collect()
+}
+
+/// A list title, with how many rows are marked if any are
+fn list_title(app: &App, title: &str) -> String {
+    if app.marked.is_empty() {
+        format!(" {} ", title)
+    } else {
+        format!(" {} ({} marked) ", title, app.marked.len())
+    }
+}
+
+/// A `width`-percent wide, `height`-line tall area in the middle of `area`
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = (area.width * width / 100).max(20).min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
 }
 
 fn draw_header(f: &mut Frame, app: &App, area: Rect) {
-    let titles = vec!["Dashboard", "Repos", "PRs", "Issues", "Pipelines"];
+    // Each tab with when its data last loaded
+    let titles: Vec<Line> = [
+        (View::Dashboard, "Dashboard"),
+        (View::Repositories, "Repos"),
+        (View::PullRequests, "PRs"),
+        (View::Issues, "Issues"),
+        (View::Pipelines, "Pipelines"),
+    ]
+    .into_iter()
+    .map(|(view, name)| match app.refreshed.get(&view) {
+        Some(at) => Line::from(vec![
+            Span::raw(name),
+            Span::styled(
+                format!(" {}", at.format("%H:%M")),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ]),
+        None => Line::from(name),
+    })
+    .collect();
     let selected = match app.current_view {
         View::Dashboard => 0,
         View::Repositories => 1,
@@ -35,12 +324,22 @@ fn draw_header(f: &mut Frame, app: &App, area: Rect) {
         View::Pipelines => 4,
     };
 
+    let title = if app.client.as_ref().is_some_and(|c| c.is_offline()) {
+        match crate::api::snapshot::oldest_served() {
+            Some(fetched_at) => format!(
+                " Bitbucket CLI · offline, data from {} ",
+                fetched_at
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+            ),
+            None => " Bitbucket CLI · offline ".to_string(),
+        }
+    } else {
+        " Bitbucket CLI ".to_string()
+    };
+
     let tabs = Tabs::new(titles)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title(" Bitbucket CLI "),
-        )
+        .block(Block::default().borders(Borders::ALL).title(title))
         .select(selected)
         .style(Style::default().fg(Color::White))
         .highlight_style(
@@ -80,34 +379,38 @@ fn draw_dashboard(f: &mut Frame, app: &App, area: Rect) {
     // Dashboard menu
     let items: Vec<ListItem> = vec![
         ListItem::new(Line::from(vec![
-            Span::styled("📁 ", Style::default()),
+            Span::raw(format!("{} ", Icon::Repository)),
             Span::raw("Repositories"),
             Span::styled(
-                format!(" ({})", app.repositories.len()),
+                count(app, app.repositories.len(), app.loading),
                 Style::default().fg(Color::DarkGray),
             ),
         ])),
         ListItem::new(Line::from(vec![
-            Span::styled("🔀 ", Style::default()),
+            Span::raw(format!("{} ", Icon::PullRequest)),
             Span::raw("Pull Requests"),
             Span::styled(
-                format!(" ({})", app.pull_requests.len()),
+                count(
+                    app,
+                    app.pull_requests.len(),
+                    app.is_pending(View::PullRequests),
+                ),
                 Style::default().fg(Color::DarkGray),
             ),
         ])),
         ListItem::new(Line::from(vec![
-            Span::styled("🐛 ", Style::default()),
+            Span::raw(format!("{} ", Icon::Issues)),
             Span::raw("Issues"),
             Span::styled(
-                format!(" ({})", app.issues.len()),
+                count(app, app.issues.len(), app.is_pending(View::Issues)),
                 Style::default().fg(Color::DarkGray),
             ),
         ])),
         ListItem::new(Line::from(vec![
-            Span::styled("⚙️  ", Style::default()),
+            Span::raw(format!("{} ", Icon::Pipelines)),
             Span::raw("Pipelines"),
             Span::styled(
-                format!(" ({})", app.pipelines.len()),
+                count(app, app.pipelines.len(), app.is_pending(View::Pipelines)),
                 Style::default().fg(Color::DarkGray),
             ),
         ])),
@@ -132,7 +435,7 @@ fn draw_dashboard(f: &mut Frame, app: &App, area: Rect) {
 }
 
 fn draw_repositories(f: &mut Frame, app: &App, area: Rect) {
-    let items: Vec<ListItem> = if app.repositories.is_empty() {
+    let mut items: Vec<ListItem> = if app.repositories.is_empty() && !app.loading {
         vec![ListItem::new(
             "No repositories loaded. Press 'r' to refresh.",
         )]
@@ -141,9 +444,9 @@ fn draw_repositories(f: &mut Frame, app: &App, area: Rect) {
             .iter()
             .map(|repo| {
                 let private_badge = if repo.is_private.unwrap_or(false) {
-                    "🔒"
+                    Icon::Private
                 } else {
-                    "🌐"
+                    Icon::Public
                 };
                 ListItem::new(Line::from(vec![
                     Span::raw(format!("{} ", private_badge)),
@@ -158,12 +461,21 @@ fn draw_repositories(f: &mut Frame, app: &App, area: Rect) {
             .collect()
     };
 
+    if app.loading {
+        items.push(ListItem::new(Line::styled(
+            format!("{} Loading repositories...", app.spinner()),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+
+    let title = if app.has_more_repositories() {
+        format!(" Repositories ({}+) ", app.repositories.len())
+    } else {
+        format!(" Repositories ({}) ", app.repositories.len())
+    };
+
     let list = List::new(items)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title(" Repositories "),
-        )
+        .block(Block::default().borders(Borders::ALL).title(title))
         .highlight_style(
             Style::default()
                 .bg(Color::DarkGray)
@@ -177,34 +489,46 @@ fn draw_repositories(f: &mut Frame, app: &App, area: Rect) {
 }
 
 fn draw_pull_requests(f: &mut Frame, app: &App, area: Rect) {
-    let items: Vec<ListItem> = if app.pull_requests.is_empty() {
-        vec![ListItem::new(
-            "No pull requests loaded. Press 'r' to refresh.",
-        )]
-    } else {
-        app.pull_requests
-            .iter()
-            .map(|pr| {
-                let state_color = match pr.state {
-                    crate::models::PullRequestState::Open => Color::Green,
-                    crate::models::PullRequestState::Merged => Color::Magenta,
-                    crate::models::PullRequestState::Declined => Color::Red,
-                    crate::models::PullRequestState::Superseded => Color::Yellow,
-                };
-                ListItem::new(Line::from(vec![
-                    Span::styled(format!("[{}] ", pr.state), Style::default().fg(state_color)),
-                    Span::styled(format!("#{} ", pr.id), Style::default().fg(Color::DarkGray)),
-                    Span::raw(&pr.title),
-                ]))
-            })
-            .collect()
-    };
+    let visible = app.visible_pull_requests();
+    let mut items: Vec<ListItem> =
+        if app.pull_requests.is_empty() && !app.is_pending(View::PullRequests) {
+            vec![ListItem::new(
+                "No pull requests loaded. Press 'r' to refresh.",
+            )]
+        } else if visible.is_empty() && !app.is_pending(View::PullRequests) {
+            vec![ListItem::new(
+                "No unread pull requests. Press 'U' to show all.",
+            )]
+        } else {
+            visible
+                .into_iter()
+                .map(|index| {
+                    let pr = &app.pull_requests[index];
+                    let state_color = match pr.state {
+                        crate::models::PullRequestState::Open => Color::Green,
+                        crate::models::PullRequestState::Merged => Color::Magenta,
+                        crate::models::PullRequestState::Declined => Color::Red,
+                        crate::models::PullRequestState::Superseded => Color::Yellow,
+                        crate::models::PullRequestState::Unknown => Color::Gray,
+                    };
+                    let spans = mark(app, index).into_iter().chain([
+                        unread(app, pr),
+                        Span::styled(format!("[{}] ", pr.state), Style::default().fg(state_color)),
+                        Span::styled(format!("#{} ", pr.id), Style::default().fg(Color::DarkGray)),
+                        Span::raw(&pr.title),
+                    ]);
+                    ListItem::new(Line::from_iter(spans))
+                })
+                .collect()
+        };
+
+    items.extend(pending_rows(app, View::PullRequests));
 
     let list = List::new(items)
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title(" Pull Requests "),
+                .title(list_title(app, &unread_title(app, "Pull Requests"))),
         )
         .highlight_style(
             Style::default()
@@ -219,32 +543,68 @@ fn draw_pull_requests(f: &mut Frame, app: &App, area: Rect) {
 }
 
 fn draw_issues(f: &mut Frame, app: &App, area: Rect) {
-    let items: Vec<ListItem> = if app.issues.is_empty() {
+    let visible = app.visible_issues();
+    let mut items: Vec<ListItem> = if app.issues.is_empty() && !app.is_pending(View::Issues) {
         vec![ListItem::new("No issues loaded. Press 'r' to refresh.")]
+    } else if visible.is_empty() && !app.is_pending(View::Issues) {
+        vec![ListItem::new("No unread issues. Press 'U' to show all.")]
     } else {
-        app.issues
-            .iter()
-            .map(|issue| {
+        visible
+            .into_iter()
+            .map(|index| {
+                let issue = &app.issues[index];
                 let kind_icon = match issue.kind {
-                    crate::models::IssueKind::Bug => "🐛",
-                    crate::models::IssueKind::Enhancement => "✨",
-                    crate::models::IssueKind::Proposal => "💡",
-                    crate::models::IssueKind::Task => "📋",
+                    crate::models::IssueKind::Bug => Icon::Bug,
+                    crate::models::IssueKind::Enhancement => Icon::Enhancement,
+                    crate::models::IssueKind::Proposal => Icon::Proposal,
+                    crate::models::IssueKind::Task => Icon::Task,
+                    crate::models::IssueKind::Unknown => Icon::Other,
                 };
-                ListItem::new(Line::from(vec![
+                let mut spans: Vec<Span> = mark(app, index).into_iter().collect();
+                spans.extend([
+                    unread(app, issue),
                     Span::raw(format!("{} ", kind_icon)),
                     Span::styled(
                         format!("#{} ", issue.id),
                         Style::default().fg(Color::DarkGray),
                     ),
+                    Span::styled(
+                        format!("[{}] ", issue.state),
+                        Style::default().fg(Color::Yellow),
+                    ),
                     Span::raw(&issue.title),
-                ]))
+                ]);
+                if let Some(assignee) = &issue.assignee {
+                    spans.push(Span::styled(
+                        format!(" · {}", assignee.display_name),
+                        Style::default().fg(Color::Cyan),
+                    ));
+                }
+                let counts: Vec<String> = [(Icon::Vote, issue.votes), (Icon::Watch, issue.watches)]
+                    .into_iter()
+                    .filter_map(|(icon, count)| {
+                        count.filter(|n| *n > 0).map(|n| format!("{}{}", icon, n))
+                    })
+                    .collect();
+                if !counts.is_empty() {
+                    spans.push(Span::styled(
+                        format!(" {}", counts.join(" ")),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                ListItem::new(Line::from(spans))
             })
             .collect()
     };
 
+    items.extend(pending_rows(app, View::Issues));
+
     let list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title(" Issues "))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(list_title(app, &unread_title(app, "Issues"))),
+        )
         .highlight_style(
             Style::default()
                 .bg(Color::DarkGray)
@@ -258,45 +618,63 @@ fn draw_issues(f: &mut Frame, app: &App, area: Rect) {
 }
 
 fn draw_pipelines(f: &mut Frame, app: &App, area: Rect) {
-    let items: Vec<ListItem> = if app.pipelines.is_empty() {
+    let visible = app.visible_pipelines();
+    let mut items: Vec<ListItem> = if app.pipelines.is_empty() && !app.is_pending(View::Pipelines) {
         vec![ListItem::new("No pipelines loaded. Press 'r' to refresh.")]
+    } else if visible.is_empty() && !app.is_pending(View::Pipelines) {
+        vec![ListItem::new(
+            "No pipelines match the filter. Press 'f' or 'F' to change it.",
+        )]
     } else {
-        app.pipelines
-            .iter()
-            .map(|pipeline| {
+        visible
+            .into_iter()
+            .map(|index| {
+                let pipeline = &app.pipelines[index];
                 let (status_icon, status_color) = match pipeline.state.name {
-                    crate::models::PipelineStateName::Pending => ("⏳", Color::Yellow),
-                    crate::models::PipelineStateName::InProgress => ("🔄", Color::Blue),
+                    crate::models::PipelineStateName::Pending => (Icon::Pending, Color::Yellow),
+                    crate::models::PipelineStateName::InProgress => (Icon::Running, Color::Blue),
                     crate::models::PipelineStateName::Completed => {
                         if let Some(result) = &pipeline.state.result {
                             match result.name {
                                 crate::models::PipelineResultName::Successful => {
-                                    ("✅", Color::Green)
+                                    (Icon::Passed, Color::Green)
                                 }
-                                crate::models::PipelineResultName::Failed => ("❌", Color::Red),
-                                _ => ("⚪", Color::Gray),
+                                crate::models::PipelineResultName::Failed => {
+                                    (Icon::Failed, Color::Red)
+                                }
+                                _ => (Icon::Neutral, Color::Gray),
                             }
                         } else {
-                            ("⚪", Color::Gray)
+                            (Icon::Neutral, Color::Gray)
                         }
                     }
-                    crate::models::PipelineStateName::Halted => ("⛔", Color::Red),
-                    crate::models::PipelineStateName::Paused => ("⏸️", Color::Yellow),
+                    crate::models::PipelineStateName::Halted => (Icon::Halted, Color::Red),
+                    crate::models::PipelineStateName::Paused => (Icon::Paused, Color::Yellow),
+                    crate::models::PipelineStateName::Unknown => (Icon::Neutral, Color::Gray),
                 };
-                ListItem::new(Line::from(vec![
+                let spans = mark(app, index).into_iter().chain([
                     Span::raw(format!("{} ", status_icon)),
                     Span::styled(
                         format!("#{} ", pipeline.build_number),
                         Style::default().fg(status_color),
                     ),
-                    Span::raw(pipeline.target.ref_name.as_deref().unwrap_or("unknown")),
-                ]))
+                    Span::raw(pipeline.target.to_string()),
+                ]);
+                ListItem::new(Line::from_iter(spans))
             })
             .collect()
     };
 
+    items.extend(pending_rows(app, View::Pipelines));
+
     let list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title(" Pipelines "))
+        .block(Block::default().borders(Borders::ALL).title(list_title(
+            app,
+            &match app.pipeline_filter.describe() {
+                Some(shown) => format!("Pipelines: {}", shown),
+                None => "Pipelines".to_string(),
+            },
+        )))
         .highlight_style(
             Style::default()
                 .bg(Color::DarkGray)
@@ -332,9 +710,60 @@ fn draw_footer(f: &mut Frame, app: &App, area: Rect) {
             Span::raw(" navigate  "),
             Span::styled("Enter", Style::default().fg(Color::Cyan)),
             Span::raw(" select  "),
-            Span::styled("r", Style::default().fg(Color::Cyan)),
-            Span::raw(" refresh"),
+            Span::styled("r/R", Style::default().fg(Color::Cyan)),
+            Span::raw(" refresh view/all  "),
+            Span::styled("w", Style::default().fg(Color::Cyan)),
+            Span::raw(" workspace"),
+        ])
+    };
+    let status_text = if app.modal.is_some() {
+        Line::from(vec![
+            Span::styled("Enter", Style::default().fg(Color::Cyan)),
+            Span::raw(" confirm  "),
+            Span::styled("Esc", Style::default().fg(Color::Cyan)),
+            Span::raw(" cancel"),
         ])
+    } else if app.error.is_none() && app.status.is_none() {
+        let hints: &[(&str, &str)] = match app.current_view {
+            _ if app.diff.is_some() => &[("c", "comment on line"), ("Esc", "close diff")],
+            View::PullRequests => &[("d", "diff"), ("U", "unread")],
+            View::Issues => &[
+                ("c", "comment"),
+                ("a", "assign me"),
+                ("s", "state"),
+                ("v", "vote"),
+                ("W", "watch"),
+                ("U", "unread"),
+            ],
+            View::Repositories => &[("c", "clone"), ("o", "open"), ("g", "copy URL")],
+            View::Pipelines => &[
+                ("t", "trigger"),
+                ("x", "stop"),
+                ("f", "status"),
+                ("F", "this repo"),
+            ],
+            _ => &[],
+        };
+        let batch: &[(&str, &str)] =
+            if app.diff.is_some() || BatchKind::for_view(app.current_view).is_empty() {
+                &[]
+            } else {
+                &[("space", "mark"), ("b", "batch")]
+            };
+        let export: &[(&str, &str)] = if app.diff.is_some() || app.current_view == View::Dashboard {
+            &[]
+        } else {
+            &[("E", "export")]
+        };
+        let mut line = status_text;
+        for (key, label) in hints.iter().chain(batch).chain(export) {
+            line.push_span(Span::raw("  "));
+            line.push_span(Span::styled(*key, Style::default().fg(Color::Cyan)));
+            line.push_span(Span::raw(format!(" {}", label)));
+        }
+        line
+    } else {
+        status_text
     };
 
     let footer =
@@ -0,0 +1,162 @@
+//! Which pull requests and issues changed since they were last opened
+//!
+//! The `updated_on` of each item seen with `Enter` (or `d` for a diff) is
+//! kept in `$XDG_STATE_HOME/bitbucket-cli/tui-seen.json`. Anything updated
+//! after that is unread, as is anything never opened that changed after the
+//! file was first written, so the first run doesn't flag the whole backlog.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Config, xdg};
+use crate::models::{Issue, PullRequest};
+
+const FILE: &str = "tui-seen.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Seen {
+    /// When tracking started; older, never-opened items count as read
+    since: DateTime<Utc>,
+    /// `updated_on` of each item when it was last opened, by [`key`]
+    items: BTreeMap<String, DateTime<Utc>>,
+}
+
+impl Default for Seen {
+    fn default() -> Self {
+        Self {
+            since: Utc::now(),
+            items: BTreeMap::new(),
+        }
+    }
+}
+
+/// Something that can be read and updated
+pub trait Trackable {
+    /// e.g. `pr:acme/engine#7`
+    fn key(&self) -> String;
+    fn updated(&self) -> Option<DateTime<Utc>>;
+}
+
+impl Trackable for PullRequest {
+    fn key(&self) -> String {
+        let repository = self
+            .destination
+            .repository
+            .as_ref()
+            .map(|r| r.full_name.as_str())
+            .unwrap_or_default();
+        format!("pr:{}#{}", repository, self.id)
+    }
+
+    fn updated(&self) -> Option<DateTime<Utc>> {
+        Some(self.updated_on)
+    }
+}
+
+impl Trackable for Issue {
+    fn key(&self) -> String {
+        let repository = self
+            .repository
+            .as_ref()
+            .map(|r| r.full_name.as_str())
+            .unwrap_or_default();
+        format!("issue:{}#{}", repository, self.id)
+    }
+
+    fn updated(&self) -> Option<DateTime<Utc>> {
+        self.updated_on
+    }
+}
+
+impl Seen {
+    /// The saved state, or a fresh start when there is none or it's
+    /// unreadable. A fresh start is saved straight away, so the time
+    /// tracking began holds even if nothing is opened this session.
+    pub fn load() -> Self {
+        let Ok(path) = path() else {
+            return Self::default();
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let seen = Self::default();
+                if let Err(e) = seen.save() {
+                    tracing::warn!("tui seen state: {:#}", e);
+                }
+                return seen;
+            }
+            Err(_) => return Self::default(),
+        };
+        serde_json::from_str(&text).unwrap_or_else(|e| {
+            tracing::warn!("ignoring {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = path()?;
+        xdg::ensure_dir(&Config::state_dir()?)?;
+        fs::write(&path, serde_json::to_string(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn is_unread(&self, item: &impl Trackable) -> bool {
+        let Some(updated) = item.updated() else {
+            return false;
+        };
+        updated > self.items.get(&item.key()).copied().unwrap_or(self.since)
+    }
+
+    /// Mark `item` read as it is now; whether that changed anything
+    pub fn mark_read(&mut self, item: &impl Trackable) -> bool {
+        if !self.is_unread(item) {
+            return false;
+        }
+        let Some(updated) = item.updated() else {
+            return false;
+        };
+        self.items.insert(item.key(), updated);
+        true
+    }
+}
+
+fn path() -> Result<PathBuf> {
+    Ok(Config::state_dir()?.join(FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    struct Item(Option<DateTime<Utc>>);
+
+    impl Trackable for Item {
+        fn key(&self) -> String {
+            "pr:acme/engine#7".to_string()
+        }
+
+        fn updated(&self) -> Option<DateTime<Utc>> {
+            self.0
+        }
+    }
+
+    #[test]
+    fn items_are_unread_once_updated_after_being_seen() {
+        let mut seen = Seen::default();
+        let earlier = seen.since - Duration::hours(1);
+        let later = seen.since + Duration::hours(1);
+        assert!(!seen.is_unread(&Item(Some(earlier))));
+        assert!(!seen.is_unread(&Item(None)));
+        assert!(seen.is_unread(&Item(Some(later))));
+
+        assert!(seen.mark_read(&Item(Some(later))));
+        assert!(!seen.is_unread(&Item(Some(later))));
+        assert!(seen.is_unread(&Item(Some(later + Duration::minutes(5)))));
+    }
+}
@@ -7,6 +7,7 @@ use ratatui::{
     widgets::{Block, Borders, List, ListItem, Paragraph},
 };
 
+use crate::cli::format;
 use crate::models::{Issue, IssueKind, IssuePriority, IssueState};
 use crate::tui::app::App;
 
@@ -116,7 +117,7 @@ impl IssuesView {
                 Line::from(""),
                 Line::from(vec![
                     Span::styled("Created: ", Style::default().fg(Color::DarkGray)),
-                    Span::raw(issue.created_on.format("%Y-%m-%d %H:%M").to_string()),
+                    Span::raw(format::date(&issue.created_on)),
                 ]),
                 Line::from(""),
                 if issue
@@ -151,6 +152,7 @@ impl IssuesView {
             IssueKind::Enhancement => "✨",
             IssueKind::Proposal => "💡",
             IssueKind::Task => "📋",
+            IssueKind::Unknown => "•",
         };
 
         let state_color = Self::state_color(&issue.state);
@@ -170,6 +172,7 @@ impl IssuesView {
             IssueState::OnHold => Color::Yellow,
             IssueState::Invalid | IssueState::Duplicate | IssueState::Wontfix => Color::DarkGray,
             IssueState::Closed => Color::Magenta,
+            IssueState::Unknown => Color::Gray,
         }
     }
 
@@ -180,6 +183,7 @@ impl IssuesView {
             IssuePriority::Major => Color::Yellow,
             IssuePriority::Critical => Color::Red,
             IssuePriority::Blocker => Color::LightRed,
+            IssuePriority::Unknown => Color::Gray,
         }
     }
 }
@@ -3,8 +3,11 @@ pub mod issues;
 pub mod prs;
 pub mod repos;
 
+use crate::models::{Pipeline, PipelineResultName, PipelineStateName};
+use crate::tui::action;
+
 /// Available views in the TUI
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum View {
     Dashboard,
     Repositories,
@@ -43,3 +46,75 @@ impl ViewState {
         self.scroll_offset = 0;
     }
 }
+
+/// Pipeline states `f` cycles the Pipelines view through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PipelineStatus {
+    #[default]
+    All,
+    Failed,
+    Running,
+}
+
+impl PipelineStatus {
+    pub fn next(self) -> Self {
+        match self {
+            PipelineStatus::All => PipelineStatus::Failed,
+            PipelineStatus::Failed => PipelineStatus::Running,
+            PipelineStatus::Running => PipelineStatus::All,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PipelineStatus::All => "all",
+            PipelineStatus::Failed => "failed",
+            PipelineStatus::Running => "running",
+        }
+    }
+}
+
+/// Which of the loaded pipelines the Pipelines view shows
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PipelineFilter {
+    pub status: PipelineStatus,
+    /// `workspace/repo` narrowed to with `F`
+    pub repository: Option<String>,
+}
+
+impl PipelineFilter {
+    pub fn matches(&self, pipeline: &Pipeline) -> bool {
+        let status = match self.status {
+            PipelineStatus::All => true,
+            PipelineStatus::Failed => {
+                pipeline.state.name == PipelineStateName::Halted
+                    || pipeline.state.result.as_ref().is_some_and(|r| {
+                        matches!(
+                            r.name,
+                            PipelineResultName::Failed | PipelineResultName::Error
+                        )
+                    })
+            }
+            PipelineStatus::Running => action::is_running(pipeline),
+        };
+        status
+            && self.repository.as_deref().is_none_or(|wanted| {
+                pipeline
+                    .repository
+                    .as_ref()
+                    .is_some_and(|r| r.full_name == wanted)
+            })
+    }
+
+    /// What the filter keeps, for the list title; `None` when it keeps everything
+    pub fn describe(&self) -> Option<String> {
+        let parts: Vec<&str> = [
+            (self.status != PipelineStatus::All).then(|| self.status.label()),
+            self.repository.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+}
@@ -7,6 +7,7 @@ use ratatui::{
     widgets::{Block, Borders, List, ListItem, Paragraph},
 };
 
+use crate::cli::format;
 use crate::models::{PullRequest, PullRequestState};
 use crate::tui::app::App;
 
@@ -104,11 +105,11 @@ impl PrsView {
                 Line::from(""),
                 Line::from(vec![
                     Span::styled("Created: ", Style::default().fg(Color::DarkGray)),
-                    Span::raw(pr.created_on.format("%Y-%m-%d %H:%M").to_string()),
+                    Span::raw(format::date(&pr.created_on)),
                 ]),
                 Line::from(vec![
                     Span::styled("Updated: ", Style::default().fg(Color::DarkGray)),
-                    Span::raw(pr.updated_on.format("%Y-%m-%d %H:%M").to_string()),
+                    Span::raw(format::date(&pr.updated_on)),
                 ]),
                 Line::from(""),
                 if let Some(count) = pr.comment_count {
@@ -139,6 +140,7 @@ impl PrsView {
             PullRequestState::Merged => "●",
             PullRequestState::Declined => "✗",
             PullRequestState::Superseded => "◌",
+            PullRequestState::Unknown => "?",
         };
 
         ListItem::new(Line::from(vec![
@@ -154,6 +156,7 @@ impl PrsView {
             PullRequestState::Merged => Color::Magenta,
             PullRequestState::Declined => Color::Red,
             PullRequestState::Superseded => Color::Yellow,
+            PullRequestState::Unknown => Color::Gray,
         }
     }
 }
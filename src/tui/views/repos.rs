@@ -7,6 +7,7 @@ use ratatui::{
     widgets::{Block, Borders, List, ListItem, Paragraph},
 };
 
+use crate::cli::format;
 use crate::models::Repository;
 use crate::tui::app::App;
 
@@ -114,7 +115,7 @@ impl ReposView {
                     Span::styled("Created: ", Style::default().fg(Color::DarkGray)),
                     Span::raw(
                         repo.created_on
-                            .map(|d| d.format("%Y-%m-%d").to_string())
+                            .map(|d| format::date(&d))
                             .unwrap_or_default(),
                     ),
                 ]),
@@ -122,7 +123,7 @@ impl ReposView {
                     Span::styled("Updated: ", Style::default().fg(Color::DarkGray)),
                     Span::raw(
                         repo.updated_on
-                            .map(|d| d.format("%Y-%m-%d").to_string())
+                            .map(|d| format::date(&d))
                             .unwrap_or_default(),
                     ),
                 ]),
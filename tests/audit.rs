@@ -0,0 +1,243 @@
+mod common;
+
+use common::TestEnv;
+
+#[tokio::test]
+async fn changes_are_recorded_and_verified() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/issues", "issues")
+        .await;
+    env.expect(
+        "PUT",
+        "/repositories/acme/engine/issues/3",
+        200,
+        Some("issue"),
+    )
+    .await;
+
+    env.run(&["issue", "list", "acme/engine"])
+        .await
+        .assert_success();
+    env.run(&["issue", "close", "acme/engine", "3"])
+        .await
+        .assert_success();
+
+    let result = env.run(&["audit", "show", "--verify"]).await;
+    result.assert_success();
+    assert_eq!(result.stdout.lines().count(), 1, "{}", result.stdout);
+    assert!(
+        result.stdout.ends_with(
+            "\tissue close acme/engine 3\tPUT /repositories/acme/engine/issues/3 200\tok\n"
+        ),
+        "{}",
+        result.stdout
+    );
+    assert!(
+        result.stderr.contains("Verified 1 entry"),
+        "{}",
+        result.stderr
+    );
+}
+
+#[tokio::test]
+async fn tampering_fails_verification() {
+    let env = TestEnv::new().await;
+    env.expect(
+        "PUT",
+        "/repositories/acme/engine/issues/3",
+        200,
+        Some("issue"),
+    )
+    .await;
+    env.run(&["issue", "close", "acme/engine", "3"])
+        .await
+        .assert_success();
+
+    let log = env.home().join("state/bitbucket-cli/audit.log");
+    let contents = std::fs::read_to_string(&log).unwrap();
+    std::fs::write(&log, contents.replace("\"exit_code\":0", "\"exit_code\":1")).unwrap();
+
+    let result = env.run(&["audit", "show", "--verify"]).await;
+    assert!(!result.success());
+    assert!(
+        result.stderr.contains("Entry 1 was modified"),
+        "{}",
+        result.stderr
+    );
+}
+
+#[tokio::test]
+async fn branch_restrictions_report_unprotected_main_branches() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme", "repositories").await;
+    for repo in ["engine", "notes"] {
+        env.mock_get_json(
+            &format!("/repositories/acme/{}/main-branch", repo),
+            serde_json::json!({ "name": "main", "type": "branch" }),
+        )
+        .await;
+    }
+    env.mock_get_json(
+        "/repositories/acme/engine/branch-restrictions",
+        serde_json::json!({ "values": [
+            { "id": 1, "kind": "require_approvals_to_merge", "branch_match_kind": "glob", "pattern": "main", "value": 2 },
+            { "id": 2, "kind": "require_passing_builds_to_merge", "branch_match_kind": "branching_model", "branch_type": "development", "value": 1 },
+        ] }),
+    )
+    .await;
+    env.mock_get_json(
+        "/repositories/acme/engine/branching-model",
+        serde_json::json!({ "development": { "branch": { "name": "main" } } }),
+    )
+    .await;
+    env.mock_get_json(
+        "/repositories/acme/notes/branch-restrictions",
+        serde_json::json!({ "values": [
+            { "id": 3, "kind": "require_approvals_to_merge", "branch_match_kind": "glob", "pattern": "release/*", "value": 1 },
+        ] }),
+    )
+    .await;
+
+    let result = env
+        .run(&[
+            "audit",
+            "branch-restrictions",
+            "--workspace",
+            "acme",
+            "--output",
+            "csv",
+        ])
+        .await;
+    result.assert_success();
+    assert_eq!(
+        result.stdout,
+        "REPOSITORY,BRANCH,APPROVALS,BUILDS,PROBLEMS\n\
+         acme/notes,main,0,0,\"requires 0 of 1 approvals, requires 0 of 1 passing builds\"\n"
+    );
+
+    env.run(&[
+        "audit",
+        "branch-restrictions",
+        "-w",
+        "acme",
+        "--output",
+        "csv",
+        "--columns",
+        "repository,problems",
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&["REPOSITORY,PROBLEMS\nacme/notes,\"requires"]);
+
+    env.run(&["audit", "branch-restrictions", "-w", "acme", "--all"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["acme/engine\tmain\t2\t1\t-"]);
+}
+
+#[tokio::test]
+async fn pipelines_are_checked_against_the_policy() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme", "repositories").await;
+    for repo in ["engine", "notes"] {
+        env.mock_get_json(
+            &format!("/repositories/acme/{}/main-branch", repo),
+            serde_json::json!({ "name": "main", "type": "branch" }),
+        )
+        .await;
+    }
+    env.mock_get_text(
+        "/repositories/acme/engine/src/main/bitbucket-pipelines.yml",
+        "bitbucket-pipelines.yml",
+    )
+    .await;
+    let policy = env.home().join("policy.toml");
+    std::fs::write(
+        &policy,
+        "required_steps = [\"Build and test\", \"Security scan\"]\nbanned_images = [\"node:12*\"]\n",
+    )
+    .unwrap();
+
+    env.run(&[
+        "audit",
+        "pipelines",
+        "-w",
+        "acme",
+        "--policy",
+        policy.to_str().unwrap(),
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&[
+        "acme/engine\t187\tno 'Security scan' step, uses node:12.22 (banned: node:12*)",
+        "acme/notes\t-\tno bitbucket-pipelines.yml",
+    ]);
+}
+
+#[tokio::test]
+async fn secrets_in_variables_and_files_are_reported() {
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme", "repositories").await;
+    env.mock_get_json(
+        "/workspaces/acme/pipelines-config/variables",
+        serde_json::json!({ "values": [
+            { "key": "SLACK_TOKEN", "value": "xoxb-1", "secured": false },
+            { "key": "NPM_TOKEN", "secured": true },
+        ] }),
+    )
+    .await;
+    env.mock_get_json(
+        "/repositories/acme/engine/pipelines_config/variables",
+        serde_json::json!({ "values": [
+            { "key": "DEPLOY_KEY", "value": "abc", "secured": false },
+            { "key": "NODE_ENV", "value": "production", "secured": false },
+        ] }),
+    )
+    .await;
+    env.mock_get_json(
+        "/repositories/acme/notes/pipelines_config/variables",
+        serde_json::json!({ "values": [] }),
+    )
+    .await;
+    Mock::given(method("GET"))
+        .and(path("/workspaces/acme/search/code"))
+        .and(query_param("search_query", "password"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "values": [{
+            "file": { "path": "config/db.yml", "commit": { "repository": { "full_name": "acme/notes" } } },
+            "content_matches": [{ "lines": [
+                { "line": 7, "segments": [{ "text": "password", "match": true }, { "text": ": \"hunter22\"" }] },
+            ] }],
+        }] })))
+        .mount(&env.server)
+        .await;
+    env.mock_get_json(
+        "/workspaces/acme/search/code",
+        serde_json::json!({ "values": [] }),
+    )
+    .await;
+
+    env.run(&["audit", "secrets", "-w", "acme"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&[
+            "variable\t(workspace)\tSLACK_TOKEN\tunsecured variable with a secret-like name",
+            "variable\tacme/engine\tDEPLOY_KEY\t",
+            "file\tacme/notes\tconfig/db.yml:7\thard-coded password",
+        ]);
+
+    let result = env
+        .run(&[
+            "audit",
+            "secrets",
+            "-w",
+            "acme",
+            "--no-code-search",
+            "--quiet",
+        ])
+        .await;
+    result.assert_success();
+    assert_eq!(result.stdout, "SLACK_TOKEN\nacme/engine:DEPLOY_KEY\n");
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use common::TestEnv;
+
+#[tokio::test]
+async fn auth_status_uses_environment_token() {
+    let env = TestEnv::new().await;
+    env.mock_get("/user", "user").await;
+
+    env.run(&["auth", "status"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["Authenticated", "Display name: Ada Lovelace"]);
+}
+
+#[tokio::test]
+async fn auth_status_flags_rejected_token() {
+    let env = TestEnv::new().await;
+    env.expect("GET", "/user", 401, None).await;
+
+    env.run(&["auth", "status"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["Credentials may be invalid"]);
+}
+
+#[tokio::test]
+async fn auth_refresh_leaves_environment_credentials_alone() {
+    let env = TestEnv::new().await;
+
+    let result = env.run(&["auth", "refresh"]).await;
+    assert_eq!(result.code, Some(1));
+    assert!(result.stderr.contains("unset them"), "{}", result.stderr);
+}
+
+#[tokio::test]
+async fn auth_login_refuses_callback_port_zero() {
+    let env = TestEnv::new().await;
+
+    let result = env
+        .run(&["auth", "login", "--oauth", "--callback-port", "0"])
+        .await;
+    assert_eq!(result.code, Some(2), "{}", result.stderr);
+    assert!(
+        result.stderr.contains("--callback-port"),
+        "{}",
+        result.stderr
+    );
+}
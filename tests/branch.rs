@@ -0,0 +1,77 @@
+mod common;
+
+use common::TestEnv;
+use serde_json::json;
+
+/// Run git in `dir`, panicking on failure
+fn git(dir: &std::path::Path, args: &[&str]) -> String {
+    let output = std::process::Command::new("git")
+        .args(["-c", "user.name=Ada", "-c", "user.email=ada@example.com"])
+        .args(["-c", "init.defaultBranch=main"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("failed to run git");
+    assert!(output.status.success(), "git {:?}: {:?}", args, output);
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[tokio::test]
+async fn branch_new_follows_the_branching_model() {
+    let env = TestEnv::new().await;
+    env.mock_get_json(
+        "/repositories/acme/engine/branching-model",
+        json!({
+            "development": { "name": "main", "branch": { "name": "main" } },
+            "branch_types": [
+                { "kind": "feature", "prefix": "feature/" },
+                { "kind": "bugfix", "prefix": "bugfix/" }
+            ]
+        }),
+    )
+    .await;
+    std::fs::create_dir_all(env.home().join("config/bitbucket-cli")).unwrap();
+    std::fs::write(
+        env.home().join("config/bitbucket-cli/config.toml"),
+        "[branch]\nticket_pattern = 'ENG-\\d+'\n",
+    )
+    .unwrap();
+
+    // The origin URL's path is what marks it as acme/engine
+    let origin = env.home().join("bitbucket.org/acme/engine.git");
+    let clone = env.home().join("engine");
+    std::fs::create_dir_all(&origin).unwrap();
+    git(&origin, &["init", "--quiet", "--bare"]);
+    git(
+        env.home(),
+        &["clone", "--quiet", origin.to_str().unwrap(), "engine"],
+    );
+    git(
+        &clone,
+        &["commit", "--quiet", "--allow-empty", "-m", "Start"],
+    );
+
+    let refused = env.run_in(&clone, &["branch", "new", "carry-bit"]).await;
+    assert_eq!(refused.code, Some(2));
+    assert!(
+        refused
+            .stderr
+            .contains("doesn't start with one of: feature/, bugfix/"),
+        "{}",
+        refused.stderr
+    );
+    assert!(refused.stderr.contains("no ticket reference"));
+
+    env.run_in(
+        &clone,
+        &["branch", "new", "feature/ENG-12-carry-bit", "--push"],
+    )
+    .await
+    .assert_success()
+    .assert_stdout_contains(&["Pushed feature/ENG-12-carry-bit"]);
+    assert_eq!(
+        git(&clone, &["symbolic-ref", "--short", "HEAD"]).trim(),
+        "feature/ENG-12-carry-bit"
+    );
+    assert!(git(&origin, &["branch"]).contains("feature/ENG-12-carry-bit"));
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::TestEnv;
+use serde_json::json;
+
+#[tokio::test]
+async fn cache_refresh_stores_names_for_completion() {
+    let env = TestEnv::new().await;
+    env.mock_get_json(
+        "/user/permissions/workspaces",
+        json!({
+            "values": [{
+                "permission": "member",
+                "user": { "uuid": "{ada}", "display_name": "Ada Lovelace", "type": "user" },
+                "workspace": { "uuid": "{acme}", "slug": "acme", "name": "Acme", "type": "workspace" }
+            }]
+        }),
+    )
+    .await;
+    env.mock_get("/repositories/acme", "repositories").await;
+    env.mock_get("/workspaces/acme/members", "workspace_members")
+        .await;
+
+    env.mock_get_json(
+        "/repositories/acme/engine/refs/branches",
+        json!({ "values": [{ "name": "main" }, { "name": "feature/gears" }] }),
+    )
+    .await;
+
+    env.run(&["cache", "refresh", "--branches-of", "acme/engine"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["Cached 1 workspaces, 2 repositories, 2 members"]);
+
+    // Served from the cache without touching the API
+    env.server.reset().await;
+    env.run(&["cache", "names", "repos"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["acme/engine\nacme/notes\n"]);
+    env.run(&["cache", "names", "members", "acme"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["Ada Lovelace\nCharles Babbage\n"]);
+    env.run(&["cache", "names", "branches", "acme/engine"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["feature/gears\nmain\n"]);
+}
+
+#[tokio::test]
+async fn cache_clear_removes_a_left_behind_refresh_lock() {
+    let env = TestEnv::new().await;
+    env.mock_get_json("/user/permissions/workspaces", json!({ "values": [] }))
+        .await;
+    env.run(&["cache", "refresh"]).await.assert_success();
+
+    let dir = env.home().join("cache/bitbucket-cli");
+    let files = || -> Vec<String> {
+        std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("metadata-"))
+            .collect()
+    };
+    let [cache] = files().try_into().unwrap();
+    // As if a background refresh died holding it
+    std::fs::write(dir.join(cache.replace(".json", ".refreshing")), "").unwrap();
+
+    env.run(&["cache", "clear"]).await.assert_success();
+    assert!(files().is_empty(), "{:?}", files());
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::{TOKEN, TestEnv, fixture};
+use wiremock::matchers::{header, method, path, query_param};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn changelog_groups_merged_pull_requests_by_kind() {
+    let env = TestEnv::new().await;
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine/pullrequests"))
+        .and(query_param("state", "MERGED"))
+        .and(query_param("q", "updated_on >= 2024-05-01T00:00:00+00:00"))
+        .and(header("authorization", format!("Bearer {}", TOKEN)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixture("pullrequests")))
+        .mount(&env.server)
+        .await;
+
+    let result = env
+        .run(&[
+            "changelog",
+            "acme/engine",
+            "--since",
+            "2024-05-01",
+            "--title",
+            "v1.1.0",
+        ])
+        .await;
+    result.assert_success();
+    assert_eq!(
+        result.stdout,
+        "## v1.1.0\n\
+         \n### Features\n\n\
+         - Add Bernoulli number routine ([#7](https://bitbucket.org/acme/engine/pull-requests/7)) by Ada Lovelace\n\
+         \n### Bug Fixes\n\n\
+         - Fix carry propagation ([#8](https://bitbucket.org/acme/engine/pull-requests/8)) by Ada Lovelace\n"
+    );
+}
+
+#[tokio::test]
+async fn changelog_goes_by_when_pull_requests_were_merged() {
+    let env = TestEnv::new().await;
+    let mut prs = fixture("pullrequests");
+    // #7 was merged before the range and only commented on since
+    prs["values"][0]["merge_commit"] =
+        serde_json::json!({ "hash": "a1b2c3d", "date": "2024-04-20T09:00:00+00:00" });
+    // #8's merge date isn't in the listing, so it's looked up
+    prs["values"][1]["merge_commit"] = serde_json::json!({ "hash": "e5f6a7b" });
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine/pullrequests"))
+        .and(query_param("fields", "+values.merge_commit.date"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(prs))
+        .mount(&env.server)
+        .await;
+    env.mock_get_json(
+        "/repositories/acme/engine/commit/e5f6a7b",
+        serde_json::json!({ "hash": "e5f6a7b", "date": "2024-05-20T09:00:00+00:00" }),
+    )
+    .await;
+
+    let result = env
+        .run(&[
+            "changelog",
+            "acme/engine",
+            "--since",
+            "2024-05-01",
+            "--until",
+            "2024-06-01",
+        ])
+        .await;
+    result.assert_success();
+    assert!(result.stdout.contains("#8"), "{}", result.stdout);
+    assert!(!result.stdout.contains("#7"), "{}", result.stdout);
+}
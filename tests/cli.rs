@@ -0,0 +1,194 @@
+mod common;
+
+use bitbucket_cli::cli::Cli;
+use clap::CommandFactory;
+use common::TestEnv;
+
+#[test]
+fn command_line_definition_is_consistent() {
+    Cli::command().debug_assert();
+}
+
+#[test]
+fn short_w_is_the_subcommands_own_flag_where_it_has_one() {
+    use bitbucket_cli::cli::Commands;
+    use bitbucket_cli::cli::pr::PrCommands;
+    use clap::Parser;
+
+    let cli = Cli::try_parse_from([
+        "bitbucket",
+        "pr",
+        "view",
+        "acme/engine",
+        "1",
+        "-w",
+        "--workspace",
+        "acme",
+    ])
+    .unwrap();
+    assert_eq!(cli.workspace.as_deref(), Some("acme"));
+    assert!(matches!(
+        cli.command,
+        Commands::Pr {
+            command: PrCommands::View { web: true, .. }
+        }
+    ));
+
+    let cli =
+        Cli::try_parse_from(["bitbucket", "pr", "list", "acme/engine", "-w", "acme"]).unwrap();
+    assert_eq!(cli.workspace.as_deref(), Some("acme"));
+}
+
+#[tokio::test]
+async fn invalid_arguments_exit_with_usage_code() {
+    let env = TestEnv::new().await;
+    let result = env.run(&["pr", "list", "not-a-repo"]).await;
+    assert_eq!(result.code, Some(2));
+    assert!(result.stderr.contains("Invalid repository format"));
+}
+
+#[tokio::test]
+async fn missing_credentials_exit_with_auth_code() {
+    let env = TestEnv::new().await;
+    let mut command = env.command(&["repo", "list", "acme"]);
+    command.env_remove("BITBUCKET_ACCESS_TOKEN");
+    let output = command.output().unwrap();
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[tokio::test]
+async fn rejected_credentials_exit_with_auth_code() {
+    let env = TestEnv::new().await;
+    env.expect("GET", "/repositories/acme", 401, None).await;
+    let result = env.run(&["repo", "list", "acme"]).await;
+    assert_eq!(result.code, Some(3));
+}
+
+#[tokio::test]
+async fn quiet_prints_only_identifiers() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pullrequests", "pullrequests")
+        .await;
+
+    let result = env.run(&["pr", "list", "acme/engine", "--quiet"]).await;
+    result.assert_success();
+    assert_eq!(result.stdout, "7\n8\n");
+}
+
+#[tokio::test]
+async fn piped_tables_are_tab_separated_without_decoration() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pullrequests", "pullrequests")
+        .await;
+
+    let result = env.run(&["pr", "list", "acme/engine"]).await;
+    result.assert_success();
+    let first = result.stdout.lines().next().unwrap();
+    assert!(first.starts_with("7\t"), "{}", result.stdout);
+    assert!(!result.stdout.contains('│'));
+}
+
+#[cfg(unix)]
+fn write_extension(dir: &std::path::Path, name: &str, script: &str) {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::create_dir_all(dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, script).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn unknown_commands_run_extensions_with_auth_context() {
+    let env = TestEnv::new().await;
+    let dir = env
+        .home()
+        .join("data/bitbucket-cli/extensions/bitbucket-hello");
+    write_extension(
+        &dir,
+        "bitbucket-hello",
+        "#!/bin/sh\necho \"$* $BITBUCKET_ACCESS_TOKEN $BITBUCKET_WORKSPACE\"\nexit 5\n",
+    );
+
+    let result = env
+        .run(&["--workspace", "acme", "hello", "--name", "world"])
+        .await;
+    assert_eq!(result.code, Some(5));
+    assert!(
+        result.stdout.starts_with("--name world "),
+        "{}",
+        result.stdout
+    );
+    assert!(
+        result.stdout.trim_end().ends_with(" acme"),
+        "{}",
+        result.stdout
+    );
+
+    let result = env.run(&["ext", "list"]).await;
+    result.assert_success();
+    assert!(result.stdout.starts_with("bitbucket-hello\t"));
+
+    env.run(&["ext", "remove", "hello"]).await.assert_success();
+    let result = env.run(&["hello"]).await;
+    assert_eq!(result.code, Some(2));
+    assert!(result.stderr.contains("bitbucket-hello"));
+}
+
+#[tokio::test]
+async fn browse_prints_web_urls() {
+    let env = TestEnv::new().await;
+    let result = env
+        .run(&[
+            "browse",
+            "--repo",
+            "acme/engine",
+            "--pr",
+            "7",
+            "--no-browser",
+        ])
+        .await;
+    result.assert_success();
+    assert_eq!(
+        result.stdout,
+        "https://bitbucket.org/acme/engine/pull-requests/7\n"
+    );
+}
+
+#[tokio::test]
+async fn browse_branch_takes_its_name_only_after_equals() {
+    let env = TestEnv::new().await;
+    let result = env
+        .run(&[
+            "browse",
+            "--repo",
+            "acme/engine",
+            "--branch=main",
+            "--no-browser",
+        ])
+        .await;
+    result.assert_success();
+    assert_eq!(
+        result.stdout,
+        "https://bitbucket.org/acme/engine/branch/main\n"
+    );
+
+    // A bare --branch is the current one, which outside a checkout there
+    // isn't; what follows is the file to open, not the branch name
+    let output = env
+        .command(&[
+            "browse",
+            "--repo",
+            "acme/engine",
+            "--branch",
+            "src/main.rs",
+            "--no-browser",
+        ])
+        .current_dir(env.home())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("Not on a branch"), "{}", stderr);
+}
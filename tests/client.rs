@@ -0,0 +1,249 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use bitbucket_cli::api::BitbucketClient;
+use bitbucket_cli::api::middleware::{Middleware, Next};
+use bitbucket_cli::auth::Credential;
+use bitbucket_cli::config::NetworkConfig;
+use bitbucket_cli::models::User;
+use futures::future::{BoxFuture, try_join_all};
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn concurrent_requests_are_limited() {
+    let server = MockServer::start().await;
+    let delay = Duration::from_millis(200);
+    Mock::given(method("GET"))
+        .and(path("/user"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(common::fixture("user"))
+                .set_delay(delay),
+        )
+        .expect(6)
+        .mount(&server)
+        .await;
+
+    let credential = Credential::OAuth {
+        access_token: "test-token".to_string(),
+        refresh_token: None,
+        expires_at: None,
+        client_id: None,
+        client_secret: None,
+    };
+    let network = NetworkConfig {
+        max_concurrent_requests: 2,
+        ..Default::default()
+    };
+    let client = BitbucketClient::with_network(credential, network)
+        .unwrap()
+        .with_base_url(server.uri());
+
+    let started = Instant::now();
+    let users = try_join_all((0..6).map(|_| client.get::<User>("/user")))
+        .await
+        .unwrap();
+
+    assert_eq!(users.len(), 6);
+    // Six requests two at a time take at least three round trips
+    assert!(started.elapsed() >= delay * 3, "{:?}", started.elapsed());
+}
+
+/// Tags requests and counts the responses that come back through it
+struct Tag(Arc<AtomicUsize>);
+
+impl Middleware for Tag {
+    fn handle<'a>(
+        &'a self,
+        mut request: reqwest::Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, bitbucket_cli::error::Result<reqwest::Response>> {
+        request
+            .headers_mut()
+            .insert("x-request-tag", "cli-test".parse().unwrap());
+        Box::pin(async move {
+            let response = next.run(request).await?;
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(response)
+        })
+    }
+}
+
+#[tokio::test]
+async fn middleware_sees_every_request() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/user"))
+        .and(header("x-request-tag", "cli-test"))
+        .and(header("authorization", "Bearer test-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(common::fixture("user")))
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("DELETE"))
+        .and(path("/user"))
+        .and(header("x-request-tag", "cli-test"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let credential = Credential::OAuth {
+        access_token: "test-token".to_string(),
+        refresh_token: None,
+        expires_at: None,
+        client_id: None,
+        client_secret: None,
+    };
+    let seen = Arc::new(AtomicUsize::new(0));
+    let client = BitbucketClient::new(credential)
+        .unwrap()
+        .with_base_url(server.uri())
+        .with_middleware(Tag(Arc::clone(&seen)));
+
+    client.get::<User>("/user").await.unwrap();
+    client.get_text("/user", "application/json").await.unwrap();
+    client.delete("/user").await.unwrap();
+    assert_eq!(seen.load(Ordering::SeqCst), 3);
+}
+
+/// Counts the requests that reach it already carrying credentials
+struct SeesAuthorization(Arc<AtomicUsize>);
+
+impl Middleware for SeesAuthorization {
+    fn handle<'a>(
+        &'a self,
+        request: reqwest::Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, bitbucket_cli::error::Result<reqwest::Response>> {
+        if request.headers().contains_key("authorization") {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+        next.run(request)
+    }
+}
+
+#[tokio::test]
+async fn credentials_are_added_below_added_middleware() {
+    let server = MockServer::start().await;
+    Mock::given(path("/user"))
+        .and(header("authorization", "Bearer test-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(common::fixture("user")))
+        .mount(&server)
+        .await;
+
+    let credential = Credential::OAuth {
+        access_token: "test-token".to_string(),
+        refresh_token: None,
+        expires_at: None,
+        client_id: None,
+        client_secret: None,
+    };
+    let seen = Arc::new(AtomicUsize::new(0));
+    let client = BitbucketClient::new(credential)
+        .unwrap()
+        .with_base_url(server.uri())
+        .with_middleware(SeesAuthorization(Arc::clone(&seen)));
+
+    client.get_file("/user", Default::default()).await.unwrap();
+    client.granted_scopes().await.unwrap();
+    client.delete("/user").await.unwrap();
+    assert_eq!(seen.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn waiting_to_retry_does_not_hold_up_other_requests() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/repositories"))
+        .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "2"))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repositories"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/user"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(common::fixture("user")))
+        .mount(&server)
+        .await;
+
+    let credential = Credential::OAuth {
+        access_token: "test-token".to_string(),
+        refresh_token: None,
+        expires_at: None,
+        client_id: None,
+        client_secret: None,
+    };
+    let network = NetworkConfig {
+        max_concurrent_requests: 1,
+        ..Default::default()
+    };
+    let client = BitbucketClient::with_network(credential, network)
+        .unwrap()
+        .with_base_url(server.uri());
+
+    let limited = client.get::<serde_json::Value>("/repositories");
+    let other = async {
+        // Let the first request get its 429 and start waiting
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let started = Instant::now();
+        client.get::<User>("/user").await.unwrap();
+        started.elapsed()
+    };
+    let (limited, elapsed) = tokio::join!(limited, other);
+    limited.unwrap();
+    assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+}
+
+#[tokio::test]
+async fn unavailable_reads_are_retried_but_writes_are_not() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/user"))
+        .respond_with(ResponseTemplate::new(503).insert_header("retry-after", "0"))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/user"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(common::fixture("user")))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/user"))
+        .respond_with(ResponseTemplate::new(503))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let credential = Credential::OAuth {
+        access_token: "test-token".to_string(),
+        refresh_token: None,
+        expires_at: None,
+        client_id: None,
+        client_secret: None,
+    };
+    let client = BitbucketClient::new(credential)
+        .unwrap()
+        .with_base_url(server.uri());
+
+    client.get::<User>("/user").await.unwrap();
+    assert!(
+        client
+            .post_no_response("/user", &serde_json::json!({}))
+            .await
+            .is_err()
+    );
+}
@@ -0,0 +1,233 @@
+mod common;
+
+use common::TestEnv;
+use serde_json::json;
+
+#[tokio::test]
+async fn commit_comment_sends_inline_location() {
+    let env = TestEnv::new().await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/commit/1a2b3c4d5e6f7a8b/comments",
+        201,
+        Some("commit_comment"),
+    )
+    .await;
+
+    env.run(&[
+        "commit",
+        "comment",
+        "acme/engine",
+        "1a2b3c4d5e6f7a8b",
+        "--body",
+        "Off by one here?",
+        "--file",
+        "src/mill.rs",
+        "--line",
+        "42",
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&["Added comment on src/mill.rs:42 in commit 1a2b3c4d5e6f"]);
+
+    let bodies = env
+        .request_bodies(
+            "POST",
+            "/repositories/acme/engine/commit/1a2b3c4d5e6f7a8b/comments",
+        )
+        .await;
+    assert_eq!(bodies[0]["content"]["raw"], "Off by one here?");
+    assert_eq!(bodies[0]["inline"]["path"], "src/mill.rs");
+    assert_eq!(bodies[0]["inline"]["to"], 42);
+}
+
+#[tokio::test]
+async fn commit_approve_posts_approval() {
+    let env = TestEnv::new().await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/commit/1a2b3c4d/approve",
+        200,
+        None,
+    )
+    .await;
+
+    env.run(&["commit", "approve", "acme/engine", "1a2b3c4d"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["Approved commit 1a2b3c4d"]);
+}
+
+#[tokio::test]
+async fn commit_list_stops_at_since() {
+    let env = TestEnv::new().await;
+    let commit = |hash: &str, date: &str, message: &str| {
+        json!({
+            "hash": hash,
+            "message": message,
+            "date": date,
+            "author": { "raw": "Ada Lovelace <ada@example.com>" }
+        })
+    };
+    env.mock_get_json(
+        "/repositories/acme/engine/commits/main",
+        json!({
+            "values": [
+                commit("c3c3c3c3c3c3", "2024-06-20T09:00:00+00:00", "Tune carry lookahead"),
+                // From a long-lived branch merged in June; newer ones follow
+                commit("d4d4d4d4d4d4", "2024-04-01T09:00:00+00:00", "Sketch the mill"),
+                commit("b2b2b2b2b2b2", "2024-06-10T09:00:00+00:00", "Add Bernoulli numbers\n\nDetails"),
+                commit("a1a1a1a1a1a1", "2024-05-01T09:00:00+00:00", "Initial engine"),
+            ]
+        }),
+    )
+    .await;
+
+    let result = env
+        .run(&[
+            "commit",
+            "list",
+            "acme/engine",
+            "--branch",
+            "main",
+            "--since",
+            "2024-06-01",
+            "--until",
+            "2024-06-15",
+        ])
+        .await;
+    result
+        .assert_success()
+        .assert_stdout_contains(&["b2b2b2b\tAda Lovelace", "Add Bernoulli numbers\n"]);
+    assert!(!result.stdout.contains("c3c3c3c"), "{}", result.stdout);
+    assert!(!result.stdout.contains("a1a1a1a"), "{}", result.stdout);
+    assert!(!result.stdout.contains("d4d4d4d"), "{}", result.stdout);
+}
+
+fn file_history() -> serde_json::Value {
+    json!({
+        "values": [
+            {
+                "path": "src/mill.rs",
+                "commit": {
+                    "hash": "9f8e7d6c5b4a39281706",
+                    "date": "2024-06-03T10:00:00+00:00",
+                    "message": "Carry the tens in the mill\n\nLonger body",
+                    "author": { "raw": "Ada Lovelace <ada@example.com>" }
+                }
+            },
+            {
+                "path": "src/engine.rs",
+                "commit": {
+                    "hash": "1a2b3c4d5e6f7a8b9c0d",
+                    "date": "2024-05-01T09:00:00+00:00",
+                    "message": "Add the engine",
+                    "author": { "raw": "Charles Babbage <charles@example.com>" }
+                }
+            }
+        ]
+    })
+}
+
+#[tokio::test]
+async fn commit_list_follow_file_lists_the_file_history() {
+    let env = TestEnv::new().await;
+    env.mock_get_json(
+        "/repositories/acme/engine/filehistory/main/src/mill.rs",
+        file_history(),
+    )
+    .await;
+
+    env.run(&[
+        "commit",
+        "list",
+        "acme/engine",
+        "--branch",
+        "main",
+        "--follow-file",
+        "src/mill.rs",
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&[
+        "9f8e7d6",
+        "Carry the tens in the mill",
+        "Charles Babbage",
+        "Add the engine",
+    ]);
+}
+
+#[tokio::test]
+async fn commit_list_follow_file_since_looks_past_an_older_commit() {
+    let env = TestEnv::new().await;
+    let mut history = file_history();
+    history["values"].as_array_mut().unwrap().reverse();
+    env.mock_get_json(
+        "/repositories/acme/engine/filehistory/main/src/mill.rs",
+        history,
+    )
+    .await;
+
+    let result = env
+        .run(&[
+            "commit",
+            "list",
+            "acme/engine",
+            "--branch",
+            "main",
+            "--follow-file",
+            "src/mill.rs",
+            "--since",
+            "2024-06-01",
+        ])
+        .await;
+    result.assert_success().assert_stdout_contains(&["9f8e7d6"]);
+    assert!(!result.stdout.contains("1a2b3c4"), "{}", result.stdout);
+}
+
+#[tokio::test]
+async fn commit_list_patch_diffs_the_file_under_its_path_in_each_commit() {
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let env = TestEnv::new().await;
+    env.mock_get_json(
+        "/repositories/acme/engine/filehistory/main/src/mill.rs",
+        file_history(),
+    )
+    .await;
+    for (hash, file, diff) in [
+        ("9f8e7d6c5b4a39281706", "src/mill.rs", "+    carry(tens);\n"),
+        ("1a2b3c4d5e6f7a8b9c0d", "src/engine.rs", "+fn engine() {}\n"),
+    ] {
+        Mock::given(method("GET"))
+            .and(path(format!("/repositories/acme/engine/diff/{}", hash)))
+            .and(query_param("path", file))
+            .respond_with(ResponseTemplate::new(200).set_body_string(diff))
+            .mount(&env.server)
+            .await;
+    }
+
+    let result = env
+        .run(&[
+            "commit",
+            "list",
+            "acme/engine",
+            "--branch",
+            "main",
+            "--follow-file",
+            "src/mill.rs",
+            "--patch",
+        ])
+        .await;
+    result.assert_success().assert_stdout_contains(&[
+        "commit 9f8e7d6c5b4a39281706",
+        "Author: Ada Lovelace <ada@example.com>",
+        "    Longer body",
+        "+    carry(tens);",
+        "+fn engine() {}",
+    ]);
+    let newest = result.stdout.find("carry(tens)").unwrap();
+    let oldest = result.stdout.find("fn engine").unwrap();
+    assert!(newest < oldest, "{}", result.stdout);
+}
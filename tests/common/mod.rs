@@ -0,0 +1,193 @@
+//! End-to-end test harness
+//!
+//! Runs the real `bitbucket` binary against a `wiremock` server loaded with
+//! canned Bitbucket responses from `tests/fixtures`. Each [`TestEnv`] gets its
+//! own home and XDG directories, so tests never touch the user's config,
+//! credentials, or cache, and authenticates with `BITBUCKET_ACCESS_TOKEN`.
+
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use serde_json::Value;
+use tempfile::TempDir;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+pub const TOKEN: &str = "test-token";
+
+/// An isolated environment for running CLI commands against a mock API
+pub struct TestEnv {
+    pub server: MockServer,
+    home: TempDir,
+}
+
+impl TestEnv {
+    pub async fn new() -> Self {
+        Self {
+            server: MockServer::start().await,
+            home: TempDir::new().expect("failed to create temp home"),
+        }
+    }
+
+    /// Root of this environment's home directory
+    pub fn home(&self) -> &Path {
+        self.home.path()
+    }
+
+    /// Build a `bitbucket` command wired to the mock server
+    pub fn command(&self, args: &[&str]) -> Command {
+        let home = self.home.path();
+        let mut command = Command::new(env!("CARGO_BIN_EXE_bitbucket"));
+        command
+            .args(args)
+            .env_clear()
+            .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+            .env("HOME", home)
+            .env("XDG_CONFIG_HOME", home.join("config"))
+            .env("XDG_DATA_HOME", home.join("data"))
+            .env("XDG_CACHE_HOME", home.join("cache"))
+            .env("XDG_STATE_HOME", home.join("state"))
+            .env("NO_COLOR", "1")
+            .env("BITBUCKET_API_URL", self.server.uri())
+            .env("BITBUCKET_STATUS_URL", self.server.uri())
+            .env("BITBUCKET_ACCESS_TOKEN", TOKEN);
+        command
+    }
+
+    /// Run a command to completion on a blocking thread
+    pub async fn run(&self, args: &[&str]) -> CommandResult {
+        let mut command = self.command(args);
+        let output = tokio::task::spawn_blocking(move || command.output())
+            .await
+            .expect("command task panicked")
+            .expect("failed to run bitbucket binary");
+        CommandResult::from(output)
+    }
+
+    /// Run the CLI with arguments from within `dir`, e.g. a git checkout
+    pub async fn run_in(&self, dir: &Path, args: &[&str]) -> CommandResult {
+        let mut command = self.command(args);
+        command.current_dir(dir);
+        let output = tokio::task::spawn_blocking(move || command.output())
+            .await
+            .expect("command task panicked")
+            .expect("failed to run bitbucket binary");
+        CommandResult::from(output)
+    }
+
+    /// Serve a JSON fixture for `GET path`
+    pub async fn mock_get(&self, route: &str, fixture_name: &str) {
+        self.mock_get_json(route, fixture(fixture_name)).await;
+    }
+
+    /// Serve `body` for `GET path`, for responses built in the test
+    pub async fn mock_get_json(&self, route: &str, body: Value) {
+        Mock::given(method("GET"))
+            .and(path(route))
+            .and(header("authorization", format!("Bearer {}", TOKEN)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Serve a raw text fixture for `GET path`
+    pub async fn mock_get_text(&self, route: &str, fixture_name: &str) {
+        Mock::given(method("GET"))
+            .and(path(route))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixture_text(fixture_name)))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Expect exactly one `verb path` request and answer it with `status`
+    /// and an optional JSON fixture
+    pub async fn expect(&self, verb: &str, route: &str, status: u16, fixture_name: Option<&str>) {
+        let mut response = ResponseTemplate::new(status);
+        if let Some(name) = fixture_name {
+            response = response.set_body_json(fixture(name));
+        }
+        Mock::given(method(verb))
+            .and(path(route))
+            .respond_with(response)
+            .expect(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// JSON bodies of every request received for `verb path`
+    pub async fn request_bodies(&self, verb: &str, route: &str) -> Vec<Value> {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|r| r.method.as_str() == verb && r.url.path() == route)
+            .map(|r| serde_json::from_slice(&r.body).unwrap_or(Value::Null))
+            .collect()
+    }
+}
+
+/// Captured output of a CLI run
+#[derive(Debug)]
+pub struct CommandResult {
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandResult {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+
+    /// Panic with both streams unless the command succeeded
+    pub fn assert_success(&self) -> &Self {
+        assert!(self.success(), "command failed: {:#?}", self);
+        self
+    }
+
+    /// Panic unless stdout contains every one of `needles`
+    pub fn assert_stdout_contains(&self, needles: &[&str]) -> &Self {
+        for needle in needles {
+            assert!(
+                self.stdout.contains(needle),
+                "stdout is missing {:?}:\n{}",
+                needle,
+                self.stdout
+            );
+        }
+        self
+    }
+}
+
+impl From<Output> for CommandResult {
+    fn from(output: Output) -> Self {
+        Self {
+            code: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+    }
+}
+
+/// Where a fixture file lives, for commands that read it themselves
+pub fn fixture_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+}
+
+/// Load a JSON fixture by file stem
+pub fn fixture(name: &str) -> Value {
+    let text = fixture_text(&format!("{}.json", name));
+    serde_json::from_str(&text).unwrap_or_else(|e| panic!("invalid fixture {}: {}", name, e))
+}
+
+/// Load a fixture file verbatim
+pub fn fixture_text(file_name: &str) -> String {
+    std::fs::read_to_string(fixture_path(file_name))
+        .unwrap_or_else(|e| panic!("missing fixture {}: {}", file_name, e))
+}
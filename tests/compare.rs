@@ -0,0 +1,68 @@
+mod common;
+
+use common::TestEnv;
+use serde_json::json;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn compare_counts_commits_on_each_side() {
+    let env = TestEnv::new().await;
+    let commit = |hash: &str, message: &str| {
+        json!({
+            "hash": hash,
+            "message": message,
+            "date": "2024-06-10T09:00:00+00:00",
+            "author": { "raw": "Ada Lovelace <ada@example.com>" }
+        })
+    };
+    for (include, exclude, values) in [
+        (
+            "feature",
+            "main",
+            vec![
+                commit("c3c3c3c3c3c3", "Tune carry lookahead"),
+                commit("b2b2b2b2b2b2", "Add Bernoulli numbers"),
+            ],
+        ),
+        (
+            "main",
+            "feature",
+            vec![commit("a1a1a1a1a1a1", "Fix the mill")],
+        ),
+    ] {
+        Mock::given(method("GET"))
+            .and(path("/repositories/acme/engine/commits"))
+            .and(query_param("include", include))
+            .and(query_param("exclude", exclude))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "values": values })))
+            .mount(&env.server)
+            .await;
+    }
+
+    env.run(&["compare", "acme/engine", "main..feature"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&[
+            "feature is 2 commits ahead of and 1 commit behind main",
+            "Only on feature (2)",
+            "c3c3c3c\tAda Lovelace",
+            "Only on main (1)",
+            "a1a1a1a\tAda Lovelace",
+        ]);
+}
+
+#[tokio::test]
+async fn compare_diff_shows_changes_since_the_branch_point() {
+    let env = TestEnv::new().await;
+    env.mock_get_text(
+        "/repositories/acme/engine/diff/feature..main",
+        "pullrequest.diff",
+    )
+    .await;
+
+    env.run(&["compare", "acme/engine", "main..feature", "--diff"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["diff --git"]);
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use common::{TestEnv, fixture};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn doctor_reports_missing_scopes() {
+    let env = TestEnv::new().await;
+    Mock::given(method("GET"))
+        .and(path("/user"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(fixture("user"))
+                .insert_header("x-oauth-scopes", "account, repository:write, pullrequest"),
+        )
+        .mount(&env.server)
+        .await;
+
+    let result = env
+        .run(&[
+            "doctor",
+            "--jq",
+            r#".[] | select(.name == "credentials" or .name == "network") | "\(.name) \(.status) \(.detail)""#,
+        ])
+        .await;
+    result.assert_success().assert_stdout_contains(&[
+        "network ok 127.0.0.1 reachable",
+        "credentials warn OAuth 2.0 from the environment, valid but missing scopes: issue, pipeline",
+    ]);
+}
+
+#[tokio::test]
+async fn doctor_fails_with_a_fix_for_rejected_credentials() {
+    let env = TestEnv::new().await;
+    env.expect("GET", "/user", 401, None).await;
+
+    let result = env.run(&["doctor"]).await;
+    assert_eq!(result.code, Some(1), "{:#?}", result);
+    result.assert_stdout_contains(&[
+        "✗ credentials  OAuth 2.0 from the environment rejected by Bitbucket",
+        "→ The token has expired or been revoked; run 'bitbucket auth login'",
+    ]);
+    assert!(
+        result.stderr.contains("1 of 7 checks failed"),
+        "{}",
+        result.stderr
+    );
+}
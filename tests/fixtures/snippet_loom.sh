@@ -0,0 +1,2 @@
+#!/bin/sh
+warp --threads 400
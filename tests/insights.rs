@@ -0,0 +1,41 @@
+mod common;
+
+use common::TestEnv;
+
+#[tokio::test]
+async fn report_create_publishes_sarif_findings_as_annotations() {
+    let env = TestEnv::new().await;
+    let report = "/repositories/acme/engine/commit/1a2b3c4d/reports/clippy";
+    let annotations = format!("{}/annotations", report);
+    env.expect("PUT", report, 200, Some("report")).await;
+    env.expect("POST", &annotations, 200, None).await;
+
+    let sarif = common::fixture_path("clippy.sarif");
+    env.run(&[
+        "insights",
+        "report",
+        "create",
+        "acme/engine",
+        "1a2b3c4d",
+        "--id",
+        "clippy",
+        "--title",
+        "Clippy",
+        "--sarif",
+        sarif.to_str().unwrap(),
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&["Published report clippy (FAILED)", "Added 1 annotation"]);
+
+    let bodies = env.request_bodies("PUT", report).await;
+    assert_eq!(bodies[0]["result"], "FAILED");
+    assert_eq!(bodies[0]["reporter"], "clippy");
+
+    let bodies = env.request_bodies("POST", &annotations).await;
+    let annotation = &bodies[0][0];
+    assert_eq!(annotation["path"], "src/mill.rs");
+    assert_eq!(annotation["line"], 42);
+    assert_eq!(annotation["severity"], "HIGH");
+    assert_eq!(annotation["annotation_type"], "BUG");
+}
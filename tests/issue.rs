@@ -0,0 +1,435 @@
+mod common;
+
+use common::TestEnv;
+
+#[tokio::test]
+async fn issue_list_shows_issues() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/issues", "issues")
+        .await;
+
+    let result = env
+        .run(&["issue", "list", "acme/engine", "--state", "all"])
+        .await;
+    result
+        .assert_success()
+        .assert_stdout_contains(&["Punched cards jam on reload", "Document the mill"]);
+    assert!(
+        result.stderr.contains("Showing 2 issues in acme/engine"),
+        "{}",
+        result.stderr
+    );
+}
+
+#[tokio::test]
+async fn issue_view_shows_details() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/issues/3", "issue")
+        .await;
+
+    env.run(&["issue", "view", "acme/engine", "3"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&[
+            "Punched cards jam on reload #3",
+            "Kind: bug",
+            "Reporter: Ada Lovelace",
+            "The reader stalls after the second deck.",
+        ]);
+}
+
+#[tokio::test]
+async fn issue_view_falls_back_to_the_configured_repository() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/issues/3", "issue")
+        .await;
+
+    // Outside a checkout, with nothing configured, there's no repository
+    let result = env.run_in(env.home(), &["issue", "view", "3"]).await;
+    assert!(!result.success());
+    assert!(result.stderr.contains("[defaults]"), "{}", result.stderr);
+
+    std::fs::create_dir_all(env.home().join("config/bitbucket-cli")).unwrap();
+    std::fs::write(
+        env.home().join("config/bitbucket-cli/config.toml"),
+        "[defaults]\nworkspace = \"acme\"\nrepository = \"engine\"\n",
+    )
+    .unwrap();
+
+    let result = env.run_in(env.home(), &["issue", "view", "3"]).await;
+    result
+        .assert_success()
+        .assert_stdout_contains(&["Punched cards jam on reload #3"]);
+    assert!(
+        result
+            .stderr
+            .contains("Using acme/engine from [defaults] in the config")
+    );
+}
+
+#[tokio::test]
+async fn issue_view_shows_comment_thread() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/issues/3", "issue")
+        .await;
+    env.mock_get(
+        "/repositories/acme/engine/issues/3/comments",
+        "issue_comments",
+    )
+    .await;
+
+    let result = env
+        .run(&["issue", "view", "acme/engine", "3", "--comments"])
+        .await;
+    result.assert_success().assert_stdout_contains(&[
+        "Comments (2)",
+        "Charles Babbage",
+        "  Seeing this on the second deck too.",
+        "  Please retest.",
+    ]);
+    // The empty comment left by a state change is skipped
+    assert_eq!(result.stdout.matches("Ada Lovelace ·").count(), 1);
+}
+
+#[tokio::test]
+async fn issue_create_sends_kind_and_priority() {
+    let env = TestEnv::new().await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/issues",
+        201,
+        Some("issue"),
+    )
+    .await;
+
+    env.run(&[
+        "issue",
+        "create",
+        "acme/engine",
+        "--title",
+        "Punched cards jam on reload",
+        "--kind",
+        "bug",
+        "--priority",
+        "major",
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&["Created issue #3"]);
+
+    let bodies = env
+        .request_bodies("POST", "/repositories/acme/engine/issues")
+        .await;
+    assert_eq!(bodies[0]["kind"], "bug");
+    assert_eq!(bodies[0]["priority"], "major");
+}
+
+#[tokio::test]
+async fn issue_create_uploads_attachments() {
+    let env = TestEnv::new().await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/issues",
+        201,
+        Some("issue"),
+    )
+    .await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/issues/3/attachments",
+        201,
+        None,
+    )
+    .await;
+
+    let screenshot = env.home().join("crash.png");
+    std::fs::write(&screenshot, b"\x89PNG\r\n\x1a\n").unwrap();
+
+    env.run(&[
+        "issue",
+        "create",
+        "acme/engine",
+        "--title",
+        "Punched cards jam on reload",
+        "--attach",
+        screenshot.to_str().unwrap(),
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&["Created issue #3", "Attached 1 file"]);
+}
+
+#[tokio::test]
+async fn issue_close_updates_state() {
+    let env = TestEnv::new().await;
+    env.expect(
+        "PUT",
+        "/repositories/acme/engine/issues/3",
+        200,
+        Some("issue_closed"),
+    )
+    .await;
+
+    env.run(&["issue", "close", "acme/engine", "3"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["Closed issue #3"]);
+
+    let bodies = env
+        .request_bodies("PUT", "/repositories/acme/engine/issues/3")
+        .await;
+    assert_eq!(bodies[0]["state"], "closed");
+}
+
+#[tokio::test]
+async fn issue_close_as_duplicate_posts_the_comment_with_the_change() {
+    let env = TestEnv::new().await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/issues/3/changes",
+        201,
+        None,
+    )
+    .await;
+
+    env.run(&[
+        "issue",
+        "close",
+        "acme/engine",
+        "3",
+        "--comment",
+        "Same jam as the other report.",
+        "--duplicate-of",
+        "2",
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&["Closed issue #3 as duplicate"]);
+
+    let bodies = env
+        .request_bodies("POST", "/repositories/acme/engine/issues/3/changes")
+        .await;
+    assert_eq!(
+        bodies[0],
+        serde_json::json!({
+            "changes": { "state": { "new": "duplicate" } },
+            "message": { "raw": "Same jam as the other report.\n\nDuplicate of #2" },
+        })
+    );
+}
+
+#[tokio::test]
+async fn issue_delete_needs_confirmation() {
+    let env = TestEnv::new().await;
+    env.expect("DELETE", "/repositories/acme/engine/issues/3", 204, None)
+        .await;
+
+    // Nobody at a terminal to ask, and no --yes
+    let refused = env.run(&["issue", "delete", "acme/engine", "3"]).await;
+    assert_eq!(refused.code, Some(2));
+    assert!(refused.stderr.contains("pass --yes"), "{}", refused.stderr);
+
+    let mut command = env.command(&["issue", "delete", "acme/engine", "3"]);
+    command.env("BITBUCKET_ASSUME_YES", "1");
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Deleted issue #3"));
+}
+
+#[tokio::test]
+async fn issue_label_add_prefixes_the_title() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/issues/3", "issue")
+        .await;
+    env.expect(
+        "PUT",
+        "/repositories/acme/engine/issues/3",
+        200,
+        Some("issue"),
+    )
+    .await;
+
+    env.run(&["issue", "label", "add", "acme/engine", "3", "bug", "ui"])
+        .await
+        .assert_success();
+
+    let bodies = env
+        .request_bodies("PUT", "/repositories/acme/engine/issues/3")
+        .await;
+    assert_eq!(
+        bodies[0],
+        serde_json::json!({ "title": "[bug] [ui] Punched cards jam on reload" })
+    );
+}
+
+#[tokio::test]
+async fn issue_list_uses_the_profile_for_the_directory_or_named() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/issues", "issues")
+        .await;
+    env.mock_get("/repositories/babbage/mill/issues", "issues")
+        .await;
+    std::fs::create_dir_all(env.home().join("config/bitbucket-cli")).unwrap();
+    let acme = env.home().join("clients/acme/engine");
+    std::fs::create_dir_all(&acme).unwrap();
+    std::fs::write(
+        env.home().join("config/bitbucket-cli/config.toml"),
+        format!(
+            "[profile.acme]\npaths = [\"{}\"]\nrepository = \"acme/engine\"\noutput = \"json\"\n\n\
+             [profile.babbage]\nrepository = \"babbage/mill\"\n",
+            env.home().join("clients/acme").display()
+        ),
+    )
+    .unwrap();
+
+    let result = env.run_in(&acme, &["issue", "list"]).await;
+    result.assert_success();
+    assert!(
+        result.stdout.trim_start().starts_with('['),
+        "{}",
+        result.stdout
+    );
+
+    env.run_in(&acme, &["issue", "list", "--profile", "babbage"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["Punched cards jam on reload"]);
+
+    let result = env.run(&["issue", "list", "--profile", "lovelace"]).await;
+    assert_eq!(result.code, Some(2), "{:#?}", result);
+    assert!(
+        result.stderr.contains("No [profile.lovelace]"),
+        "{}",
+        result.stderr
+    );
+}
+
+#[tokio::test]
+async fn issue_list_filters_by_label() {
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let env = TestEnv::new().await;
+    let mut issues = common::fixture("issues");
+    issues["values"][0]["title"] = "[bug] Punched cards jam on reload".into();
+    issues["values"][1]["title"] = "[bugfix] Document the mill".into();
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine/issues"))
+        .and(query_param("q", r#"state = "open" AND title ~ "[BUG]""#))
+        .respond_with(ResponseTemplate::new(200).set_body_json(issues))
+        .mount(&env.server)
+        .await;
+
+    let result = env
+        .run(&[
+            "issue",
+            "list",
+            "acme/engine",
+            "--state",
+            "open",
+            "--label",
+            "BUG",
+        ])
+        .await;
+    result.assert_success();
+    assert_eq!(result.stdout.lines().count(), 1, "{}", result.stdout);
+    assert!(
+        result
+            .stdout
+            .starts_with("3\tPunched cards jam on reload\tbug\t")
+    );
+}
+
+#[tokio::test]
+async fn issue_label_list_counts_component_labels() {
+    let env = TestEnv::new().await;
+    std::fs::create_dir_all(env.home().join("config/bitbucket-cli")).unwrap();
+    std::fs::write(
+        env.home().join("config/bitbucket-cli/config.toml"),
+        "[labels]\nstrategy = \"component\"\n",
+    )
+    .unwrap();
+
+    let mut issues = common::fixture("issues");
+    issues["values"][0]["component"] = serde_json::json!({ "id": 1, "name": "mill" });
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine/issues"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(issues))
+        .mount(&env.server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine/components"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "values": [{ "id": 1, "name": "mill" }, { "id": 2, "name": "store" }]
+        })))
+        .mount(&env.server)
+        .await;
+
+    let result = env.run(&["issue", "label", "list", "acme/engine"]).await;
+    result.assert_success();
+    assert_eq!(result.stdout, "mill\t1\nstore\t0\n");
+}
+
+#[tokio::test]
+async fn issue_triage_needs_a_terminal() {
+    let env = TestEnv::new().await;
+
+    let result = env.run(&["issue", "triage", "acme/engine"]).await;
+    assert_eq!(result.code, Some(2), "{:#?}", result);
+    assert!(
+        result.stderr.contains("needs an interactive terminal"),
+        "{}",
+        result.stderr
+    );
+}
+
+#[tokio::test]
+async fn issue_list_updated_since_last_run_picks_up_where_it_left_off() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/issues", "issues")
+        .await;
+    let list = [
+        "issue",
+        "list",
+        "acme/engine",
+        "--updated-since",
+        "last-run",
+    ];
+
+    // Nothing saved yet: everything is listed
+    env.run(&list).await.assert_success();
+    let saved =
+        std::fs::read_to_string(env.home().join("state/bitbucket-cli/last-run.json")).unwrap();
+    assert!(saved.contains("\"issue list acme/engine\""), "{}", saved);
+    // The newest issue listed, not the local clock
+    assert!(saved.contains("2024-06-04T10:00:00Z"), "{}", saved);
+
+    env.run(&list).await.assert_success();
+    let queries: Vec<Option<String>> = env
+        .server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| {
+            r.url
+                .query_pairs()
+                .find(|(k, _)| k == "q")
+                .map(|(_, q)| q.into_owned())
+        })
+        .collect();
+    assert!(
+        !queries[0].as_deref().unwrap_or("").contains("updated_on"),
+        "{:?}",
+        queries
+    );
+    assert!(
+        queries[1]
+            .as_deref()
+            .is_some_and(|q| q.contains("updated_on >= 2024-06-04T10:00:00Z")),
+        "{:?}",
+        queries
+    );
+}
@@ -0,0 +1,627 @@
+mod common;
+
+use common::{TestEnv, fixture};
+
+#[tokio::test]
+async fn pipeline_list_shows_builds() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pipelines", "pipelines")
+        .await;
+
+    env.run(&["pipeline", "list", "acme/engine"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["42", "43", "SUCCESS", "RUNNING", "3m 5s"]);
+}
+
+#[tokio::test]
+async fn pipeline_list_names_pull_request_tag_and_commit_targets() {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let env = TestEnv::new().await;
+    let mut pipelines = common::fixture("pipelines");
+    let mut third = pipelines["values"][1].clone();
+    pipelines["values"][0]["target"] = serde_json::json!({
+        "type": "pipeline_pullrequest_target",
+        "source": "feature/carry",
+        "destination": "main",
+        "pullrequest": { "id": 42, "title": "Carry the one" },
+        "commit": { "hash": "a1b2c3d4e5f6a7b8c9d0", "type": "commit" }
+    });
+    pipelines["values"][1]["target"]["ref_type"] = "tag".into();
+    pipelines["values"][1]["target"]["ref_name"] = "v1.2.3".into();
+    third["build_number"] = 41.into();
+    third["target"] = serde_json::json!({
+        "type": "pipeline_commit_target",
+        "selector": { "type": "custom", "pattern": "nightly" },
+        "commit": { "hash": "f00dfacecafe", "type": "commit" }
+    });
+    pipelines["values"].as_array_mut().unwrap().push(third);
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine/pipelines"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(pipelines))
+        .mount(&env.server)
+        .await;
+
+    env.run(&["pipeline", "list", "acme/engine"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["PR #42", "tag v1.2.3", "f00dfac"]);
+
+    // BRANCH was the column's name before it showed other targets
+    env.run(&[
+        "pipeline",
+        "list",
+        "acme/engine",
+        "--output",
+        "csv",
+        "--columns",
+        "#,branch",
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&["#,TARGET\n43,PR #42\n"]);
+}
+
+#[tokio::test]
+async fn pipeline_view_shows_steps() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pipelines", "pipelines")
+        .await;
+    env.mock_get(
+        "/repositories/acme/engine/pipelines/%7Bc0ffee00-0000-4000-8000-000000000042%7D/steps",
+        "pipeline_steps",
+    )
+    .await;
+
+    env.run(&["pipeline", "view", "acme/engine", "--build", "42"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["Pipeline #42 - main", "Build and test"]);
+}
+
+#[tokio::test]
+async fn pipeline_view_links_the_commit_pull_request_and_artifacts() {
+    let env = TestEnv::new().await;
+    let mut pipelines = fixture("pipelines");
+    for pipeline in pipelines["values"].as_array_mut().unwrap() {
+        pipeline["target"]["type"] = "pipeline_pullrequest_target".into();
+        pipeline["target"]["pullrequest"] = serde_json::json!({ "id": 7 });
+    }
+    env.mock_get_json("/repositories/acme/engine/pipelines", pipelines)
+        .await;
+    env.mock_get(
+        "/repositories/acme/engine/pipelines/%7Bc0ffee00-0000-4000-8000-000000000042%7D/steps",
+        "pipeline_steps",
+    )
+    .await;
+    env.mock_get_json(
+        "/repositories/acme/engine/commit/a1b2c3d4e5f6a7b8c9d0",
+        serde_json::json!({
+            "hash": "a1b2c3d4e5f6a7b8c9d0",
+            "message": "Tabulate Bernoulli numbers\n\nWith the second deck.",
+        }),
+    )
+    .await;
+    env.mock_get("/repositories/acme/engine/pullrequests/7", "pullrequest")
+        .await;
+    let download = |name: &str, created_on: &str| {
+        serde_json::json!({
+            "name": name,
+            "size": 2048,
+            "created_on": created_on,
+            "links": { "self": { "href": format!("https://bitbucket.org/acme/engine/downloads/{}", name) } },
+        })
+    };
+    // Paging stops at engine-41, uploaded before the run started
+    env.mock_get_json(
+        "/repositories/acme/engine/downloads",
+        serde_json::json!({
+            "values": [
+                download("engine-42.tar.gz", "2024-06-05T08:02:00+00:00"),
+                download("handbook.pdf", "2024-06-05T08:01:00+00:00"),
+                download("engine-41.tar.gz", "2024-06-04T08:02:00+00:00"),
+            ],
+            "next": format!("{}/repositories/acme/engine/downloads?page=2", env.server.uri()),
+        }),
+    )
+    .await;
+
+    let result = env
+        .run(&["pipeline", "view", "acme/engine", "--build", "42"])
+        .await;
+    result.assert_success().assert_stdout_contains(&[
+        "a1b2c3d Tabulate Bernoulli numbers",
+        "#7 Add Bernoulli number routine",
+        "https://bitbucket.org/acme/engine/pull-requests/7",
+        "https://bitbucket.org/acme/engine/pipelines/results/42",
+        "engine-42.tar.gz (2.00 KiB)",
+    ]);
+    assert!(!result.stdout.contains("engine-41"), "{}", result.stdout);
+    assert!(!result.stdout.contains("handbook"), "{}", result.stdout);
+}
+
+#[tokio::test]
+async fn pipeline_stats_summarises_completed_pipelines_and_steps() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pipelines", "pipelines")
+        .await;
+    env.mock_get(
+        "/repositories/acme/engine/pipelines/%7Bc0ffee00-0000-4000-8000-000000000042%7D/steps",
+        "pipeline_steps",
+    )
+    .await;
+
+    let result = env
+        .run(&[
+            "pipeline",
+            "stats",
+            "acme/engine",
+            "--since",
+            "2024-06-01",
+            "-o",
+            "json",
+        ])
+        .await;
+    result.assert_success();
+    let stats: serde_json::Value = serde_json::from_str(&result.stdout).unwrap();
+    assert_eq!(stats["pipelines"][0]["name"], "default");
+    assert_eq!(stats["pipelines"][0]["runs"], 1);
+    assert_eq!(stats["pipelines"][0]["p50_seconds"], 185);
+    assert_eq!(stats["steps"][0]["name"], "Build and test");
+    assert_eq!(stats["steps"][0]["success_rate"], 1.0);
+    assert_eq!(stats["steps"][0]["flaky"], false);
+}
+
+#[tokio::test]
+async fn pipeline_view_step_shows_its_full_log() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pipelines", "pipelines")
+        .await;
+    env.mock_get(
+        "/repositories/acme/engine/pipelines/%7Bc0ffee00-0000-4000-8000-000000000042%7D/steps",
+        "pipeline_steps",
+    )
+    .await;
+    env.mock_get_text(
+        "/repositories/acme/engine/pipelines/%7Bc0ffee00-0000-4000-8000-000000000042%7D/steps/%7B57e90000-0000-4000-8000-000000000001%7D/log",
+        "step.log",
+    )
+    .await;
+
+    env.run(&[
+        "pipeline",
+        "view",
+        "acme/engine",
+        "--build",
+        "42",
+        "--step",
+        "build and test",
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&[
+        "Step 1: Build and test (pipeline #42)",
+        "test mill::carries ... ok",
+    ]);
+
+    let result = env
+        .run(&[
+            "pipeline",
+            "view",
+            "acme/engine",
+            "--build",
+            "42",
+            "--step",
+            "1",
+            "--raw-log",
+        ])
+        .await;
+    result.assert_success();
+    assert_eq!(result.stdout, common::fixture_text("step.log"));
+
+    let result = env
+        .run(&[
+            "pipeline",
+            "view",
+            "acme/engine",
+            "--build",
+            "42",
+            "--step",
+            "2",
+        ])
+        .await;
+    assert_eq!(result.code, Some(2), "{:#?}", result);
+    assert!(
+        result.stderr.contains("1. Build and test"),
+        "{}",
+        result.stderr
+    );
+}
+
+#[tokio::test]
+async fn pipeline_logs_filters_the_step_log() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pipelines", "pipelines")
+        .await;
+    env.mock_get(
+        "/repositories/acme/engine/pipelines/%7Bc0ffee00-0000-4000-8000-000000000042%7D/steps",
+        "pipeline_steps",
+    )
+    .await;
+    env.mock_get_text(
+        "/repositories/acme/engine/pipelines/%7Bc0ffee00-0000-4000-8000-000000000042%7D/steps/%7B57e90000-0000-4000-8000-000000000001%7D/log",
+        "step.log",
+    )
+    .await;
+
+    let result = env
+        .run(&[
+            "pipeline",
+            "logs",
+            "acme/engine",
+            "--build",
+            "42",
+            "--grep",
+            "^\\+",
+            "-C",
+            "1",
+        ])
+        .await;
+    result.assert_success();
+    assert_eq!(
+        result.stdout,
+        "1: + cargo build\n2-    Compiling engine v0.1.0\n3: + cargo test\n4- test mill::carries ... ok\n"
+    );
+
+    let result = env
+        .run(&[
+            "pipeline",
+            "logs",
+            "acme/engine",
+            "--build",
+            "42",
+            "--tail",
+            "1",
+        ])
+        .await;
+    result.assert_success();
+    assert_eq!(result.stdout, "test mill::carries ... ok\n");
+}
+
+#[tokio::test]
+async fn pipeline_trigger_targets_branch() {
+    let env = TestEnv::new().await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/pipelines",
+        201,
+        Some("pipeline"),
+    )
+    .await;
+
+    env.run(&["pipeline", "trigger", "acme/engine", "--branch", "main"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["Triggered pipeline #42 on branch main"]);
+
+    let bodies = env
+        .request_bodies("POST", "/repositories/acme/engine/pipelines")
+        .await;
+    assert_eq!(bodies[0]["target"]["ref_name"], "main");
+}
+
+#[tokio::test]
+async fn pipeline_stop_looks_up_build() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pipelines", "pipelines")
+        .await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/pipelines/%7Bc0ffee00-0000-4000-8000-000000000043%7D/stopPipeline",
+        204,
+        None,
+    )
+    .await;
+
+    env.run(&["pipeline", "stop", "acme/engine", "--build", "43"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["Stopped pipeline #43"]);
+}
+
+#[tokio::test]
+async fn pipeline_list_reports_where_parsing_failed() {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let env = TestEnv::new().await;
+    let mut pipelines = common::fixture("pipelines");
+    pipelines["values"][0]["state"]["name"] = "QUEUED_ON_MARS".into();
+    pipelines["values"][1]["build_number"] = "forty-two".into();
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine/pipelines"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(pipelines))
+        .mount(&env.server)
+        .await;
+
+    let result = env
+        .run(&["--debug", "pipeline", "list", "acme/engine"])
+        .await;
+    assert!(!result.success());
+    assert!(
+        result
+            .stderr
+            .contains("at `values[1].build_number`, found \"forty-two\""),
+        "{}",
+        result.stderr
+    );
+
+    let dumps = env.home().join("state/bitbucket-cli/responses");
+    assert_eq!(std::fs::read_dir(dumps).unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn pipeline_trigger_wait_notifies() {
+    let env = TestEnv::new().await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/pipelines",
+        201,
+        Some("pipeline"),
+    )
+    .await;
+    env.mock_get(
+        "/repositories/acme/engine/pipelines/%7Bc0ffee00-0000-4000-8000-000000000042%7D",
+        "pipeline",
+    )
+    .await;
+    env.expect("POST", "/notify", 200, None).await;
+
+    let marker = env.home().join("notified");
+    std::fs::create_dir_all(env.home().join("config/bitbucket-cli")).unwrap();
+    std::fs::write(
+        env.home().join("config/bitbucket-cli/config.toml"),
+        format!(
+            "[notify]\nwebhook = \"{}/notify\"\ncommand = 'echo \"$BITBUCKET_NOTIFY_STATUS $BITBUCKET_NOTIFY_MESSAGE\" > {}'\n",
+            env.server.uri(),
+            marker.display()
+        ),
+    )
+    .unwrap();
+
+    env.run(&[
+        "pipeline",
+        "trigger",
+        "acme/engine",
+        "--branch",
+        "main",
+        "--wait",
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&["Pipeline #42 completed successfully!"]);
+
+    let bodies = env.request_bodies("POST", "/notify").await;
+    assert_eq!(bodies[0]["success"], true);
+    assert_eq!(
+        bodies[0]["url"],
+        "https://bitbucket.org/acme/engine/pipelines/results/42"
+    );
+    assert_eq!(
+        std::fs::read_to_string(marker).unwrap().trim(),
+        "success Pipeline #42 on main in acme/engine completed successfully"
+    );
+}
+
+#[tokio::test]
+async fn pipeline_trigger_wait_logs_follows_a_growing_log_to_its_end() {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let env = TestEnv::new().await;
+    let pipeline = "/repositories/acme/engine/pipelines/%7Bc0ffee00-0000-4000-8000-000000000042%7D";
+    let log = format!(
+        "{}/steps/%7B57e90000-0000-4000-8000-000000000001%7D/log",
+        pipeline
+    );
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/pipelines",
+        201,
+        Some("pipeline"),
+    )
+    .await;
+
+    // Running on the first poll, then done
+    let mut running = common::fixture("pipeline");
+    running["state"] =
+        serde_json::json!({ "name": "IN_PROGRESS", "type": "pipeline_state_in_progress" });
+    let mut running_steps = common::fixture("pipeline_steps");
+    running_steps["values"][0]["state"] =
+        serde_json::json!({ "name": "IN_PROGRESS", "type": "pipeline_step_state_in_progress" });
+    for (route, body) in [
+        (pipeline.to_string(), running),
+        (format!("{}/steps", pipeline), running_steps),
+    ] {
+        Mock::given(method("GET"))
+            .and(path(route))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&env.server)
+            .await;
+    }
+    env.mock_get(pipeline, "pipeline").await;
+    env.mock_get(&format!("{}/steps", pipeline), "pipeline_steps")
+        .await;
+
+    // The log grows between fetches, and is still catching up when the
+    // step completes
+    for (times, body) in [(1, "+ make\nbuil"), (1, "+ make\nbuilt\n")] {
+        Mock::given(method("GET"))
+            .and(path(&log))
+            .respond_with(ResponseTemplate::new(200).set_body_string(body))
+            .up_to_n_times(times)
+            .with_priority(1)
+            .mount(&env.server)
+            .await;
+    }
+    Mock::given(method("GET"))
+        .and(path(&log))
+        .respond_with(ResponseTemplate::new(200).set_body_string("+ make\nbuilt\nlinked\n"))
+        .mount(&env.server)
+        .await;
+
+    let result = env
+        .run(&[
+            "pipeline",
+            "trigger",
+            "acme/engine",
+            "--branch",
+            "main",
+            "--wait",
+            "--logs",
+        ])
+        .await;
+    result.assert_success().assert_stdout_contains(&[
+        "── 1. Build and test ──\n+ make\nbuilt\nlinked\n",
+        "1. Build and test successful",
+    ]);
+}
+
+#[tokio::test]
+async fn pipeline_trigger_wait_logs_prints_each_step_under_a_header() {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let env = TestEnv::new().await;
+    let pipeline = "/repositories/acme/engine/pipelines/%7Bc0ffee00-0000-4000-8000-000000000042%7D";
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/pipelines",
+        201,
+        Some("pipeline"),
+    )
+    .await;
+    env.mock_get(pipeline, "pipeline").await;
+    let mut steps = common::fixture("pipeline_steps");
+    let mut deploy = steps["values"][0].clone();
+    deploy["uuid"] = "{57e90000-0000-4000-8000-000000000002}".into();
+    deploy["name"] = "Deploy".into();
+    steps["values"].as_array_mut().unwrap().push(deploy);
+    env.mock_get_json(&format!("{}/steps", pipeline), steps)
+        .await;
+    env.mock_get_text(
+        &format!(
+            "{}/steps/%7B57e90000-0000-4000-8000-000000000001%7D/log",
+            pipeline
+        ),
+        "step.log",
+    )
+    .await;
+    Mock::given(method("GET"))
+        .and(path(format!(
+            "{}/steps/%7B57e90000-0000-4000-8000-000000000002%7D/log",
+            pipeline
+        )))
+        .respond_with(ResponseTemplate::new(200).set_body_string("+ ./deploy.sh\nshipped"))
+        .mount(&env.server)
+        .await;
+
+    let result = env
+        .run(&[
+            "pipeline",
+            "trigger",
+            "acme/engine",
+            "--branch",
+            "main",
+            "--wait",
+            "--logs",
+        ])
+        .await;
+    result.assert_success().assert_stdout_contains(&[
+        "── 1. Build and test ──\n+ cargo build\n",
+        "test mill::carries ... ok\n",
+        "1. Build and test successful",
+        "── 2. Deploy ──\n+ ./deploy.sh\nshipped\n",
+        "Pipeline #42 completed successfully!",
+    ]);
+    let build = result.stdout.find("Build and test ──").unwrap();
+    let deploy = result.stdout.find("Deploy ──").unwrap();
+    assert!(build < deploy, "{}", result.stdout);
+}
+
+#[tokio::test]
+async fn pipeline_trigger_many_runs_in_every_repository() {
+    let env = TestEnv::new().await;
+    for repo in ["engine", "notes"] {
+        env.expect(
+            "POST",
+            &format!("/repositories/acme/{}/pipelines", repo),
+            201,
+            Some("pipeline"),
+        )
+        .await;
+    }
+
+    env.run(&[
+        "pipeline",
+        "trigger-many",
+        "--repos",
+        "acme/engine,acme/notes",
+        "--branch",
+        "release",
+        "--pipeline",
+        "deploy",
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&[
+        "acme/engine\t42\t",
+        "acme/notes\t42\t",
+        "https://bitbucket.org/acme/notes/pipelines/results/42",
+    ]);
+
+    let bodies = env
+        .request_bodies("POST", "/repositories/acme/notes/pipelines")
+        .await;
+    assert_eq!(bodies[0]["target"]["ref_name"], "release");
+    assert_eq!(bodies[0]["target"]["selector"]["pattern"], "deploy");
+
+    // Only a repository named on its own counts as used
+    let recent = env.run(&["recent"]).await;
+    assert!(!recent.stdout.contains("acme/"), "{}", recent.stdout);
+}
+
+#[tokio::test]
+async fn pipeline_list_updated_since_last_run_lists_a_running_pipeline_again() {
+    let env = TestEnv::new().await;
+    let mut pipelines = fixture("pipelines");
+    for pipeline in pipelines["values"].as_array_mut().unwrap() {
+        if pipeline["state"]["name"] == "IN_PROGRESS" {
+            pipeline["created_on"] = "2024-06-01T08:00:00.000000+00:00".into();
+        }
+    }
+    env.mock_get_json("/repositories/acme/engine/pipelines", pipelines)
+        .await;
+    let list = [
+        "pipeline",
+        "list",
+        "acme/engine",
+        "--updated-since",
+        "last-run",
+    ];
+
+    env.run(&list).await.assert_success();
+    env.run(&list).await.assert_success();
+    let requests = env.server.received_requests().await.unwrap();
+    let query = requests[1]
+        .url
+        .query_pairs()
+        .find(|(k, _)| k == "q")
+        .map(|(_, q)| q.into_owned());
+    // Not the newest one started, which had already finished
+    assert_eq!(query.as_deref(), Some("created_on >= 2024-06-01T08:00:00Z"));
+}
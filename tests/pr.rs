@@ -0,0 +1,1074 @@
+mod common;
+
+use common::{TestEnv, fixture};
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn pr_list_shows_pull_requests() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pullrequests", "pullrequests")
+        .await;
+
+    env.run(&["pr", "list", "acme/engine"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&[
+            "Add Bernoulli number routine",
+            "Fix carry propagation",
+            "Ada Lovelace",
+        ]);
+}
+
+#[tokio::test]
+async fn pr_list_resolves_me_once() {
+    let env = TestEnv::new().await;
+    env.expect("GET", "/user", 200, Some("user")).await;
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine/pullrequests"))
+        .and(query_param(
+            "q",
+            "author.uuid = \"{4b1c7e2a-0000-4000-8000-000000000001}\" AND \
+             reviewers.uuid = \"{4b1c7e2a-0000-4000-8000-000000000001}\"",
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixture("pullrequests")))
+        .mount(&env.server)
+        .await;
+
+    env.run(&[
+        "pr",
+        "list",
+        "acme/engine",
+        "--author",
+        "@me",
+        "--reviewer",
+        "@me",
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&["Add Bernoulli number routine"]);
+}
+
+#[tokio::test]
+async fn pr_list_combines_repositories_and_groups() {
+    let env = TestEnv::new().await;
+    std::fs::create_dir_all(env.home().join("config/bitbucket-cli")).unwrap();
+    std::fs::write(
+        env.home().join("config/bitbucket-cli/config.toml"),
+        "[groups]\nbackend = [\"acme/engine\", \"acme/mill\"]\n",
+    )
+    .unwrap();
+    env.mock_get("/repositories/acme/engine/pullrequests", "pullrequests")
+        .await;
+    env.mock_get("/repositories/acme/mill/pullrequests", "pullrequests")
+        .await;
+    env.mock_get("/repositories/acme/loom/pullrequests", "pullrequests")
+        .await;
+
+    let result = env
+        .run(&[
+            "pr",
+            "list",
+            "--group",
+            "backend",
+            "--repo",
+            "acme/loom",
+            "--columns",
+            "repo,id",
+        ])
+        .await;
+    result.assert_success();
+    for repo in ["acme/engine", "acme/mill", "acme/loom"] {
+        assert!(
+            result.stdout.contains(&format!("{}\t", repo)),
+            "{}",
+            result.stdout
+        );
+    }
+
+    let result = env.run(&["pr", "list", "--group", "frontend"]).await;
+    assert_eq!(result.code, Some(2), "{:#?}", result);
+    assert!(
+        result.stderr.contains("No repository group 'frontend'"),
+        "{}",
+        result.stderr
+    );
+}
+
+#[tokio::test]
+async fn pr_list_since_filters_on_the_server() {
+    let env = TestEnv::new().await;
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine/pullrequests"))
+        .and(query_param(
+            "q",
+            "updated_on >= 2024-06-01T00:00:00Z AND updated_on < 2024-07-01T00:00:00Z",
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixture("pullrequests")))
+        .expect(1)
+        .mount(&env.server)
+        .await;
+
+    env.run(&[
+        "pr",
+        "list",
+        "acme/engine",
+        "--since",
+        "2024-06-01",
+        "--until",
+        "2024-07-01",
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&["Add Bernoulli number routine"]);
+
+    let result = env
+        .run(&["pr", "list", "acme/engine", "--since", "yesterday"])
+        .await;
+    assert_eq!(result.code, Some(2), "{:#?}", result);
+}
+
+#[tokio::test]
+async fn pr_list_prints_selected_columns_as_csv() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pullrequests", "pullrequests")
+        .await;
+
+    let result = env
+        .run(&[
+            "pr",
+            "list",
+            "acme/engine",
+            "--output",
+            "csv",
+            "--columns",
+            "author,id,title",
+        ])
+        .await;
+    result.assert_success();
+    assert_eq!(
+        result.stdout,
+        "AUTHOR,ID,TITLE\n\
+         Ada Lovelace,7,Add Bernoulli number routine\n\
+         Ada Lovelace,8,Fix carry propagation\n"
+    );
+
+    let result = env
+        .run(&[
+            "pr",
+            "list",
+            "acme/engine",
+            "--output",
+            "json",
+            "--columns",
+            "id",
+        ])
+        .await;
+    result.assert_success();
+    let json: serde_json::Value = serde_json::from_str(&result.stdout).unwrap();
+    assert_eq!(json, serde_json::json!([{ "id": 7 }, { "id": 8 }]));
+
+    let result = env
+        .run(&["pr", "list", "acme/engine", "--columns", "id,reviewers"])
+        .await;
+    assert_eq!(result.code, Some(2), "{:#?}", result);
+    assert!(
+        result.stderr.contains("Unknown column 'reviewers'"),
+        "{}",
+        result.stderr
+    );
+}
+
+#[tokio::test]
+async fn pr_view_shows_branches_and_approvals() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pullrequests/7", "pullrequest")
+        .await;
+
+    env.run(&["pr", "view", "acme/engine", "7"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&[
+            "Add Bernoulli number routine #7",
+            "feature/bernoulli → main",
+            "Approved by: Charles Babbage",
+            "Implements note G.",
+        ]);
+}
+
+#[tokio::test]
+async fn pr_view_summarises_builds_changes_and_comments() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pullrequests/7", "pullrequest")
+        .await;
+    env.mock_get_json(
+        "/repositories/acme/engine/pullrequests/7/statuses",
+        serde_json::json!({ "values": [
+            { "key": "build", "name": "Build", "state": "SUCCESSFUL" },
+            { "key": "lint", "name": "Lint", "state": "FAILED" },
+        ]}),
+    )
+    .await;
+    env.mock_get_json(
+        "/repositories/acme/engine/pullrequests/7/diffstat",
+        serde_json::json!({ "values": [
+            { "status": "modified", "lines_added": 40, "lines_removed": 2,
+              "old": { "path": "src/engine.rs" }, "new": { "path": "src/engine.rs" } },
+            { "status": "added", "lines_added": 12, "lines_removed": 0,
+              "new": { "path": "src/bernoulli.rs" } },
+        ]}),
+    )
+    .await;
+    env.mock_get_json(
+        "/repositories/acme/engine/pullrequests/7/comments",
+        serde_json::json!({ "values": [{
+            "id": 41,
+            "content": { "raw": "Looks right to me.\nOne nit below." },
+            "user": { "type": "user", "uuid": "{babbage}", "display_name": "Charles Babbage" },
+            "created_on": "2024-06-04T09:30:00.000000+00:00"
+        }]}),
+    )
+    .await;
+
+    let result = env.run(&["pr", "view", "acme/engine", "7"]).await;
+    result.assert_success().assert_stdout_contains(&[
+        "Builds: failed (2): Lint",
+        "Changes: 2 files, +52 -2",
+        "Latest comments",
+        "Looks right to me.",
+    ]);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn pr_create_runs_pre_submit_checks() {
+    let env = TestEnv::new().await;
+    std::fs::create_dir_all(env.home().join("config/bitbucket-cli")).unwrap();
+    std::fs::write(
+        env.home().join("config/bitbucket-cli/config.toml"),
+        r#"[pr]
+ticket_pattern = '[A-Z]+-\d+'
+lint_command = "grep -q '\"description\":\"Computes' || { echo 'Explain what it computes'; exit 1; }"
+"#,
+    )
+    .unwrap();
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/pullrequests",
+        201,
+        Some("pullrequest"),
+    )
+    .await;
+
+    let create = |extra: &'static [&'static str]| {
+        let mut args = vec![
+            "pr",
+            "create",
+            "acme/engine",
+            "--title",
+            "Add Bernoulli number routine",
+            "--source",
+            "feature/bernoulli",
+        ];
+        args.extend_from_slice(extra);
+        args
+    };
+
+    let result = env.run(&create(&["--body", "Adds it"])).await;
+    assert!(!result.success(), "{:#?}", result);
+    assert!(
+        result
+            .stderr
+            .contains("No ticket reference matching '[A-Z]+-\\d+'")
+            && result.stderr.contains("Explain what it computes")
+            && result.stderr.contains("--no-verify"),
+        "{}",
+        result.stderr
+    );
+
+    // Nothing was submitted until the checks passed
+    env.run(&create(&["--body", "Computes B(n) for ENG-12"]))
+        .await
+        .assert_success();
+    let bodies = env
+        .request_bodies("POST", "/repositories/acme/engine/pullrequests")
+        .await;
+    assert_eq!(bodies.len(), 1);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn pr_create_runs_the_post_create_hook() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let env = TestEnv::new().await;
+    let hooks = env.home().join("config/bitbucket-cli/hooks");
+    std::fs::create_dir_all(&hooks).unwrap();
+    let hook = hooks.join("post-pr-create");
+    let seen = env.home().join("seen.json");
+    std::fs::write(
+        &hook,
+        format!(
+            "#!/bin/sh\ncat > '{}'\necho \"hook ran for $BITBUCKET_HOOK\"\n",
+            seen.display()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/pullrequests",
+        201,
+        Some("pullrequest"),
+    )
+    .await;
+
+    let result = env
+        .run(&[
+            "pr",
+            "create",
+            "acme/engine",
+            "--title",
+            "Add Bernoulli number routine",
+            "--source",
+            "feature/bernoulli",
+        ])
+        .await;
+    result.assert_success();
+    // Hook output stays off stdout
+    assert!(
+        result.stderr.contains("hook ran for post-pr-create"),
+        "{}",
+        result.stderr
+    );
+
+    let seen: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&seen).unwrap()).unwrap();
+    assert_eq!(seen["event"], "post-pr-create");
+    assert_eq!(seen["repository"], "acme/engine");
+    assert_eq!(seen["pull_request"]["id"], 7);
+}
+
+#[tokio::test]
+async fn pr_create_sends_branches() {
+    let env = TestEnv::new().await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/pullrequests",
+        201,
+        Some("pullrequest"),
+    )
+    .await;
+
+    env.run(&[
+        "pr",
+        "create",
+        "acme/engine",
+        "--title",
+        "Add Bernoulli number routine",
+        "--source",
+        "feature/bernoulli",
+        "--destination",
+        "main",
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&["Created pull request #7"]);
+
+    let bodies = env
+        .request_bodies("POST", "/repositories/acme/engine/pullrequests")
+        .await;
+    assert_eq!(bodies[0]["source"]["branch"]["name"], "feature/bernoulli");
+    assert_eq!(bodies[0]["destination"]["branch"]["name"], "main");
+}
+
+#[tokio::test]
+async fn pr_merge_uses_requested_strategy() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pullrequests/7", "pullrequest")
+        .await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/pullrequests/7/merge",
+        200,
+        Some("pullrequest_merged"),
+    )
+    .await;
+
+    env.run(&["pr", "merge", "acme/engine", "7", "--strategy", "squash"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["Merged pull request #7"]);
+
+    let bodies = env
+        .request_bodies("POST", "/repositories/acme/engine/pullrequests/7/merge")
+        .await;
+    assert_eq!(bodies[0]["merge_strategy"], "squash");
+}
+
+#[tokio::test]
+async fn pr_merge_falls_back_to_pr_config_defaults() {
+    let env = TestEnv::new().await;
+    std::fs::create_dir_all(env.home().join("config/bitbucket-cli")).unwrap();
+    std::fs::write(
+        env.home().join("config/bitbucket-cli/config.toml"),
+        "[pr]\nclose_source_branch = true\nmerge_strategy = \"squash\"\n\
+         merge_message = \"{title} (#{id})\"\n",
+    )
+    .unwrap();
+    env.mock_get("/repositories/acme/engine/pullrequests/7", "pullrequest")
+        .await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/pullrequests/7/merge",
+        200,
+        Some("pullrequest_merged"),
+    )
+    .await;
+
+    env.run(&["pr", "merge", "acme/engine", "7", "--keep-source-branch"])
+        .await
+        .assert_success();
+
+    let bodies = env
+        .request_bodies("POST", "/repositories/acme/engine/pullrequests/7/merge")
+        .await;
+    assert_eq!(bodies[0]["merge_strategy"], "squash");
+    assert_eq!(bodies[0]["message"], "Add Bernoulli number routine (#7)");
+    assert_eq!(bodies[0]["close_source_branch"], false);
+}
+
+#[tokio::test]
+async fn pr_merge_uses_the_repository_default_and_refuses_disallowed_strategies() {
+    let env = TestEnv::new().await;
+    let mut pr = fixture("pullrequest");
+    pr["destination"]["branch"]["merge_strategies"] = serde_json::json!(["squash", "fast_forward"]);
+    pr["destination"]["branch"]["default_merge_strategy"] = serde_json::json!("fast_forward");
+    env.mock_get_json("/repositories/acme/engine/pullrequests/7", pr)
+        .await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/pullrequests/7/merge",
+        200,
+        Some("pullrequest_merged"),
+    )
+    .await;
+
+    let result = env
+        .run(&[
+            "pr",
+            "merge",
+            "acme/engine",
+            "7",
+            "--strategy",
+            "merge-commit",
+        ])
+        .await;
+    assert_eq!(result.code, Some(2), "{}", result.stderr);
+    assert!(
+        result
+            .stderr
+            .contains("doesn't allow merge-commit merges into main; pass --strategy with one of: squash, fast-forward"),
+        "{}",
+        result.stderr
+    );
+
+    env.run(&["pr", "merge", "acme/engine", "7"])
+        .await
+        .assert_success();
+    let bodies = env
+        .request_bodies("POST", "/repositories/acme/engine/pullrequests/7/merge")
+        .await;
+    assert_eq!(bodies.len(), 1);
+    assert_eq!(bodies[0]["merge_strategy"], "fast_forward");
+}
+
+/// Run git in `dir`, panicking on failure
+fn git(dir: &std::path::Path, args: &[&str]) {
+    let status = std::process::Command::new("git")
+        .args(["-c", "user.name=Ada", "-c", "user.email=ada@example.com"])
+        .args(["-c", "init.defaultBranch=main"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("failed to run git");
+    assert!(status.status.success(), "git {:?}: {:?}", args, status);
+}
+
+#[tokio::test]
+async fn pr_merge_deletes_local_branch() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pullrequests/7", "pullrequest")
+        .await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/pullrequests/7/merge",
+        200,
+        Some("pullrequest_merged"),
+    )
+    .await;
+
+    // The origin URL's path is what marks it as acme/engine
+    let origin = env.home().join("bitbucket.org/acme/engine.git");
+    let clone = env.home().join("engine");
+    std::fs::create_dir_all(&origin).unwrap();
+    git(&origin, &["init", "--quiet", "--bare"]);
+    git(
+        env.home(),
+        &["clone", "--quiet", origin.to_str().unwrap(), "engine"],
+    );
+    git(
+        &clone,
+        &["commit", "--quiet", "--allow-empty", "-m", "Start"],
+    );
+    git(&clone, &["push", "--quiet", "origin", "HEAD:main"]);
+    git(&clone, &["checkout", "--quiet", "-b", "feature/bernoulli"]);
+    git(
+        &clone,
+        &["commit", "--quiet", "--allow-empty", "-m", "Bernoulli"],
+    );
+    git(&clone, &["push", "--quiet", "origin", "feature/bernoulli"]);
+    // As Bitbucket does when closing the source branch
+    git(&origin, &["branch", "-D", "feature/bernoulli"]);
+
+    env.run_in(
+        &clone,
+        &["pr", "merge", "acme/engine", "7", "--delete-local-branch"],
+    )
+    .await
+    .assert_success()
+    .assert_stdout_contains(&["Merged pull request #7", "Deleted local branch"]);
+
+    let head = std::fs::read_to_string(clone.join(".git/HEAD")).unwrap();
+    assert_eq!(head.trim(), "ref: refs/heads/main");
+    let branches = std::process::Command::new("git")
+        .args(["branch", "--all", "--format=%(refname)"])
+        .current_dir(&clone)
+        .output()
+        .unwrap();
+    let branches = String::from_utf8_lossy(&branches.stdout);
+    assert!(!branches.contains("feature/bernoulli"), "{}", branches);
+}
+
+#[tokio::test]
+async fn pr_approve_and_decline() {
+    let env = TestEnv::new().await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/pullrequests/7/approve",
+        200,
+        Some("user"),
+    )
+    .await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/pullrequests/7/decline",
+        200,
+        Some("pullrequest"),
+    )
+    .await;
+
+    env.run(&["pr", "approve", "acme/engine", "7"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["Approved pull request #7"]);
+    env.run(&["pr", "decline", "acme/engine", "7", "--yes"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["Declined pull request #7"]);
+}
+
+#[tokio::test]
+async fn pr_view_url_prints_the_link_without_a_request() {
+    let env = TestEnv::new().await;
+
+    let result = env.run(&["pr", "view", "acme/engine", "7", "--url"]).await;
+    result.assert_success();
+    assert_eq!(
+        result.stdout,
+        "https://bitbucket.org/acme/engine/pull-requests/7\n"
+    );
+    assert!(env.server.received_requests().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn pr_diff_prints_raw_diff() {
+    let env = TestEnv::new().await;
+    env.mock_get_text(
+        "/repositories/acme/engine/pullrequests/7/diff",
+        "pullrequest.diff",
+    )
+    .await;
+
+    env.run(&["pr", "diff", "acme/engine", "7"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["+++ b/src/bernoulli.rs", "todo!(\"note G\")"]);
+}
+
+#[tokio::test]
+async fn pr_diff_local_reports_hunks_that_conflict() {
+    let env = TestEnv::new().await;
+    let checkout = env.home().join("engine");
+    std::fs::create_dir_all(&checkout).unwrap();
+    let git = |args: &[&str]| {
+        let output = std::process::Command::new("git")
+            .args(["-c", "user.name=Ada", "-c", "user.email=ada@example.com"])
+            .args(args)
+            .current_dir(&checkout)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let write = |file: &str, contents: &str| std::fs::write(checkout.join(file), contents).unwrap();
+
+    git(&["init", "-q"]);
+    write("engine.txt", "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n");
+    write("notes.txt", "carry\n");
+    git(&["add", "."]);
+    git(&["commit", "-qm", "base"]);
+    // The pull request's change, then the checkout as it was before it
+    write("engine.txt", "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nK\nl\n");
+    write("notes.txt", "carry the one\n");
+    git(&["commit", "-qam", "change"]);
+    let diff = git(&["diff", "HEAD~1", "HEAD"]);
+    git(&["reset", "-q", "--hard", "HEAD~1"]);
+    // Local edits: one next to the first hunk, one on top of notes.txt's
+    write("engine.txt", "a\nb\nc\nd\nE\nf\ng\nh\ni\nj\nk\nl\n");
+    write("notes.txt", "carry nothing\n");
+
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine/pullrequests/7/diff"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(diff))
+        .mount(&env.server)
+        .await;
+
+    let result = env
+        .run_in(&checkout, &["pr", "diff", "acme/engine", "7", "--local"])
+        .await;
+    assert!(!result.success());
+    result.assert_stdout_contains(&[
+        "merges  engine.txt",
+        "merges  @@ -1,5 +1,5 @@",
+        "conflicts  notes.txt",
+    ]);
+    assert!(
+        result
+            .stderr
+            .contains("conflicts with the working tree in 1 file")
+    );
+    // The checkout is left alone
+    assert_eq!(git(&["status", "--short"]), " M engine.txt\n M notes.txt\n");
+}
+
+#[tokio::test]
+async fn pr_list_jq_filters_json_output() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pullrequests", "pullrequests")
+        .await;
+
+    let result = env
+        .run(&[
+            "pr",
+            "list",
+            "acme/engine",
+            "--jq",
+            ".[] | select(.id == 7) | .title",
+        ])
+        .await;
+    result.assert_success();
+    assert_eq!(result.stdout.trim(), "Add Bernoulli number routine");
+}
+
+#[tokio::test]
+async fn pr_list_template_renders_each_item() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pullrequests", "pullrequests")
+        .await;
+
+    let result = env
+        .run(&[
+            "pr",
+            "list",
+            "acme/engine",
+            "--template",
+            "{{ id }}:{{ state }}",
+        ])
+        .await;
+    result.assert_success();
+    assert_eq!(result.stdout.lines().count(), 2);
+}
+
+#[tokio::test]
+async fn invalid_jq_is_a_usage_error() {
+    let env = TestEnv::new().await;
+    let result = env.run(&["pr", "list", "acme/engine", "--jq", ".["]).await;
+    assert_eq!(result.code, Some(2));
+    assert!(result.stderr.contains("Invalid --jq expression"));
+}
+
+#[tokio::test]
+async fn pr_cleanup_declines_stale_pull_requests() {
+    let env = TestEnv::new().await;
+    let mut prs = common::fixture("pullrequests");
+    // #7 was just updated; #8 is old and its branch is gone
+    prs["values"][0]["updated_on"] = chrono::Utc::now().to_rfc3339().into();
+    prs["values"][1]["source"]["branch"]["name"] = "feature/abandoned".into();
+    env.mock_get_json("/repositories/acme/engine/pullrequests", prs)
+        .await;
+    for (branch, hash) in [
+        ("feature/bernoulli", "a1b2c3d4e5f6"),
+        ("main", "0f0f0f0f0f0f"),
+    ] {
+        env.mock_get_json(
+            &format!("/repositories/acme/engine/refs/branches/{}", branch),
+            serde_json::json!({ "name": branch, "target": { "hash": hash } }),
+        )
+        .await;
+    }
+    env.mock_get_json(
+        "/repositories/acme/engine/merge-base/a1b2c3d4e5f6a7b8c9d0..0f0f0f0f0f0f",
+        serde_json::json!({ "hash": "99999999aaaa" }),
+    )
+    .await;
+
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/pullrequests/8/comments",
+        201,
+        Some("commit_comment"),
+    )
+    .await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/pullrequests/8/decline",
+        200,
+        Some("pullrequest"),
+    )
+    .await;
+
+    env.run(&["pr", "cleanup", "acme/engine", "--dry-run"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["source branch deleted", "no updates in"]);
+    assert!(
+        env.request_bodies("POST", "/repositories/acme/engine/pullrequests/8/decline")
+            .await
+            .is_empty()
+    );
+
+    env.run(&["pr", "cleanup", "acme/engine", "--yes"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["Declined pull request #8"]);
+
+    let comments = env
+        .request_bodies("POST", "/repositories/acme/engine/pullrequests/8/comments")
+        .await;
+    let comment = comments[0]["content"]["raw"].as_str().unwrap();
+    assert!(
+        comment.starts_with("Declining as stale: no updates in"),
+        "{}",
+        comment
+    );
+    assert_eq!(
+        env.request_bodies("POST", "/repositories/acme/engine/pullrequests/8/decline")
+            .await
+            .len(),
+        1
+    );
+    assert!(
+        env.request_bodies("POST", "/repositories/acme/engine/pullrequests/7/decline")
+            .await
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn pr_queue_ranks_ready_pull_requests_first() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pullrequests", "pullrequests")
+        .await;
+    let statuses =
+        |state: &str| serde_json::json!({ "values": [{ "key": "build", "state": state }] });
+    env.mock_get_json(
+        "/repositories/acme/engine/pullrequests/7/statuses",
+        statuses("FAILED"),
+    )
+    .await;
+    env.mock_get_json(
+        "/repositories/acme/engine/pullrequests/8/statuses",
+        statuses("SUCCESSFUL"),
+    )
+    .await;
+    env.mock_get_json(
+        "/repositories/acme/engine/pullrequests/7/diffstat",
+        serde_json::json!({ "values": [{ "status": "merge conflict", "new": { "path": "src/mill.rs" } }] }),
+    )
+    .await;
+    env.mock_get_json(
+        "/repositories/acme/engine/pullrequests/8/diffstat",
+        serde_json::json!({ "values": [{ "status": "modified", "new": { "path": "src/mill.rs" } }] }),
+    )
+    .await;
+    env.mock_get("/repositories/acme/engine/pullrequests/8", "pullrequest")
+        .await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine/pullrequests/8/merge",
+        200,
+        Some("pullrequest_merged"),
+    )
+    .await;
+
+    let result = env
+        .run(&[
+            "pr",
+            "queue",
+            "acme/engine",
+            "--merge-next",
+            "--strategy",
+            "squash",
+        ])
+        .await;
+    result.assert_success().assert_stdout_contains(&[
+        "1\t8\t",
+        "2\t7\t",
+        "builds failed, merge conflicts",
+        "Merged pull request #8 from the head of the queue",
+    ]);
+    let bodies = env
+        .request_bodies("POST", "/repositories/acme/engine/pullrequests/8/merge")
+        .await;
+    assert_eq!(bodies[0]["merge_strategy"], "squash");
+}
+
+#[tokio::test]
+async fn pr_comment_that_fails_to_post_is_kept_as_a_draft() {
+    let env = TestEnv::new().await;
+    let route = "/repositories/acme/engine/pullrequests/7/comments";
+    Mock::given(method("POST"))
+        .and(path(route))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(1)
+        .mount(&env.server)
+        .await;
+
+    let failed = env
+        .run(&[
+            "pr",
+            "comment",
+            "acme/engine",
+            "7",
+            "--body",
+            "A long review",
+        ])
+        .await;
+    assert!(!failed.success());
+    assert!(
+        failed.stderr.contains("saved as draft 1"),
+        "{}",
+        failed.stderr
+    );
+    env.run(&["drafts", "list"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["pull request acme/engine#7", "A long review"]);
+
+    env.expect("POST", route, 201, Some("commit_comment")).await;
+    env.run(&["drafts", "retry"]).await.assert_success();
+    let bodies = env.request_bodies("POST", route).await;
+    assert_eq!(bodies[1]["content"]["raw"], "A long review");
+    assert!(env.run(&["drafts", "list"]).await.stdout.is_empty());
+}
+
+#[tokio::test]
+async fn pr_comment_that_may_have_posted_warns_of_a_duplicate() {
+    let env = TestEnv::new().await;
+    let route = "/repositories/acme/engine/pullrequests/7/comments";
+    Mock::given(method("POST"))
+        .and(path(route))
+        .respond_with(ResponseTemplate::new(504))
+        .up_to_n_times(1)
+        .mount(&env.server)
+        .await;
+
+    let failed = env
+        .run(&["pr", "comment", "acme/engine", "7", "--body", "Looks good"])
+        .await;
+    assert!(!failed.success());
+    assert!(
+        failed.stderr.contains("may have been posted anyway"),
+        "{}",
+        failed.stderr
+    );
+    assert_eq!(failed.stderr.matches("504").count(), 1, "{}", failed.stderr);
+
+    env.expect("POST", route, 201, Some("commit_comment")).await;
+    let retried = env.run(&["drafts", "retry"]).await;
+    retried.assert_success();
+    assert!(
+        retried.stderr.contains("look for a duplicate"),
+        "{}",
+        retried.stderr
+    );
+}
+
+#[tokio::test]
+async fn pr_checkout_worktree_leaves_the_current_tree_alone() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pullrequests/7", "pullrequest")
+        .await;
+
+    let origin = env.home().join("bitbucket.org/acme/engine.git");
+    let clone = env.home().join("engine");
+    std::fs::create_dir_all(&origin).unwrap();
+    git(&origin, &["init", "--quiet", "--bare"]);
+    git(
+        env.home(),
+        &["clone", "--quiet", origin.to_str().unwrap(), "engine"],
+    );
+    git(
+        &clone,
+        &["commit", "--quiet", "--allow-empty", "-m", "Start"],
+    );
+    git(&clone, &["push", "--quiet", "origin", "HEAD:main"]);
+    git(
+        &clone,
+        &["push", "--quiet", "origin", "HEAD:feature/bernoulli"],
+    );
+
+    env.run_in(
+        &clone,
+        &["pr", "checkout", "acme/engine", "7", "--worktree"],
+    )
+    .await
+    .assert_success()
+    .assert_stdout_contains(&["Checked out feature/bernoulli into"]);
+
+    let worktree = env.home().join("engine-pr-7");
+    let head = std::fs::read_to_string(clone.join(".git/HEAD")).unwrap();
+    assert_eq!(head.trim(), "ref: refs/heads/main");
+    let output = std::process::Command::new("git")
+        .args(["symbolic-ref", "--short", "HEAD"])
+        .current_dir(&worktree)
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        "feature/bernoulli"
+    );
+}
+
+#[tokio::test]
+async fn pr_suggest_reviewers_weighs_history_against_open_reviews() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pullrequests/7", "pullrequest")
+        .await;
+    env.mock_get_json(
+        "/repositories/acme/engine/pullrequests/7/diffstat",
+        serde_json::json!({ "values": [
+            { "status": "modified", "old": { "path": "src/mill.rs" }, "new": { "path": "src/mill.rs" } },
+            { "status": "added", "new": { "path": "src/store.rs" } },
+        ] }),
+    )
+    .await;
+    let person = |name: &str| serde_json::json!({ "type": "user", "uuid": format!("{{{}}}", name), "display_name": name });
+    let by = |name: &str| serde_json::json!({ "path": "src/mill.rs", "commit": { "hash": "1a2b", "author": { "user": person(name) } } });
+    env.mock_get_json(
+        "/repositories/acme/engine/filehistory/main/src/mill.rs",
+        serde_json::json!({ "values": [
+            by("Charles Babbage"), by("Charles Babbage"), by("Charles Babbage"),
+            by("Mary Somerville"), by("Mary Somerville"),
+            by("Luigi Menabrea"),
+            // The author isn't suggested for their own pull request
+            { "path": "src/mill.rs", "commit": { "hash": "3c4d", "author": { "user": fixture("pullrequest")["author"] } } },
+        ] }),
+    )
+    .await;
+    Mock::given(method("GET"))
+        .and(path(
+            "/repositories/acme/engine/filehistory/main/src/store.rs",
+        ))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&env.server)
+        .await;
+    let waiting_on = |name: &str| serde_json::json!({ "user": person(name), "role": "REVIEWER", "approved": false });
+    let mut open = fixture("pullrequest");
+    open["participants"] = serde_json::json!([waiting_on("Charles Babbage")]);
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine/pullrequests"))
+        .and(query_param("state", "OPEN"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({ "values": [open] })),
+        )
+        .mount(&env.server)
+        .await;
+    env.expect(
+        "PUT",
+        "/repositories/acme/engine/pullrequests/7",
+        200,
+        Some("pullrequest"),
+    )
+    .await;
+
+    let result = env
+        .run(&["pr", "suggest-reviewers", "acme/engine", "7", "--apply"])
+        .await;
+    result.assert_success().assert_stdout_contains(&[
+        "Mary Somerville",
+        "Charles Babbage",
+        "Added Mary Somerville, Charles Babbage as reviewers of #7",
+    ]);
+    assert!(!result.stdout.contains("Luigi"), "{}", result.stdout);
+    assert!(!result.stdout.contains("Ada Lovelace"), "{}", result.stdout);
+
+    let bodies = env
+        .request_bodies("PUT", "/repositories/acme/engine/pullrequests/7")
+        .await;
+    assert_eq!(
+        bodies[0]["reviewers"],
+        serde_json::json!([{ "uuid": "{Mary Somerville}" }, { "uuid": "{Charles Babbage}" }])
+    );
+}
+
+#[tokio::test]
+async fn pr_list_updated_since_last_run_saves_the_newest_listed_past_the_limit() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/pullrequests", "pullrequests")
+        .await;
+    let list = [
+        "pr",
+        "list",
+        "acme/engine",
+        "--limit",
+        "1",
+        "--updated-since",
+        "last-run",
+    ];
+
+    env.run(&list).await.assert_success();
+    let saved =
+        std::fs::read_to_string(env.home().join("state/bitbucket-cli/last-run.json")).unwrap();
+    assert!(
+        saved.contains("\"pr list acme/engine\": \"2024-06-03T11:30:00Z\""),
+        "{}",
+        saved
+    );
+
+    env.run(&list).await.assert_success();
+    let requests = env.server.received_requests().await.unwrap();
+    let query = |i: usize| {
+        requests[i]
+            .url
+            .query_pairs()
+            .find(|(k, _)| k == "q")
+            .map(|(_, q)| q.into_owned())
+    };
+    assert!(query(0).is_none(), "{:?}", query(0));
+    assert_eq!(
+        query(1).as_deref(),
+        Some("updated_on >= 2024-06-03T11:30:00Z")
+    );
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::TestEnv;
+
+#[tokio::test]
+async fn recorded_session_replays_without_the_api() {
+    let recording = {
+        let env = TestEnv::new().await;
+        env.mock_get("/repositories/acme/engine", "repository")
+            .await;
+        let path = env.home().join("session.jsonl");
+
+        env.run(&[
+            "repo",
+            "view",
+            "acme/engine",
+            "--record",
+            path.to_str().unwrap(),
+        ])
+        .await
+        .assert_success();
+
+        std::fs::read_to_string(&path).unwrap()
+    };
+
+    let lines: Vec<&str> = recording.lines().collect();
+    assert_eq!(lines.len(), 1);
+    let exchange: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(exchange["method"], "GET");
+    assert_eq!(exchange["status"], 200);
+    assert!(
+        exchange["url"]
+            .as_str()
+            .unwrap()
+            .ends_with("/repositories/acme/engine")
+    );
+    assert!(!recording.contains("Bearer"));
+
+    // A fresh server with nothing mounted: every answer comes from the file
+    let env = TestEnv::new().await;
+    let path = env.home().join("session.jsonl");
+    std::fs::write(&path, &recording).unwrap();
+
+    env.run(&[
+        "repo",
+        "view",
+        "acme/engine",
+        "--replay",
+        path.to_str().unwrap(),
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&["acme/engine"]);
+
+    let result = env
+        .run(&[
+            "repo",
+            "view",
+            "acme/notes",
+            "--replay",
+            path.to_str().unwrap(),
+        ])
+        .await;
+    assert!(!result.success());
+    assert!(
+        result
+            .stderr
+            .contains("No recorded response for GET /repositories/acme/notes"),
+        "{}",
+        result.stderr
+    );
+}
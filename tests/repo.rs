@@ -0,0 +1,682 @@
+mod common;
+
+use common::{TestEnv, fixture};
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn repo_list_shows_repositories() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme", "repositories").await;
+
+    env.run(&["repo", "list", "acme"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["acme/engine", "acme/notes", "2024-06-01"]);
+}
+
+#[tokio::test]
+async fn repo_list_sorts_by_size_and_adds_them_up() {
+    let env = TestEnv::new().await;
+    let mut repositories = fixture("repositories");
+    repositories["values"][0]["size"] = 2048.into();
+    repositories["values"][1]["size"] = 5_242_880.into();
+    // The API sorts, so --limit keeps the largest
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme"))
+        .and(query_param("sort", "-size"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(repositories))
+        .mount(&env.server)
+        .await;
+
+    let result = env
+        .run(&["repo", "list", "acme", "--sort", "size", "--total"])
+        .await;
+    result
+        .assert_success()
+        .assert_stdout_contains(&["2.00 KiB"]);
+    // A note, so piped output holds only the repositories
+    assert!(!result.stdout.contains("Total:"), "{}", result.stdout);
+    assert!(
+        result
+            .stderr
+            .contains("Total: 5.00 MiB across 2 repositories"),
+        "{}",
+        result.stderr
+    );
+    let notes = result.stdout.find("acme/notes").unwrap();
+    let engine = result.stdout.find("acme/engine").unwrap();
+    assert!(notes < engine, "{}", result.stdout);
+}
+
+#[tokio::test]
+async fn repo_list_mine_groups_every_workspace() {
+    let env = TestEnv::new().await;
+    let mut repositories = fixture("repositories");
+    repositories["values"][0]["full_name"] = "babbage/engine".into();
+    Mock::given(method("GET"))
+        .and(path("/repositories"))
+        .and(query_param("role", "admin"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(repositories))
+        .mount(&env.server)
+        .await;
+
+    let result = env
+        .run(&["repo", "list", "--mine", "--role", "admin"])
+        .await;
+    result
+        .assert_success()
+        .assert_stdout_contains(&["acme/notes", "babbage/engine"]);
+    // Piped, the rows stay grouped but without headings between them
+    assert!(!result.stdout.contains("acme\n"), "{}", result.stdout);
+    let acme = result.stdout.find("acme/notes").unwrap();
+    let babbage = result.stdout.find("babbage/engine").unwrap();
+    assert!(acme < babbage, "{}", result.stdout);
+}
+
+#[tokio::test]
+async fn dash_means_the_repository_used_last() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine", "repository")
+        .await;
+
+    assert_eq!(env.run(&["repo", "view", "-"]).await.code, Some(2));
+    env.run(&["repo", "view", "acme/engine"])
+        .await
+        .assert_success();
+    env.run(&["repo", "view", "-"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["acme/engine"]);
+    env.run(&["recent"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["1\tacme/engine"]);
+}
+
+#[tokio::test]
+async fn repo_view_shows_details() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine", "repository")
+        .await;
+
+    env.run(&["repo", "view", "acme/engine"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&[
+            "acme/engine",
+            "Analytical engine firmware",
+            "Main branch: main",
+            "git@bitbucket.org:acme/engine.git",
+        ]);
+}
+
+#[tokio::test]
+async fn repo_create_puts_private_repository() {
+    let env = TestEnv::new().await;
+    env.expect(
+        "PUT",
+        "/repositories/acme/new-engine",
+        200,
+        Some("repository"),
+    )
+    .await;
+
+    env.run(&["repo", "create", "acme", "New Engine"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["Created repository acme/engine"]);
+
+    let bodies = env
+        .request_bodies("PUT", "/repositories/acme/new-engine")
+        .await;
+    assert_eq!(bodies[0]["is_private"], true);
+    assert_eq!(bodies[0]["fork_policy"], "no_public_forks");
+}
+
+#[tokio::test]
+async fn repo_delete_with_yes_skips_prompt() {
+    let env = TestEnv::new().await;
+    env.expect("DELETE", "/repositories/acme/engine", 204, None)
+        .await;
+
+    env.run(&["repo", "delete", "acme/engine", "--yes"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["Deleted repository acme/engine"]);
+}
+
+#[tokio::test]
+async fn repo_delete_names_the_repository_dash_resolves_to() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine", "repository")
+        .await;
+    env.expect("DELETE", "/repositories/acme/engine", 204, None)
+        .await;
+
+    env.run(&["repo", "view", "acme/engine"])
+        .await
+        .assert_success();
+    env.run(&["repo", "delete", "-", "--yes"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["Deleted repository acme/engine"]);
+}
+
+#[tokio::test]
+async fn repo_delete_match_removes_every_matching_repository() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme", "repositories").await;
+    env.expect("DELETE", "/repositories/acme/engine", 204, None)
+        .await;
+    Mock::given(method("DELETE"))
+        .and(path("/repositories/acme/notes"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(0)
+        .mount(&env.server)
+        .await;
+
+    let unconfirmed = env
+        .run(&["repo", "delete", "--match", "eng*", "--workspace", "acme"])
+        .await;
+    assert_eq!(unconfirmed.code, Some(2));
+
+    // --yes doesn't stand in for typing the workspace name
+    let assumed = env
+        .run(&[
+            "repo",
+            "delete",
+            "--match",
+            "eng*",
+            "--workspace",
+            "acme",
+            "--yes",
+        ])
+        .await;
+    assert_eq!(assumed.code, Some(2));
+    assert!(
+        assumed.stderr.contains("pass --confirm acme"),
+        "{}",
+        assumed.stderr
+    );
+
+    let everything = env
+        .run(&[
+            "repo",
+            "delete",
+            "--match",
+            "*",
+            "--workspace",
+            "acme",
+            "--confirm",
+            "acme",
+        ])
+        .await;
+    assert_eq!(everything.code, Some(2));
+    assert!(
+        everything.stderr.contains("--allow-all"),
+        "{}",
+        everything.stderr
+    );
+
+    env.run(&[
+        "repo",
+        "delete",
+        "--match",
+        "eng*",
+        "--workspace",
+        "acme",
+        "--confirm",
+        "acme",
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&[
+        "Repositories to delete (1):",
+        "Deleted repository acme/engine",
+    ]);
+}
+
+#[tokio::test]
+async fn repo_view_readme_shows_the_main_branch_readme() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine", "repository")
+        .await;
+    env.mock_get_json(
+        "/repositories/acme/engine/src/main/",
+        serde_json::json!({ "values": [
+            { "path": "docs", "type": "commit_directory" },
+            { "path": "readme.txt", "type": "commit_file", "size": 12 },
+            { "path": "README.md", "type": "commit_file", "size": 64 },
+        ] }),
+    )
+    .await;
+    env.mock_get_text(
+        "/repositories/acme/engine/src/main/README.md",
+        "engine_readme.md",
+    )
+    .await;
+
+    // Piped output is the README as written
+    env.run(&["repo", "view", "acme/engine", "--readme"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&[
+            "Main branch: main",
+            "README.md\n",
+            "# Analytical Engine\n\nFirmware for the **mill** and the store.",
+        ]);
+}
+
+#[tokio::test]
+async fn repo_view_reports_missing_repository() {
+    let env = TestEnv::new().await;
+    env.expect("GET", "/repositories/acme/missing", 404, Some("not_found"))
+        .await;
+
+    let result = env.run(&["repo", "view", "acme/missing"]).await;
+    assert_eq!(result.code, Some(4));
+    assert!(result.stderr.contains("not found"), "{}", result.stderr);
+}
+
+#[tokio::test]
+async fn offline_mode_serves_cached_responses() {
+    let env = TestEnv::new().await;
+    env.expect("GET", "/repositories/acme/engine", 200, Some("repository"))
+        .await;
+
+    env.run(&["repo", "view", "acme/engine"])
+        .await
+        .assert_success();
+
+    // The mock only answers once; the second run must come from the cache
+    let result = env.run(&["--offline", "repo", "view", "acme/engine"]).await;
+    result
+        .assert_success()
+        .assert_stdout_contains(&["Analytical engine firmware"]);
+    assert!(result.stderr.contains("cached data"), "{}", result.stderr);
+}
+
+#[tokio::test]
+async fn offline_mode_refuses_changes() {
+    let env = TestEnv::new().await;
+
+    let result = env
+        .run(&["--offline", "repo", "delete", "acme/engine", "--yes"])
+        .await;
+    assert!(!result.success());
+    assert!(result.stderr.contains("offline mode"), "{}", result.stderr);
+}
+
+#[tokio::test]
+async fn repo_list_all_follows_next_links() {
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let env = TestEnv::new().await;
+    let mut first = common::fixture("repositories");
+    first["values"].as_array_mut().unwrap().truncate(1);
+    first["next"] = format!("{}/repositories/acme?page=2&pagelen=100", env.server.uri()).into();
+    let mut second = common::fixture("repositories");
+    second["values"].as_array_mut().unwrap().remove(0);
+
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme"))
+        .and(query_param("page", "2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(second))
+        .expect(1)
+        .mount(&env.server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(first))
+        .expect(1)
+        .mount(&env.server)
+        .await;
+
+    env.run(&["repo", "list", "acme", "--all"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["acme/engine", "acme/notes"]);
+}
+
+#[tokio::test]
+async fn repo_list_all_prints_each_page_as_it_arrives() {
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let env = TestEnv::new().await;
+    let mut first = common::fixture("repositories");
+    first["values"].as_array_mut().unwrap().truncate(1);
+    first["next"] = format!("{}/repositories/acme?page=2&pagelen=100", env.server.uri()).into();
+
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme"))
+        .and(query_param("page", "2"))
+        .respond_with(ResponseTemplate::new(400))
+        .mount(&env.server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(first))
+        .mount(&env.server)
+        .await;
+
+    let result = env
+        .run(&["repo", "list", "acme", "--all", "--output", "csv"])
+        .await;
+    assert!(!result.success());
+    let lines: Vec<&str> = result.stdout.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", result.stdout);
+    assert!(lines[1].contains("acme/engine"), "{}", result.stdout);
+}
+
+#[tokio::test]
+async fn repo_watchers_lists_users() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine/watchers", "watchers")
+        .await;
+
+    let result = env.run(&["repo", "watchers", "acme/engine"]).await;
+    result.assert_success();
+    assert_eq!(
+        result.stdout,
+        "Ada Lovelace\tada\t557058:ada\nCharles Babbage\tcharles\t557058:charles\n"
+    );
+}
+
+#[tokio::test]
+async fn repo_watch_continues_past_failures() {
+    let env = TestEnv::new().await;
+    env.mock_get("/user", "user").await;
+    env.expect(
+        "PUT",
+        "/repositories/acme/engine/watchers/%7B4b1c7e2a-0000-4000-8000-000000000001%7D",
+        204,
+        None,
+    )
+    .await;
+    env.expect(
+        "PUT",
+        "/repositories/acme/gone/watchers/%7B4b1c7e2a-0000-4000-8000-000000000001%7D",
+        404,
+        Some("not_found"),
+    )
+    .await;
+    env.expect(
+        "PUT",
+        "/repositories/acme/notes/watchers/%7B4b1c7e2a-0000-4000-8000-000000000001%7D",
+        204,
+        None,
+    )
+    .await;
+
+    let result = env
+        .run(&["repo", "watch", "acme/engine", "acme/gone", "acme/notes"])
+        .await;
+    assert!(!result.success());
+    assert_eq!(result.stdout, "Watching acme/engine\nWatching acme/notes\n");
+    assert!(
+        result
+            .stderr
+            .contains("Failed to watch 1 of 3 repositories"),
+        "{}",
+        result.stderr
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn repo_clone_passes_protocol_and_options_to_git() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme/engine", "repository")
+        .await;
+    std::fs::create_dir_all(env.home().join("config/bitbucket-cli")).unwrap();
+    std::fs::write(
+        env.home().join("config/bitbucket-cli/config.toml"),
+        "[defaults]\nclone_protocol = \"ssh\"\n",
+    )
+    .unwrap();
+
+    // A stand-in git that records its arguments
+    let bin = env.home().join("bin");
+    std::fs::create_dir_all(&bin).unwrap();
+    let log = env.home().join("git-args");
+    std::fs::write(
+        bin.join("git"),
+        format!("#!/bin/sh\necho \"$@\" > {}\n", log.display()),
+    )
+    .unwrap();
+    std::fs::set_permissions(bin.join("git"), std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!(
+        "{}:{}",
+        bin.display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+
+    let clone = |args: &[&str]| {
+        let mut command = env.command(args);
+        command.env("PATH", &path);
+        command.output().unwrap()
+    };
+
+    let output = clone(&["repo", "clone", "acme/engine", "--depth", "1", "-b", "dev"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        std::fs::read_to_string(&log).unwrap().trim(),
+        "clone --depth=1 --branch=dev -- git@bitbucket.org:acme/engine.git engine"
+    );
+
+    let output = clone(&[
+        "repo",
+        "clone",
+        "acme/engine",
+        "--https",
+        "--recurse-submodules",
+        "-d",
+        "src",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        std::fs::read_to_string(&log).unwrap().trim(),
+        "clone --recurse-submodules -- https://bitbucket.org/acme/engine.git src"
+    );
+}
+
+#[tokio::test]
+async fn repo_download_resumes_partial_file() {
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let env = TestEnv::new().await;
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine/downloads/engine.tar.gz"))
+        .and(header("range", "bytes=6-"))
+        .and(header("if-range", "\"v1\""))
+        .respond_with(ResponseTemplate::new(206).set_body_string("world"))
+        .expect(1)
+        .mount(&env.server)
+        .await;
+
+    let dest = env.home().join("engine.tar.gz");
+    std::fs::write(env.home().join("engine.tar.gz.part"), "hello ").unwrap();
+    std::fs::write(env.home().join("engine.tar.gz.part.etag"), "\"v1\"").unwrap();
+    let dest_arg = dest.to_str().unwrap();
+
+    // SHA-256 of "hello world"
+    let sha256 = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+    env.run(&[
+        "repo",
+        "download",
+        "acme/engine",
+        "engine.tar.gz",
+        "-O",
+        dest_arg,
+        "--sha256",
+        sha256,
+    ])
+    .await
+    .assert_success();
+
+    assert_eq!(std::fs::read_to_string(&dest).unwrap(), "hello world");
+    assert!(!env.home().join("engine.tar.gz.part").exists());
+    assert!(!env.home().join("engine.tar.gz.part.etag").exists());
+}
+
+#[tokio::test]
+async fn repo_download_starts_over_when_the_file_changed() {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let env = TestEnv::new().await;
+    // The ETag no longer matches, so the server sends the whole file
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine/downloads/engine.tar.gz"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("etag", "\"v2\"")
+                .set_body_string("hello world"),
+        )
+        .expect(1)
+        .mount(&env.server)
+        .await;
+
+    let dest = env.home().join("engine.tar.gz");
+    std::fs::write(env.home().join("engine.tar.gz.part"), "HOWDY ").unwrap();
+    std::fs::write(env.home().join("engine.tar.gz.part.etag"), "\"v1\"").unwrap();
+    env.run(&[
+        "repo",
+        "download",
+        "acme/engine",
+        "engine.tar.gz",
+        "-O",
+        dest.to_str().unwrap(),
+    ])
+    .await
+    .assert_success();
+
+    assert_eq!(std::fs::read_to_string(&dest).unwrap(), "hello world");
+}
+
+#[tokio::test]
+async fn repo_download_rejects_checksum_mismatch() {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let env = TestEnv::new().await;
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine/downloads/notes.txt"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("tampered"))
+        .mount(&env.server)
+        .await;
+
+    let dest = env.home().join("notes.txt");
+    let result = env
+        .run(&[
+            "repo",
+            "download",
+            "acme/engine",
+            "notes.txt",
+            "-O",
+            dest.to_str().unwrap(),
+            "--sha256",
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9",
+        ])
+        .await;
+
+    assert!(!result.success());
+    assert!(
+        result.stderr.contains("Checksum mismatch"),
+        "{}",
+        result.stderr
+    );
+    assert!(!dest.exists());
+}
+
+/// The repository fixture renamed to `full_name`, cloning from `dir`
+fn local_repository(full_name: &str, dir: &std::path::Path) -> serde_json::Value {
+    let mut repository = fixture("repository");
+    repository["full_name"] = full_name.into();
+    repository["links"]["clone"] =
+        serde_json::json!([{ "name": "https", "href": dir.to_string_lossy() }]);
+    repository
+}
+
+#[tokio::test]
+async fn repo_fork_waits_for_the_fork_then_clones_it() {
+    let env = TestEnv::new().await;
+    let source = env.home().join("source");
+    let fork = env.home().join("fork.git");
+    std::fs::create_dir_all(&source).unwrap();
+    for args in [
+        &["init", "-q"][..],
+        &["commit", "-q", "--allow-empty", "-m", "first"],
+    ] {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=Ada", "-c", "user.email=ada@example.com"])
+            .args(args)
+            .current_dir(&source)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+    let status = std::process::Command::new("git")
+        .args(["clone", "-q", "--bare"])
+        .arg(&source)
+        .arg(&fork)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    Mock::given(method("POST"))
+        .and(path("/repositories/acme/engine/forks"))
+        .respond_with(
+            ResponseTemplate::new(201).set_body_json(local_repository("ada/engine", &fork)),
+        )
+        .mount(&env.server)
+        .await;
+    // Not there on the first look, as while Bitbucket is still copying it
+    Mock::given(method("GET"))
+        .and(path("/repositories/ada/engine"))
+        .respond_with(ResponseTemplate::new(404))
+        .up_to_n_times(1)
+        .mount(&env.server)
+        .await;
+    env.mock_get_json(
+        "/repositories/ada/engine",
+        local_repository("ada/engine", &fork),
+    )
+    .await;
+    env.mock_get_json(
+        "/repositories/acme/engine",
+        local_repository("acme/engine", &source),
+    )
+    .await;
+
+    env.run_in(
+        env.home(),
+        &[
+            "repo",
+            "fork",
+            "acme/engine",
+            "--workspace",
+            "ada",
+            "--clone",
+        ],
+    )
+    .await
+    .assert_success()
+    .assert_stdout_contains(&["Forked to ada/engine", "with upstream acme/engine"]);
+
+    let upstream = std::process::Command::new("git")
+        .args(["remote", "get-url", "upstream"])
+        .current_dir(env.home().join("engine"))
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&upstream.stdout).trim(),
+        source.to_string_lossy()
+    );
+}
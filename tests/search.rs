@@ -0,0 +1,51 @@
+mod common;
+
+use common::TestEnv;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn search_prs_merges_matches_from_every_repository() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme", "repositories").await;
+    env.mock_get_json(
+        "/repositories/acme/notes/pullrequests",
+        serde_json::json!({ "values": [] }),
+    )
+    .await;
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine/pullrequests"))
+        .and(query_param(
+            "q",
+            r#"title ~ "bernoulli" OR description ~ "bernoulli""#,
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(common::fixture("pullrequests")))
+        .mount(&env.server)
+        .await;
+
+    let result = env
+        .run(&[
+            "search",
+            "prs",
+            "bernoulli",
+            "--workspace",
+            "acme",
+            "--quiet",
+        ])
+        .await;
+    result.assert_success();
+    assert_eq!(result.stdout, "acme/engine#7\nacme/engine#8\n");
+}
+
+#[tokio::test]
+async fn search_needs_a_workspace() {
+    let env = TestEnv::new().await;
+
+    let result = env.run(&["search", "issues", "deck"]).await;
+    assert_eq!(result.code, Some(2));
+    assert!(
+        result.stderr.contains("--all-workspaces"),
+        "{}",
+        result.stderr
+    );
+}
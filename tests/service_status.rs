@@ -0,0 +1,49 @@
+mod common;
+
+use common::{TestEnv, fixture};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn service_status_shows_components_and_incidents() {
+    let env = TestEnv::new().await;
+    mock_status_page(&env).await;
+
+    let result = env.run(&["service-status"]).await;
+    result.assert_success().assert_stdout_contains(&[
+        "Partially Degraded Service",
+        "Pipelines\tdegraded performance",
+        "Delays starting Pipelines builds (identified, minor impact)",
+    ]);
+    assert!(!result.stdout.contains("Git operations"));
+}
+
+#[tokio::test]
+async fn server_errors_mention_a_reported_outage() {
+    let env = TestEnv::new().await;
+    mock_status_page(&env).await;
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine"))
+        .respond_with(ResponseTemplate::new(502))
+        .mount(&env.server)
+        .await;
+
+    let result = env.run(&["repo", "view", "acme/engine"]).await;
+    assert!(!result.success());
+    assert!(
+        result
+            .stderr
+            .contains("Bitbucket reports: Partially Degraded Service"),
+        "{}",
+        result.stderr
+    );
+}
+
+/// The status page is public, so it's served without checking credentials
+async fn mock_status_page(env: &TestEnv) {
+    Mock::given(method("GET"))
+        .and(path("/api/v2/summary.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixture("status_summary")))
+        .mount(&env.server)
+        .await;
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use common::TestEnv;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn snippet_list_shows_encoded_ids() {
+    let env = TestEnv::new().await;
+    env.mock_get("/snippets/acme", "snippets").await;
+
+    env.run(&["snippet", "list", "acme"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&["kX7r9\tLoom setup\t2\tNo"]);
+}
+
+#[tokio::test]
+async fn snippet_view_raw_labels_each_file() {
+    let env = TestEnv::new().await;
+    env.mock_get("/snippets/acme/kX7r9", "snippet").await;
+    env.mock_get_text("/snippets/acme/kX7r9/files/loom.sh", "snippet_loom.sh")
+        .await;
+    env.mock_get_text("/snippets/acme/kX7r9/files/notes.md", "snippet_notes.md")
+        .await;
+
+    let result = env
+        .run(&["snippet", "view", "acme", "kX7r9", "--raw"])
+        .await;
+    result.assert_success();
+    assert_eq!(
+        result.stdout,
+        "==> loom.sh <==\n#!/bin/sh\nwarp --threads 400\n\n==> notes.md <==\n# Loom\n"
+    );
+}
+
+#[tokio::test]
+async fn snippet_download_writes_files_byte_for_byte() {
+    let env = TestEnv::new().await;
+    env.mock_get("/snippets/acme/kX7r9", "snippet").await;
+    let binary = vec![0x7f, b'E', b'L', b'F', 0xff, 0x00, 0xfe];
+    Mock::given(method("GET"))
+        .and(path("/snippets/acme/kX7r9/files/loom.sh"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(binary.clone()))
+        .mount(&env.server)
+        .await;
+    env.mock_get_text("/snippets/acme/kX7r9/files/notes.md", "snippet_notes.md")
+        .await;
+
+    let dir = env.home().join("out");
+    env.run(&[
+        "snippet",
+        "download",
+        "acme",
+        "kX7r9",
+        "--dir",
+        dir.to_str().unwrap(),
+    ])
+    .await
+    .assert_success();
+    assert_eq!(std::fs::read(dir.join("loom.sh")).unwrap(), binary);
+}
+
+#[tokio::test]
+async fn snippet_create_uploads_files_as_form_parts() {
+    let env = TestEnv::new().await;
+    env.expect("POST", "/snippets/acme", 201, Some("snippet"))
+        .await;
+    let file = env.home().join("loom.sh");
+    std::fs::write(&file, "warp --threads 400\n").unwrap();
+
+    env.run(&[
+        "snippet",
+        "create",
+        "acme",
+        file.to_str().unwrap(),
+        "--title",
+        "Loom setup",
+        "--private",
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&["Created snippet kX7r9"]);
+
+    let requests = env.server.received_requests().await.unwrap();
+    let body = String::from_utf8_lossy(&requests[0].body);
+    assert!(
+        body.contains("name=\"title\"\r\n\r\nLoom setup\r\n"),
+        "{}",
+        body
+    );
+    assert!(
+        body.contains("name=\"is_private\"\r\n\r\ntrue\r\n"),
+        "{}",
+        body
+    );
+    assert!(
+        body.contains("name=\"file\"; filename=\"loom.sh\""),
+        "{}",
+        body
+    );
+    assert!(body.contains("warp --threads 400\n"), "{}", body);
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use common::{TestEnv, fixture};
+use serde_json::json;
+
+#[tokio::test]
+async fn stats_reports_cycle_time_and_merges_per_author() {
+    let env = TestEnv::new().await;
+    env.mock_get("/repositories/acme", "repositories").await;
+
+    let merged = fixture("pullrequest_merged");
+    let author = merged["author"].clone();
+    let reviewer = merged["participants"][0]["user"].clone();
+    env.mock_get_json(
+        "/repositories/acme/engine/pullrequests",
+        json!({ "values": [merged] }),
+    )
+    .await;
+    // The author's own comment doesn't count as a review; the first
+    // reviewer comment, before the approval, does
+    env.mock_get_json(
+        "/repositories/acme/engine/pullrequests/7/activity",
+        json!({ "values": [
+            { "approval": { "date": "2024-06-02T16:00:00+00:00", "user": reviewer } },
+            { "comment": { "id": 2, "created_on": "2024-06-02T14:00:00+00:00", "user": reviewer } },
+            { "comment": { "id": 1, "created_on": "2024-06-02T11:00:00+00:00", "user": author } },
+            { "update": { "date": "2024-06-02T10:00:00+00:00", "state": "OPEN" } },
+        ] }),
+    )
+    .await;
+    env.mock_get_json(
+        "/repositories/acme/notes/pullrequests",
+        json!({ "values": [] }),
+    )
+    .await;
+    env.mock_get("/repositories/acme/engine/issues", "issues")
+        .await;
+    env.mock_get_json("/repositories/acme/notes/issues", json!({ "values": [] }))
+        .await;
+
+    env.run(&["stats", "acme", "--since", "2024-06-01", "--output", "csv"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&[
+            "SECTION,NAME,VALUE\n",
+            "pull_requests,merged,1\n",
+            "pull_requests,cycle_time_median_hours,25.50\n",
+            "pull_requests,review_latency_median_hours,4.00\n",
+            "issues,opened,2\n",
+            "issues,closed,0\n",
+            "merged_by,Ada Lovelace,1\n",
+        ]);
+
+    env.run(&[
+        "stats",
+        "acme",
+        "--since",
+        "2024-06-01",
+        "--output",
+        "csv",
+        "--columns",
+        "name,value",
+    ])
+    .await
+    .assert_success()
+    .assert_stdout_contains(&["NAME,VALUE\n", "\nmerged,1\n"]);
+}
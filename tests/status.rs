@@ -0,0 +1,83 @@
+mod common;
+
+use common::{TestEnv, fixture};
+use serde_json::{Value, json};
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Mount one repository with an un-reviewed PR, a failing branch and a blocker
+async fn mount_repo(env: &TestEnv) {
+    let mut prs = fixture("pullrequests");
+    prs["values"][1]["participants"] = json!([]);
+    prs["values"][1]["created_on"] = json!("2024-05-20T09:00:00.000000+00:00");
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine/pullrequests"))
+        .and(query_param("state", "OPEN"))
+        .and(query_param("fields", "+values.participants"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(prs))
+        .mount(&env.server)
+        .await;
+
+    let mut pipelines = fixture("pipelines");
+    let mut failed = pipelines["values"][1].clone();
+    failed["build_number"] = json!(41);
+    failed["target"]["ref_name"] = json!("release");
+    failed["state"]["result"] =
+        json!({"name": "FAILED", "type": "pipeline_state_completed_failed"});
+    pipelines["values"].as_array_mut().unwrap().push(failed);
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine/pipelines"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(pipelines))
+        .mount(&env.server)
+        .await;
+
+    let mut issues = fixture("issues");
+    issues["values"][0]["priority"] = json!("blocker");
+    issues["values"].as_array_mut().unwrap().truncate(1);
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme/engine/issues"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(issues))
+        .mount(&env.server)
+        .await;
+}
+
+#[tokio::test]
+async fn status_summarises_a_repository() {
+    let env = TestEnv::new().await;
+    mount_repo(&env).await;
+
+    let result = env.run(&["status", "acme/engine"]).await;
+    result.assert_success();
+    assert!(result.stdout.contains("Open pull requests:  2"));
+    assert!(result.stdout.contains("Oldest un-reviewed:  acme/engine#8"));
+    assert!(result.stdout.contains("Failing pipelines:   1"));
+    assert!(
+        result
+            .stdout
+            .contains("acme/engine #41 on release (failed)")
+    );
+    assert!(result.stdout.contains("Blocker issues:      1"));
+}
+
+#[tokio::test]
+async fn status_json_output_covers_a_workspace() {
+    let env = TestEnv::new().await;
+    let mut repos = fixture("repositories");
+    repos["values"].as_array_mut().unwrap().truncate(1);
+    Mock::given(method("GET"))
+        .and(path("/repositories/acme"))
+        .and(query_param("sort", "-updated_on"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(repos))
+        .mount(&env.server)
+        .await;
+    mount_repo(&env).await;
+
+    let result = env.run(&["status", "acme", "--output", "json"]).await;
+    result.assert_success();
+    let report: Value = serde_json::from_str(&result.stdout).unwrap();
+    assert_eq!(report["open_pull_requests"], 2);
+    assert_eq!(report["oldest_unreviewed"]["id"], 8);
+    assert_eq!(report["failing_pipelines"][0]["build_number"], 41);
+    assert_eq!(report["blocker_issues"][0]["id"], 3);
+    assert_eq!(report["repositories"][0]["repository"], "acme/engine");
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::TestEnv;
+
+#[tokio::test]
+async fn user_view_shows_the_authenticated_user() {
+    let env = TestEnv::new().await;
+    env.mock_get("/user", "user").await;
+
+    env.run(&["user", "view"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&[
+            "Ada Lovelace",
+            "Account ID: 557058:ada",
+            "UUID: {4b1c7e2a-0000-4000-8000-000000000001}",
+        ]);
+}
+
+#[tokio::test]
+async fn user_view_wraps_bare_uuids_in_braces() {
+    let env = TestEnv::new().await;
+    env.mock_get("/users/%7B4b1c7e2a-0000-4000-8000-000000000001%7D", "user")
+        .await;
+
+    let result = env
+        .run(&[
+            "user",
+            "view",
+            "4b1c7e2a-0000-4000-8000-000000000001",
+            "--quiet",
+        ])
+        .await;
+    result.assert_success();
+    assert_eq!(result.stdout, "557058:ada\n");
+}
+
+#[tokio::test]
+async fn workspace_members_search_filters_by_name() {
+    let env = TestEnv::new().await;
+    env.mock_get("/workspaces/acme/members", "workspace_members")
+        .await;
+
+    let result = env
+        .run(&["workspace", "members", "acme", "--search", "babb"])
+        .await;
+    result.assert_success();
+    assert_eq!(
+        result.stdout,
+        "Charles Babbage\tcharles\t557058:charles\t{4b1c7e2a-0000-4000-8000-000000000002}\n"
+    );
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::TestEnv;
+
+#[tokio::test]
+async fn variable_list_merges_workspace_and_repository() {
+    let env = TestEnv::new().await;
+    env.mock_get(
+        "/workspaces/acme/pipelines-config/variables",
+        "workspace_variables",
+    )
+    .await;
+    env.mock_get(
+        "/repositories/acme/engine/pipelines_config/variables",
+        "repository_variables",
+    )
+    .await;
+
+    let result = env
+        .run(&[
+            "variable",
+            "list",
+            "--workspace",
+            "acme",
+            "--repo",
+            "engine",
+        ])
+        .await;
+    result.assert_success().assert_stdout_contains(&[
+        "REGION\tus-east-1\trepository\tactive",
+        "REGION\teu-west-1\tworkspace\toverridden",
+        "DEPLOY_TOKEN\t••••••••\tworkspace\tactive",
+        "SIGNING_KEY\t••••••••\trepository\tactive",
+    ]);
+    assert!(result.stdout.find("CARGO_FEATURES") < result.stdout.find("DEPLOY_TOKEN"));
+}
+
+#[tokio::test]
+async fn variable_copy_skips_secured_and_existing() {
+    let env = TestEnv::new().await;
+    env.mock_get(
+        "/repositories/acme/engine/pipelines_config/variables",
+        "repository_variables",
+    )
+    .await;
+    env.mock_get_json(
+        "/repositories/acme/engine-docs/pipelines_config/variables",
+        serde_json::json!({ "values": [{ "uuid": "{7c}", "key": "REGION", "value": "eu-west-1", "secured": false }] }),
+    )
+    .await;
+    env.expect(
+        "POST",
+        "/repositories/acme/engine-docs/pipelines_config/variables",
+        201,
+        Some("pipeline_variable"),
+    )
+    .await;
+
+    env.run(&["variable", "copy", "acme/engine", "acme/engine-docs"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&[
+            "Copied to acme/engine-docs: 1 created, 0 updated, 1 already set",
+        ]);
+
+    let bodies = env
+        .request_bodies(
+            "POST",
+            "/repositories/acme/engine-docs/pipelines_config/variables",
+        )
+        .await;
+    assert_eq!(bodies.len(), 1);
+    assert_eq!(bodies[0]["key"], "CARGO_FEATURES");
+    assert_eq!(bodies[0]["value"], "mill,printer");
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use common::TestEnv;
+
+#[tokio::test]
+async fn webhook_events_lists_the_catalog() {
+    let env = TestEnv::new().await;
+    env.mock_get("/hook_events/repository", "hook_events").await;
+
+    env.run(&["webhook", "events"])
+        .await
+        .assert_success()
+        .assert_stdout_contains(&[
+            "repo:push",
+            "pullrequest:fulfilled",
+            "Whenever a pull request is merged",
+        ]);
+}
+
+#[tokio::test]
+async fn webhook_forward_rejects_events_missing_from_the_catalog() {
+    let env = TestEnv::new().await;
+    env.mock_get("/hook_events/repository", "hook_events").await;
+
+    let result = env
+        .run(&[
+            "webhook",
+            "forward",
+            "--repo",
+            "acme/engine",
+            "--events",
+            "pullrequest:create",
+            "--relay",
+            "https://relay.example",
+        ])
+        .await;
+    assert_eq!(result.code, Some(2), "{}", result.stderr);
+    assert!(
+        result.stderr.contains("Did you mean pullrequest:created?"),
+        "{}",
+        result.stderr
+    );
+}